@@ -33,7 +33,7 @@ use rv64emu::{device::device_16550a::Device16550aUART, rvsim::RVsim};
 use crate::{
     rv64emu::device::{
         device_memory::DeviceMemory, device_sifive_plic::SIFIVE_UART_IRQ,
-        device_sifive_uart::DeviceSifiveUart, device_trait::MEM_BASE,
+        device_sifive_uart::DeviceSifiveUart, device_trait::MEM_BASE, irq::IrqTrigger,
     },
     rv64emu::rv64core::bus::{Bus, DeviceType},
     rv64emu::rv64core::cpu_core::CpuCoreBuild,
@@ -93,26 +93,32 @@ fn main() {
         // device dram len:0X08000000
         let mem = DeviceMemory::new(0x8000000);
 
-        bus_u.borrow_mut().add_device(DeviceType {
-            start: MEM_BASE,
-            len: mem.size() as u64,
-            instance: Box::new(mem),
-            name: "RAM",
-        });
+        bus_u
+            .borrow_mut()
+            .add_device(DeviceType {
+                start: MEM_BASE,
+                len: mem.size() as u64,
+                instance: Box::new(mem),
+                name: "RAM",
+            })
+            .unwrap();
 
         // device flash len:0X08000000
         let mut flash = DeviceMemory::new(0x8000000);
 
         if let Some(xipflash) = args.xipflash {
             let flash_data = fs::read(xipflash).unwrap();
-            flash.load_binary(&flash_data);
+            flash.load_binary(&flash_data).unwrap();
         }
-        bus_u.borrow_mut().add_device(DeviceType {
-            start: 0x3000_0000,
-            len: flash.size() as u64,
-            instance: Box::new(flash),
-            name: "XIPFLASH",
-        });
+        bus_u
+            .borrow_mut()
+            .add_device(DeviceType {
+                start: 0x3000_0000,
+                len: flash.size() as u64,
+                instance: Box::new(flash),
+                name: "XIPFLASH",
+            })
+            .unwrap();
 
         // we use crossbeam_queue::SegQueue as the uart fifo
         // each uart has a tx and rx fifo, and we use them to communicate with the host pc
@@ -153,12 +159,15 @@ fn main() {
         // device 16650_uart
         let device_16650_uart = Device16550aUART::new(uart_tx_fifo.clone(), uart_rx_fifo.clone());
 
-        bus_u.borrow_mut().add_device(DeviceType {
-            start: 0x1000_0000,
-            len: 0x1000,
-            instance: Box::new(device_16650_uart),
-            name: "16550a_uart",
-        });
+        bus_u
+            .borrow_mut()
+            .add_device(DeviceType {
+                start: 0x1000_0000,
+                len: 0x1000,
+                instance: Box::new(device_16650_uart),
+                name: "16550a_uart",
+            })
+            .unwrap();
 
         // device sifive_uart
         let device_sifive_uart = DeviceSifiveUart::new(uart_tx_fifo, uart_rx_fifo);
@@ -166,19 +175,20 @@ fn main() {
         // sifive_uart support irq
         bus_u
             .borrow_mut()
-            .plic
-            .instance
-            .register_irq_source(SIFIVE_UART_IRQ, Rc::clone(&device_sifive_uart.irq_pending));
-
-        bus_u.borrow_mut().add_device(DeviceType {
-            start: 0xc0000000,
-            len: 0x1000,
-            instance: Box::new(device_sifive_uart),
-            name: "Sifive_Uart",
-        });
+            .add_device_with_irq(
+                DeviceType {
+                    start: 0xc0000000,
+                    len: 0x1000,
+                    instance: Box::new(device_sifive_uart),
+                    name: "Sifive_Uart",
+                },
+                SIFIVE_UART_IRQ,
+                IrqTrigger::Level,
+            )
+            .unwrap();
 
         let boot_pc = args.boot_pc.as_ref().map_or(0x8000_0000, |x| {
-            let cleaned = x.trim_start_matches(|c| c == '0' || c == 'x' || c == 'X');
+            let cleaned = x.trim_start_matches(['0', 'x', 'X']);
             u64::from_str_radix(cleaned, 16)
                 .unwrap_or_else(|_| panic!("boot_pc is not a valid hex number"))
         });
@@ -203,9 +213,12 @@ fn main() {
 
         // create another thread to simmulate the harts
         // let cpu_main = thread::spawn(move || {
-        let mut sim = RVsim::new(hart_vec, 23456);
+        let mut sim = RVsim::new(hart_vec, 23456).unwrap();
         if let Some(ram_img) = args.img {
-            sim.load_image(&ram_img);
+            if let Err(e) = sim.load_image(&ram_img) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
         }
 
         sim.run();
@@ -4,8 +4,10 @@ use clap::Parser;
 #[allow(unused_imports)]
 use rv64emu::tools::Fifobounded;
 use rv64emu::{
+    capabilities::Capabilities,
     config::Config,
-    tools::{rc_refcell_new, FifoUnbounded},
+    manifest::RunManifest,
+    tools::{fifo_unbounded_new, rc_refcell_new, FifoUnbounded},
 };
 
 #[allow(unused_imports)]
@@ -22,39 +24,236 @@ use std::{
     thread,
     time::Duration,
 };
-use std::{
-    fs,
-    io::{stdin, Write},
-};
+use std::{fs, io::Write, path::Path, time::Instant};
 
 use log::{info, LevelFilter};
-use rv64emu::{device::device_16550a::Device16550aUART, rvsim::RVsim};
+use rv64emu::{
+    error::RvEmuResult,
+    rvsim::{run_host_command, GuestExit, RVsim, RunOutcome},
+};
 
+#[cfg(unix)]
+use crate::rv64emu::device::device_host_shm::{HostShm, HOST_SHM_IRQ, SHM_DOORBELL_SIZE};
 use crate::{
     rv64emu::device::{
-        device_memory::DeviceMemory, device_sifive_plic::SIFIVE_UART_IRQ,
-        device_sifive_uart::DeviceSifiveUart, device_trait::MEM_BASE,
+        char_backend::CharBackend,
+        device_aclint::ACLINT_SSWI_SIZE,
+        device_memory::{DeviceMemory, SharedMemory},
+        device_pipe::DevicePipe,
+        device_spi_flash::{SpiFlash, SPI_FLASH_CTRL_SIZE},
+        device_trait::MEM_BASE,
+        device_virtio_net::{VirtioNet, DEFAULT_MAC, VIRTIO_NET_IRQ},
+        device_watchdog::{DeviceWatchdog, WatchdogAction, WDT_SIZE},
+        host_console::{spawn_stdin_reader, RawTerminal},
+        irq::IrqTrigger,
+        uart::UartConfig,
+        virtio::{VirtioMmio, VIRTIO_MMIO_SIZE},
     },
+    rv64emu::net::NetSpec,
     rv64emu::rv64core::bus::{Bus, DeviceType},
     rv64emu::rv64core::cpu_core::CpuCoreBuild,
+    rv64emu::rv64core::energy::{EnergyModel, EnergyWeights},
+    rv64emu::rv64core::pipeline::Pipeline,
 };
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     #[arg(long, value_name = "FILE")]
-    /// IMG bin copy to ram
+    /// IMG bin copy to ram, an elf, a Linux Image or uImage goes to its own address
     img: Option<String>,
     #[arg(long, value_name = "FILE")]
     /// IMG bin copy to xipflash
     xipflash: Option<String>,
+    #[arg(long, value_name = "FILE")]
+    /// IMG bin in a 16MiB spi nor flash, read in place at 0x20000000, programmed through 0x10005000
+    spi_flash: Option<String>,
     #[arg(long, value_name = "HEX")]
     /// the first instruction address,default:0x80000000
     boot_pc: Option<String>,
+    #[arg(long, value_name = "HEX", num_args = 0..=1, default_missing_value = "1000")]
+    /// map a qemu-style boot rom at HEX (default 0x1000), the harts start there and jump to boot_pc
+    /// with a0 = mhartid and a1 = the fdt of --gen-fdt
+    boot_rom: Option<String>,
+    #[arg(long, value_name = "BOOTARGS", num_args = 0..=1, default_missing_value = "earlycon console=ttySIF0")]
+    /// describe the machine in a device tree at the end of the ram, passed to the guest in a1
+    gen_fdt: Option<String>,
+    #[arg(long, value_name = "FILE", conflicts_with = "gen_fdt")]
+    /// a dtb of your own instead of --gen-fdt, at the end of the ram and passed in a1 the same way
+    dtb: Option<String>,
+    #[arg(long, value_name = "FILE")]
+    /// an initrd (initramfs) in the ram above the kernel, its range goes to /chosen of --gen-fdt
+    initrd: Option<String>,
     #[arg(short, long, value_name = "USIZE")]
     /// Number of harts,default:1
     num_harts: Option<usize>,
+    #[arg(long, value_name = "FILE")]
+    /// host side of the guest-to-host pipe device (file, named pipe or /dev/fd/N)
+    pipe: Option<String>,
+    #[arg(long, value_name = "PORT")]
+    /// control socket for irq injection (line commands or HTTP), disabled by default
+    control_port: Option<u16>,
+    #[arg(long, value_name = "FILE")]
+    /// write a run manifest (version, command line, config, seed, image hashes) to FILE
+    manifest: Option<String>,
+    #[arg(long, value_name = "FILE")]
+    /// replay the run recorded in a manifest, the other options are taken from it
+    replay: Option<String>,
+    #[arg(long, value_name = "DIR")]
+    /// run every .bin/.elf image in DIR headlessly and print a result table
+    batch: Option<String>,
+    #[arg(long, value_name = "SECS")]
+    /// per image timeout of --batch,default:10
+    timeout: Option<u64>,
+    #[arg(long, value_name = "CMD")]
+    /// host command run by `sh -c` when the guest passes (GOOD TRAP or tohost)
+    on_pass: Option<String>,
+    #[arg(long, value_name = "CMD")]
+    /// host command run when the guest fails, RV64EMU_EXIT_CODE holds the exit code
+    on_fail: Option<String>,
+    #[arg(long)]
+    /// PAUSE yields the host thread, less host cpu for spinning guests
+    pause_yield: bool,
+    #[arg(long, value_name = "FILE")]
+    /// write the 5-stage pipeline diagram of hart 0 (stalls, forwarding, flushes) to FILE
+    pipeline_view: Option<String>,
+    #[arg(long, value_name = "WEIGHTS", num_args = 0..=1, default_missing_value = "")]
+    /// estimate the energy of hart 0, WEIGHTS in pJ like "load=12,stall=0.8,bus.RAM=20"
+    energy: Option<String>,
+    #[arg(long, value_name = "MODE")]
+    /// normal, strict (trap like hardware on the reserved encodings and debug csrs)
+    /// or relaxed (unimplemented csrs and unmapped memory read 0),default:normal
+    compliance: Option<String>,
+    #[arg(long)]
+    /// split misaligned loads and stores into byte accesses instead of trapping
+    misaligned: bool,
+    #[arg(long, value_name = "USIZE")]
+    /// TLB entries,default:256
+    tlb_size: Option<usize>,
+    #[arg(long, value_name = "USIZE")]
+    /// entries of each TLB set,default:fully associative
+    tlb_ways: Option<usize>,
+    #[arg(long, value_name = "POLICY")]
+    /// TLB replacement: lru, random or fifo,default:lru
+    tlb_policy: Option<String>,
+    #[arg(long, value_name = "USIZE")]
+    /// instructions each hart retires between two device updates,default:5000
+    update_budget: Option<usize>,
+    #[arg(long, value_name = "BACKEND")]
+    /// the host side of the sifive uart (the console): stdio, tcp:[HOST:]PORT,
+    /// telnet:[HOST:]PORT, pty or null,default:stdio
+    serial0: Option<String>,
+    #[arg(long, value_name = "BACKEND")]
+    /// the host side of the 16550a uart, like --serial0,default:stdio
+    serial1: Option<String>,
+    #[arg(long, value_name = "SPEC")]
+    /// add a uart: sifive|ns16550a@HEXADDR[,irq=N][,backend=BACKEND], can be repeated
+    uart: Vec<String>,
+    #[arg(long, value_name = "BACKEND")]
+    /// add a virtio-net card at 0x10001000: user (NAT through host sockets, guest 10.0.2.15 by dhcp)
+    /// or tap:IFNAME, ,pcap=FILE captures its frames
+    net: Option<String>,
+    #[arg(long, value_name = "CHIP")]
+    /// the external interrupt controller: plic, or aia (IMSICs and an APLIC in MSI mode),default:plic
+    irqchip: Option<String>,
+    #[arg(long, value_name = "FILE")]
+    /// map FILE shared (e.g. in /dev/shm) for co-simulated host processes, 1MiB at 0x40000000
+    /// after its header page, the doorbell at 0x10006000
+    host_shm: Option<String>,
+    #[arg(long, value_name = "ACTION")]
+    /// add a watchdog at 0x10004000 (mtime ticks), when it bites: reset the machine or stop
+    watchdog: Option<String>,
+    #[arg(long)]
+    /// print the extensions, csrs, devices, machine profiles and config defaults of this build as json
+    print_capabilities: bool,
 }
+fn default_config() -> Config {
+    let mut config = Config::new();
+    config.set_tlb_size(256);
+    config.set_icache_size(4096);
+    config.set_decode_cache_size(4096);
+    config.set_mmu_type("sv39"); // sv39 sv48 sv57
+    config.set_isa("rv64imac_zihintpause_zihintntl_svnapot_svpbmt_sstc_sscofpmf");
+    config.set_s_mode();
+    config
+}
+
+// .bin/.elf files, and the elf files without an extension (riscv-tests)
+fn is_image(path: &Path) -> bool {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("bin" | "elf") => true,
+        Some(_) => false,
+        None => fs::read(path).is_ok_and(|x| x.starts_with(b"\x7fELF")),
+    }
+}
+
+// a machine with only RAM, the image reports pass or fail through tohost.
+// flat binaries have no tohost symbol, 0x80001000 is used like riscv-tests
+fn run_batch_image(img: &Path, timeout: Duration) -> RvEmuResult<(RunOutcome, u64)> {
+    let bus_u = rc_refcell_new(Bus::new());
+    let mem = DeviceMemory::new(0x8000000);
+    bus_u.borrow_mut().add_device(DeviceType {
+        start: MEM_BASE,
+        len: mem.size() as u64,
+        instance: Box::new(mem),
+        name: "RAM",
+    })?;
+
+    let hart = CpuCoreBuild::new(bus_u, Rc::new(default_config()))
+        .with_boot_pc(0x8000_0000)
+        .with_smode(true)
+        .try_build()?;
+    let mut sim = RVsim::new(vec![rc_refcell_new(hart)], 23456)?;
+    sim.load_image(&img.to_string_lossy())?;
+    if sim.tohost().is_none() {
+        sim.set_tohost(0x8000_1000);
+    }
+    let outcome = sim.run_with_timeout(timeout);
+    Ok((outcome, sim.instret()))
+}
+
+// true: all images pass
+fn run_batch(dir: &str, timeout: Duration) -> bool {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("can not read {dir}:{e}"));
+    let mut images: Vec<_> = entries
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .filter(|x| x.is_file() && is_image(x))
+        .collect();
+    images.sort();
+
+    println!(
+        "{:40}{:10}{:>14}{:>10}",
+        "image", "result", "instret", "time"
+    );
+    let (mut pass, mut fail, mut timeouts) = (0, 0, 0);
+    for img in images.iter() {
+        let start = Instant::now();
+        let ret = run_batch_image(img, timeout);
+        let (result, instret) = match ret {
+            Ok((RunOutcome::Pass, instret)) => ("PASS", instret),
+            Ok((RunOutcome::Fail, instret)) => ("FAIL", instret),
+            Ok((RunOutcome::Timeout, instret)) => ("TIMEOUT", instret),
+            Err(e) => {
+                eprintln!("{}: {e}", img.display());
+                ("ERROR", 0)
+            }
+        };
+        match result {
+            "PASS" => pass += 1,
+            "TIMEOUT" => timeouts += 1,
+            _ => fail += 1,
+        }
+        let name = img.file_name().unwrap_or_default().to_string_lossy();
+        let time = format!("{:.2}s", start.elapsed().as_secs_f64());
+        println!("{name:40}{result:10}{instret:>14}{time:>10}");
+    }
+    println!(
+        "total:{} pass:{pass} fail:{fail} timeout:{timeouts}",
+        images.len()
+    );
+    fail + timeouts == 0
+}
+
 // -------------Device Tree MAP-------------
 // name:CLINT           Area:0X02000000-->0X02010000,len:0X00010000
 // name:PLIC            Area:0X0C000000-->0X10000000,len:0X04000000
@@ -62,55 +261,156 @@ struct Args {
 // name:XIPFLASH        Area:0X30000000-->0X38000000,len:0X08000000
 // name:16550a_uart     Area:0X10000000-->0X10001000,len:0X00001000
 // name:Sifive_Uart     Area:0XC0000000-->0XC0001000,len:0X00001000
+// name:HOST_PIPE       Area:0X10002000-->0X10003000,len:0X00001000
 fn main() {
     simple_logger::SimpleLogger::new()
         .with_level(LevelFilter::Off)
         .init()
         .unwrap();
 
-    let args = Args::parse();
+    let cli = Args::parse();
+    let replay = cli.replay.as_ref().map(|file| {
+        RunManifest::load(file).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1);
+        })
+    });
+    // a replay takes the recorded command line, only --manifest is kept
+    let (args, command_line) = match &replay {
+        Some(manifest) => {
+            if manifest.version != env!("CARGO_PKG_VERSION") {
+                eprintln!(
+                    "warning: the manifest was written by rv64emu {}, this is {}",
+                    manifest.version,
+                    env!("CARGO_PKG_VERSION")
+                );
+            }
+            let mut args = Args::parse_from(&manifest.command_line);
+            args.manifest = cli.manifest;
+            (args, manifest.command_line.clone())
+        }
+        None => (cli, std::env::args().collect()),
+    };
+
+    if args.print_capabilities {
+        let mut caps = Capabilities::new();
+        caps.add_profile("linux_system", &default_config());
+        print!("{}", caps.to_json());
+        return;
+    }
+
+    if let Some(dir) = args.batch.as_ref() {
+        let timeout = Duration::from_secs(args.timeout.unwrap_or(10));
+        let all_pass = run_batch(dir, timeout);
+        process::exit(if all_pass { 0 } else { 1 });
+    }
 
-    if args.img.is_none() && args.xipflash.is_none() {
-        panic!("Please specify the img or xipflash");
+    if args.img.is_none() && args.xipflash.is_none() && args.spi_flash.is_none() {
+        panic!("Please specify the img, xipflash or spi_flash");
     }
 
     // config
-    let mut config = Config::new();
-    config.set_tlb_size(256);
-    config.set_icache_size(4096);
-    config.set_decode_cache_size(4096);
-    config.set_mmu_type("sv39"); // sv39 sv48 sv57
-    config.set_isa("rv64imac");
-    config.set_s_mode();
+    let config = match &replay {
+        Some(manifest) => manifest.config(),
+        None => {
+            let mut config = default_config();
+            config.set_pause_yield(args.pause_yield);
+            config.set_misaligned(args.misaligned);
+            if let Some(mode) = &args.compliance {
+                config.set_compliance(mode);
+            }
+            if let Some(size) = args.tlb_size {
+                config.set_tlb_size(size);
+            }
+            if let Some(ways) = args.tlb_ways {
+                config.set_tlb_ways(ways);
+            }
+            if let Some(policy) = &args.tlb_policy {
+                config.set_tlb_policy(policy);
+            }
+            if let Some(budget) = args.update_budget {
+                config.set_update_budget(budget);
+            }
+            config
+        }
+    };
+    if let Err(e) = config.validate() {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+    eprintln!("compliance mode: {}", config.compliance().name());
+    let mut manifest = RunManifest::new(&config, command_line);
     let config = Rc::new(config);
+    // read an image and check it against the replayed manifest
+    let mut read_image = |file: &str| {
+        let data = fs::read(file).unwrap_or_else(|e| panic!("can not read {file}:{e}"));
+        if let Some(Err(e)) = replay.as_ref().map(|x| x.check_image(file, &data)) {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+        manifest.add_image(file, &data);
+        data
+    };
 
     let signal_term = Arc::new(AtomicBool::new(false));
 
     let bus_u = rc_refcell_new(Bus::new());
+    // before the devices with an irq, they are wired to the chip
+    match args.irqchip.as_deref() {
+        None | Some("plic") => {}
+        Some("aia") => bus_u.borrow_mut().enable_aia().unwrap(),
+        Some(chip) => panic!("unknown irqchip:{chip}, plic or aia"),
+    }
 
-    // device dram len:0X08000000
-    let mem = DeviceMemory::new(0x8000000);
+    // device dram len:0X08000000, shared with the dma of the virtio devices
+    let mem = SharedMemory::new(MEM_BASE, 0x8000000);
 
-    bus_u.borrow_mut().add_device(DeviceType {
-        start: MEM_BASE,
-        len: mem.size() as u64,
-        instance: Box::new(mem),
-        name: "RAM",
-    });
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: MEM_BASE,
+            len: mem.size() as u64,
+            instance: Box::new(mem.clone()),
+            name: "RAM",
+        })
+        .unwrap();
 
     // device flash len:0X08000000
     let mut flash = DeviceMemory::new(0x8000000);
 
     if let Some(xipflash) = args.xipflash {
-        let flash_data = fs::read(xipflash).unwrap();
-        flash.load_binary(&flash_data);
+        let flash_data = read_image(&xipflash);
+        flash.load_binary(&flash_data).unwrap();
+    }
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: 0x3000_0000,
+            len: flash.size() as u64,
+            instance: Box::new(flash),
+            name: "XIPFLASH",
+        })
+        .unwrap();
+
+    if let Some(file) = args.spi_flash {
+        let spi_flash = SpiFlash::new(0x100_0000);
+        spi_flash.load_binary(&read_image(&file)).unwrap();
+        let mut bus = bus_u.borrow_mut();
+        bus.add_device(DeviceType {
+            start: 0x2000_0000,
+            len: spi_flash.size() as u64,
+            instance: Box::new(spi_flash.xip()),
+            name: "SPI_FLASH_XIP",
+        })
+        .unwrap();
+        bus.add_device(DeviceType {
+            start: 0x1000_5000,
+            len: SPI_FLASH_CTRL_SIZE,
+            instance: Box::new(spi_flash),
+            name: "SPI_FLASH",
+        })
+        .unwrap();
     }
-    bus_u.borrow_mut().add_device(DeviceType {
-        start: 0x3000_0000,
-        len: flash.size() as u64,
-        instance: Box::new(flash),
-        name: "XIPFLASH",
-    });
 
     // we use crossbeam_queue::SegQueue as the uart fifo
     // each uart has a tx and rx fifo, and we use them to communicate with the host pc
@@ -118,22 +418,29 @@ fn main() {
     let uart_tx_fifo = FifoUnbounded::new(crossbeam_queue::SegQueue::<u8>::new());
     let uart_rx_fifo = FifoUnbounded::new(crossbeam_queue::SegQueue::<u8>::new());
 
-    let rx_fifo = uart_rx_fifo.clone();
     let tx_fifo = uart_tx_fifo.clone();
     let signal_term_uart = signal_term.clone();
-    thread::spawn(move || loop {
-        let mut buf = [0; 1];
-        if let Ok(n) = stdin().read(&mut buf) {
-            if n > 1 {
-                panic!("Read {} characters into a 1 byte buffer", n);
-            }
-            if n == 1 {
-                rx_fifo.push(buf[0]);
+    spawn_stdin_reader(uart_rx_fifo.clone());
+
+    // the uarts on stdio share its fifos, the others get their own
+    let serial = |name: &str, backend: &str| {
+        let backend = CharBackend::parse(backend).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1);
+        });
+        if backend == CharBackend::Stdio {
+            return (uart_tx_fifo.clone(), uart_rx_fifo.clone());
+        }
+        let (tx, rx) = (fifo_unbounded_new(), fifo_unbounded_new());
+        match backend.spawn(tx.clone(), rx.clone()) {
+            Ok(host) => eprintln!("{name}: {host}"),
+            Err(e) => {
+                eprintln!("can not open {name} {backend:?}:{e}");
+                process::exit(1);
             }
-            // Nothing needs to be sent for n == 0
         }
-        std::thread::sleep(Duration::from_millis(100));
-    });
+        (tx, rx)
+    };
 
     let uart_tx_thread = thread::spawn(move || loop {
         while !tx_fifo.is_empty() {
@@ -148,35 +455,143 @@ fn main() {
         // std::thread::sleep(Duration::from_millis(100));
     });
 
-    // device 16650_uart
-    let device_16650_uart = Device16550aUART::new(uart_tx_fifo.clone(), uart_rx_fifo.clone());
-
-    bus_u.borrow_mut().add_device(DeviceType {
-        start: 0x1000_0000,
-        len: 0x1000,
-        instance: Box::new(device_16650_uart),
-        name: "16550a_uart",
-    });
-
-    // device sifive_uart
-    let device_sifive_uart = DeviceSifiveUart::new(uart_tx_fifo, uart_rx_fifo);
+    // the console and the 16550a, then the uarts of --uart
+    let mut uarts = vec![
+        (
+            "Sifive_Uart",
+            UartConfig {
+                backend: args.serial0.clone().unwrap_or("stdio".to_string()),
+                ..UartConfig::sifive_console()
+            },
+        ),
+        (
+            "16550a_uart",
+            UartConfig {
+                backend: args.serial1.clone().unwrap_or("stdio".to_string()),
+                ..UartConfig::ns16550a()
+            },
+        ),
+    ];
+    for (idx, spec) in args.uart.iter().enumerate() {
+        let uart = UartConfig::parse(spec).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1);
+        });
+        // the bus keeps the names for the whole run
+        let name: &'static str = format!("uart{}", idx + 2).leak();
+        uarts.push((name, uart));
+    }
+    for (name, uart) in uarts {
+        let (tx, rx) = serial(name, &uart.backend);
+        uart.add_to(&mut bus_u.borrow_mut(), name, tx, rx)
+            .unwrap_or_else(|e| {
+                eprintln!("{e}");
+                process::exit(1);
+            });
+    }
 
-    // sifive_uart support irq
+    // device aclint sswi, the S-mode IPIs of the clint harts
+    let sswi = bus_u.borrow().clint.instance.sswi();
     bus_u
         .borrow_mut()
-        .plic
-        .instance
-        .register_irq_source(SIFIVE_UART_IRQ, Rc::clone(&device_sifive_uart.irq_pending));
+        .add_device(DeviceType {
+            start: 0x02F0_0000,
+            len: ACLINT_SSWI_SIZE,
+            instance: Box::new(sswi),
+            name: "ACLINT_SSWI",
+        })
+        .unwrap();
 
-    bus_u.borrow_mut().add_device(DeviceType {
-        start: 0xc0000000,
-        len: 0x1000,
-        instance: Box::new(device_sifive_uart),
-        name: "Sifive_Uart",
+    // device host pipe
+    if let Some(pipe_path) = args.pipe.as_ref() {
+        let device_pipe = DevicePipe::open(pipe_path)
+            .unwrap_or_else(|e| panic!("can not open pipe {pipe_path}:{e}"));
+
+        bus_u
+            .borrow_mut()
+            .add_device(DeviceType {
+                start: 0x1000_2000,
+                len: 0x1000,
+                instance: Box::new(device_pipe),
+                name: "HOST_PIPE",
+            })
+            .unwrap();
+    }
+
+    // device virtio-net
+    match args.net.as_deref() {
+        None | Some("none") => {}
+        Some(spec) => {
+            let spec = NetSpec::parse(spec).unwrap_or_else(|e| panic!("{e}"));
+            let backend = spec
+                .open()
+                .unwrap_or_else(|e| panic!("can not open net backend {spec:?}:{e}"));
+            let net = VirtioNet::new(DEFAULT_MAC, backend);
+            bus_u
+                .borrow_mut()
+                .add_device_with_irq(
+                    DeviceType {
+                        start: 0x1000_1000,
+                        len: VIRTIO_MMIO_SIZE,
+                        instance: Box::new(VirtioMmio::new(net, mem)),
+                        name: "VIRTIO_NET",
+                    },
+                    VIRTIO_NET_IRQ,
+                    IrqTrigger::Level,
+                )
+                .unwrap();
+        }
+    }
+
+    // device host shared memory
+    #[cfg(unix)]
+    if let Some(path) = args.host_shm.as_ref() {
+        let shm = HostShm::open(path, 0x10_0000)
+            .unwrap_or_else(|e| panic!("can not map host shm {path}:{e}"));
+        let mut bus = bus_u.borrow_mut();
+        bus.add_device(DeviceType {
+            start: 0x4000_0000,
+            len: shm.size() as u64,
+            instance: Box::new(shm.window()),
+            name: "HOST_SHM",
+        })
+        .unwrap();
+        bus.add_device_with_irq(
+            DeviceType {
+                start: 0x1000_6000,
+                len: SHM_DOORBELL_SIZE,
+                instance: Box::new(shm.doorbell()),
+                name: "HOST_SHM_DOORBELL",
+            },
+            HOST_SHM_IRQ,
+            IrqTrigger::Level,
+        )
+        .unwrap();
+    }
+
+    // device watchdog, the host side is polled by the sim
+    let watchdog = args.watchdog.as_deref().map(|action| {
+        let action = match action {
+            "reset" => WatchdogAction::Reset,
+            "stop" => WatchdogAction::Stop,
+            _ => panic!("unknown watchdog action:{action}, reset or stop"),
+        };
+        let mtime = bus_u.borrow().clint.instance.mtime();
+        let watchdog = DeviceWatchdog::new(mtime, action);
+        bus_u
+            .borrow_mut()
+            .add_device(DeviceType {
+                start: 0x1000_4000,
+                len: WDT_SIZE,
+                instance: Box::new(watchdog.clone()),
+                name: "WATCHDOG",
+            })
+            .unwrap();
+        watchdog
     });
 
     let boot_pc = args.boot_pc.as_ref().map_or(0x8000_0000, |x| {
-        let cleaned = x.trim_start_matches(|c| c == '0' || c == 'x' || c == 'X');
+        let cleaned = x.trim_start_matches(['0', 'x', 'X']);
         u64::from_str_radix(cleaned, 16)
             .unwrap_or_else(|_| panic!("boot_pc is not a valid hex number"))
     });
@@ -196,17 +611,102 @@ fn main() {
                 .with_smode(true)
                 .build(),
         );
+        if let (0, Some(file)) = (hart_id, &args.pipeline_view) {
+            let mut out = std::io::BufWriter::new(fs::File::create(file).unwrap());
+            hart.borrow_mut().pipeline = Some(Pipeline::new(Box::new(move |row| {
+                writeln!(out, "{row}").unwrap();
+            })));
+        }
+        if let (0, Some(weights)) = (hart_id, &args.energy) {
+            let weights = EnergyWeights::parse(weights).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(1);
+            });
+            let mut hart = hart.borrow_mut();
+            hart.energy = Some(EnergyModel::new(weights));
+            // the stalls and flushes come from the pipeline model
+            hart.pipeline.get_or_insert_with(Pipeline::without_diagram);
+        }
         hart_vec.push(hart);
     }
 
     // create another thread to simmulate the harts
     // let cpu_main = thread::spawn(move || {
-    let mut sim = RVsim::new(hart_vec, 23456);
+    let mut sim = RVsim::new(hart_vec, 23456).unwrap();
+    if let Some(cmd) = args.on_pass.clone() {
+        sim.add_exit_hook(move |exit| {
+            if exit == GuestExit::Pass {
+                run_host_command(&cmd, exit);
+            }
+        });
+    }
+    if let Some(cmd) = args.on_fail.clone() {
+        sim.add_exit_hook(move |exit| {
+            if exit != GuestExit::Pass {
+                run_host_command(&cmd, exit);
+            }
+        });
+    }
+    if let Some(watchdog) = watchdog {
+        sim.add_watchdog(watchdog);
+    }
+    if let Some(port) = args.control_port {
+        sim.enable_control_server("127.0.0.1", port);
+    }
+    if let Some(file) = args.initrd.as_ref() {
+        if args.gen_fdt.is_none() && args.dtb.is_none() {
+            eprintln!("--initrd without --gen-fdt or --dtb: the guest is not told where it is");
+        }
+        let initrd = read_image(file);
+        if let Err(e) = sim.load_initrd_from_slice(&initrd) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+    let fdt = match (args.gen_fdt.as_deref(), args.dtb.as_ref()) {
+        (Some(bootargs), _) => sim.add_fdt(bootargs),
+        (None, Some(file)) => {
+            let dtb = read_image(file);
+            sim.fdt_addr(dtb.len())
+                .and_then(|addr| sim.load_dtb_from_slice(addr, &dtb).map(|_| addr))
+        }
+        (None, None) => Ok(0),
+    };
+    let fdt = fdt.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    if let Some(addr) = args.boot_rom.as_ref() {
+        let addr = u64::from_str_radix(addr.trim_start_matches("0x"), 16)
+            .unwrap_or_else(|_| panic!("boot_rom is not a valid hex number"));
+        sim.add_boot_rom(addr, boot_pc, fdt).unwrap();
+    }
     if let Some(ram_img) = args.img {
-        sim.load_image(&ram_img);
+        read_image(&ram_img);
+        if let Err(e) = sim.load_image(&ram_img) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+    // written before the run, so a crashing run can be replayed as well
+    if let Some(file) = args.manifest.as_ref() {
+        if let Err(e) = manifest.save(file) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
     }
 
+    // the keys go to the guest as they are typed
+    let terminal = RawTerminal::enable();
+    if terminal.is_raw() {
+        eprintln!("press Ctrl+A X to quit");
+    }
     sim.run();
+    drop(terminal);
+    // the logger is off, the perf report of --energy goes to stderr
+    if let Some(report) = sim.energy_report() {
+        eprintln!("{report}");
+    }
     // notify the uart thread to exit
     signal_term.store(true, Ordering::Relaxed);
     // });
@@ -47,37 +47,44 @@ fn main() {
     let mem: DeviceMemory = DeviceMemory::new(128 * 1024 * 1024);
 
     let device_name = mem.get_name();
-    bus_u.borrow_mut().add_device(DeviceType {
-        start: MEM_BASE,
-        len: mem.size() as u64,
-        instance: Box::new(mem),
-        name: device_name,
-    });
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: MEM_BASE,
+            len: mem.size() as u64,
+            instance: Box::new(mem),
+            name: device_name,
+        })
+        .unwrap();
 
     let uart0_tx_fifo = fifo_unbounded_new::<u8>();
 
     // device uart
     let uart = DeviceUart::new(uart0_tx_fifo.clone());
     let device_name = uart.get_name();
-    bus_u.borrow_mut().add_device(DeviceType {
-        start: SERIAL_PORT,
-        len: 1,
-        instance: Box::new(uart),
-        name: device_name,
-    });
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: SERIAL_PORT,
+            len: 1,
+            instance: Box::new(uart),
+            name: device_name,
+        })
+        .unwrap();
 
     // print bus device map
     println!("{0}", bus_u.borrow());
 
     let harts = vec![hart0];
-    let mut sim = RVsim::new(harts, 23456);
+    let mut sim = RVsim::new(harts, 23456).unwrap();
 
     // run simulation
     let bin_data = std::fs::read(bin_path).unwrap();
-    sim.load_image_from_slice(&bin_data);
+    sim.load_image_from_slice(&bin_data).unwrap();
     sim.prepare_to_run();
+    let budget = sim.config().update_budget();
     while !sim.is_finish() {
-        sim.run_once(5000);
+        sim.run_once(budget);
 
         while let Some(c) = uart0_tx_fifo.pop() {
             print!("{}", c as char);
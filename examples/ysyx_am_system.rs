@@ -1,430 +1,888 @@
-// extern crate rv64emu;
-
-// use clap::Parser;
-// #[allow(unused_imports)]
-// use rv64emu::tools::Fifobounded;
-// use rv64emu::{
-//     config::Config,
-//     device::device_am_vga::VGA_BUF_SIZE,
-//     tools::{rc_refcell_new, FifoUnbounded},
-// };
-
-// #[allow(unused_imports)]
-// use std::{
-//     cell::Cell,
-//     cell::RefCell,
-//     io::{self, Read},
-//     num::NonZeroUsize,
-//     process,
-//     rc::Rc,
-//     sync::{
-//         atomic::{AtomicBool, Ordering},
-//         Arc,
-//     },
-//     thread,
-//     time::Duration,
-// };
-// use std::{
-//     io::{stdin, Write},
-//     sync::Mutex,
-// };
-
-// use log::{info, LevelFilter};
-// use rv64emu::{device::device_16550a::Device16550aUART, rvsim::RVsim};
-
-// use rv64emu::device::{
-//     device_am_kb::{DeviceKB, DeviceKbItem},
-//     device_am_mouse::{DeviceMouse, DeviceMouseItem},
-//     device_am_vga::DeviceVGA,
-//     device_am_vgactl::DeviceVGACTL,
-//     device_trait::{FB_ADDR, KBD_ADDR, MOUSE_ADDR, VGACTL_ADDR},
-// };
-// use sdl2::{
-//     event::Event,
-//     keyboard::{Keycode, Scancode},
-// };
-
-// use crate::rv64emu::tools::RcRefCell;
-
-// use crate::{
-//     rv64emu::device::{
-//         device_am_rtc::DeviceRTC,
-//         device_am_uart::DeviceUart,
-//         device_memory::DeviceMemory,
-//         device_trait::DeviceBase,
-//         device_trait::{MEM_BASE, RTC_ADDR, SERIAL_PORT},
-//     },
-//     rv64emu::rv64core::bus::{Bus, DeviceType},
-//     rv64emu::rv64core::cpu_core::CpuCoreBuild,
-// };
-
-// // -------------Device Tree MAP-------------
-// // name:CLINT           Area:0X02000000-->0X02010000,len:0X00010000
-// // name:PLIC            Area:0X0C000000-->0X10000000,len:0X04000000
-// // name:DRAM            Area:0X80000000-->0X88000000,len:0X08000000
-// // name:UART            Area:0XA00003F8-->0XA00003F9,len:0X00000001
-// // name:RTC             Area:0XA0000048-->0XA0000050,len:0X00000008
-// // name:VGA_CTL         Area:0XA0000100-->0XA0000108,len:0X00000008
-// // name:VGA_FB          Area:0XA1000000-->0XA1075300,len:0X00075300
-// // name:KeyBorad_AM     Area:0XA0000060-->0XA0000068,len:0X00000008
-// // name:Mouse           Area:0XA0000070-->0XA0000080,len:0X00000010
-// // name:Sifive_Uart     Area:0XC0000000-->0XC0001000,len:0X00001000
-
-// #[derive(Parser, Debug)]
-// #[command(author, version, about, long_about = None)]
-// struct Args {
-//     #[arg(long, value_name = "FILE")]
-//     /// IMG bin copy to ram
-//     img: Option<String>,
-//     #[arg(long, value_name = "FILE")]
-//     /// IMG bin copy to xipflash
-//     xipflash: Option<String>,
-//     #[arg(long, value_name = "HEX")]
-//     /// the first instruction address,default:0x80000000
-//     boot_pc: Option<String>,
-//     #[arg(short, long, value_name = "USIZE")]
-//     /// Number of harts,default:1
-//     num_harts: Option<usize>,
-// }
-
-// fn main() {
-//     simple_logger::SimpleLogger::new()
-//         .with_level(LevelFilter::Debug)
-//         .init()
-//         .unwrap();
-//     let args = Args::parse();
-
-//     if args.img.is_none() && args.xipflash.is_none() {
-//         panic!("Please specify the img or xipflash\n");
-//     }
-
-//     let ready_to_run = Arc::new(AtomicBool::new(false));
-
-//     let signal_term = Arc::new(AtomicBool::new(false));
-//     let signal_term_cpucore = signal_term.clone();
-//     #[allow(clippy::redundant_clone)]
-//     #[allow(unused_variables)]
-//     let signal_term_uart = signal_term.clone();
-
-//     #[allow(unused_variables)]
-//     #[allow(clippy::redundant_clone)]
-//     let signal_term_sdl_event = signal_term.clone();
-//     #[allow(unused_variables)]
-//     #[allow(clippy::redundant_clone)]
-//     let signal_term_uart = signal_term.clone();
-
-//     let bus_u = rc_refcell_new(Bus::new());
-
-//     // device dram len:0X08000000
-//     let mem = DeviceMemory::new(128 * 1024 * 1024);
-
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: MEM_BASE,
-//         len: mem.size() as u64,
-//         instance: Box::new(mem),
-//         name: "RAM",
-//     });
-
-//     // device flash len:0X01000000
-//     let falsh = DeviceMemory::new(128 * 1024 * 1024);
-
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: 0x3000_0000,
-//         len: falsh.size() as u64,
-//         instance: Box::new(falsh),
-//         name: "XIPFLASH",
-//     });
-
-//     let uart_tx_fifo = FifoUnbounded::new(crossbeam_queue::SegQueue::<u8>::new());
-//     let uart_rx_fifo = FifoUnbounded::new(crossbeam_queue::SegQueue::<u8>::new());
-
-//     let rx_fifo = uart_rx_fifo.clone();
-//     let tx_fifo = uart_tx_fifo.clone();
-
-//     thread::spawn(move || loop {
-//         let mut buf = [0; 1];
-//         if let Ok(n) = stdin().read(&mut buf) {
-//             if n > 1 {
-//                 panic!("Read {} characters into a 1 byte buffer", n);
-//             }
-//             if n == 1 {
-//                 rx_fifo.push(buf[0]);
-//             }
-//             // Nothing needs to be sent for n == 0
-//         }
-//         std::thread::sleep(Duration::from_millis(100));
-//     });
-
-//     let uart_tx_thread = thread::spawn(move || loop {
-//         while !tx_fifo.is_empty() {
-//             if let Some(c) = tx_fifo.pop() {
-//                 print!("{}", c as char)
-//             }
-//         }
-//         io::stdout().flush().unwrap();
-//         if signal_term_uart.load(Ordering::Relaxed) {
-//             break;
-//         }
-//         std::thread::sleep(Duration::from_millis(50));
-//     });
-
-//     // device am_uart
-//     let uart = DeviceUart::new(uart_tx_fifo.clone());
-
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: SERIAL_PORT,
-//         len: 1,
-//         instance: Box::new(uart),
-//         name: "AM_UART",
-//     });
-//     // device 16650_uart
-//     let device_16650_uart = Device16550aUART::new(uart_tx_fifo, uart_rx_fifo);
-
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: 0x1000_0000,
-//         len: 0x1000,
-//         instance: Box::new(device_16650_uart),
-//         name: "16550a_uart",
-//     });
-
-//     // device rtc
-//     let rtc = DeviceRTC::new();
-//     let device_name = rtc.get_name();
-
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: RTC_ADDR,
-//         len: 8,
-//         instance: Box::new(rtc),
-//         name: device_name,
-//     });
-
-//     let hart_num: usize = args.num_harts.unwrap_or(1);
-
-//     let boot_pc = if let Some(x) = args.boot_pc.as_ref() {
-//         u64::from_str_radix(
-//             x.trim_start_matches(|c| c == '0' || c == 'x' || c == 'X'),
-//             16,
-//         )
-//         .expect("boot_pc is not a valid hex number")
-//     } else {
-//         0x8000_0000
-//     };
-
-//     info!("boot_pc:0x{:x}", boot_pc);
-
-//     let mut config = Config::new();
-//     config.set_tlb_size(256);
-//     config.set_icache_size(4096);
-//     config.set_decode_cache_size(4096);
-//     config.set_mmu_type("bare");
-//     config.set_isa("rv64im");
-
-//     let config = Rc::new(config);
-
-//     let mut hart_vec = Vec::new();
-
-//     // create hart according to the number of harts
-//     for hart_id in 0..hart_num {
-//         let hart = rc_refcell_new(
-//             CpuCoreBuild::new(bus_u.clone(), config.clone())
-//                 .with_boot_pc(boot_pc)
-//                 .with_hart_id(hart_id)
-//                 .with_smode(false)
-//                 .build(),
-//         );
-//         hart_vec.push(hart);
-//     }
-
-//     let mut sim = RVsim::new(hart_vec, 23456);
-//     if let Some(ram_img) = args.img {
-//         sim.load_image(&ram_img);
-//     }
-
-//     // create another thread to simmulate the harts
-//     // while the main thread is used to handle sdl events
-//     // which will be send to the coresponding devices through ring_channel
-//     let ready_to_run_sim = ready_to_run.clone();
-//     let cpu_main = thread::spawn(move || {
-//         // wait for all devices to be ready
-//         while !ready_to_run_sim.load(Ordering::Relaxed) {
-//             std::thread::sleep(Duration::from_millis(100));
-//         }
-
-//         sim.run();
-
-//         // send signal to all other threads
-//         signal_term_cpucore.store(true, Ordering::Release);
-//     });
-
-//     create_sdl2_devices(bus_u, signal_term_sdl_event, ready_to_run);
-
-//     cpu_main.join().unwrap();
-//     uart_tx_thread.join().unwrap();
-// }
-
-// fn send_key_event(tx: &Fifobounded<DeviceKbItem>, val: Scancode, keydown: bool) {
-//     tx.force_push(DeviceKbItem {
-//         scancode: val,
-//         is_keydown: keydown,
-//     });
-// }
-
-// fn create_sdl2_devices(
-//     bus_u: RcRefCell<Bus>,
-//     signal_term_sdl_event: Arc<AtomicBool>,
-//     ready_to_run: Arc<AtomicBool>,
-// ) {
-//     /*--------init sdl --------*/
-//     // subsequnt devices are base on sdl2 api
-//     // 1. device vga
-//     // 2. device kb
-//     // 3. device mouse
-//     let sdl_context = sdl2::init().unwrap();
-//     let video_subsystem = sdl_context.video().unwrap();
-//     let event_pump: sdl2::EventPump = sdl_context.event_pump().expect("fail to get event_pump");
-
-//     let window = video_subsystem
-//         .window("rust-sdl2 demo: Video", 800, 600)
-//         .position_centered()
-//         .opengl()
-//         .build()
-//         .map_err(|e| e.to_string())
-//         .unwrap();
-
-//     let mut canvas = window.into_canvas().software().build().expect("canvas err");
-//     canvas.set_scale(2.0, 2.0).unwrap();
-
-//     // device vgactl
-//     let vgactl_msg = Rc::new(Cell::new(false));
-
-//     let vgactl = DeviceVGACTL::new(vgactl_msg.clone());
-
-//     let device_name = vgactl.get_name();
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: VGACTL_ADDR,
-//         len: 8,
-//         instance: Box::new(vgactl),
-//         name: device_name,
-//     });
-
-//     // device vga
-
-//     let vga_fb = Arc::new(Mutex::new(vec![0_u8; VGA_BUF_SIZE].into_boxed_slice()));
-
-//     let vga = DeviceVGA::new(vga_fb.clone());
-//     let device_name = vga.get_name();
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: FB_ADDR,
-//         len: DeviceVGA::get_size() as u64,
-//         instance: Box::new(vga),
-//         name: device_name,
-//     });
-
-//     let kb_am_fifo = Fifobounded::<DeviceKbItem>::new(crossbeam_queue::ArrayQueue::new(16));
-
-//     let kb_sdl_fifo =
-//         Fifobounded::<sdl2::keyboard::Keycode>::new(crossbeam_queue::ArrayQueue::new(16));
-
-//     let device_kb = DeviceKB::new(kb_am_fifo.clone(), kb_sdl_fifo.clone());
-
-//     let device_name = device_kb.get_name();
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: KBD_ADDR,
-//         len: 8,
-//         instance: Box::new(device_kb),
-//         name: device_name,
-//     });
-
-//     // device am_mouse
-//     let mouse_fifo = Fifobounded::<DeviceMouseItem>::new(crossbeam_queue::ArrayQueue::new(16));
-
-//     let device_mouse = DeviceMouse::new(mouse_fifo.clone());
-
-//     bus_u.borrow_mut().add_device(DeviceType {
-//         start: MOUSE_ADDR,
-//         len: 16,
-//         instance: Box::new(device_mouse),
-//         name: "AM_Mouse",
-//     });
-
-//     info!("{0}", bus_u.borrow_mut());
-//     info!("start sdl event loop");
-
-//     handle_sdl_event(
-//         signal_term_sdl_event,
-//         event_pump,
-//         kb_am_fifo,
-//         kb_sdl_fifo,
-//         mouse_fifo,
-//         vga_fb.clone(),
-//         vgactl_msg.clone(),
-//         canvas,
-//         ready_to_run,
-//     );
-// }
-
-// fn handle_sdl_event(
-//     signal_term: Arc<AtomicBool>,
-//     mut event_pump: sdl2::EventPump,
-//     kb_am_tx: Fifobounded<DeviceKbItem>,
-//     kb_sdl_tx: Fifobounded<Keycode>,
-//     mouse_sdl_tx: Fifobounded<DeviceMouseItem>,
-//     vga_fb: Arc<Mutex<Box<[u8]>>>,
-//     vga_ctl_msg: Rc<Cell<bool>>,
-//     mut canvas: sdl2::render::WindowCanvas,
-//     ready_to_run: Arc<AtomicBool>,
-// ) {
-//     let texture_creator = canvas.texture_creator();
-//     let mut texture = texture_creator
-//         .create_texture_target(sdl2::pixels::PixelFormatEnum::ARGB8888, 400_u32, 300_u32)
-//         .map_err(|e| e.to_string())
-//         .unwrap();
-
-//     // send signal to sim thread, all devices are ready
-//     ready_to_run.store(true, Ordering::Release);
-//     while !signal_term.load(Ordering::Relaxed) {
-//         let mouse_state = event_pump.mouse_state();
-
-//         mouse_sdl_tx.force_push(DeviceMouseItem {
-//             x: (mouse_state.x() / 2) as u32,
-//             y: (mouse_state.y() / 2) as u32,
-//             mouse_btn_state: mouse_state.to_sdl_state(),
-//         });
-
-//         for event in event_pump.poll_iter() {
-//             match event {
-//                 Event::Quit { .. } => process::exit(0),
-//                 Event::KeyUp {
-//                     scancode: Some(val),
-//                     ..
-//                 } => send_key_event(&kb_am_tx, val, false),
-
-//                 Event::KeyDown {
-//                     scancode: Some(val),
-//                     keycode: Some(sdl_key_code),
-//                     ..
-//                 } => {
-//                     send_key_event(&kb_am_tx, val, true);
-//                     kb_sdl_tx.force_push(sdl_key_code);
-//                 }
-
-//                 _ => (),
-//             }
-//         }
-
-//         if vga_ctl_msg.get() {
-//             vga_ctl_msg.set(false);
-//             let fb = vga_fb.lock().unwrap();
-//             let fb_copy = fb.clone();
-//             drop(fb);
-//             texture
-//                 .update(None, &fb_copy, 4 * 400)
-//                 .expect("update texture failed");
-//             canvas.copy(&texture, None, None).unwrap();
-//             canvas.present();
-//         }
-//         std::thread::sleep(Duration::from_millis(20));
-//     }
-// }
-
-
+extern crate rv64emu;
+
+use clap::Parser;
+use rv64emu::{
+    config::Config,
+    device::{
+        device_am_audio::{AudioStream, AUDIO_CTL_SIZE, AUDIO_SBUF_SIZE},
+        device_am_vga::{VgaFrame, VgaMode},
+    },
+    input::{KeyEvent, MouseButton, MouseButtons, MouseMotion, MouseState},
+    tools::{fifo_bounded_new, fifo_unbounded_new, rc_refcell_new, FifoUnbounded, Fifobounded},
+};
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, stdin, Read, Write},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn, LevelFilter};
+use rv64emu::{
+    device::device_16550a::Device16550aUART,
+    rvsim::{run_host_command, GuestExit, RVsim},
+};
+
+use rv64emu::device::{
+    device_am_audio::{DeviceAudio, DeviceAudioSbuf},
+    device_am_disk::{DeviceDisk, DISK_SIZE},
+    device_am_kb::DeviceKB,
+    device_am_mouse::DeviceMouse,
+    device_am_vga::DeviceVGA,
+    device_am_vgactl::{DeviceVGACTL, VGACTL_SIZE},
+    device_ps2_mouse::{DevicePs2Mouse, PS2_MOUSE_IRQ, PS2_MOUSE_SIZE},
+    device_trait::{
+        AUDIO_ADDR, AUDIO_SBUF_ADDR, DISK_ADDR, FB_ADDR, KBD_ADDR, MOUSE_ADDR, VGACTL_ADDR,
+        VGA_TEXT_ADDR,
+    },
+    device_vga_text::{DeviceVgaText, TEXT_FRAME_MODE, VGA_TEXT_SIZE},
+    irq::IrqTrigger,
+};
+use sdl2::{
+    audio::{AudioQueue, AudioSpecDesired},
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod, Scancode},
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::WindowCanvas,
+    surface::Surface,
+    video::FullscreenType,
+};
+
+use crate::{
+    rv64emu::device::{
+        device_am_rtc::DeviceRTC,
+        device_am_uart::DeviceUart,
+        device_memory::DeviceMemory,
+        device_trait::DeviceBase,
+        device_trait::{MEM_BASE, RTC_ADDR, SERIAL_PORT},
+    },
+    rv64emu::rv64core::bus::{Bus, DeviceType},
+    rv64emu::rv64core::cpu_core::CpuCoreBuild,
+};
+
+// -------------Device Tree MAP-------------
+// name:CLINT           Area:0X02000000-->0X02010000,len:0X00010000
+// name:PLIC            Area:0X0C000000-->0X10000000,len:0X04000000
+// name:RAM             Area:0X80000000-->0X88000000,len:0X08000000
+// name:XIPFLASH        Area:0X30000000-->0X38000000,len:0X08000000
+// name:AM_UART         Area:0XA00003F8-->0XA00003F9,len:0X00000001
+// name:16550a_uart     Area:0X10000000-->0X10001000,len:0X00001000
+// name:AM_RTC          Area:0XA0000048-->0XA0000050,len:0X00000008
+// name:AM_VGA_CTL      Area:0XA0000100-->0XA0000120,len:0X00000020
+// name:AM_VGA_FB       Area:0XA1000000-->0XA1075300,len:0X00075300 (--fb 400x300x32)
+// name:VGA_TEXT        Area:0XA1300000-->0XA1300FA0,len:0X00000FA0
+// name:AM_KeyBorad     Area:0XA0000060-->0XA0000068,len:0X00000008
+// name:AM_Mouse        Area:0XA0000070-->0XA0000080,len:0X00000010
+// name:AM_DISK         Area:0XA0000300-->0XA0000324,len:0X00000024
+// name:AM_AUDIO        Area:0XA0000200-->0XA0000218,len:0X00000018
+// name:AM_AUDIO_SBUF   Area:0XA1200000-->0XA1210000,len:0X00010000
+
+const MAX_SCALE: u32 = 8;
+// the longest time the window waits for a frame before polling events again
+const EVENT_INTERVAL: Duration = Duration::from_millis(10);
+// the bytes queued to SDL ahead of the playback, the rest waits in the
+// stream buffer so the guest sees it full and slows down
+const AUDIO_QUEUE_BYTES: u32 = 8192;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(long, value_name = "FILE")]
+    /// IMG bin copy to ram
+    img: Option<String>,
+    #[arg(long, value_name = "FILE")]
+    /// IMG bin copy to xipflash
+    xipflash: Option<String>,
+    #[arg(long, value_name = "HEX")]
+    /// the first instruction address,default:0x80000000
+    boot_pc: Option<String>,
+    #[arg(short, long, value_name = "USIZE")]
+    /// Number of harts,default:1
+    num_harts: Option<usize>,
+    #[arg(long, value_name = "FILE")]
+    /// the host file of the AM disk, read and written in place
+    disk: Option<String>,
+    #[arg(long, value_name = "HEX")]
+    /// add a PS/2 mouse (altera_ps2 port, PLIC irq 12) at HEX, it reports the relative moves
+    ps2_mouse: Option<String>,
+    #[arg(long, value_name = "WxH[xBPP]", default_value = "400x300x32")]
+    /// the framebuffer mode at boot, the guest can switch to any mode that fits it (bpp 16 or 32)
+    fb: String,
+    #[arg(long)]
+    /// start in the 80x25 text mode, the cells are at VGA_TEXT_ADDR
+    vga_text: bool,
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    /// window size in multiples of the guest resolution, Ctrl+Alt+Plus/Minus change it
+    scale: u32,
+    #[arg(long)]
+    /// start in fullscreen, Ctrl+Alt+F toggles it
+    fullscreen: bool,
+    #[arg(long)]
+    /// fill the window keeping the aspect ratio, instead of integer scaling
+    stretch: bool,
+    #[arg(long, value_name = "CMD")]
+    /// host command run by `sh -c` when the guest passes (GOOD TRAP or tohost)
+    on_pass: Option<String>,
+    #[arg(long, value_name = "CMD")]
+    /// host command run when the guest fails, RV64EMU_EXIT_CODE holds the exit code
+    on_fail: Option<String>,
+    #[arg(long, value_name = "USIZE")]
+    /// instructions each hart retires between two device updates,default:5000
+    update_budget: Option<usize>,
+}
+
+// how the guest frame is laid out in the window
+struct Display {
+    // the guest resolution
+    frame: (u32, u32),
+    scale: u32,
+    fullscreen: bool,
+    // integer scaling keeps the pixels crisp, the rest of the window is black
+    integer_scale: bool,
+}
+
+impl Display {
+    fn new(args: &Args, mode: VgaMode) -> Self {
+        Display {
+            frame: (mode.width, mode.height),
+            scale: args.scale.clamp(1, MAX_SCALE),
+            fullscreen: args.fullscreen,
+            integer_scale: !args.stretch,
+        }
+    }
+
+    fn window_size(&self) -> (u32, u32) {
+        (self.frame.0 * self.scale, self.frame.1 * self.scale)
+    }
+
+    // the area of the window the frame is drawn to, centered,
+    // the aspect ratio is kept
+    fn viewport(&self, output: (u32, u32)) -> Rect {
+        let ((fw, fh), (ow, oh)) = (self.frame, output);
+        let (w, h) = if self.integer_scale && ow >= fw && oh >= fh {
+            let scale = (ow / fw).min(oh / fh);
+            (fw * scale, fh * scale)
+        } else if ow * fh <= oh * fw {
+            (ow, ow * fh / fw)
+        } else {
+            (oh * fw / fh, oh)
+        };
+        let (w, h) = (w.max(1), h.max(1));
+        Rect::new(
+            (ow.saturating_sub(w) / 2) as i32,
+            (oh.saturating_sub(h) / 2) as i32,
+            w,
+            h,
+        )
+    }
+
+    // window coordinates to frame coordinates
+    fn to_frame(&self, viewport: Rect, x: i32, y: i32) -> (u32, u32) {
+        let x = (x - viewport.x()).clamp(0, viewport.width() as i32 - 1) as u32;
+        let y = (y - viewport.y()).clamp(0, viewport.height() as i32 - 1) as u32;
+        (
+            x * self.frame.0 / viewport.width(),
+            y * self.frame.1 / viewport.height(),
+        )
+    }
+
+    fn apply(&self, canvas: &mut WindowCanvas) {
+        let window = canvas.window_mut();
+        if self.fullscreen {
+            window.set_fullscreen(FullscreenType::Desktop).unwrap();
+        } else {
+            window.set_fullscreen(FullscreenType::Off).unwrap();
+            let (w, h) = self.window_size();
+            window.set_size(w, h).unwrap();
+        }
+    }
+}
+
+// the emulator actions, bound to Ctrl+Alt+<key>. the whole Ctrl+Alt
+// namespace belongs to the host, these keys are never sent to the guest
+#[derive(Clone, Copy, Debug)]
+enum HostAction {
+    Grab,
+    Fullscreen,
+    ScaleUp,
+    ScaleDown,
+    Pause,
+    Screenshot,
+    // reserved, not supported yet
+    Reset,
+    Monitor,
+}
+
+impl HostAction {
+    fn from_key(key: Keycode) -> Option<Self> {
+        match key {
+            Keycode::G => Some(HostAction::Grab),
+            Keycode::F => Some(HostAction::Fullscreen),
+            Keycode::Equals | Keycode::KpPlus => Some(HostAction::ScaleUp),
+            Keycode::Minus | Keycode::KpMinus => Some(HostAction::ScaleDown),
+            Keycode::P => Some(HostAction::Pause),
+            Keycode::S => Some(HostAction::Screenshot),
+            Keycode::R => Some(HostAction::Reset),
+            Keycode::M => Some(HostAction::Monitor),
+            _ => None,
+        }
+    }
+}
+
+fn is_host_hotkey(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+        && keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+}
+
+// everything shared between the sim thread and the window thread,
+// the devices themselves are created on the sim thread (they are not `Send`)
+#[derive(Clone)]
+struct Frontend {
+    vga_fb: Arc<Mutex<VgaFrame>>,
+    kb_am_fifo: Fifobounded<KeyEvent>,
+    kb_sdl_fifo: Fifobounded<u32>,
+    mouse_fifo: Fifobounded<MouseState>,
+    ps2_mouse_fifo: Fifobounded<MouseMotion>,
+    audio: Arc<Mutex<AudioStream>>,
+    // set by the window thread, the sim thread stops at the next run_once
+    quit: Arc<AtomicBool>,
+    // set by the window thread, the sim thread waits until it is cleared
+    pause: Arc<AtomicBool>,
+}
+
+fn main() {
+    simple_logger::SimpleLogger::new()
+        .with_level(LevelFilter::Debug)
+        .init()
+        .unwrap();
+    let args = Args::parse();
+
+    if args.img.is_none() && args.xipflash.is_none() {
+        panic!("Please specify the img or xipflash\n");
+    }
+
+    let config = create_config(&args);
+    let mut vga_fb = VgaFrame::new(VgaMode::new(config.fb_size()));
+    vga_fb.set_text_mode(args.vga_text);
+    // the window opens in the mode of the boot
+    vga_fb.take_mode_change();
+    let mode = vga_fb.frame_mode();
+    let frontend = Frontend {
+        vga_fb: Arc::new(Mutex::new(vga_fb)),
+        kb_am_fifo: fifo_bounded_new(16),
+        kb_sdl_fifo: fifo_bounded_new(16),
+        mouse_fifo: fifo_bounded_new(16),
+        ps2_mouse_fifo: fifo_bounded_new(64),
+        audio: Arc::new(Mutex::new(AudioStream::new())),
+        quit: Arc::new(AtomicBool::new(false)),
+        pause: Arc::new(AtomicBool::new(false)),
+    };
+    let (vga_sync_tx, vga_sync_rx) = mpsc::sync_channel(1);
+    let display = Display::new(&args, mode);
+
+    // SDL must stay on the main thread (required by macOS),
+    // the harts are simulated on a worker thread.
+    // `vga_sync_tx` is owned by the sim thread, the window loop
+    // ends when it is dropped.
+    let sim_frontend = frontend.clone();
+    let sim_thread = thread::spawn(move || {
+        let (mut sim, uart_tx_fifo) = create_sim(&args, config, &sim_frontend, vga_sync_tx);
+        run_sim(&mut sim, &uart_tx_fifo, &sim_frontend);
+    });
+
+    run_window(&frontend, display, vga_sync_rx);
+    sim_thread.join().unwrap();
+}
+
+fn create_config(args: &Args) -> Config {
+    let mut config = Config::new();
+    config.set_tlb_size(256);
+    config.set_icache_size(4096);
+    config.set_decode_cache_size(4096);
+    config.set_mmu_type("bare");
+    config.set_isa("rv64im");
+
+    let fb: Vec<u32> = args
+        .fb
+        .split('x')
+        .map(|x| x.parse().unwrap_or_else(|_| panic!("fb is not WxH[xBPP]")))
+        .collect();
+    match fb[..] {
+        [w, h] => config.set_fb_size(w, h, 32),
+        [w, h, bpp] => config.set_fb_size(w, h, bpp),
+        _ => panic!("fb is not WxH[xBPP]"),
+    }
+    if let Some(budget) = args.update_budget {
+        config.set_update_budget(budget);
+    }
+    if let Err(e) = config.validate() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+    config
+}
+
+fn create_sim(
+    args: &Args,
+    config: Config,
+    frontend: &Frontend,
+    vga_sync_tx: mpsc::SyncSender<()>,
+) -> (RVsim, FifoUnbounded<u8>) {
+    let bus_u = rc_refcell_new(Bus::new());
+
+    // device dram len:0X08000000
+    let mem = DeviceMemory::new(128 * 1024 * 1024);
+
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: MEM_BASE,
+            len: mem.size() as u64,
+            instance: Box::new(mem),
+            name: "RAM",
+        })
+        .unwrap();
+
+    // device flash len:0X08000000
+    let mut flash = DeviceMemory::new(128 * 1024 * 1024);
+    if let Some(xipflash) = args.xipflash.as_ref() {
+        let flash_data = fs::read(xipflash).unwrap();
+        flash.load_binary(&flash_data).unwrap();
+    }
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: 0x3000_0000,
+            len: flash.size() as u64,
+            instance: Box::new(flash),
+            name: "XIPFLASH",
+        })
+        .unwrap();
+
+    let uart_tx_fifo = fifo_unbounded_new::<u8>();
+    let uart_rx_fifo = fifo_unbounded_new::<u8>();
+
+    let rx_fifo = uart_rx_fifo.clone();
+    thread::spawn(move || loop {
+        let mut buf = [0; 1];
+        match stdin().read(&mut buf) {
+            Ok(1) => rx_fifo.push(buf[0]),
+            // stdin is closed
+            _ => break,
+        }
+    });
+
+    // device am_uart
+    let uart = DeviceUart::new(uart_tx_fifo.clone());
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: SERIAL_PORT,
+            len: 1,
+            instance: Box::new(uart),
+            name: "AM_UART",
+        })
+        .unwrap();
+
+    // device 16650_uart
+    let device_16650_uart = Device16550aUART::new(uart_tx_fifo.clone(), uart_rx_fifo);
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: 0x1000_0000,
+            len: 0x1000,
+            instance: Box::new(device_16650_uart),
+            name: "16550a_uart",
+        })
+        .unwrap();
+
+    // device rtc
+    let rtc = DeviceRTC::new();
+    let device_name = rtc.get_name();
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: RTC_ADDR,
+            len: 8,
+            instance: Box::new(rtc),
+            name: device_name,
+        })
+        .unwrap();
+
+    // device vgactl
+    let vgactl = DeviceVGACTL::new(vga_sync_tx, frontend.vga_fb.clone());
+    let device_name = vgactl.get_name();
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: VGACTL_ADDR,
+            len: VGACTL_SIZE,
+            instance: Box::new(vgactl),
+            name: device_name,
+        })
+        .unwrap();
+
+    // device vga
+    let vga = DeviceVGA::new(frontend.vga_fb.clone());
+    let device_name = vga.get_name();
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: FB_ADDR,
+            len: vga.get_size() as u64,
+            instance: Box::new(vga),
+            name: device_name,
+        })
+        .unwrap();
+
+    // device vga text
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: VGA_TEXT_ADDR,
+            len: VGA_TEXT_SIZE as u64,
+            instance: Box::new(DeviceVgaText::new(frontend.vga_fb.clone())),
+            name: "VGA_TEXT",
+        })
+        .unwrap();
+
+    // device am_kb
+    let device_kb = DeviceKB::new(frontend.kb_am_fifo.clone(), frontend.kb_sdl_fifo.clone());
+    let device_name = device_kb.get_name();
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: KBD_ADDR,
+            len: 8,
+            instance: Box::new(device_kb),
+            name: device_name,
+        })
+        .unwrap();
+
+    // device am_mouse
+    let device_mouse = DeviceMouse::new(frontend.mouse_fifo.clone());
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: MOUSE_ADDR,
+            len: 16,
+            instance: Box::new(device_mouse),
+            name: "AM_Mouse",
+        })
+        .unwrap();
+
+    // device ps2 mouse
+    if let Some(addr) = args.ps2_mouse.as_ref() {
+        let start = u64::from_str_radix(addr.trim_start_matches("0x"), 16)
+            .unwrap_or_else(|_| panic!("ps2_mouse is not a valid hex number"));
+        bus_u
+            .borrow_mut()
+            .add_device_with_irq(
+                DeviceType {
+                    start,
+                    len: PS2_MOUSE_SIZE,
+                    instance: Box::new(DevicePs2Mouse::new(frontend.ps2_mouse_fifo.clone())),
+                    name: "PS2_Mouse",
+                },
+                PS2_MOUSE_IRQ,
+                IrqTrigger::Level,
+            )
+            .unwrap();
+    }
+
+    // device am_disk, PRESENT is 0 without --disk
+    let disk = match args.disk.as_ref() {
+        Some(path) => {
+            DeviceDisk::open(path).unwrap_or_else(|e| panic!("can not open disk {path}:{e}"))
+        }
+        None => DeviceDisk::new(),
+    };
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: DISK_ADDR,
+            len: DISK_SIZE,
+            instance: Box::new(disk),
+            name: "AM_DISK",
+        })
+        .unwrap();
+
+    // device am_audio, the stream buffer is played by the window thread
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: AUDIO_ADDR,
+            len: AUDIO_CTL_SIZE,
+            instance: Box::new(DeviceAudio::new(frontend.audio.clone())),
+            name: "AM_AUDIO",
+        })
+        .unwrap();
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: AUDIO_SBUF_ADDR,
+            len: AUDIO_SBUF_SIZE as u64,
+            instance: Box::new(DeviceAudioSbuf::new(frontend.audio.clone())),
+            name: "AM_AUDIO_SBUF",
+        })
+        .unwrap();
+
+    let boot_pc = args.boot_pc.as_ref().map_or(0x8000_0000, |x| {
+        let cleaned = x.trim_start_matches(['0', 'x', 'X']);
+        u64::from_str_radix(cleaned, 16)
+            .unwrap_or_else(|_| panic!("boot_pc is not a valid hex number"))
+    });
+
+    info!("{0}", bus_u.borrow_mut());
+    info!("boot_pc:0x{:x}", boot_pc);
+
+    let config = Rc::new(config);
+
+    let hart_num: usize = args.num_harts.unwrap_or(1);
+    let mut hart_vec = Vec::new();
+    // create hart according to the number of harts
+    for hart_id in 0..hart_num {
+        let hart = rc_refcell_new(
+            CpuCoreBuild::new(bus_u.clone(), config.clone())
+                .with_boot_pc(boot_pc)
+                .with_hart_id(hart_id)
+                .with_smode(false)
+                .build(),
+        );
+        hart_vec.push(hart);
+    }
+
+    let mut sim = RVsim::new(hart_vec, 23456).unwrap();
+    if let Some(cmd) = args.on_pass.clone() {
+        sim.add_exit_hook(move |exit| {
+            if exit == GuestExit::Pass {
+                run_host_command(&cmd, exit);
+            }
+        });
+    }
+    if let Some(cmd) = args.on_fail.clone() {
+        sim.add_exit_hook(move |exit| {
+            if exit != GuestExit::Pass {
+                run_host_command(&cmd, exit);
+            }
+        });
+    }
+    if let Some(ram_img) = args.img.as_ref() {
+        if let Err(e) = sim.load_image(ram_img) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+    (sim, uart_tx_fifo)
+}
+
+fn run_sim(sim: &mut RVsim, uart_tx_fifo: &FifoUnbounded<u8>, frontend: &Frontend) {
+    sim.prepare_to_run();
+    let budget = sim.config().update_budget();
+    while !sim.is_finish() && !frontend.quit.load(Ordering::Relaxed) {
+        if frontend.pause.load(Ordering::Relaxed) {
+            thread::sleep(EVENT_INTERVAL);
+            continue;
+        }
+        sim.run_once(budget);
+
+        if !uart_tx_fifo.is_empty() {
+            while let Some(c) = uart_tx_fifo.pop() {
+                print!("{}", c as char);
+            }
+            io::stdout().flush().unwrap();
+        }
+    }
+    sim.show_perf();
+    sim.run_exit_hooks();
+}
+
+fn send_key_event(tx: &Fifobounded<KeyEvent>, val: Scancode, keydown: bool) {
+    tx.force_push(KeyEvent {
+        code: val.into(),
+        pressed: keydown,
+    });
+}
+
+fn update_title(canvas: &mut WindowCanvas, grabbed: bool, paused: bool) {
+    let mut title = String::from("rv64emu");
+    if grabbed {
+        title.push_str(" [input grabbed, Ctrl+Alt+G releases]");
+    }
+    if paused {
+        title.push_str(" [paused]");
+    }
+    canvas.window_mut().set_title(&title).unwrap();
+}
+
+// save the current frame as "rv64emu-<unix time in ms>.bmp"
+fn save_screenshot(frontend: &Frontend) -> Result<String, String> {
+    let fb = frontend.vga_fb.lock().unwrap();
+    let mode = fb.frame_mode();
+    let mut pixels = if fb.is_text_mode() {
+        fb.text.render()
+    } else {
+        fb.pixels[..mode.size()].to_vec()
+    };
+    drop(fb);
+    let surface = Surface::from_data(
+        &mut pixels,
+        mode.width,
+        mode.height,
+        mode.pitch() as u32,
+        pixel_format(mode),
+    )?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis();
+    let file_name = format!("rv64emu-{stamp}.bmp");
+    surface.save_bmp(&file_name)?;
+    Ok(file_name)
+}
+
+fn pixel_format(mode: VgaMode) -> PixelFormatEnum {
+    match mode.bpp {
+        16 => PixelFormatEnum::RGB565,
+        _ => PixelFormatEnum::ARGB8888,
+    }
+}
+
+// an SDL queue opened at the init of the guest, fed from the stream buffer
+fn feed_audio(
+    frontend: &Frontend,
+    audio: &sdl2::AudioSubsystem,
+    queue: &mut Option<AudioQueue<i16>>,
+) {
+    let mut stream = frontend.audio.lock().unwrap();
+    if let Some(spec) = stream.take_spec() {
+        let desired = AudioSpecDesired {
+            freq: Some(spec.freq as i32),
+            channels: Some(spec.channels),
+            samples: Some(spec.samples),
+        };
+        *queue = match audio.open_queue::<i16, _>(None, &desired) {
+            Ok(x) => {
+                x.resume();
+                Some(x)
+            }
+            Err(e) => {
+                warn!("can not open audio {spec:?}: {e}");
+                None
+            }
+        };
+    }
+    let Some(queue) = queue.as_ref() else {
+        return;
+    };
+    let room = AUDIO_QUEUE_BYTES.saturating_sub(queue.size()) as usize;
+    let bytes = stream.pop(room & !1);
+    drop(stream);
+    let samples: Vec<i16> = bytes
+        .chunks_exact(2)
+        .map(|x| i16::from_le_bytes([x[0], x[1]]))
+        .collect();
+    if let Err(e) = queue.queue_audio(&samples) {
+        warn!("audio queue: {e}");
+    }
+}
+
+// the window, keyboard and mouse, on the main thread
+fn run_window(frontend: &Frontend, mut display: Display, vga_sync_rx: Receiver<()>) {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let audio_subsystem = sdl_context.audio().unwrap();
+    let mut audio_queue = None;
+    let mut event_pump: sdl2::EventPump = sdl_context.event_pump().expect("fail to get event_pump");
+
+    // nearest pixel sampling, low resolution games stay sharp
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+    // a grab also takes the keyboard, the host shortcuts (Alt+Tab...) go to the guest
+    sdl2::hint::set("SDL_GRAB_KEYBOARD", "1");
+    let (width, height) = display.window_size();
+    let window = video_subsystem
+        .window("rv64emu", width, height)
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|e| e.to_string())
+        .unwrap();
+
+    let mut canvas = window.into_canvas().software().build().expect("canvas err");
+    display.apply(&mut canvas);
+    let texture_creator = canvas.texture_creator();
+    let create_texture = |mode: VgaMode| {
+        texture_creator
+            .create_texture_target(pixel_format(mode), mode.width, mode.height)
+            .map_err(|e| e.to_string())
+            .unwrap()
+    };
+    let mut texture = create_texture(frontend.vga_fb.lock().unwrap().frame_mode());
+    let mut viewport = display.viewport(canvas.output_size().unwrap());
+    let mut grabbed = false;
+    // the keys pressed as a host hotkey, their release is not sent to the guest
+    let mut host_keys: HashSet<Scancode> = HashSet::new();
+    // the buttons of the relative moves, for the PS/2 mouse
+    let mut buttons = MouseButtons::default();
+
+    info!("start sdl event loop");
+    loop {
+        let mouse_state = event_pump.mouse_state();
+        let (x, y) = display.to_frame(viewport, mouse_state.x(), mouse_state.y());
+        frontend
+            .mouse_fifo
+            .force_push(MouseState::from_sdl(&mouse_state, x, y));
+
+        // the window is redrawn after a layout change, even if the guest is idle
+        let mut redraw = false;
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => frontend.quit.store(true, Ordering::Relaxed),
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } => redraw = true,
+                Event::KeyDown {
+                    scancode: Some(val),
+                    keycode,
+                    keymod,
+                    repeat,
+                    ..
+                } if is_host_hotkey(keymod) => {
+                    host_keys.insert(val);
+                    let action = match keycode.and_then(HostAction::from_key) {
+                        Some(action) if !repeat => action,
+                        _ => continue,
+                    };
+                    let paused = frontend.pause.load(Ordering::Relaxed);
+                    match action {
+                        HostAction::Grab => {
+                            grabbed = !grabbed;
+                            canvas.window_mut().set_grab(grabbed);
+                        }
+                        HostAction::Fullscreen => display.fullscreen = !display.fullscreen,
+                        HostAction::ScaleUp => display.scale = (display.scale + 1).min(MAX_SCALE),
+                        HostAction::ScaleDown => display.scale = (display.scale - 1).max(1),
+                        HostAction::Pause => frontend.pause.store(!paused, Ordering::Relaxed),
+                        HostAction::Screenshot => match save_screenshot(frontend) {
+                            Ok(file_name) => info!("screenshot saved to {file_name}"),
+                            Err(e) => warn!("screenshot failed: {e}"),
+                        },
+                        HostAction::Reset | HostAction::Monitor => {
+                            warn!("host action {action:?} is not supported yet")
+                        }
+                    }
+                    if matches!(
+                        action,
+                        HostAction::Fullscreen | HostAction::ScaleUp | HostAction::ScaleDown
+                    ) {
+                        display.apply(&mut canvas);
+                        redraw = true;
+                    }
+                    update_title(&mut canvas, grabbed, frontend.pause.load(Ordering::Relaxed));
+                }
+                Event::KeyUp {
+                    scancode: Some(val),
+                    ..
+                } if host_keys.remove(&val) => (),
+                Event::KeyUp {
+                    scancode: Some(val),
+                    ..
+                } => send_key_event(&frontend.kb_am_fifo, val, false),
+                Event::KeyDown {
+                    scancode: Some(val),
+                    keycode: Some(sdl_key_code),
+                    ..
+                } => {
+                    send_key_event(&frontend.kb_am_fifo, val, true);
+                    frontend.kb_sdl_fifo.force_push(sdl_key_code as i32 as u32);
+                }
+                Event::MouseMotion { xrel, yrel, .. } => {
+                    frontend.ps2_mouse_fifo.force_push(MouseMotion {
+                        buttons,
+                        dx: xrel,
+                        dy: yrel,
+                        wheel: 0,
+                    });
+                }
+                Event::MouseWheel { y, .. } => {
+                    frontend.ps2_mouse_fifo.force_push(MouseMotion {
+                        buttons,
+                        wheel: y,
+                        ..Default::default()
+                    });
+                }
+                Event::MouseButtonDown { mouse_btn, .. }
+                | Event::MouseButtonUp { mouse_btn, .. } => {
+                    let Some(button) = MouseButton::from_sdl(mouse_btn) else {
+                        continue;
+                    };
+                    buttons.set(button, matches!(event, Event::MouseButtonDown { .. }));
+                    frontend.ps2_mouse_fifo.force_push(MouseMotion {
+                        buttons,
+                        ..Default::default()
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        feed_audio(frontend, &audio_subsystem, &mut audio_queue);
+
+        // sleep until the guest syncs a frame, or it is time to poll the events again
+        let synced = match vga_sync_rx.recv_timeout(EVENT_INTERVAL) {
+            Ok(()) => true,
+            Err(RecvTimeoutError::Timeout) => false,
+            // the sim thread is done
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let mut fb = frontend.vga_fb.lock().unwrap();
+        // the guest switched the mode, the window follows at the same scale
+        if let Some(mode) = fb.take_mode_change() {
+            info!("vga mode {}x{}x{}", mode.width, mode.height, mode.bpp);
+            texture = create_texture(mode);
+            display.frame = (mode.width, mode.height);
+            display.apply(&mut canvas);
+            redraw = true;
+        }
+        if fb.is_text_mode() {
+            // the text mode is drawn when a cell changes, legacy programs never sync
+            if let Some(pixels) = fb.text.take_frame() {
+                texture
+                    .update(None, &pixels, TEXT_FRAME_MODE.pitch())
+                    .expect("update texture failed");
+                redraw = true;
+            }
+        } else if synced {
+            // only the lines written since the last present are uploaded
+            let mode = fb.mode();
+            let pitch = mode.pitch();
+            for lines in fb.take_dirty_lines() {
+                let rect = Rect::new(0, lines.start as i32, mode.width, lines.len() as u32);
+                let pixels = &fb.pixels[lines.start * pitch..lines.end * pitch];
+                texture
+                    .update(rect, pixels, pitch)
+                    .expect("update texture failed");
+            }
+            redraw = true;
+        }
+        drop(fb);
+
+        if redraw {
+            viewport = display.viewport(canvas.output_size().unwrap());
+            canvas.set_draw_color(sdl2::pixels::Color::BLACK);
+            canvas.clear();
+            canvas.copy(&texture, None, viewport).unwrap();
+            canvas.present();
+        }
+    }
+}
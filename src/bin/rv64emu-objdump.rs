@@ -0,0 +1,144 @@
+// A tiny objdump built on top of the rv64emu decoder.
+// usage: rv64emu-objdump [--isa rv64imac] [--base HEX] FILE
+// ELF files are disassembled section by section (SHF_EXECINSTR only),
+// other files are treated as flat binaries loaded at --base (default 0x80000000).
+// The output follows `objdump -d -M no-aliases`.
+
+use std::{collections::BTreeMap, env, fs, process, rc::Rc};
+
+use elf::{abi::SHF_EXECINSTR, endian::AnyEndian, ElfBytes};
+use rv64emu::{
+    config::Config,
+    rv64core::{
+        inst::{inst_base::is_compressed_instruction, inst_disasm::disassemble},
+        inst_decode::InstDecode,
+    },
+};
+
+struct Args {
+    isa: String,
+    base: u64,
+    file: String,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: rv64emu-objdump [--isa rv64imac] [--base HEX] FILE");
+    process::exit(1);
+}
+
+fn parse_args() -> Args {
+    let mut isa = "rv64imac".to_string();
+    let mut base = 0x8000_0000;
+    let mut file = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--isa" => isa = args.next().unwrap_or_else(|| usage()),
+            "--base" => {
+                let x = args.next().unwrap_or_else(|| usage());
+                base =
+                    u64::from_str_radix(x.trim_start_matches("0x"), 16).unwrap_or_else(|_| usage());
+            }
+            "-h" | "--help" => usage(),
+            _ => file = Some(arg),
+        }
+    }
+
+    Args {
+        isa,
+        base,
+        file: file.unwrap_or_else(|| usage()),
+    }
+}
+
+fn dump(decoder: &mut InstDecode, data: &[u8], base: u64, symbols: &BTreeMap<u64, String>) {
+    let mut offset = 0;
+    while offset + 2 <= data.len() {
+        let pc = base + offset as u64;
+        if let Some(name) = symbols.get(&pc) {
+            println!("\n{:016x} <{}>:", pc, name);
+        }
+
+        let half = u16::from_le_bytes([data[offset], data[offset + 1]]) as u32;
+        let (word, len) = if is_compressed_instruction(half) {
+            (half, 2)
+        } else if offset + 4 <= data.len() {
+            let high = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as u32;
+            (half | (high << 16), 4)
+        } else {
+            break;
+        };
+
+        let text = match decoder.fast_path(word) {
+            Some(inst) => disassemble(inst, word, pc),
+            None if len == 2 => format!(".2byte\t0x{:x}", word),
+            None => format!(".4byte\t0x{:x}", word),
+        };
+        let raw = if len == 2 {
+            format!("{:04x}", word)
+        } else {
+            format!("{:08x}", word)
+        };
+        println!("{:8x}:\t{:<20}\t{}", pc, raw, text);
+
+        offset += len;
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let data = fs::read(&args.file).unwrap_or_else(|e| {
+        eprintln!("can not read {}:{}", args.file, e);
+        process::exit(1);
+    });
+
+    let mut config = Config::new();
+    config.set_isa(&args.isa);
+    let mut decoder = InstDecode::new(Rc::new(config));
+
+    let elf_data = match ElfBytes::<AnyEndian>::minimal_parse(&data) {
+        Ok(elf_data) => {
+            println!("\n{}:     file format elf64-littleriscv\n", args.file);
+            elf_data
+        }
+        Err(_) => {
+            println!("\n{}:     file format binary\n", args.file);
+            println!("\nDisassembly of section .data:");
+            dump(&mut decoder, &data, args.base, &BTreeMap::new());
+            return;
+        }
+    };
+
+    let mut symbols = BTreeMap::new();
+    if let Ok(Some((symtab, strtab))) = elf_data.symbol_table() {
+        for sym in symtab.iter().filter(|x| x.st_name != 0) {
+            if let Ok(name) = strtab.get(sym.st_name as usize) {
+                symbols.entry(sym.st_value).or_insert(name.to_string());
+            }
+        }
+    }
+
+    let (shdrs, strtab) = elf_data
+        .section_headers_with_strtab()
+        .expect("section header parse error");
+    let (shdrs, strtab) = match (shdrs, strtab) {
+        (Some(shdrs), Some(strtab)) => (shdrs, strtab),
+        _ => {
+            eprintln!("no section headers found");
+            process::exit(1);
+        }
+    };
+
+    for shdr in shdrs
+        .iter()
+        .filter(|x| x.sh_flags & SHF_EXECINSTR as u64 != 0)
+    {
+        let name = strtab.get(shdr.sh_name as usize).unwrap_or("?");
+        let (section_data, _) = elf_data
+            .section_data(&shdr)
+            .expect("section data parse error");
+        println!("\nDisassembly of section {}:", name);
+        dump(&mut decoder, section_data, shdr.sh_addr, &symbols);
+    }
+}
@@ -0,0 +1,140 @@
+use alloc::{format, string::ToString};
+
+use crate::error::{RvEmuError, RvEmuResult};
+
+// the RISC-V Linux Image header (Documentation/arch/riscv/boot-image-header.rst),
+// little endian: text_offset at 8, image_size at 16, the magics at 48 and 56
+const IMAGE_HEADER_SIZE: usize = 64;
+const IMAGE_MAGIC: &[u8; 8] = b"RISCV\0\0\0";
+const IMAGE_MAGIC2: &[u8; 4] = b"RSC\x05";
+
+// the legacy u-boot uImage header, big endian. the data follows it
+const UIMAGE_HEADER_SIZE: usize = 64;
+const UIMAGE_MAGIC: u32 = 0x2705_1956;
+const UIMAGE_ARCH_RISCV: u8 = 26;
+const UIMAGE_TYPE_KERNEL: u8 = 2;
+const UIMAGE_COMP_NONE: u8 = 0;
+
+// a kernel which says where it goes, see `boot_image`
+#[derive(Debug, PartialEq, Eq)]
+pub struct BootImage<'a> {
+    pub load: u64,
+    pub entry: u64,
+    // what is copied to `load`
+    pub data: &'a [u8],
+    // the memory it takes from `load` on, with its bss
+    pub size: u64,
+}
+
+fn le64(slice: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(slice[offset..offset + 8].try_into().unwrap())
+}
+
+fn be32(slice: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(slice[offset..offset + 4].try_into().unwrap())
+}
+
+// the crc of the uImage data, zlib crc32
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for x in data {
+        crc ^= *x as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+// a Linux Image goes `text_offset` into the ram at `ram_base` and is
+// entered at its first byte, a uImage has its load and entry address.
+// None for the other files (raw binaries)
+pub fn boot_image(slice: &[u8], ram_base: u64) -> Option<RvEmuResult<BootImage<'_>>> {
+    if slice.len() >= IMAGE_HEADER_SIZE
+        && (&slice[56..60] == IMAGE_MAGIC2 || &slice[48..56] == IMAGE_MAGIC)
+    {
+        let text_offset = le64(slice, 8);
+        let Some(load) = ram_base.checked_add(text_offset) else {
+            return Some(Err(RvEmuError::ImageLoad(format!(
+                "Image: text_offset {text_offset:#x} is past the address space"
+            ))));
+        };
+        let size = le64(slice, 16).max(slice.len() as u64);
+        return Some(Ok(BootImage {
+            load,
+            entry: load,
+            data: slice,
+            size,
+        }));
+    }
+    if slice.len() >= UIMAGE_HEADER_SIZE && be32(slice, 0) == UIMAGE_MAGIC {
+        return Some(uimage(slice));
+    }
+    None
+}
+
+fn uimage(slice: &[u8]) -> RvEmuResult<BootImage<'_>> {
+    let bad = |why: &str| Err(RvEmuError::ImageLoad(format!("uImage: {why}")));
+    let (arch, kind, comp) = (slice[29], slice[30], slice[31]);
+    if arch != UIMAGE_ARCH_RISCV {
+        return bad(&format!("arch {arch} is not riscv"));
+    }
+    if kind != UIMAGE_TYPE_KERNEL {
+        return bad(&format!("type {kind} is not a kernel"));
+    }
+    if comp != UIMAGE_COMP_NONE {
+        return bad("compressed, unpack it with dumpimage");
+    }
+    let len = be32(slice, 12) as usize;
+    let Some(data) = slice[UIMAGE_HEADER_SIZE..].get(..len) else {
+        return bad(&format!("the data of {len:#x} bytes is cut"));
+    };
+    if crc32(data) != be32(slice, 24) {
+        return Err(RvEmuError::ImageLoad("uImage: bad data crc".to_string()));
+    }
+    Ok(BootImage {
+        load: be32(slice, 16) as u64,
+        entry: be32(slice, 20) as u64,
+        data,
+        size: len as u64,
+    })
+}
+
+#[cfg(test)]
+mod test_boot_image {
+    use alloc::vec::Vec;
+
+    use super::{boot_image, crc32};
+
+    #[test]
+    fn boot_image_test() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert!(boot_image(&[0; 128], 0x8000_0000).is_none());
+
+        let mut image = [0_u8; 128];
+        image[8..16].copy_from_slice(&0x20_0000_u64.to_le_bytes());
+        image[16..24].copy_from_slice(&0x1000_u64.to_le_bytes());
+        image[56..60].copy_from_slice(b"RSC\x05");
+        let kernel = boot_image(&image, 0x8000_0000).unwrap().unwrap();
+        assert_eq!((kernel.load, kernel.entry), (0x8020_0000, 0x8020_0000));
+        assert_eq!((kernel.data.len(), kernel.size), (128, 0x1000));
+        image[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(boot_image(&image, 0x8000_0000).unwrap().is_err());
+
+        let data = [1_u8, 2, 3, 4];
+        let mut uimage: Vec<u8> = [0x2705_1956_u32, 0, 0, 4, 0x8040_0000, 0x8040_0002]
+            .iter()
+            .flat_map(|x| x.to_be_bytes())
+            .collect();
+        uimage.extend(crc32(&data).to_be_bytes());
+        uimage.extend([5, 26, 2, 0]);
+        uimage.resize(64, 0);
+        uimage.extend(data);
+        let kernel = boot_image(&uimage, 0x8000_0000).unwrap().unwrap();
+        assert_eq!((kernel.load, kernel.entry), (0x8040_0000, 0x8040_0002));
+        assert_eq!(kernel.data, data);
+        // gzip
+        uimage[31] = 1;
+        assert!(boot_image(&uimage, 0x8000_0000).unwrap().is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use alloc::{
+    format,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{
+    config::{Config, BASE_EXT, IMPLMENTED_EXT, IMPLMENTED_ISA},
+    manifest::json_str,
+    rv64core::{csr_regs::CsrRegs, inst::inst_base::csr_name},
+};
+
+// what this build supports: the extensions, the devices, the config defaults
+// and the csrs of every machine profile, dumped as json for CI matrices and
+// bug reports
+pub struct Capabilities {
+    profiles: Vec<(String, Vec<(&'static str, String)>)>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Capabilities {
+    // the privilege profiles of the core, the frontends add their own machines
+    pub fn new() -> Self {
+        let mut caps = Capabilities {
+            profiles: Vec::new(),
+        };
+        let profile = |isa: &str, mmu: &str, s_mode: bool, u_mode: bool| {
+            let mut config = Config::new();
+            config.set_isa(isa);
+            config.set_mmu_type(mmu);
+            if s_mode {
+                config.set_s_mode();
+            }
+            if u_mode {
+                config.set_u_mode();
+            }
+            config
+        };
+        caps.add_profile("rv64e", &profile("rv64emc", "bare", false, false));
+        caps.add_profile("rv32", &profile("rv32imac", "bare", false, true));
+        caps.add_profile("machine", &profile("rv64imac", "bare", false, false));
+        caps.add_profile("user", &profile("rv64imac", "bare", false, true));
+        caps.add_profile("supervisor", &profile("rv64gc", "sv39", true, true));
+        caps.add_profile("hypervisor", &profile("rv64gch", "sv39", true, true));
+        caps
+    }
+
+    pub fn add_profile(&mut self, name: &str, config: &Config) {
+        self.profiles.push((name.to_string(), config.to_pairs()));
+    }
+
+    // the csrs of a profile, time is added by the CLINT
+    fn csrs(pairs: &[(&'static str, String)]) -> Vec<String> {
+        let config = Config::from_pairs(pairs.iter().map(|(k, v)| (*k, v.as_str())));
+        let mut csr_regs = CsrRegs::new(0, Rc::new(config));
+        csr_regs.add_mtime(Rc::new(0.into()));
+        let mut addrs: Vec<u64> = csr_regs.csr_map.keys().copied().collect();
+        addrs.sort();
+        addrs
+            .iter()
+            .map(|x| csr_name(*x as u16).map_or(format!("{x:#x}"), |name| name.to_string()))
+            .collect()
+    }
+
+    pub fn features() -> Vec<&'static str> {
+        let features = [
+            ("std", cfg!(feature = "std")),
+            ("support_am", cfg!(feature = "support_am")),
+            ("device_sdl2", cfg!(feature = "device_sdl2")),
+            ("rv_debug_trace", cfg!(feature = "rv_debug_trace")),
+        ];
+        features
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    pub fn devices() -> Vec<&'static str> {
+        let mut devices = vec![
+            "memory",
+            "sifive_clint",
+            "aclint_sswi",
+            "sifive_plic",
+            "riscv_imsic",
+            "riscv_aplic",
+            "sifive_uart",
+            "16550a_uart",
+            "am_uart",
+            "debug_module",
+            "dma",
+            "sifive_gpio",
+            "ps2_mouse",
+            "i2c_ocores",
+            "watchdog",
+            "virtio_net",
+            "boot_rom",
+            "rom",
+            "spi_flash",
+        ];
+        if cfg!(feature = "std") {
+            devices.extend(["am_rtc", "am_disk", "host_pipe", "net_user", "net_pcap"]);
+        }
+        if cfg!(all(feature = "std", target_os = "linux")) {
+            devices.push("net_tap");
+        }
+        if cfg!(all(feature = "std", unix)) {
+            devices.push("host_shm");
+        }
+        if cfg!(feature = "support_am") {
+            devices.extend(["am_kb", "am_mouse"]);
+        }
+        if cfg!(all(feature = "device_sdl2", feature = "std")) {
+            devices.extend(["am_vga", "am_vgactl", "am_audio", "vga_text"]);
+        }
+        devices
+    }
+
+    pub fn to_json(&self) -> String {
+        let strs = |items: &[&str]| {
+            let items: Vec<String> = items.iter().map(|x| json_str(x)).collect();
+            format!("[{}]", items.join(", "))
+        };
+        let object = |pairs: &[(&'static str, String)], indent: &str| {
+            let items: Vec<String> = pairs
+                .iter()
+                .map(|(k, v)| format!("{}: {}", json_str(k), json_str(v)))
+                .collect();
+            format!(
+                "{{\n{indent}  {}\n{indent}}}",
+                items.join(&format!(",\n{indent}  "))
+            )
+        };
+        let isa: Vec<String> = IMPLMENTED_ISA
+            .iter()
+            .map(|x| (*x as char).to_string())
+            .collect();
+        let isa: Vec<&str> = isa.iter().map(|x| x.as_str()).collect();
+        let extensions: Vec<&str> = BASE_EXT
+            .iter()
+            .chain(IMPLMENTED_EXT.iter())
+            .copied()
+            .collect();
+        let profiles: Vec<String> = self
+            .profiles
+            .iter()
+            .map(|(name, pairs)| {
+                let csrs = Self::csrs(pairs);
+                let csrs: Vec<&str> = csrs.iter().map(|x| x.as_str()).collect();
+                format!(
+                    "{{\n      \"name\": {},\n      \"config\": {},\n      \"csrs\": {}\n    }}",
+                    json_str(name),
+                    object(pairs, "      "),
+                    strs(&csrs)
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"version\": {},\n  \"features\": {},\n  \"isa\": {},\n  \"extensions\": {},\n  \"mmu_types\": {},\n  \"compliance\": {},\n  \"tlb_policies\": {},\n  \"devices\": {},\n  \"config_defaults\": {},\n  \"profiles\": [\n    {}\n  ]\n}}\n",
+            json_str(env!("CARGO_PKG_VERSION")),
+            strs(&Self::features()),
+            strs(&isa),
+            strs(&extensions),
+            strs(&["bare", "sv32", "sv39", "sv48", "sv57"]),
+            strs(&["normal", "strict", "relaxed"]),
+            strs(&["lru", "random", "fifo"]),
+            strs(&Self::devices()),
+            object(&Config::new().to_pairs(), "  "),
+            profiles.join(",\n    ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod test_capabilities {
+    use super::Capabilities;
+    use crate::config::Config;
+
+    #[test]
+    fn capabilities_test() {
+        let mut caps = Capabilities::new();
+        let mut config = Config::new();
+        config.set_isa("rv64imac_sstc");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        caps.add_profile("linux", &config);
+        let json = caps.to_json();
+        assert!(json.contains("\"extensions\": [\"zicsr\", \"zifencei\", \"zfh\""));
+        assert!(json.contains("\"name\": \"linux\""));
+        assert!(json.contains("\"isa\": \"rv64imac_sstc\""));
+        // the csrs follow the profile
+        let linux = &json[json.find("\"name\": \"linux\"").unwrap()..];
+        assert!(linux.contains("\"stimecmp\""));
+        assert!(linux.contains("\"time\""));
+        assert!(!linux.contains("\"hstatus\""));
+        let hypervisor = &json[json.find("\"name\": \"hypervisor\"").unwrap()..];
+        assert!(hypervisor.contains("\"hstatus\""));
+    }
+}
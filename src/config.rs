@@ -1,9 +1,92 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
 use log::info;
 
-use crate::rv64core::csr_regs_define::StapMode;
+use crate::{
+    error::{RvEmuError, RvEmuResult},
+    rv64core::csr_regs_define::StapMode,
+};
 
-const IMPLMENTED_ISA: [u8; 4] = [b'i', b'm', b'a', b'c'];
+pub const IMPLMENTED_ISA: [u8; 8] = [b'i', b'e', b'm', b'a', b'c', b'f', b'd', b'h'];
+// multi-letter extensions, separated by '_' in the isa string
+pub const IMPLMENTED_EXT: [&str; 22] = [
+    "zfh",
+    "zve64x",
+    "zba",
+    "zbc",
+    "zbs",
+    "zicond",
+    "zicbom",
+    "zicboz",
+    "zawrs",
+    "zacas",
+    "zabha",
+    "zkn",
+    "zks",
+    "zfinx",
+    "zdinx",
+    "zihintpause",
+    "zihintntl",
+    "svnapot",
+    "svpbmt",
+    "sstc",
+    "smstateen",
+    "sscofpmf",
+];
+// the multi-letter extensions that also run on rv32
+const RV32_EXT: [&str; 6] = [
+    "zicsr",
+    "zifencei",
+    "zicond",
+    "zihintpause",
+    "zihintntl",
+    "zawrs",
+];
+// part of I before the 20191213 isa spec, implied unless `legacy_isa` is off
+pub const BASE_EXT: [&str; 2] = ["zicsr", "zifencei"];
+const VLEN_RANGE: core::ops::RangeInclusive<usize> = 64..=65536;
 
+// how close to hardware the corner cases behave. `Normal` is the default.
+// `Strict` traps on the reserved compressed encodings and on the debug csrs
+// outside Debug Mode, `Relaxed` reads 0 from unimplemented csrs and unmapped
+// memory and drops the writes, instead of trapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compliance {
+    Normal,
+    Strict,
+    Relaxed,
+}
+
+impl Compliance {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compliance::Normal => "normal",
+            Compliance::Strict => "strict",
+            Compliance::Relaxed => "relaxed",
+        }
+    }
+}
+
+// the TLB entry replaced when a set is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlbPolicy {
+    Lru,
+    Random,
+    Fifo,
+}
+
+impl TlbPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TlbPolicy::Lru => "lru",
+            TlbPolicy::Random => "random",
+            TlbPolicy::Fifo => "fifo",
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Config {
@@ -11,11 +94,37 @@ pub struct Config {
     dcache_size: Option<usize>,
     decode_cache_size: Option<usize>,
     tlb_size: Option<usize>,
+    // None is fully associative
+    tlb_ways: Option<usize>,
+    tlb_policy: TlbPolicy,
     mmu_type: StapMode,
     s_mode: bool,
     u_mode: bool,
     isa_falgs: u32,
+    isa_ext: Vec<&'static str>,
+    // 32 or 64, from the isa string
+    xlen: usize,
     disable_check_tohost: bool,
+    update_budget: usize,
+    vlen: usize,
+    wrs_yield: bool,
+    pause_yield: bool,
+    // split misaligned loads and stores instead of trapping
+    misaligned: bool,
+    // cycles a trapping WFI may sleep, see `set_wfi_timeout`
+    wfi_timeout: u64,
+    pmp_entries: usize,
+    legacy_isa: bool,
+    compliance: Compliance,
+    // initial state of the entropy source (the seed CSR)
+    seed: u64,
+    // the framebuffer mode the machine starts with, its bytes are the
+    // largest mode the guest can switch to
+    fb_width: u32,
+    fb_height: u32,
+    fb_bpp: u32,
+    // problems found by the setters, reported by `validate`
+    problems: Vec<String>,
 }
 
 impl Default for Config {
@@ -25,11 +134,29 @@ impl Default for Config {
             dcache_size: Default::default(),
             decode_cache_size: Default::default(),
             tlb_size: Default::default(),
+            tlb_ways: Default::default(),
+            tlb_policy: TlbPolicy::Lru,
             mmu_type: StapMode::Bare,
             isa_falgs: 0,
+            isa_ext: Vec::new(),
+            xlen: 64,
             s_mode: false,
             u_mode: false,
             disable_check_tohost: false,
+            update_budget: 5000,
+            vlen: 128,
+            wrs_yield: false,
+            pause_yield: false,
+            misaligned: false,
+            wfi_timeout: 0,
+            pmp_entries: 16,
+            legacy_isa: true,
+            compliance: Compliance::Normal,
+            seed: 0x9e37_79b9_7f4a_7c15,
+            fb_width: 400,
+            fb_height: 300,
+            fb_bpp: 32,
+            problems: Vec::new(),
         }
     }
 }
@@ -58,32 +185,98 @@ impl Config {
     pub fn set_tlb_size(&mut self, size: usize) {
         self.tlb_size = Some(size);
     }
-    // sv39 sv48
+    // the entries of each TLB set, a power of two up to the tlb size
+    pub fn set_tlb_ways(&mut self, ways: usize) {
+        self.tlb_ways = Some(ways);
+    }
+    // lru, random or fifo
+    pub fn set_tlb_policy(&mut self, policy: &str) {
+        match policy.to_lowercase().as_str() {
+            "lru" => self.tlb_policy = TlbPolicy::Lru,
+            "random" => self.tlb_policy = TlbPolicy::Random,
+            "fifo" => self.tlb_policy = TlbPolicy::Fifo,
+            err => self.problems.push(format!(
+                "unknown tlb policy '{err}', expected one of lru, random, fifo"
+            )),
+        }
+    }
+    // sv39 sv48, sv32 on rv32
     pub fn set_mmu_type(&mut self, mmu_type: &str) {
         let mmu_type = mmu_type.to_lowercase();
         match mmu_type.as_str() {
             "bare" => self.mmu_type = StapMode::Bare,
+            "sv32" => self.mmu_type = StapMode::Sv32,
             "sv39" => self.mmu_type = StapMode::Sv39,
             "sv48" => self.mmu_type = StapMode::Sv48,
             "sv57" => self.mmu_type = StapMode::Sv57,
-            err => panic!("mmu type err:{err}"),
+            err => self.problems.push(format!(
+                "unknown mmu type '{err}', expected one of bare, sv32, sv39, sv48, sv57"
+            )),
         }
     }
-    // TODO: parse isa string
+    // rv64imafdc_zicsr_zfh: single-letter extensions first, then the multi-letter ones.
+    // rv32ima selects XLEN 32. unimplemented extensions are reported by `validate`
     pub fn set_isa(&mut self, isa_str: &str) {
         let isa_str = isa_str.to_ascii_lowercase();
         info!("isa_str:{:?}", isa_str);
-        isa_str.strip_prefix("rv64").map_or_else(
-            || panic!("isa err:{isa_str}"),
-            |f| {
-                for i in f.bytes() {
-                    if IMPLMENTED_ISA.contains(&i) {
-                        let idx = i - b'a';
-                        self.isa_falgs |= 1 << idx;
-                    }
+        let f = match (isa_str.strip_prefix("rv64"), isa_str.strip_prefix("rv32")) {
+            (Some(f), _) => f,
+            (_, Some(f)) => {
+                self.xlen = 32;
+                f
+            }
+            _ => {
+                self.problems.push(format!(
+                    "isa string '{isa_str}' must start with rv64 or rv32, e.g. rv64imac"
+                ));
+                return;
+            }
+        };
+        let (single, multi) = f.split_at(f.find(['_', 'z', 's', 'x']).unwrap_or(f.len()));
+
+        let mut g = false;
+        let mut enable = |isa: u8| self.isa_falgs |= 1 << (isa - b'a');
+        for i in single.bytes() {
+            match i {
+                b'g' => {
+                    b"imafd".iter().for_each(|x| enable(*x));
+                    g = true;
                 }
-            },
-        )
+                _ if IMPLMENTED_ISA.contains(&i) => enable(i),
+                _ => self.problems.push(format!(
+                    "unsupported extension '{}' in isa string '{isa_str}'",
+                    i as char
+                )),
+            }
+        }
+        let exts = multi.split('_').filter(|x| !x.is_empty());
+        let base = BASE_EXT.iter().copied().filter(|_| g);
+        for ext in base.chain(exts) {
+            if self.isa_ext.contains(&ext) {
+                continue;
+            }
+            if let Some(x) = IMPLMENTED_EXT
+                .iter()
+                .chain(BASE_EXT.iter())
+                .find(|x| **x == ext)
+            {
+                self.isa_ext.push(x);
+            } else {
+                let hint = match closest_ext(ext) {
+                    Some(x) => format!(", did you mean {x}?"),
+                    None => String::new(),
+                };
+                self.problems
+                    .push(format!("unsupported extension '{ext}' in isa string{hint}"));
+            }
+        }
+        // D and Zfh depend on F, Zdinx depends on Zfinx
+        if self.is_enable_isa(b'd') || self.is_enable_ext("zfh") {
+            self.isa_falgs |= 1 << (b'f' - b'a');
+        }
+        if self.is_enable_ext("zdinx") && !self.is_enable_ext("zfinx") {
+            self.isa_ext.push("zfinx");
+        }
     }
 
     pub fn set_disable_check_tohost(&mut self, disable: bool) {
@@ -94,11 +287,130 @@ impl Config {
         self.disable_check_tohost
     }
 
+    // number of instructions each hart retires between two device updates,
+    // the traps do not count (see `CpuCore::execute`)
+    pub fn set_update_budget(&mut self, budget: usize) {
+        self.update_budget = budget;
+    }
+
+    pub fn update_budget(&self) -> usize {
+        self.update_budget
+    }
+
+    // VLEN in bits, used by the vector extension
+    pub fn set_vlen(&mut self, vlen: usize) {
+        self.vlen = vlen;
+    }
+
+    pub fn vlen(&self) -> usize {
+        self.vlen
+    }
+
+    // WRS.NTO and WRS.STO give up the host cpu while the reservation is held
+    pub fn set_wrs_yield(&mut self, wrs_yield: bool) {
+        self.wrs_yield = wrs_yield;
+    }
+
+    pub fn wrs_yield(&self) -> bool {
+        self.wrs_yield
+    }
+
+    // PAUSE gives up the host cpu, for guest spinlocks
+    pub fn set_pause_yield(&mut self, pause_yield: bool) {
+        self.pause_yield = pause_yield;
+    }
+
+    pub fn pause_yield(&self) -> bool {
+        self.pause_yield
+    }
+
+    // misaligned loads and stores are done as byte accesses, each translated on
+    // its own so they can cross a page. off: they raise address-misaligned
+    pub fn set_misaligned(&mut self, misaligned: bool) {
+        self.misaligned = misaligned;
+    }
+
+    pub fn misaligned(&self) -> bool {
+        self.misaligned
+    }
+
+    // a WFI below M-mode with mstatus.TW=1, or in U-mode with S-mode, raises an
+    // illegal instruction if no interrupt wakes it up within `timeout` cycles.
+    // 0 traps at once
+    pub fn set_wfi_timeout(&mut self, timeout: u64) {
+        self.wfi_timeout = timeout;
+    }
+
+    pub fn wfi_timeout(&self) -> u64 {
+        self.wfi_timeout
+    }
+
+    // 0, 16 or 64 PMP entries. with some entries and none of them matching,
+    // S-mode and U-mode have no access to memory
+    pub fn set_pmp_entries(&mut self, entries: usize) {
+        self.pmp_entries = entries;
+    }
+
+    pub fn pmp_entries(&self) -> usize {
+        self.pmp_entries
+    }
+
+    // normal, strict or relaxed
+    pub fn set_compliance(&mut self, compliance: &str) {
+        match compliance.to_lowercase().as_str() {
+            "normal" => self.compliance = Compliance::Normal,
+            "strict" => self.compliance = Compliance::Strict,
+            "relaxed" => self.compliance = Compliance::Relaxed,
+            err => self.problems.push(format!(
+                "unknown compliance mode '{err}', expected one of normal, strict, relaxed"
+            )),
+        }
+    }
+
+    pub fn compliance(&self) -> Compliance {
+        self.compliance
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // bpp is 16 (RGB565) or 32 (ARGB8888)
+    pub fn set_fb_size(&mut self, width: u32, height: u32, bpp: u32) {
+        self.fb_width = width;
+        self.fb_height = height;
+        self.fb_bpp = bpp;
+    }
+
+    // (width, height, bpp)
+    pub fn fb_size(&self) -> (u32, u32, u32) {
+        (self.fb_width, self.fb_height, self.fb_bpp)
+    }
+
     pub fn is_enable_isa(&self, isa: u8) -> bool {
         let idx = isa - b'a';
         self.isa_falgs & (1 << idx) != 0
     }
 
+    pub fn is_enable_ext(&self, ext: &str) -> bool {
+        self.isa_ext.contains(&ext) || (self.legacy_isa && BASE_EXT.contains(&ext))
+    }
+
+    // with `legacy_isa` off, zicsr and zifencei must be given in the isa string
+    // (or by 'g'), rv64i alone traps on the csr instructions and fence.i
+    pub fn set_legacy_isa(&mut self, legacy_isa: bool) {
+        self.legacy_isa = legacy_isa;
+    }
+
+    // 32 for the rv32 isa strings, the gprs and csrs hold sign extended 32-bit values
+    pub fn xlen(&self) -> usize {
+        self.xlen
+    }
+
     pub fn get_mmu_type(&self) -> StapMode {
         self.mmu_type
     }
@@ -115,6 +427,12 @@ impl Config {
     pub fn tlb_size(&self) -> Option<usize> {
         self.tlb_size
     }
+    pub fn tlb_ways(&self) -> Option<usize> {
+        self.tlb_ways
+    }
+    pub fn tlb_policy(&self) -> TlbPolicy {
+        self.tlb_policy
+    }
 
     pub fn s_mode(&self) -> bool {
         self.s_mode
@@ -122,6 +440,277 @@ impl Config {
     pub fn u_mode(&self) -> bool {
         self.u_mode
     }
+
+    // the canonical isa string, accepted by `set_isa`
+    pub fn isa_string(&self) -> String {
+        let mut isa = format!("rv{}", self.xlen);
+        IMPLMENTED_ISA
+            .iter()
+            .filter(|x| self.is_enable_isa(**x))
+            .for_each(|x| isa.push(*x as char));
+        for ext in self.isa_ext.iter() {
+            isa.push('_');
+            isa.push_str(ext);
+        }
+        isa
+    }
+
+    // the settings as (key, value) pairs, used by the run manifest.
+    // the seed is recorded by the manifest itself
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        let size = |x: Option<usize>| x.unwrap_or(0).to_string();
+        let mmu = match self.mmu_type {
+            StapMode::Sv32 => "sv32",
+            StapMode::Sv39 => "sv39",
+            StapMode::Sv48 => "sv48",
+            StapMode::Sv57 => "sv57",
+            _ => "bare",
+        };
+        vec![
+            ("isa", self.isa_string()),
+            ("mmu_type", mmu.to_string()),
+            ("s_mode", self.s_mode.to_string()),
+            ("u_mode", self.u_mode.to_string()),
+            ("icache_size", size(self.icache_size)),
+            ("dcache_size", size(self.dcache_size)),
+            ("decode_cache_size", size(self.decode_cache_size)),
+            ("tlb_size", size(self.tlb_size)),
+            ("tlb_ways", size(self.tlb_ways)),
+            ("tlb_policy", self.tlb_policy.name().to_string()),
+            (
+                "disable_check_tohost",
+                self.disable_check_tohost.to_string(),
+            ),
+            ("update_budget", self.update_budget.to_string()),
+            ("vlen", self.vlen.to_string()),
+            ("wrs_yield", self.wrs_yield.to_string()),
+            ("pause_yield", self.pause_yield.to_string()),
+            ("misaligned", self.misaligned.to_string()),
+            ("wfi_timeout", self.wfi_timeout.to_string()),
+            ("pmp_entries", self.pmp_entries.to_string()),
+            ("legacy_isa", self.legacy_isa.to_string()),
+            ("compliance", self.compliance.name().to_string()),
+            ("fb_width", self.fb_width.to_string()),
+            ("fb_height", self.fb_height.to_string()),
+            ("fb_bpp", self.fb_bpp.to_string()),
+        ]
+    }
+
+    // the inverse of `to_pairs`, bad keys and values are reported by `validate`
+    pub fn from_pairs<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut config = Config::new();
+        for (key, value) in pairs {
+            let num = value.strip_prefix("0x").map_or_else(
+                || value.parse::<u64>().ok(),
+                |x| u64::from_str_radix(x, 16).ok(),
+            );
+            let flag = value.parse::<bool>().ok();
+            let size = num.filter(|x| *x != 0).map(|x| x as usize);
+            match (key, num, flag) {
+                ("isa", ..) => config.set_isa(value),
+                ("mmu_type", ..) => config.set_mmu_type(value),
+                ("s_mode", _, Some(x)) => config.s_mode |= x,
+                ("u_mode", _, Some(x)) => config.u_mode |= x,
+                ("icache_size", Some(_), _) => config.icache_size = size,
+                ("dcache_size", Some(_), _) => config.dcache_size = size,
+                ("decode_cache_size", Some(_), _) => config.decode_cache_size = size,
+                ("tlb_size", Some(_), _) => config.tlb_size = size,
+                ("tlb_ways", Some(_), _) => config.tlb_ways = size,
+                ("tlb_policy", ..) => config.set_tlb_policy(value),
+                ("disable_check_tohost", _, Some(x)) => config.disable_check_tohost = x,
+                ("update_budget", Some(x), _) => config.update_budget = x as usize,
+                ("vlen", Some(x), _) => config.vlen = x as usize,
+                ("wrs_yield", _, Some(x)) => config.wrs_yield = x,
+                ("pause_yield", _, Some(x)) => config.pause_yield = x,
+                ("misaligned", _, Some(x)) => config.misaligned = x,
+                ("wfi_timeout", Some(x), _) => config.wfi_timeout = x,
+                ("pmp_entries", Some(x), _) => config.pmp_entries = x as usize,
+                ("legacy_isa", _, Some(x)) => config.legacy_isa = x,
+                ("compliance", ..) => config.set_compliance(value),
+                ("fb_width", Some(x), _) => config.fb_width = x as u32,
+                ("fb_height", Some(x), _) => config.fb_height = x as u32,
+                ("fb_bpp", Some(x), _) => config.fb_bpp = x as u32,
+                _ => config
+                    .problems
+                    .push(format!("bad config entry {key}={value}")),
+            }
+        }
+        config
+    }
+
+    // check the combination of all settings, every problem is reported at once,
+    // called by `CpuCoreBuild::build`
+    pub fn validate(&self) -> RvEmuResult<()> {
+        let mut problems = self.problems.clone();
+        let mut check = |ok: bool, problem: &dyn Fn() -> String| {
+            if !ok {
+                problems.push(problem());
+            }
+        };
+
+        let rve = self.is_enable_isa(b'e');
+        check(self.is_enable_isa(b'i') != rve, &|| {
+            "the isa needs one base, 'i' or 'e', call set_isa(\"rv64imac\")".to_string()
+        });
+        check(!rve || !self.s_mode && !self.u_mode, &|| {
+            "rv64e is machine mode only, drop set_s_mode() and set_u_mode()".to_string()
+        });
+        check(!rve || !self.is_enable_ext("zve64x"), &|| {
+            "zve64x is not supported with rv64e".to_string()
+        });
+        if self.xlen == 32 {
+            let single = IMPLMENTED_ISA
+                .iter()
+                .filter(|x| self.is_enable_isa(**x) && !b"iemac".contains(x));
+            for x in single {
+                check(false, &|| {
+                    format!(
+                        "the {} extension is not supported on rv32",
+                        (*x as char).to_ascii_uppercase()
+                    )
+                });
+            }
+            for ext in self.isa_ext.iter().filter(|x| !RV32_EXT.contains(x)) {
+                check(false, &|| format!("{ext} is not supported on rv32"));
+            }
+        }
+        let rv32 = self.xlen == 32;
+        let sv32 = self.mmu_type == StapMode::Sv32;
+        check(!rv32 || sv32 || self.mmu_type == StapMode::Bare, &|| {
+            format!(
+                "mmu type {:?} is not supported on rv32, use sv32",
+                self.mmu_type
+            )
+        });
+        check(rv32 || !sv32, &|| {
+            "sv32 is only supported on rv32, use sv39".to_string()
+        });
+        check(!self.s_mode || self.mmu_type != StapMode::Bare, &|| {
+            "s_mode requires a mmu type, call set_mmu_type(\"sv39\")".to_string()
+        });
+        check(self.s_mode || self.mmu_type == StapMode::Bare, &|| {
+            format!(
+                "mmu type {:?} requires s_mode, call set_s_mode() or use bare",
+                self.mmu_type
+            )
+        });
+        let sizes = [
+            ("icache", self.icache_size),
+            ("dcache", self.dcache_size),
+            ("decode cache", self.decode_cache_size),
+            ("tlb", self.tlb_size),
+            ("tlb ways", self.tlb_ways),
+        ];
+        for (name, size) in sizes {
+            let size = size.unwrap_or(0);
+            check(size == 0 || size.is_power_of_two(), &|| {
+                format!(
+                    "{name} size {size} is not a power of two, try {}",
+                    size.next_power_of_two()
+                )
+            });
+        }
+        let (tlb_size, tlb_ways) = (self.tlb_size.unwrap_or(0), self.tlb_ways.unwrap_or(0));
+        check(tlb_ways <= tlb_size, &|| {
+            format!("tlb ways {tlb_ways} is larger than the tlb size {tlb_size}")
+        });
+        check(self.update_budget > 0, &|| {
+            "update budget must be greater than 0".to_string()
+        });
+        check(matches!(self.pmp_entries, 0 | 16 | 64), &|| {
+            format!("pmp entries {} must be 0, 16 or 64", self.pmp_entries)
+        });
+        // the size register of the vga packs both in 16 bits
+        let fb_dims = [self.fb_width, self.fb_height];
+        check(fb_dims.iter().all(|x| (1..=0xffff).contains(x)), &|| {
+            format!(
+                "framebuffer {}x{} must be 1..=65535 in both dimensions",
+                self.fb_width, self.fb_height
+            )
+        });
+        check(matches!(self.fb_bpp, 16 | 32), &|| {
+            format!("framebuffer bpp {} must be 16 or 32", self.fb_bpp)
+        });
+        check(
+            self.vlen.is_power_of_two() && VLEN_RANGE.contains(&self.vlen),
+            &|| {
+                format!(
+                    "vlen {} must be a power of two in {}..={}",
+                    self.vlen,
+                    VLEN_RANGE.start(),
+                    VLEN_RANGE.end()
+                )
+            },
+        );
+        check(!self.is_enable_isa(b'h') || self.s_mode, &|| {
+            "the H extension requires s_mode, call set_s_mode()".to_string()
+        });
+        check(!self.is_enable_ext("sstc") || self.s_mode, &|| {
+            "sstc requires s_mode, call set_s_mode()".to_string()
+        });
+        check(!self.is_enable_ext("sscofpmf") || self.s_mode, &|| {
+            "sscofpmf requires s_mode, call set_s_mode()".to_string()
+        });
+        check(
+            !self.is_enable_ext("zfinx") || !self.is_enable_isa(b'f'),
+            &|| {
+                "zfinx and the F extension are mutually exclusive, drop 'f', 'd' and zfh"
+                    .to_string()
+            },
+        );
+        let fp = self.is_enable_isa(b'f') || self.is_enable_ext("zfinx");
+        let exts = [
+            ("F", fp),
+            ("V", self.is_enable_ext("zve64x")),
+            ("H", self.is_enable_isa(b'h')),
+        ];
+        for (name, enabled) in exts {
+            check(!enabled || self.is_enable_ext("zicsr"), &|| {
+                format!("the {name} extension requires zicsr, add it to the isa string")
+            });
+        }
+        for ext in ["zawrs", "zacas", "zabha"] {
+            check(
+                !self.is_enable_ext(ext) || self.is_enable_isa(b'a'),
+                &|| format!("{ext} requires the A extension, add 'a' to the isa string"),
+            );
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let list: Vec<String> = problems.iter().map(|x| format!("\n  - {x}")).collect();
+        Err(RvEmuError::BadConfig(format!(
+            "{} problem(s){}",
+            problems.len(),
+            list.concat()
+        )))
+    }
+}
+
+// the implemented extension with the smallest edit distance, if it is close enough
+fn closest_ext(ext: &str) -> Option<&'static str> {
+    let distance = |a: &str, b: &str| {
+        let b = b.as_bytes();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, x) in a.bytes().enumerate() {
+            let mut prev = row[0];
+            row[0] = i + 1;
+            for (j, y) in b.iter().enumerate() {
+                let cur = row[j + 1];
+                row[j + 1] = (prev + (x != *y) as usize).min(row[j] + 1).min(cur + 1);
+                prev = cur;
+            }
+        }
+        row[b.len()]
+    };
+    IMPLMENTED_EXT
+        .iter()
+        .chain(BASE_EXT.iter())
+        .map(|x| (distance(ext, x), *x))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, x)| x)
 }
 
 #[test]
@@ -137,4 +726,172 @@ fn config_isa_test() {
 
     assert!(!config.is_enable_isa(b'f'));
     assert!(!config.is_enable_isa(b'd'));
+
+    let mut config = Config::new();
+    config.set_isa("rv64ima_zfh_zicbom");
+    assert!(config.is_enable_ext("zfh"));
+    assert!(config.is_enable_ext("zicbom"));
+    // zfh implies f, the 'c' in "zicbom" is not the C extension
+    assert!(config.is_enable_isa(b'f'));
+    assert!(!config.is_enable_isa(b'c'));
+
+    let mut config = Config::new();
+    config.set_isa("rv64imac_zba_zbs_zve64x");
+    assert!(config.is_enable_ext("zba"));
+    assert!(config.is_enable_ext("zbs"));
+    assert!(!config.is_enable_ext("zbc"));
+    assert!(config.is_enable_ext("zve64x"));
+    assert!(!config.is_enable_isa(b'f'));
+}
+
+#[test]
+fn config_validate_test() {
+    let mut config = Config::new();
+    config.set_isa("rv64imac_zicsr_zifencei");
+    config.set_mmu_type("sv39");
+    config.set_s_mode();
+    config.set_tlb_size(256);
+    assert!(config.validate().is_ok());
+
+    let mut config = Config::new();
+    config.set_isa("rv64imvc_zbb_zacas");
+    config.set_mmu_type("sv40");
+    config.set_s_mode();
+    config.set_icache_size(1000);
+    let Err(RvEmuError::BadConfig(report)) = config.validate() else {
+        panic!("the config is invalid");
+    };
+    // every problem is in the report
+    assert!(report.starts_with("6 problem(s)"));
+    assert!(report.contains("unsupported extension 'v'"));
+    assert!(report.contains("'zbb' in isa string, did you mean zba?"));
+    assert!(report.contains("unknown mmu type 'sv40'"));
+    assert!(report.contains("s_mode requires a mmu type"));
+    assert!(report.contains("icache size 1000 is not a power of two, try 1024"));
+    assert!(report.contains("zacas requires the A extension"));
+}
+
+#[test]
+fn config_pairs_test() {
+    let mut config = Config::new();
+    config.set_isa("rv64gc_zba_zkn");
+    config.set_mmu_type("sv48");
+    config.set_s_mode();
+    config.set_tlb_size(64);
+    config.set_tlb_ways(4);
+    config.set_tlb_policy("FIFO");
+    let pairs = config.to_pairs();
+    let replay = Config::from_pairs(pairs.iter().map(|(k, v)| (*k, v.as_str())));
+    assert!(replay.validate().is_ok());
+    assert_eq!(replay.isa_string(), "rv64imacfd_zicsr_zifencei_zba_zkn");
+    assert_eq!(replay.to_pairs(), pairs);
+
+    let replay = Config::from_pairs([("isa", "rv64i"), ("vlen", "x"), ("foo", "1")]);
+    assert!(replay.validate().is_err());
+
+    let mut config = Config::new();
+    config.set_tlb_size(16);
+    config.set_tlb_ways(32);
+    config.set_tlb_policy("plru");
+    let Err(RvEmuError::BadConfig(report)) = config.validate() else {
+        panic!("the config is invalid");
+    };
+    assert!(report.contains("tlb ways 32 is larger than the tlb size 16"));
+    assert!(report.contains("unknown tlb policy 'plru'"));
+}
+
+#[test]
+fn config_base_ext_test() {
+    use crate::rv64core::inst_decode::InstDecode;
+    // csrrs a0,mhartid,zero and fence.i
+    let decodes = |config: Config| {
+        let mut decoder = InstDecode::new(alloc::rc::Rc::new(config));
+        [0xf1402573, 0x0000100f].map(|x| decoder.fast_path(x).is_some())
+    };
+
+    let config = Config::from_pairs([("isa", "rv64i")]);
+    assert!(config.is_enable_ext("zicsr") && config.is_enable_ext("zifencei"));
+    assert_eq!(decodes(config), [true, true]);
+
+    let mut config = Config::new();
+    config.set_isa("rv64i");
+    config.set_legacy_isa(false);
+    assert!(config.validate().is_ok());
+    assert_eq!(decodes(config), [false, false]);
+
+    let mut config = Config::new();
+    config.set_legacy_isa(false);
+    config.set_isa("rv64imac_zicsr");
+    assert_eq!(config.isa_string(), "rv64imac_zicsr");
+    assert_eq!(decodes(config), [true, false]);
+
+    let mut config = Config::new();
+    config.set_legacy_isa(false);
+    config.set_isa("rv64gc_zifencei");
+    assert_eq!(config.isa_string(), "rv64imacfd_zicsr_zifencei");
+    assert!(config.validate().is_ok());
+
+    let mut config = Config::new();
+    config.set_legacy_isa(false);
+    config.set_isa("rv64imafd_zifencei");
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn config_rve_test() {
+    use crate::rv64core::{gpr::Gpr, inst_decode::InstDecode};
+    let mut config = Config::new();
+    config.set_isa("rv64emc");
+    assert!(config.validate().is_ok());
+    assert_eq!(config.isa_string(), "rv64emc");
+
+    let mut decoder = InstDecode::new(alloc::rc::Rc::new(config));
+    // addi a5,a5,1; c.mv a5,a0; csrrwi a0,mstatus,17
+    for inst in [0x00178793, 0x87aa, 0x3008d573] {
+        assert!(decoder.fast_path(inst).is_some());
+    }
+    // addi a6,a0,1; c.mv a6,a0; csrrs a0,mhartid,s2; mul a0,a1,t6
+    for inst in [0x00150813, 0x882a, 0xf1492573, 0x03f58533] {
+        assert!(decoder.fast_path(inst).is_none());
+    }
+    let mut gpr = Gpr::new_rve();
+    gpr.write(16, 1);
+    assert_eq!(gpr.read(16), 0);
+
+    let mut config = Config::new();
+    config.set_isa("rv64ie");
+    config.set_mmu_type("sv39");
+    config.set_s_mode();
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn config_compliance_test() {
+    use crate::rv64core::inst_decode::InstDecode;
+    // the all-zero word (c.addi4spn s0,sp,0), c.addiw zero,0, c.addi16sp sp,0,
+    // c.lui a0,0, c.ldsp zero,0(sp), c.jr zero
+    let reserved = [0x0000, 0x2001, 0x6101, 0x6501, 0x6002, 0x8002];
+    // c.addi4spn s0,sp,8, c.lui a0,1, c.ldsp ra,0(sp), c.jr ra
+    let valid = [0x0020, 0x6505, 0x6082, 0x8082];
+    let decodes = |mode: &str, words: &[u32]| {
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_compliance(mode);
+        assert!(config.validate().is_ok());
+        let mut decoder = InstDecode::new(alloc::rc::Rc::new(config));
+        words
+            .iter()
+            .filter(|x| decoder.fast_path(**x).is_some())
+            .count()
+    };
+    assert_eq!(decodes("normal", &reserved), reserved.len());
+    assert_eq!(decodes("relaxed", &reserved), reserved.len());
+    assert_eq!(decodes("strict", &reserved), 0);
+    assert_eq!(decodes("STRICT", &valid), valid.len());
+
+    let replay = Config::from_pairs([("isa", "rv64imac"), ("compliance", "relaxed")]);
+    assert_eq!(replay.compliance(), Compliance::Relaxed);
+    let mut config = Config::new();
+    config.set_compliance("lenient");
+    assert!(config.validate().is_err());
 }
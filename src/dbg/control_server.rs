@@ -0,0 +1,273 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use log::info;
+
+// A tiny control socket for the running simulator.
+// Every line is a command, the reply is a single line ("ok ...", "err ...").
+// HTTP requests are accepted as well, the path segments are the command words:
+//   plic assert 10          <=> curl -X POST http://127.0.0.1:PORT/plic/assert/10
+//   mip set ssip 0          <=> curl -X POST http://127.0.0.1:PORT/mip/set/ssip/0
+//   mip get 0               <=> curl http://127.0.0.1:PORT/mip/get/0
+// mtip/meip/seip are also driven by the CLINT and the PLIC, a value written here
+// only lasts until the next device update. Use `plic assert` for external interrupts.
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ControlCmd {
+    // drive a PLIC source line
+    PlicIrq {
+        irq_id: u32,
+        level: bool,
+    },
+    // set or clear a mip bit of a hart
+    Mip {
+        hart_id: usize,
+        bit: usize,
+        level: bool,
+    },
+    // read mip of a hart
+    MipGet {
+        hart_id: usize,
+    },
+    Help,
+}
+
+pub const HELP: &str = "plic assert|deassert ID; mip set|clear BIT [HART]; mip get [HART]";
+
+fn parse_num(s: &str) -> Result<u64, String> {
+    let ret = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse::<u64>(),
+    };
+    ret.map_err(|_| format!("invalid number:{s}"))
+}
+
+fn parse_mip_bit(s: &str) -> Result<usize, String> {
+    let bit = match s {
+        "ssip" => 1,
+        "msip" => 3,
+        "stip" => 5,
+        "mtip" => 7,
+        "seip" => 9,
+        "meip" => 11,
+        _ => parse_num(s)? as usize,
+    };
+    if matches!(bit, 1 | 3 | 5 | 7 | 9 | 11) {
+        Ok(bit)
+    } else {
+        Err(format!("invalid mip bit:{s}"))
+    }
+}
+
+pub fn parse_command(line: &str) -> Result<ControlCmd, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let hart_id = |idx: usize| -> Result<usize, String> {
+        words
+            .get(idx)
+            .map_or(Ok(0), |x| parse_num(x).map(|x| x as usize))
+    };
+
+    match words.as_slice() {
+        ["plic", op @ ("assert" | "deassert"), id] => {
+            let irq_id = parse_num(id)?;
+            if irq_id == 0 || irq_id >= 64 {
+                return Err(format!("invalid irq id:{id}"));
+            }
+            Ok(ControlCmd::PlicIrq {
+                irq_id: irq_id as u32,
+                level: *op == "assert",
+            })
+        }
+        ["mip", op @ ("set" | "clear"), bit, ..] if words.len() <= 4 => Ok(ControlCmd::Mip {
+            hart_id: hart_id(3)?,
+            bit: parse_mip_bit(bit)?,
+            level: *op == "set",
+        }),
+        ["mip", "get", ..] if words.len() <= 3 => Ok(ControlCmd::MipGet {
+            hart_id: hart_id(2)?,
+        }),
+        ["help"] => Ok(ControlCmd::Help),
+        _ => Err(format!("unknown command:{}", line.trim())),
+    }
+}
+
+// "POST /plic/assert/10 HTTP/1.1" => "plic assert 10"
+fn http_to_command(request_line: &str) -> Option<String> {
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("GET" | "POST" | "PUT"), Some(path), Some(version))
+            if version.starts_with("HTTP/") =>
+        {
+            let words: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+            Some(words.join(" "))
+        }
+        _ => None,
+    }
+}
+
+pub struct ControlServer {
+    socket: TcpListener,
+    client: Option<TcpStream>,
+    rcv_buffer: Vec<u8>,
+    // command of an HTTP request, waiting for the end of the headers
+    http_cmd: Option<String>,
+}
+
+impl ControlServer {
+    pub fn new(ip: &str, port: u16) -> ControlServer {
+        let socket = TcpListener::bind((ip, port)).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        info!("Control server listening on {}:{}", ip, port);
+        ControlServer {
+            socket,
+            client: None,
+            rcv_buffer: Vec::new(),
+            http_cmd: None,
+        }
+    }
+
+    fn exec_line(line: &str, exec: impl FnMut(ControlCmd) -> Result<String, String>) -> String {
+        let reply = match parse_command(line).and_then(exec) {
+            Ok(msg) => format!("ok {msg}"),
+            Err(msg) => format!("err {msg}"),
+        };
+        info!("control: {} -> {}", line, reply);
+        reply
+    }
+
+    // exec applies a command to the simulator and returns the reply message
+    pub fn tick(&mut self, mut exec: impl FnMut(ControlCmd) -> Result<String, String>) {
+        if self.client.is_none() {
+            match self.socket.accept() {
+                Ok((stream, addr)) => {
+                    info!("Control client connected: {:?}", addr);
+                    stream.set_nonblocking(true).unwrap();
+                    self.client = Some(stream);
+                    self.rcv_buffer.clear();
+                    self.http_cmd = None;
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => info!("Failed to accept control client: {:?}", e),
+            }
+            return;
+        }
+
+        let mut buf = [0; 1024];
+        let mut client = self.client.take().unwrap();
+        match client.read(&mut buf) {
+            // connection closed
+            Ok(0) => return,
+            Ok(n) => self.rcv_buffer.extend_from_slice(&buf[..n]),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                self.client = Some(client);
+                return;
+            }
+            Err(_) => return,
+        }
+
+        let mut alive = true;
+
+        while let Some(pos) = self.rcv_buffer.iter().position(|x| *x == b'\n') {
+            let line: Vec<u8> = self.rcv_buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+
+            let ret = match self.http_cmd.take() {
+                // the headers are ignored
+                Some(cmd) if !line.is_empty() => {
+                    self.http_cmd = Some(cmd);
+                    continue;
+                }
+                Some(cmd) => {
+                    let reply = Self::exec_line(&cmd, &mut exec);
+                    let status = if reply.starts_with("ok") {
+                        "200 OK"
+                    } else {
+                        "400 Bad Request"
+                    };
+                    let body = reply + "\n";
+                    let response = format!(
+                        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    // one request per connection
+                    alive = false;
+                    client.write_all(response.as_bytes())
+                }
+                None if line.is_empty() => continue,
+                None => match http_to_command(line) {
+                    Some(cmd) => {
+                        self.http_cmd = Some(cmd);
+                        continue;
+                    }
+                    None => {
+                        let reply = Self::exec_line(line, &mut exec);
+                        client.write_all(format!("{reply}\n").as_bytes())
+                    }
+                },
+            };
+            if let Err(e) = ret {
+                info!("Failed to write to control client: {:?}", e);
+                alive = false;
+            }
+            if !alive {
+                break;
+            }
+        }
+        if alive {
+            self.client = Some(client);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_control_server {
+    use super::{http_to_command, parse_command, ControlCmd};
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(
+            parse_command("plic assert 10"),
+            Ok(ControlCmd::PlicIrq {
+                irq_id: 10,
+                level: true
+            })
+        );
+        assert_eq!(
+            parse_command("mip clear ssip 1"),
+            Ok(ControlCmd::Mip {
+                hart_id: 1,
+                bit: 1,
+                level: false
+            })
+        );
+        assert_eq!(
+            parse_command("mip set 0x7"),
+            Ok(ControlCmd::Mip {
+                hart_id: 0,
+                bit: 7,
+                level: true
+            })
+        );
+        assert_eq!(
+            parse_command("mip get"),
+            Ok(ControlCmd::MipGet { hart_id: 0 })
+        );
+        assert!(parse_command("plic assert 0").is_err());
+        assert!(parse_command("plic assert 64").is_err());
+        assert!(parse_command("mip set 2").is_err());
+        assert!(parse_command("mip set ssip 0 1").is_err());
+
+        let cmd = http_to_command("POST /plic/deassert/3 HTTP/1.1").unwrap();
+        assert_eq!(
+            parse_command(&cmd),
+            Ok(ControlCmd::PlicIrq {
+                irq_id: 3,
+                level: false
+            })
+        );
+        assert_eq!(http_to_command("plic assert 3"), None);
+    }
+}
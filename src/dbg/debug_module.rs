@@ -35,6 +35,8 @@ pub struct DebugModule {
     hartinfo: HartInfo,
     abstractcs: Abstractcs,
     command: Command,
+    // autoexecdata, the command runs again on an access to these data registers
+    abstractauto: u32,
 
     config: DebugModuleConfig,
 }
@@ -68,12 +70,22 @@ impl DebugModule {
             hartinfo,
             abstractcs,
             command,
+            abstractauto: 0,
             config,
         }
     }
 
+    // a hart reaching its own DM through the bus is still borrowed by the core
+    fn hart_busy(&self) -> bool {
+        self.hart0.try_borrow_mut().is_err()
+    }
+
     pub fn dmi_read(&mut self, address: u64) -> Option<u64> {
         let addr = (address - self.config.dm_base) as usize;
+        if self.hart_busy() {
+            debug!("dmi_read: hart is busy, {:x}", addr);
+            return None;
+        }
 
         let abstract_data_range = core::ops::Range {
             start: ABSTRACT_DATA_BASE,
@@ -86,11 +98,12 @@ impl DebugModule {
 
         let rdata = if abstract_data_range.contains(&addr) {
             let offset = addr - abstract_data_range.start;
-            let value = self.abstract_data.get(offset).unwrap_or_else(|| {
+            let value = *self.abstract_data.get(offset).unwrap_or_else(|| {
                 debug!("abstract_data out of range: {:x}", offset);
                 &0
             });
-            *value as u64
+            self.auto_exec(offset);
+            value as u64
         } else if progbuf_range.contains(&addr) {
             let offset = addr - progbuf_range.start;
             let value = self.progbuf.get(offset).unwrap_or_else(|| {
@@ -152,7 +165,7 @@ impl DebugModule {
                 HALTSUM1_ADDR => 0,
                 HAWINDOWSEL_ADDR => 0,
                 HAWINDOW => 0,
-                ABSTRACTAUTO_ADDR => 0,
+                ABSTRACTAUTO_ADDR => self.abstractauto as u64,
                 _ => {
                     debug!("unimplemented dmi_read: {:x}", addr);
                     0
@@ -167,6 +180,10 @@ impl DebugModule {
 
     pub fn dmi_write(&mut self, address: u64, wdata: u64) -> Option<()> {
         let addr = (address - self.config.dm_base) as usize;
+        if self.hart_busy() {
+            debug!("dmi_write: hart is busy, {:x}", addr);
+            return None;
+        }
 
         trace!("dmi_write: {} {:x}", get_dm_register_name(addr), wdata);
 
@@ -183,6 +200,7 @@ impl DebugModule {
             let offset = addr - abstract_data_range.start;
             if let Some(value) = self.abstract_data.get_mut(offset) {
                 *value = wdata as u32;
+                self.auto_exec(offset);
                 Some(()) // success
             } else {
                 debug!("abstract_data out of range: {:x}", offset);
@@ -273,7 +291,7 @@ impl DebugModule {
                 ABSTRACTCS_ADDR => {
                     let new_abstractcs = Abstractcs::from(wdata as u32);
 
-                    if (new_abstractcs.busy()) {
+                    if new_abstractcs.busy() {
                         // Writing this register while an abstract command is executing causes cmderr to become 1 (busy) once
                         self.abstractcs.set_cmderr(debug_const::CMDERR_BUSY as u8);
                     } else {
@@ -301,7 +319,6 @@ impl DebugModule {
                             self.abstractcs.cmderr()
                         );
                     } else {
-
                         // This bit is set as soon as command is written, and is not
                         // cleared until that command has completed.
                         self.abstractcs.set_busy(true);
@@ -311,7 +328,10 @@ impl DebugModule {
 
                     Some(())
                 }
-                ABSTRACTAUTO_ADDR => Some(()),
+                ABSTRACTAUTO_ADDR => {
+                    self.abstractauto = wdata as u32 & ((1 << self.config.abstract_data_count) - 1);
+                    Some(())
+                }
                 _ => {
                     debug!("unimplemented dmi_write: {:x}", addr);
                     None
@@ -338,6 +358,7 @@ impl DebugModule {
         assert!(arg_idx < 3);
         self.abstract_data[arg_idx as usize]
     }
+    #[allow(dead_code)]
     fn arg_write32(&mut self, arg_idx: u8, value: u32) {
         assert!(arg_idx < 3);
         self.abstract_data[arg_idx as usize] = value;
@@ -354,6 +375,16 @@ impl DebugModule {
         self.abstract_data[idx + 1] = (value >> 32) as u32;
     }
 
+    fn auto_exec(&mut self, data_idx: usize) {
+        if self.abstractauto & (1 << data_idx) != 0
+            && self.abstractcs.cmderr() == debug_const::CMDERR_NONE as u8
+        {
+            self.abstractcs.set_busy(true);
+            self.perform_abstract_command();
+            self.abstractcs.set_busy(false);
+        }
+    }
+
     fn perform_abstract_command(&mut self) {
         // only support single hart now
         let binding = self.hart0.clone();
@@ -425,8 +456,18 @@ impl DebugModule {
                             }
                         }
                         0x1020..=0x103f => {
-                            debug!("unimplemented register access: {:x}", command_reg.regno());
-                            None
+                            let address = (command_reg.regno() - 0x1020) as usize;
+
+                            if command_reg.write() {
+                                let wdata = match command_reg.aarsize() as usize {
+                                    debug_const::AARSIZE_32 => Some(self.arg_read32(0) as u64),
+                                    debug_const::AARSIZE_64 => Some(self.arg_read64(0)),
+                                    _ => None,
+                                };
+                                wdata.and_then(|x| hart0.write_fpr(address, x).map(|_| x))
+                            } else {
+                                hart0.read_fpr(address)
+                            }
                         }
                         _ => {
                             debug!("unimplemented register access: {:x}", command_reg.regno());
@@ -446,6 +487,13 @@ impl DebugModule {
                         self.abstractcs.set_cmderr(debug_const::CMDERR_NOTSUP as u8);
                     }
                 }
+                // regno is incremented after a successful transfer
+                if command_reg.aarpostincrement()
+                    && self.abstractcs.cmderr() == debug_const::CMDERR_NONE as u8
+                {
+                    let next = command_reg.with_regno(command_reg.regno().wrapping_add(1));
+                    self.command = Command::from(u32::from(next));
+                }
             }
             debug_const::COMDTYPE_QUICK_ACCESS => {
                 debug!("perform_abstract_command: COMDTYPE_QUICK_ACCESS");
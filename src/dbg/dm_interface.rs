@@ -8,6 +8,14 @@ pub trait DebugModuleSlave {
     fn read_gpr(&mut self, regno: usize) -> u64;
     fn write_gpr(&mut self, regno: usize, value: u64);
 
+    // read and write fpr, None without F
+    fn read_fpr(&mut self, _regno: usize) -> Option<u64> {
+        None
+    }
+    fn write_fpr(&mut self, _regno: usize, _value: u64) -> Option<()> {
+        None
+    }
+
     // pysically memory access
     fn read_memory(&mut self, address: u64, length: usize) -> Option<u64>;
     fn write_memory(&mut self, address: u64, length: usize, value: u64) -> Option<u64>;
@@ -1,13 +1,14 @@
-use std::fmt::Debug;
-
 use bitfield_struct::bitfield;
-use log::{debug, trace};
+use log::trace;
 
 use super::{debug_module::DebugModule, jtag_state::JtagState};
+use crate::tools::RcRefCell;
 
-
+#[allow(dead_code)]
 const DMI_OP_STATUS_SUCCESS: u8 = 0;
+#[allow(dead_code)]
 const DMI_OP_STATUS_RESERVED: u8 = 1;
+#[allow(dead_code)]
 const DMI_OP_STATUS_FAILED: u8 = 2;
 const DMI_OP_STATUS_BUSY: u8 = 3;
 
@@ -83,6 +84,7 @@ impl JtagDTMRegister {
 struct ShifterRegister {
     data: u64,
     len: u8,
+    #[allow(dead_code)]
     default: u64,
 }
 
@@ -111,6 +113,7 @@ impl ShifterRegister {
         self.data >>= 1;
         self.data |= (new_in as u64) << (self.len - 1);
     }
+    #[allow(dead_code)]
     fn shift_left(&mut self, new_in: bool) {
         self.data <<= 1;
         self.data |= new_in as u64;
@@ -122,8 +125,8 @@ impl ShifterRegister {
 }
 
 pub struct JtagDriver {
-    //  debug module
-    dm: DebugModule,
+    //  debug module, shared with the DeviceDebugModule
+    dm: RcRefCell<DebugModule>,
 
     // jtag wires
     tck: bool,
@@ -145,7 +148,7 @@ pub struct JtagDriver {
 }
 
 impl JtagDriver {
-    pub fn new(dm: DebugModule) -> JtagDriver {
+    pub fn new(dm: RcRefCell<DebugModule>) -> JtagDriver {
         let dtmcontrol = DTMCS::new()
             .with_errinfo(0)
             .with_version(1) // 0.13
@@ -294,7 +297,7 @@ impl JtagDriver {
                         log::trace!("DMI_OP_NOP");
                     }
                     DMI_OP_READ => {
-                        let rdata = self.dm.dmi_read(self.dmi.address().into());
+                        let rdata = self.dm.borrow_mut().dmi_read(self.dmi.address().into());
                         match rdata {
                             Some(data) => {
                                 self.dmi.set_data(data as u32);
@@ -314,6 +317,7 @@ impl JtagDriver {
                     DMI_OP_WRITE => {
                         let w_ret = self
                             .dm
+                            .borrow_mut()
                             .dmi_write(self.dmi.address().into(), self.dmi.data().into());
 
                         if w_ret.is_some() {
@@ -1,5 +1,3 @@
-use log::trace;
-
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum JtagState {
     TestLogicReset,
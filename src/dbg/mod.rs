@@ -1,3 +1,5 @@
+#[cfg(feature = "std")]
+pub mod control_server;
 pub mod debug_module;
 pub mod debug_module_register;
 pub mod jtag_driver;
@@ -43,9 +43,8 @@ impl RemoteBitBang {
                     println!("Failed to accept client connection: {:?}", e);
                 }
             }
-        } else {
+        } else if let Some(mut client) = self.client.as_ref() {
             // 读取 client 发送的所有数据
-            let mut client = self.client.as_ref().unwrap();
             match client.read(self.rcv_buffer.as_mut()) {
                 Ok(n) => {
                     if n > 0 {
@@ -91,7 +90,7 @@ impl RemoteBitBang {
                         }
                     }
                 }
-                Err(e) => {
+                Err(_e) => {
                     // println!("{:?}", e);
                 }
             }
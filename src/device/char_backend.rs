@@ -0,0 +1,311 @@
+use std::{
+    fs::File,
+    io::{self, stdout, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::tools::FifoUnbounded;
+
+use super::host_console::spawn_stdin_reader;
+
+const POLL: Duration = Duration::from_millis(1);
+
+// the host side of a uart, the uart sees its tx and rx fifos only.
+// stdio, tcp:[HOST:]PORT, telnet:[HOST:]PORT, pty or null
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CharBackend {
+    Stdio,
+    // a listening socket, one client at a time. the output waits in the
+    // fifo until a client connects
+    Tcp { addr: String, telnet: bool },
+    // a host pseudo-terminal, its path is logged
+    Pty,
+    // the output is dropped, there is no input
+    Null,
+}
+
+impl CharBackend {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let addr = |port: &str| match port.contains(':') {
+            true => port.to_string(),
+            false => format!("127.0.0.1:{port}"),
+        };
+        match s.split_once(':') {
+            None if s == "stdio" => Ok(CharBackend::Stdio),
+            None if s == "pty" => Ok(CharBackend::Pty),
+            None if s == "null" => Ok(CharBackend::Null),
+            Some(("tcp", port)) => Ok(CharBackend::Tcp {
+                addr: addr(port),
+                telnet: false,
+            }),
+            Some(("telnet", port)) => Ok(CharBackend::Tcp {
+                addr: addr(port),
+                telnet: true,
+            }),
+            _ => Err(format!(
+                "unknown char backend:{s}, stdio, tcp:[HOST:]PORT, telnet:[HOST:]PORT, pty or null"
+            )),
+        }
+    }
+
+    // connects the fifos of a uart to the host, the threads live until the
+    // end of the emulator. returns where the uart is, the address or the
+    // pty path for the user to attach
+    pub fn spawn(&self, tx: FifoUnbounded<u8>, rx: FifoUnbounded<u8>) -> io::Result<String> {
+        match self {
+            CharBackend::Stdio => {
+                spawn_stdin_reader(rx);
+                spawn_writer(tx, Box::new(stdout()), Arc::new(AtomicBool::new(true)));
+                Ok("stdio".to_string())
+            }
+            CharBackend::Tcp { addr, telnet } => {
+                let listener = TcpListener::bind(addr)?;
+                let local = listener.local_addr()?.to_string();
+                let telnet = *telnet;
+                thread::spawn(move || serve_tcp(listener, telnet, tx, rx));
+                Ok(local)
+            }
+            CharBackend::Pty => {
+                let (master, path) = open_pty()?;
+                let reader = master.try_clone()?;
+                thread::spawn(move || read_pty(reader, rx));
+                spawn_writer(tx, Box::new(master), Arc::new(AtomicBool::new(true)));
+                Ok(path)
+            }
+            CharBackend::Null => {
+                thread::spawn(move || loop {
+                    while tx.pop().is_some() {}
+                    thread::sleep(POLL);
+                });
+                Ok("null".to_string())
+            }
+        }
+    }
+}
+
+// writes the tx fifo to the host until `alive` turns false
+fn spawn_writer(
+    tx: FifoUnbounded<u8>,
+    mut out: Box<dyn Write + Send>,
+    alive: Arc<AtomicBool>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        while alive.load(Ordering::Relaxed) {
+            while let Some(c) = tx.pop() {
+                buf.push(c);
+            }
+            if buf.is_empty() {
+                thread::sleep(POLL);
+                continue;
+            }
+            if out.write_all(&buf).and_then(|_| out.flush()).is_err() {
+                break;
+            }
+            buf.clear();
+        }
+    })
+}
+
+fn serve_tcp(listener: TcpListener, telnet: bool, tx: FifoUnbounded<u8>, rx: FifoUnbounded<u8>) {
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let _ = stream.set_nodelay(true);
+        // will echo, will suppress go ahead: the client sends every key
+        if telnet && stream.write_all(&[255, 251, 1, 255, 251, 3]).is_err() {
+            continue;
+        }
+        let Ok(reader) = stream.try_clone() else {
+            continue;
+        };
+        let alive = Arc::new(AtomicBool::new(true));
+        let rx = rx.clone();
+        let reader_alive = alive.clone();
+        thread::spawn(move || read_tcp(reader, telnet, rx, reader_alive));
+        // one client at a time, the next is accepted when this one leaves
+        let _ = spawn_writer(tx.clone(), Box::new(stream), alive).join();
+    }
+}
+
+fn read_tcp(mut stream: TcpStream, telnet: bool, rx: FifoUnbounded<u8>, alive: Arc<AtomicBool>) {
+    let mut filter = TelnetFilter::default();
+    let mut buf = [0; 256];
+    while let Ok(n @ 1..) = stream.read(&mut buf) {
+        for c in &buf[..n] {
+            match telnet {
+                true => filter.feed(*c).into_iter().for_each(|x| rx.push(x)),
+                false => rx.push(*c),
+            }
+        }
+    }
+    alive.store(false, Ordering::Relaxed);
+}
+
+// reading the master fails while no terminal has the slave open
+fn read_pty(mut master: File, rx: FifoUnbounded<u8>) {
+    let mut buf = [0; 256];
+    loop {
+        match master.read(&mut buf) {
+            Ok(n @ 1..) => buf[..n].iter().for_each(|x| rx.push(*x)),
+            _ => thread::sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_pty() -> io::Result<(File, String)> {
+    use std::{ffi::CStr, os::fd::FromRawFd};
+
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // the file closes the fd on the errors below
+    let master = unsafe { File::from_raw_fd(fd) };
+    if unsafe { libc::grantpt(fd) } != 0 || unsafe { libc::unlockpt(fd) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let name = unsafe { libc::ptsname(fd) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let path = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    // raw mode, the guest does the line discipline
+    let mut termios = unsafe { core::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } == 0 {
+        unsafe { libc::cfmakeraw(&mut termios) };
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+    }
+    Ok((master, path))
+}
+
+#[cfg(not(unix))]
+fn open_pty() -> io::Result<(File, String)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pty is only supported on unix hosts",
+    ))
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum TelnetState {
+    #[default]
+    Data,
+    Cr,
+    Iac,
+    Option,
+    Sub,
+    SubIac,
+}
+
+// drops the telnet commands of the client, and the NUL of CR NUL
+#[derive(Default)]
+struct TelnetFilter {
+    state: TelnetState,
+}
+
+impl TelnetFilter {
+    const IAC: u8 = 255;
+    const SB: u8 = 250;
+    const SE: u8 = 240;
+
+    fn feed(&mut self, c: u8) -> Option<u8> {
+        let (state, out) = match (self.state, c) {
+            (TelnetState::Cr, 0) => (TelnetState::Data, None),
+            (TelnetState::Data | TelnetState::Cr, Self::IAC) => (TelnetState::Iac, None),
+            (TelnetState::Data | TelnetState::Cr, b'\r') => (TelnetState::Cr, Some(c)),
+            (TelnetState::Data | TelnetState::Cr, c) => (TelnetState::Data, Some(c)),
+            (TelnetState::Iac, Self::IAC) => (TelnetState::Data, Some(c)),
+            // will, won't, do, don't and their option
+            (TelnetState::Iac, 251..=254) => (TelnetState::Option, None),
+            (TelnetState::Iac, Self::SB) => (TelnetState::Sub, None),
+            (TelnetState::Iac | TelnetState::Option, _) => (TelnetState::Data, None),
+            (TelnetState::Sub, Self::IAC) => (TelnetState::SubIac, None),
+            (TelnetState::Sub, _) => (TelnetState::Sub, None),
+            (TelnetState::SubIac, Self::SE) => (TelnetState::Data, None),
+            (TelnetState::SubIac, _) => (TelnetState::Sub, None),
+        };
+        self.state = state;
+        out
+    }
+}
+
+#[cfg(test)]
+mod test_char_backend {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        time::Duration,
+    };
+
+    use super::{CharBackend, TelnetFilter};
+    use crate::tools::fifo_unbounded_new;
+
+    #[test]
+    fn char_backend_test() {
+        assert_eq!(CharBackend::parse("stdio"), Ok(CharBackend::Stdio));
+        assert_eq!(
+            CharBackend::parse("telnet:0.0.0.0:4321"),
+            Ok(CharBackend::Tcp {
+                addr: "0.0.0.0:4321".to_string(),
+                telnet: true
+            })
+        );
+        assert!(CharBackend::parse("serial").is_err());
+
+        let mut filter = TelnetFilter::default();
+        let input = [
+            b'a', 255, 251, 1, 255, 250, 24, 0, 255, 240, 255, 255, b'\r', 0, b'b',
+        ];
+        let out: Vec<u8> = input.iter().filter_map(|x| filter.feed(*x)).collect();
+        assert_eq!(out, [b'a', 255, b'\r', b'b']);
+
+        // a tcp client gets the output and sends the input
+        let (tx, rx) = (fifo_unbounded_new(), fifo_unbounded_new());
+        let backend = CharBackend::parse("tcp:0").unwrap();
+        let addr = backend.spawn(tx.clone(), rx.clone()).unwrap();
+        b"boot".iter().for_each(|x| tx.push(*x));
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut buf = [0; 4];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"boot");
+        client.write_all(b"ls\r").unwrap();
+        for _ in 0..500 {
+            if rx.len() == 3 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let input: Vec<u8> = std::iter::from_fn(|| rx.pop()).collect();
+        assert_eq!(input, b"ls\r");
+
+        // the slave of the pty is a raw terminal
+        #[cfg(unix)]
+        {
+            let (tx, rx) = (fifo_unbounded_new(), fifo_unbounded_new());
+            let path = CharBackend::Pty.spawn(tx.clone(), rx.clone()).unwrap();
+            let mut slave = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .unwrap();
+            b"login:\n".iter().for_each(|x| tx.push(*x));
+            let mut buf = [0; 7];
+            slave.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"login:\n");
+        }
+    }
+}
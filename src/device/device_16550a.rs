@@ -1,10 +1,18 @@
+use alloc::{collections::VecDeque, vec::Vec};
 use bitfield_struct::bitfield;
 
-use crate::{device::device_trait::DeviceBase, tools::FifoUnbounded};
+use crate::{
+    device::{device_trait::DeviceBase, irq::IrqLine},
+    error::RvEmuResult,
+    snapshot::{StateReader, StateWriter},
+    tools::FifoUnbounded,
+};
 
 const RBR: u64 = 0x00; // Receive Buffer Register (read only)
 const THR: u64 = 0x00; // Transmit Holding Register (write only)
+const DLL: u64 = 0x00; // Divisor Latch LSB (DLAB = 1)
 const IER: u64 = 0x01; // Interrupt Enable Register (read/write)
+const DLM: u64 = 0x01; // Divisor Latch MSB (DLAB = 1)
 const IIR: u64 = 0x02; // Interrupt Identification Register (read only)
 const FCR: u64 = 0x02; // FIFO Control Register (write only)
 const LCR: u64 = 0x03; // Line Control Register (read/write)
@@ -13,6 +21,17 @@ const LSR: u64 = 0x05; // Line Status Register (read/write)
 const MSR: u64 = 0x06; // Modem Status Register (read/write)
 const SCR: u64 = 0x07; // Scratch Register (read/write)
 
+const FIFO_SIZE: usize = 16;
+
+// the interrupt ids of IIR[3:0], by priority
+const IIR_NO_INT: u8 = 0x1;
+const IIR_RLS: u8 = 0x6; // receiver line status
+const IIR_RDA: u8 = 0x4; // received data available
+const IIR_CTI: u8 = 0xc; // character timeout
+const IIR_THRE: u8 = 0x2; // transmitter holding register empty
+const IIR_MS: u8 = 0x0; // modem status
+const IIR_FIFO_ENABLED: u8 = 0xc0;
+
 #[bitfield(u8)]
 struct Ier {
     pub rx_avali: bool,  // Received Data Available Interrupt Enable
@@ -23,39 +42,17 @@ struct Ier {
     _reserved: u8,
 }
 
-// Bits 4 and 5: Logic ‘0’.
-// Bits 6 and 7: Logic ‘1’ for compatibility reason.
-// Reset Value: C1h
-#[bitfield(u8, default = false)]
-struct Iir {
-    pub ip: bool, // Interrupt Pending
-    #[bits(3)]
-    ip_type: u8,
-    #[bits(4)]
-    _reserved: u8,
-}
-impl Default for Iir {
-    fn default() -> Self {
-        Iir::from(0xC1)
-    }
-}
-
-// Reset Value : 11000000b
 #[bitfield(u8)]
 struct Fcr {
     pub enable: bool, // FIFO Enable
     pub rx_reset: bool,
     pub tx_reset: bool,
-    #[bits(3)]
+    pub dma_mode: bool,
+    #[bits(2)]
     _reserved: u8,
-    #[bits(2, default = 0b11)]
+    #[bits(2)]
     rx_level: u8,
 }
-// impl Default for Fcr {
-//     fn default() -> Self {
-//         Fcr::from(0xC0)
-//     }
-// }
 
 //Reset Value: 00000011b
 #[bitfield(u8)]
@@ -103,84 +100,44 @@ struct Msr {
     pub dcd: bool,
 }
 
-#[bitfield(u32)]
-struct Txdata {
-    pub data: u8,
-    #[bits(23)]
-    _reserved: u32,
-    pub full: bool,
-}
-
-#[bitfield(u32)]
-struct Rxdata {
-    pub data: u8,
-    #[bits(23)]
-    _reserved: u32,
-    pub empty: bool,
-}
-
-#[bitfield(u32)]
-struct Txctrl {
-    pub txen: bool,
-    pub nstop: bool,
-    #[bits(14)]
-    _reserved0: u32,
-    #[bits(3)]
-    pub txcnt: u8,
-    #[bits(13)]
-    _reserved1: u32,
-}
-
-#[bitfield(u32)]
-struct Rxctrl {
-    pub rxen: bool,
-    #[bits(15)]
-    _reserved0: u32,
-    #[bits(3)]
-    pub rxcnt: u8,
-    #[bits(13)]
-    _reserved1: u32,
-}
-
-#[bitfield(u32)]
-struct IeIp {
-    pub txwm: bool,
-    pub rxwm: bool,
-    #[bits(30)]
-    _reserved: u32,
-}
-
 struct Uart16550aIN {
-    rbr: u8,
-    thr: u8,
     ier: Ier,
-    iir: Iir,
     fcr: Fcr,
     lcr: Lcr,
     mcr: Mcr,
+    // only the error bits, data ready and the empty bits are computed
     lsr: Lsr,
     msr: Msr,
     scr: u8,
+    dll: u8,
+    dlm: u8,
 }
 impl Uart16550aIN {
     pub fn new() -> Self {
         Uart16550aIN {
-            rbr: 0,
-            thr: 0,
             ier: Ier::new(),
-            iir: Iir::default(),
-            fcr: Fcr::default(),
+            fcr: Fcr::new(),
             lcr: Lcr::default(),
             mcr: Mcr::new(),
             lsr: Lsr::new(),
-            msr: Msr::new(),
+            msr: Msr::new().with_cts(true).with_dsr(true).with_dcd(true),
             scr: 0,
+            dll: 0,
+            dlm: 0,
         }
     }
 }
 
+// a NS16550A, the transmitter sends at once so THR and TSR are always empty.
+// the host fifos are the line, the receiver fifo takes the bytes from rxfifo
 pub struct Device16550aUART {
     regs: Uart16550aIN,
+    rx: VecDeque<u8>,
+    // the THRE interrupt, cleared by reading IIR or writing THR
+    thre_pending: bool,
+    // no byte was received since the last update, for the character timeout
+    rx_idle: bool,
+    irq: Option<IrqLine>,
     rxfifo: FifoUnbounded<u8>,
     txfifo: FifoUnbounded<u8>,
 }
@@ -189,74 +146,332 @@ impl Device16550aUART {
     pub fn new(uart_tx: FifoUnbounded<u8>, uart_rx: FifoUnbounded<u8>) -> Self {
         Device16550aUART {
             regs: Uart16550aIN::new(),
+            rx: VecDeque::with_capacity(FIFO_SIZE),
+            thre_pending: false,
+            rx_idle: false,
+            irq: None,
             txfifo: uart_tx,
             rxfifo: uart_rx,
         }
     }
 
-    fn read_lsr(&mut self) -> u8 {
-        let mut lsr = self.regs.lsr;
-        if self.rxfifo.is_empty() {
-            lsr.set_data_ready(false);
+    fn fifo_size(&self) -> usize {
+        if self.regs.fcr.enable() {
+            FIFO_SIZE
         } else {
-            lsr.set_data_ready(true);
+            1
+        }
+    }
+
+    fn rx_trigger(&self) -> usize {
+        match self.regs.fcr.rx_level() {
+            _ if !self.regs.fcr.enable() => 1,
+            0 => 1,
+            1 => 4,
+            2 => 8,
+            _ => 14,
+        }
+    }
+
+    fn receive(&mut self, c: u8) {
+        if self.rx.len() < self.fifo_size() {
+            self.rx.push_back(c);
+            self.rx_idle = false;
+        } else {
+            self.regs.lsr.set_overrun_error(true);
+        }
+    }
+
+    // the line is cut in loopback mode
+    fn fill_rx(&mut self) {
+        if self.regs.mcr.loopback() {
+            return;
+        }
+        while self.rx.len() < self.fifo_size() {
+            match self.rxfifo.pop() {
+                Some(c) => self.receive(c),
+                None => break,
+            }
         }
-        lsr.0
     }
 
     pub fn put_char(&mut self, ch: u64) {
-        // let c = char::from_u32(ch as u32).unwrap();
-        // print!("{c}");
-        // io::stdout().flush().unwrap();
-        // println!("{:x}", ch);
         let c = ch as u8;
-        // self.txfifo.send(c).unwrap();
-        self.txfifo.push(c);
-        self.regs.lsr.set_thr_empty(true);
+        if self.regs.mcr.loopback() {
+            self.receive(c);
+        } else {
+            self.txfifo.push(c);
+        }
+        self.thre_pending = true;
     }
 
     fn get_char(&mut self) -> u8 {
-        // self.regs.rbr = self.rxfifo.try_recv().unwrap_or(0);
-        self.regs.rbr = self.rxfifo.pop().unwrap_or(0);
-        self.regs.rbr
+        self.fill_rx();
+        let c = self.rx.pop_front().unwrap_or(0);
+        self.fill_rx();
+        c
+    }
+
+    fn read_lsr(&mut self) -> u8 {
+        self.fill_rx();
+        let lsr = self
+            .regs
+            .lsr
+            .with_data_ready(!self.rx.is_empty())
+            .with_thr_empty(true)
+            .with_tsr_empty(true);
+        // the error bits clear on read
+        self.regs.lsr = Lsr::new();
+        lsr.0
+    }
+
+    fn read_msr(&mut self) -> u8 {
+        let msr = self.read_msr_bits() | self.regs.msr.0 & 0xf;
+        // the deltas clear on read
+        self.regs.msr = Msr::from(self.regs.msr.0 & 0xf0);
+        msr
+    }
+
+    fn write_mcr(&mut self, mcr: Mcr) {
+        let old = self.read_msr_bits();
+        self.regs.mcr = mcr;
+        let new = self.read_msr_bits();
+        let delta = (old ^ new) >> 4;
+        // teri is set on the falling edge of ri
+        let teri = old & !new & 0x40 != 0;
+        let msr = self.regs.msr.0 | (delta & 0xb) | (teri as u8) << 2;
+        self.regs.msr = Msr::from(msr);
+    }
+
+    // the modem status inputs without the deltas
+    fn read_msr_bits(&self) -> u8 {
+        let mcr = self.regs.mcr;
+        match mcr.loopback() {
+            true => {
+                (mcr.rts() as u8) << 4
+                    | (mcr.dtr() as u8) << 5
+                    | (mcr.out1() as u8) << 6
+                    | (mcr.out2() as u8) << 7
+            }
+            false => self.regs.msr.0 & 0xf0,
+        }
+    }
+
+    fn write_fcr(&mut self, fcr: Fcr) {
+        // changing the fifo mode clears the fifos
+        if fcr.enable() != self.regs.fcr.enable() || fcr.rx_reset() {
+            self.rx.clear();
+        }
+        self.regs.fcr = fcr.with_rx_reset(false).with_tx_reset(false);
+    }
+
+    fn pending_int(&self) -> u8 {
+        let ier = self.regs.ier;
+        let lsr = self.regs.lsr;
+        let rx_ready = self.rx.len() >= self.rx_trigger();
+        let timeout = self.regs.fcr.enable() && !self.rx.is_empty() && self.rx_idle;
+        if ier.rls() && (lsr.overrun_error() || lsr.break_interrupt()) {
+            IIR_RLS
+        } else if ier.rx_avali() && rx_ready {
+            IIR_RDA
+        } else if ier.rx_avali() && timeout {
+            IIR_CTI
+        } else if ier.thr_empty() && self.thre_pending {
+            IIR_THRE
+        } else if ier.ms() && self.regs.msr.0 & 0xf != 0 {
+            IIR_MS
+        } else {
+            IIR_NO_INT
+        }
+    }
+
+    fn read_iir(&mut self) -> u8 {
+        let int = self.pending_int();
+        if int == IIR_THRE {
+            self.thre_pending = false;
+        }
+        match self.regs.fcr.enable() {
+            true => int | IIR_FIFO_ENABLED,
+            false => int,
+        }
+    }
+
+    fn update_irq(&self) {
+        if let Some(irq) = &self.irq {
+            irq.set(self.pending_int() != IIR_NO_INT);
+        }
     }
 }
 
 impl DeviceBase for Device16550aUART {
-    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
-        assert_eq!(len, 1);
-        match addr {
-            RBR => self.get_char() as u64,
-            IER => self.regs.ier.0 as u64,
-            IIR => self.regs.iir.0 as u64,
-            LCR => self.regs.lcr.0 as u64,
-            LSR => self.read_lsr() as u64,
-            MSR => self.regs.msr.0 as u64,
-            SCR => self.regs.scr as u64,
-            _ => panic!("invalid read address:{:x}", addr),
-        }
+    fn do_read(&mut self, addr: u64, _len: usize) -> u64 {
+        let dlab = self.regs.lcr.dlab();
+        let ret = match addr {
+            DLL if dlab => self.regs.dll,
+            DLM if dlab => self.regs.dlm,
+            RBR => self.get_char(),
+            IER => self.regs.ier.0,
+            IIR => self.read_iir(),
+            LCR => self.regs.lcr.0,
+            MCR => self.regs.mcr.0,
+            LSR => self.read_lsr(),
+            MSR => self.read_msr(),
+            SCR => self.regs.scr,
+            _ => 0,
+        };
+        self.update_irq();
+        ret as u64
     }
 
-    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
-        assert_eq!(len, 1);
+    fn do_write(&mut self, addr: u64, data: u64, _len: usize) -> u64 {
+        let dlab = self.regs.lcr.dlab();
+        let data = data as u8;
         match addr {
-            THR => {
-                self.regs.thr = data as u8;
-                self.put_char(data);
+            DLL if dlab => self.regs.dll = data,
+            DLM if dlab => self.regs.dlm = data,
+            THR => self.put_char(data as u64),
+            IER => {
+                let ier = Ier::from(data & 0xf);
+                // THR is always empty, enabling the interrupt raises it
+                if ier.thr_empty() && !self.regs.ier.thr_empty() {
+                    self.thre_pending = true;
+                }
+                self.regs.ier = ier;
             }
-            IER => self.regs.ier = Ier::from(data as u8),
-            FCR => self.regs.fcr = Fcr::from(data as u8),
-            LCR => self.regs.lcr = Lcr::from(data as u8),
-            MCR => self.regs.mcr = Mcr::from(data as u8),
-            LSR => self.regs.lsr = Lsr::from(data as u8),
-            MSR => self.regs.msr = Msr::from(data as u8),
-            SCR => self.regs.scr = data as u8,
-            _ => panic!("invalid write address:{:x}", addr),
+            FCR => self.write_fcr(Fcr::from(data)),
+            LCR => self.regs.lcr = Lcr::from(data),
+            MCR => self.write_mcr(Mcr::from(data & 0x1f)),
+            SCR => self.regs.scr = data,
+            _ => {}
         }
+        self.update_irq();
         0
     }
 
+    // without the rx interrupt the bytes stay on the line for the polling
+    // reads, another console sharing the host fifo still gets them
+    fn do_update(&mut self) {
+        let len = self.rx.len();
+        if self.regs.ier.rx_avali() {
+            self.fill_rx();
+        }
+        if self.rx.len() == len {
+            self.rx_idle = true;
+        }
+        self.update_irq();
+    }
+
+    fn dt_compatible(&self) -> Option<&'static str> {
+        Some("ns16550a")
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+
+    fn reset(&mut self) {
+        self.regs = Uart16550aIN::new();
+        self.rx.clear();
+        self.thre_pending = false;
+        self.rx_idle = false;
+        self.update_irq();
+    }
+
+    // the bytes still in the host fifos are not part of it
+    fn save_state(&self, out: &mut StateWriter) {
+        let regs = &self.regs;
+        for reg in [regs.ier.0, regs.fcr.0, regs.lcr.0, regs.mcr.0] {
+            out.put_u8(reg);
+        }
+        for reg in [regs.lsr.0, regs.msr.0, regs.scr, regs.dll, regs.dlm] {
+            out.put_u8(reg);
+        }
+        let rx: Vec<u8> = self.rx.iter().copied().collect();
+        out.put_bytes(&rx);
+        out.put_bool(self.thre_pending);
+        out.put_bool(self.rx_idle);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> RvEmuResult<()> {
+        let regs = &mut self.regs;
+        regs.ier = input.get_u8()?.into();
+        regs.fcr = input.get_u8()?.into();
+        regs.lcr = input.get_u8()?.into();
+        regs.mcr = input.get_u8()?.into();
+        regs.lsr = input.get_u8()?.into();
+        regs.msr = input.get_u8()?.into();
+        regs.scr = input.get_u8()?;
+        regs.dll = input.get_u8()?;
+        regs.dlm = input.get_u8()?;
+        self.rx = input.get_bytes()?.iter().copied().collect();
+        self.thre_pending = input.get_bool()?;
+        self.rx_idle = input.get_bool()?;
+        self.update_irq();
+        Ok(())
+    }
+
     fn get_name(&self) -> &'static str {
         "16550a UART"
     }
 }
+
+#[cfg(test)]
+mod test_16550a {
+    use super::*;
+    use crate::tools::fifo_unbounded_new;
+
+    fn uart() -> (Device16550aUART, FifoUnbounded<u8>, FifoUnbounded<u8>) {
+        let tx = fifo_unbounded_new();
+        let rx = fifo_unbounded_new();
+        (Device16550aUART::new(tx.clone(), rx.clone()), tx, rx)
+    }
+
+    #[test]
+    fn uart_16550a_test() {
+        let (mut uart, tx, rx) = uart();
+        // divisor latch
+        uart.do_write(LCR, 0x83, 1);
+        uart.do_write(DLL, 0x01, 1);
+        assert_eq!(uart.do_read(DLL, 1), 1);
+        uart.do_write(LCR, 0x03, 1);
+        assert_eq!(uart.do_read(IER, 1), 0);
+
+        // transmit, THRE is raised again after the write
+        assert_eq!(uart.do_read(IIR, 1), IIR_NO_INT as u64);
+        uart.do_write(IER, 0x2, 1);
+        assert_eq!(uart.do_read(IIR, 1), IIR_THRE as u64);
+        assert_eq!(uart.do_read(IIR, 1), IIR_NO_INT as u64);
+        uart.do_write(THR, b'a' as u64, 1);
+        assert_eq!(tx.pop(), Some(b'a'));
+        assert_eq!(uart.do_read(LSR, 1), 0x60);
+        assert_eq!(uart.do_read(IIR, 1), IIR_THRE as u64);
+
+        // receive with the fifo, trigger level 4 and the timeout
+        uart.do_write(FCR, 0x47, 1);
+        uart.do_write(IER, 0x1, 1);
+        b"abc".iter().for_each(|x| rx.push(*x));
+        uart.do_update();
+        assert_eq!(uart.do_read(IIR, 1), 0xc1);
+        uart.do_update();
+        assert_eq!(uart.do_read(IIR, 1), (IIR_CTI | IIR_FIFO_ENABLED) as u64);
+        rx.push(b'd');
+        uart.do_update();
+        assert_eq!(uart.do_read(IIR, 1), (IIR_RDA | IIR_FIFO_ENABLED) as u64);
+        assert_eq!(uart.do_read(LSR, 1), 0x61);
+        let data: alloc::vec::Vec<u8> = (0..4).map(|_| uart.do_read(RBR, 1) as u8).collect();
+        assert_eq!(data, b"abcd");
+        assert_eq!(uart.do_read(LSR, 1), 0x60);
+
+        // loopback: the modem lines and an overrun of the fifo
+        uart.do_write(MCR, 0x1a, 1);
+        assert_eq!(uart.do_read(MSR, 1) & 0xf0, 0x90);
+        uart.do_write(IER, 0x5, 1);
+        (0..17).for_each(|x| {
+            uart.do_write(THR, x, 1);
+        });
+        assert!(tx.is_empty());
+        assert_eq!(uart.do_read(IIR, 1), (IIR_RLS | IIR_FIFO_ENABLED) as u64);
+        assert_eq!(uart.do_read(LSR, 1), 0x63);
+        assert_eq!(uart.do_read(RBR, 1), 0);
+    }
+}
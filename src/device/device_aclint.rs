@@ -0,0 +1,338 @@
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use crate::{
+    error::{RvEmuError, RvEmuResult},
+    rv64core::{csr_regs::SstcTimer, csr_regs_define::XipIn},
+    snapshot::{StateReader, StateWriter},
+    tools::{RcCell, RcRefCell},
+};
+
+use super::{
+    device_trait::DeviceBase,
+    irq::{HartIrq, HartIrqKind},
+};
+
+// RISC-V ACLINT: the CLINT split into three devices.
+// ref: riscv-aclint-1.0-rc4
+//
+// MSWI:   base + 0x0000 + 4 * hart: MSIP, bit 0 writable
+// MTIMER: base + 0x0000 + 8 * hart: MTIMECMP, base + 0x7ff8: MTIME
+// SSWI:   base + 0x0000 + 4 * hart: SETSSIP, reads zero
+pub const ACLINT_MSWI_SIZE: u64 = 0x4000;
+pub const ACLINT_MTIMER_SIZE: u64 = 0x8000;
+pub const ACLINT_SSWI_SIZE: u64 = 0x4000;
+
+const SWI_PER_HART: u64 = 0x4;
+const MTIMECMP_PER_HART: u64 = 0x8;
+const MTIME_BASE: u64 = 0x7ff8;
+const MTIME_END: u64 = MTIME_BASE + 7;
+
+// the software interrupts of a hart
+struct SwiHart {
+    msip: HartIrq,
+    ssip: HartIrq,
+}
+
+// the harts of a machine, shared by the MSWI and the SSWI
+type SwiHarts = RcRefCell<Vec<SwiHart>>;
+
+// mtimecmp and mtime are 64-bit registers, they can be accessed as a whole
+// or as two 32-bit halves (offset 4 is the high half)
+fn reg_read(reg: u64, offset: u64, len: usize) -> u64 {
+    match len {
+        8 => reg,
+        4 => (reg >> ((offset & 4) * 8)) & 0xffff_ffff,
+        _ => panic!("aclint read len:{}", len),
+    }
+}
+fn reg_write(reg: u64, offset: u64, data: u64, len: usize) -> u64 {
+    match len {
+        8 => data,
+        4 => {
+            let shift = (offset & 4) * 8;
+            let mask = 0xffff_ffff << shift;
+            (reg & !mask) | ((data << shift) & mask)
+        }
+        _ => panic!("aclint write len:{}", len),
+    }
+}
+
+// machine-level software interrupts, the msip of each hart
+pub struct AclintMswi {
+    harts: SwiHarts,
+}
+
+impl AclintMswi {
+    pub fn new() -> Self {
+        AclintMswi {
+            harts: RcRefCell::new(RefCell::new(vec![])),
+        }
+    }
+    pub fn add_hart(&mut self, xip_share: RcCell<XipIn>) {
+        self.harts.borrow_mut().push(SwiHart {
+            msip: HartIrq::new(xip_share.clone(), HartIrqKind::MSoft),
+            ssip: HartIrq::new(xip_share, HartIrqKind::SSoft),
+        });
+    }
+    // the SSWI of the same harts, also for the harts added later
+    pub fn sswi(&self) -> AclintSswi {
+        AclintSswi {
+            harts: self.harts.clone(),
+        }
+    }
+}
+
+impl Default for AclintMswi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceBase for AclintMswi {
+    // the registers of missing harts are hardwired to zero
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "mswi read:{:x},{:x}", addr, len);
+        let harts = self.harts.borrow();
+        harts
+            .get((addr / SWI_PER_HART) as usize)
+            .map_or(0, |hart| hart.msip.level() as u64)
+    }
+    // only bit 0 is writable, the upper bits are hardwired to zero
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "mswi write:{:x},{:x},{:x}", addr, len, data);
+        if let Some(hart) = self.harts.borrow().get((addr / SWI_PER_HART) as usize) {
+            hart.msip.set(data & 1 == 1);
+        }
+        0
+    }
+    fn get_name(&self) -> &'static str {
+        "ACLINT MSWI"
+    }
+}
+
+// supervisor-level software interrupts: writing 1 sets the ssip of the
+// hart, an IPI between S-mode harts without a trap to M-mode.
+// the ssip is cleared by the receiver through sip
+pub struct AclintSswi {
+    harts: SwiHarts,
+}
+
+impl DeviceBase for AclintSswi {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "sswi read:{:x},{:x}", addr, len);
+        0
+    }
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "sswi write:{:x},{:x},{:x}", addr, len, data);
+        let harts = self.harts.borrow();
+        if let (Some(hart), 1) = (harts.get((addr / SWI_PER_HART) as usize), data & 1) {
+            hart.ssip.raise();
+        }
+        0
+    }
+    fn get_name(&self) -> &'static str {
+        "ACLINT SSWI"
+    }
+}
+
+// each hart has a memory maped mtimcmp
+// xip is a shared resource with cpu core
+struct MtimerHart {
+    mtimecmp: u64,
+    mtip: HartIrq,
+    // the stip and vstip of Sstc
+    xip: RcCell<XipIn>,
+    sstc: Option<SstcTimer>,
+}
+
+// the machine-level timer, mtime and the mtimecmp of each hart
+pub struct AclintMtimer {
+    harts: Vec<MtimerHart>,
+    mtime: RcCell<u64>,
+}
+
+impl AclintMtimer {
+    pub fn new() -> Self {
+        AclintMtimer {
+            harts: vec![],
+            mtime: RcCell::new(0.into()),
+        }
+    }
+    // add a hart, and return the shared mtime
+    pub fn add_hart(&mut self, xip_share: RcCell<XipIn>, sstc: Option<SstcTimer>) -> RcCell<u64> {
+        self.harts.push(MtimerHart {
+            mtimecmp: u64::MAX,
+            mtip: HartIrq::new(xip_share.clone(), HartIrqKind::MTimer),
+            xip: xip_share,
+            sstc,
+        });
+        self.mtime.clone()
+    }
+    // the shared mtime, for the devices counting in timer ticks
+    pub fn mtime(&self) -> RcCell<u64> {
+        self.mtime.clone()
+    }
+
+    // mtime advances `inc` ticks, the supervisor timers of Sstc
+    // are compared on every tick as well
+    pub fn tick(&mut self, inc: usize) {
+        let mtime = self.mtime.get() + inc as u64;
+        self.mtime.set(mtime);
+        for hart in self.harts.iter_mut() {
+            hart.mtip.set(mtime >= hart.mtimecmp);
+            if let Some(sstc) = &hart.sstc {
+                sstc.update(mtime, &hart.xip);
+            }
+        }
+    }
+
+    // the ticks until the next compare of a hart is reached: mtimecmp, and
+    // stimecmp and vstimecmp while Sstc is on. u64::MAX if none is ahead
+    pub fn ticks_to_deadline(&self) -> u64 {
+        let mtime = self.mtime.get();
+        let ahead = |cmp: u64, time: u64| if cmp > time { cmp - time } else { u64::MAX };
+        let sstc = |sstc: &SstcTimer| {
+            let mut ticks = u64::MAX;
+            if sstc.menvcfg.get().stce() {
+                ticks = ahead(sstc.stimecmp.get(), mtime);
+                if sstc.henvcfg.get().stce() {
+                    let time = mtime.wrapping_add(sstc.htimedelta.get());
+                    ticks = ticks.min(ahead(sstc.vstimecmp.get(), time));
+                }
+            }
+            ticks
+        };
+        self.harts
+            .iter()
+            .map(|hart| {
+                let ticks = ahead(hart.mtimecmp, mtime);
+                hart.sstc.as_ref().map_or(ticks, |x| ticks.min(sstc(x)))
+            })
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+}
+
+impl Default for AclintMtimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceBase for AclintMtimer {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        match (addr, len) {
+            (MTIME_BASE..=MTIME_END, 4 | 8) => reg_read(self.mtime.get(), addr - MTIME_BASE, len),
+            (0..MTIME_BASE, 4 | 8) => {
+                let hart_id = (addr / MTIMECMP_PER_HART) as usize;
+                self.harts
+                    .get(hart_id)
+                    .map_or(0, |hart| reg_read(hart.mtimecmp, addr, len))
+            }
+            _ => {
+                panic!("mtimer read:{:x},{:x}", addr, len);
+            }
+        }
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        match (addr, len) {
+            (MTIME_BASE..=MTIME_END, 4 | 8) => {
+                let mtime = reg_write(self.mtime.get(), addr - MTIME_BASE, data, len);
+                self.mtime.set(mtime);
+            }
+            (0..MTIME_BASE, 4 | 8) => {
+                let hart_id = (addr / MTIMECMP_PER_HART) as usize;
+                if let Some(hart) = self.harts.get_mut(hart_id) {
+                    hart.mtimecmp = reg_write(hart.mtimecmp, addr, data, len);
+                }
+            }
+            _ => {
+                panic!("mtimer write:{:x},{:x},{:x}", addr, len, data);
+            }
+        };
+        0
+    }
+
+    fn reset(&mut self) {
+        self.mtime.set(0);
+        self.harts
+            .iter_mut()
+            .for_each(|hart| hart.mtimecmp = u64::MAX);
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.put_u64(self.mtime.get());
+        out.put_u32(self.harts.len() as u32);
+        self.harts
+            .iter()
+            .for_each(|hart| out.put_u64(hart.mtimecmp));
+    }
+
+    // mtip follows the restored compares at once
+    fn load_state(&mut self, input: &mut StateReader) -> RvEmuResult<()> {
+        self.mtime.set(input.get_u64()?);
+        let harts = input.get_u32()? as usize;
+        if harts != self.harts.len() {
+            return Err(RvEmuError::Snapshot(format!(
+                "mtimer of {} harts, the state has {harts}",
+                self.harts.len()
+            )));
+        }
+        for hart in self.harts.iter_mut() {
+            hart.mtimecmp = input.get_u64()?;
+        }
+        self.tick(0);
+        Ok(())
+    }
+
+    fn get_name(&self) -> &'static str {
+        "ACLINT MTIMER"
+    }
+}
+
+#[cfg(test)]
+mod test_aclint {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use crate::{device::device_trait::DeviceBase, rv64core::csr_regs_define::XipIn};
+
+    use super::{AclintMswi, AclintMtimer};
+
+    #[test]
+    fn aclint_sswi_test() {
+        let mut mswi = AclintMswi::new();
+        let mut sswi = mswi.sswi();
+        let xips: alloc::vec::Vec<_> = (0..2).map(|_| Rc::new(Cell::new(XipIn::new()))).collect();
+        xips.iter().for_each(|x| mswi.add_hart(x.clone()));
+
+        // setssip is edge: the register reads zero, writing 0 does nothing
+        sswi.do_write(4, 1, 4);
+        assert!(xips[1].get().ssip() && !xips[0].get().ssip());
+        assert_eq!(sswi.do_read(4, 4), 0);
+        sswi.do_write(4, 0, 4);
+        assert!(xips[1].get().ssip());
+        assert!(!xips[1].get().msip());
+        // a missing hart is ignored
+        sswi.do_write(8, 1, 4);
+        assert_eq!(mswi.do_read(8, 4), 0);
+
+        mswi.do_write(0, 1, 4);
+        assert!(xips[0].get().msip() && !xips[0].get().ssip());
+    }
+
+    #[test]
+    fn aclint_mtimer_test() {
+        let mut mtimer = AclintMtimer::new();
+        let xip = Rc::new(Cell::new(XipIn::new()));
+        let mtime = mtimer.add_hart(xip.clone(), None);
+        mtimer.do_write(0, 10, 8);
+        mtimer.tick(10);
+        assert!(xip.get().mtip());
+        assert_eq!(mtimer.do_read(0x7ff8, 8), 10);
+        assert_eq!(mtime.get(), 10);
+        assert_eq!(mtimer.do_read(8, 8), 0);
+    }
+}
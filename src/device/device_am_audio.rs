@@ -0,0 +1,226 @@
+use std::sync::Mutex;
+
+use alloc::sync::Arc;
+
+use crate::device::device_trait::DeviceBase;
+
+// the NEMU audio, the registers at AUDIO_ADDR:
+// 0x00: freq, 0x04: channels, 0x08: samples, written before init
+// 0x0c: sbuf_size, read-only
+// 0x10: init, writing 1 opens the host audio with the spec above
+// 0x14: count, the bytes of the stream buffer not played yet.
+//       the guest adds what it wrote, the host takes what it plays
+// the stream buffer at AUDIO_SBUF_ADDR is a ring of 16-bit samples, the
+// guest writes it from where it stopped the last time, with a wrap at the end
+pub const AUDIO_SBUF_SIZE: usize = 0x10000;
+pub const AUDIO_CTL_SIZE: u64 = 0x18;
+
+const FREQ: u64 = 0x00;
+const CHANNELS: u64 = 0x04;
+const SAMPLES: u64 = 0x08;
+const SBUF_SIZE: u64 = 0x0c;
+const INIT: u64 = 0x10;
+const COUNT: u64 = 0x14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioSpec {
+    pub freq: u32,
+    pub channels: u8,
+    pub samples: u16,
+}
+
+// the stream buffer shared with the thread feeding the host audio
+pub struct AudioStream {
+    sbuf: Box<[u8]>,
+    // where the host plays from
+    head: usize,
+    count: usize,
+    // a new init of the guest, taken by the host
+    spec: Option<AudioSpec>,
+}
+
+impl AudioStream {
+    pub fn new() -> Self {
+        AudioStream {
+            sbuf: vec![0_u8; AUDIO_SBUF_SIZE].into_boxed_slice(),
+            head: 0,
+            count: 0,
+            spec: None,
+        }
+    }
+
+    pub fn take_spec(&mut self) -> Option<AudioSpec> {
+        self.spec.take()
+    }
+
+    // at most `max` bytes to play, the buffer frees them for the guest
+    pub fn pop(&mut self, max: usize) -> Vec<u8> {
+        let n = self.count.min(max);
+        let data: Vec<u8> = (0..n)
+            .map(|i| self.sbuf[(self.head + i) % AUDIO_SBUF_SIZE])
+            .collect();
+        self.head = (self.head + n) % AUDIO_SBUF_SIZE;
+        self.count -= n;
+        data
+    }
+}
+
+impl Default for AudioStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct DeviceAudio {
+    stream: Arc<Mutex<AudioStream>>,
+    freq: u32,
+    channels: u32,
+    samples: u32,
+    // the count of the last read
+    count_read: usize,
+}
+
+impl DeviceAudio {
+    pub fn new(stream: Arc<Mutex<AudioStream>>) -> Self {
+        DeviceAudio {
+            stream,
+            freq: 0,
+            channels: 0,
+            samples: 0,
+            count_read: 0,
+        }
+    }
+}
+
+impl DeviceBase for DeviceAudio {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "audio read len:{}", len);
+        let data = match addr {
+            FREQ => self.freq,
+            CHANNELS => self.channels,
+            SAMPLES => self.samples,
+            SBUF_SIZE => AUDIO_SBUF_SIZE as u32,
+            COUNT => {
+                self.count_read = self.stream.lock().unwrap().count;
+                self.count_read as u32
+            }
+            _ => 0,
+        };
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "audio write len:{}", len);
+        let data = data as u32;
+        match addr {
+            FREQ => self.freq = data,
+            CHANNELS => self.channels = data,
+            SAMPLES => self.samples = data,
+            // a new stream, the old samples are dropped
+            INIT if data == 1 => {
+                let mut stream = self.stream.lock().unwrap();
+                stream.head = 0;
+                stream.count = 0;
+                self.count_read = 0;
+                stream.spec = Some(AudioSpec {
+                    freq: self.freq,
+                    channels: self.channels as u8,
+                    samples: self.samples as u16,
+                });
+            }
+            // the host may have played some since the guest read count,
+            // only what the guest added to it counts
+            COUNT => {
+                let mut stream = self.stream.lock().unwrap();
+                let added = (data as usize).saturating_sub(self.count_read);
+                stream.count = (stream.count + added).min(AUDIO_SBUF_SIZE);
+                self.count_read = stream.count;
+            }
+            _ => {}
+        }
+        0
+    }
+
+    fn get_name(&self) -> &'static str {
+        "AM_AUDIO"
+    }
+}
+
+pub struct DeviceAudioSbuf {
+    stream: Arc<Mutex<AudioStream>>,
+}
+
+impl DeviceAudioSbuf {
+    pub fn new(stream: Arc<Mutex<AudioStream>>) -> Self {
+        DeviceAudioSbuf { stream }
+    }
+}
+
+impl DeviceBase for DeviceAudioSbuf {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        let stream = self.stream.lock().unwrap();
+        let mut data_bytes = 0_u64.to_le_bytes();
+        data_bytes[..len].copy_from_slice(&stream.sbuf[addr as usize..addr as usize + len]);
+        u64::from_le_bytes(data_bytes)
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        let mut stream = self.stream.lock().unwrap();
+        let data_bytes = data.to_le_bytes();
+        stream.sbuf[addr as usize..addr as usize + len].copy_from_slice(&data_bytes[..len]);
+        0
+    }
+
+    fn copy_from_slice(&mut self, addr: u64, slice: &[u8]) {
+        let mut stream = self.stream.lock().unwrap();
+        stream.sbuf[addr as usize..addr as usize + slice.len()].copy_from_slice(slice);
+    }
+
+    fn get_name(&self) -> &'static str {
+        "AM_AUDIO_SBUF"
+    }
+}
+
+#[cfg(test)]
+mod test_audio {
+    use std::sync::Mutex;
+
+    use alloc::sync::Arc;
+
+    use super::{AudioSpec, AudioStream, DeviceAudio, DeviceAudioSbuf, AUDIO_SBUF_SIZE};
+    use crate::device::device_trait::DeviceBase;
+
+    #[test]
+    fn audio_test() {
+        let stream = Arc::new(Mutex::new(AudioStream::new()));
+        let mut ctl = DeviceAudio::new(stream.clone());
+        let mut sbuf = DeviceAudioSbuf::new(stream.clone());
+        ctl.do_write(0x00, 8000, 4);
+        ctl.do_write(0x04, 1, 4);
+        ctl.do_write(0x08, 1024, 4);
+        ctl.do_write(0x10, 1, 4);
+        let spec = stream.lock().unwrap().take_spec();
+        assert_eq!(
+            spec,
+            Some(AudioSpec {
+                freq: 8000,
+                channels: 1,
+                samples: 1024
+            })
+        );
+        assert_eq!(ctl.do_read(0x0c, 4), AUDIO_SBUF_SIZE as u64);
+
+        // 4 bytes at the end and 4 after the wrap
+        stream.lock().unwrap().head = AUDIO_SBUF_SIZE - 4;
+        sbuf.do_write(AUDIO_SBUF_SIZE as u64 - 4, 0x0403_0201, 4);
+        sbuf.do_write(0, 0x0807_0605, 4);
+        let count = ctl.do_read(0x14, 4);
+        ctl.do_write(0x14, count + 8, 4);
+        // the host plays 2 bytes between the read and the write of the guest
+        let count = ctl.do_read(0x14, 4);
+        assert_eq!(stream.lock().unwrap().pop(2), [1, 2]);
+        ctl.do_write(0x14, count + 4, 4);
+        assert_eq!(ctl.do_read(0x14, 4), 10);
+        assert_eq!(stream.lock().unwrap().pop(8), [3, 4, 5, 6, 7, 8, 0, 0]);
+    }
+}
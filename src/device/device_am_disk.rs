@@ -0,0 +1,211 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+use log::warn;
+
+use super::device_trait::{BusMaster, DeviceBase};
+
+// the AM disk (AM_DISK_CONFIG, AM_DISK_STATUS, AM_DISK_BLKIO) on a host file
+// 0x00 PRESENT (R) 1 with a file
+// 0x04 BLKSZ   (R) bytes per block
+// 0x08 BLKCNT  (R) blocks of the file
+// 0x0c STATUS  (R) bit0: ready, bit1: the last command failed
+// 0x10 BUF     (W) the guest buffer, 64-bit or two 32-bit halves
+// 0x18 BLKNO   (W) the first block
+// 0x1c COUNT   (W) the blocks to move
+// 0x20 CMD     (W) 1: read into BUF, 2: write from BUF
+// the transfer is done by the next bus update, STATUS is not ready meanwhile
+pub const DISK_BLKSZ: usize = 512;
+pub const DISK_SIZE: u64 = 0x24;
+
+const PRESENT: u64 = 0x00;
+const BLKSZ: u64 = 0x04;
+const BLKCNT: u64 = 0x08;
+const STATUS: u64 = 0x0c;
+const BUF: u64 = 0x10;
+const BUF_HI: u64 = 0x14;
+const BLKNO: u64 = 0x18;
+const COUNT: u64 = 0x1c;
+const CMD: u64 = 0x20;
+
+const CMD_READ: u32 = 1;
+const CMD_WRITE: u32 = 2;
+const STATUS_READY: u32 = 1 << 0;
+const STATUS_ERROR: u32 = 1 << 1;
+
+pub struct DeviceDisk {
+    file: Option<File>,
+    blkcnt: u32,
+    buf: u64,
+    blkno: u32,
+    count: u32,
+    // the command waiting for the bus update
+    cmd: Option<u32>,
+    error: bool,
+}
+
+impl DeviceDisk {
+    // the size of the file is rounded down to whole blocks
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let blkcnt = (file.metadata()?.len() / DISK_BLKSZ as u64) as u32;
+        Ok(DeviceDisk {
+            file: Some(file),
+            blkcnt,
+            ..Self::new()
+        })
+    }
+
+    // a machine without a disk, PRESENT reads 0
+    pub fn new() -> Self {
+        DeviceDisk {
+            file: None,
+            blkcnt: 0,
+            buf: 0,
+            blkno: 0,
+            count: 0,
+            cmd: None,
+            error: false,
+        }
+    }
+
+    fn transfer(&mut self, cmd: u32, bus: &mut dyn BusMaster) -> bool {
+        let Some(file) = self.file.as_mut() else {
+            return false;
+        };
+        if self.blkno as u64 + self.count as u64 > self.blkcnt as u64 {
+            return false;
+        }
+        let mut data = vec![0_u8; self.count as usize * DISK_BLKSZ];
+        let offset = self.blkno as u64 * DISK_BLKSZ as u64;
+        let ret = file.seek(SeekFrom::Start(offset)).and_then(|_| match cmd {
+            CMD_READ => file
+                .read_exact(&mut data)
+                .map(|_| bus.dma_write(self.buf, &data)),
+            _ if !bus.dma_read(self.buf, &mut data) => Ok(false),
+            _ => file.write_all(&data).map(|_| true),
+        });
+        ret.unwrap_or_else(|e| {
+            warn!("disk io: {e}");
+            false
+        })
+    }
+}
+
+impl Default for DeviceDisk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceBase for DeviceDisk {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "disk read len:{}", len);
+        let data = match addr {
+            PRESENT => self.file.is_some() as u32,
+            BLKSZ => DISK_BLKSZ as u32,
+            BLKCNT => self.blkcnt,
+            STATUS => {
+                let ready = if self.cmd.is_none() { STATUS_READY } else { 0 };
+                ready | if self.error { STATUS_ERROR } else { 0 }
+            }
+            _ => 0,
+        };
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        match (addr, len) {
+            (BUF, 8) => self.buf = data,
+            (BUF, 4) => self.buf = self.buf & !0xffff_ffff | data & 0xffff_ffff,
+            (BUF_HI, 4) => self.buf = self.buf & 0xffff_ffff | data << 32,
+            (BLKNO, 4) => self.blkno = data as u32,
+            (COUNT, 4) => self.count = data as u32,
+            (CMD, 4) => {
+                let cmd = data as u32;
+                if matches!(cmd, CMD_READ | CMD_WRITE) {
+                    self.cmd = Some(cmd);
+                }
+            }
+            _ => {}
+        }
+        0
+    }
+
+    fn do_dma(&mut self, bus: &mut dyn BusMaster) {
+        if let Some(cmd) = self.cmd.take() {
+            self.error = !self.transfer(cmd, bus);
+        }
+    }
+
+    fn get_name(&self) -> &'static str {
+        "AM_DISK"
+    }
+}
+
+#[cfg(test)]
+mod test_disk {
+    use std::fs;
+
+    use alloc::boxed::Box;
+
+    use super::{DeviceDisk, DISK_BLKSZ, DISK_SIZE};
+    use crate::{
+        device::{device_memory::DeviceMemory, device_trait::MEM_BASE},
+        rv64core::bus::{Bus, DeviceType},
+    };
+
+    const DISK: u64 = 0xa000_0300;
+
+    #[test]
+    fn disk_test() {
+        // one file per test run, the runs may be in parallel
+        let path = std::env::temp_dir().join(format!("rv64emu_disk_test_{}", std::process::id()));
+        let image: alloc::vec::Vec<u8> = (0..4 * DISK_BLKSZ)
+            .map(|x| (x / DISK_BLKSZ) as u8)
+            .collect();
+        fs::write(&path, &image).unwrap();
+        let mut bus = Bus::new();
+        bus.add_device(DeviceType {
+            start: MEM_BASE,
+            len: 0x10000,
+            instance: Box::new(DeviceMemory::new(0x10000)),
+            name: "RAM",
+        })
+        .unwrap();
+        bus.add_device(DeviceType {
+            start: DISK,
+            len: DISK_SIZE,
+            instance: Box::new(DeviceDisk::open(path.to_str().unwrap()).unwrap()),
+            name: "AM_DISK",
+        })
+        .unwrap();
+        assert_eq!(bus.read(DISK, 4).ok(), Some(1));
+        assert_eq!(bus.read(DISK + 0x8, 4).ok(), Some(4));
+
+        // blocks 2 and 3 into memory
+        bus.write(DISK + 0x10, MEM_BASE, 8).unwrap();
+        bus.write(DISK + 0x18, 2, 4).unwrap();
+        bus.write(DISK + 0x1c, 2, 4).unwrap();
+        bus.write(DISK + 0x20, 1, 4).unwrap();
+        assert_eq!(bus.read(DISK + 0x0c, 4).ok(), Some(0));
+        bus.update(10);
+        assert_eq!(bus.read(DISK + 0x0c, 4).ok(), Some(1));
+        assert_eq!(bus.read(MEM_BASE + DISK_BLKSZ as u64, 1).ok(), Some(3));
+
+        // and back to block 0
+        bus.write(DISK + 0x18, 0, 4).unwrap();
+        bus.write(DISK + 0x1c, 1, 4).unwrap();
+        bus.write(DISK + 0x20, 2, 4).unwrap();
+        bus.update(10);
+        // past the end
+        bus.write(DISK + 0x18, 4, 4).unwrap();
+        bus.write(DISK + 0x20, 1, 4).unwrap();
+        bus.update(10);
+        assert_eq!(bus.read(DISK + 0x0c, 4).ok(), Some(0b11));
+        assert_eq!(fs::read(&path).unwrap()[..DISK_BLKSZ], [2; DISK_BLKSZ]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
-use sdl2::keyboard::{Keycode, Scancode};
-
-use crate::{device::device_trait::DeviceBase, tools::Fifobounded};
+use crate::{
+    device::{device_trait::DeviceBase, irq::IrqLine},
+    input::KeyEvent,
+    tools::Fifobounded,
+};
 
 // int keymap[256] = { 0,0,0,0,43,60,58,45,31,46,47,48,36,49,50,51,62,61,37,38,
 //     29,32,44,33,35,59,30,57,34,56,15,16,17,18,19,20,21,22,23,
@@ -27,51 +29,49 @@ const AM_KEYMAP: [u32; 256] = [
 
 const KEYDOWN_MASK: u32 = 0x8000;
 
-pub struct DeviceKbItem {
-    pub scancode: Scancode,
-    pub is_keydown: bool,
-}
+// the keymap is indexed by the HID usage id of the key
+pub fn get_am_keycode(event: &KeyEvent) -> u32 {
+    let am_code = AM_KEYMAP
+        .get(event.code.0 as usize)
+        .copied()
+        .unwrap_or_default();
+    let mask = match event.pressed {
+        true => KEYDOWN_MASK,
+        false => 0,
+    };
 
-impl DeviceKbItem {
-    pub fn get_am_keycode(&self) -> u32 {
-        let am_code = AM_KEYMAP[self.scancode as usize];
-        let mask = match self.is_keydown {
-            true => KEYDOWN_MASK,
-            false => 0,
-        };
-
-        if am_code != 0 {
-            am_code | mask
-        } else {
-            0
-        }
+    if am_code != 0 {
+        am_code | mask
+    } else {
+        0
     }
 }
 
 pub struct DeviceKB {
-    rx_am_key: Fifobounded<DeviceKbItem>,
-    rx_sdl_key: Fifobounded<Keycode>,
+    rx_am_key: Fifobounded<KeyEvent>,
+    // the key symbols of the pressed keys (SDL keycode values), read at offset 4
+    rx_key_sym: Fifobounded<u32>,
+    // raised while keys are waiting
+    irq: Option<IrqLine>,
 }
 
 impl DeviceKB {
-    pub fn new(rx_am_key: Fifobounded<DeviceKbItem>, rx_sdl_key: Fifobounded<Keycode>) -> Self {
+    pub fn new(rx_am_key: Fifobounded<KeyEvent>, rx_key_sym: Fifobounded<u32>) -> Self {
         DeviceKB {
             rx_am_key,
-            rx_sdl_key,
+            rx_key_sym,
+            irq: None,
         }
     }
 
     fn get_am_key(&mut self) -> u32 {
-        // self.rx_am_key
-        //     .try_recv()
-        //     .map_or(0, |item| item.get_am_keycode())
-
-        self.rx_am_key.pop().map_or(0, |item| item.get_am_keycode())
+        self.rx_am_key
+            .pop()
+            .map_or(0, |event| get_am_keycode(&event))
     }
 
-    fn get_sdl_key(&mut self) -> u32 {
-        // self.rx_sdl_key.try_recv().map_or(0, |k_code| k_code as u32)
-        self.rx_sdl_key.pop().map_or(0, |k_code| k_code as u32)
+    fn get_key_sym(&mut self) -> u32 {
+        self.rx_key_sym.pop().unwrap_or(0)
     }
 }
 
@@ -79,7 +79,7 @@ impl DeviceBase for DeviceKB {
     fn do_read(&mut self, addr: u64, _len: usize) -> u64 {
         match addr {
             0 => self.get_am_key() as u64,
-            4 => self.get_sdl_key() as u64,
+            4 => self.get_key_sym() as u64,
             _ => panic!("deviceKB read err {addr}"),
         }
     }
@@ -91,4 +91,45 @@ impl DeviceBase for DeviceKB {
     fn get_name(&self) -> &'static str {
         "AM_KeyBorad"
     }
+
+    fn do_update(&mut self) {
+        if let Some(irq) = &self.irq {
+            irq.set(!self.rx_am_key.is_empty());
+        }
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+}
+
+#[cfg(test)]
+mod test_am_kb {
+    use crate::{
+        device::device_trait::DeviceBase,
+        input::{KeyCode, KeyEvent},
+        tools::fifo_bounded_new,
+    };
+
+    use super::DeviceKB;
+
+    #[test]
+    fn am_keycode_test() {
+        let am_key = fifo_bounded_new(4);
+        let mut kb = DeviceKB::new(am_key.clone(), fifo_bounded_new(4));
+        let press = |code, pressed| am_key.push(KeyEvent { code, pressed }).unwrap();
+        press(KeyCode::A, true);
+        press(KeyCode::A, false);
+        press(KeyCode::ESCAPE, true);
+        // not in the AM keymap
+        press(KeyCode(0x200), true);
+
+        // AM_KEY_A = 43, AM_KEY_ESCAPE = 1
+        assert_eq!(kb.do_read(0, 4), 0x8000 | 43);
+        assert_eq!(kb.do_read(0, 4), 43);
+        assert_eq!(kb.do_read(0, 4), 0x8000 | 1);
+        assert_eq!(kb.do_read(0, 4), 0);
+        // AM_KEY_NONE when empty
+        assert_eq!(kb.do_read(0, 4), 0);
+    }
 }
@@ -1,7 +1,6 @@
-
 use device_trait::DeviceBase;
 
-use crate::tools::Fifobounded;
+use crate::{input::MouseState, tools::Fifobounded};
 
 use super::device_trait;
 
@@ -9,45 +8,28 @@ const MOUSE_KEY_OFFSET: u64 = 0;
 const POSITION_X_OFFSET: u64 = 8;
 const POSITION_Y_OFFSET: u64 = 12;
 
-pub struct DeviceMouseItem {
-    pub mouse_btn_state: u32,
-    pub x: u32,
-    pub y: u32,
-}
-
-impl DeviceMouseItem {
-    fn new() -> Self {
-        DeviceMouseItem {
-            mouse_btn_state: 0,
-            x: 0,
-            y: 0,
-        }
-    }
-}
-
 pub struct DeviceMouse {
-    rx_mouse: Fifobounded<DeviceMouseItem>,
-    mouse_state: DeviceMouseItem,
+    rx_mouse: Fifobounded<MouseState>,
+    mouse_state: MouseState,
 }
 
 impl DeviceMouse {
-    pub fn new(rx_mouse: Fifobounded<DeviceMouseItem>) -> Self {
+    pub fn new(rx_mouse: Fifobounded<MouseState>) -> Self {
         DeviceMouse {
             rx_mouse,
-            mouse_state: DeviceMouseItem::new(),
+            mouse_state: MouseState::default(),
         }
     }
 }
 
 impl DeviceBase for DeviceMouse {
     fn do_read(&mut self, addr: u64, len: usize) -> u64 {
-
         if let Some(item) = self.rx_mouse.pop() {
             self.mouse_state = item
         }
 
         match (addr, len) {
-            (MOUSE_KEY_OFFSET, _) => self.mouse_state.mouse_btn_state as u64,
+            (MOUSE_KEY_OFFSET, _) => self.mouse_state.buttons.0 as u64,
             (POSITION_X_OFFSET, _) => self.mouse_state.x as u64,
             (POSITION_Y_OFFSET, _) => self.mouse_state.y as u64,
             (addr, len) => panic!("DeviceMouse: addr:{addr},len:{len}"),
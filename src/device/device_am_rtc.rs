@@ -1,14 +1,32 @@
 use std::time::SystemTime;
 
-use super::device_trait::DeviceBase;
+use crate::{
+    error::RvEmuResult,
+    snapshot::{StateReader, StateWriter},
+};
+
+use super::{device_trait::DeviceBase, irq::IrqLine};
 
 pub struct DeviceRTC {
     pub rtc_time: u64,
+    // raised once per second, lowered by reading the time
+    irq: Option<IrqLine>,
+    last_second: u64,
 }
 
 impl DeviceRTC {
     pub fn new() -> Self {
-        DeviceRTC { rtc_time: 0 }
+        DeviceRTC {
+            rtc_time: 0,
+            irq: None,
+            last_second: 0,
+        }
+    }
+    fn now_micros() -> u64 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_micros() as u64
     }
 }
 
@@ -23,10 +41,10 @@ impl DeviceBase for DeviceRTC {
         assert_eq!(len, 4);
         match addr {
             0 => {
-                self.rtc_time = SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_micros() as u64;
+                self.rtc_time = Self::now_micros();
+                if let Some(irq) = &self.irq {
+                    irq.lower();
+                }
                 self.rtc_time as u32 as u64
             }
             4 => self.rtc_time >> 32,
@@ -41,6 +59,32 @@ impl DeviceBase for DeviceRTC {
     fn get_name(&self) -> &'static str {
         "AM_RTC"
     }
+
+    fn do_update(&mut self) {
+        if let Some(irq) = &self.irq {
+            let second = Self::now_micros() / 1_000_000;
+            if second != self.last_second {
+                self.last_second = second;
+                irq.raise();
+            }
+        }
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+
+    // the time of the last read, the next read takes the host time again
+    fn save_state(&self, out: &mut StateWriter) {
+        out.put_u64(self.rtc_time);
+        out.put_u64(self.last_second);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> RvEmuResult<()> {
+        self.rtc_time = input.get_u64()?;
+        self.last_second = input.get_u64()?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
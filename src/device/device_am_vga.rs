@@ -1,24 +1,171 @@
-use std::sync::Mutex;
+use std::{ops::Range, sync::Mutex};
 
 use alloc::sync::Arc;
 
-use crate::device::device_trait::DeviceBase;
+use crate::{
+    device::{
+        device_trait::DeviceBase,
+        device_vga_text::{VgaText, TEXT_FRAME_MODE},
+    },
+    error::{RvEmuError, RvEmuResult},
+    snapshot::{StateReader, StateWriter},
+};
 
-const VGA_H: usize = 300;
-const VGA_W: usize = 400;
-pub const VGA_BUF_SIZE: usize = VGA_H * VGA_W * 4;
+pub const VGA_H: usize = 300;
+pub const VGA_W: usize = 400;
+
+// the resolution of the frame, bpp 32 is ARGB8888, 16 is RGB565
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VgaMode {
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u32,
+}
+
+impl VgaMode {
+    // (width, height, bpp), from `Config::fb_size`
+    pub fn new((width, height, bpp): (u32, u32, u32)) -> Self {
+        VgaMode { width, height, bpp }
+    }
+
+    // bytes per line, the lines are packed
+    pub fn pitch(&self) -> usize {
+        self.width as usize * self.bpp as usize / 8
+    }
+
+    pub fn size(&self) -> usize {
+        self.height as usize * self.pitch()
+    }
+
+    fn is_valid(&self) -> bool {
+        let dims = [self.width, self.height];
+        dims.iter().all(|x| (1..=0xffff).contains(x)) && matches!(self.bpp, 16 | 32)
+    }
+}
+
+impl Default for VgaMode {
+    fn default() -> Self {
+        VgaMode::new((VGA_W as u32, VGA_H as u32, 32))
+    }
+}
+
+// the framebuffer shared with the window thread.
+// the lines written by the guest are recorded, so only they are
+// uploaded at the next present
+pub struct VgaFrame {
+    pub pixels: Box<[u8]>,
+    // the 80x25 text mode, shown instead of the pixels
+    pub text: VgaText,
+    mode: VgaMode,
+    text_mode: bool,
+    // a new mode of the guest, taken by the window
+    mode_changed: bool,
+    dirty: Vec<bool>,
+}
+
+impl VgaFrame {
+    // the buffer holds `mode`, the guest may switch to any mode that fits
+    pub fn new(mode: VgaMode) -> Self {
+        VgaFrame {
+            pixels: vec![0_u8; mode.size()].into_boxed_slice(),
+            text: VgaText::new(),
+            mode,
+            text_mode: false,
+            mode_changed: false,
+            // the first present uploads the whole frame
+            dirty: vec![true; mode.height as usize],
+        }
+    }
+
+    pub fn mode(&self) -> VgaMode {
+        self.mode
+    }
+
+    // false if the mode is not valid or does not fit the buffer
+    pub fn set_mode(&mut self, mode: VgaMode) -> bool {
+        if !mode.is_valid() || mode.size() > self.pixels.len() {
+            return false;
+        }
+        self.mode = mode;
+        self.mode_changed = true;
+        self.dirty = vec![true; mode.height as usize];
+        true
+    }
+
+    pub fn is_text_mode(&self) -> bool {
+        self.text_mode
+    }
+
+    // the whole screen is drawn again after a switch
+    pub fn set_text_mode(&mut self, on: bool) {
+        if on == self.text_mode {
+            return;
+        }
+        self.text_mode = on;
+        self.mode_changed = true;
+        self.text.mark_dirty();
+        self.dirty.fill(true);
+    }
+
+    // the mode of the window, TEXT_FRAME_MODE in the text mode
+    pub fn frame_mode(&self) -> VgaMode {
+        if self.text_mode {
+            TEXT_FRAME_MODE
+        } else {
+            self.mode
+        }
+    }
+
+    // a new `frame_mode` since the last call
+    pub fn take_mode_change(&mut self) -> Option<VgaMode> {
+        core::mem::take(&mut self.mode_changed).then(|| self.frame_mode())
+    }
+
+    // the bytes past the visible lines are kept but never presented
+    fn mark_dirty(&mut self, addr: usize, len: usize) {
+        let pitch = self.mode.pitch();
+        let first = addr / pitch;
+        let last = ((addr + len - 1) / pitch).min(self.dirty.len() - 1);
+        if first <= last {
+            self.dirty[first..=last].fill(true);
+        }
+    }
+
+    // the dirty lines since the last call, merged into ranges
+    pub fn take_dirty_lines(&mut self) -> Vec<Range<usize>> {
+        let mut ret: Vec<Range<usize>> = Vec::new();
+        for (line, dirty) in self.dirty.iter_mut().enumerate() {
+            if !*dirty {
+                continue;
+            }
+            *dirty = false;
+            match ret.last_mut() {
+                Some(range) if range.end == line => range.end += 1,
+                _ => ret.push(line..line + 1),
+            }
+        }
+        ret
+    }
+}
+
+impl Default for VgaFrame {
+    fn default() -> Self {
+        Self::new(VgaMode::default())
+    }
+}
 
 pub struct DeviceVGA {
-    pix_buff: Arc<Mutex<Box<[u8]>>>,
+    pix_buff: Arc<Mutex<VgaFrame>>,
 }
 
 impl DeviceVGA {
-    pub fn new(pix_buff: Arc<Mutex<Box<[u8]>>>) -> Self {
+    pub fn new(pix_buff: Arc<Mutex<VgaFrame>>) -> Self {
         DeviceVGA { pix_buff }
     }
 
-    pub fn get_size() -> usize {
-        VGA_BUF_SIZE
+    // the bytes of the buffer, mapped at FB_ADDR
+    pub fn get_size(&self) -> usize {
+        self.pix_buff.lock().unwrap().pixels.len()
     }
 
     pub fn updata_vga(&mut self) {}
@@ -29,7 +176,8 @@ impl DeviceBase for DeviceVGA {
         let mut data_bytes = 0_u64.to_le_bytes();
 
         let binding = self.pix_buff.lock().unwrap();
-        data_bytes[..(len)].copy_from_slice(&binding[(addr as usize)..(addr as usize + len)]);
+        data_bytes[..(len)]
+            .copy_from_slice(&binding.pixels[(addr as usize)..(addr as usize + len)]);
 
         u64::from_le_bytes(data_bytes)
     }
@@ -37,14 +185,94 @@ impl DeviceBase for DeviceVGA {
     fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
         let data_bytes = data.to_le_bytes();
         let mut binding = self.pix_buff.lock().unwrap();
-        binding[(addr as usize)..(addr as usize + len)].copy_from_slice(&data_bytes[..(len)]);
+        binding.pixels[(addr as usize)..(addr as usize + len)]
+            .copy_from_slice(&data_bytes[..(len)]);
+        binding.mark_dirty(addr as usize, len);
         drop(binding);
         data
     }
 
+    fn copy_from_slice(&mut self, addr: u64, slice: &[u8]) {
+        if slice.is_empty() {
+            return;
+        }
+        let mut binding = self.pix_buff.lock().unwrap();
+        binding.pixels[(addr as usize)..(addr as usize + slice.len())].copy_from_slice(slice);
+        binding.mark_dirty(addr as usize, slice.len());
+    }
+
     fn do_update(&mut self) {}
 
+    // the whole frame with its mode and the text cells, the window
+    // draws all of it again after a load
+    fn save_state(&self, out: &mut StateWriter) {
+        let frame = self.pix_buff.lock().unwrap();
+        let mode = frame.mode;
+        for x in [mode.width, mode.height, mode.bpp] {
+            out.put_u32(x);
+        }
+        out.put_bool(frame.text_mode);
+        out.put_bytes(&frame.pixels);
+        out.put_bytes(frame.text.cells());
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> RvEmuResult<()> {
+        let mut frame = self.pix_buff.lock().unwrap();
+        let mode = VgaMode::new((input.get_u32()?, input.get_u32()?, input.get_u32()?));
+        if !frame.set_mode(mode) {
+            return Err(RvEmuError::Snapshot(format!(
+                "vga mode {mode:?} does not fit"
+            )));
+        }
+        frame.text_mode = input.get_bool()?;
+        let size = frame.pixels.len();
+        frame
+            .pixels
+            .copy_from_slice(input.get_bytes_of(size, "vga frame")?);
+        let size = frame.text.cells().len();
+        let cells = input.get_bytes_of(size, "vga text")?;
+        frame.text.cells_mut().copy_from_slice(cells);
+        frame.text.mark_dirty();
+        Ok(())
+    }
+
+    // the mode is from `Config::fb_size`
+    fn dt_compatible(&self) -> Option<&'static str> {
+        Some("simple-framebuffer")
+    }
+
     fn get_name(&self) -> &'static str {
         "AM_VGA_FB"
     }
 }
+
+#[cfg(test)]
+mod test_vga {
+    use std::sync::{Arc, Mutex};
+
+    use crate::device::device_trait::DeviceBase;
+
+    use super::{DeviceVGA, VgaFrame, VGA_H};
+
+    #[test]
+    fn dirty_lines_test() {
+        let frame = Arc::new(Mutex::new(VgaFrame::default()));
+        let mut vga = DeviceVGA::new(frame.clone());
+        let vga_pitch = frame.lock().unwrap().mode().pitch();
+        assert_eq!(frame.lock().unwrap().take_dirty_lines(), vec![0..VGA_H]);
+        assert!(frame.lock().unwrap().take_dirty_lines().is_empty());
+
+        vga.do_write(0, 0xffff_ffff, 4);
+        vga.do_write(vga_pitch as u64 + 8, 0xffff_ffff, 8);
+        vga.do_write(10 * vga_pitch as u64, 0xffff_ffff, 4);
+        // a block spanning lines 20 and 21
+        vga.copy_from_slice(21 * vga_pitch as u64 - 4, &[0xff; 8]);
+        // reads do not change the frame
+        vga.do_read(50 * vga_pitch as u64, 4);
+
+        let mut frame = frame.lock().unwrap();
+        assert_eq!(frame.take_dirty_lines(), vec![0..2, 10..11, 20..22]);
+        assert_eq!(frame.pixels[vga_pitch + 8], 0xff);
+        assert!(frame.take_dirty_lines().is_empty());
+    }
+}
@@ -1,34 +1,91 @@
-use std::{
-    rc::Rc, cell::Cell,
-};
+use std::sync::{mpsc::SyncSender, Mutex};
 
+use alloc::sync::Arc;
 
+use crate::device::{
+    device_am_vga::{VgaFrame, VgaMode},
+    device_trait::DeviceBase,
+};
 
+// a sync request of the guest, received by the thread which owns the window.
+// use a `sync_channel(1)`, requests arriving before the last one is
+// presented are merged, the sim thread never blocks.
+type VgaCtlSender = SyncSender<()>;
 
-use crate::device::device_trait::DeviceBase;
+// 0x00 SIZE   (R) width << 16 | height of the current mode, as AM reads it
+// 0x04 SYNC   (W) present the frame
+// 0x08 WIDTH  (RW) the mode asked by the guest
+// 0x0c HEIGHT (RW)
+// 0x10 BPP    (RW) 16 or 32
+// 0x14 SET    (W) switch to the mode asked, WIDTH, HEIGHT and BPP read back
+//             the mode in use, the old one if the new one does not fit
+// 0x18 PITCH  (R) bytes per line of the current mode
+// 0x1c FBSIZE (R) bytes of the framebuffer at FB_ADDR
+// 0x20 TEXT   (RW) 1: the 80x25 text mode, the cells at VGA_TEXT_ADDR are
+//             shown instead of the framebuffer, no SYNC is needed
+pub const VGACTL_SIZE: u64 = 0x24;
 
-// type VgaCtlSender = Producer<bool, Rc<LocalRb<bool, Vec<MaybeUninit<bool>>>>>;
-type VgaCtlSender = Rc<Cell<bool>>;
+const SIZE: u64 = 0x00;
+const SYNC: u64 = 0x04;
+const WIDTH: u64 = 0x08;
+const HEIGHT: u64 = 0x0c;
+const BPP: u64 = 0x10;
+const SET: u64 = 0x14;
+const PITCH: u64 = 0x18;
+const FBSIZE: u64 = 0x1c;
+const TEXT: u64 = 0x20;
 
 pub struct DeviceVGACTL {
     tx: VgaCtlSender,
+    frame: Arc<Mutex<VgaFrame>>,
+    // the mode in WIDTH, HEIGHT and BPP
+    asked: VgaMode,
 }
 
 impl DeviceVGACTL {
-    pub fn new(tx: VgaCtlSender) -> Self {
-        DeviceVGACTL { tx }
+    pub fn new(tx: VgaCtlSender, frame: Arc<Mutex<VgaFrame>>) -> Self {
+        let asked = frame.lock().unwrap().mode();
+        DeviceVGACTL { tx, frame, asked }
     }
 }
 
 impl DeviceBase for DeviceVGACTL {
-    fn do_read(&mut self, _addr: u64, _len: usize) -> u64 {
-        panic!("should not read !!");
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "vgactl read len:{}", len);
+        let frame = self.frame.lock().unwrap();
+        let mode = frame.mode();
+        let data = match addr {
+            SIZE => mode.width << 16 | mode.height,
+            WIDTH => self.asked.width,
+            HEIGHT => self.asked.height,
+            BPP => self.asked.bpp,
+            PITCH => mode.pitch() as u32,
+            FBSIZE => frame.pixels.len() as u32,
+            TEXT => frame.is_text_mode() as u32,
+            _ => 0,
+        };
+        data as u64
     }
 
-    fn do_write(&mut self, addr: u64, _data: u64, len: usize) -> u64 {
-        assert_eq!(addr, 4);
-        assert_eq!(len, 4);
-        self.tx.set(true);
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "vgactl write len:{}", len);
+        let data = data as u32;
+        match addr {
+            // full: a frame is already waiting. disconnected: the window is closed
+            SYNC => {
+                let _ = self.tx.try_send(());
+            }
+            WIDTH => self.asked.width = data,
+            HEIGHT => self.asked.height = data,
+            BPP => self.asked.bpp = data,
+            SET => {
+                let mut frame = self.frame.lock().unwrap();
+                frame.set_mode(self.asked);
+                self.asked = frame.mode();
+            }
+            TEXT => self.frame.lock().unwrap().set_text_mode(data & 1 != 0),
+            _ => {}
+        }
         0
     }
 
@@ -36,3 +93,50 @@ impl DeviceBase for DeviceVGACTL {
         "AM_VGA_CTL"
     }
 }
+
+#[cfg(test)]
+mod test_vgactl {
+    use std::sync::{mpsc, Arc, Mutex};
+
+    use super::DeviceVGACTL;
+    use crate::device::{
+        device_am_vga::{VgaFrame, VgaMode},
+        device_trait::DeviceBase,
+        device_vga_text::TEXT_FRAME_MODE,
+    };
+
+    #[test]
+    fn vgactl_mode_test() {
+        let frame = Arc::new(Mutex::new(VgaFrame::new(VgaMode::new((640, 480, 16)))));
+        let (tx, rx) = mpsc::sync_channel(1);
+        let mut ctl = DeviceVGACTL::new(tx, frame.clone());
+        assert_eq!(ctl.do_read(0x00, 4), 640 << 16 | 480);
+        assert_eq!(frame.lock().unwrap().take_mode_change(), None);
+
+        // too large for the buffer, the old mode stays
+        ctl.do_write(0x10, 32, 4);
+        ctl.do_write(0x14, 1, 4);
+        assert_eq!(ctl.do_read(0x10, 4), 16);
+        assert_eq!(frame.lock().unwrap().take_mode_change(), None);
+
+        ctl.do_write(0x08, 320, 4);
+        ctl.do_write(0x0c, 240, 4);
+        ctl.do_write(0x10, 32, 4);
+        ctl.do_write(0x14, 1, 4);
+        assert_eq!(ctl.do_read(0x00, 4), 320 << 16 | 240);
+        assert_eq!(ctl.do_read(0x18, 4), 1280);
+        assert_eq!(ctl.do_read(0x1c, 4), 640 * 480 * 2);
+        let mut fb = frame.lock().unwrap();
+        assert_eq!(fb.take_mode_change(), Some(VgaMode::new((320, 240, 32))));
+        assert_eq!(fb.take_dirty_lines(), vec![0..240]);
+        drop(fb);
+
+        ctl.do_write(0x04, 1, 4);
+        assert!(rx.try_recv().is_ok());
+
+        ctl.do_write(0x20, 1, 4);
+        let mode = frame.lock().unwrap().take_mode_change();
+        assert_eq!(mode, Some(TEXT_FRAME_MODE));
+        assert_eq!(ctl.do_read(0x20, 4), 1);
+    }
+}
@@ -0,0 +1,598 @@
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use crate::{
+    error::RvEmuResult,
+    rv64core::{csr_regs::CsrRegs, csr_regs_define::XipIn},
+    tools::{RcCell, RcRefCell},
+};
+
+use super::{
+    device_imsic::Imsic,
+    device_trait::DeviceBase,
+    irq::{HartIrq, InterruptController, IrqLine, IrqTrigger},
+};
+
+// RISC-V AIA advanced PLIC, one interrupt domain without children.
+// in direct mode the domain drives the external interrupt of the harts
+// through its interrupt delivery controllers (IDC), in MSI mode it forwards
+// the interrupts to the IMSIC files of the harts. the MSIs are delivered to
+// the files directly, the MSI address registers are only kept for the guest.
+// ref: riscv-interrupts-1.0, chapter 4 (APLIC)
+pub const APLIC_SIZE: u64 = 0x8000;
+// sources 1..=63, like the PLIC
+pub const APLIC_NUM_SOURCES: u32 = 63;
+
+const DOMAINCFG: u64 = 0x0000;
+const SOURCECFG_BASE: u64 = 0x0004;
+const SOURCECFG_END: u64 = 0x0ffc;
+const MMSIADDRCFG: u64 = 0x1bc0;
+const SMSIADDRCFGH: u64 = 0x1bcc;
+const SETIP_BASE: u64 = 0x1c00;
+const SETIP_END: u64 = 0x1c7c;
+const SETIPNUM: u64 = 0x1cdc;
+const IN_CLRIP_BASE: u64 = 0x1d00;
+const IN_CLRIP_END: u64 = 0x1d7c;
+const CLRIPNUM: u64 = 0x1ddc;
+const SETIE_BASE: u64 = 0x1e00;
+const SETIE_END: u64 = 0x1e7c;
+const SETIENUM: u64 = 0x1edc;
+const CLRIE_BASE: u64 = 0x1f00;
+const CLRIE_END: u64 = 0x1f7c;
+const CLRIENUM: u64 = 0x1fdc;
+const SETIPNUM_LE: u64 = 0x2000;
+const SETIPNUM_BE: u64 = 0x2004;
+const GENMSI: u64 = 0x3000;
+const TARGET_BASE: u64 = 0x3004;
+const TARGET_END: u64 = 0x3ffc;
+const IDC_BASE: u64 = 0x4000;
+const IDC_PER_HART: u64 = 0x20;
+const IDC_END: u64 = APLIC_SIZE - 1;
+
+const IDC_IDELIVERY: u64 = 0x00;
+const IDC_IFORCE: u64 = 0x04;
+const IDC_ITHRESHOLD: u64 = 0x08;
+const IDC_TOPI: u64 = 0x18;
+const IDC_CLAIMI: u64 = 0x1c;
+
+// domaincfg: bits 31:24 read 0x80, IE, DM (1: MSI mode), BE is 0
+const DOMAINCFG_IE: u32 = 1 << 8;
+const DOMAINCFG_DM: u32 = 1 << 2;
+
+// source modes of sourcecfg
+const SM_INACTIVE: u32 = 0;
+const SM_DETACHED: u32 = 1;
+const SM_EDGE1: u32 = 4;
+const SM_EDGE0: u32 = 5;
+const SM_LEVEL1: u32 = 6;
+const SM_LEVEL0: u32 = 7;
+
+#[derive(Default)]
+struct AplicSource {
+    line: Option<IrqLine>,
+    sourcecfg: u32,
+    target: u32,
+    pending: bool,
+    enabled: bool,
+    // the rectified input at the last update
+    last: bool,
+}
+
+impl AplicSource {
+    fn active(&self) -> bool {
+        !matches!(self.sourcecfg, SM_INACTIVE)
+    }
+    fn level(&self) -> bool {
+        matches!(self.sourcecfg, SM_LEVEL1 | SM_LEVEL0)
+    }
+    // the input after the inversion of the *0 modes, detached sources
+    // only become pending by setip
+    fn rectified(&self) -> bool {
+        let input = self.line.as_ref().is_some_and(|x| x.level());
+        match self.sourcecfg {
+            SM_EDGE1 | SM_LEVEL1 => input,
+            SM_EDGE0 | SM_LEVEL0 => !input,
+            _ => false,
+        }
+    }
+    fn hart(&self) -> usize {
+        (self.target >> 18) as usize
+    }
+    // direct mode: the lower the priority number the higher the priority
+    fn iprio(&self) -> u32 {
+        self.target & 0xff
+    }
+    fn eiid(&self) -> u32 {
+        self.target & 0x7ff
+    }
+}
+
+struct AplicIdc {
+    eip: HartIrq,
+    idelivery: bool,
+    iforce: bool,
+    ithreshold: u32,
+}
+
+struct AplicDomain {
+    mmode: bool,
+    domaincfg: u32,
+    msiaddrcfg: [u32; 4],
+    // index 0 is the missing source 0
+    sources: Vec<AplicSource>,
+    idcs: Vec<AplicIdc>,
+    msi: Option<Imsic>,
+}
+
+impl AplicDomain {
+    fn msi_mode(&self) -> bool {
+        self.domaincfg & DOMAINCFG_DM != 0
+    }
+    fn source(&mut self, id: u32) -> Option<&mut AplicSource> {
+        match id {
+            1..=APLIC_NUM_SOURCES => Some(&mut self.sources[id as usize]),
+            _ => None,
+        }
+    }
+
+    // the pending bits follow the inputs, see 4.7 of the spec
+    fn sample(&mut self) {
+        let msi_mode = self.msi_mode();
+        for src in self.sources.iter_mut().skip(1).filter(|x| x.active()) {
+            let now = src.rectified();
+            // an EDGE1 pulse between two samples is latched by the line
+            let edges = src.line.as_ref().map_or(0, |x| x.take_edges());
+            let rising = (now && !src.last) || (src.sourcecfg == SM_EDGE1 && edges > 0);
+            src.last = now;
+            match (src.level(), msi_mode) {
+                // a level source in direct mode is pending while it is high
+                (true, false) => src.pending = now,
+                (true, true) => src.pending = (src.pending || rising) && now,
+                (false, _) => src.pending |= rising,
+            }
+        }
+        self.deliver();
+    }
+
+    fn deliver(&mut self) {
+        let enabled = self.domaincfg & DOMAINCFG_IE != 0;
+        if self.msi_mode() {
+            let Some(imsic) = self.msi.as_ref() else {
+                return;
+            };
+            let ready = self.sources.iter_mut().skip(1);
+            for src in ready.filter(|x| enabled && x.pending && x.enabled) {
+                src.pending = false;
+                imsic.send_msi(src.hart(), src.eiid());
+            }
+            return;
+        }
+        for hart in 0..self.idcs.len() {
+            let idc = &self.idcs[hart];
+            let level = enabled && idc.idelivery && (idc.iforce || self.top(hart).is_some());
+            idc.eip.set(level);
+        }
+    }
+
+    // direct mode: the pending and enabled source of the hart with the
+    // highest priority below the threshold, the lowest id on a tie
+    fn top(&self, hart: usize) -> Option<u32> {
+        let threshold = self.idcs[hart].ithreshold;
+        (1..=APLIC_NUM_SOURCES)
+            .map(|id| (id, &self.sources[id as usize]))
+            .filter(|(_, src)| src.active() && src.pending && src.enabled && src.hart() == hart)
+            .filter(|(_, src)| threshold == 0 || src.iprio() < threshold)
+            .min_by_key(|(id, src)| (src.iprio(), *id))
+            .map(|(id, _)| id)
+    }
+    fn topi(&self, hart: usize) -> u32 {
+        self.top(hart)
+            .map_or(0, |id| id << 16 | self.sources[id as usize].iprio())
+    }
+    // the claim clears the pending bit, a level source still high is
+    // pending again at the next sample
+    fn claimi(&mut self, hart: usize) -> u32 {
+        let topi = self.topi(hart);
+        match topi >> 16 {
+            0 => self.idcs[hart].iforce = false,
+            id => self.sources[id as usize].pending = false,
+        }
+        self.deliver();
+        topi
+    }
+
+    // a bit per source in the words of setip/in_clrip/setie/clrie
+    fn bits(&self, word: u64, f: impl Fn(&AplicSource) -> bool) -> u32 {
+        (0..32).fold(0, |acc, bit| {
+            let id = word as u32 * 32 + bit;
+            match id {
+                1..=APLIC_NUM_SOURCES if f(&self.sources[id as usize]) => acc | 1 << bit,
+                _ => acc,
+            }
+        })
+    }
+    fn set_bits(&mut self, word: u64, data: u32, f: impl Fn(&mut AplicSource)) {
+        for bit in (0..32).filter(|bit| data >> bit & 1 == 1) {
+            if let Some(src) = self.source(word as u32 * 32 + bit) {
+                f(src);
+            }
+        }
+    }
+
+    fn read(&self, addr: u64) -> u32 {
+        let source = |base: u64| ((addr - base) / 4 + 1) as usize;
+        match addr {
+            DOMAINCFG => 0x8000_0000 | self.domaincfg,
+            SOURCECFG_BASE..=SOURCECFG_END => self
+                .sources
+                .get(source(SOURCECFG_BASE))
+                .map_or(0, |x| x.sourcecfg),
+            MMSIADDRCFG..=SMSIADDRCFGH => self.msiaddrcfg[((addr - MMSIADDRCFG) / 4) as usize],
+            SETIP_BASE..=SETIP_END => self.bits((addr - SETIP_BASE) / 4, |x| x.pending),
+            IN_CLRIP_BASE..=IN_CLRIP_END => {
+                self.bits((addr - IN_CLRIP_BASE) / 4, |x| x.rectified())
+            }
+            SETIE_BASE..=SETIE_END => self.bits((addr - SETIE_BASE) / 4, |x| x.enabled),
+            TARGET_BASE..=TARGET_END => self
+                .sources
+                .get(source(TARGET_BASE))
+                .map_or(0, |x| x.target),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: u64, data: u32) {
+        let source = |base: u64| ((addr - base) / 4 + 1) as u32;
+        match addr {
+            DOMAINCFG => self.domaincfg = data & (DOMAINCFG_IE | DOMAINCFG_DM),
+            SOURCECFG_BASE..=SOURCECFG_END => {
+                // no child domains, D is 0 and the reserved modes are inactive
+                let mode = match data & 0x7 {
+                    SM_DETACHED | SM_EDGE1 | SM_EDGE0 | SM_LEVEL1 | SM_LEVEL0 => data & 0x7,
+                    _ => SM_INACTIVE,
+                };
+                if let Some(src) = self.source(source(SOURCECFG_BASE)) {
+                    *src = AplicSource {
+                        line: src.line.take(),
+                        sourcecfg: mode,
+                        target: src.target,
+                        ..Default::default()
+                    };
+                }
+            }
+            MMSIADDRCFG..=SMSIADDRCFGH => {
+                self.msiaddrcfg[((addr - MMSIADDRCFG) / 4) as usize] = data
+            }
+            SETIP_BASE..=SETIP_END => {
+                self.set_bits((addr - SETIP_BASE) / 4, data, |x| x.pending |= x.active())
+            }
+            SETIPNUM | SETIPNUM_LE => {
+                if let Some(src) = self.source(data) {
+                    src.pending |= src.active();
+                }
+            }
+            SETIPNUM_BE => {
+                if let Some(src) = self.source(data.swap_bytes()) {
+                    src.pending |= src.active();
+                }
+            }
+            IN_CLRIP_BASE..=IN_CLRIP_END => {
+                self.set_bits((addr - IN_CLRIP_BASE) / 4, data, |x| x.pending = false)
+            }
+            CLRIPNUM => {
+                if let Some(src) = self.source(data) {
+                    src.pending = false;
+                }
+            }
+            SETIE_BASE..=SETIE_END => {
+                self.set_bits((addr - SETIE_BASE) / 4, data, |x| x.enabled |= x.active())
+            }
+            SETIENUM => {
+                if let Some(src) = self.source(data) {
+                    src.enabled |= src.active();
+                }
+            }
+            CLRIE_BASE..=CLRIE_END => {
+                self.set_bits((addr - CLRIE_BASE) / 4, data, |x| x.enabled = false)
+            }
+            CLRIENUM => {
+                if let Some(src) = self.source(data) {
+                    src.enabled = false;
+                }
+            }
+            // an MSI from software, delivered at once
+            GENMSI => {
+                if let (true, Some(imsic)) = (self.msi_mode(), self.msi.as_ref()) {
+                    imsic.send_msi((data >> 18) as usize, data & 0x7ff);
+                }
+            }
+            TARGET_BASE..=TARGET_END => {
+                let msi_mode = self.msi_mode();
+                if let Some(src) = self.source(source(TARGET_BASE)) {
+                    src.target = match msi_mode {
+                        // hart index, guest index 0, eiid
+                        true => data & (0x3fff << 18 | 0x7ff),
+                        // hart index, iprio 0 reads as 1
+                        false => data & 0x3fff << 18 | (data & 0xff).max(1),
+                    };
+                }
+            }
+            _ => {}
+        }
+        self.deliver();
+    }
+}
+
+// the domain is shared by the bus, which samples the sources on every
+// update, and the MMIO window
+#[derive(Clone)]
+pub struct Aplic {
+    inner: RcRefCell<AplicDomain>,
+}
+
+impl Aplic {
+    // `msi` are the files of the level of the domain, for the MSI mode
+    pub fn new(mmode: bool, msi: Option<Imsic>) -> Self {
+        let domain = AplicDomain {
+            mmode,
+            domaincfg: 0,
+            msiaddrcfg: [0; 4],
+            sources: (0..=APLIC_NUM_SOURCES)
+                .map(|_| Default::default())
+                .collect(),
+            idcs: vec![],
+            msi,
+        };
+        Aplic {
+            inner: RcRefCell::new(RefCell::new(domain)),
+        }
+    }
+
+    // the IDC of the next hart
+    pub fn add_hart(&self, xip_share: RcCell<XipIn>) {
+        let mut domain = self.inner.borrow_mut();
+        let eip = HartIrq::external(xip_share, domain.mmode);
+        domain.idcs.push(AplicIdc {
+            eip,
+            idelivery: false,
+            iforce: false,
+            ithreshold: 0,
+        });
+    }
+
+    pub fn tick(&self) {
+        self.inner.borrow_mut().sample();
+    }
+}
+
+impl InterruptController for Aplic {
+    // the source mode comes from sourcecfg, `_trigger` is the one the
+    // device asks for, the guest configures it from the device tree
+    fn alloc_irq_line(&mut self, irq_id: u32, _trigger: IrqTrigger) -> IrqLine {
+        assert!(
+            irq_id > 0 && irq_id <= APLIC_NUM_SOURCES,
+            "invalid irq_id:{}",
+            irq_id
+        );
+        let mut domain = self.inner.borrow_mut();
+        let src = &mut domain.sources[irq_id as usize];
+        assert!(
+            src.line.is_none(),
+            "irq_id:{} is already registered",
+            irq_id
+        );
+        let line = IrqLine::new(irq_id);
+        src.line = Some(line.clone());
+        line
+    }
+}
+
+impl DeviceBase for Aplic {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "aplic read len:{}", len);
+        let mut domain = self.inner.borrow_mut();
+        let data = match addr {
+            IDC_BASE..=IDC_END => {
+                let hart = ((addr - IDC_BASE) / IDC_PER_HART) as usize;
+                if hart >= domain.idcs.len() {
+                    return 0;
+                }
+                let idc = &domain.idcs[hart];
+                match (addr - IDC_BASE) % IDC_PER_HART {
+                    IDC_IDELIVERY => idc.idelivery as u32,
+                    IDC_IFORCE => idc.iforce as u32,
+                    IDC_ITHRESHOLD => idc.ithreshold,
+                    IDC_TOPI => domain.topi(hart),
+                    IDC_CLAIMI => domain.claimi(hart),
+                    _ => 0,
+                }
+            }
+            _ => domain.read(addr),
+        };
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "aplic write len:{}", len);
+        let mut domain = self.inner.borrow_mut();
+        let data = data as u32;
+        match addr {
+            IDC_BASE..=IDC_END => {
+                let hart = ((addr - IDC_BASE) / IDC_PER_HART) as usize;
+                if let Some(idc) = domain.idcs.get_mut(hart) {
+                    match (addr - IDC_BASE) % IDC_PER_HART {
+                        IDC_IDELIVERY => idc.idelivery = data & 1 == 1,
+                        IDC_IFORCE => idc.iforce = data & 1 == 1,
+                        IDC_ITHRESHOLD => idc.ithreshold = data & 0xff,
+                        _ => {}
+                    }
+                }
+                domain.deliver();
+            }
+            _ => domain.write(addr, data),
+        }
+        0
+    }
+
+    // the lines of the devices and the harts stay wired
+    fn reset(&mut self) {
+        let mut domain = self.inner.borrow_mut();
+        domain.domaincfg = 0;
+        domain.msiaddrcfg = [0; 4];
+        for src in domain.sources.iter_mut() {
+            *src = AplicSource {
+                line: src.line.take(),
+                ..Default::default()
+            };
+        }
+        for idc in domain.idcs.iter_mut() {
+            idc.idelivery = false;
+            idc.iforce = false;
+            idc.ithreshold = 0;
+        }
+    }
+
+    fn get_name(&self) -> &'static str {
+        "APLIC"
+    }
+}
+
+pub const IMSIC_M_BASE: u64 = 0x2400_0000;
+pub const IMSIC_S_BASE: u64 = 0x2800_0000;
+pub const APLIC_S_BASE: u64 = 0x0d00_0000;
+
+// the AIA of a machine, instead of the PLIC: an IMSIC for M-mode and one
+// for S-mode, and an S-level APLIC for the wired interrupts of the devices
+#[derive(Clone)]
+pub struct Aia {
+    pub imsic_m: Imsic,
+    pub imsic_s: Imsic,
+    pub aplic: Aplic,
+}
+
+impl Aia {
+    pub fn new() -> Self {
+        let imsic_s = Imsic::new(false);
+        Aia {
+            imsic_m: Imsic::new(true),
+            aplic: Aplic::new(false, Some(imsic_s.clone())),
+            imsic_s,
+        }
+    }
+
+    // the interrupt files of a hart and their csrs
+    pub fn add_hart(
+        &self,
+        csr_regs: &mut CsrRegs,
+        xip_share: RcCell<XipIn>,
+        smode: bool,
+    ) -> RvEmuResult<()> {
+        self.imsic_m.add_hart(csr_regs, xip_share.clone())?;
+        if smode {
+            self.imsic_s.add_hart(csr_regs, xip_share.clone())?;
+            self.aplic.add_hart(xip_share);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Aia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test_aplic {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use crate::{
+        config::Config,
+        device::{
+            device_trait::DeviceBase,
+            irq::{InterruptController, IrqTrigger},
+        },
+        rv64core::{
+            csr_regs::CsrRegs,
+            csr_regs_define::XipIn,
+            inst::inst_base::PrivilegeLevels,
+            inst::inst_base::{CSR_SIREG, CSR_SISELECT, CSR_STOPEI},
+        },
+    };
+
+    use super::{Aia, Aplic};
+
+    const IDC0: u64 = 0x4000;
+
+    #[test]
+    fn aplic_direct_test() {
+        let xip = Rc::new(Cell::new(XipIn::new()));
+        let mut aplic = Aplic::new(false, None);
+        aplic.add_hart(xip.clone());
+        let uart = aplic.alloc_irq_line(10, IrqTrigger::Level);
+        let net = aplic.alloc_irq_line(1, IrqTrigger::Level);
+        // level high sources, 1 has priority 5 and 10 priority 2
+        aplic.do_write(0x4 * 10, 6, 4);
+        aplic.do_write(0x4, 6, 4);
+        aplic.do_write(0x3000 + 4 * 10, 2, 4);
+        aplic.do_write(0x3000 + 4, 5, 4);
+        aplic.do_write(0x1edc, 10, 4);
+        aplic.do_write(0x1edc, 1, 4);
+        aplic.do_write(0x0, 1 << 8, 4);
+        aplic.do_write(IDC0, 1, 4);
+
+        uart.raise();
+        net.raise();
+        aplic.tick();
+        assert!(xip.get().seip());
+        assert_eq!(aplic.do_read(0x1c00, 4), 1 << 10 | 1 << 1);
+        assert_eq!(aplic.do_read(IDC0 + 0x18, 4), 10 << 16 | 2);
+        // the threshold masks 1
+        aplic.do_write(IDC0 + 0x8, 3, 4);
+        assert_eq!(aplic.do_read(IDC0 + 0x1c, 4), 10 << 16 | 2);
+        assert!(!xip.get().seip());
+        // a level still high is pending again
+        aplic.tick();
+        assert!(xip.get().seip());
+        uart.lower();
+        aplic.tick();
+        assert!(!xip.get().seip());
+    }
+
+    #[test]
+    fn aplic_msi_test() {
+        let mut config = Config::new();
+        config.set_s_mode();
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        let xip = Rc::new(Cell::new(XipIn::new()));
+        let aia = Aia::new();
+        aia.add_hart(&mut csr, xip.clone(), true).unwrap();
+        let mut aplic = aia.aplic.clone();
+        let line = aplic.alloc_irq_line(3, IrqTrigger::Edge);
+
+        // source 3 is eiid 42 of hart 0
+        aplic.do_write(0x0, 1 << 8 | 1 << 2, 4);
+        aplic.do_write(0x4 * 3, 4, 4);
+        aplic.do_write(0x3000 + 4 * 3, 42, 4);
+        aplic.do_write(0x1edc, 3, 4);
+        let s = PrivilegeLevels::Supervisor;
+        csr.write(CSR_SISELECT as u64, 0x70, s).unwrap();
+        csr.write(CSR_SIREG as u64, 1, s).unwrap();
+        csr.write(CSR_SISELECT as u64, 0xc0, s).unwrap();
+        csr.write(CSR_SIREG as u64, 1 << 42, s).unwrap();
+
+        line.raise();
+        aplic.tick();
+        assert!(xip.get().seip());
+        assert_eq!(csr.read(CSR_STOPEI as u64, s).unwrap(), 42 << 16 | 42);
+        // the write claims it
+        csr.write(CSR_STOPEI as u64, 0, s).unwrap();
+        assert!(!xip.get().seip());
+        // the line stays high, no new edge
+        aplic.tick();
+        assert!(!xip.get().seip());
+        // the m-mode file is apart
+        assert!(!xip.get().meip());
+    }
+}
@@ -0,0 +1,93 @@
+use alloc::vec::Vec;
+
+use super::device_rom::{DeviceRom, RomWrite};
+
+// the reset vector of qemu virt, the harts start here and jump to the
+// firmware with a0 = mhartid, a1 = the fdt and a2 = the fw_dynamic_info of
+// OpenSBI (the kernel 2MiB after the firmware, in S-mode)
+pub const BOOT_ROM_ADDR: u64 = 0x1000;
+pub const BOOT_ROM_SIZE: u64 = 0x1000;
+// the dwords read by the stub, patched by the host when the fdt moves
+pub const BOOT_ROM_ENTRY_OFFSET: u64 = 24;
+pub const BOOT_ROM_FDT_OFFSET: u64 = 32;
+
+#[rustfmt::skip]
+const RESET_VECTOR: [u32; 6] = [
+    0x0000_0297, // 1: auipc t0, 0
+    0x0282_8613, //    addi  a2, t0, 40 (fw_dynamic_info)
+    0xf140_2573, //    csrr  a0, mhartid
+    0x0202_b583, //    ld    a1, 32(t0) (fdt)
+    0x0182_b283, //    ld    t0, 24(t0) (entry)
+    0x0002_8067, //    jr    t0
+];
+
+const FW_DYNAMIC_INFO_MAGIC: u64 = 0x4942_534f;
+const FW_DYNAMIC_INFO_VERSION: u64 = 2;
+const FW_DYNAMIC_NEXT_MODE_S: u64 = 1;
+const KERNEL_OFFSET: u64 = 0x20_0000;
+
+// the code, the entry and fdt dwords and the fw_dynamic_info
+pub fn boot_rom_image(entry: u64, fdt: u64) -> Vec<u8> {
+    let mut image: Vec<u8> = RESET_VECTOR.iter().flat_map(|x| x.to_le_bytes()).collect();
+    let info = [
+        entry,
+        fdt,
+        FW_DYNAMIC_INFO_MAGIC,
+        FW_DYNAMIC_INFO_VERSION,
+        // next_addr, next_mode, options
+        entry + KERNEL_OFFSET,
+        FW_DYNAMIC_NEXT_MODE_S,
+        0,
+        // boot_hart, any hart
+        u64::MAX,
+    ];
+    image.extend(info.iter().flat_map(|x| x.to_le_bytes()));
+    image
+}
+
+// a BOOT_ROM_SIZE rom holding `boot_rom_image`, the stores fault
+pub fn boot_rom(entry: u64, fdt: u64) -> DeviceRom {
+    let mut rom = DeviceRom::new(BOOT_ROM_SIZE as usize, RomWrite::Fault);
+    rom.load_binary(&boot_rom_image(entry, fdt)).unwrap();
+    rom
+}
+
+#[cfg(test)]
+mod test_boot_rom {
+    use alloc::{boxed::Box, rc::Rc};
+
+    use super::{boot_rom, BOOT_ROM_ADDR, BOOT_ROM_SIZE};
+    use crate::{
+        config::Config,
+        device::device_trait::MEM_BASE,
+        rv64core::{
+            bus::{Bus, DeviceType},
+            cpu_core::{CpuCoreBuild, CpuState},
+        },
+        tools::RcRefCell,
+    };
+
+    #[test]
+    fn boot_rom_test() {
+        let mut bus = Bus::new();
+        bus.add_device(DeviceType {
+            start: BOOT_ROM_ADDR,
+            len: BOOT_ROM_SIZE,
+            instance: Box::new(boot_rom(MEM_BASE, 0x8220_0000)),
+            name: "BOOT_ROM",
+        })
+        .unwrap();
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        let mut cpu = CpuCoreBuild::new(RcRefCell::new(bus.into()), Rc::new(config))
+            .with_boot_pc(BOOT_ROM_ADDR)
+            .with_hart_id(3)
+            .build();
+        cpu.cpu_state = CpuState::Running;
+        cpu.execute(6);
+        assert_eq!(cpu.npc, MEM_BASE);
+        assert_eq!(cpu.gpr.read(10), 3);
+        assert_eq!(cpu.gpr.read(11), 0x8220_0000);
+        assert_eq!(cpu.gpr.read(12), BOOT_ROM_ADDR + 40);
+    }
+}
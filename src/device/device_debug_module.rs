@@ -0,0 +1,137 @@
+use crate::{dbg::debug_module::DebugModule, tools::RcRefCell};
+
+use super::device_trait::DeviceBase;
+
+// the dmi registers 0x00..0x7f, 4 bytes each
+pub const DEBUG_MODULE_SIZE: u64 = 0x200;
+
+// the Debug Module on the bus, the dmi register `n` is at offset 4 * n.
+// it is the DM of the JTAG port, see `RVsim::debug_module`. a hart can not
+// debug itself: the accesses of the hart the DM controls read 0 and are ignored
+pub struct DeviceDebugModule {
+    dm: RcRefCell<DebugModule>,
+}
+
+impl DeviceDebugModule {
+    pub fn new(dm: RcRefCell<DebugModule>) -> Self {
+        DeviceDebugModule { dm }
+    }
+}
+
+impl DeviceBase for DeviceDebugModule {
+    fn do_read(&mut self, addr: u64, _len: usize) -> u64 {
+        self.dm.borrow_mut().dmi_read(addr >> 2).unwrap_or(0)
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, _len: usize) -> u64 {
+        self.dm
+            .borrow_mut()
+            .dmi_write(addr >> 2, data & 0xffff_ffff);
+        0
+    }
+
+    fn get_name(&self) -> &'static str {
+        "DEBUG_MODULE"
+    }
+}
+
+#[cfg(test)]
+mod test_debug_module {
+    use core::cell::RefCell;
+
+    use alloc::rc::Rc;
+
+    use super::DeviceDebugModule;
+    use crate::{
+        dbg::{debug_module::DebugModule, dm_interface::DebugModuleSlave},
+        device::device_trait::DeviceBase,
+    };
+
+    #[derive(Default)]
+    struct Hart {
+        gpr: [u64; 32],
+        fpr: [u64; 32],
+        halted: bool,
+    }
+
+    impl DebugModuleSlave for Hart {
+        fn read_gpr(&mut self, regno: usize) -> u64 {
+            self.gpr[regno]
+        }
+        fn write_gpr(&mut self, regno: usize, value: u64) {
+            self.gpr[regno] = value;
+        }
+        fn read_fpr(&mut self, regno: usize) -> Option<u64> {
+            Some(self.fpr[regno])
+        }
+        fn write_fpr(&mut self, regno: usize, value: u64) -> Option<()> {
+            self.fpr[regno] = value;
+            Some(())
+        }
+        fn read_memory(&mut self, _address: u64, _length: usize) -> Option<u64> {
+            None
+        }
+        fn write_memory(&mut self, _address: u64, _length: usize, _value: u64) -> Option<u64> {
+            None
+        }
+        fn read_csr(&mut self, _csr_addr: usize) -> u64 {
+            0
+        }
+        fn write_csr(&mut self, _csr_addr: usize, _value: u64) {}
+        fn set_haltreq(&mut self, val: bool) {
+            self.halted |= val;
+        }
+        fn resumereq(&mut self) {
+            self.halted = false;
+        }
+        fn halted(&mut self) -> bool {
+            self.halted
+        }
+        fn resume_ack(&mut self) -> bool {
+            !self.halted
+        }
+        fn set_reset_req(&mut self, _val: bool) {}
+        fn havereset(&mut self) -> bool {
+            false
+        }
+        fn clear_havereset(&mut self) {}
+    }
+
+    #[test]
+    fn debug_module_test() {
+        let hart = Rc::new(RefCell::new(Hart::default()));
+        let dm = Rc::new(RefCell::new(DebugModule::new(hart.clone())));
+        let mut device = DeviceDebugModule::new(dm);
+        let (data0, dmcontrol, dmstatus, abstractcs, command) = (0x10, 0x40, 0x44, 0x58, 0x5c);
+        let abstractauto = 0x60;
+        // access register, 64 bits, transfer, write
+        let write_reg = |regno: u32| 3 << 20 | 1 << 17 | 1 << 16 | regno;
+
+        // the hart has to be halted, cmderr is cleared by writing 1
+        device.do_write(dmcontrol, 1, 4);
+        device.do_write(data0, 0x1234, 4);
+        device.do_write(command, write_reg(0x1001) as u64, 4);
+        assert_eq!((device.do_read(abstractcs, 4) >> 8) & 0x7, 4);
+        device.do_write(abstractcs, 0x7 << 8, 4);
+
+        // haltreq, anyhalted
+        device.do_write(dmcontrol, 1 << 31 | 1, 4);
+        assert_ne!(device.do_read(dmstatus, 4) & 1 << 8, 0);
+        device.do_write(dmcontrol, 1, 4);
+
+        // x1 and x2 with aarpostincrement and autoexecdata, then f3
+        device.do_write(abstractauto, 1, 4);
+        device.do_write(command, (write_reg(0x1001) | 1 << 19) as u64, 4);
+        device.do_write(data0, 0x5678, 4);
+        device.do_write(abstractauto, 0, 4);
+        device.do_write(data0, 0xabcd, 4);
+        device.do_write(command, write_reg(0x1023) as u64, 4);
+        assert_eq!(device.do_read(abstractcs, 4) >> 8 & 0x7, 0);
+        assert_eq!(hart.borrow().gpr[1..3], [0x1234, 0x5678]);
+        assert_eq!(hart.borrow().fpr[3], 0xabcd);
+
+        // a borrowed hart is running the access itself
+        let _busy = hart.borrow_mut();
+        assert_eq!(device.do_read(dmstatus, 4), 0);
+    }
+}
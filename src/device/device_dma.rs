@@ -0,0 +1,230 @@
+use alloc::vec::Vec;
+
+use super::{
+    device_trait::{BusMaster, DeviceBase},
+    irq::IrqLine,
+};
+
+// a one channel DMA engine, a bus master copying memory to memory.
+// base + 0x00: SRC, 64-bit or two 32-bit halves
+// base + 0x08: DST
+// base + 0x10: LEN, in bytes
+// base + 0x14: CTRL, bit 0 START (reads 0), bit 1 IRQ_EN
+// base + 0x18: STATUS, bit 0 BUSY, bit 1 DONE, bit 2 ERROR,
+//              DONE and ERROR are cleared by writing 1
+// the registers are read-only while BUSY. the irq is high while DONE or
+// ERROR is set and IRQ_EN
+pub const DMA_SIZE: u64 = 0x1000;
+pub const DMA_IRQ: u32 = 2;
+// bytes moved on each bus update
+pub const DMA_BURST: usize = 4096;
+
+const SRC: u64 = 0x00;
+const DST: u64 = 0x08;
+const LEN: u64 = 0x10;
+const CTRL: u64 = 0x14;
+const STATUS: u64 = 0x18;
+
+const CTRL_START: u32 = 1 << 0;
+const CTRL_IRQ_EN: u32 = 1 << 1;
+const STATUS_BUSY: u32 = 1 << 0;
+const STATUS_DONE: u32 = 1 << 1;
+const STATUS_ERROR: u32 = 1 << 2;
+
+pub struct DeviceDma {
+    src: u64,
+    dst: u64,
+    len: u32,
+    ctrl: u32,
+    status: u32,
+    // the bytes copied of the running transfer
+    copied: u32,
+    buf: Vec<u8>,
+    irq: Option<IrqLine>,
+}
+
+impl DeviceDma {
+    pub fn new() -> Self {
+        DeviceDma {
+            src: 0,
+            dst: 0,
+            len: 0,
+            ctrl: 0,
+            status: 0,
+            copied: 0,
+            buf: vec![0; DMA_BURST],
+            irq: None,
+        }
+    }
+
+    fn busy(&self) -> bool {
+        self.status & STATUS_BUSY != 0
+    }
+
+    fn finish(&mut self, status: u32) {
+        self.status = self.status & !STATUS_BUSY | status;
+        self.update_irq();
+    }
+
+    fn update_irq(&self) {
+        let level = self.ctrl & CTRL_IRQ_EN != 0 && self.status & (STATUS_DONE | STATUS_ERROR) != 0;
+        if let Some(irq) = &self.irq {
+            irq.set(level);
+        }
+    }
+}
+
+impl Default for DeviceDma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a 64-bit register accessed as a whole or by halves
+fn half_write(reg: u64, offset: u64, data: u64, len: usize) -> u64 {
+    match (len, offset & 4) {
+        (8, _) => data,
+        (_, 0) => reg & !0xffff_ffff | data & 0xffff_ffff,
+        _ => reg & 0xffff_ffff | data << 32,
+    }
+}
+fn half_read(reg: u64, offset: u64, len: usize) -> u64 {
+    match len {
+        8 => reg,
+        _ => reg >> ((offset & 4) * 8) & 0xffff_ffff,
+    }
+}
+
+impl DeviceBase for DeviceDma {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        match addr & !3 {
+            SRC | 0x04 => half_read(self.src, addr, len),
+            DST | 0x0c => half_read(self.dst, addr, len),
+            LEN => self.len as u64,
+            CTRL => (self.ctrl & CTRL_IRQ_EN) as u64,
+            STATUS => self.status as u64,
+            _ => 0,
+        }
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        match addr & !3 {
+            STATUS => {
+                self.status &= !(data as u32 & (STATUS_DONE | STATUS_ERROR));
+            }
+            _ if self.busy() => {}
+            SRC | 0x04 => self.src = half_write(self.src, addr, data, len),
+            DST | 0x0c => self.dst = half_write(self.dst, addr, data, len),
+            LEN => self.len = data as u32,
+            CTRL => {
+                self.ctrl = data as u32 & CTRL_IRQ_EN;
+                if data as u32 & CTRL_START != 0 {
+                    self.status = STATUS_BUSY;
+                    self.copied = 0;
+                }
+            }
+            _ => {}
+        }
+        self.update_irq();
+        0
+    }
+
+    fn do_dma(&mut self, bus: &mut dyn BusMaster) {
+        if !self.busy() {
+            return;
+        }
+        let n = ((self.len - self.copied) as usize).min(DMA_BURST);
+        let offset = self.copied as u64;
+        let buf = &mut self.buf[..n];
+        let ok = n == 0
+            || bus.dma_read(self.src.wrapping_add(offset), buf)
+                && bus.dma_write(self.dst.wrapping_add(offset), buf);
+        match (ok, self.copied + n as u32 == self.len) {
+            (false, _) => self.finish(STATUS_ERROR),
+            (true, true) => self.finish(STATUS_DONE),
+            (true, false) => self.copied += n as u32,
+        }
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+
+    fn reset(&mut self) {
+        let irq = self.irq.take();
+        *self = DeviceDma {
+            irq,
+            ..DeviceDma::new()
+        };
+        self.update_irq();
+    }
+
+    fn get_name(&self) -> &'static str {
+        "DMA"
+    }
+}
+
+#[cfg(test)]
+mod test_dma {
+    use alloc::boxed::Box;
+
+    use super::{DeviceDma, DMA_BURST, DMA_IRQ, DMA_SIZE};
+    use crate::{
+        device::{device_memory::DeviceMemory, device_trait::MEM_BASE, irq::IrqTrigger},
+        rv64core::bus::{Bus, DeviceType},
+    };
+
+    const DMA: u64 = 0x1000_3000;
+
+    #[test]
+    fn dma_copy_test() {
+        let mut bus = Bus::new();
+        bus.add_device(DeviceType {
+            start: MEM_BASE,
+            len: 0x10000,
+            instance: Box::new(DeviceMemory::new(0x10000)),
+            name: "RAM",
+        })
+        .unwrap();
+        bus.add_device_with_irq(
+            DeviceType {
+                start: DMA,
+                len: DMA_SIZE,
+                instance: Box::new(DeviceDma::new()),
+                name: "DMA",
+            },
+            DMA_IRQ,
+            IrqTrigger::Level,
+        )
+        .unwrap();
+        let data: alloc::vec::Vec<u8> = (0..DMA_BURST + 16).map(|x| x as u8).collect();
+        bus.copy_from_slice(MEM_BASE, &data).unwrap();
+
+        bus.write(DMA, MEM_BASE, 8).unwrap();
+        bus.write(DMA + 0x8, (MEM_BASE + 0x8000) as u32 as u64, 4)
+            .unwrap();
+        bus.write(DMA + 0xc, (MEM_BASE + 0x8000) >> 32, 4).unwrap();
+        bus.write(DMA + 0x10, data.len() as u64, 4).unwrap();
+        bus.write(DMA + 0x14, 0b11, 4).unwrap();
+        assert_eq!(bus.read(DMA + 0x18, 4).ok(), Some(1));
+        // one burst per update, the registers are locked meanwhile
+        bus.update(10);
+        bus.write(DMA + 0x10, 0, 4).unwrap();
+        assert_eq!(bus.read(DMA + 0x10, 4).ok(), Some(data.len() as u64));
+        assert_eq!(bus.read(DMA + 0x18, 4).ok(), Some(1));
+        bus.update(10);
+        assert_eq!(bus.read(DMA + 0x18, 4).ok(), Some(0b10));
+        let mut copy = alloc::vec![0; data.len()];
+        bus.copy_to_slice(MEM_BASE + 0x8000, &mut copy).unwrap();
+        assert_eq!(copy, data);
+        // the irq reaches the plic on the same update
+        assert_eq!(bus.read(0x0c00_1000, 4).ok(), Some(1 << DMA_IRQ));
+
+        // a copy to nowhere
+        bus.write(DMA + 0x18, 0b10, 4).unwrap();
+        bus.write(DMA + 0x8, 0x10, 8).unwrap();
+        bus.write(DMA + 0x14, 0b01, 4).unwrap();
+        bus.update(10);
+        assert_eq!(bus.read(DMA + 0x18, 4).ok(), Some(0b100));
+    }
+}
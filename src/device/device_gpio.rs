@@ -0,0 +1,266 @@
+use alloc::boxed::Box;
+use core::cell::RefCell;
+
+use crate::tools::RcRefCell;
+
+use super::{device_trait::DeviceBase, irq::IrqLine};
+
+// a 32 pin GPIO block with the registers of the SiFive GPIO (FU540 manual,
+// chapter 12), the interrupts of all the pins on one irq line.
+// base + 0x00: input_val, the level of the pads
+// base + 0x04: input_en
+// base + 0x08: output_en
+// base + 0x0c: output_val
+// base + 0x10: pue, base + 0x14: ds, kept only
+// base + 0x18: rise_ie  base + 0x1c: rise_ip
+// base + 0x20: fall_ie  base + 0x24: fall_ip
+// base + 0x28: high_ie  base + 0x2c: high_ip
+// base + 0x30: low_ie   base + 0x34: low_ip
+// base + 0x40: out_xor
+// the *_ip bits are cleared by writing 1
+pub const GPIO_SIZE: u64 = 0x1000;
+pub const GPIO_PINS: u32 = 32;
+
+const INPUT_VAL: u64 = 0x00;
+const INPUT_EN: u64 = 0x04;
+const OUTPUT_EN: u64 = 0x08;
+const OUTPUT_VAL: u64 = 0x0c;
+const PUE: u64 = 0x10;
+const DS: u64 = 0x14;
+const RISE_IE: u64 = 0x18;
+const RISE_IP: u64 = 0x1c;
+const FALL_IE: u64 = 0x20;
+const FALL_IP: u64 = 0x24;
+const HIGH_IE: u64 = 0x28;
+const HIGH_IP: u64 = 0x2c;
+const LOW_IE: u64 = 0x30;
+const LOW_IP: u64 = 0x34;
+const OUT_XOR: u64 = 0x40;
+
+#[derive(Default)]
+struct GpioState {
+    // the levels the host drives on the pins
+    host_in: u32,
+    input_en: u32,
+    output_en: u32,
+    output_val: u32,
+    out_xor: u32,
+    pue: u32,
+    ds: u32,
+    // the input_val of the last update, for the edges
+    last_in: u32,
+    ie: [u32; 4],
+    ip: [u32; 4],
+    // the pins of the last `on_output` call
+    last_out: u32,
+    on_output: Option<Box<dyn FnMut(u32)>>,
+    irq: Option<IrqLine>,
+}
+
+// rise, fall, high, low
+const RISE: usize = 0;
+const FALL: usize = 1;
+const HIGH: usize = 2;
+const LOW: usize = 3;
+
+impl GpioState {
+    fn output(&self) -> u32 {
+        (self.output_val ^ self.out_xor) & self.output_en
+    }
+    // an output pin reads what it drives
+    fn input_val(&self) -> u32 {
+        (self.output() | self.host_in & !self.output_en) & self.input_en
+    }
+
+    fn sample(&mut self) {
+        let now = self.input_val();
+        self.ip[RISE] |= now & !self.last_in;
+        self.ip[FALL] |= !now & self.last_in & self.input_en;
+        self.ip[HIGH] |= now;
+        self.ip[LOW] |= !now & self.input_en;
+        self.last_in = now;
+        let pending = self
+            .ip
+            .iter()
+            .zip(self.ie.iter())
+            .any(|(ip, ie)| ip & ie != 0);
+        if let Some(irq) = &self.irq {
+            irq.set(pending);
+        }
+    }
+}
+
+// the device and the host side of the pins share one state, the clones
+// kept by the host drive the inputs and see the outputs
+#[derive(Clone)]
+pub struct DeviceGpio {
+    inner: RcRefCell<GpioState>,
+}
+
+impl DeviceGpio {
+    pub fn new() -> Self {
+        DeviceGpio {
+            inner: RcRefCell::new(RefCell::new(GpioState::default())),
+        }
+    }
+
+    // a button: the level of an input pin, the edges are latched at once
+    pub fn set_input(&self, pin: u32, level: bool) {
+        assert!(pin < GPIO_PINS, "gpio pin:{} does not exist", pin);
+        let mut gpio = self.inner.borrow_mut();
+        gpio.host_in = gpio.host_in & !(1 << pin) | (level as u32) << pin;
+        gpio.sample();
+    }
+
+    // the levels driven by the guest, a bit per pin, 0 if not an output
+    pub fn output(&self) -> u32 {
+        self.inner.borrow().output()
+    }
+    pub fn output_pin(&self, pin: u32) -> bool {
+        self.output() >> pin & 1 == 1
+    }
+
+    // a LED: `f` gets the output levels every time the guest changes them
+    pub fn on_output(&self, f: impl FnMut(u32) + 'static) {
+        self.inner.borrow_mut().on_output = Some(Box::new(f));
+    }
+
+    // the callback runs without the borrow, it may use the pins itself
+    fn notify_output(&self) {
+        let mut gpio = self.inner.borrow_mut();
+        let out = gpio.output();
+        if out == gpio.last_out {
+            return;
+        }
+        gpio.last_out = out;
+        let Some(mut f) = gpio.on_output.take() else {
+            return;
+        };
+        drop(gpio);
+        f(out);
+        let mut gpio = self.inner.borrow_mut();
+        gpio.on_output.get_or_insert(f);
+    }
+}
+
+impl Default for DeviceGpio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn ip_index(addr: u64) -> usize {
+    ((addr - RISE_IE) / 8) as usize
+}
+
+impl DeviceBase for DeviceGpio {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "gpio read len:{}", len);
+        let gpio = self.inner.borrow();
+        let data = match addr {
+            INPUT_VAL => gpio.input_val(),
+            INPUT_EN => gpio.input_en,
+            OUTPUT_EN => gpio.output_en,
+            OUTPUT_VAL => gpio.output_val,
+            PUE => gpio.pue,
+            DS => gpio.ds,
+            RISE_IE | FALL_IE | HIGH_IE | LOW_IE => gpio.ie[ip_index(addr)],
+            RISE_IP | FALL_IP | HIGH_IP | LOW_IP => gpio.ip[ip_index(addr)],
+            OUT_XOR => gpio.out_xor,
+            _ => 0,
+        };
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "gpio write len:{}", len);
+        let data = data as u32;
+        {
+            let mut gpio = self.inner.borrow_mut();
+            match addr {
+                INPUT_EN => gpio.input_en = data,
+                OUTPUT_EN => gpio.output_en = data,
+                OUTPUT_VAL => gpio.output_val = data,
+                PUE => gpio.pue = data,
+                DS => gpio.ds = data,
+                RISE_IE | FALL_IE | HIGH_IE | LOW_IE => gpio.ie[ip_index(addr)] = data,
+                RISE_IP | FALL_IP | HIGH_IP | LOW_IP => gpio.ip[ip_index(addr)] &= !data,
+                OUT_XOR => gpio.out_xor = data,
+                _ => {}
+            }
+            gpio.sample();
+        }
+        self.notify_output();
+        0
+    }
+
+    // the high and low interrupts stay pending while the level lasts
+    fn do_update(&mut self) {
+        self.inner.borrow_mut().sample();
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.inner.borrow_mut().irq = Some(irq);
+    }
+
+    fn reset(&mut self) {
+        let mut gpio = self.inner.borrow_mut();
+        *gpio = GpioState {
+            host_in: gpio.host_in,
+            on_output: gpio.on_output.take(),
+            irq: gpio.irq.take(),
+            ..Default::default()
+        };
+        gpio.sample();
+    }
+
+    fn get_name(&self) -> &'static str {
+        "GPIO"
+    }
+}
+
+#[cfg(test)]
+mod test_gpio {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use super::DeviceGpio;
+    use crate::device::{device_trait::DeviceBase, irq::IrqLine};
+
+    #[test]
+    fn gpio_test() {
+        let mut gpio = DeviceGpio::new();
+        let host = gpio.clone();
+        let irq = IrqLine::new(5);
+        gpio.connect_irq(irq.clone());
+        let leds = Rc::new(Cell::new(0));
+        let seen = leds.clone();
+        host.on_output(move |x| seen.set(x));
+
+        // pin 0 is a LED, active low
+        gpio.do_write(0x08, 1, 4);
+        gpio.do_write(0x40, 1, 4);
+        assert_eq!(leds.get(), 1);
+        gpio.do_write(0x0c, 1, 4);
+        assert_eq!(leds.get(), 0);
+        assert!(!host.output_pin(0));
+
+        // pin 3 is a button, a rising edge interrupt
+        gpio.do_write(0x04, 1 << 3, 4);
+        gpio.do_write(0x18, 1 << 3, 4);
+        host.set_input(3, true);
+        assert_eq!(gpio.do_read(0x00, 4), 1 << 3);
+        assert_eq!(gpio.do_read(0x1c, 4), 1 << 3);
+        assert!(irq.level());
+        gpio.do_write(0x1c, 1 << 3, 4);
+        assert!(!irq.level());
+        // the released button is a falling edge only
+        host.set_input(3, false);
+        assert_eq!(gpio.do_read(0x24, 4), 1 << 3);
+        assert!(!irq.level());
+        // a pin without input_en reads 0 and has no edges
+        host.set_input(4, true);
+        assert_eq!(gpio.do_read(0x00, 4), 0);
+        assert_eq!(gpio.do_read(0x1c, 4), 0);
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::{AsRawFd, FromRawFd, RawFd},
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::device::{device_trait::DeviceBase, irq::IrqLine};
+
+// a host file mapped shared (a memfd, a file in /dev/shm, ...) for the data
+// exchange with co-simulated host processes, which map the same file.
+// the file starts with a header page of the doorbells:
+// 0x00 the count of the rings of the guest, 0x04 the value of its last ring
+// 0x08 the count of the rings of the host,  0x0c the value of its last ring
+// a peer writes the value then increments the count, the rest of the file is
+// the window of the guest (`HostShm::window`).
+// the doorbell registers of the guest (`HostShm::doorbell`):
+// 0x00 DOORBELL (W) ring the host with the value
+// 0x04 STATUS   (RW) bit 0 the host rang, writing 1 clears it
+// 0x08 MSG      (R) the value of the last ring of the host
+// 0x0c IRQ_EN   (RW) bit 0, the irq is high while enabled and STATUS is set
+// 0x10 SIZE     (R) the bytes of the window
+pub const SHM_HEADER: usize = 0x1000;
+pub const SHM_DOORBELL_SIZE: u64 = 0x14;
+pub const HOST_SHM_IRQ: u32 = 3;
+
+const DOORBELL: u64 = 0x00;
+const STATUS: u64 = 0x04;
+const MSG: u64 = 0x08;
+const IRQ_EN: u64 = 0x0c;
+const SIZE: u64 = 0x10;
+
+const GUEST_BELL: usize = 0x00;
+const HOST_BELL: usize = 0x08;
+
+struct ShmMap {
+    ptr: *mut u8,
+    len: usize,
+    file: File,
+}
+
+impl ShmMap {
+    fn new(file: File) -> io::Result<Self> {
+        let len = file.metadata()?.len() as usize;
+        if len <= SHM_HEADER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the shared memory has no window past its header",
+            ));
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ShmMap {
+            ptr: ptr as *mut u8,
+            len,
+            file,
+        })
+    }
+
+    // the other processes change the header under us
+    fn header(&self, offset: usize) -> &AtomicU32 {
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU32) }
+    }
+
+    fn ring(&self, bell: usize, msg: u32) {
+        self.header(bell + 4).store(msg, Ordering::Relaxed);
+        self.header(bell).fetch_add(1, Ordering::Release);
+    }
+
+    fn window(&self, addr: u64, len: usize) -> *mut u8 {
+        assert!(SHM_HEADER + addr as usize + len <= self.len);
+        unsafe { self.ptr.add(SHM_HEADER + addr as usize) }
+    }
+}
+
+impl Drop for ShmMap {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+// the mapping, shared by the window, the doorbell and the host side
+#[derive(Clone)]
+pub struct HostShm {
+    map: Rc<ShmMap>,
+}
+
+impl HostShm {
+    // `path` is grown to the header and `size` bytes of window if shorter
+    pub fn open(path: &str, size: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Self::from_file(file, size)
+    }
+
+    // an anonymous memfd, `fd` is passed to the co-simulated process
+    #[cfg(target_os = "linux")]
+    pub fn memfd(size: usize) -> io::Result<Self> {
+        let fd = unsafe { libc::memfd_create(c"rv64emu-shm".as_ptr(), 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Self::from_file(unsafe { File::from_raw_fd(fd) }, size)
+    }
+
+    fn from_file(file: File, size: usize) -> io::Result<Self> {
+        let len = (SHM_HEADER + size) as u64;
+        if file.metadata()?.len() < len {
+            file.set_len(len)?;
+        }
+        Ok(HostShm {
+            map: Rc::new(ShmMap::new(file)?),
+        })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.map.file.as_raw_fd()
+    }
+
+    // the bytes of the window
+    pub fn size(&self) -> usize {
+        self.map.len - SHM_HEADER
+    }
+
+    // the doorbell of the host, for a peer in this process
+    pub fn ring(&self, msg: u32) {
+        self.map.ring(HOST_BELL, msg);
+    }
+
+    pub fn window(&self) -> HostShmWindow {
+        HostShmWindow {
+            map: self.map.clone(),
+        }
+    }
+
+    pub fn doorbell(&self) -> HostShmDoorbell {
+        HostShmDoorbell {
+            host_bell: self.map.header(HOST_BELL).load(Ordering::Acquire),
+            map: self.map.clone(),
+            pending: false,
+            irq_en: false,
+            irq: None,
+        }
+    }
+}
+
+pub struct HostShmWindow {
+    map: Rc<ShmMap>,
+}
+
+impl DeviceBase for HostShmWindow {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        let mut data_bytes = 0_u64.to_le_bytes();
+        let src = self.map.window(addr, len);
+        unsafe { core::ptr::copy_nonoverlapping(src, data_bytes.as_mut_ptr(), len) };
+        u64::from_le_bytes(data_bytes)
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        let dst = self.map.window(addr, len);
+        unsafe { core::ptr::copy_nonoverlapping(data.to_le_bytes().as_ptr(), dst, len) };
+        0
+    }
+
+    fn copy_from_slice(&mut self, addr: u64, slice: &[u8]) {
+        let dst = self.map.window(addr, slice.len());
+        unsafe { core::ptr::copy_nonoverlapping(slice.as_ptr(), dst, slice.len()) };
+    }
+
+    fn copy_to_slice(&mut self, addr: u64, slice: &mut [u8]) {
+        let src = self.map.window(addr, slice.len());
+        unsafe { core::ptr::copy_nonoverlapping(src, slice.as_mut_ptr(), slice.len()) };
+    }
+
+    fn get_name(&self) -> &'static str {
+        "HOST_SHM"
+    }
+}
+
+pub struct HostShmDoorbell {
+    map: Rc<ShmMap>,
+    // the host rings seen
+    host_bell: u32,
+    pending: bool,
+    irq_en: bool,
+    irq: Option<IrqLine>,
+}
+
+impl HostShmDoorbell {
+    fn update_irq(&self) {
+        if let Some(irq) = &self.irq {
+            irq.set(self.irq_en && self.pending);
+        }
+    }
+}
+
+impl DeviceBase for HostShmDoorbell {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "host shm read len:{}", len);
+        let data = match addr {
+            STATUS => self.pending as u32,
+            MSG => self.map.header(HOST_BELL + 4).load(Ordering::Relaxed),
+            IRQ_EN => self.irq_en as u32,
+            SIZE => (self.map.len - SHM_HEADER) as u32,
+            _ => 0,
+        };
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "host shm write len:{}", len);
+        match addr {
+            DOORBELL => self.map.ring(GUEST_BELL, data as u32),
+            STATUS if data & 1 != 0 => self.pending = false,
+            IRQ_EN => self.irq_en = data & 1 != 0,
+            _ => {}
+        }
+        self.update_irq();
+        0
+    }
+
+    // the rings of the host processes are polled
+    fn do_update(&mut self) {
+        let host_bell = self.map.header(HOST_BELL).load(Ordering::Acquire);
+        if host_bell != self.host_bell {
+            self.host_bell = host_bell;
+            self.pending = true;
+        }
+        self.update_irq();
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+
+    fn reset(&mut self) {
+        self.pending = false;
+        self.irq_en = false;
+        self.update_irq();
+    }
+
+    fn get_name(&self) -> &'static str {
+        "HOST_SHM_DOORBELL"
+    }
+}
+
+#[cfg(test)]
+mod test_host_shm {
+    use std::sync::atomic::Ordering;
+
+    use super::{HostShm, GUEST_BELL, SHM_HEADER};
+    use crate::device::{device_trait::DeviceBase, irq::IrqLine};
+
+    #[test]
+    fn host_shm_test() {
+        // one file per test run, the runs may be in parallel
+        let path =
+            std::env::temp_dir().join(format!("rv64emu_host_shm_test_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let shm = HostShm::open(path, 0x1000).unwrap();
+        // the co-simulated process
+        let peer = HostShm::open(path, 0x1000).unwrap();
+        let mut window = shm.window();
+        let mut doorbell = shm.doorbell();
+        let irq = IrqLine::new(3);
+        doorbell.connect_irq(irq.clone());
+        assert_eq!(doorbell.do_read(0x10, 4), 0x1000);
+
+        window.do_write(0x10, 0x1122_3344_5566_7788, 8);
+        assert_eq!(peer.window().do_read(0x14, 4), 0x1122_3344);
+        doorbell.do_write(0x00, 42, 4);
+        let bell = peer.map.header(GUEST_BELL).load(Ordering::Acquire);
+        assert_eq!(bell, 1);
+        assert_eq!(peer.map.header(GUEST_BELL + 4).load(Ordering::Relaxed), 42);
+
+        peer.window().do_write(0xff8, 0xabcd, 2);
+        peer.ring(7);
+        doorbell.do_update();
+        assert!(!irq.level());
+        doorbell.do_write(0x0c, 1, 4);
+        assert!(irq.level());
+        assert_eq!(doorbell.do_read(0x08, 4), 7);
+        assert_eq!(window.do_read(0xff8, 2), 0xabcd);
+        doorbell.do_write(0x04, 1, 4);
+        assert!(!irq.level());
+        doorbell.do_update();
+        assert_eq!(doorbell.do_read(0x04, 4), 0);
+
+        assert_eq!(
+            std::fs::metadata(path).unwrap().len(),
+            SHM_HEADER as u64 + 0x1000
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn host_shm_memfd_test() {
+        let shm = HostShm::memfd(0x2000).unwrap();
+        assert!(shm.fd() >= 0);
+        assert_eq!(shm.size(), 0x2000);
+    }
+}
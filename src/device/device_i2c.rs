@@ -0,0 +1,399 @@
+use alloc::{boxed::Box, format, vec::Vec};
+use core::cell::Cell;
+
+use crate::{
+    error::{RvEmuError, RvEmuResult},
+    tools::RcCell,
+};
+
+use super::{device_trait::DeviceBase, irq::IrqLine};
+
+// the OpenCores I2C master, like the SiFive I2C (registers every 4 bytes).
+// base + 0x00: PRERlo, base + 0x04: PRERhi, the prescaler, kept only
+// base + 0x08: CTR, bit 7 EN, bit 6 IEN
+// base + 0x0c: TXR when written, RXR when read
+// base + 0x10: CR when written, STA STO RD WR ACK - - IACK
+//              SR when read, RxACK BUSY AL - - - TIP IF
+// a command is done at once, TIP never reads 1
+pub const I2C_SIZE: u64 = 0x1000;
+
+const PRERLO: u64 = 0x00;
+const PRERHI: u64 = 0x04;
+const CTR: u64 = 0x08;
+const TXR_RXR: u64 = 0x0c;
+const CR_SR: u64 = 0x10;
+
+const CTR_EN: u8 = 1 << 7;
+const CTR_IEN: u8 = 1 << 6;
+
+const CR_STA: u8 = 1 << 7;
+const CR_STO: u8 = 1 << 6;
+const CR_RD: u8 = 1 << 5;
+const CR_WR: u8 = 1 << 4;
+const CR_IACK: u8 = 1 << 0;
+
+const SR_RXACK: u8 = 1 << 7;
+const SR_BUSY: u8 = 1 << 6;
+const SR_IF: u8 = 1 << 0;
+
+// a device on the bus, seen by the master through its 7-bit address.
+// the acks are true when the slave pulls SDA low
+pub trait I2cSlave {
+    fn address(&self) -> u8;
+    // a start (or repeated start) with the address of the slave
+    fn start(&mut self, _read: bool) -> bool {
+        true
+    }
+    fn write(&mut self, data: u8) -> bool;
+    fn read(&mut self) -> u8;
+    fn stop(&mut self) {}
+}
+
+pub struct DeviceI2c {
+    prescale: u16,
+    ctr: u8,
+    txr: u8,
+    rxr: u8,
+    sr: u8,
+    slaves: Vec<Box<dyn I2cSlave>>,
+    // the slave of the transfer since the last start
+    target: Option<usize>,
+    irq: Option<IrqLine>,
+}
+
+impl DeviceI2c {
+    pub fn new() -> Self {
+        DeviceI2c {
+            prescale: 0xffff,
+            ctr: 0,
+            txr: 0,
+            rxr: 0,
+            sr: 0,
+            slaves: vec![],
+            target: None,
+            irq: None,
+        }
+    }
+
+    // two slaves may not answer the same address
+    pub fn attach(&mut self, slave: Box<dyn I2cSlave>) -> RvEmuResult<()> {
+        let addr = slave.address();
+        if addr > 0x7f || self.slaves.iter().any(|x| x.address() == addr) {
+            return Err(RvEmuError::BadConfig(format!(
+                "i2c address {addr:#x} is invalid or in use"
+            )));
+        }
+        self.slaves.push(slave);
+        Ok(())
+    }
+
+    fn command(&mut self, cr: u8) {
+        if self.ctr & CTR_EN == 0 {
+            return;
+        }
+        if cr & CR_IACK != 0 {
+            self.sr &= !SR_IF;
+        }
+        let mut done = false;
+        if cr & CR_STA != 0 && cr & CR_WR != 0 {
+            // the byte after a start is the address
+            let (addr, read) = (self.txr >> 1, self.txr & 1 == 1);
+            self.target = self.slaves.iter().position(|x| x.address() == addr);
+            let ack = match self.target {
+                Some(idx) => self.slaves[idx].start(read),
+                None => false,
+            };
+            self.set_ack(ack);
+            self.sr |= SR_BUSY;
+            done = true;
+        } else if cr & CR_WR != 0 {
+            let ack = match self.target {
+                Some(idx) => self.slaves[idx].write(self.txr),
+                None => false,
+            };
+            self.set_ack(ack);
+            done = true;
+        } else if cr & CR_RD != 0 {
+            // nobody drives SDA, it floats high
+            self.rxr = match self.target {
+                Some(idx) => self.slaves[idx].read(),
+                None => 0xff,
+            };
+            done = true;
+        }
+        if cr & CR_STO != 0 {
+            if let Some(idx) = self.target.take() {
+                self.slaves[idx].stop();
+            }
+            self.sr &= !SR_BUSY;
+            done = true;
+        }
+        if done {
+            self.sr |= SR_IF;
+        }
+        self.update_irq();
+    }
+
+    fn set_ack(&mut self, ack: bool) {
+        match ack {
+            true => self.sr &= !SR_RXACK,
+            false => self.sr |= SR_RXACK,
+        }
+    }
+
+    fn update_irq(&self) {
+        if let Some(irq) = &self.irq {
+            irq.set(self.ctr & CTR_IEN != 0 && self.sr & SR_IF != 0);
+        }
+    }
+}
+
+impl Default for DeviceI2c {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceBase for DeviceI2c {
+    fn do_read(&mut self, addr: u64, _len: usize) -> u64 {
+        let data = match addr {
+            PRERLO => self.prescale as u8,
+            PRERHI => (self.prescale >> 8) as u8,
+            CTR => self.ctr,
+            TXR_RXR => self.rxr,
+            CR_SR => self.sr,
+            _ => 0,
+        };
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, _len: usize) -> u64 {
+        let data = data as u8;
+        match addr {
+            PRERLO => self.prescale = self.prescale & 0xff00 | data as u16,
+            PRERHI => self.prescale = self.prescale & 0xff | (data as u16) << 8,
+            CTR => {
+                self.ctr = data & (CTR_EN | CTR_IEN);
+                self.update_irq();
+            }
+            TXR_RXR => self.txr = data,
+            CR_SR => self.command(data),
+            _ => {}
+        }
+        0
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+
+    fn reset(&mut self) {
+        if let Some(idx) = self.target.take() {
+            self.slaves[idx].stop();
+        }
+        self.prescale = 0xffff;
+        self.ctr = 0;
+        self.sr = 0;
+        self.update_irq();
+    }
+
+    fn get_name(&self) -> &'static str {
+        "I2C"
+    }
+}
+
+// a 24c02 like EEPROM of up to 256 bytes: the first byte written after the
+// address is the word address, the next ones are stored from it, and the
+// reads go on from it, with a wrap at the end
+pub struct I2cEeprom {
+    addr: u8,
+    data: Vec<u8>,
+    pointer: u8,
+    // the next write is the word address
+    addressing: bool,
+}
+
+impl I2cEeprom {
+    pub fn new(addr: u8, data: Vec<u8>) -> Self {
+        assert!(
+            !data.is_empty() && data.len() <= 256,
+            "eeprom of {} bytes",
+            data.len()
+        );
+        I2cEeprom {
+            addr,
+            data,
+            pointer: 0,
+            addressing: false,
+        }
+    }
+    fn advance(&mut self) {
+        self.pointer = ((self.pointer as usize + 1) % self.data.len()) as u8;
+    }
+}
+
+impl I2cSlave for I2cEeprom {
+    fn address(&self) -> u8 {
+        self.addr
+    }
+    fn start(&mut self, read: bool) -> bool {
+        self.addressing = !read;
+        true
+    }
+    fn write(&mut self, data: u8) -> bool {
+        if self.addressing {
+            self.pointer = (data as usize % self.data.len()) as u8;
+            self.addressing = false;
+        } else {
+            self.data[self.pointer as usize] = data;
+            self.advance();
+        }
+        true
+    }
+    fn read(&mut self) -> u8 {
+        let data = self.data[self.pointer as usize];
+        self.advance();
+        data
+    }
+}
+
+// an LM75 temperature sensor: a pointer byte, then the 16-bit registers
+// msb first, 0: temperature 1: config 2: thyst 3: tos, in 1/256 degree
+// with 0.5 degree resolution. the host sets the temperature through the
+// cell of `temperature`
+pub struct I2cLm75 {
+    addr: u8,
+    temperature: RcCell<i16>,
+    regs: [u16; 4],
+    pointer: usize,
+    // the bytes of the current access, the pointer byte first on a write
+    index: usize,
+}
+
+impl I2cLm75 {
+    pub fn new(addr: u8) -> Self {
+        I2cLm75 {
+            addr,
+            temperature: RcCell::new(Cell::new(25 << 8)),
+            // the reset values of thyst and tos, 75 and 80 degrees
+            regs: [0, 0, 75 << 8, 80 << 8],
+            pointer: 0,
+            index: 0,
+        }
+    }
+    // the temperature in 1/256 degree, shared with the host
+    pub fn temperature(&self) -> RcCell<i16> {
+        self.temperature.clone()
+    }
+    fn reg(&self, idx: usize) -> u16 {
+        match idx {
+            0 => self.temperature.get() as u16 & 0xff80,
+            _ => self.regs[idx],
+        }
+    }
+}
+
+impl I2cSlave for I2cLm75 {
+    fn address(&self) -> u8 {
+        self.addr
+    }
+    fn start(&mut self, _read: bool) -> bool {
+        self.index = 0;
+        true
+    }
+    fn write(&mut self, data: u8) -> bool {
+        match self.index {
+            0 => self.pointer = data as usize & 3,
+            // the temperature is read-only, config is one byte
+            1 | 2 if self.pointer != 0 => {
+                let reg = &mut self.regs[self.pointer];
+                *reg = match (self.pointer, self.index) {
+                    (1, _) => data as u16,
+                    (_, 1) => *reg & 0xff | (data as u16) << 8,
+                    _ => *reg & 0xff00 | data as u16 & 0x80,
+                };
+            }
+            _ => {}
+        }
+        self.index += 1;
+        true
+    }
+    fn read(&mut self) -> u8 {
+        let reg = self.reg(self.pointer);
+        let data = match (self.pointer, self.index % 2) {
+            (1, _) => reg as u8,
+            (_, 0) => (reg >> 8) as u8,
+            _ => reg as u8,
+        };
+        self.index += 1;
+        data
+    }
+}
+
+#[cfg(test)]
+mod test_i2c {
+    use alloc::{boxed::Box, vec};
+
+    use super::{DeviceI2c, I2cEeprom, I2cLm75};
+    use crate::device::{device_trait::DeviceBase, irq::IrqLine};
+
+    const CTR: u64 = 0x08;
+    const TXR: u64 = 0x0c;
+    const CR: u64 = 0x10;
+    const STA_WR: u64 = 0x90;
+    const WR: u64 = 0x10;
+    const RD: u64 = 0x20;
+    const RD_NACK_STO: u64 = 0x68;
+    const STO: u64 = 0x40;
+
+    // (txr, cr) pairs, then the status
+    fn xfer(i2c: &mut DeviceI2c, txr: u8, cr: u64) -> (u8, u8) {
+        i2c.do_write(TXR, txr as u64, 1);
+        i2c.do_write(CR, cr | 1, 1);
+        (i2c.do_read(TXR, 1) as u8, i2c.do_read(CR, 1) as u8)
+    }
+
+    #[test]
+    fn i2c_test() {
+        let mut i2c = DeviceI2c::new();
+        let irq = IrqLine::new(4);
+        i2c.connect_irq(irq.clone());
+        i2c.attach(Box::new(I2cEeprom::new(0x50, vec![0; 256])))
+            .unwrap();
+        let lm75 = I2cLm75::new(0x48);
+        let temperature = lm75.temperature();
+        i2c.attach(Box::new(lm75)).unwrap();
+        assert!(i2c.attach(Box::new(I2cLm75::new(0x48))).is_err());
+        i2c.do_write(CTR, 0xc0, 1);
+
+        // write "hi" at word 0x10 of the eeprom
+        let (_, sr) = xfer(&mut i2c, 0x50 << 1, STA_WR);
+        assert_eq!(sr & 0xc1, 0x41);
+        assert!(irq.level());
+        xfer(&mut i2c, 0x10, WR);
+        xfer(&mut i2c, b'h', WR);
+        let (_, sr) = xfer(&mut i2c, b'i', WR | STO);
+        assert_eq!(sr & 0xc0, 0);
+        // and read it back
+        xfer(&mut i2c, 0x50 << 1, STA_WR);
+        xfer(&mut i2c, 0x10, WR);
+        xfer(&mut i2c, 0x50 << 1 | 1, STA_WR);
+        assert_eq!(xfer(&mut i2c, 0, RD).0, b'h');
+        assert_eq!(xfer(&mut i2c, 0, RD_NACK_STO).0, b'i');
+        i2c.do_write(CR, 1, 1);
+        assert!(!irq.level());
+
+        // 21.5 degrees from the sensor
+        temperature.set(21 << 8 | 0xc0);
+        xfer(&mut i2c, 0x48 << 1, STA_WR);
+        xfer(&mut i2c, 0, WR);
+        xfer(&mut i2c, 0x48 << 1 | 1, STA_WR);
+        assert_eq!(xfer(&mut i2c, 0, RD).0, 21);
+        assert_eq!(xfer(&mut i2c, 0, RD_NACK_STO).0, 0x80);
+
+        // nobody at 0x20
+        let (_, sr) = xfer(&mut i2c, 0x20 << 1, STA_WR);
+        assert_eq!(sr & 0x80, 0x80);
+        assert_eq!(xfer(&mut i2c, 0, STO).1 & 0x40, 0);
+    }
+}
@@ -0,0 +1,263 @@
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+
+use crate::{
+    error::RvEmuResult,
+    rv64core::{
+        csr_regs::CsrRegs,
+        csr_regs_define::XipIn,
+        inst::inst_base::{
+            CSR_MIREG, CSR_MISELECT, CSR_MTOPEI, CSR_SIREG, CSR_SISELECT, CSR_STOPEI,
+        },
+    },
+    tools::{RcCell, RcRefCell},
+};
+
+use super::{device_trait::DeviceBase, irq::HartIrq};
+
+// RISC-V AIA incoming MSI controller: an interrupt file per hart and level.
+// a device signals interrupt identity N by writing N to the seteipnum of the
+// file, the hart reads the top one through *topei.
+// ref: riscv-interrupts-1.0, chapter 3 (IMSIC)
+//
+// the page of a file:
+// base + 0x000: seteipnum_le
+// base + 0x004: seteipnum_be
+pub const IMSIC_PAGE_SIZE: u64 = 0x1000;
+pub const IMSIC_MAX_HARTS: u64 = 16;
+// identities 1..=255, identity 0 does not exist
+pub const IMSIC_NUM_IDS: u32 = 255;
+
+const SETEIPNUM_LE: u64 = 0x0;
+const SETEIPNUM_BE: u64 = 0x4;
+
+// the registers reached through *iselect/*ireg
+const ISELECT_EIDELIVERY: u64 = 0x70;
+const ISELECT_EITHRESHOLD: u64 = 0x72;
+const ISELECT_EIP0: u64 = 0x80;
+const ISELECT_EIP63: u64 = 0xbf;
+const ISELECT_EIE0: u64 = 0xc0;
+const ISELECT_EIE63: u64 = 0xff;
+
+const WORDS: usize = (IMSIC_NUM_IDS as usize + 1) / 64;
+
+pub struct ImsicFile {
+    irq: HartIrq,
+    eidelivery: bool,
+    eithreshold: u32,
+    eip: [u64; WORDS],
+    eie: [u64; WORDS],
+}
+
+impl ImsicFile {
+    pub fn new(xip_share: RcCell<XipIn>, mmode: bool) -> Self {
+        ImsicFile {
+            irq: HartIrq::external(xip_share, mmode),
+            eidelivery: false,
+            eithreshold: 0,
+            eip: [0; WORDS],
+            eie: [0; WORDS],
+        }
+    }
+
+    // the pending identity 0 and the ones past the file are dropped
+    pub fn set_pending(&mut self, id: u32) {
+        if id > 0 && id <= IMSIC_NUM_IDS {
+            self.eip[id as usize / 64] |= 1 << (id % 64);
+            self.update_xip();
+        }
+    }
+
+    // the lowest pending and enabled identity below the threshold,
+    // the lower the identity the higher the priority
+    pub fn top(&self) -> Option<u32> {
+        (1..=IMSIC_NUM_IDS)
+            .take_while(|id| self.eithreshold == 0 || *id < self.eithreshold)
+            .find(|id| {
+                let (word, bit) = (*id as usize / 64, id % 64);
+                (self.eip[word] & self.eie[word]) >> bit & 1 == 1
+            })
+    }
+
+    // *topei: the identity in bits 26:16 and its priority (the same
+    // number) in bits 10:0
+    pub fn topei(&self) -> u64 {
+        self.top().map_or(0, |id| (id as u64) << 16 | id as u64)
+    }
+    // a write of *topei claims the top identity, whatever the value
+    pub fn claim(&mut self) {
+        if let Some(id) = self.top() {
+            self.eip[id as usize / 64] &= !(1 << (id % 64));
+            self.update_xip();
+        }
+    }
+
+    // rv64: the odd eip/eie registers do not exist, an unknown iselect
+    // reads zero and ignores the writes
+    pub fn ireg_read(&self, iselect: u64) -> u64 {
+        match iselect {
+            ISELECT_EIDELIVERY => self.eidelivery as u64,
+            ISELECT_EITHRESHOLD => self.eithreshold as u64,
+            ISELECT_EIP0..=ISELECT_EIP63 if iselect & 1 == 0 => {
+                let word = ((iselect - ISELECT_EIP0) / 2) as usize;
+                self.eip.get(word).copied().unwrap_or(0)
+            }
+            ISELECT_EIE0..=ISELECT_EIE63 if iselect & 1 == 0 => {
+                let word = ((iselect - ISELECT_EIE0) / 2) as usize;
+                self.eie.get(word).copied().unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+    pub fn ireg_write(&mut self, iselect: u64, data: u64) {
+        match iselect {
+            ISELECT_EIDELIVERY => self.eidelivery = data & 1 == 1,
+            ISELECT_EITHRESHOLD => self.eithreshold = (data as u32).min(IMSIC_NUM_IDS),
+            ISELECT_EIP0..=ISELECT_EIP63 if iselect & 1 == 0 => {
+                let word = ((iselect - ISELECT_EIP0) / 2) as usize;
+                if let Some(x) = self.eip.get_mut(word) {
+                    // identity 0 is hardwired to zero
+                    *x = if word == 0 { data & !1 } else { data };
+                }
+            }
+            ISELECT_EIE0..=ISELECT_EIE63 if iselect & 1 == 0 => {
+                let word = ((iselect - ISELECT_EIE0) / 2) as usize;
+                if let Some(x) = self.eie.get_mut(word) {
+                    *x = if word == 0 { data & !1 } else { data };
+                }
+            }
+            _ => return,
+        }
+        self.update_xip();
+    }
+
+    // a file with the delivery off leaves the external interrupt to the
+    // APLIC in direct mode
+    fn update_xip(&self) {
+        self.irq.set(self.eidelivery && self.top().is_some());
+    }
+
+    fn reset(&mut self) {
+        self.eidelivery = false;
+        self.eithreshold = 0;
+        self.eip = [0; WORDS];
+        self.eie = [0; WORDS];
+    }
+}
+
+// the files of one level, a page per hart in hart order
+#[derive(Clone)]
+pub struct Imsic {
+    mmode: bool,
+    files: RcRefCell<Vec<RcRefCell<ImsicFile>>>,
+}
+
+impl Imsic {
+    pub fn new(mmode: bool) -> Self {
+        Imsic {
+            mmode,
+            files: RcRefCell::new(RefCell::new(vec![])),
+        }
+    }
+    pub fn size(&self) -> u64 {
+        IMSIC_PAGE_SIZE * IMSIC_MAX_HARTS
+    }
+
+    // the file of the next hart, with the *iselect, *ireg and *topei
+    // csrs of its level on `csr_regs`
+    pub fn add_hart(&self, csr_regs: &mut CsrRegs, xip_share: RcCell<XipIn>) -> RvEmuResult<()> {
+        let file = RcRefCell::new(RefCell::new(ImsicFile::new(xip_share, self.mmode)));
+        let (iselect_addr, ireg_addr, topei_addr) = match self.mmode {
+            true => (CSR_MISELECT, CSR_MIREG, CSR_MTOPEI),
+            false => (CSR_SISELECT, CSR_SIREG, CSR_STOPEI),
+        };
+        let iselect = RcCell::new(Cell::new(0u64));
+        let (r, w) = (iselect.clone(), iselect.clone());
+        csr_regs.register_csr(iselect_addr, (move || r.get(), move |x| w.set(x & 0xfff)))?;
+        let (r, w) = (file.clone(), file.clone());
+        let (sel_r, sel_w) = (iselect.clone(), iselect);
+        csr_regs.register_csr(
+            ireg_addr,
+            (
+                move || r.borrow().ireg_read(sel_r.get()),
+                move |x| w.borrow_mut().ireg_write(sel_w.get(), x),
+            ),
+        )?;
+        let (r, w) = (file.clone(), file.clone());
+        csr_regs.register_csr(
+            topei_addr,
+            (move || r.borrow().topei(), move |_| w.borrow_mut().claim()),
+        )?;
+        self.files.borrow_mut().push(file);
+        Ok(())
+    }
+
+    // an MSI from a device on the machine, to the file of hart `hart`
+    pub fn send_msi(&self, hart: usize, id: u32) {
+        if let Some(file) = self.files.borrow().get(hart) {
+            file.borrow_mut().set_pending(id);
+        }
+    }
+}
+
+impl DeviceBase for Imsic {
+    // seteipnum reads zero
+    fn do_read(&mut self, _addr: u64, _len: usize) -> u64 {
+        0
+    }
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        let id = match (addr % IMSIC_PAGE_SIZE, len) {
+            (SETEIPNUM_LE, 4) => data as u32,
+            (SETEIPNUM_BE, 4) => (data as u32).swap_bytes(),
+            _ => return 0,
+        };
+        self.send_msi((addr / IMSIC_PAGE_SIZE) as usize, id);
+        0
+    }
+    fn reset(&mut self) {
+        let files = self.files.borrow();
+        files.iter().for_each(|file| file.borrow_mut().reset());
+    }
+    fn get_name(&self) -> &'static str {
+        "IMSIC"
+    }
+}
+
+#[cfg(test)]
+mod test_imsic {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use crate::rv64core::csr_regs_define::XipIn;
+
+    use super::ImsicFile;
+
+    #[test]
+    fn imsic_file_test() {
+        let xip = Rc::new(Cell::new(XipIn::new()));
+        let mut file = ImsicFile::new(xip.clone(), false);
+        file.ireg_write(0xc0, 0xffff_ffff);
+        file.ireg_write(0xc2, 1 << (70 - 64));
+        file.set_pending(70);
+        file.set_pending(9);
+        // nothing is delivered before eidelivery
+        assert!(!xip.get().seip());
+        assert_eq!(file.topei(), 9 << 16 | 9);
+        file.ireg_write(0x70, 1);
+        assert!(xip.get().seip());
+
+        // the threshold masks the identities from it upwards
+        file.ireg_write(0x72, 9);
+        assert!(!xip.get().seip());
+        assert_eq!(file.topei(), 0);
+        file.ireg_write(0x72, 0);
+        file.claim();
+        assert_eq!(file.topei(), 70 << 16 | 70);
+        assert_eq!(file.ireg_read(0x82), 1 << (70 - 64));
+        // the odd registers do not exist on rv64
+        assert_eq!(file.ireg_read(0x83), 0);
+        file.claim();
+        assert!(!xip.get().seip());
+        assert_eq!(file.ireg_read(0x80), 0);
+    }
+}
@@ -1,7 +1,12 @@
-use alloc::{vec::Vec, boxed::Box};
+use alloc::{boxed::Box, vec::Vec};
 use log::info;
 
-use crate::device::device_trait::DeviceBase;
+use crate::{
+    device::device_trait::{BusMaster, DeviceBase, PmaAttr},
+    error::{RvEmuError, RvEmuResult},
+    snapshot::{StateReader, StateWriter},
+    tools::{rc_refcell_new, RcRefCell},
+};
 
 pub struct DeviceMemory {
     data: Box<[u8]>,
@@ -20,9 +25,17 @@ impl DeviceMemory {
     pub fn size(&self) -> usize {
         self.data.len()
     }
-    pub fn load_binary(&mut self, slice: &[u8]) {
+    pub fn load_binary(&mut self, slice: &[u8]) -> RvEmuResult<()> {
+        if slice.len() > self.data.len() {
+            return Err(RvEmuError::ImageLoad(format!(
+                "binary of {:#x} bytes does not fit in memory of {:#x} bytes",
+                slice.len(),
+                self.data.len()
+            )));
+        }
         self.copy_from_slice(0, slice);
         info!("load binary success: {:#x} bytes", slice.len());
+        Ok(())
     }
 }
 
@@ -48,6 +61,103 @@ impl DeviceBase for DeviceMemory {
         slice.copy_from_slice(&self.data[(addr as usize)..(addr as usize + slice.len())]);
     }
 
+    fn pma(&self) -> PmaAttr {
+        PmaAttr::MEMORY
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.put_bytes(&self.data);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> RvEmuResult<()> {
+        let data = input.get_bytes_of(self.data.len(), "memory")?;
+        self.data.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn get_name(&self) -> &'static str {
+        "memory"
+    }
+}
+
+// RAM shared by the bus and the devices reading and writing guest memory
+// themselves (virtio), `start` is the bus address of the memory
+#[derive(Clone)]
+pub struct SharedMemory {
+    start: u64,
+    mem: RcRefCell<DeviceMemory>,
+}
+
+impl SharedMemory {
+    pub fn new(start: u64, size: usize) -> Self {
+        SharedMemory {
+            start,
+            mem: rc_refcell_new(DeviceMemory::new(size)),
+        }
+    }
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+    pub fn size(&self) -> usize {
+        self.mem.borrow().size()
+    }
+    pub fn load_binary(&self, slice: &[u8]) -> RvEmuResult<()> {
+        self.mem.borrow_mut().load_binary(slice)
+    }
+    fn offset(&self, addr: u64, len: usize) -> Option<u64> {
+        let offset = addr.checked_sub(self.start)?;
+        let end = offset.checked_add(len as u64)?;
+        (end <= self.size() as u64).then_some(offset)
+    }
+    // false if a byte is outside the memory, nothing is read then
+    pub fn read_bytes(&self, addr: u64, buf: &mut [u8]) -> bool {
+        let Some(offset) = self.offset(addr, buf.len()) else {
+            return false;
+        };
+        self.mem.borrow_mut().copy_to_slice(offset, buf);
+        true
+    }
+    pub fn write_bytes(&self, addr: u64, buf: &[u8]) -> bool {
+        let Some(offset) = self.offset(addr, buf.len()) else {
+            return false;
+        };
+        self.mem.borrow_mut().copy_from_slice(offset, buf);
+        true
+    }
+}
+
+// the memory alone, for the devices with a DMA outside the bus update
+impl BusMaster for SharedMemory {
+    fn dma_read(&mut self, addr: u64, buf: &mut [u8]) -> bool {
+        self.read_bytes(addr, buf)
+    }
+    fn dma_write(&mut self, addr: u64, buf: &[u8]) -> bool {
+        self.write_bytes(addr, buf)
+    }
+}
+
+impl DeviceBase for SharedMemory {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        self.mem.borrow_mut().do_read(addr, len)
+    }
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        self.mem.borrow_mut().do_write(addr, data, len)
+    }
+    fn copy_from_slice(&mut self, addr: u64, slice: &[u8]) {
+        self.mem.borrow_mut().copy_from_slice(addr, slice);
+    }
+    fn copy_to_slice(&mut self, addr: u64, slice: &mut [u8]) {
+        self.mem.borrow_mut().copy_to_slice(addr, slice);
+    }
+    fn pma(&self) -> PmaAttr {
+        PmaAttr::MEMORY
+    }
+    fn save_state(&self, out: &mut StateWriter) {
+        self.mem.borrow().save_state(out);
+    }
+    fn load_state(&mut self, input: &mut StateReader) -> RvEmuResult<()> {
+        self.mem.borrow_mut().load_state(input)
+    }
     fn get_name(&self) -> &'static str {
         "memory"
     }
@@ -0,0 +1,161 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+};
+
+use log::warn;
+
+use super::device_trait::DeviceBase;
+
+// guest-to-host byte stream, the host side can be a regular file,
+// a named pipe (mkfifo) or an inherited fd (/dev/fd/N)
+// 0x0 TXDATA  (W) push one byte to the host
+// 0x4 STATUS  (R) bit0: host side is open, bit1: host write error
+// 0x8 CTRL    (W) bit0: flush, bit1: close the host side
+// 0xc TXCOUNT (R) number of bytes accepted so far (low 32 bits)
+const PIPE_TXDATA: u64 = 0x0;
+const PIPE_STATUS: u64 = 0x4;
+const PIPE_CTRL: u64 = 0x8;
+const PIPE_TXCOUNT: u64 = 0xc;
+
+const STATUS_OPEN: u64 = 1 << 0;
+const STATUS_ERROR: u64 = 1 << 1;
+
+const CTRL_FLUSH: u64 = 1 << 0;
+const CTRL_CLOSE: u64 = 1 << 1;
+
+pub struct DevicePipe {
+    host: Option<BufWriter<Box<dyn Write>>>,
+    error: bool,
+    tx_count: u64,
+}
+
+impl DevicePipe {
+    pub fn new(host: Box<dyn Write>) -> Self {
+        DevicePipe {
+            host: Some(BufWriter::new(host)),
+            error: false,
+            tx_count: 0,
+        }
+    }
+
+    /// Open the host side by path, the file is created if it does not exist.
+    /// Opening a named pipe blocks until the reader side is opened.
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(DevicePipe::new(Box::new(file)))
+    }
+
+    fn host_write(&mut self, byte: u8) {
+        if let Some(host) = self.host.as_mut() {
+            if let Err(e) = host.write_all(&[byte]) {
+                warn!("pipe write error:{e}");
+                self.error = true;
+                self.host = None;
+            }
+        }
+    }
+
+    fn host_flush(&mut self) {
+        if let Some(host) = self.host.as_mut() {
+            if let Err(e) = host.flush() {
+                warn!("pipe flush error:{e}");
+                self.error = true;
+                self.host = None;
+            }
+        }
+    }
+}
+
+impl DeviceBase for DevicePipe {
+    fn do_read(&mut self, addr: u64, _len: usize) -> u64 {
+        match addr {
+            PIPE_STATUS => {
+                let open = if self.host.is_some() { STATUS_OPEN } else { 0 };
+                let error = if self.error { STATUS_ERROR } else { 0 };
+                open | error
+            }
+            PIPE_TXCOUNT => self.tx_count as u32 as u64,
+            _ => 0,
+        }
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, _len: usize) -> u64 {
+        match addr {
+            PIPE_TXDATA => {
+                self.tx_count += 1;
+                self.host_write(data as u8);
+            }
+            PIPE_CTRL => {
+                if data & (CTRL_FLUSH | CTRL_CLOSE) != 0 {
+                    self.host_flush();
+                }
+                if data & CTRL_CLOSE != 0 {
+                    self.host = None;
+                }
+            }
+            _ => {}
+        }
+        0
+    }
+
+    // bulk write to TXDATA, used by debugger and loaders
+    fn copy_from_slice(&mut self, addr: u64, slice: &[u8]) {
+        if addr == PIPE_TXDATA {
+            slice.iter().for_each(|&x| {
+                self.tx_count += 1;
+                self.host_write(x);
+            });
+        }
+    }
+
+    fn get_name(&self) -> &'static str {
+        "HOST_PIPE"
+    }
+
+    fn reset(&mut self) {
+        self.host_flush();
+        self.tx_count = 0;
+    }
+}
+
+impl Drop for DevicePipe {
+    fn drop(&mut self) {
+        self.host_flush();
+    }
+}
+
+#[cfg(test)]
+mod test_pipe {
+    use std::fs;
+
+    use crate::device::device_trait::DeviceBase;
+
+    use super::DevicePipe;
+
+    #[test]
+    fn pipe_write() {
+        // one file per test run, the runs may be in parallel
+        let path = std::env::temp_dir().join(format!("rv64emu_pipe_test_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut pipe = DevicePipe::open(path).unwrap();
+
+        assert_eq!(pipe.do_read(0x4, 4), 1);
+        "{\"pass\":1}".bytes().for_each(|c| {
+            pipe.do_write(0x0, c as u64, 1);
+        });
+        pipe.do_write(0x8, 1, 4);
+        assert_eq!(pipe.do_read(0xc, 4), 10);
+        assert_eq!(fs::read_to_string(path).unwrap(), "{\"pass\":1}");
+
+        pipe.do_write(0x8, 2, 4);
+        assert_eq!(pipe.do_read(0x4, 4), 0);
+        pipe.do_write(0x0, b'x' as u64, 1);
+        assert_eq!(fs::read_to_string(path).unwrap(), "{\"pass\":1}");
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+use alloc::collections::VecDeque;
+
+use crate::{
+    device::{device_trait::DeviceBase, irq::IrqLine},
+    input::{MouseButton, MouseButtons, MouseMotion},
+    tools::Fifobounded,
+};
+
+// a PS/2 mouse behind the port of the Altera University Program PS/2 core
+// (linux: altera_ps2, "altr,ps2-1.0"), the guest speaks the PS/2 protocol.
+// 0x0 DATA (R) bit 15 RVALID, bits 7:0 the byte, bits 31:16 the bytes left,
+//              reading pops the byte
+//          (W) a command byte to the mouse
+// 0x4 CONTROL  bit 0 RE, the irq enable, bit 8 RI (R) the irq is pending
+// the irq is high while RE and bytes are waiting.
+// the mouse reports 3-byte packets after 0xf4, 4-byte packets with the
+// wheel after the IntelliMouse sample rates 200, 100, 80
+pub const PS2_MOUSE_SIZE: u64 = 0x8;
+pub const PS2_MOUSE_IRQ: u32 = 12;
+
+const DATA: u64 = 0x0;
+const CONTROL: u64 = 0x4;
+
+const DATA_RVALID: u32 = 1 << 15;
+const CONTROL_RE: u32 = 1 << 0;
+const CONTROL_RI: u32 = 1 << 8;
+
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+const SELF_TEST_OK: u8 = 0xaa;
+// the bytes which wait for the guest, the packets past it are dropped
+const OUT_LIMIT: usize = 64;
+
+pub struct DevicePs2Mouse {
+    rx_motion: Fifobounded<MouseMotion>,
+    out: VecDeque<u8>,
+    // the command waiting for its argument byte
+    arg_of: Option<u8>,
+    reporting: bool,
+    scaling: bool,
+    resolution: u8,
+    rate: u8,
+    // the last sample rates, for the IntelliMouse knock
+    rates: [u8; 3],
+    // 0: a standard mouse, 3: with a wheel
+    id: u8,
+    buttons: MouseButtons,
+    irq_en: bool,
+    irq: Option<IrqLine>,
+}
+
+impl DevicePs2Mouse {
+    pub fn new(rx_motion: Fifobounded<MouseMotion>) -> Self {
+        DevicePs2Mouse {
+            rx_motion,
+            out: VecDeque::new(),
+            arg_of: None,
+            reporting: false,
+            scaling: false,
+            resolution: 2,
+            rate: 100,
+            rates: [0; 3],
+            id: 0,
+            buttons: MouseButtons::default(),
+            irq_en: false,
+            irq: None,
+        }
+    }
+
+    fn set_defaults(&mut self) {
+        self.reporting = false;
+        self.scaling = false;
+        self.resolution = 2;
+        self.rate = 100;
+    }
+
+    // left, right, middle in bits 0..2
+    fn button_bits(&self) -> u8 {
+        [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (self.buttons.is_pressed(*x) as u8) << i)
+            .sum()
+    }
+
+    // dy is upwards on PS/2, the wheel grows towards the user
+    fn push_packet(&mut self, dx: i32, dy: i32, wheel: i32) {
+        let (dx, dy) = (dx.clamp(-256, 255), (-dy).clamp(-256, 255));
+        let flags = self.button_bits() | 0x08 | ((dx < 0) as u8) << 4 | ((dy < 0) as u8) << 5;
+        self.out.extend([flags, dx as u8, dy as u8]);
+        if self.id == 3 {
+            self.out.push_back((-wheel).clamp(-8, 7) as u8);
+        }
+    }
+
+    fn command(&mut self, cmd: u8) {
+        if let Some(of) = self.arg_of.take() {
+            match of {
+                0xf3 => {
+                    self.rate = cmd;
+                    self.rates = [self.rates[1], self.rates[2], cmd];
+                    if self.rates == [200, 100, 80] {
+                        self.id = 3;
+                    }
+                }
+                _ => self.resolution = cmd & 3,
+            }
+            self.out.push_back(ACK);
+            return;
+        }
+        match cmd {
+            0xff => {
+                self.out.clear();
+                self.set_defaults();
+                self.id = 0;
+                self.out.extend([ACK, SELF_TEST_OK, 0x00]);
+            }
+            0xf6 => {
+                self.set_defaults();
+                self.out.push_back(ACK);
+            }
+            0xf5 | 0xf4 => {
+                self.reporting = cmd == 0xf4;
+                self.out.push_back(ACK);
+            }
+            0xf3 | 0xe8 => {
+                self.arg_of = Some(cmd);
+                self.out.push_back(ACK);
+            }
+            0xf2 => self.out.extend([ACK, self.id]),
+            0xe9 => {
+                let status = (self.reporting as u8) << 5 | (self.scaling as u8) << 4;
+                let buttons = self.button_bits();
+                // left in bit 2, middle in bit 1, right in bit 0
+                let buttons = (buttons & 1) << 2 | (buttons & 4) >> 1 | (buttons & 2) >> 1;
+                self.out
+                    .extend([ACK, status | buttons, self.resolution, self.rate]);
+            }
+            0xe6 | 0xe7 => {
+                self.scaling = cmd == 0xe7;
+                self.out.push_back(ACK);
+            }
+            0xeb => {
+                self.out.push_back(ACK);
+                self.push_packet(0, 0, 0);
+            }
+            // stream and remote mode, wrap mode off
+            0xea | 0xf0 | 0xec => self.out.push_back(ACK),
+            _ => self.out.push_back(RESEND),
+        }
+    }
+
+    fn update_irq(&self) {
+        if let Some(irq) = &self.irq {
+            irq.set(self.irq_en && !self.out.is_empty());
+        }
+    }
+}
+
+impl DeviceBase for DevicePs2Mouse {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "ps2 mouse read len:{}", len);
+        let data = match addr {
+            DATA => match self.out.pop_front() {
+                Some(x) => (self.out.len() as u32) << 16 | DATA_RVALID | x as u32,
+                None => 0,
+            },
+            CONTROL => {
+                let ri = self.irq_en && !self.out.is_empty();
+                self.irq_en as u32 | if ri { CONTROL_RI } else { 0 }
+            }
+            _ => 0,
+        };
+        self.update_irq();
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "ps2 mouse write len:{}", len);
+        match addr {
+            DATA => self.command(data as u8),
+            CONTROL => self.irq_en = data as u32 & CONTROL_RE != 0,
+            _ => {}
+        }
+        self.update_irq();
+        0
+    }
+
+    // the moves of a disabled mouse are dropped
+    fn do_update(&mut self) {
+        while let Some(motion) = self.rx_motion.pop() {
+            let moved = motion.dx != 0 || motion.dy != 0 || motion.wheel != 0;
+            let changed = motion.buttons != self.buttons;
+            self.buttons = motion.buttons;
+            if self.reporting && (moved || changed) && self.out.len() < OUT_LIMIT {
+                self.push_packet(motion.dx, motion.dy, motion.wheel);
+            }
+        }
+        self.update_irq();
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+
+    fn reset(&mut self) {
+        self.out.clear();
+        self.arg_of = None;
+        self.set_defaults();
+        self.id = 0;
+        self.irq_en = false;
+        self.update_irq();
+    }
+
+    fn get_name(&self) -> &'static str {
+        "PS2_Mouse"
+    }
+}
+
+#[cfg(test)]
+mod test_ps2_mouse {
+    use alloc::vec::Vec;
+
+    use super::DevicePs2Mouse;
+    use crate::{
+        device::{device_trait::DeviceBase, irq::IrqLine},
+        input::{MouseButton, MouseButtons, MouseMotion},
+        tools::fifo_bounded_new,
+    };
+
+    fn read_all(mouse: &mut DevicePs2Mouse) -> Vec<u8> {
+        let mut ret = Vec::new();
+        loop {
+            let data = mouse.do_read(0, 4);
+            if data & 1 << 15 == 0 {
+                return ret;
+            }
+            ret.push(data as u8);
+        }
+    }
+
+    #[test]
+    fn ps2_mouse_test() {
+        let fifo = fifo_bounded_new(16);
+        let mut mouse = DevicePs2Mouse::new(fifo.clone());
+        let irq = IrqLine::new(12);
+        mouse.connect_irq(irq.clone());
+        mouse.do_write(4, 1, 4);
+
+        mouse.do_write(0, 0xff, 4);
+        assert!(irq.level());
+        assert_eq!(read_all(&mut mouse), [0xfa, 0xaa, 0x00]);
+        assert!(!irq.level());
+        // the IntelliMouse knock
+        for rate in [200, 100, 80] {
+            mouse.do_write(0, 0xf3, 4);
+            mouse.do_write(0, rate, 4);
+        }
+        mouse.do_write(0, 0xf2, 4);
+        assert_eq!(read_all(&mut mouse)[6..], [0xfa, 0x03]);
+
+        // nothing before the reporting is enabled
+        fifo.force_push(MouseMotion {
+            dx: 1,
+            ..Default::default()
+        });
+        mouse.do_update();
+        assert!(read_all(&mut mouse).is_empty());
+        mouse.do_write(0, 0xf4, 4);
+        assert_eq!(read_all(&mut mouse), [0xfa]);
+
+        let mut buttons = MouseButtons::default();
+        buttons.set(MouseButton::Right, true);
+        fifo.force_push(MouseMotion {
+            buttons,
+            dx: -3,
+            dy: 5,
+            wheel: 1,
+        });
+        mouse.do_update();
+        assert!(irq.level());
+        // right button, x and y negative (y is upwards), wheel -1
+        assert_eq!(read_all(&mut mouse), [0x3a, 0xfd, 0xfb, 0xff]);
+        assert_eq!(mouse.do_read(4, 4), 1);
+    }
+}
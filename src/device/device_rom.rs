@@ -0,0 +1,127 @@
+use alloc::boxed::Box;
+use log::{debug, info};
+
+use crate::{
+    device::device_trait::{DeviceBase, PmaAttr},
+    error::{RvEmuError, RvEmuResult},
+};
+
+// what a store of the guest to the rom does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomWrite {
+    // a store access fault, the rom is not writable in its PMA
+    Fault,
+    // the store is dropped, like a flash without its write enable
+    Ignore,
+}
+
+// read-only memory for boot roms and flash, only the host writes it
+// (`load_binary`, `copy_from_slice`)
+pub struct DeviceRom {
+    data: Box<[u8]>,
+    on_write: RomWrite,
+}
+
+impl DeviceRom {
+    pub fn new(size: usize, on_write: RomWrite) -> Self {
+        DeviceRom {
+            data: vec![0; size].into_boxed_slice(),
+            on_write,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn load_binary(&mut self, slice: &[u8]) -> RvEmuResult<()> {
+        if slice.len() > self.data.len() {
+            return Err(RvEmuError::ImageLoad(format!(
+                "binary of {:#x} bytes does not fit in rom of {:#x} bytes",
+                slice.len(),
+                self.data.len()
+            )));
+        }
+        self.data[..slice.len()].copy_from_slice(slice);
+        info!("load rom success: {:#x} bytes", slice.len());
+        Ok(())
+    }
+}
+
+impl DeviceBase for DeviceRom {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        let mut data_bytes = 0_u64.to_le_bytes();
+        data_bytes[..len].copy_from_slice(&self.data[addr as usize..addr as usize + len]);
+        u64::from_le_bytes(data_bytes)
+    }
+
+    // the stores of the harts are stopped by the PMA, this is the DMA
+    fn do_write(&mut self, addr: u64, _data: u64, len: usize) -> u64 {
+        debug!("rom write dropped: {addr:#x}+{len}");
+        0
+    }
+
+    fn copy_from_slice(&mut self, addr: u64, slice: &[u8]) {
+        self.data[addr as usize..addr as usize + slice.len()].copy_from_slice(slice);
+    }
+
+    fn copy_to_slice(&mut self, addr: u64, slice: &mut [u8]) {
+        slice.copy_from_slice(&self.data[addr as usize..addr as usize + slice.len()]);
+    }
+
+    fn pma(&self) -> PmaAttr {
+        PmaAttr {
+            writable: self.on_write == RomWrite::Ignore,
+            atomic: false,
+            ..PmaAttr::MEMORY
+        }
+    }
+
+    fn get_name(&self) -> &'static str {
+        "rom"
+    }
+}
+
+#[cfg(test)]
+mod test_rom {
+    use alloc::boxed::Box;
+
+    use super::{DeviceRom, RomWrite};
+    use crate::rv64core::{
+        bus::DeviceType, inst::inst_base::AccessType, test_cpu::test_cpu, traptype::TrapType,
+    };
+
+    #[test]
+    fn rom_test() {
+        let (mut cpu, bus) = test_cpu("rv64imac", 0);
+        for (start, on_write) in [(0x1000, RomWrite::Fault), (0x2000, RomWrite::Ignore)] {
+            let mut rom = DeviceRom::new(0x1000, on_write);
+            rom.load_binary(&[0x11; 8]).unwrap();
+            bus.borrow_mut()
+                .add_device(DeviceType {
+                    start,
+                    len: 0x1000,
+                    instance: Box::new(rom),
+                    name: "ROM",
+                })
+                .unwrap();
+        }
+
+        assert_eq!(
+            cpu.write(0x1000, 0, 8, AccessType::Store(0x1000)),
+            Err(TrapType::StoreAccessFault(0x1000))
+        );
+        assert!(cpu.write(0x2000, 0, 8, AccessType::Store(0x2000)).is_ok());
+        for addr in [0x1000, 0x2000] {
+            assert_eq!(
+                cpu.read(addr, 8, AccessType::Load(addr)),
+                Ok(0x1111_1111_1111_1111)
+            );
+        }
+        // no AMO on a rom
+        assert_eq!(
+            cpu.read(0x2000, 8, AccessType::Amo(0x2000)),
+            Err(TrapType::StoreAccessFault(0x2000))
+        );
+    }
+}
@@ -1,12 +1,19 @@
-use core::{cell::Cell, cmp::Ordering};
+use core::cell::Cell;
 
-use alloc::{rc::Rc, vec::Vec};
+use alloc::{rc::Rc, string::String, vec::Vec};
 use bitfield_struct::bitfield;
 use log::warn;
 
-use crate::rv64core::csr_regs_define::XipIn;
+use crate::{
+    error::{RvEmuError, RvEmuResult},
+    rv64core::csr_regs_define::XipIn,
+    snapshot::{StateReader, StateWriter},
+};
 
-use super::device_trait::DeviceBase;
+use super::{
+    device_trait::DeviceBase,
+    irq::{HartIrq, HartIrqKind, InterruptController, IrqLine, IrqTrigger},
+};
 
 /* ref spike plic */
 const _PLIC_MAX_CONTEXTS: usize = 15872;
@@ -78,10 +85,13 @@ const CONTEXT_END: u64 = REG_SIZE - 1;
 const REG_SIZE: u64 = 0x1000000;
 
 pub const SIFIVE_UART_IRQ: u32 = 10;
+pub const UART16550_IRQ: u32 = 11;
 
 struct IrqSource {
     id: u32,
-    pending: Rc<Cell<bool>>,
+    line: IrqLine,
+    trigger: IrqTrigger,
+    edge_count: u32,
 }
 
 // PLIC Interrupt Priority Register (priority)
@@ -97,8 +107,9 @@ impl IrqPriority {
     pub fn get(&self) -> u8 {
         self.priority()
     }
+    // WARL, the upper bits are dropped
     pub fn set(&mut self, priority: u8) {
-        self.set_priority(priority)
+        self.set_priority(priority & 0x7)
     }
 }
 
@@ -154,7 +165,7 @@ impl IrqThreshold {
         self.threshold() as u32
     }
     pub fn set_all(&mut self, val: u32) {
-        self.set_threshold(val as u8)
+        self.set_threshold((val & 0x7) as u8)
     }
 }
 
@@ -166,34 +177,23 @@ pub struct DevicePlic {
 }
 
 struct PlicContext {
-    mmode: bool,
-    xip: Rc<Cell<XipIn>>,
+    eip: HartIrq,
     threshold: IrqThreshold,
     enable: [IrqEnable; 2], // 0: 0-31, 1: 32-63
     claim: u32,
 }
 impl PlicContext {
-    pub fn new(xip_share: Rc<Cell<XipIn>>, mmode: bool) -> Self {
+    pub fn new(eip: HartIrq) -> Self {
         PlicContext {
+            eip,
             threshold: IrqThreshold::new(),
             enable: [IrqEnable::new(); 2],
             claim: 0,
-            mmode,
-            xip: xip_share,
         }
     }
     pub fn get_enable_by_id(&self, irq_id: u32) -> bool {
         self.enable[irq_id as usize / 32].get_bit(irq_id % 32)
     }
-
-    pub fn update_xip(&self, level: bool) {
-        let mut xip = self.xip.get();
-        match self.mmode {
-            true => xip.set_meip(level),
-            false => xip.set_seip(level),
-        };
-        self.xip.set(xip);
-    }
 }
 pub struct SifvePlic {
     irq_sources: Vec<IrqSource>,
@@ -214,57 +214,129 @@ impl SifvePlic {
             context: Vec::new(),
         }
     }
-    pub fn register_irq_source(&mut self, irq_id: u32, irq_pending: Rc<Cell<bool>>) {
+    fn add_source(&mut self, line: IrqLine, trigger: IrqTrigger) {
+        let irq_id = line.id();
+        assert!(irq_id > 0, "irq_id 0 does not exist");
         assert!(irq_id < 64, "irq_id:{} is too large", irq_id);
         // Check if the irq_id is already registered, if so, panic
         if self.irq_sources.iter().any(|item| item.id == irq_id) {
             panic!("irq_id:{} is already registered", irq_id);
         };
-        // Register the new irq_source
         self.irq_sources.push(IrqSource {
             id: irq_id,
-            pending: irq_pending,
+            line,
+            trigger,
+            edge_count: 0,
         });
     }
+    // drive an irq line without a device behind it (monitor irq injection).
+    // the source is registered on demand, a line owned by a device
+    // is overwritten by the device at its next update.
+    pub fn set_irq_line(&mut self, irq_id: u32, level: bool) {
+        assert!(irq_id > 0 && irq_id < 64, "invalid irq_id:{}", irq_id);
+        match self.irq_sources.iter().find(|item| item.id == irq_id) {
+            Some(item) => item.line.set(level),
+            None => {
+                let line = IrqLine::new(irq_id);
+                line.set(level);
+                self.add_source(line, IrqTrigger::Level);
+            }
+        }
+    }
+    // the external interrupt of a hart, meip or seip
     pub fn add_context(&mut self, xip_share: Rc<Cell<XipIn>>, mmode: bool) {
-        self.context.push(PlicContext::new(xip_share, mmode));
+        let eip = HartIrq::external(xip_share, mmode);
+        self.context.push(PlicContext::new(eip));
     }
+    // MExternal or SExternal, one for each context in order
+    pub fn context_kinds(&self) -> Vec<HartIrqKind> {
+        self.context.iter().map(|x| x.eip.kind()).collect()
+    }
+    fn get_pending(&self, irq_id: u32) -> bool {
+        self.irq_pending[irq_id as usize / 32].get_bit(irq_id % 32)
+    }
+    fn set_pending(&mut self, irq_id: u32, val: bool) {
+        self.irq_pending[irq_id as usize / 32].set_bool(irq_id % 32, val);
+    }
+
     pub fn tick(&mut self) {
-        // Update the pending bits
-        for item in self.irq_sources.iter() {
-            let pending_bit = item.pending.get();
-            let irq_id = item.id;
-            self.irq_pending[irq_id as usize / 32].set_bool(irq_id % 32, pending_bit);
+        for idx in 0..self.irq_sources.len() {
+            self.gateway(idx);
+        }
+        self.update_xip();
+    }
+
+    // interrupt gateway: forward one request per source, the next one
+    // waits until the previous is completed. each source latches its own
+    // pending bit, whatever the other sources are doing
+    fn gateway(&mut self, idx: usize) {
+        let item = &mut self.irq_sources[idx];
+        // the edges are latched by the line, also those between two ticks
+        let edges = item.line.take_edges();
+        let request = match item.trigger {
+            IrqTrigger::Level => item.line.level(),
+            IrqTrigger::Edge => {
+                item.edge_count = item.edge_count.saturating_add(edges);
+                item.edge_count > 0
+            }
+        };
+
+        let irq_id = item.id;
+        let idle = !self.claimed[irq_id as usize] && !self.get_pending(irq_id);
+        if request && idle {
+            let item = &mut self.irq_sources[idx];
+            if item.trigger == IrqTrigger::Edge {
+                item.edge_count -= 1;
+            }
+            self.set_pending(irq_id, true);
         }
-        // Update the xip (mip or sip)
+    }
+
+    // the source a claim of the context would return: pending, enabled,
+    // and above the threshold (so never priority 0). the highest priority
+    // wins, the lowest id on a tie
+    fn best_irq(&self, c: &PlicContext) -> Option<u32> {
+        let threshold = c.threshold.get_all() as u8;
+        self.irq_sources
+            .iter()
+            .map(|item| (item.id, self.vec_irq_priority[item.id as usize].get()))
+            .filter(|(id, priority)| {
+                self.get_pending(*id) && c.get_enable_by_id(*id) && *priority > threshold
+            })
+            .max_by(|(id_a, pri_a), (id_b, pri_b)| pri_a.cmp(pri_b).then(id_b.cmp(id_a)))
+            .map(|(id, _)| id)
+    }
+
+    // Update the xip (mip or sip), after every change of the pending bits,
+    // the priorities, the enables or the thresholds
+    fn update_xip(&self) {
         for c in &self.context {
-            let int_flag = self.irq_sources.iter().any(|item| {
-                let irq_id = item.id;
-                let pending_bit = item.pending.get();
-                let enable_bit = c.get_enable_by_id(irq_id);
-                let priority = self.vec_irq_priority[irq_id as usize].get();
-                let threshold = c.threshold.get_all() as u8;
-                pending_bit && enable_bit && priority > threshold
-            });
-            // if int_flag {
-            //     warn!("int_flag is true");
-            // }
-            c.update_xip(int_flag);
+            c.eip.set(self.best_irq(c).is_some());
         }
     }
 
+    // unimplemented sources and contexts are hardwired to zero
     fn priority_read(&self, offset: u32) -> u32 {
         let idx_word = (offset >> 2) as usize;
-        self.vec_irq_priority[idx_word].get() as u32
+        self.vec_irq_priority
+            .get(idx_word)
+            .map_or(0, |x| x.get() as u32)
     }
     fn priority_write(&mut self, offset: u32, val: u32) {
         let idx_word = (offset >> 2) as usize;
-        self.vec_irq_priority[idx_word].set(val as u8);
+        // source 0 does not exist
+        if idx_word == 0 {
+            return;
+        }
+        if let Some(x) = self.vec_irq_priority.get_mut(idx_word) {
+            x.set(val as u8);
+        }
+        self.update_xip();
     }
 
     fn pending_read(&self, offset: u32) -> u32 {
         let idx_word = (offset >> 2) as usize;
-        self.irq_pending[idx_word].get_all()
+        self.irq_pending.get(idx_word).map_or(0, |x| x.get_all())
     }
     // pending bits are read-only
     fn pending_write(&mut self, _irq_id: u32, _val: u32) {
@@ -274,19 +346,32 @@ impl SifvePlic {
     fn context_enbale_read(&self, offset: u32) -> u32 {
         let context_idx = (offset / ENABLE_PER_HART as u32) as usize;
         let idx_word = ((offset % ENABLE_PER_HART as u32) >> 2) as usize;
-        self.context[context_idx].enable[idx_word].get_all()
+        self.context
+            .get(context_idx)
+            .and_then(|c| c.enable.get(idx_word))
+            .map_or(0, |x| x.get_all())
     }
     fn context_enbale_write(&mut self, offset: u32, val: u32) {
         let context_idx = (offset / ENABLE_PER_HART as u32) as usize;
         let idx_word = ((offset % ENABLE_PER_HART as u32) >> 2) as usize;
         // The first bit of the first word is reserved and must be zero
         let val = if idx_word == 0 { val & !1 } else { val };
-        self.context[context_idx].enable[idx_word].set_all(val);
+        if let Some(x) = self
+            .context
+            .get_mut(context_idx)
+            .and_then(|c| c.enable.get_mut(idx_word))
+        {
+            x.set_all(val);
+        }
+        self.update_xip();
     }
 
     fn context_read(&mut self, offset: u32) -> u32 {
         let context_idx = (offset / CONTEXT_PER_HART as u32) as usize;
         let context_offset = (offset % CONTEXT_PER_HART as u32) as u64;
+        if context_idx >= self.context.len() {
+            return 0;
+        }
         match context_offset {
             CONTEXT_THRESHOLD => self.context[context_idx].threshold.get_all(),
             CONTEXT_CLAIM => {
@@ -295,72 +380,64 @@ impl SifvePlic {
 
                 self.context_claim(context_idx)
             }
-            _ => panic!("context_read invalid offset:{}", context_offset),
+            // reserved
+            _ => 0,
         }
     }
     fn context_write(&mut self, offset: u32, val: u32) {
         let context_idx = (offset / CONTEXT_PER_HART as u32) as usize;
         let context_offset = (offset % CONTEXT_PER_HART as u32) as u64;
-
+        if context_idx >= self.context.len() {
+            return;
+        }
         match context_offset {
-            CONTEXT_THRESHOLD => self.context[context_idx].threshold.set_all(val),
+            CONTEXT_THRESHOLD => {
+                self.context[context_idx].threshold.set_all(val);
+                self.update_xip();
+            }
             CONTEXT_CLAIM => {
                 // debug!("context_write(context_idx:{}, val:{})", context_idx, val);
                 self.context_complete(context_idx, val)
             }
-            _ => panic!("context_write invalid offset:{}", context_offset),
+            _ => {}
         }
     }
 
+    // clears the pending bit of the source and returns its id, 0 if none
     fn context_claim(&mut self, context_idx: usize) -> u32 {
-        // 1. Get the highest priority pending interrupt, and clear the pending bit.
-        // 2. Return the interrupt ID
-        let mut irq_id = 0;
-        let mut irq_priority = 0;
-        let mut irq_pendding = Rc::new(Cell::new(false));
-        let c = self.context.get_mut(context_idx).unwrap();
+        let Some(irq_id) = self.best_irq(&self.context[context_idx]) else {
+            return 0;
+        };
+        self.set_pending(irq_id, false);
+        self.claimed[irq_id as usize] = true;
+        self.context[context_idx].claim = irq_id;
+        self.update_xip();
+        irq_id
+    }
 
-        self.irq_sources
-            .iter()
-            .filter(|item| item.pending.get() && c.get_enable_by_id(item.id))
-            .for_each(|item| {
-                let priority_tmp = self.vec_irq_priority[item.id as usize].get();
-                match priority_tmp.cmp(&irq_priority) {
-                    Ordering::Greater => {
-                        irq_id = item.id;
-                        irq_priority = priority_tmp;
-                        irq_pendding = item.pending.clone();
-                    }
-                    Ordering::Equal => {
-                        // If two interrupts have the same priority, the one with the lower interrupt ID is selected.
-                        if item.id < irq_id {
-                            irq_id = item.id;
-                            irq_priority = priority_tmp;
-                            irq_pendding = item.pending.clone();
-                        }
-                    }
-                    Ordering::Less => {}
-                };
-            });
-
-        match self.claimed[irq_id as usize] {
-            true => 0,
-            false => {
-                // debug!("context_claim(context_idx:{}),id:{}", context_idx,irq_id);
-                irq_pendding.set(false);
-                self.claimed[irq_id as usize] = true;
-                c.claim = irq_id;
-                irq_id
-            }
+    // the completion is ignored if the source is not enabled for the context
+    // or has not been claimed. a level source still high is pending again
+    fn context_complete(&mut self, context_idx: usize, val: u32) {
+        if val == 0 || val >= 64 || !self.claimed[val as usize] {
+            return;
+        }
+        if !self.context[context_idx].get_enable_by_id(val) {
+            return;
+        }
+        self.claimed[val as usize] = false;
+        if let Some(idx) = self.irq_sources.iter().position(|item| item.id == val) {
+            self.gateway(idx);
         }
-        // warn!(
-        //     "context_claim(context_idx:{}, irq_id:{})",
-        //     context_idx, irq_id
-        // );
+        self.update_xip();
     }
+}
 
-    fn context_complete(&mut self, _context_idx: usize, val: u32) {
-        self.claimed[val as usize] = false;
+impl InterruptController for SifvePlic {
+    // register source `irq_id` and return the handle that drives it
+    fn alloc_irq_line(&mut self, irq_id: u32, trigger: IrqTrigger) -> IrqLine {
+        let line = IrqLine::new(irq_id);
+        self.add_source(line.clone(), trigger);
+        line
     }
 }
 
@@ -421,7 +498,242 @@ impl DeviceBase for SifvePlic {
         }
         0
     }
+    // the sources and the contexts stay, their registers are cleared
+    fn reset(&mut self) {
+        self.vec_irq_priority.fill(IrqPriority::new());
+        self.irq_pending = [IrqPending::new(); 2];
+        self.claimed = [false; 64];
+        for src in self.irq_sources.iter_mut() {
+            src.line.take_edges();
+            src.edge_count = 0;
+        }
+        for c in self.context.iter_mut() {
+            *c = PlicContext::new(c.eip.clone());
+        }
+    }
+    // the levels of the lines belong to the devices, the eip bits of the
+    // harts follow at the next tick
+    fn save_state(&self, out: &mut StateWriter) {
+        self.vec_irq_priority
+            .iter()
+            .for_each(|x| out.put_u8(x.get()));
+        self.irq_pending
+            .iter()
+            .for_each(|x| out.put_u32(x.get_all()));
+        self.claimed.iter().for_each(|x| out.put_bool(*x));
+        out.put_u32(self.irq_sources.len() as u32);
+        for src in self.irq_sources.iter() {
+            out.put_u32(src.id);
+            out.put_u32(src.edge_count);
+        }
+        out.put_u32(self.context.len() as u32);
+        for c in self.context.iter() {
+            out.put_u32(c.threshold.get_all());
+            c.enable.iter().for_each(|x| out.put_u32(x.get_all()));
+            out.put_u32(c.claim);
+        }
+    }
+    fn load_state(&mut self, input: &mut StateReader) -> RvEmuResult<()> {
+        let bad = |reason: String| Err(RvEmuError::Snapshot(format!("plic: {reason}")));
+        for x in self.vec_irq_priority.iter_mut() {
+            x.set(input.get_u8()?);
+        }
+        for x in self.irq_pending.iter_mut() {
+            x.set_all(input.get_u32()?);
+        }
+        for x in self.claimed.iter_mut() {
+            *x = input.get_bool()?;
+        }
+        for _ in 0..input.get_u32()? {
+            let id = input.get_u32()?;
+            let Some(src) = self.irq_sources.iter_mut().find(|x| x.id == id) else {
+                return bad(format!("no source {id}"));
+            };
+            src.edge_count = input.get_u32()?;
+        }
+        let contexts = input.get_u32()? as usize;
+        if contexts != self.context.len() {
+            return bad(format!(
+                "{} contexts, the state has {contexts}",
+                self.context.len()
+            ));
+        }
+        for c in self.context.iter_mut() {
+            c.threshold.set_all(input.get_u32()?);
+            for x in c.enable.iter_mut() {
+                x.set_all(input.get_u32()?);
+            }
+            c.claim = input.get_u32()?;
+        }
+        Ok(())
+    }
     fn get_name(&self) -> &'static str {
         "PLIC"
     }
 }
+
+#[cfg(test)]
+mod test_plic {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use crate::{
+        device::{
+            device_trait::DeviceBase,
+            irq::{InterruptController, IrqLine, IrqTrigger},
+        },
+        rv64core::csr_regs_define::XipIn,
+    };
+
+    use super::SifvePlic;
+
+    const CLAIM: u64 = 0x200004;
+
+    fn plic_with_source(trigger: IrqTrigger) -> (SifvePlic, IrqLine, Rc<Cell<XipIn>>) {
+        let xip = Rc::new(Cell::new(XipIn::new()));
+        let mut plic = SifvePlic::new();
+        plic.add_context(xip.clone(), true);
+        let line = plic.alloc_irq_line(3, trigger);
+        // priority 1, enable source 3 on context 0
+        plic.do_write(3 * 4, 1, 4);
+        plic.do_write(0x2000, 1 << 3, 4);
+        (plic, line, xip)
+    }
+
+    #[test]
+    fn irq_line_test() {
+        let xip = Rc::new(Cell::new(XipIn::new()));
+        let mut plic = SifvePlic::new();
+        plic.add_context(xip.clone(), true);
+        let line = plic.alloc_irq_line(3, IrqTrigger::Level);
+        assert_eq!(line.id(), 3);
+
+        // priority 1, enable source 3 on context 0
+        plic.do_write(3 * 4, 1, 4);
+        plic.do_write(0x2000, 1 << 3, 4);
+
+        line.raise();
+        plic.tick();
+        assert!(xip.get().meip());
+        assert_eq!(plic.do_read(0x1000, 4), 1 << 3);
+        assert_eq!(plic.do_read(CLAIM, 4), 3);
+        assert!(!xip.get().meip());
+    }
+
+    #[test]
+    fn level_trigger_test() {
+        let (mut plic, line, xip) = plic_with_source(IrqTrigger::Level);
+
+        line.raise();
+        plic.tick();
+        assert_eq!(plic.do_read(CLAIM, 4), 3);
+        // still high, but no new request before completion
+        plic.tick();
+        assert!(!xip.get().meip());
+        assert_eq!(plic.do_read(CLAIM, 4), 0);
+
+        plic.do_write(CLAIM, 3, 4);
+        plic.tick();
+        assert!(xip.get().meip());
+        assert_eq!(plic.do_read(CLAIM, 4), 3);
+
+        // the device has been served
+        line.lower();
+        plic.do_write(CLAIM, 3, 4);
+        plic.tick();
+        assert!(!xip.get().meip());
+        assert_eq!(plic.do_read(CLAIM, 4), 0);
+    }
+
+    #[test]
+    fn edge_trigger_test() {
+        let (mut plic, line, xip) = plic_with_source(IrqTrigger::Edge);
+
+        line.raise();
+        plic.tick();
+        assert_eq!(plic.do_read(CLAIM, 4), 3);
+        // a level that stays high is one request
+        plic.do_write(CLAIM, 3, 4);
+        plic.tick();
+        assert!(!xip.get().meip());
+
+        // two edges while the first one is in flight
+        line.lower();
+        plic.tick();
+        line.raise();
+        plic.tick();
+        assert_eq!(plic.do_read(CLAIM, 4), 3);
+        line.lower();
+        plic.tick();
+        line.raise();
+        plic.tick();
+        assert!(!xip.get().meip());
+
+        plic.do_write(CLAIM, 3, 4);
+        plic.tick();
+        assert_eq!(plic.do_read(CLAIM, 4), 3);
+        plic.do_write(CLAIM, 3, 4);
+        plic.tick();
+        assert!(!xip.get().meip());
+        assert_eq!(plic.do_read(CLAIM, 4), 0);
+    }
+
+    #[test]
+    fn multi_source_test() {
+        let xip = Rc::new(Cell::new(XipIn::new()));
+        let mut plic = SifvePlic::new();
+        plic.add_context(xip.clone(), true);
+        plic.add_context(xip.clone(), false);
+        let lines: alloc::vec::Vec<_> = [2, 5, 7, 33]
+            .iter()
+            .map(|id| plic.alloc_irq_line(*id, IrqTrigger::Level))
+            .collect();
+        // 2 and 5 share priority 3, 7 has 6, 33 has 0 and never interrupts
+        plic.do_write(2 * 4, 3, 4);
+        plic.do_write(5 * 4, 3, 4);
+        plic.do_write(7 * 4, 6, 4);
+        plic.do_write(0x2000, (1 << 2) | (1 << 5) | (1 << 7), 4);
+        plic.do_write(0x2004, 1 << 1, 4);
+        // the s-mode context only takes 5
+        plic.do_write(0x2080, 1 << 5, 4);
+        lines.iter().for_each(|x| x.raise());
+        plic.tick();
+        assert_eq!(plic.do_read(0x1000, 4), (1 << 2) | (1 << 5) | (1 << 7));
+        assert_eq!(plic.do_read(0x1004, 4), 1 << 1);
+
+        // the threshold masks 2 and 5 at once, without a tick
+        plic.do_write(CLAIM - 4, 3, 4);
+        assert!(xip.get().meip());
+        assert_eq!(plic.do_read(CLAIM, 4), 7);
+        assert!(!xip.get().meip());
+        assert_eq!(plic.do_read(CLAIM, 4), 0);
+        plic.do_write(CLAIM - 4, 0, 4);
+        assert!(xip.get().meip());
+
+        // same priority, the lowest id first
+        assert_eq!(plic.do_read(CLAIM, 4), 2);
+        assert!(xip.get().seip());
+        assert_eq!(plic.do_read(CLAIM + 0x1000, 4), 5);
+        assert!(!xip.get().meip() && !xip.get().seip());
+        assert_eq!(plic.do_read(CLAIM, 4), 0);
+
+        // a completion of a source that is not claimed is ignored, a level
+        // still high is pending again at the completion
+        plic.do_write(CLAIM, 33, 4);
+        plic.do_write(CLAIM, 7, 4);
+        assert!(xip.get().meip());
+        lines[1].lower();
+        plic.do_write(CLAIM + 0x1000, 5, 4);
+        assert!(!xip.get().seip());
+        assert_eq!(plic.do_read(CLAIM, 4), 7);
+        assert_eq!(plic.do_read(0x1000, 4), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn irq_line_twice_test() {
+        let mut plic = SifvePlic::new();
+        plic.alloc_irq_line(3, IrqTrigger::Level);
+        plic.alloc_irq_line(3, IrqTrigger::Edge);
+    }
+}
@@ -1,9 +1,12 @@
-use core::cell::Cell;
-
-use alloc::{boxed::Box, rc::Rc};
+use alloc::boxed::Box;
 use bitfield_struct::bitfield;
 
-use crate::{device::device_trait::DeviceBase, tools::FifoUnbounded};
+use crate::{
+    device::{device_trait::DeviceBase, irq::IrqLine},
+    error::RvEmuResult,
+    snapshot::{StateReader, StateWriter},
+    tools::FifoUnbounded,
+};
 
 const TXDATA: usize = 0x00;
 const RXDATA: usize = 0x04;
@@ -86,7 +89,7 @@ impl SifiveUartIN {
 
 pub struct DeviceSifiveUart {
     regs: Box<SifiveUartIN>,
-    pub irq_pending: Rc<Cell<bool>>,
+    irq: Option<IrqLine>,
 
     rxfifo: FifoUnbounded<u8>,
     txfifo: FifoUnbounded<u8>,
@@ -98,7 +101,7 @@ impl DeviceSifiveUart {
             regs: Box::new(SifiveUartIN::new()),
             txfifo: uart_tx,
             rxfifo: uart_rx,
-            irq_pending: Rc::new(Cell::new(false)),
+            irq: None,
         }
     }
     pub fn put_char(&mut self, ch: u64) {
@@ -170,10 +173,36 @@ impl DeviceBase for DeviceSifiveUart {
         self.regs.ip.set_rxwm(rxwm_pending);
         self.regs.ip.set_txwm(txwm_pending);
 
-        // update irq_pending
+        // update irq line
         let has_irq = 0 != (self.regs.ip.0 & self.regs.ie.0);
         // println!("ie:{:?}", self.regs.ie);
         // debug!("sifive_uart: irq_pending: {}", has_irq);
-        self.irq_pending.set(has_irq);
+        if let Some(irq) = &self.irq {
+            irq.set(has_irq);
+        }
+    }
+
+    fn dt_compatible(&self) -> Option<&'static str> {
+        Some("sifive,uart0")
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+
+    // ip is computed again by the next update
+    fn save_state(&self, out: &mut StateWriter) {
+        let regs = &self.regs;
+        for reg in [regs.txctrl.0, regs.rxctrl.0, regs.ie.0, regs.div] {
+            out.put_u32(reg);
+        }
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> RvEmuResult<()> {
+        self.regs.txctrl = input.get_u32()?.into();
+        self.regs.rxctrl = input.get_u32()?.into();
+        self.regs.ie = input.get_u32()?.into();
+        self.regs.div = input.get_u32()?;
+        Ok(())
     }
 }
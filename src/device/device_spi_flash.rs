@@ -0,0 +1,291 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::cell::RefCell;
+
+use crate::{
+    error::{RvEmuError, RvEmuResult},
+    tools::RcRefCell,
+};
+
+use super::device_trait::{DeviceBase, PmaAttr};
+
+// a NOR flash behind a SPI controller with execute-in-place: the window
+// (`SpiFlash::xip`) reads the array like a rom, the controller programs it
+// with the usual SPI NOR commands, every command completes at once.
+// controller registers:
+// 0x00 CMD    (W) 0x06 write enable, 0x04 write disable, 0x02 page program,
+//                 0x20 4KiB sector erase, 0xd8 64KiB block erase, 0xc7 chip erase
+// 0x04 ADDR   (RW) the flash offset of the command
+// 0x08 LEN    (RW) the bytes of a page program, at most 256
+// 0x0c STATUS (R) bit 1 WEL, bit 2 the last command was refused
+//                 (no write enable or out of the array), bit 0 WIP is never set
+// 0x10 JEDEC  (R) the manufacturer and device id
+// 0x100..0x200 the page buffer of the program command
+// a program clears bits only, like NOR, and wraps inside its 256 byte page.
+// program and erase clear WEL
+pub const SPI_FLASH_CTRL_SIZE: u64 = 0x200;
+pub const SPI_FLASH_PAGE: usize = 256;
+
+const CMD: u64 = 0x00;
+const ADDR: u64 = 0x04;
+const LEN: u64 = 0x08;
+const STATUS: u64 = 0x0c;
+const JEDEC: u64 = 0x10;
+const BUF: u64 = 0x100;
+
+const CMD_WREN: u32 = 0x06;
+const CMD_WRDI: u32 = 0x04;
+const CMD_PP: u32 = 0x02;
+const CMD_SE: u32 = 0x20;
+const CMD_BE: u32 = 0xd8;
+const CMD_CE: u32 = 0xc7;
+
+const STATUS_WEL: u32 = 1 << 1;
+const STATUS_ERR: u32 = 1 << 2;
+// a winbond W25Q family part
+const JEDEC_ID: u32 = 0x00ef_4000;
+
+struct FlashState {
+    array: Box<[u8]>,
+    addr: u32,
+    len: u32,
+    wel: bool,
+    err: bool,
+    buf: [u8; SPI_FLASH_PAGE],
+}
+
+impl FlashState {
+    fn erase(&mut self, size: usize) -> bool {
+        let start = self.addr as usize & !(size - 1);
+        match self.array.get_mut(start..start + size) {
+            Some(block) => {
+                block.fill(0xff);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn program(&mut self) -> bool {
+        let len = self.len as usize;
+        let page = self.addr as usize & !(SPI_FLASH_PAGE - 1);
+        if len > SPI_FLASH_PAGE || page + SPI_FLASH_PAGE > self.array.len() {
+            return false;
+        }
+        for i in 0..len {
+            let at = page + (self.addr as usize + i) % SPI_FLASH_PAGE;
+            self.array[at] &= self.buf[i];
+        }
+        true
+    }
+
+    fn command(&mut self, cmd: u32) {
+        let done = match cmd {
+            CMD_WREN => {
+                self.wel = true;
+                true
+            }
+            CMD_WRDI => {
+                self.wel = false;
+                true
+            }
+            CMD_PP | CMD_SE | CMD_BE | CMD_CE if !self.wel => false,
+            CMD_PP | CMD_SE | CMD_BE | CMD_CE => {
+                self.wel = false;
+                match cmd {
+                    CMD_PP => self.program(),
+                    CMD_SE => self.erase(0x1000),
+                    CMD_BE => self.erase(0x10000),
+                    _ => {
+                        self.array.fill(0xff);
+                        true
+                    }
+                }
+            }
+            _ => false,
+        };
+        self.err = !done;
+    }
+}
+
+// the controller, `xip` gives the window on the same array
+#[derive(Clone)]
+pub struct SpiFlash {
+    inner: RcRefCell<FlashState>,
+}
+
+impl SpiFlash {
+    // an erased flash of `size` bytes, a power of two of at least 64KiB
+    pub fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two() && size >= 0x10000);
+        SpiFlash {
+            inner: RcRefCell::new(RefCell::new(FlashState {
+                array: vec![0xff; size].into_boxed_slice(),
+                addr: 0,
+                len: 0,
+                wel: false,
+                err: false,
+                buf: [0xff; SPI_FLASH_PAGE],
+            })),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.inner.borrow().array.len()
+    }
+
+    // the image at offset 0, the rest stays erased
+    pub fn load_binary(&self, slice: &[u8]) -> RvEmuResult<()> {
+        let mut flash = self.inner.borrow_mut();
+        if slice.len() > flash.array.len() {
+            return Err(RvEmuError::ImageLoad(format!(
+                "binary of {:#x} bytes does not fit in flash of {:#x} bytes",
+                slice.len(),
+                flash.array.len()
+            )));
+        }
+        flash.array[..slice.len()].copy_from_slice(slice);
+        Ok(())
+    }
+
+    // the array as the guest left it, e.g. to save it to a file
+    pub fn image(&self) -> Vec<u8> {
+        self.inner.borrow().array.to_vec()
+    }
+
+    pub fn xip(&self) -> SpiFlashXip {
+        SpiFlashXip {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl DeviceBase for SpiFlash {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        let flash = self.inner.borrow();
+        if addr >= BUF {
+            let at = (addr - BUF) as usize;
+            let mut data_bytes = 0_u64.to_le_bytes();
+            data_bytes[..len].copy_from_slice(&flash.buf[at..at + len]);
+            return u64::from_le_bytes(data_bytes);
+        }
+        assert_eq!(len, 4, "spi flash read len:{}", len);
+        let data = match addr {
+            ADDR => flash.addr,
+            LEN => flash.len,
+            STATUS => {
+                let wel = if flash.wel { STATUS_WEL } else { 0 };
+                wel | if flash.err { STATUS_ERR } else { 0 }
+            }
+            JEDEC => JEDEC_ID | flash.array.len().trailing_zeros(),
+            _ => 0,
+        };
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        let mut flash = self.inner.borrow_mut();
+        if addr >= BUF {
+            let at = (addr - BUF) as usize;
+            flash.buf[at..at + len].copy_from_slice(&data.to_le_bytes()[..len]);
+            return 0;
+        }
+        assert_eq!(len, 4, "spi flash write len:{}", len);
+        match addr {
+            CMD => flash.command(data as u32),
+            ADDR => flash.addr = data as u32,
+            LEN => flash.len = data as u32,
+            _ => {}
+        }
+        0
+    }
+
+    fn get_name(&self) -> &'static str {
+        "SPI_FLASH"
+    }
+}
+
+// the execute-in-place window, a store of the guest faults
+pub struct SpiFlashXip {
+    inner: RcRefCell<FlashState>,
+}
+
+impl DeviceBase for SpiFlashXip {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        let flash = self.inner.borrow();
+        let mut data_bytes = 0_u64.to_le_bytes();
+        data_bytes[..len].copy_from_slice(&flash.array[addr as usize..addr as usize + len]);
+        u64::from_le_bytes(data_bytes)
+    }
+
+    // the DMA writes are dropped, the array changes through the commands
+    fn do_write(&mut self, _addr: u64, _data: u64, _len: usize) -> u64 {
+        0
+    }
+
+    fn copy_from_slice(&mut self, addr: u64, slice: &[u8]) {
+        let mut flash = self.inner.borrow_mut();
+        flash.array[addr as usize..addr as usize + slice.len()].copy_from_slice(slice);
+    }
+
+    fn copy_to_slice(&mut self, addr: u64, slice: &mut [u8]) {
+        let flash = self.inner.borrow();
+        slice.copy_from_slice(&flash.array[addr as usize..addr as usize + slice.len()]);
+    }
+
+    fn pma(&self) -> PmaAttr {
+        PmaAttr {
+            writable: false,
+            atomic: false,
+            ..PmaAttr::MEMORY
+        }
+    }
+
+    fn get_name(&self) -> &'static str {
+        "SPI_FLASH_XIP"
+    }
+}
+
+#[cfg(test)]
+mod test_spi_flash {
+    use super::SpiFlash;
+    use crate::device::device_trait::DeviceBase;
+
+    #[test]
+    fn spi_flash_test() {
+        let mut flash = SpiFlash::new(0x10_0000);
+        flash.load_binary(&[0x13, 0, 0, 0]).unwrap();
+        let mut xip = flash.xip();
+        assert_eq!(xip.do_read(0, 4), 0x13);
+        assert_eq!(xip.do_read(0x1000, 4), 0xffff_ffff);
+        // 1MiB
+        assert_eq!(flash.do_read(0x10, 4), 0xef_4014);
+
+        // a program without the write enable is refused
+        flash.do_write(0x100, 0x1234_5678, 4);
+        flash.do_write(0x04, 0x10fe, 4);
+        flash.do_write(0x08, 4, 4);
+        flash.do_write(0x00, 0x02, 4);
+        assert_eq!(flash.do_read(0x0c, 4), 0b100);
+        assert_eq!(xip.do_read(0x10fe, 2), 0xffff);
+
+        // it wraps at the end of the page
+        flash.do_write(0x00, 0x06, 4);
+        assert_eq!(flash.do_read(0x0c, 4), 0b010);
+        flash.do_write(0x00, 0x02, 4);
+        assert_eq!(flash.do_read(0x0c, 4), 0);
+        assert_eq!(xip.do_read(0x10fe, 2), 0x5678);
+        assert_eq!(xip.do_read(0x1000, 2), 0x1234);
+        // without an erase only bits are cleared
+        flash.do_write(0x100, 0x00ff, 2);
+        flash.do_write(0x04, 0x1000, 4);
+        flash.do_write(0x08, 2, 4);
+        flash.do_write(0x00, 0x06, 4);
+        flash.do_write(0x00, 0x02, 4);
+        assert_eq!(xip.do_read(0x1000, 2), 0x0034);
+
+        flash.do_write(0x00, 0x06, 4);
+        flash.do_write(0x00, 0x20, 4);
+        assert_eq!(xip.do_read(0x1000, 8), u64::MAX);
+        assert_eq!(flash.image()[..4], [0x13, 0, 0, 0]);
+    }
+}
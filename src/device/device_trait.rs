@@ -1,4 +1,10 @@
+use crate::{
+    error::RvEmuResult,
+    rv64core::inst::inst_base::AccessType,
+    snapshot::{StateReader, StateWriter},
+};
 
+use super::irq::IrqLine;
 
 pub const MEM_BASE: u64 = 0x80000000;
 pub const DEVICE_BASE: u64 = 0xa0000000;
@@ -8,6 +14,53 @@ pub const KBD_ADDR: u64 = DEVICE_BASE + 0x0000060;
 pub const MOUSE_ADDR: u64 = DEVICE_BASE + 0x0000070;
 pub const FB_ADDR: u64 = DEVICE_BASE + 0x1000000;
 pub const VGACTL_ADDR: u64 = DEVICE_BASE + 0x0000100;
+pub const AUDIO_ADDR: u64 = DEVICE_BASE + 0x0000200;
+pub const DISK_ADDR: u64 = DEVICE_BASE + 0x0000300;
+pub const AUDIO_SBUF_ADDR: u64 = DEVICE_BASE + 0x1200000;
+pub const VGA_TEXT_ADDR: u64 = DEVICE_BASE + 0x1300000;
+
+// physical memory attributes of a device, checked after PMP on every access
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PmaAttr {
+    pub cacheable: bool,
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    pub atomic: bool,
+}
+
+impl PmaAttr {
+    pub const MEMORY: PmaAttr = PmaAttr {
+        cacheable: true,
+        readable: true,
+        writable: true,
+        executable: true,
+        atomic: true,
+    };
+    pub const IO: PmaAttr = PmaAttr {
+        cacheable: false,
+        readable: true,
+        writable: true,
+        executable: false,
+        atomic: false,
+    };
+
+    pub fn allows(&self, access: &AccessType) -> bool {
+        match access {
+            AccessType::Fetch(_) => self.executable,
+            AccessType::Load(_) => self.readable,
+            AccessType::Store(_) => self.writable,
+            AccessType::Amo(_) => self.readable && self.writable && self.atomic,
+        }
+    }
+}
+
+// the bus as seen by a device which moves data itself, see `DeviceBase::do_dma`.
+// false if the area is not inside one device, nothing is moved then
+pub trait BusMaster {
+    fn dma_read(&mut self, addr: u64, buf: &mut [u8]) -> bool;
+    fn dma_write(&mut self, addr: u64, buf: &[u8]) -> bool;
+}
 
 pub trait DeviceBase {
     fn do_read(&mut self, addr: u64, len: usize) -> u64;
@@ -28,6 +81,28 @@ pub trait DeviceBase {
     }
     fn get_name(&self) -> &'static str;
     fn do_update(&mut self) {}
+    // called by `Bus::update` after `do_update`, a bus master does its
+    // transfers here. the device itself is off the bus meanwhile
+    fn do_dma(&mut self, _bus: &mut dyn BusMaster) {}
+    // called by `Bus::add_device_with_irq`, devices which can raise interrupts keep the line
+    fn connect_irq(&mut self, _irq: IrqLine) {}
+    // MMIO by default, no fetch, no AMO and not cached
+    fn pma(&self) -> PmaAttr {
+        PmaAttr::IO
+    }
+    // the compatible of its node in a generated device tree, see
+    // `fdt::machine_fdt`. the devices without one are left out
+    fn dt_compatible(&self) -> Option<&'static str> {
+        None
+    }
 
     fn reset(&mut self) {}
+
+    // the device half of a machine snapshot, see `Bus::save_state`.
+    // the devices without a state worth keeping save nothing, the host
+    // side (fifos, files, irq lines) is not part of the state
+    fn save_state(&self, _out: &mut StateWriter) {}
+    fn load_state(&mut self, _input: &mut StateReader) -> RvEmuResult<()> {
+        Ok(())
+    }
 }
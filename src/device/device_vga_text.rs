@@ -0,0 +1,284 @@
+use std::sync::Mutex;
+
+use alloc::sync::Arc;
+
+use crate::device::{
+    device_am_vga::{VgaFrame, VgaMode},
+    device_trait::DeviceBase,
+};
+
+// the 80x25 text mode of the VGA, a cell is the character in the low byte
+// and the attribute in the high byte, as in the text buffer of the PC:
+// bits 3:0 the foreground, bits 6:4 the background, bit 7 blink (shown steady).
+// a cell is 8x16 pixels, the 8x8 font is doubled vertically.
+// the characters outside 0x20..=0x7e are drawn as blanks
+pub const TEXT_COLS: usize = 80;
+pub const TEXT_ROWS: usize = 25;
+pub const VGA_TEXT_SIZE: usize = TEXT_COLS * TEXT_ROWS * 2;
+// the frame of the text mode in the window
+pub const TEXT_FRAME_MODE: VgaMode = VgaMode {
+    width: (TEXT_COLS * CELL_W) as u32,
+    height: (TEXT_ROWS * CELL_H) as u32,
+    bpp: 32,
+};
+
+const CELL_W: usize = 8;
+const CELL_H: usize = 16;
+// a space, light grey on black
+const BLANK_CELL: [u8; 2] = [b' ', 0x07];
+
+// the CGA palette, ARGB8888
+const PALETTE: [u32; 16] = [
+    0xff00_0000,
+    0xff00_00aa,
+    0xff00_aa00,
+    0xff00_aaaa,
+    0xffaa_0000,
+    0xffaa_00aa,
+    0xffaa_5500,
+    0xffaa_aaaa,
+    0xff55_5555,
+    0xff55_55ff,
+    0xff55_ff55,
+    0xff55_ffff,
+    0xffff_5555,
+    0xffff_55ff,
+    0xffff_ff55,
+    0xffff_ffff,
+];
+
+// 0x20..=0x7e, a byte per row, bit 0 is the leftmost pixel
+#[rustfmt::skip]
+const FONT_8X8: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x18, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x18, 0x00], // '!'
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x36, 0x36, 0x7f, 0x36, 0x7f, 0x36, 0x36, 0x00], // '#'
+    [0x0c, 0x3e, 0x03, 0x1e, 0x30, 0x1f, 0x0c, 0x00], // '$'
+    [0x00, 0x63, 0x33, 0x18, 0x0c, 0x66, 0x63, 0x00], // '%'
+    [0x1c, 0x36, 0x1c, 0x6e, 0x3b, 0x33, 0x6e, 0x00], // '&'
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // "'"
+    [0x18, 0x0c, 0x06, 0x06, 0x06, 0x0c, 0x18, 0x00], // '('
+    [0x06, 0x0c, 0x18, 0x18, 0x18, 0x0c, 0x06, 0x00], // ')'
+    [0x00, 0x66, 0x3c, 0xff, 0x3c, 0x66, 0x00, 0x00], // '*'
+    [0x00, 0x0c, 0x0c, 0x3f, 0x0c, 0x0c, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x06], // ','
+    [0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x00], // '.'
+    [0x60, 0x30, 0x18, 0x0c, 0x06, 0x03, 0x01, 0x00], // '/'
+    [0x3e, 0x63, 0x73, 0x7b, 0x6f, 0x67, 0x3e, 0x00], // '0'
+    [0x0c, 0x0e, 0x0c, 0x0c, 0x0c, 0x0c, 0x3f, 0x00], // '1'
+    [0x1e, 0x33, 0x30, 0x1c, 0x06, 0x33, 0x3f, 0x00], // '2'
+    [0x1e, 0x33, 0x30, 0x1c, 0x30, 0x33, 0x1e, 0x00], // '3'
+    [0x38, 0x3c, 0x36, 0x33, 0x7f, 0x30, 0x78, 0x00], // '4'
+    [0x3f, 0x03, 0x1f, 0x30, 0x30, 0x33, 0x1e, 0x00], // '5'
+    [0x1c, 0x06, 0x03, 0x1f, 0x33, 0x33, 0x1e, 0x00], // '6'
+    [0x3f, 0x33, 0x30, 0x18, 0x0c, 0x0c, 0x0c, 0x00], // '7'
+    [0x1e, 0x33, 0x33, 0x1e, 0x33, 0x33, 0x1e, 0x00], // '8'
+    [0x1e, 0x33, 0x33, 0x3e, 0x30, 0x18, 0x0e, 0x00], // '9'
+    [0x00, 0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x00], // ':'
+    [0x00, 0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x06], // ';'
+    [0x18, 0x0c, 0x06, 0x03, 0x06, 0x0c, 0x18, 0x00], // '<'
+    [0x00, 0x00, 0x3f, 0x00, 0x00, 0x3f, 0x00, 0x00], // '='
+    [0x06, 0x0c, 0x18, 0x30, 0x18, 0x0c, 0x06, 0x00], // '>'
+    [0x1e, 0x33, 0x30, 0x18, 0x0c, 0x00, 0x0c, 0x00], // '?'
+    [0x3e, 0x63, 0x7b, 0x7b, 0x7b, 0x03, 0x1e, 0x00], // '@'
+    [0x0c, 0x1e, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x00], // 'A'
+    [0x3f, 0x66, 0x66, 0x3e, 0x66, 0x66, 0x3f, 0x00], // 'B'
+    [0x3c, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3c, 0x00], // 'C'
+    [0x1f, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1f, 0x00], // 'D'
+    [0x7f, 0x46, 0x16, 0x1e, 0x16, 0x46, 0x7f, 0x00], // 'E'
+    [0x7f, 0x46, 0x16, 0x1e, 0x16, 0x06, 0x0f, 0x00], // 'F'
+    [0x3c, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7c, 0x00], // 'G'
+    [0x33, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x33, 0x00], // 'H'
+    [0x1e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // 'I'
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1e, 0x00], // 'J'
+    [0x67, 0x66, 0x36, 0x1e, 0x36, 0x66, 0x67, 0x00], // 'K'
+    [0x0f, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7f, 0x00], // 'L'
+    [0x63, 0x77, 0x7f, 0x7f, 0x6b, 0x63, 0x63, 0x00], // 'M'
+    [0x63, 0x67, 0x6f, 0x7b, 0x73, 0x63, 0x63, 0x00], // 'N'
+    [0x1c, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1c, 0x00], // 'O'
+    [0x3f, 0x66, 0x66, 0x3e, 0x06, 0x06, 0x0f, 0x00], // 'P'
+    [0x1e, 0x33, 0x33, 0x33, 0x3b, 0x1e, 0x38, 0x00], // 'Q'
+    [0x3f, 0x66, 0x66, 0x3e, 0x36, 0x66, 0x67, 0x00], // 'R'
+    [0x1e, 0x33, 0x07, 0x0e, 0x38, 0x33, 0x1e, 0x00], // 'S'
+    [0x3f, 0x2d, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // 'T'
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3f, 0x00], // 'U'
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1e, 0x0c, 0x00], // 'V'
+    [0x63, 0x63, 0x63, 0x6b, 0x7f, 0x77, 0x63, 0x00], // 'W'
+    [0x63, 0x63, 0x36, 0x1c, 0x1c, 0x36, 0x63, 0x00], // 'X'
+    [0x33, 0x33, 0x33, 0x1e, 0x0c, 0x0c, 0x1e, 0x00], // 'Y'
+    [0x7f, 0x63, 0x31, 0x18, 0x4c, 0x66, 0x7f, 0x00], // 'Z'
+    [0x1e, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1e, 0x00], // '['
+    [0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x40, 0x00], // '\\'
+    [0x1e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1e, 0x00], // ']'
+    [0x08, 0x1c, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff], // '_'
+    [0x0c, 0x0c, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x1e, 0x30, 0x3e, 0x33, 0x6e, 0x00], // 'a'
+    [0x07, 0x06, 0x06, 0x3e, 0x66, 0x66, 0x3b, 0x00], // 'b'
+    [0x00, 0x00, 0x1e, 0x33, 0x03, 0x33, 0x1e, 0x00], // 'c'
+    [0x38, 0x30, 0x30, 0x3e, 0x33, 0x33, 0x6e, 0x00], // 'd'
+    [0x00, 0x00, 0x1e, 0x33, 0x3f, 0x03, 0x1e, 0x00], // 'e'
+    [0x1c, 0x36, 0x06, 0x0f, 0x06, 0x06, 0x0f, 0x00], // 'f'
+    [0x00, 0x00, 0x6e, 0x33, 0x33, 0x3e, 0x30, 0x1f], // 'g'
+    [0x07, 0x06, 0x36, 0x6e, 0x66, 0x66, 0x67, 0x00], // 'h'
+    [0x0c, 0x00, 0x0e, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // 'i'
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1e], // 'j'
+    [0x07, 0x06, 0x66, 0x36, 0x1e, 0x36, 0x67, 0x00], // 'k'
+    [0x0e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // 'l'
+    [0x00, 0x00, 0x33, 0x7f, 0x7f, 0x6b, 0x63, 0x00], // 'm'
+    [0x00, 0x00, 0x1f, 0x33, 0x33, 0x33, 0x33, 0x00], // 'n'
+    [0x00, 0x00, 0x1e, 0x33, 0x33, 0x33, 0x1e, 0x00], // 'o'
+    [0x00, 0x00, 0x3b, 0x66, 0x66, 0x3e, 0x06, 0x0f], // 'p'
+    [0x00, 0x00, 0x6e, 0x33, 0x33, 0x3e, 0x30, 0x78], // 'q'
+    [0x00, 0x00, 0x3b, 0x6e, 0x66, 0x06, 0x0f, 0x00], // 'r'
+    [0x00, 0x00, 0x3e, 0x03, 0x1e, 0x30, 0x1f, 0x00], // 's'
+    [0x08, 0x0c, 0x3e, 0x0c, 0x0c, 0x2c, 0x18, 0x00], // 't'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6e, 0x00], // 'u'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1e, 0x0c, 0x00], // 'v'
+    [0x00, 0x00, 0x63, 0x6b, 0x7f, 0x7f, 0x36, 0x00], // 'w'
+    [0x00, 0x00, 0x63, 0x36, 0x1c, 0x36, 0x63, 0x00], // 'x'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3e, 0x30, 0x1f], // 'y'
+    [0x00, 0x00, 0x3f, 0x19, 0x0c, 0x26, 0x3f, 0x00], // 'z'
+    [0x38, 0x0c, 0x0c, 0x07, 0x0c, 0x0c, 0x38, 0x00], // '{'
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // '|'
+    [0x07, 0x0c, 0x0c, 0x38, 0x0c, 0x0c, 0x07, 0x00], // '}'
+    [0x6e, 0x3b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+// the character/attribute buffer, written by the guest through `DeviceVgaText`
+pub struct VgaText {
+    cells: Box<[u8]>,
+    // the cells changed since the last render
+    dirty: bool,
+}
+
+impl VgaText {
+    pub fn new() -> Self {
+        VgaText {
+            cells: BLANK_CELL.repeat(TEXT_COLS * TEXT_ROWS).into_boxed_slice(),
+            dirty: true,
+        }
+    }
+
+    pub(crate) fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub(crate) fn cells_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+
+    pub(crate) fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    // the screen in TEXT_FRAME_MODE
+    pub fn render(&self) -> Vec<u8> {
+        let pitch = TEXT_FRAME_MODE.pitch();
+        let mut pixels = vec![0_u8; TEXT_FRAME_MODE.size()];
+        for (i, cell) in self.cells.chunks_exact(2).enumerate() {
+            let (ch, attr) = (cell[0], cell[1]);
+            let glyph = match ch {
+                0x20..=0x7e => FONT_8X8[(ch - 0x20) as usize],
+                _ => [0; 8],
+            };
+            let fg = PALETTE[(attr & 0xf) as usize].to_le_bytes();
+            let bg = PALETTE[(attr >> 4 & 0x7) as usize].to_le_bytes();
+            let (col, row) = (i % TEXT_COLS, i / TEXT_COLS);
+            for y in 0..CELL_H {
+                let bits = glyph[y / 2];
+                let line = (row * CELL_H + y) * pitch + col * CELL_W * 4;
+                for x in 0..CELL_W {
+                    let color = if bits >> x & 1 != 0 { &fg } else { &bg };
+                    pixels[line + x * 4..line + x * 4 + 4].copy_from_slice(color);
+                }
+            }
+        }
+        pixels
+    }
+
+    // a new render when a cell changed
+    pub fn take_frame(&mut self) -> Option<Vec<u8>> {
+        core::mem::take(&mut self.dirty).then(|| self.render())
+    }
+}
+
+impl Default for VgaText {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the text buffer at VGA_TEXT_ADDR, shown while VGACTL selects the text mode
+pub struct DeviceVgaText {
+    frame: Arc<Mutex<VgaFrame>>,
+}
+
+impl DeviceVgaText {
+    pub fn new(frame: Arc<Mutex<VgaFrame>>) -> Self {
+        DeviceVgaText { frame }
+    }
+}
+
+impl DeviceBase for DeviceVgaText {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        let frame = self.frame.lock().unwrap();
+        let mut data_bytes = 0_u64.to_le_bytes();
+        data_bytes[..len].copy_from_slice(&frame.text.cells[addr as usize..addr as usize + len]);
+        u64::from_le_bytes(data_bytes)
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        let mut frame = self.frame.lock().unwrap();
+        let data_bytes = data.to_le_bytes();
+        frame.text.cells[addr as usize..addr as usize + len].copy_from_slice(&data_bytes[..len]);
+        frame.text.mark_dirty();
+        0
+    }
+
+    fn copy_from_slice(&mut self, addr: u64, slice: &[u8]) {
+        let mut frame = self.frame.lock().unwrap();
+        frame.text.cells[addr as usize..addr as usize + slice.len()].copy_from_slice(slice);
+        frame.text.mark_dirty();
+    }
+
+    fn get_name(&self) -> &'static str {
+        "VGA_TEXT"
+    }
+}
+
+#[cfg(test)]
+mod test_vga_text {
+    use std::sync::{Arc, Mutex};
+
+    use super::{DeviceVgaText, TEXT_COLS, TEXT_FRAME_MODE};
+    use crate::device::{device_am_vga::VgaFrame, device_trait::DeviceBase};
+
+    #[test]
+    fn vga_text_test() {
+        let frame = Arc::new(Mutex::new(VgaFrame::default()));
+        let mut text = DeviceVgaText::new(frame.clone());
+        assert!(frame.lock().unwrap().text.take_frame().is_some());
+        assert!(frame.lock().unwrap().text.take_frame().is_none());
+
+        // 'A' yellow on blue in row 1, column 2
+        let cell = (TEXT_COLS + 2) as u64 * 2;
+        text.do_write(cell, 0x1e41, 2);
+        assert_eq!(text.do_read(cell, 2), 0x1e41);
+        let pixels = frame.lock().unwrap().text.take_frame().unwrap();
+        let pitch = TEXT_FRAME_MODE.pitch();
+        let pixel = |x: usize, y: usize| {
+            let at = y * pitch + x * 4;
+            u32::from_le_bytes(pixels[at..at + 4].try_into().unwrap())
+        };
+        // the top row of 'A' is 0x0c, pixels 2 and 3 of the cell
+        let (x, y) = (2 * 8, 16);
+        assert_eq!(pixel(x + 1, y), 0xff00_00aa);
+        assert_eq!(pixel(x + 2, y), 0xffff_ff55);
+        assert_eq!(pixel(x + 3, y + 1), 0xffff_ff55);
+        // the blank cells are black
+        assert_eq!(pixel(0, 0), 0xff00_0000);
+    }
+}
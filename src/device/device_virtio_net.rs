@@ -0,0 +1,223 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{
+    device_trait::BusMaster,
+    virtio::{VirtioDevice, Virtqueue},
+};
+use crate::net::NetBackend;
+
+pub const VIRTIO_NET_F_MAC: u64 = 1 << 5;
+pub const VIRTIO_NET_F_STATUS: u64 = 1 << 16;
+const VIRTIO_NET_S_LINK_UP: u8 = 1;
+const DEVICE_ID_NET: u32 = 1;
+const RECEIVEQ: usize = 0;
+const TRANSMITQ: usize = 1;
+// struct virtio_net_hdr with num_buffers, no offloads are negotiated
+pub const NET_HDR_LEN: usize = 12;
+// the header and the largest frame, a longer transmit chain is dropped
+const MAX_TX_LEN: usize = NET_HDR_LEN + 65535;
+pub const VIRTIO_NET_IRQ: u32 = 1;
+pub const DEFAULT_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+// virtio-net (device id 1), queue 0 receives and queue 1 transmits
+pub struct VirtioNet {
+    mac: [u8; 6],
+    backend: Box<dyn NetBackend>,
+    // a frame waiting for a receive buffer of the guest
+    pending: Option<Vec<u8>>,
+    rx_frames: u64,
+    tx_frames: u64,
+}
+
+impl VirtioNet {
+    pub fn new(mac: [u8; 6], backend: Box<dyn NetBackend>) -> Self {
+        VirtioNet {
+            mac,
+            backend,
+            pending: None,
+            rx_frames: 0,
+            tx_frames: 0,
+        }
+    }
+
+    // (received, transmitted) frames of the guest
+    pub fn frames(&self) -> (u64, u64) {
+        (self.rx_frames, self.tx_frames)
+    }
+
+    fn transmit(&mut self, queue: &mut Virtqueue, mem: &mut dyn BusMaster) -> bool {
+        let mut used = false;
+        while let Some(chain) = queue.pop(mem) {
+            let data = chain.read_all(mem, MAX_TX_LEN).unwrap_or_default();
+            if data.len() > NET_HDR_LEN {
+                self.backend.send(&data[NET_HDR_LEN..]);
+                self.tx_frames += 1;
+            }
+            queue.push_used(mem, chain.head, 0);
+            used = true;
+        }
+        used
+    }
+
+    fn receive(&mut self, queue: &mut Virtqueue, mem: &mut dyn BusMaster) -> bool {
+        let mut used = false;
+        while let Some(frame) = self.pending.take().or_else(|| self.backend.recv()) {
+            let Some(chain) = queue.pop(mem) else {
+                self.pending = Some(frame);
+                break;
+            };
+            let mut data = Vec::with_capacity(NET_HDR_LEN + frame.len());
+            // num_buffers is 1
+            data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]);
+            data.extend_from_slice(&frame);
+            let len = chain.write_all(mem, &data);
+            queue.push_used(mem, chain.head, len as u32);
+            self.rx_frames += 1;
+            used = true;
+        }
+        used
+    }
+}
+
+impl VirtioDevice for VirtioNet {
+    fn device_id(&self) -> u32 {
+        DEVICE_ID_NET
+    }
+
+    fn features(&self) -> u64 {
+        VIRTIO_NET_F_MAC | VIRTIO_NET_F_STATUS
+    }
+
+    fn queue_count(&self) -> usize {
+        2
+    }
+
+    // mac[6], status (le16)
+    fn read_config(&mut self, offset: u64, len: usize) -> u64 {
+        let mut config = [0; 8];
+        config[..6].copy_from_slice(&self.mac);
+        config[6] = VIRTIO_NET_S_LINK_UP;
+        (0..len)
+            .filter_map(|i| config.get(offset as usize + i).map(|x| (i, *x)))
+            .fold(0, |acc, (i, x)| acc | (x as u64) << (8 * i))
+    }
+
+    fn process(&mut self, queues: &mut [Virtqueue], mem: &mut dyn BusMaster) -> bool {
+        let (rx, tx) = queues.split_at_mut(TRANSMITQ);
+        let sent = self.transmit(&mut tx[0], mem);
+        let received = self.receive(&mut rx[RECEIVEQ], mem);
+        sent || received
+    }
+
+    fn reset(&mut self) {
+        self.pending = None;
+    }
+
+    fn get_name(&self) -> &'static str {
+        "VIRTIO_NET"
+    }
+}
+
+#[cfg(test)]
+mod test_virtio_net {
+    use alloc::{boxed::Box, collections::VecDeque, rc::Rc, vec::Vec};
+    use core::cell::RefCell;
+
+    use super::{VirtioNet, DEFAULT_MAC, NET_HDR_LEN};
+    use crate::device::{
+        device_memory::SharedMemory, device_trait::DeviceBase, device_trait::MEM_BASE,
+        virtio::VirtioMmio,
+    };
+    use crate::net::NetBackend;
+
+    #[derive(Default)]
+    struct Wire {
+        to_host: Vec<Vec<u8>>,
+        to_guest: VecDeque<Vec<u8>>,
+    }
+
+    struct Loop(Rc<RefCell<Wire>>);
+
+    impl NetBackend for Loop {
+        fn send(&mut self, frame: &[u8]) {
+            self.0.borrow_mut().to_host.push(frame.to_vec());
+        }
+        fn recv(&mut self) -> Option<Vec<u8>> {
+            self.0.borrow_mut().to_guest.pop_front()
+        }
+    }
+
+    #[test]
+    fn virtio_net_test() {
+        let mem = SharedMemory::new(MEM_BASE, 0x10000);
+        let wire = Rc::new(RefCell::new(Wire::default()));
+        let net = VirtioNet::new(DEFAULT_MAC, Box::new(Loop(wire.clone())));
+        let mut dev = VirtioMmio::new(net, mem.clone());
+        assert_eq!(dev.do_read(0x0, 4), 0x7472_6976);
+        assert_eq!(dev.do_read(0x8, 4), 1);
+        assert_eq!(dev.do_read(0x100, 4), 0x1200_5452);
+        assert_eq!(dev.do_read(0x104, 4), 0x0001_5634);
+        dev.do_write(0x14, 1, 4);
+        assert_eq!(dev.do_read(0x10, 4), 1);
+
+        // queue q: desc at 0x1000 * (q + 1), avail +0x400, used +0x800, 4 entries
+        for q in 0..2 {
+            let base = MEM_BASE + 0x1000 * (q + 1);
+            dev.do_write(0x30, q, 4);
+            dev.do_write(0x38, 4, 4);
+            dev.do_write(0x80, base & 0xffff_ffff, 4);
+            dev.do_write(0x90, (base + 0x400) & 0xffff_ffff, 4);
+            dev.do_write(0xa0, (base + 0x800) & 0xffff_ffff, 4);
+            dev.do_write(0x44, 1, 4);
+        }
+        dev.do_write(0x70, 0xf, 4);
+        let add_buf = |q: u64, addr: u64, len: u32, write: bool| {
+            let base = MEM_BASE + 0x1000 * (q + 1);
+            let mut desc = [0; 16];
+            desc[0..8].copy_from_slice(&addr.to_le_bytes());
+            desc[8..12].copy_from_slice(&len.to_le_bytes());
+            desc[12] = (write as u8) << 1;
+            mem.write_bytes(base, &desc);
+            mem.write_bytes(base + 0x400 + 2, &1_u16.to_le_bytes());
+        };
+
+        // transmit: the header is dropped
+        let mut tx = [0; NET_HDR_LEN + 4];
+        tx[NET_HDR_LEN..].copy_from_slice(b"ping");
+        mem.write_bytes(MEM_BASE + 0x4000, &tx);
+        add_buf(1, MEM_BASE + 0x4000, tx.len() as u32, false);
+        dev.do_write(0x50, 1, 4);
+        assert_eq!(wire.borrow().to_host, [b"ping".to_vec()]);
+        assert_eq!(dev.do_read(0x60, 4), 1);
+        dev.do_write(0x64, 1, 4);
+
+        // a chain longer than a frame is dropped, desc 0 in avail slot 1
+        add_buf(1, MEM_BASE + 0x4000, u32::MAX, false);
+        mem.write_bytes(MEM_BASE + 0x2400 + 2, &2_u16.to_le_bytes());
+        dev.do_write(0x50, 1, 4);
+        assert_eq!(wire.borrow().to_host.len(), 1);
+        assert_eq!(dev.do_read(0x60, 4), 1);
+        dev.do_write(0x64, 1, 4);
+
+        // receive: the frame waits for a buffer
+        wire.borrow_mut().to_guest.push_back(b"pong".to_vec());
+        dev.do_update();
+        assert_eq!(dev.do_read(0x60, 4), 0);
+        add_buf(0, MEM_BASE + 0x5000, 0x600, true);
+        dev.do_update();
+        assert_eq!(dev.do_read(0x60, 4), 1);
+        let mut used = [0; 12];
+        mem.read_bytes(MEM_BASE + 0x1800, &mut used);
+        assert_eq!(used[2..4], [1, 0]);
+        assert_eq!(used[8], (NET_HDR_LEN + 4) as u8);
+        let mut rx = [0; NET_HDR_LEN + 4];
+        mem.read_bytes(MEM_BASE + 0x5000, &mut rx);
+        assert_eq!(rx[10], 1);
+        assert_eq!(&rx[NET_HDR_LEN..], b"pong");
+        assert_eq!(dev.device().frames(), (1, 1));
+
+        // a reset clears the queues
+        dev.do_write(0x70, 0, 4);
+        assert_eq!(dev.do_read(0x44, 4), 0);
+    }
+}
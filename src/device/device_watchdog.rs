@@ -0,0 +1,189 @@
+use core::cell::RefCell;
+
+use crate::tools::{RcCell, RcRefCell};
+
+use super::device_trait::DeviceBase;
+
+// a watchdog counting mtime ticks, which resets the machine or stops the
+// simulation when the guest does not feed it in time.
+// base + 0x00: TIMEOUT, in mtime ticks
+// base + 0x04: CTRL, bit 0 EN, bit 1 LOCK (CTRL and TIMEOUT are read-only
+//              until the next reset)
+// base + 0x08: FEED, writing WDT_FEED_KEY restarts the count, reads 0
+// base + 0x0c: COUNT, the ticks left, read-only
+// base + 0x10: STATUS, bit 0 the last reset came from the watchdog,
+//              cleared by writing 1
+// the host polls `take_expired`, see `RVsim::add_watchdog`
+pub const WDT_SIZE: u64 = 0x1000;
+pub const WDT_FEED_KEY: u32 = 0x0d09_f00d;
+
+const TIMEOUT: u64 = 0x00;
+const CTRL: u64 = 0x04;
+const FEED: u64 = 0x08;
+const COUNT: u64 = 0x0c;
+const STATUS: u64 = 0x10;
+
+const CTRL_EN: u32 = 1 << 0;
+const CTRL_LOCK: u32 = 1 << 1;
+const STATUS_WDTRST: u32 = 1 << 0;
+
+// what the host does when the watchdog bites
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogAction {
+    // `RVsim::reset`, like the reset line of a real watchdog
+    Reset,
+    // the harts abort, to catch a hung guest
+    Stop,
+}
+
+struct WatchdogState {
+    mtime: RcCell<u64>,
+    action: WatchdogAction,
+    timeout: u32,
+    ctrl: u32,
+    status: u32,
+    // the mtime of the last feed
+    fed_at: u64,
+    expired: bool,
+}
+
+impl WatchdogState {
+    fn left(&self) -> u64 {
+        let elapsed = self.mtime.get().saturating_sub(self.fed_at);
+        (self.timeout as u64).saturating_sub(elapsed)
+    }
+    fn feed(&mut self) {
+        self.fed_at = self.mtime.get();
+    }
+}
+
+// the device and the host share one state
+#[derive(Clone)]
+pub struct DeviceWatchdog {
+    inner: RcRefCell<WatchdogState>,
+}
+
+impl DeviceWatchdog {
+    // `mtime` is the timebase, from `Clint::mtime`
+    pub fn new(mtime: RcCell<u64>, action: WatchdogAction) -> Self {
+        DeviceWatchdog {
+            inner: RcRefCell::new(RefCell::new(WatchdogState {
+                mtime,
+                action,
+                timeout: u32::MAX,
+                ctrl: 0,
+                status: 0,
+                fed_at: 0,
+                expired: false,
+            })),
+        }
+    }
+
+    // the action once per expiry
+    pub fn take_expired(&self) -> Option<WatchdogAction> {
+        let mut wdt = self.inner.borrow_mut();
+        let expired = core::mem::take(&mut wdt.expired);
+        expired.then_some(wdt.action)
+    }
+}
+
+impl DeviceBase for DeviceWatchdog {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "watchdog read len:{}", len);
+        let wdt = self.inner.borrow();
+        let data = match addr {
+            TIMEOUT => wdt.timeout,
+            CTRL => wdt.ctrl,
+            COUNT if wdt.ctrl & CTRL_EN != 0 => wdt.left() as u32,
+            COUNT => wdt.timeout,
+            STATUS => wdt.status,
+            _ => 0,
+        };
+        data as u64
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        assert_eq!(len, 4, "watchdog write len:{}", len);
+        let mut wdt = self.inner.borrow_mut();
+        let data = data as u32;
+        let locked = wdt.ctrl & CTRL_LOCK != 0;
+        match addr {
+            TIMEOUT if !locked => wdt.timeout = data,
+            CTRL if !locked => {
+                // the count starts from the enable
+                if wdt.ctrl & CTRL_EN == 0 {
+                    wdt.feed();
+                }
+                wdt.ctrl = data & (CTRL_EN | CTRL_LOCK);
+            }
+            FEED if data == WDT_FEED_KEY => wdt.feed(),
+            STATUS => wdt.status &= !(data & STATUS_WDTRST),
+            _ => {}
+        }
+        0
+    }
+
+    // once it bites it is off until the guest enables it again
+    fn do_update(&mut self) {
+        let mut wdt = self.inner.borrow_mut();
+        if wdt.ctrl & CTRL_EN != 0 && wdt.left() == 0 {
+            wdt.ctrl = 0;
+            wdt.expired = true;
+            wdt.status |= STATUS_WDTRST;
+        }
+    }
+
+    // STATUS survives the reset, the driver reports the cause of the boot
+    fn reset(&mut self) {
+        let mut wdt = self.inner.borrow_mut();
+        wdt.timeout = u32::MAX;
+        wdt.ctrl = 0;
+        wdt.expired = false;
+    }
+
+    fn get_name(&self) -> &'static str {
+        "WATCHDOG"
+    }
+}
+
+#[cfg(test)]
+mod test_watchdog {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use super::{DeviceWatchdog, WatchdogAction, WDT_FEED_KEY};
+    use crate::device::device_trait::DeviceBase;
+
+    #[test]
+    fn watchdog_test() {
+        let mtime = Rc::new(Cell::new(100));
+        let mut wdt = DeviceWatchdog::new(mtime.clone(), WatchdogAction::Reset);
+        wdt.do_write(0x00, 50, 4);
+        wdt.do_write(0x04, 0b11, 4);
+        // locked
+        wdt.do_write(0x00, 1000, 4);
+        wdt.do_write(0x04, 0, 4);
+        assert_eq!(wdt.do_read(0x00, 4), 50);
+
+        mtime.set(140);
+        wdt.do_update();
+        assert_eq!(wdt.do_read(0x0c, 4), 10);
+        // a feed with a wrong key does nothing
+        wdt.do_write(0x08, 1, 4);
+        wdt.do_write(0x08, WDT_FEED_KEY as u64, 4);
+        mtime.set(180);
+        wdt.do_update();
+        assert_eq!(wdt.take_expired(), None);
+        mtime.set(190);
+        wdt.do_update();
+        assert_eq!(wdt.take_expired(), Some(WatchdogAction::Reset));
+        assert_eq!(wdt.take_expired(), None);
+
+        // after the reset it is unlocked, the cause stays in STATUS
+        wdt.reset();
+        assert_eq!(wdt.do_read(0x04, 4), 0);
+        assert_eq!(wdt.do_read(0x10, 4), 1);
+        wdt.do_write(0x10, 1, 4);
+        assert_eq!(wdt.do_read(0x10, 4), 0);
+    }
+}
@@ -0,0 +1,144 @@
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::{
+    io::{stdin, Read},
+    thread::{self, JoinHandle},
+};
+
+use crate::tools::FifoUnbounded;
+
+// Ctrl+A, like qemu -nographic: Ctrl+A X quits, Ctrl+A Ctrl+A sends Ctrl+A
+const ESCAPE: u8 = 0x01;
+
+// the termios before the raw mode
+#[cfg(unix)]
+static SAVED: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+// the host terminal in raw mode: the keys go to the guest as they are typed,
+// without echo and line editing, and Ctrl+C reaches the guest shell.
+// the output processing is kept, so the host logs still end their lines
+pub struct RawTerminal {
+    raw: bool,
+}
+
+impl RawTerminal {
+    // does nothing if stdin is not a terminal or the host is not unix
+    #[cfg(not(unix))]
+    pub fn enable() -> Self {
+        RawTerminal { raw: false }
+    }
+
+    #[cfg(unix)]
+    pub fn enable() -> Self {
+        let mut termios = unsafe { core::mem::zeroed::<libc::termios>() };
+        let is_tty = unsafe { libc::isatty(libc::STDIN_FILENO) } == 1;
+        if !is_tty || unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return RawTerminal { raw: false };
+        }
+        *SAVED.lock().unwrap() = Some(termios);
+        termios.c_iflag &= !(libc::IGNBRK
+            | libc::BRKINT
+            | libc::PARMRK
+            | libc::ISTRIP
+            | libc::INLCR
+            | libc::IGNCR
+            | libc::ICRNL
+            | libc::IXON);
+        termios.c_lflag &= !(libc::ECHO | libc::ECHONL | libc::ICANON | libc::ISIG | libc::IEXTEN);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+        RawTerminal { raw: true }
+    }
+
+    pub fn is_raw(&self) -> bool {
+        self.raw
+    }
+
+    fn restore() {
+        #[cfg(unix)]
+        if let Some(termios) = SAVED.lock().unwrap().take() {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+        }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        Self::restore();
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ConsoleKey {
+    Byte(u8),
+    Exit,
+    None,
+}
+
+// the escape sequences of the host console
+#[derive(Default)]
+struct ConsoleEscape {
+    armed: bool,
+}
+
+impl ConsoleEscape {
+    fn feed(&mut self, c: u8) -> ConsoleKey {
+        match (self.armed, c) {
+            (false, ESCAPE) => {
+                self.armed = true;
+                ConsoleKey::None
+            }
+            (false, c) => ConsoleKey::Byte(c),
+            (true, b'x' | b'X') => ConsoleKey::Exit,
+            (true, c) => {
+                self.armed = false;
+                match c {
+                    ESCAPE => ConsoleKey::Byte(ESCAPE),
+                    _ => ConsoleKey::None,
+                }
+            }
+        }
+    }
+}
+
+// reads the host stdin into the uart rx fifo, the uart raises its rx
+// interrupt on the next bus update. the thread ends at the end of stdin
+pub fn spawn_stdin_reader(rx: FifoUnbounded<u8>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut escape = ConsoleEscape::default();
+        let mut buf = [0; 64];
+        while let Ok(n @ 1..) = stdin().read(&mut buf) {
+            for c in &buf[..n] {
+                match escape.feed(*c) {
+                    ConsoleKey::Byte(c) => rx.push(c),
+                    ConsoleKey::Exit => {
+                        RawTerminal::restore();
+                        eprintln!("\r\nrv64emu: terminated by Ctrl+A X");
+                        std::process::exit(0);
+                    }
+                    ConsoleKey::None => {}
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test_host_console {
+    use super::{ConsoleEscape, ConsoleKey, ESCAPE};
+
+    #[test]
+    fn console_escape_test() {
+        let mut escape = ConsoleEscape::default();
+        assert_eq!(escape.feed(b'a'), ConsoleKey::Byte(b'a'));
+        assert_eq!(escape.feed(0x03), ConsoleKey::Byte(0x03));
+        assert_eq!(escape.feed(ESCAPE), ConsoleKey::None);
+        assert_eq!(escape.feed(ESCAPE), ConsoleKey::Byte(ESCAPE));
+        assert_eq!(escape.feed(ESCAPE), ConsoleKey::None);
+        assert_eq!(escape.feed(b'q'), ConsoleKey::None);
+        assert_eq!(escape.feed(b'x'), ConsoleKey::Byte(b'x'));
+        assert_eq!(escape.feed(ESCAPE), ConsoleKey::None);
+        assert_eq!(escape.feed(b'X'), ConsoleKey::Exit);
+    }
+}
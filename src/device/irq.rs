@@ -0,0 +1,169 @@
+use alloc::rc::Rc;
+use core::cell::Cell;
+
+use crate::{rv64core::csr_regs_define::XipIn, tools::RcCell};
+
+// the interrupt lines of a machine: a device drives an `IrqLine` of an
+// `InterruptController` (PLIC, APLIC), a controller drives the `HartIrq`
+// of the harts (the eip, tip and sip bits of their mip)
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqTrigger {
+    // a request while the line is high, the next one only after completion
+    Level,
+    // a request for every rising edge, edges seen while a request
+    // is outstanding are counted and forwarded after completion
+    Edge,
+}
+
+struct IrqLineState {
+    level: Cell<bool>,
+    // the rising edges the controller has not taken yet
+    edges: Cell<u32>,
+}
+
+// the device side of an interrupt source, see
+// `InterruptController::alloc_irq_line` and `Bus::add_device_with_irq`.
+// the device raises and lowers it at any time, the controller samples it
+// at its tick. the rising edges are latched, a pulse between two ticks
+// is not lost
+#[derive(Clone)]
+pub struct IrqLine {
+    id: u32,
+    state: Rc<IrqLineState>,
+}
+
+impl IrqLine {
+    // a low line of source `id`
+    pub fn new(id: u32) -> Self {
+        IrqLine {
+            id,
+            state: Rc::new(IrqLineState {
+                level: Cell::new(false),
+                edges: Cell::new(0),
+            }),
+        }
+    }
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+    pub fn raise(&self) {
+        self.set(true);
+    }
+    pub fn lower(&self) {
+        self.set(false);
+    }
+    pub fn set(&self, level: bool) {
+        if level && !self.state.level.get() {
+            let edges = self.state.edges.get();
+            self.state.edges.set(edges.saturating_add(1));
+        }
+        self.state.level.set(level);
+    }
+    pub fn level(&self) -> bool {
+        self.state.level.get()
+    }
+    // the rising edges since the last call, for the controller
+    pub fn take_edges(&self) -> u32 {
+        self.state.edges.take()
+    }
+}
+
+// a controller the devices are wired to, one line per source id
+pub trait InterruptController {
+    // a new line of source `irq_id`, the id can be taken once
+    fn alloc_irq_line(&mut self, irq_id: u32, trigger: IrqTrigger) -> IrqLine;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HartIrqKind {
+    MSoft,
+    SSoft,
+    MTimer,
+    MExternal,
+    SExternal,
+}
+
+// one interrupt of one hart as driven by a controller, a bit of the xip
+// the hart shares with its csrs
+#[derive(Clone)]
+pub struct HartIrq {
+    xip: RcCell<XipIn>,
+    kind: HartIrqKind,
+}
+
+impl HartIrq {
+    pub fn new(xip: RcCell<XipIn>, kind: HartIrqKind) -> Self {
+        HartIrq { xip, kind }
+    }
+    // the external interrupt of an M or S level controller
+    pub fn external(xip: RcCell<XipIn>, mmode: bool) -> Self {
+        let kind = match mmode {
+            true => HartIrqKind::MExternal,
+            false => HartIrqKind::SExternal,
+        };
+        HartIrq::new(xip, kind)
+    }
+    pub fn kind(&self) -> HartIrqKind {
+        self.kind
+    }
+    pub fn raise(&self) {
+        self.set(true);
+    }
+    pub fn lower(&self) {
+        self.set(false);
+    }
+    pub fn set(&self, level: bool) {
+        let mut xip = self.xip.get();
+        match self.kind {
+            HartIrqKind::MSoft => xip.set_msip(level),
+            HartIrqKind::SSoft => xip.set_ssip(level),
+            HartIrqKind::MTimer => xip.set_mtip(level),
+            HartIrqKind::MExternal => xip.set_meip(level),
+            HartIrqKind::SExternal => xip.set_seip(level),
+        }
+        self.xip.set(xip);
+    }
+    pub fn level(&self) -> bool {
+        let xip = self.xip.get();
+        match self.kind {
+            HartIrqKind::MSoft => xip.msip(),
+            HartIrqKind::SSoft => xip.ssip(),
+            HartIrqKind::MTimer => xip.mtip(),
+            HartIrqKind::MExternal => xip.meip(),
+            HartIrqKind::SExternal => xip.seip(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_irq {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use super::{HartIrq, HartIrqKind, IrqLine};
+    use crate::rv64core::csr_regs_define::XipIn;
+
+    #[test]
+    fn irq_line_test() {
+        let line = IrqLine::new(5);
+        let device = line.clone();
+        // a pulse between two samples
+        device.raise();
+        device.lower();
+        device.raise();
+        device.raise();
+        assert_eq!(line.take_edges(), 2);
+        assert_eq!(line.take_edges(), 0);
+        assert!(line.level());
+
+        let xip = Rc::new(Cell::new(XipIn::new()));
+        let seip = HartIrq::external(xip.clone(), false);
+        let mtip = HartIrq::new(xip.clone(), HartIrqKind::MTimer);
+        seip.raise();
+        mtip.raise();
+        mtip.lower();
+        assert!(xip.get().seip() && !xip.get().meip() && !xip.get().mtip());
+        assert!(seip.level());
+    }
+}
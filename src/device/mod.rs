@@ -1,18 +1,48 @@
 pub mod device_16550a;
+pub mod device_aclint;
+pub mod device_aplic;
 pub mod device_am_uart;
+pub mod device_boot_rom;
+pub mod device_debug_module;
+pub mod device_dma;
+pub mod device_gpio;
+pub mod device_i2c;
+pub mod device_imsic;
 pub mod device_memory;
+pub mod device_ps2_mouse;
+pub mod device_rom;
 pub mod device_sifive_clint;
 pub mod device_sifive_plic;
 pub mod device_sifive_uart;
+pub mod device_spi_flash;
 pub mod device_trait;
+pub mod device_virtio_net;
+pub mod device_watchdog;
+pub mod irq;
+pub mod uart;
+pub mod virtio;
 
+#[cfg(feature = "std")]
+pub mod device_am_disk;
 #[cfg(feature = "std")]
 pub mod device_am_rtc;
+#[cfg(feature = "std")]
+pub mod device_pipe;
+#[cfg(feature = "std")]
+pub mod char_backend;
+#[cfg(feature = "std")]
+pub mod host_console;
+#[cfg(all(feature = "std", unix))]
+pub mod device_host_shm;
+#[cfg(feature = "support_am")]
+pub mod device_am_kb;
+#[cfg(feature = "support_am")]
+pub mod device_am_mouse;
 cfg_if::cfg_if! {
     if #[cfg(all(feature = "device_sdl2", feature = "std"))] {
-        pub mod device_am_kb;
-        pub mod device_am_mouse;
+        pub mod device_am_audio;
         pub mod device_am_vga;
         pub mod device_am_vgactl;
+        pub mod device_vga_text;
     }
 }
@@ -0,0 +1,175 @@
+use alloc::{boxed::Box, format, string::String, string::ToString};
+
+use crate::{
+    error::{RvEmuError, RvEmuResult},
+    rv64core::bus::{Bus, DeviceType},
+    tools::FifoUnbounded,
+};
+
+use super::{
+    device_16550a::Device16550aUART,
+    device_sifive_plic::{SIFIVE_UART_IRQ, UART16550_IRQ},
+    device_sifive_uart::DeviceSifiveUart,
+    device_trait::DeviceBase,
+    irq::IrqTrigger,
+};
+
+pub const UART_SIZE: u64 = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartKind {
+    Sifive,
+    Ns16550a,
+}
+
+impl UartKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UartKind::Sifive => "sifive",
+            UartKind::Ns16550a => "ns16550a",
+        }
+    }
+}
+
+// one uart of the machine: KIND@ADDR[,irq=N][,backend=BACKEND].
+// without irq the uart is polled only, the backend is the host side
+// (see char_backend), stdio by default
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UartConfig {
+    pub kind: UartKind,
+    pub base: u64,
+    pub irq: Option<u32>,
+    pub backend: String,
+}
+
+impl UartConfig {
+    // the console of the default machine
+    pub fn sifive_console() -> Self {
+        UartConfig {
+            kind: UartKind::Sifive,
+            base: 0xc000_0000,
+            irq: Some(SIFIVE_UART_IRQ),
+            backend: "stdio".to_string(),
+        }
+    }
+
+    pub fn ns16550a() -> Self {
+        UartConfig {
+            kind: UartKind::Ns16550a,
+            base: 0x1000_0000,
+            irq: Some(UART16550_IRQ),
+            backend: "stdio".to_string(),
+        }
+    }
+
+    pub fn parse(spec: &str) -> RvEmuResult<Self> {
+        let bad = |reason: &str| RvEmuError::BadConfig(format!("uart '{spec}': {reason}"));
+        let mut fields = spec.split(',');
+        let (kind, base) = fields
+            .next()
+            .and_then(|x| x.split_once('@'))
+            .ok_or_else(|| bad("expected KIND@ADDR[,irq=N][,backend=BACKEND]"))?;
+        let kind = match kind {
+            "sifive" => UartKind::Sifive,
+            "ns16550a" | "16550a" => UartKind::Ns16550a,
+            _ => return Err(bad("the kind is sifive or ns16550a")),
+        };
+        let base = u64::from_str_radix(base.trim_start_matches("0x"), 16)
+            .map_err(|_| bad("the address is a hex number"))?;
+        let mut uart = UartConfig {
+            kind,
+            base,
+            irq: None,
+            backend: "stdio".to_string(),
+        };
+        while let Some(field) = fields.next() {
+            match field.split_once('=') {
+                Some(("irq", irq)) => {
+                    uart.irq = Some(irq.parse().map_err(|_| bad("irq is a number"))?);
+                }
+                // the backend may have commas of its own, it is the last field
+                Some(("backend", backend)) => {
+                    let tail: String = fields.by_ref().flat_map(|x| [",", x]).collect();
+                    uart.backend = format!("{backend}{tail}");
+                }
+                _ => return Err(bad(&format!("unknown field '{field}'"))),
+            }
+        }
+        Ok(uart)
+    }
+
+    pub fn instance(&self, tx: FifoUnbounded<u8>, rx: FifoUnbounded<u8>) -> Box<dyn DeviceBase> {
+        match self.kind {
+            UartKind::Sifive => Box::new(DeviceSifiveUart::new(tx, rx)),
+            UartKind::Ns16550a => Box::new(Device16550aUART::new(tx, rx)),
+        }
+    }
+
+    // the uart on `bus`, connected to its PLIC source if it has one
+    pub fn add_to(
+        &self,
+        bus: &mut Bus,
+        name: &'static str,
+        tx: FifoUnbounded<u8>,
+        rx: FifoUnbounded<u8>,
+    ) -> RvEmuResult<()> {
+        let device = DeviceType {
+            start: self.base,
+            len: UART_SIZE,
+            instance: self.instance(tx, rx),
+            name,
+        };
+        match self.irq {
+            Some(irq) => bus.add_device_with_irq(device, irq, IrqTrigger::Level),
+            None => bus.add_device(device),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_uart {
+    use super::{UartConfig, UartKind};
+    use crate::{
+        rv64core::bus::Bus,
+        tools::{fifo_unbounded_new, FifoUnbounded},
+    };
+
+    #[test]
+    fn uart_config_test() {
+        let uart = UartConfig::parse("ns16550a@0x10010000,irq=12,backend=tcp:4321").unwrap();
+        assert_eq!(uart.kind, UartKind::Ns16550a);
+        assert_eq!(uart.base, 0x1001_0000);
+        assert_eq!(uart.irq, Some(12));
+        assert_eq!(uart.backend, "tcp:4321");
+        let uart = UartConfig::parse("sifive@c0001000").unwrap();
+        assert_eq!((uart.irq, uart.backend.as_str()), (None, "stdio"));
+        assert!(UartConfig::parse("pl011@0x9000000").is_err());
+        assert!(UartConfig::parse("sifive@0xc0001000,baud=9600").is_err());
+
+        // a second 16550a next to the default uarts, an overlap is refused
+        let mut bus = Bus::new();
+        let fifos = || -> (FifoUnbounded<u8>, FifoUnbounded<u8>) {
+            (fifo_unbounded_new(), fifo_unbounded_new())
+        };
+        let uarts = [
+            UartConfig::sifive_console(),
+            UartConfig::ns16550a(),
+            UartConfig::parse("ns16550a@0x10010000,irq=12").unwrap(),
+        ];
+        let mut txs = alloc::vec::Vec::new();
+        for (uart, name) in uarts.iter().zip(["uart0", "uart1", "uart2"]) {
+            let (tx, rx) = fifos();
+            uart.add_to(&mut bus, name, tx.clone(), rx).unwrap();
+            txs.push(tx);
+        }
+        let (tx, rx) = fifos();
+        let overlap = UartConfig::parse("sifive@0x10010800").unwrap();
+        assert!(overlap.add_to(&mut bus, "uart3", tx, rx).is_err());
+
+        // the third uart has its own line
+        assert_eq!(bus.read(0x1001_0005, 1).ok(), Some(0x60));
+        bus.write(0x1001_0000, b'x' as u64, 1).unwrap();
+        assert_eq!(txs[2].pop(), Some(b'x'));
+        assert!(txs[1].is_empty());
+    }
+}
@@ -0,0 +1,423 @@
+use alloc::{vec, vec::Vec};
+
+use super::{
+    device_memory::SharedMemory,
+    device_trait::{BusMaster, DeviceBase},
+    irq::IrqLine,
+};
+
+// virtio over MMIO, version 2 (virtio 1.2 section 4.2.2)
+const MAGIC_VALUE: u64 = 0x000;
+const VERSION: u64 = 0x004;
+const DEVICE_ID: u64 = 0x008;
+const VENDOR_ID: u64 = 0x00c;
+const DEVICE_FEATURES: u64 = 0x010;
+const DEVICE_FEATURES_SEL: u64 = 0x014;
+const DRIVER_FEATURES: u64 = 0x020;
+const DRIVER_FEATURES_SEL: u64 = 0x024;
+const QUEUE_SEL: u64 = 0x030;
+const QUEUE_NUM_MAX: u64 = 0x034;
+const QUEUE_NUM: u64 = 0x038;
+const QUEUE_READY: u64 = 0x044;
+const QUEUE_NOTIFY: u64 = 0x050;
+const INTERRUPT_STATUS: u64 = 0x060;
+const INTERRUPT_ACK: u64 = 0x064;
+const STATUS: u64 = 0x070;
+const QUEUE_DESC_LOW: u64 = 0x080;
+const QUEUE_DESC_HIGH: u64 = 0x084;
+const QUEUE_DRIVER_LOW: u64 = 0x090;
+const QUEUE_DRIVER_HIGH: u64 = 0x094;
+const QUEUE_DEVICE_LOW: u64 = 0x0a0;
+const QUEUE_DEVICE_HIGH: u64 = 0x0a4;
+const CONFIG_GENERATION: u64 = 0x0fc;
+const CONFIG: u64 = 0x100;
+
+pub const VIRTIO_MMIO_SIZE: u64 = 0x1000;
+pub const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+// "virt" and "rvem"
+const MAGIC: u64 = 0x7472_6976;
+const VENDOR: u64 = 0x6d65_7672;
+const STATUS_DRIVER_OK: u32 = 4;
+// the used buffer notification bit of InterruptStatus
+const INT_USED_BUFFER: u32 = 1;
+pub const QUEUE_SIZE_MAX: u16 = 256;
+
+const VIRTQ_DESC_F_NEXT: u16 = 1;
+const VIRTQ_DESC_F_WRITE: u16 = 2;
+const VIRTQ_AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+fn read_u16(mem: &mut dyn BusMaster, addr: u64) -> Option<u16> {
+    let mut buf = [0; 2];
+    mem.dma_read(addr, &mut buf)
+        .then(|| u16::from_le_bytes(buf))
+}
+
+// the buffers of one request, the driver reads the writable ones
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DescChain {
+    pub head: u16,
+    // (addr, len)
+    pub readable: Vec<(u64, u32)>,
+    pub writable: Vec<(u64, u32)>,
+}
+
+impl DescChain {
+    // the readable buffers, concatenated. None if they are longer than
+    // `max`, nothing is allocated for them then
+    pub fn read_all(&self, mem: &mut dyn BusMaster, max: usize) -> Option<Vec<u8>> {
+        let total: u64 = self.readable.iter().map(|x| x.1 as u64).sum();
+        if total > max as u64 {
+            return None;
+        }
+        let mut data = Vec::new();
+        for &(addr, len) in self.readable.iter() {
+            let start = data.len();
+            data.resize(start + len as usize, 0);
+            if !mem.dma_read(addr, &mut data[start..]) {
+                data.truncate(start);
+            }
+        }
+        Some(data)
+    }
+
+    // fill the writable buffers in order, the bytes written are returned
+    pub fn write_all(&self, mem: &mut dyn BusMaster, mut data: &[u8]) -> usize {
+        let mut written = 0;
+        for &(addr, len) in self.writable.iter() {
+            let n = data.len().min(len as usize);
+            if n == 0 || !mem.dma_write(addr, &data[..n]) {
+                break;
+            }
+            data = &data[n..];
+            written += n;
+        }
+        written
+    }
+
+    pub fn writable_len(&self) -> usize {
+        self.writable.iter().map(|x| x.1 as usize).sum()
+    }
+}
+
+// a split virtqueue, the three areas are in guest memory
+#[derive(Debug, Default, Clone)]
+pub struct Virtqueue {
+    pub num: u16,
+    pub ready: bool,
+    desc: u64,
+    avail: u64,
+    used: u64,
+    last_avail: u16,
+}
+
+impl Virtqueue {
+    fn new() -> Self {
+        Virtqueue {
+            num: QUEUE_SIZE_MAX,
+            ..Default::default()
+        }
+    }
+
+    // the next available chain, None if the queue is empty or not ready.
+    // a chain with a bad descriptor is returned with the buffers before it
+    pub fn pop(&mut self, mem: &mut dyn BusMaster) -> Option<DescChain> {
+        if !self.ready || self.num == 0 {
+            return None;
+        }
+        let avail_idx = read_u16(mem, self.avail + 2)?;
+        if avail_idx == self.last_avail {
+            return None;
+        }
+        let slot = self.last_avail % self.num;
+        let head = read_u16(mem, self.avail + 4 + 2 * slot as u64)?;
+        self.last_avail = self.last_avail.wrapping_add(1);
+
+        let mut chain = DescChain {
+            head,
+            ..Default::default()
+        };
+        let mut idx = head;
+        // a loop in the chain ends after `num` descriptors
+        for _ in 0..self.num {
+            if idx >= self.num {
+                break;
+            }
+            let mut desc = [0; 16];
+            if !mem.dma_read(self.desc + 16 * idx as u64, &mut desc) {
+                break;
+            }
+            let addr = u64::from_le_bytes(desc[0..8].try_into().unwrap());
+            let len = u32::from_le_bytes(desc[8..12].try_into().unwrap());
+            let flags = u16::from_le_bytes(desc[12..14].try_into().unwrap());
+            match flags & VIRTQ_DESC_F_WRITE {
+                0 => chain.readable.push((addr, len)),
+                _ => chain.writable.push((addr, len)),
+            }
+            if flags & VIRTQ_DESC_F_NEXT == 0 {
+                break;
+            }
+            idx = u16::from_le_bytes(desc[14..16].try_into().unwrap());
+        }
+        Some(chain)
+    }
+
+    // return a chain to the driver, `len` bytes were written into it
+    pub fn push_used(&mut self, mem: &mut dyn BusMaster, head: u16, len: u32) {
+        let Some(used_idx) = read_u16(mem, self.used + 2) else {
+            return;
+        };
+        let slot = used_idx % self.num;
+        let mut elem = [0; 8];
+        elem[0..4].copy_from_slice(&(head as u32).to_le_bytes());
+        elem[4..8].copy_from_slice(&len.to_le_bytes());
+        mem.dma_write(self.used + 4 + 8 * slot as u64, &elem);
+        mem.dma_write(self.used + 2, &used_idx.wrapping_add(1).to_le_bytes());
+    }
+
+    // the driver does not want an interrupt for the used buffers
+    pub fn no_interrupt(&self, mem: &mut dyn BusMaster) -> bool {
+        read_u16(mem, self.avail).is_some_and(|x| x & VIRTQ_AVAIL_F_NO_INTERRUPT != 0)
+    }
+
+    fn set_low(val: &mut u64, data: u64) {
+        *val = (*val & !0xffff_ffff) | (data & 0xffff_ffff);
+    }
+
+    fn set_high(val: &mut u64, data: u64) {
+        *val = (*val & 0xffff_ffff) | (data << 32);
+    }
+}
+
+// the device types behind the virtio MMIO transport
+pub trait VirtioDevice {
+    fn device_id(&self) -> u32;
+    // the device feature bits, VIRTIO_F_VERSION_1 is added by the transport
+    fn features(&self) -> u64;
+    fn queue_count(&self) -> usize;
+    fn read_config(&mut self, offset: u64, len: usize) -> u64;
+    fn write_config(&mut self, _offset: u64, _data: u64, _len: usize) {}
+    // called on a queue notify and on every bus update once the driver is ready,
+    // true if buffers were put into a used ring
+    fn process(&mut self, queues: &mut [Virtqueue], mem: &mut dyn BusMaster) -> bool;
+    // the driver wrote 0 to Status
+    fn reset(&mut self) {}
+    fn get_name(&self) -> &'static str;
+}
+
+// the virtio MMIO register file, the device does its own DMA into `mem`
+pub struct VirtioMmio<D: VirtioDevice> {
+    device: D,
+    mem: SharedMemory,
+    queues: Vec<Virtqueue>,
+    queue_sel: usize,
+    device_features_sel: u32,
+    driver_features_sel: u32,
+    driver_features: u64,
+    status: u32,
+    interrupt_status: u32,
+    irq: Option<IrqLine>,
+}
+
+impl<D: VirtioDevice> VirtioMmio<D> {
+    pub fn new(device: D, mem: SharedMemory) -> Self {
+        let queues = vec![Virtqueue::new(); device.queue_count()];
+        VirtioMmio {
+            device,
+            mem,
+            queues,
+            queue_sel: 0,
+            device_features_sel: 0,
+            driver_features_sel: 0,
+            driver_features: 0,
+            status: 0,
+            interrupt_status: 0,
+            irq: None,
+        }
+    }
+
+    pub fn device(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    pub fn driver_features(&self) -> u64 {
+        self.driver_features
+    }
+
+    fn device_features(&self) -> u64 {
+        self.device.features() | VIRTIO_F_VERSION_1
+    }
+
+    fn process(&mut self) {
+        if self.status & STATUS_DRIVER_OK == 0 {
+            return;
+        }
+        if self.device.process(&mut self.queues, &mut self.mem) {
+            let wanted = self.queues.iter().any(|x| !x.no_interrupt(&mut self.mem));
+            if wanted {
+                self.interrupt_status |= INT_USED_BUFFER;
+            }
+        }
+        self.update_irq();
+    }
+
+    fn update_irq(&self) {
+        if let Some(irq) = &self.irq {
+            irq.set(self.interrupt_status != 0);
+        }
+    }
+
+    fn reset_transport(&mut self) {
+        self.queues = vec![Virtqueue::new(); self.device.queue_count()];
+        self.queue_sel = 0;
+        self.driver_features = 0;
+        self.status = 0;
+        self.interrupt_status = 0;
+        self.device.reset();
+        self.update_irq();
+    }
+}
+
+impl<D: VirtioDevice> DeviceBase for VirtioMmio<D> {
+    fn do_read(&mut self, addr: u64, len: usize) -> u64 {
+        if addr >= CONFIG {
+            return self.device.read_config(addr - CONFIG, len);
+        }
+        let queue = self.queues.get(self.queue_sel);
+        match addr {
+            MAGIC_VALUE => MAGIC,
+            VERSION => 2,
+            DEVICE_ID => self.device.device_id() as u64,
+            VENDOR_ID => VENDOR,
+            DEVICE_FEATURES => match self.device_features_sel {
+                0 => self.device_features() & 0xffff_ffff,
+                1 => self.device_features() >> 32,
+                _ => 0,
+            },
+            QUEUE_NUM_MAX => queue.map_or(0, |_| QUEUE_SIZE_MAX as u64),
+            QUEUE_READY => queue.map_or(0, |x| x.ready as u64),
+            INTERRUPT_STATUS => self.interrupt_status as u64,
+            STATUS => self.status as u64,
+            CONFIG_GENERATION => 0,
+            _ => 0,
+        }
+    }
+
+    fn do_write(&mut self, addr: u64, data: u64, len: usize) -> u64 {
+        if addr >= CONFIG {
+            self.device.write_config(addr - CONFIG, data, len);
+            return 0;
+        }
+        let data = data & 0xffff_ffff;
+        let queue = self.queues.get_mut(self.queue_sel);
+        match (addr, queue) {
+            (DEVICE_FEATURES_SEL, _) => self.device_features_sel = data as u32,
+            (DRIVER_FEATURES_SEL, _) => self.driver_features_sel = data as u32,
+            (DRIVER_FEATURES, _) => match self.driver_features_sel {
+                0 => Virtqueue::set_low(&mut self.driver_features, data),
+                1 => Virtqueue::set_high(&mut self.driver_features, data),
+                _ => {}
+            },
+            (QUEUE_SEL, _) => self.queue_sel = data as usize,
+            (QUEUE_NUM, Some(queue)) if data <= QUEUE_SIZE_MAX as u64 => queue.num = data as u16,
+            (QUEUE_READY, Some(queue)) => queue.ready = data & 1 != 0,
+            (QUEUE_DESC_LOW, Some(queue)) => Virtqueue::set_low(&mut queue.desc, data),
+            (QUEUE_DESC_HIGH, Some(queue)) => Virtqueue::set_high(&mut queue.desc, data),
+            (QUEUE_DRIVER_LOW, Some(queue)) => Virtqueue::set_low(&mut queue.avail, data),
+            (QUEUE_DRIVER_HIGH, Some(queue)) => Virtqueue::set_high(&mut queue.avail, data),
+            (QUEUE_DEVICE_LOW, Some(queue)) => Virtqueue::set_low(&mut queue.used, data),
+            (QUEUE_DEVICE_HIGH, Some(queue)) => Virtqueue::set_high(&mut queue.used, data),
+            (QUEUE_NOTIFY, _) => self.process(),
+            (INTERRUPT_ACK, _) => {
+                self.interrupt_status &= !(data as u32);
+                self.update_irq();
+            }
+            (STATUS, _) if data == 0 => self.reset_transport(),
+            (STATUS, _) => self.status = data as u32,
+            _ => {}
+        }
+        0
+    }
+
+    fn do_update(&mut self) {
+        self.process();
+    }
+
+    fn dt_compatible(&self) -> Option<&'static str> {
+        Some("virtio,mmio")
+    }
+
+    fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+    }
+
+    fn reset(&mut self) {
+        self.reset_transport();
+    }
+
+    fn get_name(&self) -> &'static str {
+        self.device.get_name()
+    }
+}
+
+#[cfg(test)]
+mod test_virtio {
+    use super::{DescChain, Virtqueue, VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
+    use crate::device::{device_memory::SharedMemory, device_trait::MEM_BASE};
+
+    #[test]
+    fn virtqueue_test() {
+        let mut mem = SharedMemory::new(MEM_BASE, 0x10000);
+        let (desc, avail, used) = (MEM_BASE, MEM_BASE + 0x1000, MEM_BASE + 0x2000);
+        let mut queue = Virtqueue {
+            num: 4,
+            ready: true,
+            desc,
+            avail,
+            used,
+            last_avail: 0,
+        };
+        assert!(queue.pop(&mut mem).is_none());
+
+        // desc 2 (read 3 bytes) -> desc 0 (write 8 bytes)
+        let write_desc = |idx: u64, addr: u64, len: u32, flags: u16, next: u16| {
+            let mut buf = [0; 16];
+            buf[0..8].copy_from_slice(&addr.to_le_bytes());
+            buf[8..12].copy_from_slice(&len.to_le_bytes());
+            buf[12..14].copy_from_slice(&flags.to_le_bytes());
+            buf[14..16].copy_from_slice(&next.to_le_bytes());
+            mem.write_bytes(desc + 16 * idx, &buf);
+        };
+        write_desc(2, MEM_BASE + 0x3000, 3, VIRTQ_DESC_F_NEXT, 0);
+        write_desc(0, MEM_BASE + 0x4000, 8, VIRTQ_DESC_F_WRITE, 0);
+        mem.write_bytes(MEM_BASE + 0x3000, b"abc");
+        mem.write_bytes(avail + 4, &2_u16.to_le_bytes());
+        mem.write_bytes(avail + 2, &1_u16.to_le_bytes());
+
+        let chain = queue.pop(&mut mem).unwrap();
+        let expected = DescChain {
+            head: 2,
+            readable: vec![(MEM_BASE + 0x3000, 3)],
+            writable: vec![(MEM_BASE + 0x4000, 8)],
+        };
+        assert_eq!(chain, expected);
+        assert!(queue.pop(&mut mem).is_none());
+        assert_eq!(chain.read_all(&mut mem, 3).unwrap(), b"abc");
+        assert!(chain.read_all(&mut mem, 2).is_none());
+        assert_eq!(chain.write_all(&mut mem, b"0123456789"), 8);
+        queue.push_used(&mut mem, chain.head, 8);
+
+        let mut buf = [0; 12];
+        mem.read_bytes(used, &mut buf);
+        assert_eq!(buf, [0, 0, 1, 0, 2, 0, 0, 0, 8, 0, 0, 0]);
+        mem.read_bytes(MEM_BASE + 0x4000, &mut buf[..8]);
+        assert_eq!(&buf[..8], b"01234567");
+
+        // a huge len is not allocated
+        let chain = DescChain {
+            head: 0,
+            readable: vec![(MEM_BASE, u32::MAX), (MEM_BASE, u32::MAX)],
+            writable: vec![],
+        };
+        assert!(chain.read_all(&mut mem, u32::MAX as usize).is_none());
+    }
+}
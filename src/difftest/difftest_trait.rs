@@ -1,4 +1,4 @@
-/**
+/*
  * 1. get gpr value
  * 2. get csr value
  * 3. get memory value
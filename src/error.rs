@@ -0,0 +1,53 @@
+use core::fmt;
+
+use alloc::string::String;
+
+// the errors returned to the embedders of the library,
+// the guest visible errors are traps (see rv64core::traptype)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RvEmuError {
+    // the image can not be read, parsed or placed in memory
+    ImageLoad(String),
+    // an invalid or unsupported configuration
+    BadConfig(String),
+    // the new device overlaps a device already on the bus
+    DeviceConflict {
+        name: &'static str,
+        start: u64,
+        len: u64,
+        other: &'static str,
+    },
+    // the custom csr has the address of a csr already implemented
+    CsrConflict(u64),
+    // a snapshot can not be saved or restored
+    Snapshot(String),
+    // a run manifest can not be read or does not match the run
+    Manifest(String),
+}
+
+pub type RvEmuResult<T> = Result<T, RvEmuError>;
+
+impl fmt::Display for RvEmuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RvEmuError::ImageLoad(reason) => write!(f, "image load failed: {reason}"),
+            RvEmuError::BadConfig(reason) => write!(f, "bad config: {reason}"),
+            RvEmuError::DeviceConflict {
+                name,
+                start,
+                len,
+                other,
+            } => write!(
+                f,
+                "device {name} at {start:#x}..{:#x} overlaps device {other}",
+                start + len
+            ),
+            RvEmuError::CsrConflict(addr) => write!(f, "csr {addr:#x} is already implemented"),
+            RvEmuError::Snapshot(reason) => write!(f, "snapshot failed: {reason}"),
+            RvEmuError::Manifest(reason) => write!(f, "bad manifest: {reason}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RvEmuError {}
@@ -0,0 +1,465 @@
+use alloc::{format, string::String, string::ToString, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    config::Config,
+    device::{
+        device_trait::{PmaAttr, MEM_BASE},
+        irq::HartIrqKind,
+    },
+    error::{RvEmuError, RvEmuResult},
+    rv64core::{bus::Bus, csr_regs_define::StapMode},
+};
+
+pub const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_END: u32 = 9;
+const HEADER_SIZE: usize = 40;
+// one empty entry, the end of the memory reservation block
+const RSVMAP_SIZE: usize = 16;
+
+// the mtime ticks per second, the clint counts one per bus update
+pub const FDT_TIMEBASE: u32 = 10_000_000;
+// the input clock of the uarts, the guest derives its divisors from it
+const UART_CLOCK: u32 = 3_686_400;
+
+// a flattened device tree (dtb, version 17) built node by node.
+// the cells are big endian, every node is closed by `end_node`
+#[derive(Default)]
+pub struct FdtWriter {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+    depth: usize,
+}
+
+impl FdtWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn put_u32(&mut self, x: u32) {
+        self.structure.extend_from_slice(&x.to_be_bytes());
+    }
+
+    fn align(&mut self) {
+        while !self.structure.len().is_multiple_of(4) {
+            self.structure.push(0);
+        }
+    }
+
+    // the names are stored once, the properties point at them
+    fn name_offset(&mut self, name: &str) -> u32 {
+        let mut offset = 0;
+        for x in self.strings.split(|x| *x == 0) {
+            if x == name.as_bytes() && offset < self.strings.len() {
+                return offset as u32;
+            }
+            offset += x.len() + 1;
+        }
+        let offset = self.strings.len();
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        offset as u32
+    }
+
+    // the root is ""
+    pub fn begin_node(&mut self, name: &str) {
+        self.put_u32(FDT_BEGIN_NODE);
+        self.structure.extend_from_slice(name.as_bytes());
+        self.structure.push(0);
+        self.align();
+        self.depth += 1;
+    }
+
+    pub fn end_node(&mut self) {
+        assert!(self.depth > 0, "fdt: end_node without a node");
+        self.put_u32(FDT_END_NODE);
+        self.depth -= 1;
+    }
+
+    pub fn prop(&mut self, name: &str, value: &[u8]) {
+        let nameoff = self.name_offset(name);
+        self.put_u32(FDT_PROP);
+        self.put_u32(value.len() as u32);
+        self.put_u32(nameoff);
+        self.structure.extend_from_slice(value);
+        self.align();
+    }
+
+    pub fn prop_null(&mut self, name: &str) {
+        self.prop(name, &[]);
+    }
+
+    pub fn prop_u32(&mut self, name: &str, x: u32) {
+        self.prop_cells(name, &[x]);
+    }
+
+    pub fn prop_cells(&mut self, name: &str, cells: &[u32]) {
+        let value: Vec<u8> = cells.iter().flat_map(|x| x.to_be_bytes()).collect();
+        self.prop(name, &value);
+    }
+
+    // two cells each, for the #address-cells and #size-cells of 2
+    pub fn prop_u64s(&mut self, name: &str, xs: &[u64]) {
+        let value: Vec<u8> = xs.iter().flat_map(|x| x.to_be_bytes()).collect();
+        self.prop(name, &value);
+    }
+
+    pub fn prop_str(&mut self, name: &str, x: &str) {
+        self.prop_strs(name, &[x]);
+    }
+
+    pub fn prop_strs(&mut self, name: &str, xs: &[&str]) {
+        let mut value = Vec::new();
+        for x in xs {
+            value.extend_from_slice(x.as_bytes());
+            value.push(0);
+        }
+        self.prop(name, &value);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        assert_eq!(self.depth, 0, "fdt: a node is not closed");
+        self.put_u32(FDT_END);
+        let off_struct = HEADER_SIZE + RSVMAP_SIZE;
+        let off_strings = off_struct + self.structure.len();
+        let total = off_strings + self.strings.len();
+        let header = [
+            FDT_MAGIC,
+            total as u32,
+            off_struct as u32,
+            off_strings as u32,
+            HEADER_SIZE as u32,
+            17,
+            16,
+            0,
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ];
+        let mut dtb: Vec<u8> = header.iter().flat_map(|x| x.to_be_bytes()).collect();
+        dtb.resize(off_struct, 0);
+        dtb.append(&mut self.structure);
+        dtb.append(&mut self.strings);
+        dtb
+    }
+}
+
+// the isa letters in the canonical order, as the kernel expects them
+fn fdt_isa(config: &Config) -> String {
+    let mut isa = format!("rv{}", config.xlen());
+    b"iemafdch"
+        .iter()
+        .filter(|x| config.is_enable_isa(**x))
+        .for_each(|x| isa.push(*x as char));
+    let base = config.isa_string();
+    for ext in base.split('_').skip(1) {
+        isa.push('_');
+        isa.push_str(ext);
+    }
+    isa
+}
+
+fn mmu_type(config: &Config) -> Option<&'static str> {
+    match config.get_mmu_type() {
+        StapMode::Bare => None,
+        StapMode::Sv32 => Some("riscv,sv32"),
+        StapMode::Sv39 => Some("riscv,sv39"),
+        StapMode::Sv48 => Some("riscv,sv48"),
+        StapMode::Sv57 => Some("riscv,sv57"),
+        StapMode::Sv64 => Some("riscv,sv64"),
+    }
+}
+
+// the device tree of the machine on `bus` with `harts` harts 0..harts:
+// the cpus, the memory (the MEMORY devices from MEM_BASE), the clint, the
+// plic and the devices which have a `DeviceBase::dt_compatible`.
+// the first uart is the console, /chosen has the `initrd` range.
+// the AIA is not described
+pub fn machine_fdt(
+    bus: &Bus,
+    config: &Config,
+    harts: usize,
+    bootargs: &str,
+    initrd: Option<Range<u64>>,
+) -> RvEmuResult<Vec<u8>> {
+    if bus.aia.is_some() {
+        return Err(RvEmuError::BadConfig(
+            "the generated fdt has no AIA, pass a dtb".to_string(),
+        ));
+    }
+    // the phandles: the intc of hart i is i + 1, then the plic and the clock
+    let intc = |hart: usize| hart as u32 + 1;
+    let plic_phandle = harts as u32 + 1;
+    let clock_phandle = harts as u32 + 2;
+
+    let mut fdt = FdtWriter::new();
+    fdt.begin_node("");
+    fdt.prop_u32("#address-cells", 2);
+    fdt.prop_u32("#size-cells", 2);
+    fdt.prop_str("compatible", "riscv-virtio");
+    fdt.prop_str("model", "rv64-emu-rs");
+
+    let devices: Vec<_> = bus
+        .devices
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, x)| x.instance.dt_compatible().map(|c| (idx, x, c)))
+        .collect();
+    let uart = devices
+        .iter()
+        .find(|(_, _, c)| matches!(*c, "ns16550a" | "sifive,uart0"));
+
+    fdt.begin_node("chosen");
+    if !bootargs.is_empty() {
+        fdt.prop_str("bootargs", bootargs);
+    }
+    if let Some(initrd) = initrd {
+        fdt.prop_u64s("linux,initrd-start", &[initrd.start]);
+        fdt.prop_u64s("linux,initrd-end", &[initrd.end]);
+    }
+    if let Some((_, x, _)) = uart {
+        fdt.prop_str("stdout-path", &format!("/soc/serial@{:x}", x.start));
+    }
+    fdt.end_node();
+
+    fdt.begin_node("cpus");
+    fdt.prop_u32("#address-cells", 1);
+    fdt.prop_u32("#size-cells", 0);
+    fdt.prop_u32("timebase-frequency", FDT_TIMEBASE);
+    let isa = fdt_isa(config);
+    for hart in 0..harts {
+        fdt.begin_node(&format!("cpu@{hart:x}"));
+        fdt.prop_str("device_type", "cpu");
+        fdt.prop_u32("reg", hart as u32);
+        fdt.prop_str("status", "okay");
+        fdt.prop_str("compatible", "riscv");
+        fdt.prop_str("riscv,isa", &isa);
+        if let Some(mmu) = mmu_type(config) {
+            fdt.prop_str("mmu-type", mmu);
+        }
+        fdt.begin_node("interrupt-controller");
+        fdt.prop_u32("#interrupt-cells", 1);
+        fdt.prop_null("interrupt-controller");
+        fdt.prop_str("compatible", "riscv,cpu-intc");
+        fdt.prop_u32("phandle", intc(hart));
+        fdt.end_node();
+        fdt.end_node();
+    }
+    fdt.end_node();
+
+    for x in bus.devices.iter() {
+        if x.start >= MEM_BASE && x.instance.pma() == PmaAttr::MEMORY {
+            fdt.begin_node(&format!("memory@{:x}", x.start));
+            fdt.prop_str("device_type", "memory");
+            fdt.prop_u64s("reg", &[x.start, x.len]);
+            fdt.end_node();
+        }
+    }
+
+    if devices.iter().any(|(_, _, c)| *c == "sifive,uart0") {
+        fdt.begin_node("clock");
+        fdt.prop_u32("#clock-cells", 0);
+        fdt.prop_str("compatible", "fixed-clock");
+        fdt.prop_u32("clock-frequency", UART_CLOCK);
+        fdt.prop_u32("phandle", clock_phandle);
+        fdt.end_node();
+    }
+
+    fdt.begin_node("soc");
+    fdt.prop_u32("#address-cells", 2);
+    fdt.prop_u32("#size-cells", 2);
+    fdt.prop_str("compatible", "simple-bus");
+    fdt.prop_null("ranges");
+
+    // the soft and the timer irq of every hart
+    let clint_irqs: Vec<u32> = (0..harts)
+        .flat_map(|hart| [intc(hart), 3, intc(hart), 7])
+        .collect();
+    fdt.begin_node(&format!("clint@{:x}", bus.clint.start));
+    fdt.prop_strs("compatible", &["sifive,clint0", "riscv,clint0"]);
+    fdt.prop_u64s("reg", &[bus.clint.start, bus.clint.len]);
+    fdt.prop_cells("interrupts-extended", &clint_irqs);
+    fdt.end_node();
+
+    // the contexts are added hart by hart, m-mode first
+    let mut hart = 0;
+    let mut plic_irqs = Vec::new();
+    for (idx, kind) in bus.plic.instance.context_kinds().iter().enumerate() {
+        if *kind == HartIrqKind::MExternal && idx > 0 {
+            hart += 1;
+        }
+        let irq = if *kind == HartIrqKind::MExternal {
+            11
+        } else {
+            9
+        };
+        plic_irqs.extend([intc(hart), irq]);
+    }
+    fdt.begin_node(&format!("interrupt-controller@{:x}", bus.plic.start));
+    fdt.prop_u32("#interrupt-cells", 1);
+    fdt.prop_u32("#address-cells", 0);
+    fdt.prop_strs("compatible", &["sifive,plic-1.0.0", "riscv,plic0"]);
+    fdt.prop_u64s("reg", &[bus.plic.start, bus.plic.len]);
+    fdt.prop_cells("interrupts-extended", &plic_irqs);
+    fdt.prop_null("interrupt-controller");
+    fdt.prop_u32("riscv,ndev", 63);
+    fdt.prop_u32("phandle", plic_phandle);
+    fdt.end_node();
+
+    let (fb_width, fb_height, fb_bpp) = config.fb_size();
+    for (idx, x, compatible) in devices.iter() {
+        let node = match *compatible {
+            "ns16550a" | "sifive,uart0" => "serial",
+            "virtio,mmio" => "virtio_mmio",
+            "simple-framebuffer" => "framebuffer",
+            _ => "device",
+        };
+        fdt.begin_node(&format!("{node}@{:x}", x.start));
+        fdt.prop_str("compatible", compatible);
+        fdt.prop_u64s("reg", &[x.start, x.len]);
+        if let Some(irq) = bus.device_irq(*idx) {
+            fdt.prop_u32("interrupt-parent", plic_phandle);
+            fdt.prop_u32("interrupts", irq);
+        }
+        match *compatible {
+            "ns16550a" => fdt.prop_u32("clock-frequency", UART_CLOCK),
+            "sifive,uart0" => fdt.prop_u32("clocks", clock_phandle),
+            "simple-framebuffer" => {
+                fdt.prop_u32("width", fb_width);
+                fdt.prop_u32("height", fb_height);
+                fdt.prop_u32("stride", fb_width * fb_bpp / 8);
+                let format = if fb_bpp == 16 { "r5g6b5" } else { "a8r8g8b8" };
+                fdt.prop_str("format", format);
+            }
+            _ => {}
+        }
+        fdt.end_node();
+    }
+    fdt.end_node();
+
+    fdt.end_node();
+    Ok(fdt.finish())
+}
+
+#[cfg(test)]
+mod test_fdt {
+    use alloc::{boxed::Box, string::String, vec::Vec};
+
+    use super::{machine_fdt, FdtWriter};
+    use crate::{
+        config::Config,
+        device::{device_memory::DeviceMemory, device_trait::MEM_BASE, uart::UartConfig},
+        rv64core::{
+            bus::{Bus, DeviceType},
+            csr_regs_define::XipIn,
+        },
+        tools::fifo_unbounded_new,
+    };
+
+    fn be32(dtb: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(dtb[offset..offset + 4].try_into().unwrap())
+    }
+
+    // the properties as (path/name, value)
+    fn props(dtb: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let (off_struct, off_strings) = (be32(dtb, 8) as usize, be32(dtb, 12) as usize);
+        let cstr = |offset: usize| {
+            let end = dtb[offset..].iter().position(|x| *x == 0).unwrap();
+            String::from_utf8(dtb[offset..offset + end].to_vec()).unwrap()
+        };
+        let mut path: Vec<String> = Vec::new();
+        let mut ret = Vec::new();
+        let mut pos = off_struct;
+        loop {
+            let token = be32(dtb, pos);
+            pos += 4;
+            match token {
+                1 => {
+                    let name = cstr(pos);
+                    pos += (name.len() + 4) & !3;
+                    path.push(name);
+                }
+                2 => {
+                    path.pop();
+                }
+                3 => {
+                    let len = be32(dtb, pos) as usize;
+                    let name = cstr(off_strings + be32(dtb, pos + 4) as usize);
+                    let value = dtb[pos + 8..pos + 8 + len].to_vec();
+                    ret.push((alloc::format!("{}/{name}", path.join("/")), value));
+                    pos += (8 + len + 3) & !3;
+                }
+                _ => return ret,
+            }
+        }
+    }
+
+    #[test]
+    fn fdt_test() {
+        let mut fdt = FdtWriter::new();
+        fdt.begin_node("");
+        fdt.prop_u32("a", 1);
+        fdt.prop_u32("a", 2);
+        fdt.end_node();
+        let dtb = fdt.finish();
+        // the name is stored once
+        assert_eq!(be32(&dtb, 32), 2);
+        assert_eq!(be32(&dtb, 4) as usize, dtb.len());
+
+        let mut bus = Bus::new();
+        bus.add_device(DeviceType {
+            start: MEM_BASE,
+            len: 0x10000,
+            instance: Box::new(DeviceMemory::new(0x10000)),
+            name: "RAM",
+        })
+        .unwrap();
+        UartConfig::ns16550a()
+            .add_to(&mut bus, "uart", fifo_unbounded_new(), fifo_unbounded_new())
+            .unwrap();
+        let xip = alloc::rc::Rc::new(core::cell::Cell::new(XipIn::new()));
+        bus.plic.instance.add_context(xip.clone(), true);
+        bus.plic.instance.add_context(xip, false);
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_mmu_type("sv39");
+        let dtb = machine_fdt(
+            &bus,
+            &config,
+            1,
+            "console=ttyS0",
+            Some(0x8000_8000..0x8000_9000),
+        )
+        .unwrap();
+        assert_eq!(be32(&dtb, 0), 0xd00d_feed);
+
+        let props = props(&dtb);
+        let get = |name: &str| {
+            props
+                .iter()
+                .find(|(x, _)| x == name)
+                .map(|(_, v)| v.clone())
+                .unwrap_or_else(|| panic!("no {name}"))
+        };
+        assert_eq!(get("/chosen/bootargs"), b"console=ttyS0\0");
+        assert_eq!(
+            get("/chosen/linux,initrd-end"),
+            [0, 0, 0, 0, 0x80, 0, 0x90, 0]
+        );
+        assert_eq!(get("/chosen/stdout-path"), b"/soc/serial@10000000\0");
+        assert_eq!(get("/cpus/cpu@0/riscv,isa"), b"rv64imac\0");
+        assert_eq!(get("/cpus/cpu@0/mmu-type"), b"riscv,sv39\0");
+        assert_eq!(
+            get("/memory@80000000/reg"),
+            [0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0]
+        );
+        // the m-mode and s-mode external irqs of hart 0
+        assert_eq!(
+            get("/soc/interrupt-controller@c000000/interrupts-extended"),
+            [0, 0, 0, 1, 0, 0, 0, 11, 0, 0, 0, 1, 0, 0, 0, 9]
+        );
+        assert_eq!(get("/soc/serial@10000000/interrupts"), [0, 0, 0, 11]);
+    }
+}
@@ -0,0 +1,144 @@
+// Input events shared by the frontends (SDL, winit, VNC, web...) and the
+// input devices. A frontend converts its native events to these types,
+// the devices never see the frontend types.
+
+// a physical key, numbered by the USB HID usage id (keyboard page 0x07).
+// SDL scancodes use the same numbering
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyCode(pub u16);
+
+impl KeyCode {
+    pub const A: KeyCode = KeyCode(0x04);
+    pub const Z: KeyCode = KeyCode(0x1d);
+    pub const NUM_1: KeyCode = KeyCode(0x1e);
+    pub const NUM_0: KeyCode = KeyCode(0x27);
+    pub const RETURN: KeyCode = KeyCode(0x28);
+    pub const ESCAPE: KeyCode = KeyCode(0x29);
+    pub const BACKSPACE: KeyCode = KeyCode(0x2a);
+    pub const TAB: KeyCode = KeyCode(0x2b);
+    pub const SPACE: KeyCode = KeyCode(0x2c);
+    pub const F1: KeyCode = KeyCode(0x3a);
+    pub const F12: KeyCode = KeyCode(0x45);
+    pub const RIGHT: KeyCode = KeyCode(0x4f);
+    pub const LEFT: KeyCode = KeyCode(0x50);
+    pub const DOWN: KeyCode = KeyCode(0x51);
+    pub const UP: KeyCode = KeyCode(0x52);
+    pub const LCTRL: KeyCode = KeyCode(0xe0);
+    pub const LSHIFT: KeyCode = KeyCode(0xe1);
+    pub const LALT: KeyCode = KeyCode(0xe2);
+    pub const RCTRL: KeyCode = KeyCode(0xe4);
+    pub const RSHIFT: KeyCode = KeyCode(0xe5);
+    pub const RALT: KeyCode = KeyCode(0xe6);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    X1,
+    X2,
+}
+
+// the pressed buttons, bit n is set when button n is down
+// (Left, Middle, Right, X1, X2, the order of the SDL button masks)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MouseButtons(pub u32);
+
+impl MouseButtons {
+    pub fn set(&mut self, button: MouseButton, pressed: bool) {
+        let mask = 1 << button as u32;
+        if pressed {
+            self.0 |= mask;
+        } else {
+            self.0 &= !mask;
+        }
+    }
+
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        self.0 & (1 << button as u32) != 0
+    }
+}
+
+// the absolute pointer position in frame coordinates, and the buttons
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MouseState {
+    pub buttons: MouseButtons,
+    pub x: u32,
+    pub y: u32,
+}
+
+// a relative move for the devices without an absolute position (PS/2),
+// dy grows downwards like the window, wheel grows away from the user
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MouseMotion {
+    pub buttons: MouseButtons,
+    pub dx: i32,
+    pub dy: i32,
+    pub wheel: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    Key(KeyEvent),
+    Mouse(MouseState),
+    Motion(MouseMotion),
+}
+
+#[cfg(feature = "device_sdl2")]
+mod sdl {
+    use super::{KeyCode, MouseButton, MouseButtons, MouseState};
+
+    impl From<sdl2::keyboard::Scancode> for KeyCode {
+        fn from(scancode: sdl2::keyboard::Scancode) -> Self {
+            KeyCode(scancode as u16)
+        }
+    }
+
+    impl MouseState {
+        // x and y are already converted to frame coordinates
+        pub fn from_sdl(state: &sdl2::mouse::MouseState, x: u32, y: u32) -> Self {
+            MouseState {
+                buttons: MouseButtons(state.to_sdl_state()),
+                x,
+                y,
+            }
+        }
+    }
+
+    impl MouseButton {
+        pub fn from_sdl(button: sdl2::mouse::MouseButton) -> Option<Self> {
+            match button {
+                sdl2::mouse::MouseButton::Left => Some(MouseButton::Left),
+                sdl2::mouse::MouseButton::Middle => Some(MouseButton::Middle),
+                sdl2::mouse::MouseButton::Right => Some(MouseButton::Right),
+                sdl2::mouse::MouseButton::X1 => Some(MouseButton::X1),
+                sdl2::mouse::MouseButton::X2 => Some(MouseButton::X2),
+                sdl2::mouse::MouseButton::Unknown => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_input {
+    use super::{MouseButton, MouseButtons};
+
+    #[test]
+    fn mouse_buttons_test() {
+        let mut buttons = MouseButtons::default();
+        buttons.set(MouseButton::Left, true);
+        buttons.set(MouseButton::Right, true);
+        // the same bits as SDL_BUTTON_LMASK | SDL_BUTTON_RMASK
+        assert_eq!(buttons.0, 0b101);
+        buttons.set(MouseButton::Left, false);
+        assert!(!buttons.is_pressed(MouseButton::Left));
+        assert!(buttons.is_pressed(MouseButton::Right));
+    }
+}
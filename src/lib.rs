@@ -3,11 +3,19 @@
 extern crate alloc;
 
 
+pub mod boot_image;
+pub mod capabilities;
 pub mod dbg;
 pub mod device;
 pub mod difftest;
+pub mod error;
+pub mod fdt;
+pub mod input;
+pub mod manifest;
+pub mod net;
 pub mod rv64core;
 pub mod rvsim;
+pub mod snapshot;
 pub mod tools;
 pub mod config;
 
@@ -0,0 +1,404 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{
+    config::Config,
+    error::{RvEmuError, RvEmuResult},
+};
+
+// a run manifest records everything needed to replay a run:
+// the emulator version, the command line, the config, the seed
+// and the hashes of the loaded images
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunManifest {
+    pub version: String,
+    pub command_line: Vec<String>,
+    pub seed: u64,
+    pub config: Vec<(String, String)>,
+    pub images: Vec<ImageHash>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageHash {
+    pub path: String,
+    pub size: usize,
+    pub fnv1a: u64,
+}
+
+impl ImageHash {
+    pub fn new(path: &str, data: &[u8]) -> Self {
+        Self {
+            path: path.to_string(),
+            size: data.len(),
+            fnv1a: fnv1a(data),
+        }
+    }
+}
+
+// 64-bit FNV-1a, good enough to spot a changed image
+pub fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, x| {
+        (hash ^ *x as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+impl RunManifest {
+    pub fn new(config: &Config, command_line: Vec<String>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            command_line,
+            seed: config.seed(),
+            config: config
+                .to_pairs()
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn add_image(&mut self, path: &str, data: &[u8]) {
+        self.images.push(ImageHash::new(path, data));
+    }
+
+    // rebuild the recorded config, check it with `Config::validate`
+    pub fn config(&self) -> Config {
+        let pairs = self.config.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+        let mut config = Config::from_pairs(pairs);
+        config.set_seed(self.seed);
+        config
+    }
+
+    // the image must be the one recorded in the manifest
+    pub fn check_image(&self, path: &str, data: &[u8]) -> RvEmuResult<()> {
+        let Some(image) = self.images.iter().find(|x| x.path == path) else {
+            return Err(RvEmuError::Manifest(format!(
+                "image {path} is not recorded"
+            )));
+        };
+        if *image != ImageHash::new(path, data) {
+            return Err(RvEmuError::Manifest(format!(
+                "image {path} changed, expected {} bytes with fnv1a {:#018x}",
+                image.size, image.fnv1a
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        let list = |items: Vec<String>, indent: &str| {
+            if items.is_empty() {
+                return String::new();
+            }
+            format!(
+                "\n{indent}  {}\n{indent}",
+                items.join(&format!(",\n{indent}  "))
+            )
+        };
+        let command_line = self.command_line.iter().map(|x| json_str(x)).collect();
+        let config = self
+            .config
+            .iter()
+            .map(|(k, v)| format!("{}: {}", json_str(k), json_str(v)))
+            .collect();
+        let images = self
+            .images
+            .iter()
+            .map(|x| {
+                format!(
+                    "{{\"path\": {}, \"size\": {}, \"fnv1a\": \"{:#018x}\"}}",
+                    json_str(&x.path),
+                    x.size,
+                    x.fnv1a
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"version\": {},\n  \"command_line\": [{}],\n  \"seed\": \"{:#x}\",\n  \"config\": {{{}}},\n  \"images\": [{}]\n}}\n",
+            json_str(&self.version),
+            list(command_line, "  "),
+            self.seed,
+            list(config, "  "),
+            list(images, "  "),
+        )
+    }
+
+    pub fn from_json(text: &str) -> RvEmuResult<Self> {
+        let bad = |reason: &str| RvEmuError::Manifest(reason.to_string());
+        let mut parser = JsonParser {
+            text: text.as_bytes(),
+            pos: 0,
+        };
+        let root = parser.value().map_err(|x| bad(&x))?;
+        parser.skip_ws();
+        if parser.pos != text.len() {
+            return Err(bad("trailing characters"));
+        }
+
+        let field = |obj: &Json, key: &str| {
+            obj.get(key)
+                .cloned()
+                .ok_or_else(|| bad(&format!("missing field {key}")))
+        };
+        let string = |x: Json, key: &str| match x {
+            Json::Str(s) => Ok(s),
+            _ => Err(bad(&format!("{key} is not a string"))),
+        };
+        let hex = |x: Json, key: &str| {
+            let s = string(x, key)?;
+            let digits = s.strip_prefix("0x").unwrap_or(&s);
+            u64::from_str_radix(digits, 16).map_err(|_| bad(&format!("{key} is not a hex number")))
+        };
+
+        let Json::Arr(command_line) = field(&root, "command_line")? else {
+            return Err(bad("command_line is not an array"));
+        };
+        let Json::Obj(config) = field(&root, "config")? else {
+            return Err(bad("config is not an object"));
+        };
+        let Json::Arr(images) = field(&root, "images")? else {
+            return Err(bad("images is not an array"));
+        };
+        Ok(Self {
+            version: string(field(&root, "version")?, "version")?,
+            command_line: command_line
+                .into_iter()
+                .map(|x| string(x, "command_line"))
+                .collect::<RvEmuResult<_>>()?,
+            seed: hex(field(&root, "seed")?, "seed")?,
+            config: config
+                .into_iter()
+                .map(|(k, v)| Ok((k, string(v, "config")?)))
+                .collect::<RvEmuResult<_>>()?,
+            images: images
+                .iter()
+                .map(|x| {
+                    let Json::Num(size) = field(x, "size")? else {
+                        return Err(bad("size is not a number"));
+                    };
+                    Ok(ImageHash {
+                        path: string(field(x, "path")?, "path")?,
+                        size: size as usize,
+                        fnv1a: hex(field(x, "fnv1a")?, "fnv1a")?,
+                    })
+                })
+                .collect::<RvEmuResult<_>>()?,
+        })
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save(&self, file_name: &str) -> RvEmuResult<()> {
+        std::fs::write(file_name, self.to_json())
+            .map_err(|e| RvEmuError::Manifest(format!("{file_name}:{e}")))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load(file_name: &str) -> RvEmuResult<Self> {
+        let text = std::fs::read_to_string(file_name)
+            .map_err(|e| RvEmuError::Manifest(format!("{file_name}:{e}")))?;
+        Self::from_json(&text)
+    }
+}
+
+pub(crate) fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// the subset of json written by the manifest: strings, unsigned numbers,
+// arrays and objects
+#[derive(Debug, Clone)]
+enum Json {
+    Str(String),
+    Num(u64),
+    Arr(Vec<Json>),
+    Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Obj(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn skip_ws(&mut self) {
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|x| x.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        self.skip_ws();
+        if self.text.get(self.pos) != Some(&c) {
+            return Err(format!("expected '{}' at {}", c as char, self.pos));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // parse a comma separated list up to `end`
+    fn list(
+        &mut self,
+        end: u8,
+        mut item: impl FnMut(&mut Self) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.skip_ws();
+        if self.text.get(self.pos) == Some(&end) {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            self.skip_ws();
+            match self.text.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(x) if *x == end => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(format!("expected ',' or '{}' at {}", end as char, self.pos)),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_ws();
+        let rest = &self.text[self.pos..];
+        match rest.first() {
+            Some(b'"') => self.string().map(Json::Str),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.list(b']', |p| {
+                    items.push(p.value()?);
+                    Ok(())
+                })?;
+                Ok(Json::Arr(items))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.list(b'}', |p| {
+                    p.skip_ws();
+                    let key = p.string()?;
+                    p.expect(b':')?;
+                    fields.push((key, p.value()?));
+                    Ok(())
+                })?;
+                Ok(Json::Obj(fields))
+            }
+            Some(x) if x.is_ascii_digit() => {
+                let len = rest.iter().take_while(|x| x.is_ascii_digit()).count();
+                self.pos += len;
+                let digits = core::str::from_utf8(&rest[..len]).unwrap();
+                digits
+                    .parse()
+                    .map(Json::Num)
+                    .map_err(|_| format!("number {digits} is too large"))
+            }
+            _ => Err(format!("unexpected character at {}", self.pos)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(c) = self.text.get(self.pos).copied() else {
+                return Err("unterminated string".to_string());
+            };
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let Some(e) = self.text.get(self.pos).copied() else {
+                        return Err("unterminated string".to_string());
+                    };
+                    self.pos += 1;
+                    let c = match e {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let hex = self.text.get(self.pos..self.pos + 4);
+                            let code = hex
+                                .and_then(|x| core::str::from_utf8(x).ok())
+                                .and_then(|x| u32::from_str_radix(x, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| format!("bad escape at {}", self.pos))?;
+                            self.pos += 4;
+                            code
+                        }
+                        x => x as char,
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                x => bytes.push(x),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| "string is not utf-8".to_string())
+    }
+}
+
+#[cfg(test)]
+mod test_manifest {
+    use alloc::{string::ToString, vec};
+
+    use crate::{config::Config, manifest::RunManifest};
+
+    #[test]
+    fn manifest_round_trip_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        config.set_seed(0xdead_beef);
+        let command_line = vec!["linux_system".to_string(), "--img=\"a b\".bin".to_string()];
+        let mut manifest = RunManifest::new(&config, command_line);
+        manifest.add_image("\"a b\".bin", b"hello");
+
+        let json = manifest.to_json();
+        let replay = RunManifest::from_json(&json).unwrap();
+        assert_eq!(replay, manifest);
+        assert_eq!(replay.config().seed(), 0xdead_beef);
+        assert_eq!(replay.config().isa_string(), "rv64imac");
+        assert!(replay.config().validate().is_ok());
+
+        assert!(replay.check_image("\"a b\".bin", b"hello").is_ok());
+        assert!(replay.check_image("\"a b\".bin", b"hellO").is_err());
+        assert!(replay.check_image("other.bin", b"hello").is_err());
+        assert!(RunManifest::from_json("{\"version\": \"0.1\"}").is_err());
+        assert!(RunManifest::from_json(&json[1..]).is_err());
+    }
+}
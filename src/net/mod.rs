@@ -0,0 +1,87 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+pub mod pcap;
+#[cfg(feature = "std")]
+pub mod tap;
+#[cfg(feature = "std")]
+pub mod user;
+
+// the host side of a network card, it sees ethernet frames without the
+// headers of the card (virtio-net or any other MAC)
+pub trait NetBackend {
+    // a frame sent by the guest
+    fn send(&mut self, frame: &[u8]);
+    // the next frame for the guest, polled on every bus update
+    fn recv(&mut self) -> Option<Vec<u8>>;
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetBackendKind {
+    // NAT through host sockets, see `user::UserNet`
+    User,
+    // a host TAP interface by name, see `tap::TapNet`
+    Tap(String),
+}
+
+// user[,pcap=FILE] or tap:IFNAME[,pcap=FILE], the capture has the frames of
+// both directions
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetSpec {
+    pub kind: NetBackendKind,
+    pub pcap: Option<String>,
+}
+
+#[cfg(feature = "std")]
+impl NetSpec {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parts = s.split(',');
+        let head = parts.next().unwrap_or("");
+        let kind = match head.split_once(':') {
+            None if head == "user" => NetBackendKind::User,
+            Some(("tap", ifname)) if !ifname.is_empty() => NetBackendKind::Tap(ifname.to_string()),
+            _ => {
+                return Err(format!(
+                    "unknown net backend:{s}, user or tap:IFNAME, with ,pcap=FILE"
+                ))
+            }
+        };
+        let mut pcap = None;
+        for opt in parts {
+            match opt.split_once('=') {
+                Some(("pcap", file)) => pcap = Some(file.to_string()),
+                _ => return Err(format!("unknown net option:{opt}, pcap=FILE")),
+            }
+        }
+        Ok(NetSpec { kind, pcap })
+    }
+
+    pub fn open(&self) -> std::io::Result<Box<dyn NetBackend>> {
+        let backend: Box<dyn NetBackend> = match &self.kind {
+            NetBackendKind::User => Box::new(user::UserNet::new()),
+            NetBackendKind::Tap(ifname) => Box::new(tap::TapNet::open(ifname)?),
+        };
+        match &self.pcap {
+            Some(file) => Ok(Box::new(pcap::PcapCapture::create(backend, file)?)),
+            None => Ok(backend),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test_net {
+    use super::{NetBackendKind, NetSpec};
+
+    #[test]
+    fn net_spec_test() {
+        let spec = NetSpec::parse("tap:tap0,pcap=/tmp/net.pcap").unwrap();
+        assert_eq!(spec.kind, NetBackendKind::Tap("tap0".to_string()));
+        assert_eq!(spec.pcap.as_deref(), Some("/tmp/net.pcap"));
+        assert_eq!(NetSpec::parse("user").unwrap().kind, NetBackendKind::User);
+        assert!(NetSpec::parse("tap:").is_err());
+        assert!(NetSpec::parse("user,foo=1").is_err());
+        assert!(NetSpec::parse("slirp").is_err());
+    }
+}
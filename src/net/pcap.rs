@@ -0,0 +1,148 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+
+use super::NetBackend;
+
+// the classic libpcap format (wireshark, tcpdump -r), microsecond stamps
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const LINKTYPE_ETHERNET: u32 = 1;
+const SNAPLEN: u32 = 65535;
+
+pub struct PcapWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    // writes the file header
+    pub fn new(mut out: W) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+        header.extend_from_slice(&2_u16.to_le_bytes());
+        header.extend_from_slice(&4_u16.to_le_bytes());
+        // thiszone, sigfigs
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        out.write_all(&header)?;
+        Ok(PcapWriter { out })
+    }
+
+    // a record is written at once, the file is complete if the emulator dies
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let caplen = frame.len().min(SNAPLEN as usize);
+        let mut record = Vec::with_capacity(16 + caplen);
+        record.extend_from_slice(&(now.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&now.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(caplen as u32).to_le_bytes());
+        record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        record.extend_from_slice(&frame[..caplen]);
+        self.out.write_all(&record)?;
+        self.out.flush()
+    }
+}
+
+// a backend which records the frames of both directions
+pub struct PcapCapture<W: Write = File> {
+    backend: Box<dyn NetBackend>,
+    pcap: Option<PcapWriter<W>>,
+}
+
+impl PcapCapture {
+    pub fn create(backend: Box<dyn NetBackend>, path: &str) -> io::Result<Self> {
+        Self::new(backend, File::create(path)?)
+    }
+}
+
+impl<W: Write> PcapCapture<W> {
+    pub fn new(backend: Box<dyn NetBackend>, out: W) -> io::Result<Self> {
+        Ok(PcapCapture {
+            backend,
+            pcap: Some(PcapWriter::new(out)?),
+        })
+    }
+
+    // the capture stops at the first error, the network goes on
+    fn capture(&mut self, frame: &[u8]) {
+        if let Some(pcap) = self.pcap.as_mut() {
+            if let Err(e) = pcap.write_frame(frame) {
+                warn!("pcap capture stopped: {e}");
+                self.pcap = None;
+            }
+        }
+    }
+}
+
+impl<W: Write> NetBackend for PcapCapture<W> {
+    fn send(&mut self, frame: &[u8]) {
+        self.capture(frame);
+        self.backend.send(frame);
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        let frame = self.backend.recv()?;
+        self.capture(&frame);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod test_pcap {
+    use std::{cell::RefCell, io::Write, rc::Rc};
+
+    use super::PcapCapture;
+    use crate::net::NetBackend;
+
+    // a shared sink, the test reads what the capture wrote
+    #[derive(Clone, Default)]
+    struct Sink(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // sends every frame back
+    struct Echo(Option<Vec<u8>>);
+
+    impl NetBackend for Echo {
+        fn send(&mut self, frame: &[u8]) {
+            self.0 = Some(frame.to_vec());
+        }
+        fn recv(&mut self) -> Option<Vec<u8>> {
+            self.0.take()
+        }
+    }
+
+    #[test]
+    fn pcap_test() {
+        let sink = Sink::default();
+        let mut net = PcapCapture::new(Box::new(Echo(None)), sink.clone()).unwrap();
+        assert_eq!(sink.0.borrow().len(), 24);
+        assert_eq!(sink.0.borrow()[..4], [0xd4, 0xc3, 0xb2, 0xa1]);
+        // the linktype
+        assert_eq!(sink.0.borrow()[20..24], [1, 0, 0, 0]);
+
+        net.send(&[0xaa; 60]);
+        assert_eq!(net.recv(), Some(vec![0xaa; 60]));
+        assert_eq!(net.recv(), None);
+        let data = sink.0.borrow();
+        assert_eq!(data.len(), 24 + 2 * (16 + 60));
+        // incl_len and orig_len of the second record
+        let second = 24 + 16 + 60;
+        assert_eq!(data[second + 8..second + 16], [60, 0, 0, 0, 60, 0, 0, 0]);
+        assert_eq!(data[second + 16..], [0xaa; 60]);
+    }
+}
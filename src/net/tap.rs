@@ -0,0 +1,100 @@
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use log::warn;
+
+use super::NetBackend;
+
+// the largest frame of the interface, with a vlan tag
+const MAX_FRAME: usize = 1522;
+
+// a host TAP interface, the frames go to the host kernel as they are.
+// the interface is created for the user beforehand, e.g.
+// `ip tuntap add tap0 mode tap user $USER && ip link set tap0 up`
+pub struct TapNet {
+    file: File,
+    buf: Vec<u8>,
+}
+
+impl TapNet {
+    pub fn open(ifname: &str) -> io::Result<Self> {
+        Ok(TapNet {
+            file: open_tap(ifname)?,
+            buf: vec![0; MAX_FRAME],
+        })
+    }
+}
+
+impl NetBackend for TapNet {
+    // a full queue of the interface drops the frame, as on a wire
+    fn send(&mut self, frame: &[u8]) {
+        match self.file.write(frame) {
+            Err(e) if e.kind() != ErrorKind::WouldBlock => warn!("tap send: {e}"),
+            _ => {}
+        }
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        match self.file.read(&mut self.buf) {
+            Ok(n @ 1..) => Some(self.buf[..n].to_vec()),
+            Err(e) if e.kind() != ErrorKind::WouldBlock => {
+                warn!("tap recv: {e}");
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+// struct ifreq with ifr_flags, see linux/if.h
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct IfReq {
+    name: [libc::c_char; libc::IFNAMSIZ],
+    flags: libc::c_short,
+    _pad: [u8; 22],
+}
+
+#[cfg(target_os = "linux")]
+fn open_tap(ifname: &str) -> io::Result<File> {
+    use std::{fs::OpenOptions, os::fd::AsRawFd, os::unix::fs::OpenOptionsExt};
+
+    const TUNSETIFF: libc::c_ulong = 0x4004_54ca;
+    const IFF_TAP: libc::c_short = 0x0002;
+    // no packet info before the frames
+    const IFF_NO_PI: libc::c_short = 0x1000;
+
+    if ifname.len() >= libc::IFNAMSIZ {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("tap name {ifname} is too long"),
+        ));
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open("/dev/net/tun")?;
+    let mut ifr = IfReq {
+        name: [0; libc::IFNAMSIZ],
+        flags: IFF_TAP | IFF_NO_PI,
+        _pad: [0; 22],
+    };
+    for (dst, src) in ifr.name.iter_mut().zip(ifname.bytes()) {
+        *dst = src as libc::c_char;
+    }
+    if unsafe { libc::ioctl(file.as_raw_fd(), TUNSETIFF as _, &mut ifr) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_tap(_ifname: &str) -> io::Result<File> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "tap is only supported on linux hosts",
+    ))
+}
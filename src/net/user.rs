@@ -0,0 +1,678 @@
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read, Write},
+    net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4, TcpStream, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use hashbrown::HashMap;
+
+use super::NetBackend;
+use crate::device::device_virtio_net::DEFAULT_MAC;
+
+// a slirp-style user-mode network, the guest is NATed through host sockets:
+// 10.0.2.2 is the gateway (the host loopback), 10.0.2.3 the dns server and
+// the guest gets 10.0.2.15 by dhcp. there is no ICMP beyond the gateway
+pub const GUEST_IP: [u8; 4] = [10, 0, 2, 15];
+pub const GATEWAY_IP: [u8; 4] = [10, 0, 2, 2];
+pub const DNS_IP: [u8; 4] = [10, 0, 2, 3];
+const NETMASK: [u8; 4] = [255, 255, 255, 0];
+const GATEWAY_MAC: [u8; 6] = [0x52, 0x55, 0x0a, 0x00, 0x02, 0x02];
+const BROADCAST_MAC: [u8; 6] = [0xff; 6];
+
+const ETH_HLEN: usize = 14;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_ARP: u16 = 0x0806;
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+const TCP_MSS: usize = 1460;
+const TCP_WINDOW: usize = 0xffff;
+
+const DHCP_DISCOVER: u8 = 1;
+const DHCP_OFFER: u8 = 2;
+const DHCP_REQUEST: u8 = 3;
+const DHCP_ACK: u8 = 5;
+const DHCP_MAGIC: [u8; 4] = [99, 130, 83, 99];
+const DHCP_LEASE: u32 = 86400;
+
+// the connect blocks the emulation, keep it short
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const UDP_IDLE: Duration = Duration::from_secs(120);
+
+fn be16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn be32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn ip4(data: &[u8], offset: usize) -> [u8; 4] {
+    data[offset..offset + 4].try_into().unwrap()
+}
+
+fn checksum(init: u32, data: &[u8]) -> u16 {
+    let mut sum = data.chunks(2).fold(init, |acc, x| {
+        acc + u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)]) as u32
+    });
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn pseudo_sum(src: [u8; 4], dst: [u8; 4], proto: u8, len: usize) -> u32 {
+    be16(&src, 0) as u32
+        + be16(&src, 2) as u32
+        + be16(&dst, 0) as u32
+        + be16(&dst, 2) as u32
+        + proto as u32
+        + len as u32
+}
+
+// a < b in the sequence space
+fn seq_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FlowKey {
+    guest_port: u16,
+    ip: [u8; 4],
+    port: u16,
+}
+
+struct TcpConn {
+    stream: TcpStream,
+    // the next sequence number we send
+    seq: u32,
+    // the next sequence number expected from the guest
+    ack: u32,
+    // the last sequence number acked by the guest
+    acked: u32,
+    window: u32,
+    to_host: Vec<u8>,
+    host_eof: bool,
+    guest_fin: bool,
+    shutdown: bool,
+}
+
+struct UdpFlow {
+    socket: UdpSocket,
+    last: Instant,
+}
+
+pub struct UserNet {
+    guest_mac: [u8; 6],
+    dns: Ipv4Addr,
+    to_guest: VecDeque<Vec<u8>>,
+    tcp: HashMap<FlowKey, TcpConn>,
+    udp: HashMap<FlowKey, UdpFlow>,
+    isn: u32,
+    ip_id: u16,
+}
+
+impl Default for UserNet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UserNet {
+    pub fn new() -> Self {
+        UserNet {
+            guest_mac: DEFAULT_MAC,
+            dns: Self::host_dns(),
+            to_guest: VecDeque::new(),
+            tcp: HashMap::new(),
+            udp: HashMap::new(),
+            isn: 0x1000_0000,
+            ip_id: 0,
+        }
+    }
+
+    // the first ipv4 nameserver of the host
+    fn host_dns() -> Ipv4Addr {
+        let conf = std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default();
+        conf.lines()
+            .filter_map(|x| x.trim().strip_prefix("nameserver"))
+            .find_map(|x| x.trim().parse().ok())
+            .unwrap_or(Ipv4Addr::new(8, 8, 8, 8))
+    }
+
+    // the host side of a guest destination
+    fn host_addr(&self, ip: [u8; 4], port: u16) -> SocketAddr {
+        let ip = match ip {
+            GATEWAY_IP => Ipv4Addr::LOCALHOST,
+            DNS_IP if port == 53 => self.dns,
+            _ => Ipv4Addr::from(ip),
+        };
+        SocketAddr::V4(SocketAddrV4::new(ip, port))
+    }
+
+    fn eth_frame(&self, dst: [u8; 6], ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(ETH_HLEN + payload.len());
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&GATEWAY_MAC);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn send_ip(&mut self, src: [u8; 4], dst: [u8; 4], proto: u8, payload: &[u8]) {
+        let mut ip = vec![0; 20];
+        ip[0] = 0x45;
+        ip[2..4].copy_from_slice(&((20 + payload.len()) as u16).to_be_bytes());
+        ip[4..6].copy_from_slice(&self.ip_id.to_be_bytes());
+        // don't fragment
+        ip[6] = 0x40;
+        ip[8] = 64;
+        ip[9] = proto;
+        ip[12..16].copy_from_slice(&src);
+        ip[16..20].copy_from_slice(&dst);
+        let sum = checksum(0, &ip);
+        ip[10..12].copy_from_slice(&sum.to_be_bytes());
+        ip.extend_from_slice(payload);
+        self.ip_id = self.ip_id.wrapping_add(1);
+        let frame = self.eth_frame(self.guest_mac, ETH_P_IP, &ip);
+        self.to_guest.push_back(frame);
+    }
+
+    fn send_udp(&mut self, src: ([u8; 4], u16), dst: ([u8; 4], u16), payload: &[u8]) {
+        let len = 8 + payload.len();
+        let mut udp = Vec::with_capacity(len);
+        udp.extend_from_slice(&src.1.to_be_bytes());
+        udp.extend_from_slice(&dst.1.to_be_bytes());
+        udp.extend_from_slice(&(len as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(payload);
+        let sum = match checksum(pseudo_sum(src.0, dst.0, IPPROTO_UDP, len), &udp) {
+            0 => 0xffff,
+            sum => sum,
+        };
+        udp[6..8].copy_from_slice(&sum.to_be_bytes());
+        self.send_ip(src.0, dst.0, IPPROTO_UDP, &udp);
+    }
+
+    fn send_tcp(&mut self, key: FlowKey, seq: u32, ack: u32, flags: u8, data: &[u8]) {
+        let window = match self.tcp.get(&key) {
+            Some(conn) => TCP_WINDOW.saturating_sub(conn.to_host.len()),
+            None => 0,
+        };
+        // the mss option on the syn
+        let opt_len = if flags & TCP_SYN != 0 { 4 } else { 0 };
+        let mut tcp = Vec::with_capacity(20 + opt_len + data.len());
+        tcp.extend_from_slice(&key.port.to_be_bytes());
+        tcp.extend_from_slice(&key.guest_port.to_be_bytes());
+        tcp.extend_from_slice(&seq.to_be_bytes());
+        tcp.extend_from_slice(&ack.to_be_bytes());
+        tcp.push(((20 + opt_len) as u8 / 4) << 4);
+        tcp.push(flags);
+        tcp.extend_from_slice(&(window as u16).to_be_bytes());
+        tcp.extend_from_slice(&[0, 0, 0, 0]);
+        if opt_len != 0 {
+            tcp.extend_from_slice(&[2, 4]);
+            tcp.extend_from_slice(&(TCP_MSS as u16).to_be_bytes());
+        }
+        tcp.extend_from_slice(data);
+        let sum = checksum(pseudo_sum(key.ip, GUEST_IP, IPPROTO_TCP, tcp.len()), &tcp);
+        tcp[16..18].copy_from_slice(&sum.to_be_bytes());
+        self.send_ip(key.ip, GUEST_IP, IPPROTO_TCP, &tcp);
+    }
+
+    fn handle_arp(&mut self, arp: &[u8]) {
+        if arp.len() < 28 || be16(arp, 6) != 1 {
+            return;
+        }
+        let target = ip4(arp, 24);
+        if target[..3] != GUEST_IP[..3] || target == GUEST_IP {
+            return;
+        }
+        let mut reply = Vec::with_capacity(28);
+        reply.extend_from_slice(&arp[..6]);
+        reply.extend_from_slice(&2_u16.to_be_bytes());
+        reply.extend_from_slice(&GATEWAY_MAC);
+        reply.extend_from_slice(&target);
+        reply.extend_from_slice(&arp[8..18]);
+        let frame = self.eth_frame(self.guest_mac, ETH_P_ARP, &reply);
+        self.to_guest.push_back(frame);
+    }
+
+    fn handle_ip(&mut self, ip: &[u8]) {
+        if ip.len() < 20 || ip[0] >> 4 != 4 {
+            return;
+        }
+        let ihl = (ip[0] & 0xf) as usize * 4;
+        let total = (be16(ip, 2) as usize).min(ip.len());
+        // fragments are not supported
+        if total < ihl || be16(ip, 6) & 0x3fff != 0 {
+            return;
+        }
+        let (src, dst) = (ip4(ip, 12), ip4(ip, 16));
+        let payload = &ip[ihl..total];
+        match ip[9] {
+            IPPROTO_ICMP => self.handle_icmp(src, dst, payload),
+            IPPROTO_UDP => self.handle_udp(dst, payload),
+            IPPROTO_TCP => self.handle_tcp(dst, payload),
+            _ => {}
+        }
+    }
+
+    // echo replies for the virtual hosts
+    fn handle_icmp(&mut self, src: [u8; 4], dst: [u8; 4], icmp: &[u8]) {
+        if icmp.len() < 8 || icmp[0] != 8 || (dst != GATEWAY_IP && dst != DNS_IP) {
+            return;
+        }
+        let mut reply = icmp.to_vec();
+        reply[0] = 0;
+        reply[2..4].fill(0);
+        let sum = checksum(0, &reply);
+        reply[2..4].copy_from_slice(&sum.to_be_bytes());
+        self.send_ip(dst, src, IPPROTO_ICMP, &reply);
+    }
+
+    fn handle_udp(&mut self, dst: [u8; 4], udp: &[u8]) {
+        if udp.len() < 8 {
+            return;
+        }
+        let (sport, dport) = (be16(udp, 0), be16(udp, 2));
+        let len = (be16(udp, 4) as usize).clamp(8, udp.len());
+        let payload = &udp[8..len];
+        if dport == 67 {
+            self.handle_dhcp(payload);
+            return;
+        }
+        let key = FlowKey {
+            guest_port: sport,
+            ip: dst,
+            port: dport,
+        };
+        if !self.udp.contains_key(&key) {
+            let Ok(socket) = UdpSocket::bind("0.0.0.0:0") else {
+                return;
+            };
+            if socket.connect(self.host_addr(dst, dport)).is_err()
+                || socket.set_nonblocking(true).is_err()
+            {
+                return;
+            }
+            self.udp.insert(
+                key,
+                UdpFlow {
+                    socket,
+                    last: Instant::now(),
+                },
+            );
+        }
+        let flow = self.udp.get_mut(&key).unwrap();
+        flow.last = Instant::now();
+        let _ = flow.socket.send(payload);
+    }
+
+    fn handle_dhcp(&mut self, bootp: &[u8]) {
+        if bootp.len() < 240 || bootp[0] != 1 || bootp[236..240] != DHCP_MAGIC {
+            return;
+        }
+        let mut options = &bootp[240..];
+        let mut msg_type = None;
+        while let [code, rest @ ..] = options {
+            match code {
+                0 => options = rest,
+                255 => break,
+                _ => {
+                    let Some((len, rest)) = rest.split_first() else {
+                        break;
+                    };
+                    let len = (*len as usize).min(rest.len());
+                    if *code == 53 && len == 1 {
+                        msg_type = Some(rest[0]);
+                    }
+                    options = &rest[len..];
+                }
+            }
+        }
+        let reply_type = match msg_type {
+            Some(DHCP_DISCOVER) => DHCP_OFFER,
+            Some(DHCP_REQUEST) => DHCP_ACK,
+            _ => return,
+        };
+        let mut reply = vec![0; 240];
+        reply[0] = 2;
+        reply[1..3].copy_from_slice(&bootp[1..3]);
+        reply[4..12].copy_from_slice(&bootp[4..12]);
+        reply[16..20].copy_from_slice(&GUEST_IP);
+        reply[20..24].copy_from_slice(&GATEWAY_IP);
+        reply[28..44].copy_from_slice(&bootp[28..44]);
+        reply[236..240].copy_from_slice(&DHCP_MAGIC);
+        reply.extend_from_slice(&[53, 1, reply_type]);
+        reply.extend_from_slice(&[54, 4]);
+        reply.extend_from_slice(&GATEWAY_IP);
+        reply.extend_from_slice(&[51, 4]);
+        reply.extend_from_slice(&DHCP_LEASE.to_be_bytes());
+        reply.extend_from_slice(&[1, 4]);
+        reply.extend_from_slice(&NETMASK);
+        reply.extend_from_slice(&[3, 4]);
+        reply.extend_from_slice(&GATEWAY_IP);
+        reply.extend_from_slice(&[6, 4]);
+        reply.extend_from_slice(&DNS_IP);
+        reply.push(255);
+        self.send_udp((GATEWAY_IP, 67), ([255; 4], 68), &reply);
+    }
+
+    fn handle_tcp(&mut self, dst: [u8; 4], tcp: &[u8]) {
+        if tcp.len() < 20 {
+            return;
+        }
+        let key = FlowKey {
+            guest_port: be16(tcp, 0),
+            ip: dst,
+            port: be16(tcp, 2),
+        };
+        let (seq, ack) = (be32(tcp, 4), be32(tcp, 8));
+        let data_off = ((tcp[12] >> 4) as usize * 4).clamp(20, tcp.len());
+        let flags = tcp[13];
+        let window = be16(tcp, 14) as u32;
+        let data = &tcp[data_off..];
+
+        if flags & TCP_RST != 0 {
+            self.tcp.remove(&key);
+            return;
+        }
+        if !self.tcp.contains_key(&key) {
+            if flags & (TCP_SYN | TCP_ACK) == TCP_SYN {
+                self.tcp_connect(key, seq, window);
+            } else {
+                // the rst of a segment without ack takes sequence number 0
+                let rst_seq = if flags & TCP_ACK != 0 { ack } else { 0 };
+                let end = seq.wrapping_add(data.len() as u32);
+                self.send_tcp(key, rst_seq, end, TCP_RST | TCP_ACK, &[]);
+            }
+            return;
+        }
+
+        let conn = self.tcp.get_mut(&key).unwrap();
+        if flags & TCP_ACK != 0 && !seq_lt(conn.seq, ack) && seq_lt(conn.acked, ack) {
+            conn.acked = ack;
+        }
+        if flags & TCP_ACK != 0 {
+            conn.window = window;
+        }
+        let mut need_ack = !data.is_empty();
+        if seq == conn.ack {
+            conn.to_host.extend_from_slice(data);
+            conn.ack = conn.ack.wrapping_add(data.len() as u32);
+            if flags & TCP_FIN != 0 && !conn.guest_fin {
+                conn.ack = conn.ack.wrapping_add(1);
+                conn.guest_fin = true;
+                need_ack = true;
+            }
+        }
+        if need_ack {
+            let (seq, ack) = (conn.seq, conn.ack);
+            self.send_tcp(key, seq, ack, TCP_ACK, &[]);
+        }
+        self.poll_tcp(key);
+    }
+
+    fn tcp_connect(&mut self, key: FlowKey, seq: u32, window: u32) {
+        let addr = self.host_addr(key.ip, key.port);
+        let ack = seq.wrapping_add(1);
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .and_then(|x| x.set_nonblocking(true).map(|_| x));
+        let Ok(stream) = stream else {
+            self.send_tcp(key, 0, ack, TCP_RST | TCP_ACK, &[]);
+            return;
+        };
+        let _ = stream.set_nodelay(true);
+        let isn = self.isn;
+        self.isn = self.isn.wrapping_add(0x1_0000);
+        self.tcp.insert(
+            key,
+            TcpConn {
+                stream,
+                seq: isn.wrapping_add(1),
+                ack,
+                acked: isn,
+                window,
+                to_host: Vec::new(),
+                host_eof: false,
+                guest_fin: false,
+                shutdown: false,
+            },
+        );
+        self.send_tcp(key, isn, ack, TCP_SYN | TCP_ACK, &[]);
+    }
+
+    // moves the data between the host socket and the guest
+    fn poll_tcp(&mut self, key: FlowKey) {
+        let Some(conn) = self.tcp.get_mut(&key) else {
+            return;
+        };
+        let mut failed = false;
+        while !conn.to_host.is_empty() {
+            match conn.stream.write(&conn.to_host) {
+                Ok(n) => drop(conn.to_host.drain(..n)),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    failed = true;
+                    break;
+                }
+            }
+        }
+        if conn.guest_fin && conn.to_host.is_empty() && !conn.shutdown {
+            let _ = conn.stream.shutdown(Shutdown::Write);
+            conn.shutdown = true;
+        }
+
+        let mut segments = Vec::new();
+        let mut buf = [0; TCP_MSS];
+        while !failed && !conn.host_eof {
+            let in_flight = conn.seq.wrapping_sub(conn.acked) as usize;
+            let room = (conn.window as usize)
+                .saturating_sub(in_flight)
+                .min(TCP_MSS);
+            if room == 0 {
+                break;
+            }
+            match conn.stream.read(&mut buf[..room]) {
+                Ok(0) => {
+                    segments.push((conn.seq, TCP_FIN | TCP_ACK, Vec::new()));
+                    conn.seq = conn.seq.wrapping_add(1);
+                    conn.host_eof = true;
+                }
+                Ok(n) => {
+                    segments.push((conn.seq, TCP_PSH | TCP_ACK, buf[..n].to_vec()));
+                    conn.seq = conn.seq.wrapping_add(n as u32);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => failed = true,
+            }
+        }
+        let (ack, closed) = (
+            conn.ack,
+            conn.host_eof && conn.guest_fin && conn.acked == conn.seq,
+        );
+        for (seq, flags, data) in segments {
+            self.send_tcp(key, seq, ack, flags, &data);
+        }
+        if failed {
+            let seq = self.tcp[&key].seq;
+            self.send_tcp(key, seq, ack, TCP_RST | TCP_ACK, &[]);
+        }
+        if failed || closed {
+            self.tcp.remove(&key);
+        }
+    }
+
+    fn poll_udp(&mut self) {
+        let mut replies = Vec::new();
+        let mut buf = [0; 0x10000];
+        self.udp.retain(|key, flow| {
+            while let Ok(n) = flow.socket.recv(&mut buf) {
+                replies.push((*key, buf[..n].to_vec()));
+                flow.last = Instant::now();
+            }
+            flow.last.elapsed() < UDP_IDLE
+        });
+        for (key, data) in replies {
+            self.send_udp((key.ip, key.port), (GUEST_IP, key.guest_port), &data);
+        }
+    }
+
+    fn poll(&mut self) {
+        let keys: Vec<FlowKey> = self.tcp.keys().copied().collect();
+        keys.into_iter().for_each(|key| self.poll_tcp(key));
+        self.poll_udp();
+    }
+}
+
+impl NetBackend for UserNet {
+    fn send(&mut self, frame: &[u8]) {
+        if frame.len() < ETH_HLEN {
+            return;
+        }
+        let dst: [u8; 6] = frame[..6].try_into().unwrap();
+        if dst != GATEWAY_MAC && dst != BROADCAST_MAC {
+            return;
+        }
+        self.guest_mac = frame[6..12].try_into().unwrap();
+        match be16(frame, 12) {
+            ETH_P_ARP => self.handle_arp(&frame[ETH_HLEN..]),
+            ETH_P_IP => self.handle_ip(&frame[ETH_HLEN..]),
+            _ => {}
+        }
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        if self.to_guest.is_empty() {
+            self.poll();
+        }
+        self.to_guest.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test_net_user {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    fn frame(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = GATEWAY_MAC.to_vec();
+        frame.extend_from_slice(&DEFAULT_MAC);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn ip_frame(dst: [u8; 4], proto: u8, payload: &[u8]) -> Vec<u8> {
+        let mut ip = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, proto, 0, 0];
+        ip[2..4].copy_from_slice(&((20 + payload.len()) as u16).to_be_bytes());
+        ip.extend_from_slice(&GUEST_IP);
+        ip.extend_from_slice(&dst);
+        ip.extend_from_slice(payload);
+        frame(ETH_P_IP, &ip)
+    }
+
+    fn tcp_frame(port: u16, seq: u32, ack: u32, flags: u8, data: &[u8]) -> Vec<u8> {
+        let mut tcp = 40000_u16.to_be_bytes().to_vec();
+        tcp.extend_from_slice(&port.to_be_bytes());
+        tcp.extend_from_slice(&seq.to_be_bytes());
+        tcp.extend_from_slice(&ack.to_be_bytes());
+        tcp.extend_from_slice(&[5 << 4, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        tcp.extend_from_slice(data);
+        ip_frame(GATEWAY_IP, IPPROTO_TCP, &tcp)
+    }
+
+    #[test]
+    fn net_user_test() {
+        let mut net = UserNet::new();
+
+        // arp for the gateway
+        let mut arp = vec![0, 1, 8, 0, 6, 4, 0, 1];
+        arp.extend_from_slice(&DEFAULT_MAC);
+        arp.extend_from_slice(&GUEST_IP);
+        arp.extend_from_slice(&[0; 6]);
+        arp.extend_from_slice(&GATEWAY_IP);
+        net.send(&frame(ETH_P_ARP, &arp));
+        let reply = net.recv().unwrap();
+        assert_eq!(reply[..6], DEFAULT_MAC);
+        assert_eq!(be16(&reply, ETH_HLEN + 6), 2);
+        assert_eq!(reply[ETH_HLEN + 8..ETH_HLEN + 14], GATEWAY_MAC);
+        assert_eq!(ip4(&reply, ETH_HLEN + 14), GATEWAY_IP);
+
+        // dhcp discover, the offer is 10.0.2.15
+        let mut bootp = vec![0; 240];
+        bootp[0] = 1;
+        bootp[4..8].copy_from_slice(&[1, 2, 3, 4]);
+        bootp[236..240].copy_from_slice(&DHCP_MAGIC);
+        bootp.extend_from_slice(&[53, 1, DHCP_DISCOVER, 255]);
+        let mut udp = vec![0, 68, 0, 67, 0, 0, 0, 0];
+        udp[4..6].copy_from_slice(&((8 + bootp.len()) as u16).to_be_bytes());
+        udp.extend_from_slice(&bootp);
+        net.send(&ip_frame([255; 4], IPPROTO_UDP, &udp));
+        let offer = net.recv().unwrap();
+        let bootp = &offer[ETH_HLEN + 28..];
+        assert_eq!(bootp[0], 2);
+        assert_eq!(bootp[4..8], [1, 2, 3, 4]);
+        assert_eq!(ip4(bootp, 16), GUEST_IP);
+        assert_eq!(bootp[240..243], [53, 1, DHCP_OFFER]);
+        assert_eq!(checksum(0, &offer[ETH_HLEN..ETH_HLEN + 20]), 0);
+
+        // icmp echo of the gateway
+        net.send(&ip_frame(
+            GATEWAY_IP,
+            IPPROTO_ICMP,
+            &[8, 0, 0xf7, 0xfe, 0, 1, 0, 0],
+        ));
+        let echo = net.recv().unwrap();
+        assert_eq!(ip4(&echo, ETH_HLEN + 12), GATEWAY_IP);
+        assert_eq!(echo[ETH_HLEN + 20], 0);
+        assert_eq!(checksum(0, &echo[ETH_HLEN + 20..]), 0);
+
+        // tcp to the gateway reaches the host loopback
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        net.send(&tcp_frame(port, 100, 0, TCP_SYN, &[]));
+        let syn_ack = net.recv().unwrap();
+        let tcp = &syn_ack[ETH_HLEN + 20..];
+        assert_eq!(tcp[13], TCP_SYN | TCP_ACK);
+        assert_eq!(be32(tcp, 8), 101);
+        let pseudo = pseudo_sum(GATEWAY_IP, GUEST_IP, IPPROTO_TCP, tcp.len());
+        assert_eq!(checksum(pseudo, tcp), 0);
+        let isn = be32(tcp, 4);
+        let (mut host, _) = listener.accept().unwrap();
+
+        net.send(&tcp_frame(port, 101, isn + 1, TCP_ACK | TCP_PSH, b"hello"));
+        let ack = net.recv().unwrap();
+        assert_eq!(be32(&ack, ETH_HLEN + 28), 106);
+        let mut buf = [0; 5];
+        host.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        host.write_all(b"world").unwrap();
+        let data = loop {
+            if let Some(x) = net.recv() {
+                break x;
+            }
+        };
+        assert_eq!(be32(&data, ETH_HLEN + 24), isn + 1);
+        assert_eq!(&data[ETH_HLEN + 40..], b"world");
+
+        // a segment of an unknown connection is reset
+        net.send(&tcp_frame(port + 1, 7, 9, TCP_ACK, &[]));
+        let rst = net.recv().unwrap();
+        assert_eq!(rst[ETH_HLEN + 33], TCP_RST | TCP_ACK);
+        assert_eq!(be32(&rst, ETH_HLEN + 24), 9);
+    }
+}
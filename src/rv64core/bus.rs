@@ -1,17 +1,24 @@
 use core::cmp::max;
 
 use alloc::vec::Vec;
-use alloc::{boxed::Box, string::ToString};
-use log::warn;
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+};
+use log::{info, warn};
 
 use crate::tools::{check_aligned, check_area};
 use crate::{
     device::{
+        device_aplic::{Aia, APLIC_SIZE, APLIC_S_BASE, IMSIC_M_BASE, IMSIC_S_BASE},
         device_sifive_clint::{Clint, DeviceClint},
         device_sifive_plic::{DevicePlic, SifvePlic},
-        device_trait::DeviceBase,
+        device_trait::{BusMaster, DeviceBase, PmaAttr},
+        irq::{InterruptController, IrqTrigger},
     },
+    error::{RvEmuError, RvEmuResult},
     rv64core::inst::inst_rv64a::LrScReservation,
+    snapshot::{StateReader, StateWriter},
 };
 
 use super::inst::inst_base::RVerr;
@@ -25,11 +32,51 @@ pub struct DeviceType {
 
 unsafe impl Send for DeviceType {}
 
+// access counters of a device, to find MMIO hot loops in the guest
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BusPerf {
+    // number of accesses, indexed by log2(len): 1, 2, 4, 8 bytes
+    pub reads: [u64; 4],
+    pub writes: [u64; 4],
+    // copy_from_slice/copy_to_slice are counted in bytes only
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+impl BusPerf {
+    fn record_read(&mut self, len: usize) {
+        if let Some(x) = self.reads.get_mut(len.trailing_zeros() as usize) {
+            *x += 1;
+        }
+        self.read_bytes += len as u64;
+    }
+    fn record_write(&mut self, len: usize) {
+        if let Some(x) = self.writes.get_mut(len.trailing_zeros() as usize) {
+            *x += 1;
+        }
+        self.write_bytes += len as u64;
+    }
+    pub fn accesses(&self) -> u64 {
+        self.reads.iter().chain(self.writes.iter()).sum()
+    }
+    pub fn bytes(&self) -> u64 {
+        self.read_bytes + self.write_bytes
+    }
+}
+
 pub struct Bus {
     pub clint: DeviceClint,
     pub plic: DevicePlic,
+    // the AIA instead of the PLIC, see `Bus::enable_aia`
+    pub aia: Option<Aia>,
     pub devices: Vec<DeviceType>,
     pub lr_sc_set: LrScReservation, // for rv64a inst
+    // one for each entry of `devices`
+    perf: Vec<BusPerf>,
+    // (index in `devices`, irq id) of the devices added with an irq
+    irqs: Vec<(usize, u32)>,
+    clint_perf: BusPerf,
+    plic_perf: BusPerf,
 }
 
 unsafe impl Send for Bus {}
@@ -54,12 +101,116 @@ impl Bus {
             devices: vec![],
             clint,
             plic,
+            aia: None,
             lr_sc_set: LrScReservation::new(),
+            perf: vec![],
+            irqs: vec![],
+            clint_perf: BusPerf::default(),
+            plic_perf: BusPerf::default(),
         }
     }
 
-    pub fn add_device(&mut self, device: DeviceType) {
+    // devices are updated in the order they are added.
+    // a device may not overlap the devices already on the bus
+    pub fn add_device(&mut self, device: DeviceType) -> RvEmuResult<()> {
+        let end = device.start.checked_add(device.len);
+        if device.len == 0 || end.is_none() {
+            return Err(RvEmuError::BadConfig(format!(
+                "device {} has an invalid area {:#x}+{:#x}",
+                device.name, device.start, device.len
+            )));
+        }
+        let overlap =
+            |start: u64, len: u64| device.start < start + len && start < device.start + device.len;
+        let other = self
+            .devices
+            .iter()
+            .map(|x| (x.start, x.len, x.name))
+            .chain([
+                (self.clint.start, self.clint.len, self.clint.name),
+                (self.plic.start, self.plic.len, self.plic.name),
+            ])
+            .find(|(start, len, _)| overlap(*start, *len));
+        if let Some((_, _, other)) = other {
+            return Err(RvEmuError::DeviceConflict {
+                name: device.name,
+                start: device.start,
+                len: device.len,
+                other,
+            });
+        }
         self.devices.push(device);
+        Ok(())
+    }
+
+    // the harts get IMSIC files instead of PLIC contexts, and the devices
+    // added later with an irq are wired to the APLIC. the PLIC stays on
+    // the bus without sources
+    pub fn enable_aia(&mut self) -> RvEmuResult<()> {
+        let aia = Aia::new();
+        let windows: [(u64, u64, Box<dyn DeviceBase>, &'static str); 3] = [
+            (
+                IMSIC_M_BASE,
+                aia.imsic_m.size(),
+                Box::new(aia.imsic_m.clone()),
+                "IMSIC_M",
+            ),
+            (
+                IMSIC_S_BASE,
+                aia.imsic_s.size(),
+                Box::new(aia.imsic_s.clone()),
+                "IMSIC_S",
+            ),
+            (
+                APLIC_S_BASE,
+                APLIC_SIZE,
+                Box::new(aia.aplic.clone()),
+                "APLIC_S",
+            ),
+        ];
+        for (start, len, instance, name) in windows {
+            self.add_device(DeviceType {
+                start,
+                len,
+                instance,
+                name,
+            })?;
+        }
+        self.aia = Some(aia);
+        Ok(())
+    }
+
+    fn device_perf(perf: &mut Vec<BusPerf>, idx: usize) -> &mut BusPerf {
+        // `devices` is public, a device may be pushed without `add_device`
+        if idx >= perf.len() {
+            perf.resize(idx + 1, BusPerf::default());
+        }
+        &mut perf[idx]
+    }
+
+    // add a device and connect it to PLIC source `irq_id`,
+    // or to APLIC source `irq_id` with the AIA
+    pub fn add_device_with_irq(
+        &mut self,
+        mut device: DeviceType,
+        irq_id: u32,
+        trigger: IrqTrigger,
+    ) -> RvEmuResult<()> {
+        let chip: &mut dyn InterruptController = match self.aia.as_mut() {
+            Some(aia) => &mut aia.aplic,
+            None => &mut self.plic.instance,
+        };
+        device
+            .instance
+            .connect_irq(chip.alloc_irq_line(irq_id, trigger));
+        self.add_device(device)?;
+        self.irqs.push((self.devices.len() - 1, irq_id));
+        Ok(())
+    }
+
+    // the irq of `devices[idx]` given to `add_device_with_irq`
+    pub fn device_irq(&self, idx: usize) -> Option<u32> {
+        self.irqs.iter().find(|x| x.0 == idx).map(|x| x.1)
     }
 
     pub fn read(&mut self, addr: u64, len: usize) -> Result<u64, RVerr> {
@@ -72,8 +223,10 @@ impl Bus {
         // such as clint
         let mut special_device = || -> Result<u64, RVerr> {
             if check_area(self.clint.start, self.clint.len, addr) {
+                self.clint_perf.record_read(len);
                 Ok(self.clint.instance.do_read(addr - self.clint.start, len))
             } else if check_area(self.plic.start, self.plic.len, addr) {
+                self.plic_perf.record_read(len);
                 Ok(self.plic.instance.do_read(addr - self.plic.start, len))
             } else {
                 warn!("can not find device,read addr{addr:X}");
@@ -87,16 +240,58 @@ impl Bus {
         let general_device = self
             .devices
             .iter_mut()
-            .find(|device| check_area(device.start, device.len, addr))
-            .map(|device| device.instance.do_read(addr - device.start, len));
+            .enumerate()
+            .find(|(_, device)| check_area(device.start, device.len, addr))
+            .map(|(idx, device)| (idx, device.instance.do_read(addr - device.start, len)));
 
         // first find general devices
         match general_device {
-            Some(val) => Ok(val),
+            Some((idx, val)) => {
+                Self::device_perf(&mut self.perf, idx).record_read(len);
+                Ok(val)
+            }
             None => special_device(),
         }
     }
 
+    // the attributes of the device at `addr`, None if nothing is mapped there
+    pub fn pma(&self, addr: u64) -> Option<PmaAttr> {
+        let general_device = self
+            .devices
+            .iter()
+            .find(|device| check_area(device.start, device.len, addr))
+            .map(|device| device.instance.pma());
+        if general_device.is_some() {
+            general_device
+        } else if check_area(self.clint.start, self.clint.len, addr) {
+            Some(self.clint.instance.pma())
+        } else if check_area(self.plic.start, self.plic.len, addr) {
+            Some(self.plic.instance.pma())
+        } else {
+            None
+        }
+    }
+
+    // 128-bit access (AMOCAS.Q), split into two 64-bit accesses, low half first.
+    // the devices are at least 16 bytes aligned, both halves hit the same device
+    pub fn read128(&mut self, addr: u64) -> Result<u128, RVerr> {
+        if !check_aligned(addr, 16) {
+            return Err(RVerr::AddrMisalign);
+        }
+        let lo = self.read(addr, 8)?;
+        let hi = self.read(addr + 8, 8)?;
+        Ok(((hi as u128) << 64) | lo as u128)
+    }
+
+    pub fn write128(&mut self, addr: u64, data: u128) -> Result<(), RVerr> {
+        if !check_aligned(addr, 16) {
+            return Err(RVerr::AddrMisalign);
+        }
+        self.write(addr, data as u64, 8)?;
+        self.write(addr + 8, (data >> 64) as u64, 8)?;
+        Ok(())
+    }
+
     pub fn write(&mut self, addr: u64, data: u64, len: usize) -> Result<u64, RVerr> {
         if !check_aligned(addr, len) {
             return Err(RVerr::AddrMisalign);
@@ -104,11 +299,13 @@ impl Bus {
 
         let mut special_device = || -> Result<u64, RVerr> {
             if check_area(self.clint.start, self.clint.len, addr) {
+                self.clint_perf.record_write(len);
                 Ok(self
                     .clint
                     .instance
                     .do_write(addr - self.clint.start, data, len))
             } else if check_area(self.plic.start, self.plic.len, addr) {
+                self.plic_perf.record_write(len);
                 Ok(self
                     .plic
                     .instance
@@ -124,23 +321,53 @@ impl Bus {
         let general_device = self
             .devices
             .iter_mut()
-            .find(|device| check_area(device.start, device.len, addr))
-            .map(|device| device.instance.do_write(addr - device.start, data, len));
+            .enumerate()
+            .find(|(_, device)| check_area(device.start, device.len, addr))
+            .map(|(idx, device)| {
+                let ret = device.instance.do_write(addr - device.start, data, len);
+                (idx, ret)
+            });
 
         match general_device {
-            Some(val) => Ok(val),
+            Some((idx, val)) => {
+                Self::device_perf(&mut self.perf, idx).record_write(len);
+                Ok(val)
+            }
             None => special_device(),
         }
     }
 
+    // a slice copy must not run past the end of the device it starts in
+    fn check_slice(&self, addr: u64, len: usize) -> Result<(), RVerr> {
+        let fits = self
+            .devices
+            .iter()
+            .map(|x| (x.start, x.len))
+            .chain([
+                (self.clint.start, self.clint.len),
+                (self.plic.start, self.plic.len),
+            ])
+            .find(|(start, len)| check_area(*start, *len, addr))
+            .is_some_and(|(start, dev_len)| addr - start + len as u64 <= dev_len);
+        if fits {
+            Ok(())
+        } else {
+            warn!("slice out of device,addr{addr:X},len:{len:#x}");
+            Err(RVerr::NotFindDevice)
+        }
+    }
+
     pub fn copy_from_slice(&mut self, addr: u64, data: &[u8]) -> Result<(), RVerr> {
+        self.check_slice(addr, data.len())?;
         let mut special_device = || -> Result<(), RVerr> {
             if check_area(self.clint.start, self.clint.len, addr) {
+                self.clint_perf.write_bytes += data.len() as u64;
                 self.clint
                     .instance
                     .copy_from_slice(addr - self.clint.start, data);
                 Ok(())
             } else if check_area(self.plic.start, self.plic.len, addr) {
+                self.plic_perf.write_bytes += data.len() as u64;
                 self.plic
                     .instance
                     .copy_from_slice(addr - self.plic.start, data);
@@ -156,29 +383,43 @@ impl Bus {
         let general_device = self
             .devices
             .iter_mut()
-            .find(|device| check_area(device.start, device.len, addr))
-            .map(|device| device.instance.copy_from_slice(addr - device.start, data));
+            .enumerate()
+            .find(|(_, device)| check_area(device.start, device.len, addr))
+            .map(|(idx, device)| {
+                device.instance.copy_from_slice(addr - device.start, data);
+                idx
+            });
 
         match general_device {
-            Some(val) => Ok(val),
+            Some(idx) => {
+                Self::device_perf(&mut self.perf, idx).write_bytes += data.len() as u64;
+                Ok(())
+            }
             None => special_device(),
         }
     }
 
     pub fn copy_to_slice(&mut self, addr: u64, data: &mut [u8]) -> Result<(), RVerr> {
+        self.check_slice(addr, data.len())?;
         let general_device = self
             .devices
             .iter_mut()
-            .find(|device| check_area(device.start, device.len, addr))
-            .map(|device| device.instance.copy_to_slice(addr - device.start, data));
+            .enumerate()
+            .find(|(_, device)| check_area(device.start, device.len, addr))
+            .map(|(idx, device)| {
+                device.instance.copy_to_slice(addr - device.start, data);
+                idx
+            });
 
         let mut special_device = || -> Result<(), RVerr> {
             if check_area(self.clint.start, self.clint.len, addr) {
+                self.clint_perf.read_bytes += data.len() as u64;
                 self.clint
                     .instance
                     .copy_to_slice(addr - self.clint.start, data);
                 Ok(())
             } else if check_area(self.plic.start, self.plic.len, addr) {
+                self.plic_perf.read_bytes += data.len() as u64;
                 self.plic
                     .instance
                     .copy_to_slice(addr - self.plic.start, data);
@@ -192,17 +433,142 @@ impl Bus {
         };
 
         match general_device {
-            Some(val) => Ok(val),
+            Some(idx) => {
+                Self::device_perf(&mut self.perf, idx).read_bytes += data.len() as u64;
+                Ok(())
+            }
             None => special_device(),
         }
     }
 
+    // update order is fixed:
+    // 1. general devices, in the order they are added
+    // 2. clint, mtime advances interval_cycle/10 ticks
+    // 3. plic (or aplic), the last one, so the irq lines raised in 1 are
+    //    sampled in the same update
     pub fn update(&mut self, interval_cycle: usize) {
-        self.devices
-            .iter_mut()
-            .for_each(|device| device.instance.do_update());
+        for idx in 0..self.devices.len() {
+            self.devices[idx].instance.do_update();
+            // a bus master gets the bus without itself
+            let mut instance =
+                core::mem::replace(&mut self.devices[idx].instance, Box::new(Detached));
+            instance.do_dma(self);
+            self.devices[idx].instance = instance;
+        }
         self.clint.instance.tick(max(interval_cycle / 10, 1));
         self.plic.instance.tick();
+        if let Some(aia) = self.aia.as_ref() {
+            aia.aplic.tick();
+        }
+    }
+
+    // a system reset: the registers of every device, the memories keep
+    // their content
+    pub fn reset(&mut self) {
+        self.devices
+            .iter_mut()
+            .for_each(|device| device.instance.reset());
+        self.clint.instance.reset();
+        self.plic.instance.reset();
+        self.lr_sc_set.clear();
+    }
+
+    // the header, then the state of every device with its name and type,
+    // in the order of the bus. the harts are saved apart, an LR
+    // reservation does not survive
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::new();
+        out.put_header();
+        let mut put = |name: &str, device: &dyn DeviceBase| {
+            let mut state = StateWriter::new();
+            device.save_state(&mut state);
+            out.put_bytes(name.as_bytes());
+            out.put_bytes(device.get_name().as_bytes());
+            out.put_bytes(&state.finish());
+        };
+        put(self.clint.name, &self.clint.instance);
+        put(self.plic.name, &self.plic.instance);
+        self.devices
+            .iter()
+            .for_each(|device| put(device.name, device.instance.as_ref()));
+        out.finish()
+    }
+
+    // into a bus with the devices of the saved one, of this build
+    pub fn load_state(&mut self, state: &[u8]) -> RvEmuResult<()> {
+        let mut input = StateReader::new(state);
+        input.get_header()?;
+        let mut get = |name: &str, device: &mut dyn DeviceBase| -> RvEmuResult<()> {
+            let saved = input.get_bytes()?;
+            let kind = input.get_bytes()?;
+            if saved != name.as_bytes() || kind != device.get_name().as_bytes() {
+                return Err(RvEmuError::Snapshot(format!(
+                    "device {name} ({}), the state has {} ({})",
+                    device.get_name(),
+                    String::from_utf8_lossy(saved),
+                    String::from_utf8_lossy(kind)
+                )));
+            }
+            let mut device_state = StateReader::new(input.get_bytes()?);
+            device
+                .load_state(&mut device_state)
+                .and_then(|_| device_state.finish())
+                .map_err(|e| match e {
+                    RvEmuError::Snapshot(reason) => {
+                        RvEmuError::Snapshot(format!("device {name}: {reason}"))
+                    }
+                    e => e,
+                })
+        };
+        get(self.clint.name, &mut self.clint.instance)?;
+        get(self.plic.name, &mut self.plic.instance)?;
+        for device in self.devices.iter_mut() {
+            get(device.name, device.instance.as_mut())?;
+        }
+        self.lr_sc_set.clear();
+        input.finish()
+    }
+
+    // (name, counters) of every device, the busiest first
+    pub fn perf(&self) -> Vec<(&'static str, BusPerf)> {
+        let mut ret: Vec<(&'static str, BusPerf)> = self
+            .devices
+            .iter()
+            .enumerate()
+            .map(|(idx, device)| (device.name, self.perf.get(idx).copied().unwrap_or_default()))
+            .collect();
+        ret.push((self.clint.name, self.clint_perf));
+        ret.push((self.plic.name, self.plic_perf));
+        ret.sort_by_key(|(_, perf)| core::cmp::Reverse(perf.accesses()));
+        ret
+    }
+
+    pub fn perf_report(&self) -> String {
+        let mut report = String::from("-------------Bus Perf-------------\n");
+        report += &format!(
+            "{:15} {:>12} {:>12} {:>12} {:>12}   {}\n",
+            "name", "reads", "writes", "read bytes", "write bytes", "r/w by size(1/2/4/8)"
+        );
+        self.perf()
+            .iter()
+            .filter(|(_, perf)| perf.accesses() != 0 || perf.bytes() != 0)
+            .for_each(|(name, perf)| {
+                report += &format!(
+                    "{:15} {:>12} {:>12} {:>12} {:>12}   {:?}/{:?}\n",
+                    name,
+                    perf.reads.iter().sum::<u64>(),
+                    perf.writes.iter().sum::<u64>(),
+                    perf.read_bytes,
+                    perf.write_bytes,
+                    perf.reads,
+                    perf.writes
+                );
+            });
+        report
+    }
+
+    pub fn show_perf(&self) {
+        info!("\n{}", self.perf_report());
     }
 }
 
@@ -212,6 +578,30 @@ impl Default for Bus {
     }
 }
 
+impl BusMaster for Bus {
+    fn dma_read(&mut self, addr: u64, buf: &mut [u8]) -> bool {
+        self.copy_to_slice(addr, buf).is_ok()
+    }
+    fn dma_write(&mut self, addr: u64, buf: &[u8]) -> bool {
+        self.copy_from_slice(addr, buf).is_ok()
+    }
+}
+
+// the place of a device during its own `do_dma`
+struct Detached;
+
+impl DeviceBase for Detached {
+    fn do_read(&mut self, _addr: u64, _len: usize) -> u64 {
+        0
+    }
+    fn do_write(&mut self, _addr: u64, _data: u64, _len: usize) -> u64 {
+        0
+    }
+    fn get_name(&self) -> &'static str {
+        "detached"
+    }
+}
+
 impl core::fmt::Display for Bus {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let x = self.devices.iter().map(|device| {
@@ -248,3 +638,183 @@ impl core::fmt::Display for Bus {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test_bus {
+    use alloc::boxed::Box;
+
+    use crate::{
+        device::{
+            device_16550a::Device16550aUART,
+            device_memory::DeviceMemory,
+            device_sifive_plic::UART16550_IRQ,
+            device_trait::{PmaAttr, MEM_BASE},
+            irq::IrqTrigger,
+        },
+        error::RvEmuError,
+        tools::fifo_unbounded_new,
+    };
+
+    use super::{Bus, DeviceType};
+
+    #[test]
+    fn perf_test() {
+        let mut bus = Bus::new();
+        bus.add_device(DeviceType {
+            start: MEM_BASE,
+            len: 0x1000,
+            instance: Box::new(DeviceMemory::new(0x1000)),
+            name: "DRAM",
+        })
+        .unwrap();
+
+        bus.copy_from_slice(MEM_BASE, &[0; 16]).unwrap();
+        bus.write(MEM_BASE, 1, 8).unwrap();
+        bus.read(MEM_BASE, 4).unwrap();
+        bus.read(MEM_BASE + 2, 2).unwrap();
+        // clint mtime, polled
+        for _ in 0..10 {
+            bus.read(0x0200_bff8, 8).unwrap();
+        }
+        // misaligned and unmapped accesses are not counted
+        assert!(bus.read(MEM_BASE + 1, 4).is_err());
+        assert!(bus.read(0x1000, 4).is_err());
+
+        let perf = bus.perf();
+        assert_eq!(perf[0].0, "CLINT");
+        assert_eq!(perf[0].1.reads, [0, 0, 0, 10]);
+        assert_eq!(perf[0].1.read_bytes, 80);
+
+        let (name, dram) = perf[1];
+        assert_eq!(name, "DRAM");
+        assert_eq!(dram.reads, [0, 1, 1, 0]);
+        assert_eq!(dram.writes, [0, 0, 0, 1]);
+        assert_eq!(dram.read_bytes, 6);
+        assert_eq!(dram.write_bytes, 24);
+        assert_eq!(dram.accesses(), 3);
+
+        let report = bus.perf_report();
+        assert!(report.contains("DRAM"));
+        // PLIC is idle
+        assert!(!report.contains("PLIC"));
+    }
+
+    #[test]
+    fn device_conflict_test() {
+        let mut bus = Bus::new();
+        let mem = |start: u64, len: u64, name: &'static str| DeviceType {
+            start,
+            len,
+            instance: Box::new(DeviceMemory::new(len as usize)),
+            name,
+        };
+        bus.add_device(mem(MEM_BASE, 0x1000, "DRAM")).unwrap();
+        bus.add_device(mem(MEM_BASE + 0x1000, 0x1000, "SRAM"))
+            .unwrap();
+
+        let ret = bus.add_device(mem(MEM_BASE + 0xff8, 0x10, "UART"));
+        assert_eq!(
+            ret,
+            Err(RvEmuError::DeviceConflict {
+                name: "UART",
+                start: MEM_BASE + 0xff8,
+                len: 0x10,
+                other: "DRAM",
+            })
+        );
+        // the clint is always on the bus
+        let ret = bus.add_device(mem(0x0200_0000, 0x10, "UART"));
+        assert!(matches!(
+            ret,
+            Err(RvEmuError::DeviceConflict { other: "CLINT", .. })
+        ));
+        assert!(matches!(
+            bus.add_device(mem(0x1000, 0, "UART")),
+            Err(RvEmuError::BadConfig(_))
+        ));
+
+        // a slice copy can not run past the end of a device
+        assert!(bus.copy_from_slice(MEM_BASE + 0x1ff8, &[0; 16]).is_err());
+        assert!(bus.copy_from_slice(MEM_BASE + 0x1ff0, &[0; 16]).is_ok());
+
+        assert_eq!(bus.pma(MEM_BASE + 0x1ff8), Some(PmaAttr::MEMORY));
+        assert_eq!(bus.pma(0x0c00_0000), Some(PmaAttr::IO));
+        assert_eq!(bus.pma(MEM_BASE + 0x2000), None);
+    }
+
+    #[test]
+    fn snapshot_test() {
+        const UART: u64 = 0x1000_0000;
+        let machine = |uart: bool| {
+            let mut bus = Bus::new();
+            bus.add_device(DeviceType {
+                start: MEM_BASE,
+                len: 0x1000,
+                instance: Box::new(DeviceMemory::new(0x1000)),
+                name: "DRAM",
+            })
+            .unwrap();
+            if uart {
+                let uart = Device16550aUART::new(fifo_unbounded_new(), fifo_unbounded_new());
+                let device = DeviceType {
+                    start: UART,
+                    len: 0x8,
+                    instance: Box::new(uart),
+                    name: "UART",
+                };
+                bus.add_device_with_irq(device, UART16550_IRQ, IrqTrigger::Level)
+                    .unwrap();
+            }
+            bus
+        };
+        let mut bus = machine(true);
+        bus.write(MEM_BASE + 0x10, 0x1122_3344_5566_7788, 8)
+            .unwrap();
+        // scratch, mtime and the priority of the uart
+        bus.write(UART + 7, 0x5a, 1).unwrap();
+        bus.write(0x0200_bff8, 1234, 8).unwrap();
+        bus.write(0x0c00_0000 + 4 * UART16550_IRQ as u64, 3, 4)
+            .unwrap();
+        let state = bus.save_state();
+
+        let mut restored = machine(true);
+        restored.load_state(&state).unwrap();
+        assert_eq!(
+            restored.read(MEM_BASE + 0x10, 8).ok(),
+            Some(0x1122_3344_5566_7788)
+        );
+        assert_eq!(restored.read(UART + 7, 1).ok(), Some(0x5a));
+        assert_eq!(restored.read(0x0200_bff8, 8).ok(), Some(1234));
+        assert_eq!(
+            restored
+                .read(0x0c00_0000 + 4 * UART16550_IRQ as u64, 4)
+                .ok(),
+            Some(3)
+        );
+
+        // another machine
+        assert!(matches!(
+            machine(false).load_state(&state),
+            Err(RvEmuError::Snapshot(_))
+        ));
+        assert!(restored.load_state(&state[..state.len() - 1]).is_err());
+        // another device of the same name
+        let mut other = Bus::new();
+        let uart = Device16550aUART::new(fifo_unbounded_new(), fifo_unbounded_new());
+        let device = DeviceType {
+            start: MEM_BASE,
+            len: 0x8,
+            instance: Box::new(uart),
+            name: "DRAM",
+        };
+        other
+            .add_device_with_irq(device, UART16550_IRQ, IrqTrigger::Level)
+            .unwrap();
+        let ret = other.load_state(&state);
+        assert!(matches!(ret, Err(RvEmuError::Snapshot(x)) if x.contains("memory")));
+        // another build
+        let mut state = state;
+        state[8] ^= 1;
+        assert!(restored.load_state(&state).is_err());
+    }
+}
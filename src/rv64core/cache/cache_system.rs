@@ -1,6 +1,10 @@
 use alloc::rc::Rc;
 
-use crate::{config::Config, rv64core::bus::Bus, tools::RcRefCell};
+use crate::{
+    config::Config,
+    rv64core::{bus::Bus, inst::inst_base::RVerr, mmu::vm_info::MemType},
+    tools::RcRefCell,
+};
 
 use super::{cpu_dcache::CpuDcache, cpu_icache::CpuIcache};
 
@@ -8,13 +12,14 @@ pub struct CacheSystem {
     pub icache: CpuIcache,
     pub dcache: CpuDcache,
     pub bus: RcRefCell<Bus>,
+    #[allow(dead_code)]
     config: Rc<Config>,
 }
 
 impl CacheSystem {
     pub fn new(bus: RcRefCell<Bus>, config: Rc<Config>) -> Self {
         let icache = CpuIcache::new(bus.clone(), config.icache_size().unwrap_or(0));
-        let dcache = CpuDcache::new(bus.clone(),config.dcache_size().unwrap_or(0));
+        let dcache = CpuDcache::new(bus.clone(), config.dcache_size().unwrap_or(0));
         CacheSystem {
             icache,
             dcache,
@@ -27,6 +32,27 @@ impl CacheSystem {
         self.dcache.show_perf();
     }
 
+    // the NC and IO pages of Svpbmt bypass the dcache
+    pub fn read(&mut self, addr: u64, len: usize, mem_type: MemType) -> Result<u64, RVerr> {
+        match mem_type {
+            MemType::Pma => self.dcache.read(addr, len),
+            MemType::Nc | MemType::Io => self.bus.borrow_mut().read(addr, len),
+        }
+    }
+
+    pub fn write(
+        &mut self,
+        addr: u64,
+        data: u64,
+        len: usize,
+        mem_type: MemType,
+    ) -> Result<u64, RVerr> {
+        match mem_type {
+            MemType::Pma => self.dcache.write(addr, data, len),
+            MemType::Nc | MemType::Io => self.bus.borrow_mut().write(addr, data, len),
+        }
+    }
+
     pub fn clear(&mut self) {
         self.icache.clear();
         self.dcache.clear();
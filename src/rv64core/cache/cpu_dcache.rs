@@ -145,21 +145,78 @@ impl CpuDcache {
                 self.write(addr, data, len)
             })
     }
+    // a 16 bytes aligned block never crosses a cache line
+    pub fn read128(&mut self, addr: u64) -> Result<u128, RVerr> {
+        if !self.cacheable(addr) {
+            return self.bus.borrow_mut().read128(addr);
+        }
+        let lo = self.read(addr, 8)?;
+        let hi = self.read(addr + 8, 8)?;
+        Ok(((hi as u128) << 64) | lo as u128)
+    }
+
+    pub fn write128(&mut self, addr: u64, data: u128) -> Result<(), RVerr> {
+        if !self.cacheable(addr) {
+            return self.bus.borrow_mut().write128(addr, data);
+        }
+        self.write(addr, data as u64, 8)?;
+        self.write(addr + 8, (data >> 64) as u64, 8)?;
+        Ok(())
+    }
+
     pub fn clear(&mut self) {
         let mut bus = self.bus.borrow_mut();
         self.caches.iter_mut().for_each(|(_, cache_line)| {
             if cache_line.dirty() {
-                let addr = cache_line.tag << 6;
-                for i in (0..64).step_by(8) {
-                    let data = cache_line.read(i, 8);
-                    bus.write(addr + i as u64, data, 8).unwrap();
-                }
+                Self::write_back(&mut bus, cache_line);
             }
             // cache_line.clear()
         });
         self.caches.clear();
     }
 
+    fn write_back(bus: &mut Bus, cache_line: &mut CacheLine) {
+        let addr = cache_line.tag << 6;
+        for i in (0..64).step_by(8) {
+            let data = cache_line.read(i, 8);
+            bus.write(addr + i as u64, data, 8).unwrap();
+        }
+        cache_line.dirty = false;
+    }
+
+    // cache block operations, the block is the cache line holding addr.
+    // a block which is not cached is already clean, nothing to do
+
+    // write back the block if dirty, the block stays cached
+    pub fn clean(&mut self, addr: u64) {
+        let tag = self.tag(addr);
+        if let Some(cache_line) = self.caches.get_mut(&tag) {
+            if cache_line.dirty() {
+                Self::write_back(&mut self.bus.borrow_mut(), cache_line);
+            }
+        }
+    }
+
+    // write back the block if dirty, then drop it
+    pub fn flush(&mut self, addr: u64) {
+        let tag = self.tag(addr);
+        if let Some(mut cache_line) = self.caches.remove(&tag) {
+            if cache_line.dirty() {
+                Self::write_back(&mut self.bus.borrow_mut(), &mut cache_line);
+            }
+        }
+    }
+
+    // drop the block, dirty data is discarded
+    pub fn invalidate(&mut self, addr: u64) {
+        let tag = self.tag(addr);
+        self.caches.remove(&tag);
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.miss
+    }
+
     pub fn show_perf(&self) {
         info!("dcache hit: {}, miss: {}", self.hit, self.miss);
         info!(
@@ -78,6 +78,9 @@ impl CpuIcache {
     pub fn clear(&mut self) {
         self.inst_hash.clear();
     }
+    pub fn misses(&self) -> u64 {
+        self.miss
+    }
     pub fn show_perf(&self) {
         info!("icache hit: {}, miss: {}", self.hit, self.miss);
         info!(
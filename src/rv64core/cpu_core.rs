@@ -1,20 +1,29 @@
-use core::{borrow::Borrow, cell::Cell, result};
+use core::cell::Cell;
 
-use alloc::rc::Rc;
+use alloc::{format, rc::Rc, string::String};
 use log::{debug, info, warn};
 
 use crate::{
-    config::Config,
+    config::{Compliance, Config},
     dbg::dm_interface::DebugModuleSlave,
     difftest::difftest_trait::Difftest,
+    error::RvEmuResult,
     rv64core::{
         bus::Bus,
         csr_regs::CsrRegs,
-        csr_regs_define::XipIn,
+        csr_regs_define::{XipIn, XstatusIn},
+        energy::{EnergyModel, InstClass},
+        fpr::Fpr,
         gpr::Gpr,
-        inst::inst_base::{AccessType, PrivilegeLevels},
+        inst::{
+            inst_base::{AccessType, PrivilegeLevels, MATCH_WFI},
+            inst_disasm::disassemble,
+        },
         inst_decode::InstDecode,
+        pipeline::Pipeline,
         traptype::TrapType,
+        trigger::{TriggerAccess, TriggerAction},
+        vector::vpr::Vpr,
     },
     tools::{check_aligned, RcRefCell},
 };
@@ -23,8 +32,11 @@ use crate::{
 use crate::trace::traces::TraceType;
 
 use super::{
-    cache::cache_system::CacheSystem, inst::inst_base::is_compressed_instruction,
-    mmu::cpu_mmu::Mmu, traptype::DebugCause,
+    cache::cache_system::CacheSystem,
+    csr_regs_define::HpmEvent,
+    inst::inst_base::is_compressed_instruction,
+    mmu::cpu_mmu::{Mmu, VirtRegs},
+    traptype::DebugCause,
 };
 
 pub struct DebugState {
@@ -47,6 +59,8 @@ pub struct DebugState {
     pub havereset: bool,
     // 处理器是否处于 debug 模式
     pub debug_mode: bool,
+    // an action 1 trigger fired, the instruction is left to enter debug mode
+    pub trigger_flag: bool,
 }
 
 impl DebugState {
@@ -59,6 +73,7 @@ impl DebugState {
             havereset: false,
             debug_mode: false,
             singlestep_flag: false,
+            trigger_flag: false,
         }
     }
 }
@@ -73,6 +88,8 @@ impl Default for DebugState {
 pub enum CpuState {
     Running,
     Haltd,
+    // sleeping in WFI until an interrupt is pending
+    Wfi,
     Stop,
     Abort,
 }
@@ -115,7 +132,18 @@ impl CpuCoreBuild {
         self
     }
 
+    // panics with the `Config::validate` report if the config is invalid
     pub fn build(&self) -> CpuCore {
+        self.try_build().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_build(&self) -> RvEmuResult<CpuCore> {
+        self.config.validate()?;
+        info!(
+            "hart {} compliance mode: {}",
+            self.hart_id,
+            self.config.compliance().name()
+        );
         let mut csr_regs_u = CsrRegs::new(self.hart_id, self.config.clone());
         let privi_u = Rc::new(Cell::new(PrivilegeLevels::Machine));
         // some csr regs are shared with other modules
@@ -123,6 +151,14 @@ impl CpuCoreBuild {
         let satp = csr_regs_u.satp.clone();
         // let mtime = csr_regs_u.time.clone();
         let xip = csr_regs_u.xip.clone();
+        let virt_regs = VirtRegs {
+            virt: csr_regs_u.h.virt.clone(),
+            hstatus: csr_regs_u.h.hstatus.clone(),
+            vsstatus: csr_regs_u.h.vsstatus.clone(),
+            vsatp: csr_regs_u.h.vsatp.clone(),
+            hgatp: csr_regs_u.h.hgatp.clone(),
+            henvcfg: csr_regs_u.h.henvcfg.clone(),
+        };
 
         let cache_system =
             RcRefCell::new(CacheSystem::new(self.shared_bus.clone(), self.config.clone()).into());
@@ -132,41 +168,84 @@ impl CpuCoreBuild {
             privi_u.clone(),
             xstatus,
             satp,
+            csr_regs_u.menvcfg.clone(),
+            virt_regs,
             self.config.clone(),
-        );
+        )
+        .with_pmp(csr_regs_u.pmp.clone());
+        #[cfg(feature = "rv_debug_trace")]
+        let mmu_u = mmu_u.with_ptw_trace(self.trace_sender.is_some());
         {
             let bus_u = mmu_u.caches.borrow_mut().bus.clone();
             let mut bus_u = bus_u.borrow_mut();
 
-            let mtime = bus_u.clint.instance.add_hart(xip.clone());
+            let mtime = match csr_regs_u.sstc.clone() {
+                Some(sstc) => bus_u.clint.instance.add_hart_sstc(xip.clone(), sstc),
+                None => bus_u.clint.instance.add_hart(xip.clone()),
+            };
             csr_regs_u.add_mtime(mtime);
-            // add plic context for core0 m-mode and s-mode
-            bus_u.plic.instance.add_context(xip.clone(), true);
-            if self.smode {
-                bus_u.plic.instance.add_context(xip, false);
+            match bus_u.aia.as_ref() {
+                // imsic files and csrs for m-mode and s-mode
+                Some(aia) => aia.add_hart(&mut csr_regs_u, xip, self.smode)?,
+                None => {
+                    // add plic context for core0 m-mode and s-mode
+                    bus_u.plic.instance.add_context(xip.clone(), true);
+                    if self.smode {
+                        bus_u.plic.instance.add_context(xip, false);
+                    }
+                }
             }
         }
 
-        CpuCore {
-            gpr: Gpr::new(),
+        let mut gpr = match self.config.is_enable_isa(b'e') {
+            true => Gpr::new_rve(),
+            false => Gpr::new(),
+        };
+        gpr.set_xlen(self.config.xlen());
+        let mut decode = InstDecode::new(self.config.clone());
+        decode.follow_misa(csr_regs_u.misa.clone());
+        Ok(CpuCore {
+            gpr,
+            fpr: Fpr::new(
+                csr_regs_u.xstatus.clone(),
+                self.config.is_enable_ext("zfinx"),
+            ),
+            vpr: Vpr::new(self.config.vlen(), csr_regs_u.xstatus.clone()),
             csr_regs: csr_regs_u,
             mmu: mmu_u,
-            decode: InstDecode::new(self.config.clone()),
+            decode,
             cache_system,
             pc: self.boot_pc,
             npc: self.boot_pc,
+            boot_pc: self.boot_pc,
             cur_priv: privi_u,
             cpu_state: CpuState::Stop,
+            pipeline: None,
+            energy: None,
             #[cfg(feature = "rv_debug_trace")]
             trace_sender: self.trace_sender.clone(),
             config: self.config.clone(),
             debug_state: DebugState::new(),
-        }
+            wfi_deadline: None,
+        })
     }
 }
 
+// the H extension state written by a trap to M-mode or HS-mode
+#[derive(Clone, Copy)]
+struct GuestTrap {
+    // xtval holds a guest virtual address
+    gva: bool,
+    // the guest physical address >> 2, written to htval or mtval2
+    tval2: u64,
+    // written to htinst or mtinst
+    tinst: u64,
+}
+
 pub struct CpuCore {
     pub gpr: Gpr,
+    pub fpr: Fpr,
+    pub vpr: Vpr,
     pub csr_regs: CsrRegs,
     pub mmu: Mmu,
     pub decode: InstDecode,
@@ -176,18 +255,39 @@ pub struct CpuCore {
     pub cur_priv: Rc<Cell<PrivilegeLevels>>,
     pub cpu_state: CpuState,
     pub debug_state: DebugState,
+    // the reset vector
+    boot_pc: u64,
+    // the cycle a sleeping WFI raises an illegal instruction at, see `Config::wfi_timeout`
+    wfi_deadline: Option<u64>,
     pub config: Rc<Config>,
+    // the optional in-order pipeline model, it only observes the execution
+    pub pipeline: Option<Pipeline>,
+    // the optional energy estimate, the bubbles come from `pipeline`
+    pub energy: Option<EnergyModel>,
     #[cfg(feature = "rv_debug_trace")]
     pub trace_sender: Option<crossbeam_channel::Sender<TraceType>>,
 }
 impl CpuCore {
-    fn reset(&mut self) {
-        self.gpr = Gpr::new();
+    // the pc of the reset, the hart starts there at once
+    pub fn set_boot_pc(&mut self, boot_pc: u64) {
+        self.boot_pc = boot_pc;
+        self.pc = boot_pc;
+        self.npc = boot_pc;
+    }
+
+    pub fn reset(&mut self) {
+        self.gpr.reset();
+        self.fpr.reset();
+        self.vpr.reset();
         self.csr_regs.reset();
-        self.npc = 0x8000_0000; //TODO: config
+        self.pc = self.boot_pc;
+        self.npc = self.boot_pc;
+        self.cur_priv.set(PrivilegeLevels::Machine);
         self.cpu_state = CpuState::Running;
         self.debug_state = DebugState::new();
+        self.wfi_deadline = None;
         self.decode.reset();
+        self.mmu.clear_tlb();
         let mut cache = self.cache_system.borrow_mut();
         cache.icache.clear();
         cache.dcache.clear();
@@ -198,7 +298,7 @@ impl CpuCore {
     fn fetch_from_mem(&mut self, addr: u64, size: u64) -> Result<u64, TrapType> {
         if check_aligned(addr, 4) {
             self.icahce_read(addr, 4)
-        } else if self.config.is_enable_isa(b'c') {
+        } else if self.csr_regs.misa_ext(b'c') {
             // Initialize data_bytes to store the bytes read from memory.
             let mut data_bytes = 0_u32.to_le_bytes();
             // Read two bytes at a time from memory and store them in data_bytes.
@@ -221,10 +321,43 @@ impl CpuCore {
         }
     }
     pub fn inst_fetch(&mut self) -> Result<u64, TrapType> {
+        // rv32 jump targets are sign extended from the gprs
+        if self.config.xlen() == 32 {
+            self.npc &= 0xffff_ffff;
+        }
         self.pc = self.npc;
 
         // assert!(self.pc % 2 == 0, "pc must be aligned to 2");
-        self.fetch_from_mem(self.pc, 4)
+        self.check_triggers(TriggerAccess::Fetch, self.pc, None)?;
+        let inst = self.fetch_from_mem(self.pc, 4)?;
+        self.check_triggers(TriggerAccess::Fetch, self.pc, Some(inst))?;
+        Ok(inst)
+    }
+
+    // Sdtrig, the address triggers are checked with data = None before the access
+    fn check_triggers(
+        &mut self,
+        access: TriggerAccess,
+        addr: u64,
+        data: Option<u64>,
+    ) -> Result<(), TrapType> {
+        if self.debug_state.debug_mode {
+            return Ok(());
+        }
+        let (privi, virt) = (self.cur_priv.get(), self.csr_regs.virt());
+        let action = self
+            .csr_regs
+            .triggers
+            .borrow_mut()
+            .hit(access, addr, data, privi, virt);
+        match action {
+            None => Ok(()),
+            Some(TriggerAction::Breakpoint) => Err(TrapType::Breakpoint(addr)),
+            Some(TriggerAction::DebugMode) => {
+                self.debug_state.trigger_flag = true;
+                Err(TrapType::Breakpoint(addr))
+            }
+        }
     }
 
     pub fn decode_and_excute(&mut self, inst: u32) -> Result<(), TrapType> {
@@ -252,9 +385,20 @@ impl CpuCore {
         let cycle = self.csr_regs.cycle.get();
         let instret = self.csr_regs.instret.get();
         info!("cycle:{},instret:{}", cycle, instret);
+        if let Some(pipeline) = &self.pipeline {
+            let stats = pipeline.stats();
+            info!(
+                "pipeline: cycles:{},cpi:{:.3},stalls:{},flushes:{},forwards:{}",
+                stats.cycles,
+                stats.cycles as f64 / stats.instructions.max(1) as f64,
+                stats.stalls,
+                stats.flushes,
+                stats.forwards
+            );
+        }
         // let x = self.cache_system.borrow();
         // self.decode.show_perf();
-        // self.mmu.show_perf();
+        self.mmu.show_perf();
     }
 
     fn set_pc(&mut self, pc: u64) {
@@ -271,9 +415,13 @@ impl CpuCore {
         // 1. pc changes to the value stored in dpc.
         self.set_pc(self.csr_regs.dpc.get());
         // 2. The current privilege mode and virtualization mode are changed to that specified by prv and v.
-        // NOT support virtualization now
-        self.cur_priv
-            .set(PrivilegeLevels::from_usize(dcsr.prv().into()).unwrap());
+        let new_priv = PrivilegeLevels::from_usize(dcsr.prv().into()).unwrap();
+        self.cur_priv.set(new_priv);
+        let virt = dcsr.v() && self.config.is_enable_isa(b'h');
+        self.csr_regs
+            .h
+            .virt
+            .set(virt && new_priv != PrivilegeLevels::Machine);
 
         // 3. When resuming from debug mode, clear mstatus.MPRV if the new privilege mode is less than M-mode
         if (self.cur_priv.get() as usize) < (PrivilegeLevels::Machine as usize) {
@@ -284,6 +432,7 @@ impl CpuCore {
 
         // 4. The debug mode is cleared.
         self.debug_state.debug_mode = false;
+        self.csr_regs.debug_mode = false;
         self.cpu_state = CpuState::Running;
 
         self.debug_state.resumeack = true;
@@ -303,19 +452,30 @@ impl CpuCore {
         // execute one instruction
         self.real_excute();
         // after execute one instruction, enter debug mode
-        self.enter_debug_mode(DebugCause::Step, self.npc)
+        if !self.debug_state.debug_mode {
+            self.enter_debug_mode(DebugCause::Step, self.npc)
+        }
     }
 
-    // fetch and execute one instruction
-    fn real_excute(&mut self) {
+    // fetch and execute one instruction. false: it did not retire, it
+    // trapped or hit a trigger
+    fn real_excute(&mut self) -> bool {
         assert!(!self.debug_state.debug_mode, "in debug mode");
         assert_eq!(self.cpu_state, CpuState::Running, "not in running state");
 
         // Increment the cycle counter
-        let cycle = self.csr_regs.cycle.get();
-        self.csr_regs.cycle.set(cycle + 1);
+        self.csr_regs.count_cycle(1);
+        let mode = (self.cur_priv.get(), self.csr_regs.virt());
+        self.hpm_count(HpmEvent::Cycle, mode);
+        let hpm_active = self
+            .csr_regs
+            .hpm
+            .as_ref()
+            .is_some_and(|x| x.active.get() != 0);
+        let misses = hpm_active.then(|| self.hpm_misses());
 
         let fetch_ret = self.inst_fetch();
+        let inst = fetch_ret.as_ref().map_or(0, |x| *x as u32);
         let exe_ret = match fetch_ret {
             // op ret
             Ok(inst_val) => {
@@ -325,29 +485,136 @@ impl CpuCore {
             // fetch fault
             Err(trap_type) => Err(trap_type),
         };
+        #[cfg(feature = "rv_debug_trace")]
+        if let Some(sender) = &self.trace_sender {
+            for record in self.mmu.take_ptw_records() {
+                sender.send(TraceType::Ptw(record)).unwrap();
+            }
+        }
 
-        if let Err(trap_type) = exe_ret {
+        let retired = if self.debug_state.trigger_flag {
+            self.debug_state.trigger_flag = false;
+            self.enter_debug_mode(DebugCause::Trigger, self.pc);
+            false
+        } else if let Err(trap_type) = exe_ret {
             self.handle_exceptions(trap_type);
+            false
         } else {
             // Increment the instruction counter
-            let instret = self.csr_regs.instret.get();
-            self.csr_regs.instret.set(instret + 1);
+            self.csr_regs.count_instret();
+            self.hpm_count(HpmEvent::Instret, mode);
+            if let Some(energy) = self.energy.as_mut() {
+                energy.record(inst);
+            }
+            true
+        };
+        if let Some(misses) = misses {
+            self.hpm_events(misses, inst, exe_ret.is_ok(), mode);
+        }
+        if self.pipeline.is_some() {
+            self.pipeline_retire(inst, exe_ret);
+        }
+        retired
+    }
+
+    // mode is the privilege and V of the instruction, before an mret or a trap
+    fn hpm_count(&self, event: HpmEvent, mode: (PrivilegeLevels, bool)) {
+        if let Some(hpm) = &self.csr_regs.hpm {
+            hpm.count(event, mode.0, mode.1, &self.csr_regs.xip);
         }
     }
 
+    // the icache, dcache and TLB misses so far
+    fn hpm_misses(&self) -> [u64; 3] {
+        let caches = self.cache_system.borrow();
+        [
+            caches.icache.misses(),
+            caches.dcache.misses(),
+            self.mmu.tlb_misses(),
+        ]
+    }
+
+    // the events of one instruction, `misses` are the ones before it
+    fn hpm_events(
+        &self,
+        misses: [u64; 3],
+        inst: u32,
+        retired: bool,
+        mode: (PrivilegeLevels, bool),
+    ) {
+        let Some(hpm) = &self.csr_regs.hpm else {
+            return;
+        };
+        let events = [
+            HpmEvent::IcacheMiss,
+            HpmEvent::DcacheMiss,
+            HpmEvent::TlbMiss,
+        ];
+        let now = self.hpm_misses();
+        for ((event, before), now) in events.iter().zip(misses).zip(now) {
+            hpm.count_n(*event, now - before, mode.0, mode.1, &self.csr_regs.xip);
+        }
+        if !retired {
+            return;
+        }
+        let event = match InstClass::classify(inst) {
+            InstClass::Load => Some(HpmEvent::Load),
+            InstClass::Store => Some(HpmEvent::Store),
+            InstClass::Branch => {
+                let len = if is_compressed_instruction(inst) {
+                    2
+                } else {
+                    4
+                };
+                (self.npc != self.pc.wrapping_add(len)).then_some(HpmEvent::BranchTaken)
+            }
+            _ => None,
+        };
+        if let Some(event) = event {
+            self.hpm_count(event, mode);
+        }
+    }
+
+    // after the trap handling, npc is the next instruction
+    fn pipeline_retire(&mut self, inst: u32, exe_ret: Result<(), TrapType>) {
+        let pipeline = self.pipeline.as_mut().unwrap();
+        let disasm = match pipeline.has_diagram() {
+            true => self
+                .decode
+                .fast_path(inst)
+                .map_or(String::new(), |x| disassemble(x, inst, self.pc)),
+            false => String::new(),
+        };
+        match exe_ret {
+            Ok(()) => pipeline.retire(self.pc, inst, self.npc, &disasm),
+            Err(trap_type) => pipeline.trap(self.pc, inst, &format!("{:?}", trap_type)),
+        };
+    }
+
+    // runs until `num` instructions are retired, so a hart always ends a
+    // round at the same instret (see `RVsim::run_once`). the traps, the WFI
+    // wakeups and the debug steps do not retire and are counted apart, a
+    // hart which only traps stops after `num` of them
     pub fn execute(&mut self, num: usize) {
-        for _ in 0..num {
+        let (mut retired, mut other) = (0, 0);
+        while retired < num && other < num {
             match self.cpu_state {
                 CpuState::Running => {
                     if self.debug_state.resetreq_signal {
                         self.debug_state.havereset = true;
                         self.reset();
+                        other += 1;
                     } else if self.debug_state.haltreq_signal {
-                        self.enter_debug_mode(DebugCause::HaltReq, self.npc)
+                        self.enter_debug_mode(DebugCause::HaltReq, self.npc);
+                        other += 1;
                     } else if self.debug_state.singlestep_flag {
                         self.single_step_proc();
+                        other += 1;
                     } else {
-                        self.real_excute();
+                        match self.real_excute() {
+                            true => retired += 1,
+                            false => other += 1,
+                        }
                         self.handle_interrupt();
                     }
                 }
@@ -355,20 +622,68 @@ impl CpuCore {
                     if self.debug_state.resumereq_flag {
                         self.resume_proc();
                     }
+                    other += 1;
+                }
+                CpuState::Wfi => {
+                    if !self.wfi_wakeup(num - retired) {
+                        break;
+                    }
+                    other += 1;
                 }
                 _ => break,
             };
         }
     }
 
+    // called by WFI, npc is the next instruction. `timeout`: the WFI raises an
+    // illegal instruction if it sleeps for `Config::wfi_timeout` cycles
+    pub fn wait_for_interrupt(&mut self, timeout: bool) {
+        if self.interrupt_pending() {
+            return;
+        }
+        self.cpu_state = CpuState::Wfi;
+        self.wfi_deadline = timeout.then(|| self.csr_regs.cycle.get() + self.config.wfi_timeout());
+    }
+
+    // an interrupt wakes up WFI even if it is disabled by xstatus.xIE or delegation
+    fn interrupt_pending(&self) -> bool {
+        u64::from(self.csr_regs.xip.get()) & u64::from(self.csr_regs.xie.get()) != 0
+    }
+
+    // the devices only change at the end of a round, so the sleeping hart
+    // skips the `cycles` left in the round at once. false: still sleeping
+    fn wfi_wakeup(&mut self, cycles: usize) -> bool {
+        if self.interrupt_pending()
+            || self.debug_state.haltreq_signal
+            || self.debug_state.resetreq_signal
+        {
+            self.cpu_state = CpuState::Running;
+            self.wfi_deadline = None;
+            self.handle_interrupt();
+            return true;
+        }
+        self.csr_regs.count_cycle(cycles as u64);
+        let cycle = self.csr_regs.cycle.get();
+        match self.wfi_deadline {
+            Some(deadline) if cycle >= deadline => {
+                self.cpu_state = CpuState::Running;
+                self.wfi_deadline = None;
+                self.handle_exceptions(TrapType::IllegalInstruction(MATCH_WFI.into()));
+                true
+            }
+            _ => false,
+        }
+    }
+
     // for difftest
     pub fn execute_as_ref(&mut self, num: usize) {
         for _ in 0..num {
             match self.cpu_state {
-                CpuState::Running => {
+                // the ref does not sleep, the interrupts come from the dut
+                CpuState::Running | CpuState::Wfi => {
+                    self.cpu_state = CpuState::Running;
                     // Increment the cycle counter
-                    let cycle = self.csr_regs.cycle.get();
-                    self.csr_regs.cycle.set(cycle + 1);
+                    self.csr_regs.count_cycle(1);
 
                     let fetch_ret = self.inst_fetch();
                     let exe_ret = match fetch_ret {
@@ -387,8 +702,7 @@ impl CpuCore {
                     }
                     // self.handle_interrupt();
                     // Increment the instruction counter
-                    let instret = self.csr_regs.instret.get();
-                    self.csr_regs.instret.set(instret + 1);
+                    self.csr_regs.count_instret();
                 }
                 _ => break,
             };
@@ -410,9 +724,11 @@ impl CpuCore {
 
     pub fn handle_exceptions(&mut self, trap_type: TrapType) {
         let medeleg = self.csr_regs.medeleg.get();
-        let mut mstatus = self.csr_regs.xstatus.get();
+        let hedeleg = self.csr_regs.h.hedeleg.get();
+        let exception_bit = 1_u64 << trap_type.get_exception_num();
 
-        let has_exception = u64::from(medeleg) & (1_u64 << trap_type.get_exception_num()) != 0;
+        let has_exception = u64::from(medeleg) & exception_bit != 0;
+        let trap_to_vs = self.csr_regs.virt() && hedeleg & exception_bit != 0;
 
         let trap_to_s_enable = self.cur_priv.get() <= PrivilegeLevels::Supervisor;
 
@@ -426,53 +742,135 @@ impl CpuCore {
             cause,
             tval
         );
+        #[cfg(feature = "rv_debug_trace")]
+        if let Some(sender) = &self.trace_sender {
+            sender
+                .send(TraceType::Trap(trap_type, self.pc, tval))
+                .unwrap();
+        };
 
-        // exception to S mode
-        if has_exception & trap_to_s_enable {
-            // When a trap is taken, SPP is set to 0 if the trap originated from user mode, or 1 otherwise.
-            mstatus.set_spp(!(self.cur_priv.get() == PrivilegeLevels::User));
-            // When a trap is taken into supervisor mode, SPIE is set to SIE
-            mstatus.set_spie(mstatus.sie());
-            // and SIE is set to 0
-            mstatus.set_sie(false);
-
-            self.csr_regs.xstatus.set(mstatus);
-            self.csr_regs.sepc.set(self.pc);
-            self.csr_regs.scause.set(cause.into());
-            self.csr_regs.stval.set(tval);
-            #[cfg(feature = "rv_debug_trace")]
-            if let Some(sender) = &self.trace_sender {
-                sender
-                    .send(TraceType::Trap(trap_type, self.pc, tval))
-                    .unwrap();
-            };
-
-            let stvec = self.csr_regs.stvec.get();
+        // the guest virtual address is written to xtval by the memory accesses of VS/VU-mode, HLV and HSV
+        let gva = trap_type.has_address_tval() && (self.csr_regs.virt() || self.mmu.virt_access());
+        // the guest page faults of the VS-stage PTE reads write the pseudoinstruction
+        // of a 32-bit or 64-bit read
+        let implicit = trap_type.get_tval2() != 0 && self.mmu.implicit_fault();
+        let tinst = match (implicit, self.config.xlen()) {
+            (false, _) => 0,
+            (true, 32) => 0x2000,
+            (true, _) => 0x3000,
+        };
+        let trap = GuestTrap {
+            gva,
+            tval2: trap_type.get_tval2(),
+            tinst,
+        };
 
-            self.npc = stvec.get_trap_pc(trap_type);
-            self.cur_priv.set(PrivilegeLevels::Supervisor);
+        // exception to VS mode
+        if has_exception && trap_to_s_enable && trap_to_vs {
+            self.trap_to_vs(trap_type, self.pc, tval);
+        }
+        // exception to S mode
+        else if has_exception && trap_to_s_enable {
+            self.trap_to_s(trap_type, self.pc, tval, trap);
         }
         // exception to M mode
         else {
-            mstatus.set_mpie(mstatus.mie());
-            mstatus.set_mie(false);
-            mstatus.set_mpp(self.cur_priv.get() as u8);
-
-            self.csr_regs.xstatus.set(mstatus);
-            self.csr_regs.mepc.set(self.pc);
-            self.csr_regs.mcause.set(cause.into());
-            self.csr_regs.mtval.set(tval);
-            #[cfg(feature = "rv_debug_trace")]
-            if let Some(sender) = &self.trace_sender {
-                sender
-                    .send(TraceType::Trap(trap_type, self.pc, tval))
-                    .unwrap();
-            };
+            self.trap_to_m(trap_type, self.pc, tval, trap);
+        }
+    }
 
-            let mtvec = self.csr_regs.mtvec.get();
-            self.npc = mtvec.get_trap_pc(trap_type);
-            self.cur_priv.set(PrivilegeLevels::Machine);
+    // the V=1 changes of mstatus.FS and mstatus.VS are also recorded in vsstatus
+    fn leave_virt(&mut self) {
+        if !self.csr_regs.virt() {
+            return;
+        }
+        let mstatus = self.csr_regs.xstatus.get();
+        let mut vsstatus = self.csr_regs.h.vsstatus.get();
+        if mstatus.fs() == 3 && vsstatus.fs() != 0 {
+            vsstatus.set_fs(3);
         }
+        if mstatus.vs() == 3 && vsstatus.vs() != 0 {
+            vsstatus.set_vs(3);
+        }
+        vsstatus.set_sd(vsstatus.fs() == 3 || vsstatus.vs() == 3 || vsstatus.xs() == 3);
+        self.csr_regs.h.vsstatus.set(vsstatus);
+        self.csr_regs.h.virt.set(false);
+    }
+
+    fn trap_to_m(&mut self, trap_type: TrapType, epc: u64, tval: u64, trap: GuestTrap) {
+        self.hpm_count(HpmEvent::Trap, (self.cur_priv.get(), self.csr_regs.virt()));
+        let mut mstatus = self.csr_regs.xstatus.get();
+        mstatus.set_mpie(mstatus.mie());
+        mstatus.set_mie(false);
+        mstatus.set_mpp(self.cur_priv.get() as u8);
+        if self.config.is_enable_isa(b'h') {
+            mstatus.set_mpv(self.csr_regs.virt());
+            mstatus.set_gva(trap.gva);
+            self.csr_regs.h.mtval2.set(trap.tval2);
+            self.csr_regs.h.mtinst.set(trap.tinst);
+        }
+
+        self.csr_regs.xstatus.set(mstatus);
+        self.csr_regs.mepc.set(epc);
+        self.csr_regs.mcause.set(trap_type.idx().into());
+        self.csr_regs.mtval.set(tval);
+        self.leave_virt();
+
+        let mtvec = self.csr_regs.mtvec.get();
+        self.npc = mtvec.get_trap_pc(trap_type);
+        self.cur_priv.set(PrivilegeLevels::Machine);
+    }
+
+    // the trap to S-mode, HS-mode when the H extension is enabled
+    fn trap_to_s(&mut self, trap_type: TrapType, epc: u64, tval: u64, trap: GuestTrap) {
+        self.hpm_count(HpmEvent::Trap, (self.cur_priv.get(), self.csr_regs.virt()));
+        let mut mstatus = self.csr_regs.xstatus.get();
+        // When a trap is taken, SPP is set to 0 if the trap originated from user mode, or 1 otherwise.
+        mstatus.set_spp(!(self.cur_priv.get() == PrivilegeLevels::User));
+        // When a trap is taken into supervisor mode, SPIE is set to SIE
+        mstatus.set_spie(mstatus.sie());
+        // and SIE is set to 0
+        mstatus.set_sie(false);
+        if self.config.is_enable_isa(b'h') {
+            let virt = self.csr_regs.virt();
+            let mut hstatus = self.csr_regs.h.hstatus.get();
+            hstatus.set_spv(virt);
+            if virt {
+                hstatus.set_spvp(self.cur_priv.get() == PrivilegeLevels::Supervisor);
+            }
+            hstatus.set_gva(trap.gva);
+            self.csr_regs.h.hstatus.set(hstatus);
+            self.csr_regs.h.htval.set(trap.tval2);
+            self.csr_regs.h.htinst.set(trap.tinst);
+        }
+
+        self.csr_regs.xstatus.set(mstatus);
+        self.csr_regs.sepc.set(epc);
+        self.csr_regs.scause.set(trap_type.idx().into());
+        self.csr_regs.stval.set(tval);
+        self.leave_virt();
+
+        let stvec = self.csr_regs.stvec.get();
+        self.npc = stvec.get_trap_pc(trap_type);
+        self.cur_priv.set(PrivilegeLevels::Supervisor);
+    }
+
+    // the trap to VS-mode uses the VS CSRs, V is kept
+    fn trap_to_vs(&mut self, trap_type: TrapType, epc: u64, tval: u64) {
+        self.hpm_count(HpmEvent::Trap, (self.cur_priv.get(), self.csr_regs.virt()));
+        let mut vsstatus = self.csr_regs.h.vsstatus.get();
+        vsstatus.set_spp(!(self.cur_priv.get() == PrivilegeLevels::User));
+        vsstatus.set_spie(vsstatus.sie());
+        vsstatus.set_sie(false);
+
+        self.csr_regs.h.vsstatus.set(vsstatus);
+        self.csr_regs.h.vsepc.set(epc);
+        self.csr_regs.h.vscause.set(trap_type.idx().into());
+        self.csr_regs.h.vstval.set(tval);
+
+        let vstvec = self.csr_regs.h.vstvec.get();
+        self.npc = vstvec.get_trap_pc(trap_type);
+        self.cur_priv.set(PrivilegeLevels::Supervisor);
     }
 
     pub fn handle_interrupt(&mut self) {
@@ -487,43 +885,48 @@ impl CpuCore {
             return;
         }
         // warn!("mip_mie_val:{:?}", XieIn::from(mip_mie_val));
-        let mut mstatus = self.csr_regs.xstatus.get();
+        let mstatus = self.csr_regs.xstatus.get();
 
-        let mideleg = self.csr_regs.mideleg.get();
+        let mideleg = u64::from(self.csr_regs.mideleg.get());
+        let hideleg = self.csr_regs.h.hideleg.get();
+        let virt = self.csr_regs.virt();
 
         let m_a1 = mstatus.mie() & (self.cur_priv.get() == PrivilegeLevels::Machine);
         let m_a2 = self.cur_priv.get() < PrivilegeLevels::Machine;
         let int_to_m_enable = m_a1 | m_a2;
-        let int_to_m_peding = mip_mie_val & !u64::from(mideleg);
+        let int_to_m_peding = mip_mie_val & !mideleg;
 
+        // HS-mode interrupts are always enabled in VS-mode and VU-mode
         let s_a1 = mstatus.sie() & (self.cur_priv.get() == PrivilegeLevels::Supervisor);
         let s_a2 = self.cur_priv.get() < PrivilegeLevels::Supervisor;
-        let int_to_s_enable = s_a1 | s_a2;
-        let int_to_s_peding = mip_mie_val & u64::from(mideleg);
+        let int_to_s_enable = s_a1 | s_a2 | virt;
+        let int_to_s_peding = mip_mie_val & mideleg & !hideleg;
+
+        let vsstatus: XstatusIn = self.csr_regs.h.vsstatus.get();
+        let vs_a1 = vsstatus.sie() & (self.cur_priv.get() == PrivilegeLevels::Supervisor);
+        let vs_a2 = self.cur_priv.get() < PrivilegeLevels::Supervisor;
+        let int_to_vs_enable = virt && (vs_a1 | vs_a2);
+        let int_to_vs_peding = mip_mie_val & mideleg & hideleg;
+
+        let no_guest_trap = GuestTrap {
+            gva: false,
+            tval2: 0,
+            tinst: 0,
+        };
 
         // handing interupt in M mode
         if int_to_m_enable && int_to_m_peding != 0 {
             let cause = XipIn::from(int_to_m_peding).get_priority_interupt();
 
             log::trace!("mmode int pc:{:x},cause:{:?}", self.pc, cause,);
-
-            mstatus.set_mpie(mstatus.mie());
-            mstatus.set_mpp(self.cur_priv.get() as u8);
-            mstatus.set_mie(false);
-
-            self.csr_regs.xstatus.set(mstatus);
-            self.csr_regs.mepc.set(self.npc);
-            self.csr_regs.mcause.set(cause.idx().into());
-            // self.csr_regs.mtval.set(0);
             #[cfg(feature = "rv_debug_trace")]
             if let Some(sender) = &self.trace_sender {
                 sender.send(TraceType::Trap(cause, self.pc, 0)).unwrap();
             };
 
-            let mtvec = self.csr_regs.mtvec.get();
             // todo! improve me
-            self.npc = mtvec.get_trap_pc(cause);
-            self.cur_priv.set(PrivilegeLevels::Machine);
+            let mtval = self.csr_regs.mtval.get();
+            self.trap_to_m(cause, self.npc, mtval, no_guest_trap);
         }
         // handing interupt in S mode
         // The sstatus register is a subset of the mstatus register.
@@ -534,26 +937,98 @@ impl CpuCore {
             let cause = XipIn::from(int_to_s_peding).get_priority_interupt();
 
             log::trace!("smode int pc:{:x},cause:{:?}", self.pc, cause,);
+            #[cfg(feature = "rv_debug_trace")]
+            if let Some(sender) = &self.trace_sender {
+                sender.send(TraceType::Trap(cause, self.pc, 0)).unwrap();
+            };
+
+            let stval = self.csr_regs.stval.get();
+            self.trap_to_s(cause, self.npc, stval, no_guest_trap);
+        }
+        // handing interupt in VS mode, the VS-level interrupts are seen as the S-level ones
+        else if int_to_vs_enable && int_to_vs_peding != 0 {
+            let cause = XipIn::from(int_to_vs_peding)
+                .get_priority_interupt()
+                .to_vs_interrupt();
 
-            // When a trap is taken, SPP is set to 0 if the trap originated from user mode, or 1 otherwise.
-            mstatus.set_spp(!(self.cur_priv.get() == PrivilegeLevels::User));
-            // When a trap is taken into supervisor mode, SPIE is set to SIE
-            mstatus.set_spie(mstatus.sie());
-            // and SIE is set to 0
-            mstatus.set_sie(false);
+            log::trace!("vsmode int pc:{:x},cause:{:?}", self.pc, cause,);
             #[cfg(feature = "rv_debug_trace")]
             if let Some(sender) = &self.trace_sender {
                 sender.send(TraceType::Trap(cause, self.pc, 0)).unwrap();
             };
 
-            self.csr_regs.xstatus.set(mstatus);
-            self.csr_regs.sepc.set(self.npc);
-            self.csr_regs.scause.set(cause.idx().into());
+            let vstval = self.csr_regs.h.vstval.get();
+            self.trap_to_vs(cause, self.npc, vstval);
+        }
+    }
+
+    // a load or store the bus can not serve is an access fault, in relaxed
+    // mode it reads 0 or is dropped
+    fn bus_error(&self, paddr: u64, access_type: AccessType) -> Result<u64, TrapType> {
+        if self.config.compliance() == Compliance::Relaxed {
+            warn!("unmapped access {access_type:?} at {paddr:#x} ignored, relaxed mode");
+            return Ok(0);
+        }
+        Err(access_type.throw_access_exception())
+    }
+
+    // `Config::misaligned` splits the misaligned loads and stores, the AMOs still trap
+    fn split_misaligned(&self, addr: u64, len: usize, access_type: &AccessType) -> bool {
+        let load_store = matches!(access_type, AccessType::Load(_) | AccessType::Store(_));
+        self.config.misaligned() && load_store && !check_aligned(addr, len)
+    }
 
-            let stvec = self.csr_regs.stvec.get();
-            self.cur_priv.set(PrivilegeLevels::Supervisor);
-            self.npc = stvec.get_trap_pc(cause);
+    // one byte at a time, a fault reports the address of the first faulting byte
+    fn read_split(
+        &mut self,
+        addr: u64,
+        len: usize,
+        access_type: AccessType,
+        virt: Option<bool>,
+    ) -> Result<u64, TrapType> {
+        let mut data = 0;
+        for i in 0..len as u64 {
+            let byte_addr = addr.wrapping_add(i);
+            let byte_type = access_type.with_addr(byte_addr);
+            let byte = match virt {
+                Some(hlvx) => self.read_virt(byte_addr, 1, byte_type, hlvx)?,
+                None => self.read_mem(byte_addr, 1, byte_type)?,
+            };
+            data |= byte << (i * 8);
         }
+        Ok(data)
+    }
+
+    // both pages are translated first, so a fault on the second one writes nothing
+    fn write_split(
+        &mut self,
+        addr: u64,
+        data: u64,
+        len: usize,
+        access_type: AccessType,
+        virt: bool,
+    ) -> Result<u64, TrapType> {
+        let last = addr.wrapping_add(len as u64 - 1);
+        let next_page = (addr | 0xfff).wrapping_add(1);
+        let pages = [Some(addr), (next_page <= last).then_some(next_page)];
+        for byte_addr in pages.into_iter().flatten() {
+            self.mmu
+                .update_access_type(&access_type.with_addr(byte_addr));
+            if virt {
+                self.mmu.set_hlv(false);
+            }
+            self.mmu.translate(byte_addr, 1)?;
+        }
+        for i in 0..len as u64 {
+            let byte_addr = addr.wrapping_add(i);
+            let byte_type = access_type.with_addr(byte_addr);
+            let byte = (data >> (i * 8)) & 0xff;
+            match virt {
+                true => self.write_virt(byte_addr, byte, 1, byte_type)?,
+                false => self.write_mem(byte_addr, byte, 1, byte_type)?,
+            };
+        }
+        Ok(0)
     }
 
     pub fn read(
@@ -562,12 +1037,29 @@ impl CpuCore {
         len: usize,
         access_type: AccessType,
     ) -> Result<u64, TrapType> {
+        self.check_triggers(TriggerAccess::Load, addr, None)?;
+        if let AccessType::Amo(_) = access_type {
+            self.check_triggers(TriggerAccess::Store, addr, None)?;
+        }
+        let data = self.read_mem(addr, len, access_type)?;
+        self.check_triggers(TriggerAccess::Load, addr, Some(data))?;
+        Ok(data)
+    }
+
+    fn read_mem(
+        &mut self,
+        addr: u64,
+        len: usize,
+        access_type: AccessType,
+    ) -> Result<u64, TrapType> {
+        if self.split_misaligned(addr, len, &access_type) {
+            return self.read_split(addr, len, access_type, None);
+        }
         self.mmu.update_access_type(&access_type);
         let paddr = self.mmu.translate(addr, len)?;
-        match self.cache_system.borrow_mut().dcache.read(paddr, len) {
-            Ok(data) => Ok(data),
-            Err(_err) => Err(access_type.throw_access_exception()),
-        }
+        let mem_type = self.mmu.mem_type();
+        let ret = self.cache_system.borrow_mut().read(paddr, len, mem_type);
+        ret.or_else(|_| self.bus_error(paddr, access_type))
     }
 
     pub fn icahce_read(&mut self, addr: u64, len: usize) -> Result<u64, TrapType> {
@@ -582,6 +1074,7 @@ impl CpuCore {
         }
     }
 
+    // the address of an amo is checked by the read
     pub fn write(
         &mut self,
         addr: u64,
@@ -589,19 +1082,98 @@ impl CpuCore {
         len: usize,
         access_type: AccessType,
     ) -> Result<u64, TrapType> {
+        if !matches!(access_type, AccessType::Amo(_)) {
+            self.check_triggers(TriggerAccess::Store, addr, None)?;
+        }
+        self.check_triggers(TriggerAccess::Store, addr, Some(data))?;
+        self.write_mem(addr, data, len, access_type)
+    }
+
+    fn write_mem(
+        &mut self,
+        addr: u64,
+        data: u64,
+        len: usize,
+        access_type: AccessType,
+    ) -> Result<u64, TrapType> {
+        if self.split_misaligned(addr, len, &access_type) {
+            return self.write_split(addr, data, len, access_type, false);
+        }
         self.mmu.update_access_type(&access_type);
         let paddr = self.mmu.translate(addr, len)?;
-        match self
+        let mem_type = self.mmu.mem_type();
+        let ret = self
             .cache_system
             .borrow_mut()
-            .dcache
-            .write(paddr, data, len)
-        {
+            .write(paddr, data, len, mem_type);
+        ret.or_else(|_| self.bus_error(paddr, access_type))
+    }
+
+    // HLV and HLVX, translated as though V=1 and the privilege were hstatus.SPVP
+    pub fn read_virt(
+        &mut self,
+        addr: u64,
+        len: usize,
+        access_type: AccessType,
+        hlvx: bool,
+    ) -> Result<u64, TrapType> {
+        if self.split_misaligned(addr, len, &access_type) {
+            return self.read_split(addr, len, access_type, Some(hlvx));
+        }
+        self.mmu.update_access_type(&access_type);
+        self.mmu.set_hlv(hlvx);
+        let paddr = self.mmu.translate(addr, len)?;
+        let mem_type = self.mmu.mem_type();
+        let ret = self.cache_system.borrow_mut().read(paddr, len, mem_type);
+        ret.or_else(|_| self.bus_error(paddr, access_type))
+    }
+
+    // HSV
+    pub fn write_virt(
+        &mut self,
+        addr: u64,
+        data: u64,
+        len: usize,
+        access_type: AccessType,
+    ) -> Result<u64, TrapType> {
+        if self.split_misaligned(addr, len, &access_type) {
+            return self.write_split(addr, data, len, access_type, true);
+        }
+        self.mmu.update_access_type(&access_type);
+        self.mmu.set_hlv(false);
+        let paddr = self.mmu.translate(addr, len)?;
+        let mem_type = self.mmu.mem_type();
+        let ret = self
+            .cache_system
+            .borrow_mut()
+            .write(paddr, data, len, mem_type);
+        ret.or_else(|_| self.bus_error(paddr, access_type))
+    }
+
+    // 128-bit access, only used by AMOCAS.Q
+    pub fn read128(&mut self, addr: u64, access_type: AccessType) -> Result<u128, TrapType> {
+        self.mmu.update_access_type(&access_type);
+        let paddr = self.mmu.translate(addr, 16)?;
+        match self.cache_system.borrow_mut().dcache.read128(paddr) {
             Ok(data) => Ok(data),
             Err(_err) => Err(access_type.throw_access_exception()),
         }
     }
 
+    pub fn write128(
+        &mut self,
+        addr: u64,
+        data: u128,
+        access_type: AccessType,
+    ) -> Result<(), TrapType> {
+        self.mmu.update_access_type(&access_type);
+        let paddr = self.mmu.translate(addr, 16)?;
+        match self.cache_system.borrow_mut().dcache.write128(paddr, data) {
+            Ok(()) => Ok(()),
+            Err(_err) => Err(access_type.throw_access_exception()),
+        }
+    }
+
     pub fn lr_sc_reservation_set(&mut self, addr: u64) {
         self.mmu
             .caches
@@ -620,6 +1192,9 @@ impl CpuCore {
             .lr_sc_set
             .check_and_clear(addr)
     }
+    pub fn lr_sc_reservation_valid(&self) -> bool {
+        self.mmu.caches.borrow().bus.borrow().lr_sc_set.is_valid()
+    }
     // pub fn lr_sc_reservation_clear(&mut self) {
     //     self.lr_sc_set.lock().unwrap().clear();
     // }
@@ -634,7 +1209,8 @@ impl CpuCore {
         dcsr.set_cause(cause as u8);
         // 2. dcsr->prv and dcsr->v are set to reflect current privilege mode.
         dcsr.set_prv(self.cur_priv.get() as u8);
-        dcsr.set_v(false); // do not support virtualnization
+        dcsr.set_v(self.csr_regs.virt());
+        self.leave_virt();
 
         self.csr_regs.dcsr.set(dcsr);
 
@@ -643,6 +1219,7 @@ impl CpuCore {
         self.csr_regs.dpc.set(pc);
         // 4. The hart enters Debug Mode.
         self.debug_state.debug_mode = true;
+        self.csr_regs.debug_mode = true;
         self.cpu_state = CpuState::Haltd;
 
         // 5. debug mode is always performed in M mode
@@ -704,12 +1281,29 @@ impl DebugModuleSlave for CpuCore {
         debug!("[DebugModuleSlave] write gpr[{}]:{:x}", regno, value);
     }
 
+    fn read_fpr(&mut self, regno: usize) -> Option<u64> {
+        let val = self.fpr.read(regno as u64);
+        debug!("[DebugModuleSlave] read fpr[{}]:{:x}", regno, val);
+        self.config.is_enable_isa(b'f').then_some(val)
+    }
+
+    fn write_fpr(&mut self, regno: usize, value: u64) -> Option<()> {
+        if !self.config.is_enable_isa(b'f') {
+            return None;
+        }
+        self.fpr.write(regno as u64, value);
+        debug!("[DebugModuleSlave] write fpr[{}]:{:x}", regno, value);
+        Some(())
+    }
+
     fn read_memory(&mut self, address: u64, length: usize) -> Option<u64> {
         let paddr = address;
-        let result = match self.cache_system.borrow_mut().dcache.read(paddr, length) {
-            Ok(data) => Some(data),
-            Err(_err) => None,
-        };
+        let result = self
+            .cache_system
+            .borrow_mut()
+            .dcache
+            .read(paddr, length)
+            .ok();
         debug!(
             "[DebugModuleSlave] read memory address:{:x},length:{},value:{:x?}",
             address, length, result
@@ -719,15 +1313,12 @@ impl DebugModuleSlave for CpuCore {
 
     fn write_memory(&mut self, address: u64, length: usize, value: u64) -> Option<u64> {
         let paddr = address;
-        let result = match self
+        let result = self
             .cache_system
             .borrow_mut()
             .dcache
             .write(paddr, value, length)
-        {
-            Ok(data) => Some(data),
-            Err(_err) => None,
-        };
+            .ok();
         debug!(
             "[DebugModuleSlave] write memory address:{:x},length:{},value:{:x?}",
             address, length, value
@@ -777,3 +1368,75 @@ impl DebugModuleSlave for CpuCore {
         self.debug_state.havereset = false;
     }
 }
+
+#[cfg(test)]
+mod test_cpu_core {
+    use crate::{
+        config::Config,
+        device::device_trait::MEM_BASE,
+        rv64core::{
+            csr_regs_define::{HpmEvent, MhpmeventIn},
+            inst::inst_base::{PrivilegeLevels, CSR_MHPMCOUNTER3, CSR_MHPMEVENT3},
+            test_cpu::{test_bus, test_cpu_on},
+        },
+    };
+
+    use super::CpuState;
+
+    #[test]
+    fn hpm_event_test() {
+        let bus = test_bus(0x1000);
+        // beq zero,zero,8; nop; bne zero,zero,8; sd zero,0(a0); ld a1,0(a0); ecall; nop
+        let program = [
+            0x00000463_u32,
+            0x00000013,
+            0x00001463,
+            0x00053023,
+            0x00053583,
+            0x00000073,
+            0x00000013,
+        ];
+        for (i, inst) in program.iter().enumerate() {
+            bus.borrow_mut()
+                .write(MEM_BASE + i as u64 * 4, *inst as u64, 4)
+                .unwrap();
+        }
+
+        let mut config = Config::new();
+        config.set_isa("rv64imac_sscofpmf");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        config.set_dcache_size(64);
+        let mut cpu = test_cpu_on(&bus, config);
+        cpu.cpu_state = CpuState::Running;
+        cpu.npc = MEM_BASE;
+        cpu.gpr.write(10, MEM_BASE + 0x800);
+        cpu.csr_regs.mtvec.set((MEM_BASE + 0x18).into());
+        let m_mode = PrivilegeLevels::Machine;
+        let events = [
+            HpmEvent::BranchTaken,
+            HpmEvent::Load,
+            HpmEvent::Store,
+            HpmEvent::Trap,
+            HpmEvent::DcacheMiss,
+            HpmEvent::Instret,
+        ];
+        for (i, event) in events.iter().enumerate() {
+            let mhpmevent = MhpmeventIn::new().with_event(*event as u64);
+            cpu.csr_regs
+                .write(CSR_MHPMEVENT3 as u64 + i as u64, mhpmevent.into(), m_mode)
+                .unwrap();
+        }
+        // the ecall is not one of the 5
+        cpu.execute(5);
+        assert_eq!(cpu.pc, MEM_BASE + 0x18);
+
+        let mut counter = |i: u64| {
+            cpu.csr_regs
+                .read(CSR_MHPMCOUNTER3 as u64 + i, m_mode)
+                .unwrap()
+        };
+        // the ecall traps and does not retire, the store misses the dcache
+        assert_eq!([0, 1, 2, 3, 4, 5].map(&mut counter), [1, 1, 1, 1, 1, 5]);
+    }
+}
@@ -2,34 +2,243 @@ use core::cell::Cell;
 
 use alloc::rc::Rc;
 use hashbrown::HashMap;
+use log::warn;
 
 use crate::{
-    config::Config,
+    config::{Compliance, Config},
+    error::{RvEmuError, RvEmuResult},
     rv64core::csr_regs_define::{
-        CommonCSR, Counter, Csr, CsrEnum, Medeleg, MedelegIn, Mideleg, MidelegIn, Misa,
-        ReadOnlyCSR, Satp, SatpIn, Xcause, XcauseIn, Xie, XieIn, Xip, XipIn, Xstatus, XstatusIn,
-        Xtvec, XtvecIn,
+        CommonCSR, Counter, Csr, CsrAccess, CsrEnum, CustomCSR, Envcfg, EnvcfgIn, Fcsr, FcsrIn,
+        Medeleg, MedelegIn, Mideleg, MidelegIn, Misa, MisaIn, ReadOnlyCSR, Satp, SatpIn, Seed,
+        TriggerCsr, Vcsr, Xcause, XcauseIn, Xie, XieIn, Xip, XipIn, Xstatus, XstatusIn, Xtvec,
+        XtvecIn,
     },
     rv64core::inst::inst_base::{
-        AccessType, PrivilegeLevels, CSR_CYCLE, CSR_INSTRET, CSR_MARCHID, CSR_MCAUSE,
-        CSR_MCOUNTEREN, CSR_MCYCLE, CSR_MEDELEG, CSR_MEPC, CSR_MHARTID, CSR_MIDELEG, CSR_MIE,
-        CSR_MIMPID, CSR_MINSTRET, CSR_MIP, CSR_MISA, CSR_MSCRATCH, CSR_MSTATUS, CSR_MTVAL,
-        CSR_MTVEC, CSR_MVENDORID, CSR_SATP, CSR_SCAUSE, CSR_SCOUNTEREN, CSR_SEPC, CSR_SIE, CSR_SIP,
-        CSR_SSCRATCH, CSR_SSTATUS, CSR_STVAL, CSR_STVEC, CSR_TIME, CSR_TSELECT, MASK_ALL,
+        AccessType, PrivilegeLevels, CSR_CYCLE, CSR_FCSR, CSR_FFLAGS, CSR_FRM, CSR_INSTRET,
+        CSR_MARCHID, CSR_MCAUSE, CSR_MCOUNTEREN, CSR_MCOUNTINHIBIT, CSR_MCYCLE, CSR_MEDELEG,
+        CSR_MENVCFG, CSR_MEPC, CSR_MHARTID, CSR_MIDELEG, CSR_MIE, CSR_MIMPID, CSR_MINSTRET,
+        CSR_MIP, CSR_MISA, CSR_MSCRATCH, CSR_MSTATUS, CSR_MTVAL, CSR_MTVEC, CSR_MVENDORID,
+        CSR_SATP, CSR_SCAUSE, CSR_SCOUNTEREN, CSR_SEED, CSR_SENVCFG, CSR_SEPC, CSR_SIE, CSR_SIP,
+        CSR_SSCRATCH, CSR_SSTATUS, CSR_STIMECMP, CSR_STVAL, CSR_STVEC, CSR_TIME, CSR_TSELECT,
+        CSR_VCSR, CSR_VL, CSR_VLENB, CSR_VSTART, CSR_VTYPE, CSR_VXRM, CSR_VXSAT, MASK_ALL,
     },
     rv64core::traptype::TrapType,
-    tools::RcCell,
+    rv64core::vector::vtype::VtypeIn,
+    tools::{RcCell, RcRefCell},
 };
 
 use super::{
-    csr_regs_define::{Dcsr, DcsrIn},
-    inst::inst_base::{CSR_DCSR, CSR_DPC, CSR_DSCRATCH0, CSR_DSCRATCH1},
+    csr_regs_define::{
+        Dcsr, DcsrIn, Hgatp, HgatpIn, HpmEvent, Hstatus, HstatusIn, MaskedCSR, Mhpmevent,
+        MhpmeventIn, PMPaddr, PMPcfg, Scountovf, Stateen, Vsie, Vsip, HPM_COUNTERS, SGEI_INTERRUPT,
+        STATEEN_ENVCFG, STATEEN_FCSR, STATEEN_SE, VS_INTERRUPTS,
+    },
+    inst::inst_base::{
+        CSR_CYCLEH, CSR_DCSR, CSR_DPC, CSR_DSCRATCH0, CSR_DSCRATCH1, CSR_HCOUNTEREN, CSR_HEDELEG,
+        CSR_HENVCFG, CSR_HGATP, CSR_HGEIE, CSR_HGEIP, CSR_HIDELEG, CSR_HIE, CSR_HIP,
+        CSR_HPMCOUNTER3, CSR_HPMCOUNTER31, CSR_HPMCOUNTER31H, CSR_HSTATEEN0, CSR_HSTATEEN3,
+        CSR_HSTATUS, CSR_HTIMEDELTA, CSR_HTINST, CSR_HTVAL, CSR_HVIP, CSR_MCYCLEH, CSR_MENVCFGH,
+        CSR_MHPMCOUNTER3, CSR_MHPMCOUNTER31H, CSR_MHPMEVENT3, CSR_MHPMEVENT31H, CSR_MHPMEVENT3H,
+        CSR_MSTATEEN0, CSR_MSTATEEN0H, CSR_MSTATEEN3H, CSR_MSTATUSH, CSR_MTINST, CSR_MTVAL2,
+        CSR_PMPADDR0, CSR_PMPCFG0, CSR_PMPCFG15, CSR_SCOUNTOVF, CSR_SSTATEEN0, CSR_SSTATEEN3,
+        CSR_STIMECMPH, CSR_TDATA1, CSR_TDATA2, CSR_TDATA3, CSR_TINFO, CSR_VSATP, CSR_VSCAUSE,
+        CSR_VSEPC, CSR_VSIE, CSR_VSIP, CSR_VSSCRATCH, CSR_VSSTATUS, CSR_VSTIMECMP, CSR_VSTVAL,
+        CSR_VSTVEC,
+    },
+    mmu::pmp::{Pmp, PMP_MAX_ENTRIES},
+    trigger::{Triggers, TDATA1_DMODE},
 };
 
+// hedeleg: the exceptions which can be delegated to VS-mode,
+// not the ecalls from HS/VS/M-mode, the guest page faults and virtual instruction
+const HEDELEG_MASK: u64 = 0xb1ff;
+
+// Sstc: STIP is mtime >= stimecmp while menvcfg.STCE is set, VSTIP is
+// time + htimedelta >= vstimecmp while henvcfg.STCE is also set.
+// it is evaluated by the CLINT on every tick and after the CSR writes
+#[derive(Clone)]
+pub struct SstcTimer {
+    pub stimecmp: RcCell<u64>,
+    pub vstimecmp: RcCell<u64>,
+    pub menvcfg: RcCell<EnvcfgIn>,
+    pub henvcfg: RcCell<EnvcfgIn>,
+    pub htimedelta: RcCell<u64>,
+}
+
+impl SstcTimer {
+    pub fn update(&self, mtime: u64, xip: &RcCell<XipIn>) {
+        if !self.menvcfg.get().stce() {
+            return;
+        }
+        let mut ip = xip.get();
+        ip.set_stip(mtime >= self.stimecmp.get());
+        // the VSTIP of hvip is overridden while the guest timer is on
+        if self.henvcfg.get().stce() {
+            let time = mtime.wrapping_add(self.htimedelta.get());
+            ip.set_vstip(time >= self.vstimecmp.get());
+        }
+        xip.set(ip);
+    }
+}
+
+// Sscofpmf: mhpmcounter3-31 count the event selected by their mhpmevent,
+// unless the current mode is inhibited. the carry out of a counter sets OF
+// and raises LCOFIP, only when OF was clear
+pub struct HpmCounters {
+    pub counters: [RcCell<u64>; HPM_COUNTERS],
+    pub events: [RcCell<MhpmeventIn>; HPM_COUNTERS],
+    pub active: RcCell<u32>,
+    // bits 3-31 of mcountinhibit
+    pub inhibit: RcCell<u64>,
+}
+
+impl HpmCounters {
+    fn new(inhibit: RcCell<u64>) -> Self {
+        Self {
+            counters: core::array::from_fn(|_| RcCell::new(0.into())),
+            events: core::array::from_fn(|_| RcCell::new(MhpmeventIn::new().into())),
+            active: RcCell::new(0.into()),
+            inhibit,
+        }
+    }
+
+    fn reset(&self) {
+        self.counters.iter().for_each(|x| x.set(0));
+        self.events.iter().for_each(|x| x.set(MhpmeventIn::new()));
+        self.active.set(0);
+    }
+
+    pub fn count(&self, event: HpmEvent, privi: PrivilegeLevels, virt: bool, xip: &RcCell<XipIn>) {
+        self.count_n(event, 1, privi, virt, xip);
+    }
+
+    pub fn count_n(
+        &self,
+        event: HpmEvent,
+        n: u64,
+        privi: PrivilegeLevels,
+        virt: bool,
+        xip: &RcCell<XipIn>,
+    ) {
+        if n == 0 {
+            return;
+        }
+        let mut active = self.active.get() & !(self.inhibit.get() >> 3) as u32;
+        while active != 0 {
+            let idx = active.trailing_zeros() as usize;
+            active &= active - 1;
+            let mut mhpmevent = self.events[idx].get();
+            let inhibit = match (privi, virt) {
+                (PrivilegeLevels::Machine, _) => mhpmevent.minh(),
+                (PrivilegeLevels::Supervisor, false) => mhpmevent.sinh(),
+                (PrivilegeLevels::User, false) => mhpmevent.uinh(),
+                (PrivilegeLevels::Supervisor, true) => mhpmevent.vsinh(),
+                (PrivilegeLevels::User, true) => mhpmevent.vuinh(),
+            };
+            if inhibit || mhpmevent.event() != event as u64 {
+                continue;
+            }
+            let old = self.counters[idx].get();
+            let counter = old.wrapping_add(n);
+            self.counters[idx].set(counter);
+            if counter < old && !mhpmevent.of() {
+                mhpmevent.set_of(true);
+                self.events[idx].set(mhpmevent);
+                let mut ip = xip.get();
+                ip.set_lcofip(true);
+                xip.set(ip);
+            }
+        }
+    }
+}
+
+// H extension state, V is the virtualization mode
+pub struct HypervisorRegs {
+    pub virt: RcCell<bool>,
+    pub hstatus: RcCell<HstatusIn>,
+    pub hedeleg: RcCell<u64>,
+    pub hideleg: RcCell<u64>,
+    pub htval: RcCell<u64>,
+    pub htinst: RcCell<u64>,
+    pub hgatp: RcCell<HgatpIn>,
+    pub htimedelta: RcCell<u64>,
+    pub mtval2: RcCell<u64>,
+    pub mtinst: RcCell<u64>,
+    // the VS-mode copies of the supervisor CSRs
+    pub vsstatus: RcCell<XstatusIn>,
+    pub vstvec: RcCell<XtvecIn>,
+    pub vsepc: RcCell<u64>,
+    pub vscause: RcCell<XcauseIn>,
+    pub vstval: RcCell<u64>,
+    pub vsatp: RcCell<SatpIn>,
+    pub henvcfg: RcCell<EnvcfgIn>,
+    pub vstimecmp: RcCell<u64>,
+    pub hstateen: [RcCell<u64>; 4],
+    pub hcounteren: RcCell<u64>,
+}
+
+impl HypervisorRegs {
+    fn new() -> Self {
+        Self {
+            virt: RcCell::new(false.into()),
+            hstatus: RcCell::new(HstatusIn::new().with_vsxl(2).into()),
+            hedeleg: RcCell::new(0.into()),
+            hideleg: RcCell::new(0.into()),
+            htval: RcCell::new(0.into()),
+            htinst: RcCell::new(0.into()),
+            hgatp: RcCell::new(HgatpIn::new().into()),
+            htimedelta: RcCell::new(0.into()),
+            mtval2: RcCell::new(0.into()),
+            mtinst: RcCell::new(0.into()),
+            vsstatus: RcCell::new(XstatusIn::new().with_uxl(2).into()),
+            vstvec: RcCell::new(XtvecIn::new().into()),
+            vsepc: RcCell::new(0.into()),
+            vscause: RcCell::new(XcauseIn::new().into()),
+            vstval: RcCell::new(0.into()),
+            vsatp: RcCell::new(SatpIn::new().into()),
+            henvcfg: RcCell::new(EnvcfgIn::new().into()),
+            vstimecmp: RcCell::new(u64::MAX.into()),
+            hstateen: core::array::from_fn(|_| RcCell::new(0.into())),
+            hcounteren: RcCell::new(0.into()),
+        }
+    }
+
+    fn reset(&self) {
+        self.virt.set(false);
+        self.hstatus.set(HstatusIn::new().with_vsxl(2));
+        self.hedeleg.set(0);
+        self.hideleg.set(0);
+        self.htval.set(0);
+        self.htinst.set(0);
+        self.hgatp.set(HgatpIn::new());
+        self.htimedelta.set(0);
+        self.mtval2.set(0);
+        self.mtinst.set(0);
+        self.vsstatus.set(XstatusIn::new().with_uxl(2));
+        self.vstvec.set(XtvecIn::new());
+        self.vsepc.set(0);
+        self.vscause.set(XcauseIn::new());
+        self.vstval.set(0);
+        self.vsatp.set(SatpIn::new());
+        self.henvcfg.set(EnvcfgIn::new());
+        self.vstimecmp.set(u64::MAX);
+        self.hstateen.iter().for_each(|x| x.set(0));
+        self.hcounteren.set(0);
+    }
+}
+
 pub struct CsrRegs {
     config: Rc<Config>,
     pub csr_map: HashMap<u64, CsrEnum>,
     pub cur_priv: PrivilegeLevels,
+    // set by the core on entering and leaving Debug Mode
+    pub debug_mode: bool,
+    // the extensions turned off in misa are illegal
+    pub misa: RcCell<MisaIn>,
+    // shared with the mmu
+    pub pmp: RcRefCell<Pmp>,
+    // Sdtrig, checked by the core on the fetches, loads and stores
+    pub triggers: RcRefCell<Triggers>,
     pub xstatus: RcCell<XstatusIn>,
     pub xip: RcCell<XipIn>,
     pub xie: RcCell<XieIn>,
@@ -46,24 +255,55 @@ pub struct CsrRegs {
     pub stval: RcCell<u64>,
     pub cycle: RcCell<u64>,
     pub instret: RcCell<u64>,
+    pub mcounteren: RcCell<u64>,
+    pub scounteren: RcCell<u64>,
+    pub mcountinhibit: RcCell<u64>,
+    pub fcsr: RcCell<FcsrIn>,
+    pub menvcfg: RcCell<EnvcfgIn>,
+    pub senvcfg: RcCell<EnvcfgIn>,
+    // Smstateen
+    pub mstateen: [RcCell<u64>; 4],
+    pub sstateen: [RcCell<u64>; 4],
+    // vector
+    pub vstart: RcCell<u64>,
+    pub vcsr: RcCell<u64>,
+    pub vl: RcCell<u64>,
+    pub vtype: RcCell<u64>,
 
     // debug mode
     pub dcsr: RcCell<DcsrIn>,
     pub dpc: RcCell<u64>,
+    // H extension
+    pub h: HypervisorRegs,
+    // Sstc, shared with the CLINT
+    pub sstc: Option<SstcTimer>,
+    pub stimecmp: RcCell<u64>,
+    // Sscofpmf
+    pub hpm: Option<HpmCounters>,
+    mtime: Option<RcCell<u64>>,
+    mideleg_ro_one: u64,
 }
 
 impl CsrRegs {
     pub fn reset(&mut self) {
+        self.misa.set(misa_reset(&self.config));
+        self.pmp.borrow_mut().reset();
+        self.triggers.borrow_mut().reset();
         let mut mstatus_val = XstatusIn::new().with_mpp(PrivilegeLevels::Machine as u8);
 
         mstatus_val.set_mbe(false);
         mstatus_val.set_sbe(false);
         mstatus_val.set_ube(false);
+        // rv32 has no SXL and UXL
+        let xl = match self.config.xlen() {
+            32 => 0,
+            _ => 2, // 64
+        };
         if self.config.s_mode() {
-            mstatus_val.set_sxl(2) // 64
+            mstatus_val.set_sxl(xl)
         }
         if self.config.u_mode() {
-            mstatus_val.set_uxl(2); // 64
+            mstatus_val.set_uxl(xl);
             mstatus_val.set_mprv(false);
         }
         self.xstatus.set(mstatus_val);
@@ -75,7 +315,7 @@ impl CsrRegs {
         self.mcause.set(XcauseIn::new());
         self.scause.set(XcauseIn::new());
         self.medeleg.set(MedelegIn::new());
-        self.mideleg.set(MidelegIn::new());
+        self.mideleg.set(MidelegIn::from(self.mideleg_ro_one));
         self.mepc.set(0);
         self.sepc.set(0);
         self.satp.set(SatpIn::new());
@@ -83,40 +323,46 @@ impl CsrRegs {
         self.stval.set(0);
         self.cycle.set(0);
         self.instret.set(0);
+        self.mcounteren.set(0);
+        self.scounteren.set(0);
+        self.mcountinhibit.set(0);
+        self.fcsr.set(FcsrIn::new());
+        self.menvcfg.set(EnvcfgIn::new());
+        self.senvcfg.set(EnvcfgIn::new());
+        self.mstateen.iter().for_each(|x| x.set(0));
+        self.sstateen.iter().for_each(|x| x.set(0));
+        self.vstart.set(0);
+        self.vcsr.set(0);
+        self.vl.set(0);
+        self.vtype.set(VtypeIn::new().with_vill(true).into());
         self.dcsr
             .set(DcsrIn::new().with_debugver(4).with_mprven(true));
         self.dpc.set(0);
+        self.h.reset();
+        self.stimecmp.set(u64::MAX);
+        if let Some(hpm) = &self.hpm {
+            hpm.reset();
+        }
     }
 
     pub fn new(hart_id: usize, config: Rc<Config>) -> Self {
-        let mut misa_val = Misa::new().with_i(true).with_mxl(2); // 64
-
-        if config.is_enable_isa(b'm') {
-            misa_val.set_m(true);
-        }
-        if config.is_enable_isa(b'a') {
-            misa_val.set_a(true);
-        }
-        if config.is_enable_isa(b'c') {
-            misa_val.set_c(true);
-        }
-        if config.s_mode() {
-            misa_val.set_s(true);
-        }
-        if config.u_mode() {
-            misa_val.set_u(true);
-        }
+        let misa_val = misa_reset(&config);
+        let h_ext = config.is_enable_isa(b'h');
 
         let mut mstatus_val = XstatusIn::new().with_mpp(PrivilegeLevels::Machine as u8);
 
         mstatus_val.set_mbe(false);
         mstatus_val.set_sbe(false);
         mstatus_val.set_ube(false);
+        let xl = match config.xlen() {
+            32 => 0,
+            _ => misa_val.mxl(),
+        };
         if config.s_mode() {
-            mstatus_val.set_sxl(misa_val.mxl())
+            mstatus_val.set_sxl(xl)
         }
         if config.u_mode() {
-            mstatus_val.set_uxl(misa_val.mxl());
+            mstatus_val.set_uxl(xl);
             mstatus_val.set_mprv(false);
         }
 
@@ -141,33 +387,45 @@ impl CsrRegs {
         if !config.u_mode() && !config.s_mode() {
             mstatus_rmask.set_tw(true);
         }
+        if !h_ext {
+            mstatus_rmask.set_gva(true);
+            mstatus_rmask.set_mpv(true);
+        }
+        // not support custom extensions now
+        mstatus_rmask.set_xs(0b11);
+        if !config.is_enable_ext("zve64x") {
+            mstatus_rmask.set_vs(0b11);
+        }
         if !config.is_enable_isa(b'f') {
             mstatus_rmask.set_fs(0b11);
-            mstatus_rmask.set_vs(0b11);
-            mstatus_rmask.set_xs(0b11);
+        }
+        if !config.is_enable_isa(b'f') && !config.is_enable_ext("zve64x") {
             mstatus_rmask.set_sd(true);
         }
 
         let mstatus_rmask = !u64::from(mstatus_rmask);
 
-        let sstatus_wmask = u64::from(
-            XstatusIn::new()
-                .with_spp(true)
-                .with_sie(true)
-                .with_spie(true)
-                .with_ube(true)
-                .with_vs(0b11)
-                .with_fs(0b11)
-                .with_xs(0b11)
-                .with_sum(true)
-                .with_mxr(true)
-                .with_sd(true),
-        ) & mstatus_rmask;
+        // sstatus is a view of mstatus without the M-mode fields,
+        // UXL is read-only and SD follows FS, XS and VS
+        let sstatus_wmask = XstatusIn::new()
+            .with_spp(true)
+            .with_sie(true)
+            .with_spie(true)
+            .with_ube(true)
+            .with_vs(0b11)
+            .with_fs(0b11)
+            .with_xs(0b11)
+            .with_sum(true)
+            .with_mxr(true);
+        let sstatus_rmask = u64::from(sstatus_wmask.with_uxl(0b11).with_sd(true)) & mstatus_rmask;
+        let sstatus_wmask = u64::from(sstatus_wmask) & mstatus_rmask;
 
         let mstatus_wmask = XstatusIn::from(mstatus_rmask).with_uxl(0).with_sxl(0);
 
-        // read only
-        let misa = misa_val;
+        // M, A, F, D and C can be turned off
+        let misa_share = Rc::new(Cell::new(misa_val));
+        let misa_mask = u64::from(misa_val) & 0b1_0000_0010_1101;
+        let misa = Misa::new(misa_share.clone(), misa_mask);
         let mhartid = ReadOnlyCSR(hart_id as u64);
         let marchid = CommonCSR::new_noshare(0);
         let mvendorid = CommonCSR::new_noshare(0);
@@ -175,17 +433,30 @@ impl CsrRegs {
         // important csrs
         let xstatus_share = RcCell::new(mstatus_val.into());
         let mstatus = Xstatus::new(xstatus_share.clone(), mstatus_rmask, mstatus_wmask.into());
-        let sstatus = Xstatus::new(xstatus_share.clone(), mstatus_rmask, sstatus_wmask);
+        let sstatus = Xstatus::new(xstatus_share.clone(), sstatus_rmask, sstatus_wmask);
+        let h = HypervisorRegs::new();
+        let vsstatus = Xstatus::new(h.vsstatus.clone(), sstatus_rmask, sstatus_wmask);
 
-        let sip_mask = XieIn::new().with_seie(true).with_ssie(true).with_stie(true);
+        let sscofpmf = config.is_enable_ext("sscofpmf");
+        let sip_mask = XieIn::new()
+            .with_seie(true)
+            .with_ssie(true)
+            .with_stie(true)
+            .with_lcofie(sscofpmf);
 
         let xip_share = Rc::new(Cell::new(XipIn::new()));
         let mip = Xip::new(xip_share.clone(), MASK_ALL);
         let sip = Xip::new(xip_share.clone(), sip_mask.into());
+        // hvip injects the VS-level interrupts, only VSSIP is writable in hip
+        let hvip = Xip::new(xip_share.clone(), VS_INTERRUPTS);
+        let hip = Xip::new(xip_share.clone(), VS_INTERRUPTS | SGEI_INTERRUPT).with_wmask(1 << 2);
+        let vsip = Vsip::new(xip_share.clone(), h.hideleg.clone());
 
         let xie_share = Rc::new(Cell::new(XieIn::new()));
         let mie = Xie::new(xie_share.clone(), MASK_ALL);
         let sie = Xie::new(xie_share.clone(), sip_mask.into());
+        let hie = Xie::new(xie_share.clone(), VS_INTERRUPTS | SGEI_INTERRUPT);
+        let vsie = Vsie::new(xie_share.clone(), h.hideleg.clone());
 
         let mcause_share = Rc::new(Cell::new(XcauseIn::new()));
         let mcause = Xcause::new(mcause_share.clone());
@@ -200,7 +471,12 @@ impl CsrRegs {
         let medeleg_share = Rc::new(Cell::new(MedelegIn::new()));
         let medeleg = Medeleg::new(medeleg_share.clone());
         let mideleg_share = Rc::new(Cell::new(MidelegIn::new()));
-        let mideleg = Mideleg::new(mideleg_share.clone(), MASK_ALL);
+        let mideleg_ro_one = if h_ext {
+            VS_INTERRUPTS | SGEI_INTERRUPT
+        } else {
+            0
+        };
+        let mideleg = Mideleg::new(mideleg_share.clone(), MASK_ALL, mideleg_ro_one);
 
         let mepc_share = Rc::new(Cell::new(0_u64));
         let mepc = CommonCSR::new(mepc_share.clone());
@@ -221,17 +497,70 @@ impl CsrRegs {
         );
 
         let cycle_share = Rc::new(Cell::new(0));
-        let mcycle = Counter::new(cycle_share.clone());
+        let mcycle = CommonCSR::new(cycle_share.clone());
         let cycle = Counter::new(cycle_share.clone());
 
         let instret_share = Rc::new(Cell::new(0));
-        let minstret = Counter::new(instret_share.clone());
+        let minstret = CommonCSR::new(instret_share.clone());
         let instret = Counter::new(instret_share.clone());
 
+        // CY, IR and HPM3-31 if the counters are there
+        let mcountinhibit_share = Rc::new(Cell::new(0));
+        let mcountinhibit_mask = match sscofpmf {
+            true => 0xffff_fffd,
+            false => 0b101,
+        };
+        let mcountinhibit = MaskedCSR::new(mcountinhibit_share.clone(), mcountinhibit_mask);
+
         let mcounteren_share = Rc::new(Cell::new(0));
         let scounteren_share = Rc::new(Cell::new(0));
-        let mcounteren = CommonCSR::new(mcounteren_share);
-        let scounteren = CommonCSR::new(scounteren_share);
+        let mcounteren = CommonCSR::new(mcounteren_share.clone());
+        let scounteren = CommonCSR::new(scounteren_share.clone());
+
+        // envcfg, only the fields of the enabled extensions are writable
+        let mut envcfg_mask = EnvcfgIn::new().with_fiom(true);
+        if config.is_enable_ext("zicbom") {
+            envcfg_mask.set_cbie(0b11);
+            envcfg_mask.set_cbcfe(true);
+        }
+        if config.is_enable_ext("zicboz") {
+            envcfg_mask.set_cbze(true);
+        }
+        // senvcfg has no pbmte and stce
+        let pbmte = config.is_enable_ext("svpbmt");
+        let sstc = config.is_enable_ext("sstc");
+        let senvcfg_mask = envcfg_mask;
+        envcfg_mask.set_pbmte(pbmte);
+        envcfg_mask.set_stce(sstc);
+        let menvcfg_share = Rc::new(Cell::new(EnvcfgIn::new()));
+        let senvcfg_share = Rc::new(Cell::new(EnvcfgIn::new()));
+        let menvcfg = Envcfg::new(menvcfg_share.clone(), envcfg_mask.into());
+        let senvcfg = Envcfg::new(senvcfg_share.clone(), senvcfg_mask.into());
+        let henvcfg =
+            Envcfg::new(h.henvcfg.clone(), envcfg_mask.into()).with_menvcfg(menvcfg_share.clone());
+
+        // stateen, the fp state is only gated with Zfinx
+        let mstateen_share: [RcCell<u64>; 4] = core::array::from_fn(|_| Rc::new(Cell::new(0)));
+        let sstateen_share: [RcCell<u64>; 4] = core::array::from_fn(|_| Rc::new(Cell::new(0)));
+        let zfinx = config.is_enable_ext("zfinx");
+        let se = if config.s_mode() { STATEEN_SE } else { 0 };
+        let fcsr_en = if zfinx { STATEEN_FCSR } else { 0 };
+        let mstateen_mask = |idx: usize| match idx {
+            0 if config.s_mode() => se | STATEEN_ENVCFG | fcsr_en,
+            0 => fcsr_en,
+            _ => se,
+        };
+
+        // hypervisor
+        let hstatus = Hstatus::new(h.hstatus.clone());
+        let hedeleg = MaskedCSR::new(h.hedeleg.clone(), HEDELEG_MASK);
+        let hideleg = MaskedCSR::new(h.hideleg.clone(), VS_INTERRUPTS);
+        let hgatp = Hgatp::new(
+            h.hgatp.clone(),
+            xstatus_share.clone(),
+            config.get_mmu_type(),
+        );
+        let vsatp = Satp::new(h.vsatp.clone(), h.vsstatus.clone(), config.get_mmu_type());
 
         // debug mode
         let dcsr_share = Rc::new(Cell::new(DcsrIn::new().with_debugver(4).with_mprven(true)));
@@ -241,13 +570,37 @@ impl CsrRegs {
         let dscratch0 = CommonCSR::new_noshare(0);
         let dscratch1 = CommonCSR::new_noshare(0);
 
-        // not support hardware trigger now
-        let tselect = ReadOnlyCSR(0);
-        let tdata1 = ReadOnlyCSR(0);
-        let tdata2 = ReadOnlyCSR(0);
-        let tdata3 = ReadOnlyCSR(0);
-        let tinfo = ReadOnlyCSR(0);
+        // floating point
+        let fcsr_share = Rc::new(Cell::new(FcsrIn::new()));
+        let fp_csr = |shift, mask| {
+            Fcsr::new(fcsr_share.clone(), xstatus_share.clone(), shift, mask)
+                .with_zfinx(zfinx)
+                .with_virt(h.virt.clone(), h.vsstatus.clone())
+        };
+        let fflags = fp_csr(0, 0x1f);
+        let frm = fp_csr(5, 0x7);
+        let fcsr = fp_csr(0, 0xff);
+
+        // vector
+        let vstart_share = Rc::new(Cell::new(0));
+        let vcsr_share = Rc::new(Cell::new(0));
+        let vl_share = Rc::new(Cell::new(0));
+        let vtype_share = Rc::new(Cell::new(VtypeIn::new().with_vill(true).into()));
+        let vlenb_share = Rc::new(Cell::new(config.vlen() as u64 / 8));
+        let vstart = Vcsr::new(
+            vstart_share.clone(),
+            xstatus_share.clone(),
+            0,
+            config.vlen() as u64 - 1,
+        );
+        let vxsat = Vcsr::new(vcsr_share.clone(), xstatus_share.clone(), 0, 0x1);
+        let vxrm = Vcsr::new(vcsr_share.clone(), xstatus_share.clone(), 1, 0x3);
+        let vcsr = Vcsr::new(vcsr_share.clone(), xstatus_share.clone(), 0, 0x7);
+        let vl = Vcsr::new(vl_share.clone(), xstatus_share.clone(), 0, MASK_ALL);
+        let vtype = Vcsr::new(vtype_share.clone(), xstatus_share.clone(), 0, MASK_ALL);
+        let vlenb = Vcsr::new(vlenb_share, xstatus_share.clone(), 0, MASK_ALL);
 
+        let triggers: RcRefCell<Triggers> = RcRefCell::new(Triggers::new(h_ext).into());
 
         let mut csr_map: HashMap<u64, CsrEnum> = HashMap::new();
 
@@ -282,8 +635,148 @@ impl CsrRegs {
         csr_map.insert(CSR_MINSTRET.into(), minstret.into());
         csr_map.insert(CSR_INSTRET.into(), instret.into());
         csr_map.insert(CSR_MCOUNTEREN.into(), mcounteren.into());
+        csr_map.insert(CSR_MCOUNTINHIBIT.into(), mcountinhibit.into());
+
+        // the odd pmpcfg are the high halves of the even ones on rv32
+        let pmp: RcRefCell<Pmp> = RcRefCell::new(Pmp::new(config.pmp_entries()).into());
+        if config.pmp_entries() > 0 {
+            for reg in (0..16).step_by(2) {
+                let csr = PMPcfg::new(pmp.clone(), reg);
+                csr_map.insert(CSR_PMPCFG0 as u64 + reg as u64, csr.into());
+            }
+            for idx in 0..PMP_MAX_ENTRIES {
+                let csr = PMPaddr::new(pmp.clone(), idx);
+                csr_map.insert(CSR_PMPADDR0 as u64 + idx as u64, csr.into());
+            }
+        }
         csr_map.insert(CSR_SCOUNTEREN.into(), scounteren.into());
-        csr_map.insert(CSR_TSELECT.into(), tselect.into());
+        for addr in [CSR_TSELECT, CSR_TDATA1, CSR_TDATA2, CSR_TINFO] {
+            csr_map.insert(addr.into(), TriggerCsr::new(triggers.clone(), addr).into());
+        }
+        csr_map.insert(CSR_TDATA3.into(), ReadOnlyCSR(0).into());
+        if config.u_mode() {
+            csr_map.insert(CSR_MENVCFG.into(), menvcfg.into());
+        }
+        if config.s_mode() {
+            csr_map.insert(CSR_SENVCFG.into(), senvcfg.into());
+        }
+        if config.is_enable_ext("zkn") {
+            csr_map.insert(
+                CSR_SEED.into(),
+                Seed::new(config.seed() ^ hart_id as u64).into(),
+            );
+        }
+        if h_ext {
+            csr_map.insert(CSR_HSTATUS.into(), hstatus.into());
+            csr_map.insert(CSR_HEDELEG.into(), hedeleg.into());
+            csr_map.insert(CSR_HIDELEG.into(), hideleg.into());
+            csr_map.insert(CSR_HIE.into(), hie.into());
+            csr_map.insert(CSR_HIP.into(), hip.into());
+            csr_map.insert(CSR_HVIP.into(), hvip.into());
+            csr_map.insert(CSR_HGATP.into(), hgatp.into());
+            csr_map.insert(CSR_HENVCFG.into(), henvcfg.into());
+            csr_map.insert(CSR_HTVAL.into(), CommonCSR::new(h.htval.clone()).into());
+            csr_map.insert(CSR_HTINST.into(), CommonCSR::new(h.htinst.clone()).into());
+            csr_map.insert(
+                CSR_HTIMEDELTA.into(),
+                CommonCSR::new(h.htimedelta.clone()).into(),
+            );
+            let hcounteren = CommonCSR::new(h.hcounteren.clone());
+            csr_map.insert(CSR_HCOUNTEREN.into(), hcounteren.into());
+            // no guest external interrupt
+            csr_map.insert(CSR_HGEIE.into(), ReadOnlyCSR(0).into());
+            csr_map.insert(CSR_HGEIP.into(), ReadOnlyCSR(0).into());
+            csr_map.insert(CSR_MTVAL2.into(), CommonCSR::new(h.mtval2.clone()).into());
+            csr_map.insert(CSR_MTINST.into(), CommonCSR::new(h.mtinst.clone()).into());
+            csr_map.insert(CSR_VSSTATUS.into(), vsstatus.into());
+            csr_map.insert(CSR_VSIE.into(), vsie.into());
+            csr_map.insert(CSR_VSIP.into(), vsip.into());
+            csr_map.insert(CSR_VSTVEC.into(), Xtvec::new(h.vstvec.clone()).into());
+            csr_map.insert(CSR_VSSCRATCH.into(), CommonCSR::new_noshare(0).into());
+            csr_map.insert(CSR_VSEPC.into(), CommonCSR::new(h.vsepc.clone()).into());
+            csr_map.insert(CSR_VSCAUSE.into(), Xcause::new(h.vscause.clone()).into());
+            csr_map.insert(CSR_VSTVAL.into(), CommonCSR::new(h.vstval.clone()).into());
+            csr_map.insert(CSR_VSATP.into(), vsatp.into());
+        }
+
+        if config.is_enable_ext("smstateen") {
+            for idx in 0..4 {
+                let mstateen = Stateen::new(mstateen_share[idx].clone(), mstateen_mask(idx));
+                csr_map.insert(CSR_MSTATEEN0 as u64 + idx as u64, mstateen.into());
+                if config.s_mode() {
+                    // sstateen has no SE and ENVCFG
+                    let mask = if idx == 0 { fcsr_en } else { 0 };
+                    let mut sstateen = Stateen::new(sstateen_share[idx].clone(), mask)
+                        .with_upper(mstateen_share[idx].clone());
+                    if h_ext {
+                        sstateen =
+                            sstateen.with_virt_upper(h.virt.clone(), h.hstateen[idx].clone());
+                    }
+                    csr_map.insert(CSR_SSTATEEN0 as u64 + idx as u64, sstateen.into());
+                }
+                if h_ext {
+                    let hstateen = Stateen::new(h.hstateen[idx].clone(), mstateen_mask(idx))
+                        .with_upper(mstateen_share[idx].clone());
+                    csr_map.insert(CSR_HSTATEEN0 as u64 + idx as u64, hstateen.into());
+                }
+            }
+        }
+
+        // Sscofpmf, the VS and VU filters need the H extension
+        let hpm = sscofpmf.then(|| HpmCounters::new(mcountinhibit_share.clone()));
+        if let Some(hpm) = &hpm {
+            let mut filter = MhpmeventIn::new()
+                .with_event((1 << 56) - 1)
+                .with_of(true)
+                .with_minh(true)
+                .with_sinh(true)
+                .with_uinh(true);
+            filter.set_vsinh(h_ext);
+            filter.set_vuinh(h_ext);
+            for idx in 0..HPM_COUNTERS {
+                let counter = hpm.counters[idx].clone();
+                let offset = idx as u64;
+                let mhpmevent = Mhpmevent::new(
+                    hpm.events[idx].clone(),
+                    filter.into(),
+                    hpm.active.clone(),
+                    idx,
+                );
+                csr_map.insert(
+                    CSR_MHPMCOUNTER3 as u64 + offset,
+                    CommonCSR::new(counter.clone()).into(),
+                );
+                csr_map.insert(
+                    CSR_HPMCOUNTER3 as u64 + offset,
+                    Counter::new(counter).into(),
+                );
+                csr_map.insert(CSR_MHPMEVENT3 as u64 + offset, mhpmevent.into());
+            }
+            let scountovf = Scountovf::new(
+                hpm.events.to_vec(),
+                mcounteren_share.clone(),
+                h.hcounteren.clone(),
+                h.virt.clone(),
+            );
+            csr_map.insert(CSR_SCOUNTOVF.into(), scountovf.into());
+        }
+
+        let stimecmp_share = Rc::new(Cell::new(u64::MAX));
+        let sstc = sstc.then(|| SstcTimer {
+            stimecmp: stimecmp_share.clone(),
+            vstimecmp: h.vstimecmp.clone(),
+            menvcfg: menvcfg_share.clone(),
+            henvcfg: h.henvcfg.clone(),
+            htimedelta: h.htimedelta.clone(),
+        });
+        if sstc.is_some() {
+            let stimecmp = CommonCSR::new(stimecmp_share.clone());
+            csr_map.insert(CSR_STIMECMP.into(), stimecmp.into());
+            if h_ext {
+                let vstimecmp = CommonCSR::new(h.vstimecmp.clone());
+                csr_map.insert(CSR_VSTIMECMP.into(), vstimecmp.into());
+            }
+        }
 
         // debug mode
         csr_map.insert(CSR_DCSR.into(), dcsr.into());
@@ -291,9 +784,27 @@ impl CsrRegs {
         csr_map.insert(CSR_DSCRATCH0.into(), dscratch0.into());
         csr_map.insert(CSR_DSCRATCH1.into(), dscratch1.into());
 
+        if config.is_enable_isa(b'f') || zfinx {
+            csr_map.insert(CSR_FFLAGS.into(), fflags.into());
+            csr_map.insert(CSR_FRM.into(), frm.into());
+            csr_map.insert(CSR_FCSR.into(), fcsr.into());
+        }
+        if config.is_enable_ext("zve64x") {
+            csr_map.insert(CSR_VSTART.into(), vstart.into());
+            csr_map.insert(CSR_VXSAT.into(), vxsat.into());
+            csr_map.insert(CSR_VXRM.into(), vxrm.into());
+            csr_map.insert(CSR_VCSR.into(), vcsr.into());
+            csr_map.insert(CSR_VL.into(), vl.into());
+            csr_map.insert(CSR_VTYPE.into(), vtype.into());
+            csr_map.insert(CSR_VLENB.into(), vlenb.into());
+        }
+
         Self {
             config,
             csr_map,
+            misa: misa_share,
+            pmp,
+            triggers,
             xstatus: xstatus_share,
             xip: xip_share,
             xie: xie_share,
@@ -308,26 +819,259 @@ impl CsrRegs {
             satp: satp_share,
             cycle: cycle_share,
             instret: instret_share,
+            mcounteren: mcounteren_share,
+            scounteren: scounteren_share,
+            mcountinhibit: mcountinhibit_share,
+            fcsr: fcsr_share,
+            menvcfg: menvcfg_share,
+            senvcfg: senvcfg_share,
+            mstateen: mstateen_share,
+            sstateen: sstateen_share,
+            vstart: vstart_share,
+            vcsr: vcsr_share,
+            vl: vl_share,
+            vtype: vtype_share,
             cur_priv: PrivilegeLevels::Machine,
+            debug_mode: false,
             mtvec: mtvec_share,
             stvec: stvec_share,
             dcsr: dcsr_share,
             dpc: dpc_share,
+            h,
+            sstc,
+            stimecmp: stimecmp_share,
+            hpm,
+            mtime: None,
+            mideleg_ro_one,
+        }
+    }
+
+    // mcountinhibit.CY and IR stop mcycle and minstret
+    pub fn count_cycle(&self, cycles: u64) {
+        if self.mcountinhibit.get() & 0b1 == 0 {
+            self.cycle.set(self.cycle.get().wrapping_add(cycles));
+        }
+    }
+
+    pub fn count_instret(&self) {
+        if self.mcountinhibit.get() & 0b100 == 0 {
+            self.instret.set(self.instret.get().wrapping_add(1));
         }
     }
 
+    pub fn virt(&self) -> bool {
+        self.h.virt.get()
+    }
+
+    // In VS-mode and VU-mode, the supervisor CSRs are replaced by the VS CSRs,
+    // accessing a hypervisor CSR raises a virtual instruction exception.
+    fn virt_csr_addr(&self, addr: u64, privi: PrivilegeLevels) -> Result<u64, TrapType> {
+        if !self.virt() {
+            return Ok(addr);
+        }
+        let csr_priv = (addr >> 8) & 0b11;
+        let exist = self.csr_map.contains_key(&addr);
+        let virtual_inst = match csr_priv {
+            0b01 | 0b10 if privi == PrivilegeLevels::User => true,
+            0b10 => true,
+            _ => false,
+        };
+        if virtual_inst && exist {
+            return Err(TrapType::VirtualInstruction(0));
+        }
+        if addr == CSR_SATP.into() && self.h.hstatus.get().vtvm() {
+            return Err(TrapType::VirtualInstruction(0));
+        }
+        let vs_addr = match addr as u16 {
+            CSR_SSTATUS | CSR_SIE | CSR_STVEC | CSR_SSCRATCH | CSR_SEPC | CSR_SCAUSE
+            | CSR_STVAL | CSR_SIP | CSR_SATP | CSR_STIMECMP => addr + 0x100,
+            _ => addr,
+        };
+        Ok(vs_addr)
+    }
+
+    // a vendor csr, e.g. in the custom 0x7c0..0x7ff or 0xbc0..0xbff ranges
+    pub fn register_csr(&mut self, addr: u16, csr: impl CsrAccess + 'static) -> RvEmuResult<()> {
+        let addr = addr as u64 & 0xfff;
+        if self.csr_map.contains_key(&addr) {
+            return Err(RvEmuError::CsrConflict(addr));
+        }
+        self.csr_map.insert(addr, CustomCSR::new(csr).into());
+        Ok(())
+    }
+
     pub fn add_mtime(&mut self, mtime: RcCell<u64>) {
-        let time = Counter::new(mtime);
+        let time = Counter::new(mtime.clone());
         self.csr_map.insert(CSR_TIME.into(), time.into());
+        self.mtime = Some(mtime);
+    }
+
+    // below M-mode stimecmp needs menvcfg.STCE, in VS-mode also henvcfg.STCE
+    fn check_stimecmp(&self, addr: u64, privi: PrivilegeLevels) -> Result<(), TrapType> {
+        if addr != CSR_STIMECMP.into() || privi == PrivilegeLevels::Machine {
+            return Ok(());
+        }
+        if !self.menvcfg.get().stce() {
+            return Err(TrapType::IllegalInstruction(0));
+        }
+        if self.virt() && !self.h.henvcfg.get().stce() {
+            return Err(TrapType::VirtualInstruction(0));
+        }
+        Ok(())
+    }
+
+    // cycle, time, instret and hpmcounter3-31: below M-mode they need the mcounteren bit,
+    // in VS-mode and VU-mode also the hcounteren bit, and in U-mode and VU-mode the scounteren bit
+    fn check_counteren(&self, addr: u64, privi: PrivilegeLevels) -> Result<(), TrapType> {
+        if !(CSR_CYCLE..=CSR_HPMCOUNTER31).contains(&(addr as u16))
+            || privi == PrivilegeLevels::Machine
+        {
+            return Ok(());
+        }
+        let bit = 1 << (addr & 0x1f);
+        if self.mcounteren.get() & bit == 0 {
+            return Err(TrapType::IllegalInstruction(0));
+        }
+        let u_mode = privi == PrivilegeLevels::User && self.config.s_mode();
+        let scounteren = !u_mode || self.scounteren.get() & bit != 0;
+        if self.virt() {
+            if self.h.hcounteren.get() & bit == 0 || !scounteren {
+                return Err(TrapType::VirtualInstruction(0));
+            }
+        } else if !scounteren {
+            return Err(TrapType::IllegalInstruction(0));
+        }
+        Ok(())
+    }
+
+    // Smstateen: below M-mode the gated state needs the mstateen bit, in VS-mode
+    // and VU-mode also the hstateen bit, and in U-mode the sstateen bit
+    pub fn check_stateen(&self, addr: u64, privi: PrivilegeLevels) -> Result<(), TrapType> {
+        if privi == PrivilegeLevels::Machine || !self.config.is_enable_ext("smstateen") {
+            return Ok(());
+        }
+        let zfinx = self.config.is_enable_ext("zfinx");
+        let (idx, bit) = match addr as u16 {
+            CSR_SSTATEEN0..=CSR_SSTATEEN3 | CSR_HSTATEEN0..=CSR_HSTATEEN3 => {
+                (addr as usize & 0b11, STATEEN_SE)
+            }
+            CSR_SENVCFG | CSR_HENVCFG => (0, STATEEN_ENVCFG),
+            CSR_FFLAGS | CSR_FRM | CSR_FCSR if zfinx => (0, STATEEN_FCSR),
+            _ => return Ok(()),
+        };
+        if self.mstateen[idx].get() & bit == 0 {
+            return Err(TrapType::IllegalInstruction(0));
+        }
+        if self.virt() && self.h.hstateen[idx].get() & bit == 0 {
+            return Err(TrapType::VirtualInstruction(0));
+        }
+        let u_mode = privi == PrivilegeLevels::User && self.config.s_mode();
+        if u_mode && self.sstateen[idx].get() & bit == 0 {
+            return Err(TrapType::IllegalInstruction(0));
+        }
+        Ok(())
+    }
+
+    // strict: the debug csrs 0x7b0-0x7bf are only accessible in Debug Mode
+    fn check_debug_csr(&self, addr: u64) -> Result<(), TrapType> {
+        let strict = self.config.compliance() == Compliance::Strict;
+        if strict && !self.debug_mode && (0x7b0..=0x7bf).contains(&addr) {
+            return Err(TrapType::IllegalInstruction(0));
+        }
+        Ok(())
+    }
+
+    // a single letter extension is enabled by the isa string and misa
+    pub fn misa_ext(&self, ext: u8) -> bool {
+        u64::from(self.misa.get()) & 1 << (ext - b'a') != 0
+    }
+
+    // without C, IALIGN is 32 and bit 1 of xepc reads 0
+    pub fn xepc(&self, epc: u64) -> u64 {
+        match self.misa_ext(b'c') {
+            true => epc,
+            false => epc & !0b10,
+        }
+    }
+
+    fn relaxed(&self) -> bool {
+        self.config.compliance() == Compliance::Relaxed
+    }
+
+    // STIP and VSTIP follow the new compare values right away
+    fn update_sstc(&self) {
+        if let (Some(sstc), Some(mtime)) = (&self.sstc, &self.mtime) {
+            sstc.update(mtime.get(), &self.xip);
+        }
     }
 
     pub fn read(&mut self, addr: u64, privi: PrivilegeLevels) -> Result<u64, TrapType> {
+        if self.config.xlen() == 32 {
+            return self.read_rv32(addr, privi);
+        }
+        self.read_csr(addr, privi)
+    }
+
+    pub fn write(&mut self, addr: u64, data: u64, privi: PrivilegeLevels) -> Result<(), TrapType> {
+        if self.config.xlen() == 32 {
+            return self.write_rv32(addr, data, privi);
+        }
+        self.write_csr(addr, data, privi)
+    }
+
+    // rv32 reads the low half of a csr, the high half of the 64-bit ones is
+    // at the ...h address. SD and the interrupt bit of xcause move to bit 31,
+    // satp is MODE[31] ASID[30:22] PPN[21:0]
+    fn read_rv32(&mut self, addr: u64, privi: PrivilegeLevels) -> Result<u64, TrapType> {
+        if let Some(base) = rv32_high_half(addr) {
+            return Ok(self.read_csr(base, privi)? >> 32);
+        }
+        let data = self.read_csr(addr, privi)?;
+        let ret = match addr as u16 {
+            CSR_MSTATUS | CSR_SSTATUS | CSR_VSSTATUS | CSR_MCAUSE | CSR_SCAUSE | CSR_VSCAUSE => {
+                data & 0x7fff_ffff | (data >> 63) << 31
+            }
+            CSR_MISA => data & 0x3ff_ffff | (data >> 62) << 30,
+            CSR_SATP => (data >> 60 & 1) << 31 | (data >> 44 & 0x1ff) << 22 | data & 0x3f_ffff,
+            _ => data & 0xffff_ffff,
+        };
+        Ok(ret)
+    }
+
+    // a write to one half keeps the other one
+    fn write_rv32(&mut self, addr: u64, data: u64, privi: PrivilegeLevels) -> Result<(), TrapType> {
+        let data = data & 0xffff_ffff;
+        let (base, high) = match rv32_high_half(addr) {
+            Some(base) => (base, true),
+            None => (addr, false),
+        };
+        let old = self.read_csr(base, privi)?;
+        let data = match (base as u16, high) {
+            (_, true) => old & 0xffff_ffff | data << 32,
+            (CSR_MCAUSE | CSR_SCAUSE | CSR_VSCAUSE, _) => data & 0x7fff_ffff | (data >> 31) << 63,
+            (CSR_SATP, _) => (data >> 31) << 60 | (data >> 22 & 0x1ff) << 44 | data & 0x3f_ffff,
+            _ => old & !0xffff_ffff | data,
+        };
+        self.write_csr(base, data, privi)
+    }
+
+    fn read_csr(&mut self, addr: u64, privi: PrivilegeLevels) -> Result<u64, TrapType> {
         assert!(addr < 4096); // The size of a CSR is 4KB
         self.cur_priv = privi; // Update the current privilege level
+        self.check_stimecmp(addr, privi)?;
+        self.check_stateen(addr, privi)?;
+        self.check_counteren(addr, privi)?;
+        self.check_debug_csr(addr)?;
+        let addr = self.virt_csr_addr(addr, privi)?;
+        let relaxed = self.relaxed();
 
         // Get the CSR with address addr from the CSR map. If it does not exist, return an illegal instruction trap.
         let csr = match self.csr_map.get(&addr) {
             Some(csr) => csr,
+            None if relaxed => {
+                warn!("unimplemented csr {addr:#x} reads 0, relaxed mode");
+                return Ok(0);
+            }
             None => return Err(TrapType::IllegalInstruction(0)),
         };
 
@@ -339,17 +1083,33 @@ impl CsrRegs {
             return Err(TrapType::IllegalInstruction(0));
         }
 
+        // the time of VS-mode and VU-mode is shifted by htimedelta
+        if self.virt() && addr == CSR_TIME.into() {
+            return Ok(csr.read().wrapping_add(self.h.htimedelta.get()));
+        }
+        if matches!(addr as u16, CSR_MEPC | CSR_SEPC | CSR_VSEPC) {
+            return Ok(self.xepc(csr.read()));
+        }
         // Return the value of the CSR.
         Ok(csr.read())
     }
 
-    pub fn write(&mut self, addr: u64, data: u64, privi: PrivilegeLevels) -> Result<(), TrapType> {
+    fn write_csr(&mut self, addr: u64, data: u64, privi: PrivilegeLevels) -> Result<(), TrapType> {
         assert!(addr < 4096); // The size of a CSR is 4KB
         self.cur_priv = privi; // Update the current privilege level
+        self.check_stimecmp(addr, privi)?;
+        self.check_stateen(addr, privi)?;
+        self.check_debug_csr(addr)?;
+        let addr = self.virt_csr_addr(addr, privi)?;
+        let relaxed = self.relaxed();
 
         // Get the CSR with address addr from the CSR map. If it does not exist, return an illegal instruction trap.
         let csr = match self.csr_map.get_mut(&addr) {
             Some(csr) => csr,
+            None if relaxed => {
+                warn!("unimplemented csr {addr:#x} ignores the write, relaxed mode");
+                return Ok(());
+            }
             None => return Err(TrapType::IllegalInstruction(0)),
         };
 
@@ -361,8 +1121,17 @@ impl CsrRegs {
             return Err(TrapType::IllegalInstruction(0));
         }
 
+        // dmode and the triggers with dmode set are only writable in Debug Mode
+        let data = match addr as u16 {
+            CSR_TDATA1 | CSR_TDATA2 if !self.debug_mode && self.triggers.borrow().dmode() => {
+                return Ok(())
+            }
+            CSR_TDATA1 if !self.debug_mode => data & !TDATA1_DMODE,
+            _ => data,
+        };
         // Return the value of the CSR.
         csr.write(data);
+        self.update_sstc();
         Ok(())
     }
 
@@ -376,6 +1145,7 @@ impl CsrRegs {
         };
 
         csr.write(data);
+        self.update_sstc();
     }
 
     pub fn read_raw(&mut self, addr: u64) -> u64 {
@@ -389,3 +1159,454 @@ impl CsrRegs {
         csr.read()
     }
 }
+
+// the extensions of the isa string, the reset value of misa
+fn misa_reset(config: &Config) -> MisaIn {
+    let rve = config.is_enable_isa(b'e');
+    let mxl = match config.xlen() {
+        32 => 1,
+        _ => 2,
+    };
+    let mut misa_val = MisaIn::new().with_i(!rve).with_e(rve).with_mxl(mxl);
+
+    if config.is_enable_isa(b'm') {
+        misa_val.set_m(true);
+    }
+    if config.is_enable_isa(b'a') {
+        misa_val.set_a(true);
+    }
+    if config.is_enable_isa(b'c') {
+        misa_val.set_c(true);
+    }
+    if config.is_enable_isa(b'f') {
+        misa_val.set_f(true);
+    }
+    if config.is_enable_isa(b'd') {
+        misa_val.set_d(true);
+    }
+    if config.s_mode() {
+        misa_val.set_s(true);
+    }
+    if config.u_mode() {
+        misa_val.set_u(true);
+    }
+    if config.is_enable_isa(b'h') {
+        misa_val.set_h(true);
+    }
+
+    misa_val
+}
+
+// the csr a rv32 ...h address is the high half of
+fn rv32_high_half(addr: u64) -> Option<u64> {
+    let base = match addr as u16 {
+        CSR_CYCLEH..=CSR_HPMCOUNTER31H | CSR_MCYCLEH..=CSR_MHPMCOUNTER31H => addr - 0x80,
+        CSR_MHPMEVENT3H..=CSR_MHPMEVENT31H => addr - 0x400,
+        CSR_PMPCFG0..=CSR_PMPCFG15 if addr & 1 == 1 => addr - 1,
+        CSR_MSTATUSH | CSR_MENVCFGH | CSR_MSTATEEN0H..=CSR_MSTATEEN3H | CSR_STIMECMPH => {
+            addr - 0x10
+        }
+        _ => return None,
+    };
+    Some(base)
+}
+
+#[cfg(test)]
+mod test_csr_regs {
+    use alloc::rc::Rc;
+
+    use crate::{
+        config::Config,
+        rv64core::{
+            csr_regs_define::EnvcfgIn,
+            inst::inst_base::{
+                PrivilegeLevels, CSR_CYCLE, CSR_DPC, CSR_FCSR, CSR_HCOUNTEREN, CSR_HSTATEEN0,
+                CSR_INSTRET, CSR_MCAUSE, CSR_MCOUNTEREN, CSR_MCOUNTINHIBIT, CSR_MCYCLE,
+                CSR_MCYCLEH, CSR_MENVCFG, CSR_MHPMCOUNTER3, CSR_MHPMEVENT3, CSR_MINSTRET, CSR_MIP,
+                CSR_MISA, CSR_MSTATEEN0, CSR_MSTATUS, CSR_MSTATUSH, CSR_MTVEC, CSR_SCOUNTEREN,
+                CSR_SCOUNTOVF, CSR_SENVCFG, CSR_SIP, CSR_SSTATEEN0, CSR_SSTATEEN1, CSR_SSTATUS,
+                CSR_STIMECMP, CSR_TIME, CSR_VSTIMECMP,
+            },
+            traptype::TrapType,
+        },
+        tools::RcCell,
+    };
+
+    use super::{CsrRegs, HpmEvent, MhpmeventIn, STATEEN_ENVCFG, STATEEN_SE};
+
+    #[test]
+    fn sstc_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imach_sstc");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        config.set_u_mode();
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        let mtime = RcCell::new(500.into());
+        csr.add_mtime(mtime.clone());
+        let (stimecmp, s_mode) = (CSR_STIMECMP.into(), PrivilegeLevels::Supervisor);
+
+        // S-mode needs menvcfg.STCE, VS-mode also henvcfg.STCE
+        assert_eq!(
+            csr.write(stimecmp, 100, s_mode),
+            Err(TrapType::IllegalInstruction(0))
+        );
+        let stce = EnvcfgIn::new().with_stce(true);
+        csr.write(CSR_MENVCFG.into(), stce.into(), PrivilegeLevels::Machine)
+            .unwrap();
+        // the interrupt follows the write, without waiting for the CLINT
+        csr.write(stimecmp, 100, s_mode).unwrap();
+        assert!(csr.xip.get().stip());
+        // mip.STIP is read-only
+        csr.write(CSR_MIP.into(), 0, PrivilegeLevels::Machine)
+            .unwrap();
+        assert!(csr.xip.get().stip());
+        csr.write(stimecmp, 501, s_mode).unwrap();
+        assert!(!csr.xip.get().stip());
+
+        csr.h.virt.set(true);
+        assert_eq!(
+            csr.read(stimecmp, s_mode),
+            Err(TrapType::VirtualInstruction(0))
+        );
+        csr.h.henvcfg.set(stce);
+        csr.h.htimedelta.set(10);
+        // VS-mode stimecmp is vstimecmp
+        csr.write(stimecmp, 510, s_mode).unwrap();
+        assert_eq!(csr.h.vstimecmp.get(), 510);
+        assert!(csr.xip.get().vstip());
+        csr.h.virt.set(false);
+        assert_eq!(csr.read(CSR_VSTIMECMP.into(), s_mode), Ok(510));
+        assert_eq!(csr.read(stimecmp, s_mode), Ok(501));
+    }
+
+    #[test]
+    fn compliance_test() {
+        let csr_regs = |mode: &str| {
+            let mut config = Config::new();
+            config.set_isa("rv64imac");
+            config.set_compliance(mode);
+            CsrRegs::new(0, Rc::new(config))
+        };
+        let (m_mode, dpc, unknown) = (PrivilegeLevels::Machine, CSR_DPC.into(), 0x7c0);
+
+        let mut csr = csr_regs("normal");
+        assert!(csr.read(dpc, m_mode).is_ok());
+        assert_eq!(
+            csr.read(unknown, m_mode),
+            Err(TrapType::IllegalInstruction(0))
+        );
+
+        // the debug csrs trap outside Debug Mode
+        let mut csr = csr_regs("strict");
+        assert_eq!(
+            csr.write(dpc, 0x80, m_mode),
+            Err(TrapType::IllegalInstruction(0))
+        );
+        csr.debug_mode = true;
+        csr.write(dpc, 0x80, m_mode).unwrap();
+        assert_eq!(csr.read(dpc, m_mode), Ok(0x80));
+
+        // the unimplemented csrs read 0 and ignore the writes
+        let mut csr = csr_regs("relaxed");
+        assert_eq!(csr.write(unknown, 1, m_mode), Ok(()));
+        assert_eq!(csr.read(unknown, m_mode), Ok(0));
+        // the privilege is still checked
+        assert!(csr.read(dpc, PrivilegeLevels::User).is_err());
+    }
+
+    #[test]
+    fn stateen_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imach_zfinx_smstateen");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        let (m_mode, s_mode, u_mode) = (
+            PrivilegeLevels::Machine,
+            PrivilegeLevels::Supervisor,
+            PrivilegeLevels::User,
+        );
+        let (mstateen0, sstateen0, hstateen0) = (
+            CSR_MSTATEEN0.into(),
+            CSR_SSTATEEN0.into(),
+            CSR_HSTATEEN0.into(),
+        );
+        let illegal = Err(TrapType::IllegalInstruction(0));
+
+        // reset to 0, the lower levels can not reach the gated state
+        assert_eq!(csr.read(CSR_SENVCFG.into(), s_mode), illegal);
+        assert_eq!(csr.read(sstateen0, s_mode), illegal);
+        assert_eq!(csr.read(hstateen0, s_mode), illegal);
+        assert_eq!(csr.read(CSR_SENVCFG.into(), m_mode), Ok(0));
+
+        // SE0, ENVCFG and FCSR are writable, the unimplemented bits read 0
+        csr.write(mstateen0, u64::MAX, m_mode).unwrap();
+        assert_eq!(csr.read(mstateen0, m_mode), Ok(0xc000_0000_0000_0002));
+        assert!(csr.read(CSR_SENVCFG.into(), s_mode).is_ok());
+        // sstateen only has the bits of mstateen, and no SE0
+        csr.write(sstateen0, u64::MAX, s_mode).unwrap();
+        assert_eq!(csr.read(sstateen0, s_mode), Ok(0x2));
+        assert_eq!(csr.read(CSR_SSTATEEN1.into(), s_mode), illegal);
+
+        // the fp state of U-mode is gated by sstateen
+        assert!(csr.check_stateen(CSR_FCSR.into(), u_mode).is_ok());
+        csr.write(sstateen0, 0, s_mode).unwrap();
+        assert_eq!(
+            csr.check_stateen(CSR_FCSR.into(), u_mode),
+            Err(TrapType::IllegalInstruction(0))
+        );
+
+        // VS-mode also needs hstateen
+        csr.h.virt.set(true);
+        assert_eq!(
+            csr.read(CSR_SENVCFG.into(), s_mode),
+            Err(TrapType::VirtualInstruction(0))
+        );
+        csr.h.hstateen[0].set(STATEEN_ENVCFG | STATEEN_SE);
+        assert!(csr.read(CSR_SENVCFG.into(), s_mode).is_ok());
+        // the FCSR bit is cleared in hstateen, read-only zero in VS-mode
+        csr.write(sstateen0, u64::MAX, s_mode).unwrap();
+        assert_eq!(csr.read(sstateen0, s_mode), Ok(0));
+    }
+
+    #[test]
+    fn counteren_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imach");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        config.set_u_mode();
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        csr.add_mtime(RcCell::new(7.into()));
+        let m_mode = PrivilegeLevels::Machine;
+        let (s_mode, u_mode) = (PrivilegeLevels::Supervisor, PrivilegeLevels::User);
+        let (cycle, time, instret) = (CSR_CYCLE.into(), CSR_TIME.into(), CSR_INSTRET.into());
+        let illegal = Err(TrapType::IllegalInstruction(0));
+        let virtual_inst = Err(TrapType::VirtualInstruction(0));
+
+        assert_eq!(csr.read(time, m_mode), Ok(7));
+        assert_eq!(csr.read(time, s_mode), illegal);
+        // CY and TM for S-mode, only TM for U-mode
+        csr.write(CSR_MCOUNTEREN.into(), 0b011, m_mode).unwrap();
+        csr.write(CSR_SCOUNTEREN.into(), 0b010, s_mode).unwrap();
+        assert_eq!(csr.read(cycle, s_mode), Ok(0));
+        assert_eq!(csr.read(instret, s_mode), illegal);
+        assert_eq!(csr.read(time, u_mode), Ok(7));
+        assert_eq!(csr.read(cycle, u_mode), illegal);
+
+        // VS-mode needs hcounteren, VU-mode also scounteren
+        csr.h.virt.set(true);
+        assert_eq!(csr.read(time, s_mode), virtual_inst);
+        assert_eq!(csr.read(instret, s_mode), illegal);
+        csr.write_raw(CSR_HCOUNTEREN.into(), 0b011);
+        assert_eq!(csr.read(time, s_mode), Ok(7));
+        assert_eq!(csr.read(time, u_mode), Ok(7));
+        assert_eq!(csr.read(cycle, u_mode), virtual_inst);
+
+        csr.reset();
+        csr.h.virt.set(false);
+        assert_eq!(csr.read(cycle, s_mode), illegal);
+    }
+
+    #[test]
+    fn sscofpmf_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imac_sscofpmf");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        let (m_mode, s_mode, u_mode) = (
+            PrivilegeLevels::Machine,
+            PrivilegeLevels::Supervisor,
+            PrivilegeLevels::User,
+        );
+        let (mhpmevent4, mhpmcounter4) = (CSR_MHPMEVENT3 as u64 + 1, CSR_MHPMCOUNTER3 as u64 + 1);
+
+        // count instret outside M-mode, the unsupported events read 0
+        let minh = MhpmeventIn::new()
+            .with_event(HpmEvent::Instret as u64)
+            .with_minh(true);
+        csr.write(mhpmevent4, minh.into(), m_mode).unwrap();
+        csr.write(CSR_MHPMEVENT3.into(), 0xff, m_mode).unwrap();
+        assert_eq!(csr.read(CSR_MHPMEVENT3.into(), m_mode), Ok(0));
+        csr.write(mhpmcounter4, u64::MAX - 1, m_mode).unwrap();
+        let hpm = csr.hpm.as_ref().unwrap();
+        hpm.count(HpmEvent::Instret, m_mode, false, &csr.xip);
+        hpm.count(HpmEvent::Cycle, s_mode, false, &csr.xip);
+        hpm.count(HpmEvent::Instret, s_mode, false, &csr.xip);
+        assert_eq!(csr.read(mhpmcounter4, m_mode), Ok(u64::MAX));
+        assert!(!csr.xip.get().lcofip());
+
+        // the carry out sets OF and raises LCOFIP
+        let hpm = csr.hpm.as_ref().unwrap();
+        hpm.count(HpmEvent::Instret, u_mode, false, &csr.xip);
+        assert_eq!(csr.read(mhpmcounter4, m_mode), Ok(0));
+        assert!(csr.xip.get().lcofip());
+        assert!(MhpmeventIn::from(csr.read(mhpmevent4, m_mode).unwrap()).of());
+        assert_eq!(
+            csr.xip.get().get_priority_interupt(),
+            TrapType::LocalCounterOverflowInterrupt
+        );
+
+        // scountovf follows mcounteren, S-mode clears LCOFIP through sip
+        assert_eq!(csr.read(CSR_SCOUNTOVF.into(), s_mode), Ok(0));
+        csr.write(CSR_MCOUNTEREN.into(), 1 << 4, m_mode).unwrap();
+        assert_eq!(csr.read(CSR_SCOUNTOVF.into(), s_mode), Ok(1 << 4));
+        csr.write(CSR_SIP.into(), 0, s_mode).unwrap();
+        assert!(!csr.xip.get().lcofip());
+    }
+
+    #[test]
+    fn mcountinhibit_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imac_sscofpmf");
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        let m_mode = PrivilegeLevels::Machine;
+        let (mcountinhibit, mhpmcounter3) = (CSR_MCOUNTINHIBIT.into(), CSR_MHPMCOUNTER3.into());
+        let event = MhpmeventIn::new().with_event(HpmEvent::Cycle as u64);
+        csr.write(CSR_MHPMEVENT3.into(), event.into(), m_mode)
+            .unwrap();
+        csr.write(CSR_MCYCLE.into(), 10, m_mode).unwrap();
+        csr.write(CSR_MINSTRET.into(), 20, m_mode).unwrap();
+
+        // bit 1 is TM, there is no mtime counter in the hart
+        csr.write(mcountinhibit, u64::MAX, m_mode).unwrap();
+        assert_eq!(csr.read(mcountinhibit, m_mode), Ok(0xffff_fffd));
+        csr.count_cycle(5);
+        csr.count_instret();
+        let hpm = csr.hpm.as_ref().unwrap();
+        hpm.count(HpmEvent::Cycle, m_mode, false, &csr.xip);
+        assert_eq!(csr.read(CSR_MCYCLE.into(), m_mode), Ok(10));
+        assert_eq!(csr.read(CSR_MINSTRET.into(), m_mode), Ok(20));
+        assert_eq!(csr.read(mhpmcounter3, m_mode), Ok(0));
+
+        csr.write(mcountinhibit, 0b100, m_mode).unwrap();
+        csr.count_cycle(5);
+        csr.count_instret();
+        let hpm = csr.hpm.as_ref().unwrap();
+        hpm.count(HpmEvent::Cycle, m_mode, false, &csr.xip);
+        assert_eq!(csr.read(CSR_MCYCLE.into(), m_mode), Ok(15));
+        assert_eq!(csr.read(CSR_MINSTRET.into(), m_mode), Ok(20));
+        assert_eq!(csr.read(mhpmcounter3, m_mode), Ok(1));
+
+        // without the hpm counters only CY and IR
+        let mut csr = CsrRegs::new(0, Rc::new(Config::new()));
+        csr.write(mcountinhibit, u64::MAX, m_mode).unwrap();
+        assert_eq!(csr.read(mcountinhibit, m_mode), Ok(0b101));
+    }
+
+    #[test]
+    fn register_csr_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_u_mode();
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        let (m_mode, u_mode) = (PrivilegeLevels::Machine, PrivilegeLevels::User);
+        let value = RcCell::new(0x55.into());
+        let (r, w) = (value.clone(), value.clone());
+        csr.register_csr(0x7c0, (move || r.get(), move |x| w.set(x & 0xff)))
+            .unwrap();
+        // read-only 0xfc0, a user csr 0x800
+        csr.register_csr(0xfc0, (|| 7, |_| ())).unwrap();
+        csr.register_csr(0x800, (|| 8, |_| ())).unwrap();
+        assert!(csr.register_csr(CSR_MSTATUS, (|| 0, |_| ())).is_err());
+
+        assert_eq!(csr.read(0x7c0, m_mode), Ok(0x55));
+        csr.write(0x7c0, 0x1234, m_mode).unwrap();
+        assert_eq!(value.get(), 0x34);
+        assert!(csr.read(0x7c0, u_mode).is_err());
+        assert_eq!(csr.read(0xfc0, m_mode), Ok(7));
+        assert!(csr.write(0xfc0, 1, m_mode).is_err());
+        assert_eq!(csr.read(0x800, u_mode), Ok(8));
+    }
+
+    #[test]
+    fn xtvec_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        let (m_mode, mtvec) = (PrivilegeLevels::Machine, CSR_MTVEC.into());
+        csr.write(mtvec, 0x8000_0101, m_mode).unwrap();
+        assert_eq!(csr.read(mtvec, m_mode), Ok(0x8000_0101));
+        let tvec = csr.mtvec.get();
+        assert_eq!(
+            tvec.get_trap_pc(TrapType::MachineTimerInterrupt),
+            0x8000_011c
+        );
+        assert_eq!(
+            tvec.get_trap_pc(TrapType::IllegalInstruction(0)),
+            0x8000_0100
+        );
+        // a local interrupt above 15
+        assert_eq!(tvec.trap_pc(1 << 63 | 35), 0x8000_018c);
+        // the reserved modes keep the old mode
+        csr.write(mtvec, 0x8000_0202, m_mode).unwrap();
+        assert_eq!(csr.read(mtvec, m_mode), Ok(0x8000_0201));
+        csr.write(mtvec, 0x8000_0300, m_mode).unwrap();
+        csr.write(mtvec, 0x8000_0303, m_mode).unwrap();
+        assert_eq!(csr.read(mtvec, m_mode), Ok(0x8000_0300));
+        let tvec = csr.mtvec.get();
+        assert_eq!(
+            tvec.get_trap_pc(TrapType::MachineTimerInterrupt),
+            0x8000_0300
+        );
+    }
+
+    #[test]
+    fn sstatus_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imafdc");
+        config.set_s_mode();
+        config.set_u_mode();
+        let mut csr = CsrRegs::new(0, Rc::new(config));
+        let (m_mode, s_mode) = (PrivilegeLevels::Machine, PrivilegeLevels::Supervisor);
+        let (mstatus, sstatus) = (CSR_MSTATUS.into(), CSR_SSTATUS.into());
+        // MIE, MPP and TVM are not visible, UXL can not be changed
+        let m_fields = 1 << 3 | 3 << 11 | 1 << 20;
+        csr.write(mstatus, m_fields, m_mode).unwrap();
+        assert_eq!(csr.read(sstatus, s_mode), Ok(2 << 32));
+        csr.write(sstatus, u64::MAX, s_mode).unwrap();
+        let status = csr.read(mstatus, m_mode).unwrap();
+        assert_eq!(status & m_fields, m_fields);
+        assert_eq!((status >> 32) & 0b11, 2);
+        // SD is set by FS = dirty
+        let sstatus_val = csr.read(sstatus, s_mode).unwrap();
+        assert_eq!(
+            sstatus_val,
+            1 << 63 | 2 << 32 | 0b11 << 18 | 0b11 << 13 | 0x122
+        );
+        csr.write(sstatus, 0, s_mode).unwrap();
+        assert_eq!(csr.read(sstatus, s_mode), Ok(2 << 32));
+    }
+
+    #[test]
+    fn rv32_csr_test() {
+        let csr_regs = |isa: &str| {
+            let mut config = Config::new();
+            config.set_isa(isa);
+            config.set_u_mode();
+            CsrRegs::new(0, Rc::new(config))
+        };
+        let m_mode = PrivilegeLevels::Machine;
+        let (mcycle, mcycleh) = (CSR_MCYCLE.into(), CSR_MCYCLEH.into());
+        let mut csr = csr_regs("rv32imac");
+        csr.cycle.set(0x2_0000_0001);
+        assert_eq!(csr.read(mcycle, m_mode), Ok(1));
+        assert_eq!(csr.read(mcycleh, m_mode), Ok(2));
+        // the interrupt bit of mcause is bit 31
+        let mcause = CSR_MCAUSE.into();
+        csr.write(mcause, 0x8000_0007, m_mode).unwrap();
+        assert!(csr.mcause.get().interrupt());
+        assert_eq!(csr.read(mcause, m_mode), Ok(0x8000_0007));
+        // MXL=1 at bit 30, no SXL and UXL
+        assert_eq!(csr.read(CSR_MISA.into(), m_mode).unwrap() >> 30, 1);
+        csr.write(CSR_MSTATUS.into(), 1 << 3, m_mode).unwrap();
+        assert!(csr.xstatus.get().mie());
+        assert_eq!(csr.read(CSR_MSTATUSH.into(), m_mode), Ok(0));
+
+        let mut csr = csr_regs("rv64imac");
+        assert!(csr.read(CSR_MSTATUSH.into(), m_mode).is_err());
+        assert_eq!(csr.read(CSR_MISA.into(), m_mode).unwrap() >> 62, 2);
+    }
+}
@@ -1,14 +1,18 @@
-use alloc::boxed::Box;
+use core::cell::Cell;
+
+use alloc::{boxed::Box, vec::Vec};
 use bitfield_struct::bitfield;
 use enum_dispatch::enum_dispatch;
 
 use crate::{
     rv64core::inst::inst_base::{AccessType, PrivilegeLevels},
+    rv64core::mmu::pmp::Pmp,
     rv64core::traptype::TrapType,
-    tools::RcCell,
+    rv64core::trigger::Triggers,
+    tools::{RcCell, RcRefCell},
 };
 
-use super::inst::inst_base::RVerr;
+use super::inst::inst_base::{RVerr, CSR_TDATA1, CSR_TDATA2, CSR_TINFO, CSR_TSELECT};
 
 #[enum_dispatch]
 pub enum CsrEnum {
@@ -21,15 +25,28 @@ pub enum CsrEnum {
     Xip,
     Xcause,
     Medeleg,
-    // Mideleg,
+    Mideleg,
+    MaskedCSR,
+    Hstatus,
+    Hgatp,
+    Vsip,
+    Vsie,
     Mcounteren,
-    Menvcfg,
+    Envcfg,
+    Stateen,
     Mseccfg,
+    Seed,
     PMPcfg,
     PMPaddr,
+    TriggerCsr,
     Satp,
     Counter,
+    Mhpmevent,
+    Scountovf,
     Dcsr,
+    Fcsr,
+    Vcsr,
+    CustomCSR,
 }
 
 #[enum_dispatch(CsrEnum)]
@@ -54,6 +71,40 @@ pub trait Csr {
         }
     }
 }
+// a csr of the library user, see `CsrRegs::register_csr`. the privilege and
+// read-only bits of the address are checked first, like for any other csr
+pub trait CsrAccess {
+    fn read(&self) -> u64;
+    fn write(&mut self, _data: u64) {}
+}
+
+// (read, write) callbacks
+impl<R: Fn() -> u64, W: FnMut(u64)> CsrAccess for (R, W) {
+    fn read(&self) -> u64 {
+        (self.0)()
+    }
+    fn write(&mut self, data: u64) {
+        (self.1)(data)
+    }
+}
+
+pub struct CustomCSR(Box<dyn CsrAccess>);
+
+impl CustomCSR {
+    pub fn new(csr: impl CsrAccess + 'static) -> Self {
+        CustomCSR(Box::new(csr))
+    }
+}
+
+impl Csr for CustomCSR {
+    fn write(&mut self, data: u64) {
+        self.0.write(data);
+    }
+    fn read_raw(&self) -> u64 {
+        self.0.read()
+    }
+}
+
 fn write_with_mask(old: u64, data: u64, mask: u64) -> u64 {
     (old & !mask) | (data & mask)
 }
@@ -112,7 +163,13 @@ pub struct CsrAddr {
 // writes to the read-only bits are ignored.
 impl CsrAddr {
     pub fn check_privilege(&self, privi: PrivilegeLevels, access_type: AccessType) -> bool {
-        let has_privilege = (privi as u8) >= self.privilege();
+        // 0b10: the hypervisor and VS CSRs, accessible from HS-mode.
+        // the VS-mode and VU-mode accesses are checked by `CsrRegs`
+        let required = match self.privilege() {
+            0b10 => PrivilegeLevels::Supervisor as u8,
+            x => x,
+        };
+        let has_privilege = (privi as u8) >= required;
         // warn!("privi:{:?},{}", privi, has_privilege);
         match access_type {
             AccessType::Store(_) => self.not_read_only() && has_privilege,
@@ -127,7 +184,7 @@ impl CsrAddr {
 }
 
 #[bitfield(u64)]
-pub struct Misa {
+pub struct MisaIn {
     pub a: bool,
     pub b: bool,
     pub c: bool,
@@ -159,10 +216,29 @@ pub struct Misa {
     #[bits(2)]
     pub mxl: u8,
 }
-// read only
+// WARL, only the extensions in `mask` can be turned off and on again.
+// D depends on F
+pub struct Misa {
+    inner: RcCell<MisaIn>,
+    mask: u64,
+}
+
+impl Misa {
+    pub fn new(share: RcCell<MisaIn>, mask: u64) -> Self {
+        Self { inner: share, mask }
+    }
+}
+
 impl Csr for Misa {
+    fn write(&mut self, data: u64) {
+        let mut misa = MisaIn::from(write_with_mask(self.inner.get().0, data, self.mask));
+        if !misa.f() {
+            misa.set_d(false);
+        }
+        self.inner.set(misa);
+    }
     fn read_raw(&self) -> u64 {
-        self.0
+        self.inner.get().0
     }
 }
 
@@ -199,7 +275,9 @@ pub struct XstatusIn {
     pub sxl: u8,
     pub sbe: bool,
     pub mbe: bool,
-    #[bits(25)]
+    pub gva: bool,
+    pub mpv: bool,
+    #[bits(23)]
     _wpri4: u32,
     pub sd: bool,
 }
@@ -293,11 +371,17 @@ pub struct XtvecIn {
 
 impl XtvecIn {
     pub fn get_trap_pc(&self, trap: TrapType) -> u64 {
+        self.trap_pc(trap.idx())
+    }
+
+    // cause is the xcause value, the interrupts of any number are vectored to BASE + 4 * cause.
+    // the reserved modes can not be written
+    pub fn trap_pc(&self, cause: u64) -> u64 {
         let base = self.base() << 2;
+        let interrupt = cause >> 63 != 0;
         match self.mode() {
-            TvecMode::Vectored if trap.is_interupt() => base + 4 * trap.get_irq_num(),
-            TvecMode::Direct | TvecMode::Vectored => base,
-            TvecMode::Reserved => panic!("invalid tvec mode:{:?}", self.mode()),
+            TvecMode::Vectored if interrupt => base.wrapping_add(4 * (cause & !(1 << 63))),
+            _ => base,
         }
     }
 
@@ -322,8 +406,13 @@ impl Xtvec {
 }
 
 impl Csr for Xtvec {
+    // WARL, a reserved mode keeps the old mode, BASE is 4-byte aligned
     fn write(&mut self, data: u64) {
-        self.inner.set(XtvecIn::from(data));
+        let mut tvec = XtvecIn::from(data);
+        if tvec.mode() == TvecMode::Reserved {
+            tvec.set_mode(self.inner.get().mode());
+        }
+        self.inner.set(tvec);
     }
     fn read_raw(&self) -> u64 {
         self.inner.get().into()
@@ -334,17 +423,19 @@ impl Csr for Xtvec {
 pub struct XieIn {
     _pad0: bool,
     pub ssie: bool,
-    _pad1: bool,
+    pub vssie: bool,
     pub msie: bool,
     _pad2: bool,
     pub stie: bool,
-    _pad3: bool,
+    pub vstie: bool,
     pub mtie: bool,
     _pad4: bool,
     pub seie: bool,
-    _pad5: bool,
+    pub vseie: bool,
     pub meie: bool,
-    #[bits(52)]
+    pub sgeie: bool,
+    pub lcofie: bool,
+    #[bits(50)]
     _pad6: u64,
 }
 
@@ -376,20 +467,29 @@ impl Csr for Xie {
 pub struct XipIn {
     _pad0: bool,
     pub ssip: bool,
-    _pad1: bool,
+    pub vssip: bool,
     pub msip: bool,
     _pad2: bool,
     pub stip: bool,
-    _pad3: bool,
+    pub vstip: bool,
     pub mtip: bool,
     _pad4: bool,
     pub seip: bool,
-    _pad5: bool,
+    pub vseip: bool,
     pub meip: bool,
-    #[bits(52)]
+    pub sgeip: bool,
+    pub lcofip: bool,
+    #[bits(50)]
     _pad6: u64,
 }
-// standard interrupt priority is MEI, MSI, MTI, SEI, SSI, STI
+
+// the VS-level interrupts of the H extension: VSSIP, VSTIP, VSEIP
+pub const VS_INTERRUPTS: u64 = (1 << 2) | (1 << 6) | (1 << 10);
+pub const SGEI_INTERRUPT: u64 = 1 << 12;
+// Sscofpmf, the local counter overflow interrupt
+pub const LCOF_INTERRUPT: u64 = 1 << 13;
+
+// standard interrupt priority is MEI, MSI, MTI, SEI, SSI, STI, SGEI, VSEI, VSSI, VSTI, LCOFI
 impl XipIn {
     pub fn get_priority_interupt(&self) -> TrapType {
         if self.meip() {
@@ -404,17 +504,28 @@ impl XipIn {
             return TrapType::SupervisorSoftwareInterrupt;
         } else if self.stip() {
             return TrapType::SupervisorTimerInterrupt;
+        } else if self.sgeip() {
+            return TrapType::SupervisorGuestExternalInterrupt;
+        } else if self.vseip() {
+            return TrapType::VirtualSupervisorExternalInterrupt;
+        } else if self.vssip() {
+            return TrapType::VirtualSupervisorSoftwareInterrupt;
+        } else if self.vstip() {
+            return TrapType::VirtualSupervisorTimerInterrupt;
+        } else if self.lcofip() {
+            return TrapType::LocalCounterOverflowInterrupt;
         }
         panic!("no interupt:{self:?}");
     }
     pub fn set_irq(&mut self, irq_num: usize) {
         match irq_num {
-            1 => self.set_ssip(true),
-            3 => self.set_msip(true),
-            5 => self.set_stip(true),
-            7 => self.set_mtip(true),
-            9 => self.set_seip(true),
-            11 => self.set_meip(true),
+            1 | 2 | 3 | 5 | 6 | 7 | 9 | 10 | 11 | 12 | 13 => self.0 |= 1 << irq_num,
+            _ => panic!("invalid irq num:{}", irq_num),
+        }
+    }
+    pub fn clear_irq(&mut self, irq_num: usize) {
+        match irq_num {
+            1 | 2 | 3 | 5 | 6 | 7 | 9 | 10 | 11 | 12 | 13 => self.0 &= !(1 << irq_num),
             _ => panic!("invalid irq num:{}", irq_num),
         }
     }
@@ -423,11 +534,21 @@ impl XipIn {
 pub struct Xip {
     inner: RcCell<XipIn>,
     mask: u64,
+    wmask: u64,
 }
 
 impl Xip {
     pub fn new(share: RcCell<XipIn>, mask: u64) -> Self {
-        Self { inner: share, mask }
+        Self {
+            inner: share,
+            mask,
+            wmask: mask,
+        }
+    }
+    // only a part of the visible bits are writable, such as hip
+    pub fn with_wmask(mut self, wmask: u64) -> Self {
+        self.wmask = self.mask & wmask;
+        self
     }
 }
 impl Csr for Xip {
@@ -435,7 +556,7 @@ impl Csr for Xip {
         self.inner.get().0 & self.mask
     }
     fn write(&mut self, data: u64) {
-        let mask = self.mask;
+        let mask = self.wmask;
         let mut inner = self.inner.get();
         inner.0 = write_with_mask(inner.0, data, mask);
         self.inner.set(inner);
@@ -468,9 +589,58 @@ impl Csr for Xcause {
     }
 }
 
-pub type Mideleg = Xip;
 pub type MidelegIn = XipIn;
 
+// the bits in `ro_one` are read-only one, the VS-level interrupts
+// are always delegated if the H extension is implemented
+pub struct Mideleg {
+    inner: RcCell<MidelegIn>,
+    mask: u64,
+    ro_one: u64,
+}
+
+impl Mideleg {
+    pub fn new(share: RcCell<MidelegIn>, mask: u64, ro_one: u64) -> Self {
+        share.set(MidelegIn::from(u64::from(share.get()) | ro_one));
+        Self {
+            inner: share,
+            mask,
+            ro_one,
+        }
+    }
+}
+
+impl Csr for Mideleg {
+    fn write(&mut self, data: u64) {
+        let data = (data & self.mask) | self.ro_one;
+        self.inner.set(MidelegIn::from(data));
+    }
+    fn read_raw(&self) -> u64 {
+        self.inner.get().0
+    }
+}
+
+// a plain register with writable bits, such as hedeleg and hideleg
+pub struct MaskedCSR {
+    inner: RcCell<u64>,
+    mask: u64,
+}
+
+impl MaskedCSR {
+    pub fn new(share: RcCell<u64>, mask: u64) -> Self {
+        Self { inner: share, mask }
+    }
+}
+
+impl Csr for MaskedCSR {
+    fn write(&mut self, data: u64) {
+        self.inner.set(data & self.mask);
+    }
+    fn read_raw(&self) -> u64 {
+        self.inner.get()
+    }
+}
+
 #[bitfield(u64)]
 pub struct MedelegIn {
     pub inst_addr_misalign: bool,
@@ -545,7 +715,7 @@ impl Mcounteren {
 }
 
 #[bitfield(u64)]
-pub struct Menvcfg {
+pub struct EnvcfgIn {
     pub fiom: bool,
     #[bits(3)]
     _wpri0: u8,
@@ -559,9 +729,102 @@ pub struct Menvcfg {
     pub stce: bool,
 }
 
-impl Csr for Menvcfg {
+// menvcfg, senvcfg and henvcfg, senvcfg has no pbmte and stce.
+// the pbmte and stce of henvcfg read 0 while they are clear in menvcfg
+pub struct Envcfg {
+    inner: RcCell<EnvcfgIn>,
+    mask: u64,
+    menvcfg: Option<RcCell<EnvcfgIn>>,
+}
+
+impl Envcfg {
+    pub fn new(share: RcCell<EnvcfgIn>, mask: u64) -> Self {
+        Self {
+            inner: share,
+            mask,
+            menvcfg: None,
+        }
+    }
+
+    pub fn with_menvcfg(mut self, menvcfg: RcCell<EnvcfgIn>) -> Self {
+        self.menvcfg = Some(menvcfg);
+        self
+    }
+}
+
+impl Csr for Envcfg {
+    fn write(&mut self, data: u64) {
+        let mask = self.mask;
+        let mut inner = self.inner.get();
+        let pre_cbie = inner.cbie();
+        inner.0 = write_with_mask(inner.0, data, mask);
+        // cbie is WARL, 0b10 is reserved
+        if inner.cbie() == 0b10 {
+            inner.set_cbie(pre_cbie);
+        }
+        self.inner.set(inner);
+    }
     fn read_raw(&self) -> u64 {
-        self.0
+        let mut inner = EnvcfgIn::from(self.inner.get().0 & self.mask);
+        if let Some(menvcfg) = &self.menvcfg {
+            let menvcfg = menvcfg.get();
+            inner.set_pbmte(inner.pbmte() && menvcfg.pbmte());
+            inner.set_stce(inner.stce() && menvcfg.stce());
+        }
+        inner.0
+    }
+}
+
+// Smstateen: SE0 (the SE bit of stateen1-3) gates the lower level stateen csr,
+// ENVCFG gates senvcfg and henvcfg, FCSR gates the fp state of Zfinx
+pub const STATEEN_SE: u64 = 1 << 63;
+pub const STATEEN_ENVCFG: u64 = 1 << 62;
+pub const STATEEN_FCSR: u64 = 1 << 1;
+
+// mstateen, hstateen and sstateen, the bits cleared in the upper level are
+// read-only zero. sstateen is also masked by hstateen in VS-mode
+pub struct Stateen {
+    inner: RcCell<u64>,
+    mask: u64,
+    upper: Option<RcCell<u64>>,
+    virt_upper: Option<(RcCell<bool>, RcCell<u64>)>,
+}
+
+impl Stateen {
+    pub fn new(share: RcCell<u64>, mask: u64) -> Self {
+        Self {
+            inner: share,
+            mask,
+            upper: None,
+            virt_upper: None,
+        }
+    }
+    pub fn with_upper(mut self, upper: RcCell<u64>) -> Self {
+        self.upper = Some(upper);
+        self
+    }
+    pub fn with_virt_upper(mut self, virt: RcCell<bool>, upper: RcCell<u64>) -> Self {
+        self.virt_upper = Some((virt, upper));
+        self
+    }
+    fn mask(&self) -> u64 {
+        let upper = self.upper.as_ref().map_or(u64::MAX, |x| x.get());
+        let virt_upper = match &self.virt_upper {
+            Some((virt, upper)) if virt.get() => upper.get(),
+            _ => u64::MAX,
+        };
+        self.mask & upper & virt_upper
+    }
+}
+
+impl Csr for Stateen {
+    fn write(&mut self, data: u64) {
+        let mask = self.mask();
+        self.inner
+            .set(write_with_mask(self.inner.get(), data, mask));
+    }
+    fn read_raw(&self) -> u64 {
+        self.inner.get() & self.mask()
     }
 }
 
@@ -583,6 +846,50 @@ impl Csr for Mseccfg {
     }
 }
 
+// OPST = ES16, the 16 bits in seed[15:0] are valid
+const SEED_ES16: u64 = 0b10 << 30;
+
+// the entropy source (seed) stub, every read returns ES16 and 16 bits of a
+// xorshift generator. it is not a real entropy source, only for the probing of
+// the crypto libraries. mseccfg.SSEED/USEED are 0, so only M-mode can access it
+pub struct Seed {
+    state: Cell<u64>,
+}
+
+impl Seed {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            // xorshift gets stuck at zero
+            state: Cell::new(seed.max(1)),
+        }
+    }
+}
+
+impl Csr for Seed {
+    fn read(&self) -> u64 {
+        let mut x = self.state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state.set(x);
+        self.read_raw()
+    }
+    fn read_raw(&self) -> u64 {
+        SEED_ES16 | (self.state.get() & 0xffff)
+    }
+    fn check_permission(
+        &self,
+        _addr: u64,
+        privi: PrivilegeLevels,
+        _access_type: AccessType,
+    ) -> Result<(), RVerr> {
+        match privi {
+            PrivilegeLevels::Machine => Ok(()),
+            _ => Err(RVerr::CsrNotPermit),
+        }
+    }
+}
+
 #[bitfield(u8)]
 pub struct PMPcfgIn {
     pub r: bool,
@@ -595,72 +902,86 @@ pub struct PMPcfgIn {
     pub l: bool,
 }
 
-impl PMPcfgIn {
-    const fn into_bits(self) -> u64 {
-        self.0 as u64
-    }
-    const fn from_bits(v: u64) -> Self {
-        let mut new = Self::new();
-        new.0 = v as u8;
-        new
-    }
+// pmpcfg`reg`, the odd ones are the high halves on rv32
+pub struct PMPcfg {
+    pmp: RcRefCell<Pmp>,
+    reg: usize,
 }
 
-// impl From<u64> for PMPcfgIn {
-//     fn from(value: u64) -> Self {
-//         PMPcfgIn::from(value as u8)
-//     }
-// }
-
-// impl From<PMPcfgIn> for u64 {
-//     fn from(val: PMPcfgIn) -> Self {
-//         val.0 as u64
-//     }
-// }
-pub type PMPcfgShare = RcCell<Box<PMPcfg>>;
-#[bitfield(u64)]
-pub struct PMPcfg {
-    #[bits(8)]
-    pub pmp0cfg: PMPcfgIn,
-    #[bits(8)]
-    pub pmp1cfg: PMPcfgIn,
-    #[bits(8)]
-    pub pmp2cfg: PMPcfgIn,
-    #[bits(8)]
-    pub pmp3cfg: PMPcfgIn,
-    #[bits(8)]
-    pub pmp4cfg: PMPcfgIn,
-    #[bits(8)]
-    pub pmp5cfg: PMPcfgIn,
-    #[bits(8)]
-    pub pmp6cfg: PMPcfgIn,
-    #[bits(8)]
-    pub pmp7cfg: PMPcfgIn,
+impl PMPcfg {
+    pub fn new(pmp: RcRefCell<Pmp>, reg: usize) -> Self {
+        Self { pmp, reg }
+    }
 }
 
 impl Csr for PMPcfg {
+    fn write(&mut self, data: u64) {
+        self.pmp.borrow_mut().write_cfg(self.reg, data);
+    }
     fn read_raw(&self) -> u64 {
-        self.0
+        self.pmp.borrow().read_cfg(self.reg)
     }
 }
 
-#[bitfield(u64)]
 pub struct PMPaddr {
-    #[bits(54)]
-    pub address_55_2: u64,
-    #[bits(10)]
-    _pad: u32,
+    pmp: RcRefCell<Pmp>,
+    idx: usize,
+}
+
+impl PMPaddr {
+    pub fn new(pmp: RcRefCell<Pmp>, idx: usize) -> Self {
+        Self { pmp, idx }
+    }
 }
 
 impl Csr for PMPaddr {
+    fn write(&mut self, data: u64) {
+        self.pmp.borrow_mut().write_addr(self.idx, data);
+    }
     fn read_raw(&self) -> u64 {
-        self.0
+        self.pmp.borrow().read_addr(self.idx)
+    }
+}
+
+// tselect, tdata1, tdata2, tdata3 and tinfo of the trigger module
+pub struct TriggerCsr {
+    triggers: RcRefCell<Triggers>,
+    addr: u16,
+}
+
+impl TriggerCsr {
+    pub fn new(triggers: RcRefCell<Triggers>, addr: u16) -> Self {
+        Self { triggers, addr }
+    }
+}
+
+impl Csr for TriggerCsr {
+    fn write(&mut self, data: u64) {
+        let mut triggers = self.triggers.borrow_mut();
+        match self.addr {
+            CSR_TSELECT => triggers.write_tselect(data),
+            CSR_TDATA1 => triggers.write_tdata1(data),
+            CSR_TDATA2 => triggers.write_tdata2(data),
+            _ => (),
+        }
+    }
+    fn read_raw(&self) -> u64 {
+        let triggers = self.triggers.borrow();
+        match self.addr {
+            CSR_TSELECT => triggers.tselect(),
+            CSR_TDATA1 => triggers.tdata1(),
+            CSR_TDATA2 => triggers.tdata2(),
+            CSR_TINFO => triggers.tinfo(),
+            _ => 0,
+        }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StapMode {
     Bare = 0,
+    // the rv32 satp.MODE is one bit, 1 is Sv32
+    Sv32 = 1,
     Sv39 = 8,
     Sv48 = 9,
     Sv57 = 10,
@@ -671,6 +992,7 @@ impl StapMode {
     pub fn get_levels(&self) -> usize {
         match self {
             StapMode::Bare => 0,
+            StapMode::Sv32 => 2,
             StapMode::Sv39 => 3,
             StapMode::Sv48 => 4,
             StapMode::Sv57 => 5,
@@ -681,6 +1003,7 @@ impl StapMode {
     pub fn get_ptesize(&self) -> usize {
         match self {
             StapMode::Bare => 0,
+            StapMode::Sv32 => 4,
             StapMode::Sv39 => 8,
             StapMode::Sv48 => 8,
             StapMode::Sv57 => 8,
@@ -694,6 +1017,7 @@ impl StapMode {
     const fn from_bits(v: u64) -> Self {
         match v {
             0 => StapMode::Bare,
+            1 => StapMode::Sv32,
             8 => StapMode::Sv39,
             9 => StapMode::Sv48,
             10 => StapMode::Sv57,
@@ -753,23 +1077,22 @@ impl Satp {
 }
 
 impl Satp {
-    fn unsupport_mod(&self, new_mode: StapMode) -> bool {
-        new_mode as usize > self.max_satp_mode as usize
+    fn unsupport_mod(&self, new_mode: u64) -> bool {
+        let supported = match self.max_satp_mode {
+            StapMode::Sv32 => matches!(new_mode, 0 | 1),
+            max => matches!(new_mode, 0 | 8 | 9 | 10) && new_mode <= max as u64,
+        };
+        !supported
     }
 }
 
 impl Csr for Satp {
+    // the write of an unsupported mode has no effect
     fn write(&mut self, data: u64) {
-        let new_val = SatpIn::from(data);
-
-        let mut stap = self.inner.get();
-        if !self.unsupport_mod(new_val.mode()) {
-            stap.set_mode(new_val.mode());
+        if self.unsupport_mod(data >> 60) {
+            return;
         }
-        stap.set_asid(new_val.asid());
-        stap.set_ppn(new_val.ppn());
-
-        self.inner.set(stap);
+        self.inner.set(SatpIn::from(data));
     }
     fn read_raw(&self) -> u64 {
         self.inner.get().into()
@@ -796,6 +1119,203 @@ impl Csr for Satp {
     }
 }
 
+#[bitfield(u64)]
+pub struct HstatusIn {
+    #[bits(5)]
+    _wpri0: u8,
+    pub vsbe: bool,
+    pub gva: bool,
+    pub spv: bool,
+    pub spvp: bool,
+    pub hu: bool,
+    #[bits(2)]
+    _wpri1: u8,
+    #[bits(6)]
+    pub vgein: u8,
+    #[bits(2)]
+    _wpri2: u8,
+    pub vtvm: bool,
+    pub vtw: bool,
+    pub vtsr: bool,
+    #[bits(9)]
+    _wpri3: u16,
+    #[bits(2)]
+    pub vsxl: u8,
+    #[bits(30)]
+    _wpri4: u32,
+}
+
+impl HstatusIn {
+    // the privilege of the HLV and HSV accesses
+    pub fn get_spvp_priv(&self) -> PrivilegeLevels {
+        match self.spvp() {
+            true => PrivilegeLevels::Supervisor,
+            false => PrivilegeLevels::User,
+        }
+    }
+}
+
+// no guest external interrupt (GEILEN = 0), VSXL is fixed to 64
+pub struct Hstatus {
+    inner: RcCell<HstatusIn>,
+}
+
+impl Hstatus {
+    pub fn new(share: RcCell<HstatusIn>) -> Self {
+        Self { inner: share }
+    }
+}
+
+impl Csr for Hstatus {
+    fn write(&mut self, data: u64) {
+        let mask = HstatusIn::new()
+            .with_gva(true)
+            .with_spv(true)
+            .with_spvp(true)
+            .with_hu(true)
+            .with_vtvm(true)
+            .with_vtw(true)
+            .with_vtsr(true);
+        let inner = self.inner.get();
+        let new_val = write_with_mask(inner.into(), data, mask.into());
+        self.inner.set(HstatusIn::from(new_val).with_vsxl(2));
+    }
+    fn read_raw(&self) -> u64 {
+        self.inner.get().into()
+    }
+}
+
+#[bitfield(u64)]
+pub struct HgatpIn {
+    #[bits(44)]
+    pub ppn: u64,
+    #[bits(14)]
+    pub vmid: u16,
+    #[bits(2)]
+    _pad: u8,
+    #[bits(4)]
+    pub mode: u8,
+}
+
+impl HgatpIn {
+    // Sv39x4, Sv48x4 and Sv57x4 use the same encodings as Sv39, Sv48 and Sv57
+    pub fn stage_mode(&self) -> StapMode {
+        match self.mode() {
+            8 => StapMode::Sv39,
+            9 => StapMode::Sv48,
+            10 => StapMode::Sv57,
+            _ => StapMode::Bare,
+        }
+    }
+}
+
+pub struct Hgatp {
+    inner: RcCell<HgatpIn>,
+    xstatus: RcCell<XstatusIn>,
+    max_mode: StapMode,
+}
+
+impl Hgatp {
+    pub fn new(share: RcCell<HgatpIn>, xstatus: RcCell<XstatusIn>, max_mode: StapMode) -> Self {
+        Self {
+            inner: share,
+            xstatus,
+            max_mode,
+        }
+    }
+}
+
+impl Csr for Hgatp {
+    fn write(&mut self, data: u64) {
+        let new_val = HgatpIn::from(data);
+        let mut hgatp = self.inner.get();
+        // mode is WARL, an unsupported mode keeps the old one
+        let mode_ok = matches!(new_val.mode(), 0 | 8 | 9 | 10)
+            && new_val.stage_mode() as usize <= self.max_mode as usize;
+        if mode_ok {
+            hgatp.set_mode(new_val.mode());
+        }
+        hgatp.set_vmid(new_val.vmid());
+        // the root page table is 16KiB aligned
+        hgatp.set_ppn(new_val.ppn() & !0b11);
+        self.inner.set(hgatp);
+    }
+    fn read_raw(&self) -> u64 {
+        self.inner.get().into()
+    }
+    fn check_permission(
+        &self,
+        _addr: u64,
+        privi: PrivilegeLevels,
+        _access_type: AccessType,
+    ) -> Result<(), RVerr> {
+        let require_priv = if self.xstatus.get().tvm() {
+            PrivilegeLevels::Machine
+        } else {
+            PrivilegeLevels::Supervisor
+        };
+        match require_priv.check_priv(privi) {
+            true => Ok(()),
+            false => Err(RVerr::CsrNotPermit),
+        }
+    }
+}
+
+// vsip and vsie: the VS-level bits of hip/hie delegated by hideleg,
+// shifted to the S-level positions
+pub struct Vsip {
+    inner: RcCell<XipIn>,
+    hideleg: RcCell<u64>,
+}
+
+impl Vsip {
+    pub fn new(share: RcCell<XipIn>, hideleg: RcCell<u64>) -> Self {
+        Self {
+            inner: share,
+            hideleg,
+        }
+    }
+}
+
+impl Csr for Vsip {
+    fn write(&mut self, data: u64) {
+        // only VSSIP is writable
+        let mask = (1 << 2) & self.hideleg.get();
+        let mut inner = self.inner.get();
+        inner.0 = write_with_mask(inner.0, data << 1, mask);
+        self.inner.set(inner);
+    }
+    fn read_raw(&self) -> u64 {
+        (self.inner.get().0 & self.hideleg.get() & VS_INTERRUPTS) >> 1
+    }
+}
+
+pub struct Vsie {
+    inner: RcCell<XieIn>,
+    hideleg: RcCell<u64>,
+}
+
+impl Vsie {
+    pub fn new(share: RcCell<XieIn>, hideleg: RcCell<u64>) -> Self {
+        Self {
+            inner: share,
+            hideleg,
+        }
+    }
+}
+
+impl Csr for Vsie {
+    fn write(&mut self, data: u64) {
+        let mask = VS_INTERRUPTS & self.hideleg.get();
+        let mut inner = self.inner.get();
+        inner.0 = write_with_mask(inner.0, data << 1, mask);
+        self.inner.set(inner);
+    }
+    fn read_raw(&self) -> u64 {
+        (self.inner.get().0 & self.hideleg.get() & VS_INTERRUPTS) >> 1
+    }
+}
+
 pub struct Counter {
     inner: RcCell<u64>,
 }
@@ -812,6 +1332,116 @@ impl Csr for Counter {
     }
 }
 
+// the events of mhpmcounter3-31, 0 counts nothing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HpmEvent {
+    Cycle = 1,
+    Instret = 2,
+    IcacheMiss = 3,
+    DcacheMiss = 4,
+    TlbMiss = 5,
+    BranchTaken = 6,
+    // the exceptions and the interrupts taken
+    Trap = 7,
+    // the retired loads and stores, the AMOs are neither
+    Load = 8,
+    Store = 9,
+}
+pub const HPM_EVENT_MAX: u64 = HpmEvent::Store as u64;
+pub const HPM_COUNTERS: usize = 29;
+
+// Sscofpmf: the overflow flag and the privilege mode filters of mhpmevent
+#[bitfield(u64)]
+pub struct MhpmeventIn {
+    #[bits(56)]
+    pub event: u64,
+    #[bits(2)]
+    _wpri: u8,
+    pub vuinh: bool,
+    pub vsinh: bool,
+    pub uinh: bool,
+    pub sinh: bool,
+    pub minh: bool,
+    pub of: bool,
+}
+
+// the unsupported events read back as 0. `active` has a bit for every counter
+// with an event, the core skips the idle counters
+pub struct Mhpmevent {
+    inner: RcCell<MhpmeventIn>,
+    mask: u64,
+    active: RcCell<u32>,
+    idx: usize,
+}
+
+impl Mhpmevent {
+    pub fn new(share: RcCell<MhpmeventIn>, mask: u64, active: RcCell<u32>, idx: usize) -> Self {
+        Self {
+            inner: share,
+            mask,
+            active,
+            idx,
+        }
+    }
+}
+
+impl Csr for Mhpmevent {
+    fn write(&mut self, data: u64) {
+        let mut inner = MhpmeventIn::from(write_with_mask(self.inner.get().0, data, self.mask));
+        if inner.event() > HPM_EVENT_MAX {
+            inner.set_event(0);
+        }
+        self.inner.set(inner);
+        let active = self.active.get() & !(1 << self.idx);
+        self.active
+            .set(active | ((inner.event() != 0) as u32) << self.idx);
+    }
+    fn read_raw(&self) -> u64 {
+        self.inner.get().0
+    }
+}
+
+// scountovf: bit 3-31 are the OF bits of mhpmevent3-31, the counters not
+// enabled by mcounteren (and hcounteren in VS-mode) read 0
+pub struct Scountovf {
+    events: Vec<RcCell<MhpmeventIn>>,
+    mcounteren: RcCell<u64>,
+    hcounteren: RcCell<u64>,
+    virt: RcCell<bool>,
+}
+
+impl Scountovf {
+    pub fn new(
+        events: Vec<RcCell<MhpmeventIn>>,
+        mcounteren: RcCell<u64>,
+        hcounteren: RcCell<u64>,
+        virt: RcCell<bool>,
+    ) -> Self {
+        Self {
+            events,
+            mcounteren,
+            hcounteren,
+            virt,
+        }
+    }
+}
+
+impl Csr for Scountovf {
+    fn read_raw(&self) -> u64 {
+        let of = self
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.get().of())
+            .fold(0, |acc, (idx, _)| acc | 1 << (idx + 3));
+        let hcounteren = match self.virt.get() {
+            true => self.hcounteren.get(),
+            false => u64::MAX,
+        };
+        of & self.mcounteren.get() & hcounteren
+    }
+}
+
 #[bitfield(u32)]
 pub struct DcsrIn {
     #[bits(2)]
@@ -881,3 +1511,151 @@ impl Csr for Dcsr {
         self.inner.set(old_val);
     }
 }
+
+#[bitfield(u64)]
+pub struct FcsrIn {
+    pub nx: bool,
+    pub uf: bool,
+    pub of: bool,
+    pub dz: bool,
+    pub nv: bool,
+    #[bits(3)]
+    pub frm: u8,
+    #[bits(56)]
+    _pad: u64,
+}
+
+impl FcsrIn {
+    pub fn fflags(&self) -> u8 {
+        (self.0 & 0x1f) as u8
+    }
+    pub fn set_fflags(&mut self, flags: u8) {
+        self.0 = (self.0 & !0x1f) | (flags & 0x1f) as u64;
+    }
+}
+
+// fflags, frm and fcsr are views of the same register
+pub struct Fcsr {
+    inner: RcCell<FcsrIn>,
+    xstatus: RcCell<XstatusIn>,
+    shift: u64,
+    mask: u64,
+    zfinx: bool,
+    // V and vsstatus, with the H extension
+    virt: Option<(RcCell<bool>, RcCell<XstatusIn>)>,
+}
+
+impl Fcsr {
+    pub fn new(share: RcCell<FcsrIn>, xstatus: RcCell<XstatusIn>, shift: u64, mask: u64) -> Self {
+        Fcsr {
+            inner: share,
+            xstatus,
+            shift,
+            mask,
+            zfinx: false,
+            virt: None,
+        }
+    }
+    // Zfinx has no mstatus.FS, the fp csrs are always accessible
+    pub fn with_zfinx(mut self, zfinx: bool) -> Self {
+        self.zfinx = zfinx;
+        self
+    }
+    // with V=1 vsstatus.FS has to be on as well
+    pub fn with_virt(mut self, virt: RcCell<bool>, vsstatus: RcCell<XstatusIn>) -> Self {
+        self.virt = Some((virt, vsstatus));
+        self
+    }
+}
+
+impl Csr for Fcsr {
+    fn read_raw(&self) -> u64 {
+        (self.inner.get().0 >> self.shift) & self.mask
+    }
+    fn write(&mut self, data: u64) {
+        let mut inner = self.inner.get();
+        inner.0 = write_with_mask(inner.0, data << self.shift, self.mask << self.shift);
+        self.inner.set(inner);
+        if self.zfinx {
+            return;
+        }
+        // fp state is modified, set mstatus.FS to dirty
+        let mut status = self.xstatus.get();
+        status.set_fs(0b11);
+        status.update_sd();
+        self.xstatus.set(status);
+    }
+    // fp csrs are not accessible when mstatus.FS is off, or vsstatus.FS
+    // in VS-mode and VU-mode
+    fn check_permission(
+        &self,
+        addr: u64,
+        privi: PrivilegeLevels,
+        access_type: AccessType,
+    ) -> Result<(), RVerr> {
+        assert!(addr < 4096);
+        let csr_addr = CsrAddr::from(addr as u16);
+        let vs_off = self
+            .virt
+            .as_ref()
+            .is_some_and(|(virt, vsstatus)| virt.get() && vsstatus.get().fs() == 0);
+        let fs_on = self.zfinx || (self.xstatus.get().fs() != 0 && !vs_off);
+        match fs_on && csr_addr.check_privilege(privi, access_type) {
+            true => Ok(()),
+            false => Err(RVerr::CsrNotPermit),
+        }
+    }
+}
+
+// vstart, vxsat, vxrm, vcsr, vl, vtype and vlenb.
+// vxsat and vxrm are views of vcsr, vl, vtype and vlenb are read-only
+pub struct Vcsr {
+    inner: RcCell<u64>,
+    xstatus: RcCell<XstatusIn>,
+    shift: u64,
+    mask: u64,
+}
+
+impl Vcsr {
+    pub fn new(share: RcCell<u64>, xstatus: RcCell<XstatusIn>, shift: u64, mask: u64) -> Self {
+        Vcsr {
+            inner: share,
+            xstatus,
+            shift,
+            mask,
+        }
+    }
+}
+
+impl Csr for Vcsr {
+    fn read_raw(&self) -> u64 {
+        (self.inner.get() >> self.shift) & self.mask
+    }
+    fn write(&mut self, data: u64) {
+        let inner = self.inner.get();
+        self.inner.set(write_with_mask(
+            inner,
+            data << self.shift,
+            self.mask << self.shift,
+        ));
+        // vector state is modified, set mstatus.VS to dirty
+        let mut status = self.xstatus.get();
+        status.set_vs(0b11);
+        status.update_sd();
+        self.xstatus.set(status);
+    }
+    // vector csrs are not accessible when mstatus.VS is off
+    fn check_permission(
+        &self,
+        addr: u64,
+        privi: PrivilegeLevels,
+        access_type: AccessType,
+    ) -> Result<(), RVerr> {
+        assert!(addr < 4096);
+        let csr_addr = CsrAddr::from(addr as u16);
+        match self.xstatus.get().vs() != 0 && csr_addr.check_privilege(privi, access_type) {
+            true => Ok(()),
+            false => Err(RVerr::CsrNotPermit),
+        }
+    }
+}
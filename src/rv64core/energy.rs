@@ -0,0 +1,287 @@
+use alloc::{format, string::String, vec::Vec};
+
+use super::{bus::BusPerf, inst::inst_base::is_compressed_instruction, pipeline::PipelineStats};
+
+// a rough energy estimate: every retired instruction, pipeline bubble and
+// bus access costs a fixed weight in pJ. the default weights are made up,
+// they are in the range of the published numbers of small in-order cores
+// and only good for comparing two runs, not for a real power budget
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstClass {
+    Alu,
+    MulDiv,
+    Load,
+    Store,
+    Branch,
+    Jump,
+    Fp,
+    Vector,
+    Atomic,
+    System,
+    Other,
+}
+
+const CLASSES: [InstClass; 11] = [
+    InstClass::Alu,
+    InstClass::MulDiv,
+    InstClass::Load,
+    InstClass::Store,
+    InstClass::Branch,
+    InstClass::Jump,
+    InstClass::Fp,
+    InstClass::Vector,
+    InstClass::Atomic,
+    InstClass::System,
+    InstClass::Other,
+];
+
+impl InstClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstClass::Alu => "alu",
+            InstClass::MulDiv => "muldiv",
+            InstClass::Load => "load",
+            InstClass::Store => "store",
+            InstClass::Branch => "branch",
+            InstClass::Jump => "jump",
+            InstClass::Fp => "fp",
+            InstClass::Vector => "vector",
+            InstClass::Atomic => "atomic",
+            InstClass::System => "system",
+            InstClass::Other => "other",
+        }
+    }
+
+    pub fn classify(word: u32) -> InstClass {
+        if is_compressed_instruction(word) {
+            return match (word & 0b11, (word >> 13) & 0b111) {
+                (0b00, 0b000) | (0b01, 0b000..=0b100) | (0b10, 0b000) => InstClass::Alu,
+                (0b00, 0b001..=0b011) | (0b10, 0b001..=0b011) => InstClass::Load,
+                (0b00, 0b101..=0b111) | (0b10, 0b101..=0b111) => InstClass::Store,
+                (0b01, 0b101) => InstClass::Jump,
+                (0b01, 0b110 | 0b111) => InstClass::Branch,
+                // c.jr, c.jalr, c.ebreak, c.mv, c.add
+                (0b10, 0b100) => match ((word >> 7) & 0x1f, (word >> 2) & 0x1f) {
+                    (0, 0) => InstClass::System,
+                    (_, 0) => InstClass::Jump,
+                    _ => InstClass::Alu,
+                },
+                _ => InstClass::Other,
+            };
+        }
+        match word & 0x7f {
+            // OP,OP-32 with funct7 = 1 are M
+            0b0110011 | 0b0111011 if word >> 25 == 1 => InstClass::MulDiv,
+            0b0110011 | 0b0111011 | 0b0010011 | 0b0011011 | 0b0110111 | 0b0010111 => InstClass::Alu,
+            0b0000011 | 0b0000111 => InstClass::Load,
+            0b0100011 | 0b0100111 => InstClass::Store,
+            0b1100011 => InstClass::Branch,
+            0b1101111 | 0b1100111 => InstClass::Jump,
+            0b1000011 | 0b1000111 | 0b1001011 | 0b1001111 | 0b1010011 => InstClass::Fp,
+            0b1010111 => InstClass::Vector,
+            0b0101111 => InstClass::Atomic,
+            0b1110011 => InstClass::System,
+            _ => InstClass::Other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnergyWeights {
+    // pJ per retired instruction, indexed like CLASSES
+    inst: [f64; 11],
+    // pJ per stall cycle and per flushed branch or trap
+    stall: f64,
+    flush: f64,
+    // pJ per bus access and per byte moved, `bus.<device>` overrides the access weight
+    bus: f64,
+    byte: f64,
+    devices: Vec<(String, f64)>,
+}
+
+impl Default for EnergyWeights {
+    fn default() -> Self {
+        EnergyWeights {
+            inst: [1.0, 4.0, 10.0, 10.0, 1.5, 2.0, 3.5, 8.0, 15.0, 2.5, 1.0],
+            stall: 0.5,
+            flush: 2.0,
+            bus: 5.0,
+            byte: 0.5,
+            devices: Vec::new(),
+        }
+    }
+}
+
+impl EnergyWeights {
+    // "load=12,fp=4,stall=0.8,bus.RAM=20", the keys not listed keep the default weight
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut weights = EnergyWeights::default();
+        for item in s.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or(format!("energy weight '{item}' is not key=value"))?;
+            let value: f64 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("energy weight '{item}' is not a number"))?;
+            let key = key.trim();
+            match key {
+                "stall" => weights.stall = value,
+                "flush" => weights.flush = value,
+                "bus" => weights.bus = value,
+                "byte" => weights.byte = value,
+                _ => {
+                    if let Some(device) = key.strip_prefix("bus.") {
+                        weights.devices.push((device.into(), value));
+                    } else if let Some(idx) = CLASSES.iter().position(|x| x.name() == key) {
+                        weights.inst[idx] = value;
+                    } else {
+                        return Err(format!("unknown energy weight '{key}'"));
+                    }
+                }
+            }
+        }
+        Ok(weights)
+    }
+
+    fn device(&self, name: &str) -> f64 {
+        self.devices
+            .iter()
+            .rev()
+            .find(|(x, _)| x == name)
+            .map_or(self.bus, |(_, w)| *w)
+    }
+}
+
+pub struct EnergyModel {
+    weights: EnergyWeights,
+    counts: [u64; 11],
+}
+
+impl EnergyModel {
+    pub fn new(weights: EnergyWeights) -> Self {
+        EnergyModel {
+            weights,
+            counts: [0; 11],
+        }
+    }
+
+    pub fn record(&mut self, word: u32) {
+        let class = InstClass::classify(word);
+        self.counts[class as usize] += 1;
+    }
+
+    pub fn count(&self, class: InstClass) -> u64 {
+        self.counts[class as usize]
+    }
+
+    // (item, events, pJ) of the instruction classes, the pipeline bubbles and the devices
+    pub fn breakdown(
+        &self,
+        pipeline: Option<PipelineStats>,
+        devices: &[(&'static str, BusPerf)],
+    ) -> Vec<(String, u64, f64)> {
+        let mut ret: Vec<(String, u64, f64)> = CLASSES
+            .iter()
+            .map(|x| {
+                let n = self.count(*x);
+                (
+                    x.name().into(),
+                    n,
+                    n as f64 * self.weights.inst[*x as usize],
+                )
+            })
+            .collect();
+        if let Some(stats) = pipeline {
+            let stall = stats.stalls as f64 * self.weights.stall;
+            let flush = stats.flushes as f64 * self.weights.flush;
+            ret.push(("stall".into(), stats.stalls, stall));
+            ret.push(("flush".into(), stats.flushes, flush));
+        }
+        devices.iter().for_each(|(name, perf)| {
+            let energy = perf.accesses() as f64 * self.weights.device(name)
+                + perf.bytes() as f64 * self.weights.byte;
+            ret.push((format!("bus.{name}"), perf.accesses(), energy));
+        });
+        ret
+    }
+
+    pub fn report(
+        &self,
+        pipeline: Option<PipelineStats>,
+        devices: &[(&'static str, BusPerf)],
+    ) -> String {
+        let breakdown = self.breakdown(pipeline, devices);
+        let total: f64 = breakdown.iter().map(|(_, _, e)| e).sum();
+        let mut report = String::from("-------------Energy Estimate-------------\n");
+        report += &format!(
+            "{:20} {:>14} {:>16} {:>8}\n",
+            "item", "events", "pJ", "share"
+        );
+        breakdown
+            .iter()
+            .filter(|(_, n, _)| *n != 0)
+            .for_each(|(name, n, e)| {
+                let share = if total > 0.0 { e / total * 100.0 } else { 0.0 };
+                report += &format!("{:20} {:>14} {:>16.1} {:>7.2}%\n", name, n, e, share);
+            });
+        let instret: u64 = self.counts.iter().sum();
+        report += &format!(
+            "total: {:.3} uJ, {:.2} pJ/inst\n",
+            total / 1e6,
+            total / instret.max(1) as f64
+        );
+        report
+    }
+}
+
+#[cfg(test)]
+mod test_energy {
+    use super::{EnergyModel, EnergyWeights, InstClass};
+    use crate::rv64core::pipeline::PipelineStats;
+
+    #[test]
+    fn classify_test() {
+        let class = InstClass::classify;
+        // add, mul, ld, sd, beq, jal, fadd.d, amoadd.d, csrrw
+        assert_eq!(class(0x00b50533), InstClass::Alu);
+        assert_eq!(class(0x02b50533), InstClass::MulDiv);
+        assert_eq!(class(0x00053583), InstClass::Load);
+        assert_eq!(class(0x00b53023), InstClass::Store);
+        assert_eq!(class(0x00b50463), InstClass::Branch);
+        assert_eq!(class(0x0080006f), InstClass::Jump);
+        assert_eq!(class(0x02b57553), InstClass::Fp);
+        assert_eq!(class(0x00b5352f), InstClass::Atomic);
+        assert_eq!(class(0x34051573), InstClass::System);
+        // c.ld, c.jr ra, c.add, c.ebreak
+        assert_eq!(class(0x610c), InstClass::Load);
+        assert_eq!(class(0x8082), InstClass::Jump);
+        assert_eq!(class(0x952e), InstClass::Alu);
+        assert_eq!(class(0x9002), InstClass::System);
+    }
+
+    #[test]
+    fn energy_test() {
+        let weights = EnergyWeights::parse("alu=2, load=20,stall=1,bus.RAM=3,byte=0").unwrap();
+        let mut energy = EnergyModel::new(weights);
+        energy.record(0x00b50533);
+        energy.record(0x00b50533);
+        energy.record(0x00053583);
+        let stats = PipelineStats {
+            stalls: 3,
+            ..Default::default()
+        };
+        let mut ram = crate::rv64core::bus::BusPerf::default();
+        ram.reads[3] = 10;
+        let breakdown = energy.breakdown(Some(stats), &[("RAM", ram)]);
+        let total: f64 = breakdown.iter().map(|(_, _, e)| e).sum();
+        assert_eq!(total, 2.0 * 2.0 + 20.0 + 3.0 + 30.0);
+        assert!(energy
+            .report(Some(stats), &[("RAM", ram)])
+            .contains("bus.RAM"));
+
+        assert!(EnergyWeights::parse("alu").is_err());
+        assert!(EnergyWeights::parse("cache=1").is_err());
+        assert!(EnergyWeights::parse("alu=x").is_err());
+    }
+}
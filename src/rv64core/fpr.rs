@@ -0,0 +1,130 @@
+use core::fmt;
+
+use crate::{rv64core::csr_regs_define::XstatusIn, tools::RcCell};
+
+// floating-point register file, registers are FLEN(64) bits wide.
+// narrower values are NaN-boxed (the unused upper bits are all ones)
+pub struct Fpr {
+    regs: [u64; 32],
+    // any write sets mstatus.FS to dirty
+    xstatus: RcCell<XstatusIn>,
+    // Zfinx: the F and D instructions use the integer registers,
+    // this file is unused and mstatus.FS is read-only zero
+    in_x: bool,
+}
+
+impl Fpr {
+    pub fn new(xstatus: RcCell<XstatusIn>, in_x: bool) -> Self {
+        Fpr {
+            regs: [0; 32],
+            xstatus,
+            in_x,
+        }
+    }
+
+    pub fn in_x(&self) -> bool {
+        self.in_x
+    }
+
+    pub fn set_dirty(&self) {
+        if self.in_x {
+            return;
+        }
+        let mut status = self.xstatus.get();
+        status.set_fs(0b11);
+        status.update_sd();
+        self.xstatus.set(status);
+    }
+
+    pub fn read(&self, idx: u64) -> u64 {
+        assert!(idx < 32);
+        self.regs.get(idx as usize).copied().unwrap_or(0)
+    }
+    pub fn write(&mut self, idx: u64, data: u64) {
+        assert!(idx < 32);
+        if let Some(x) = self.regs.get_mut(idx as usize) {
+            *x = data;
+        }
+        self.set_dirty();
+    }
+
+    // a value which is not properly NaN-boxed is read as the canonical NaN
+    pub fn read_f32(&self, idx: u64) -> u32 {
+        let data = self.read(idx);
+        if data >> 32 == 0xffff_ffff {
+            data as u32
+        } else {
+            0x7fc0_0000
+        }
+    }
+    pub fn write_f32(&mut self, idx: u64, data: u32) {
+        self.write(idx, 0xffff_ffff_0000_0000 | data as u64);
+    }
+    pub fn read_f16(&self, idx: u64) -> u16 {
+        let data = self.read(idx);
+        if data >> 16 == 0xffff_ffff_ffff {
+            data as u16
+        } else {
+            0x7e00
+        }
+    }
+    pub fn write_f16(&mut self, idx: u64, data: u16) {
+        self.write(idx, 0xffff_ffff_ffff_0000 | data as u64);
+    }
+
+    pub fn get_register_name(num: u64) -> &'static str {
+        assert!(num < 32);
+        match num {
+            0 => "ft0",
+            1 => "ft1",
+            2 => "ft2",
+            3 => "ft3",
+            4 => "ft4",
+            5 => "ft5",
+            6 => "ft6",
+            7 => "ft7",
+            8 => "fs0",
+            9 => "fs1",
+            10 => "fa0",
+            11 => "fa1",
+            12 => "fa2",
+            13 => "fa3",
+            14 => "fa4",
+            15 => "fa5",
+            16 => "fa6",
+            17 => "fa7",
+            18 => "fs2",
+            19 => "fs3",
+            20 => "fs4",
+            21 => "fs5",
+            22 => "fs6",
+            23 => "fs7",
+            24 => "fs8",
+            25 => "fs9",
+            26 => "fs10",
+            27 => "fs11",
+            28 => "ft8",
+            29 => "ft9",
+            30 => "ft10",
+            31 => "ft11",
+            _ => panic!(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.regs = [0; 32];
+    }
+}
+
+impl fmt::Display for Fpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..32 {
+            f.write_fmt(format_args!(
+                "{}:{:x}\n",
+                Fpr::get_register_name(i),
+                self.read(i)
+            ))?;
+        }
+        Ok(())
+    }
+}
@@ -37,11 +37,32 @@ pub enum GprName {
 }
 pub struct Gpr {
     regs: [u64; 32],
+    // 16 for RV64E, the registers above read as zero and ignore writes
+    num: usize,
+    // rv32, the writes are sign extended from bit 31
+    rv32: bool,
 }
 
 impl Gpr {
     pub fn new() -> Self {
-        Gpr { regs: [0; 32] }
+        Gpr {
+            regs: [0; 32],
+            num: 32,
+            rv32: false,
+        }
+    }
+
+    // x0-x15 only, the decoder rejects the instructions naming x16-x31
+    pub fn new_rve() -> Self {
+        Gpr {
+            regs: [0; 32],
+            num: 16,
+            rv32: false,
+        }
+    }
+
+    pub fn set_xlen(&mut self, xlen: usize) {
+        self.rv32 = xlen == 32;
     }
 
     pub fn read(&self, idx: u64) -> u64 {
@@ -49,14 +70,20 @@ impl Gpr {
         if idx == 0 {
             0
         } else {
-            self.regs.get(idx as usize).copied().unwrap_or(0)
+            self.regs[..self.num]
+                .get(idx as usize)
+                .copied()
+                .unwrap_or(0)
         }
     }
     pub fn write(&mut self, idx: u64, data: u64) {
         assert!(idx < 32);
         if idx != 0 {
-            if let Some(x) = self.regs.get_mut(idx as usize) {
-                *x = data;
+            if let Some(x) = self.regs[..self.num].get_mut(idx as usize) {
+                *x = match self.rv32 {
+                    true => data as i32 as i64 as u64,
+                    false => data,
+                };
             }
         }
     }
@@ -0,0 +1,773 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use hashbrown::HashMap;
+
+use crate::rv64core::{fpr::Fpr, gpr::Gpr, vector::vpr::Vpr};
+
+use super::{
+    inst_base::{parse_format_v, Instruction},
+    inst_disasm::{
+        fp_operands, inst_name, v_layout, v_uimm, VLayout, LMUL_NAMES, ROUND_MODE_NAMES,
+    },
+    inst_rv64a::{
+        INSTRUCTIONS_A, INSTRUCTIONS_ZABHA, INSTRUCTIONS_ZABHA_ZACAS, INSTRUCTIONS_ZACAS,
+    },
+    inst_rv64d::INSTRUCTIONS_D,
+    inst_rv64f::INSTRUCTIONS_F,
+    inst_rv64h::INSTRUCTIONS_H,
+    inst_rv64i::INSTRUCTIONS_I,
+    inst_rv64m::INSTRUCTIONS_M,
+    inst_rv64v::INSTRUCTIONS_V,
+    inst_rv64z::{INSTRUCTIONS_Z, INSTRUCTIONS_ZICSR, INSTRUCTIONS_ZIFENCEI},
+    inst_rv64zawrs::INSTRUCTIONS_ZAWRS,
+    inst_rv64zba::INSTRUCTIONS_ZBA,
+    inst_rv64zbc::INSTRUCTIONS_ZBC,
+    inst_rv64zbs::INSTRUCTIONS_ZBS,
+    inst_rv64zfh::{INSTRUCTIONS_ZFH, INSTRUCTIONS_ZFH_D},
+    inst_rv64zicbo::{INSTRUCTIONS_ZICBOM, INSTRUCTIONS_ZICBOZ},
+    inst_rv64zicond::INSTRUCTIONS_ZICOND,
+    inst_rv64zihint::{INSTRUCTIONS_ZIHINTNTL, INSTRUCTIONS_ZIHINTPAUSE},
+    inst_rv64zkn::INSTRUCTIONS_ZKN,
+    inst_rv64zks::INSTRUCTIONS_ZKS,
+};
+
+// A minimal assembler for the base instructions and the common pseudo instructions.
+// The syntax follows `objdump -M no-aliases`, so the output of the disassembler
+// can be fed back. Statements are separated by ';' or '\n', '#' starts a comment,
+// "name:" defines a label. Branch and jump targets are labels or absolute addresses.
+// no compressed instructions, no relocations.
+
+type AsmResult<T> = Result<T, String>;
+
+struct Stmt {
+    addr: u64,
+    mnemonic: String,
+    operands: Vec<String>,
+}
+
+fn find_inst(name: &str) -> Option<&'static Instruction> {
+    let tables = [
+        INSTRUCTIONS_I,
+        INSTRUCTIONS_Z,
+        INSTRUCTIONS_ZICSR,
+        INSTRUCTIONS_ZIFENCEI,
+        INSTRUCTIONS_M,
+        INSTRUCTIONS_A,
+        INSTRUCTIONS_F,
+        INSTRUCTIONS_D,
+        INSTRUCTIONS_ZFH,
+        INSTRUCTIONS_ZFH_D,
+        INSTRUCTIONS_V,
+        INSTRUCTIONS_ZBA,
+        INSTRUCTIONS_ZBC,
+        INSTRUCTIONS_ZBS,
+        INSTRUCTIONS_ZICOND,
+        INSTRUCTIONS_ZICBOM,
+        INSTRUCTIONS_ZICBOZ,
+        INSTRUCTIONS_ZAWRS,
+        INSTRUCTIONS_ZIHINTPAUSE,
+        INSTRUCTIONS_ZIHINTNTL,
+        INSTRUCTIONS_ZACAS,
+        INSTRUCTIONS_ZABHA,
+        INSTRUCTIONS_ZABHA_ZACAS,
+        INSTRUCTIONS_H,
+        INSTRUCTIONS_ZKN,
+        INSTRUCTIONS_ZKS,
+    ];
+    tables
+        .iter()
+        .flat_map(|x| x.iter())
+        .find(|x| inst_name(x) == name)
+}
+
+fn parse_reg(s: &str) -> AsmResult<u32> {
+    let s = s.trim();
+    if let Some(idx) = s.strip_prefix('x') {
+        if let Ok(idx) = idx.parse::<u32>() {
+            if idx < 32 {
+                return Ok(idx);
+            }
+        }
+    }
+    if s == "fp" {
+        return Ok(8);
+    }
+    (0..32)
+        .find(|&i| Gpr::get_register_name(i) == s)
+        .map(|i| i as u32)
+        .ok_or(format!("invalid register:{s}"))
+}
+
+fn parse_freg(s: &str) -> AsmResult<u32> {
+    let s = s.trim();
+    if let Some(idx) = s.strip_prefix('f') {
+        if let Ok(idx) = idx.parse::<u32>() {
+            if idx < 32 {
+                return Ok(idx);
+            }
+        }
+    }
+    (0..32)
+        .find(|&i| Fpr::get_register_name(i) == s)
+        .map(|i| i as u32)
+        .ok_or(format!("invalid fp register:{s}"))
+}
+
+fn parse_vreg(s: &str) -> AsmResult<u32> {
+    let s = s.trim();
+    (0..32)
+        .find(|&i| Vpr::get_register_name(i) == s)
+        .map(|i| i as u32)
+        .ok_or(format!("invalid vector register:{s}"))
+}
+
+// "e32,m2,ta,ma" or a number, the policies default to tu,mu
+fn parse_vtype(ops: &[String]) -> AsmResult<u32> {
+    if let [val] = ops {
+        if let Ok(val) = parse_imm(val) {
+            return Ok(val as u32);
+        }
+    }
+    let (mut sew, mut lmul, mut policy) = (None, None, 0);
+    for x in ops {
+        match x.as_str() {
+            "ta" => policy |= 1 << 6,
+            "ma" => policy |= 1 << 7,
+            "tu" | "mu" => {}
+            _ if x.starts_with('e') => {
+                sew = ["e8", "e16", "e32", "e64"]
+                    .iter()
+                    .position(|e| e == x)
+                    .map(|e| e as u32);
+            }
+            _ => {
+                lmul = LMUL_NAMES
+                    .iter()
+                    .position(|m| !m.is_empty() && m == x)
+                    .map(|m| m as u32);
+            }
+        }
+    }
+    match (sew, lmul) {
+        (Some(sew), Some(lmul)) => Ok(sew << 3 | lmul | policy),
+        _ => Err(format!("invalid vtype:{}", ops.join(","))),
+    }
+}
+
+fn parse_round_mode(s: Option<&String>, default: u8) -> AsmResult<u32> {
+    match s {
+        None => Ok(default as u32),
+        Some(s) => ROUND_MODE_NAMES
+            .iter()
+            .position(|x| x == s && !x.starts_with(char::is_numeric))
+            .map(|x| x as u32)
+            .ok_or(format!("invalid rounding mode:{s}")),
+    }
+}
+
+fn parse_imm(s: &str) -> AsmResult<i64> {
+    let s = s.trim();
+    let (neg, abs) = match s.strip_prefix('-') {
+        Some(x) => (true, x),
+        None => (false, s),
+    };
+    let val = if let Some(hex) = abs.strip_prefix("0x").or(abs.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16)
+    } else {
+        abs.parse::<u64>()
+    }
+    .map_err(|_| format!("invalid immediate:{s}"))? as i64;
+
+    Ok(if neg { val.wrapping_neg() } else { val })
+}
+
+fn check_range(val: i64, min: i64, max: i64) -> AsmResult<i64> {
+    if val < min || val > max {
+        Err(format!("immediate {val} out of range [{min},{max}]"))
+    } else {
+        Ok(val)
+    }
+}
+
+// "imm(rs1)" or "(rs1)"
+fn parse_mem(s: &str) -> AsmResult<(i64, u32)> {
+    let s = s.trim();
+    let (imm, rest) = s
+        .split_once('(')
+        .ok_or(format!("invalid memory operand:{s}"))?;
+    let rs1 = rest
+        .strip_suffix(')')
+        .ok_or(format!("invalid memory operand:{s}"))?;
+    let imm = if imm.trim().is_empty() {
+        0
+    } else {
+        parse_imm(imm)?
+    };
+    Ok((imm, parse_reg(rs1)?))
+}
+
+fn parse_target(s: &str, pc: u64, labels: &HashMap<String, u64>) -> AsmResult<i64> {
+    let target = match labels.get(s.trim()) {
+        Some(addr) => *addr,
+        None => parse_imm(s)? as u64,
+    };
+    Ok(target.wrapping_sub(pc) as i64)
+}
+
+fn parse_fence_set(s: &str) -> AsmResult<u32> {
+    if s == "0" {
+        return Ok(0);
+    }
+    s.chars().try_fold(0, |acc, c| match c {
+        'i' => Ok(acc | 8),
+        'o' => Ok(acc | 4),
+        'r' => Ok(acc | 2),
+        'w' => Ok(acc | 1),
+        _ => Err(format!("invalid fence operand:{s}")),
+    })
+}
+
+fn sext12(val: i64) -> i64 {
+    (val << 52) >> 52
+}
+
+fn fmt_li(rd: &str, val: i64) -> Vec<(String, Vec<String>)> {
+    let rd = rd.to_string();
+    if (-2048..2048).contains(&val) {
+        return vec![(
+            "addi".to_string(),
+            vec![rd, "zero".to_string(), val.to_string()],
+        )];
+    }
+    if val == val as i32 as i64 {
+        let lo12 = sext12(val);
+        let hi20 = ((val - lo12) >> 12) & 0xfffff;
+        let mut seq = vec![("lui".to_string(), vec![rd.clone(), format!("0x{hi20:x}")])];
+        if lo12 != 0 {
+            seq.push(("addiw".to_string(), vec![rd.clone(), rd, lo12.to_string()]));
+        }
+        return seq;
+    }
+    // same as the llvm's RISCVMatInt
+    let lo12 = sext12(val);
+    let mut hi52 = (val.wrapping_sub(lo12)) >> 12;
+    let shift = hi52.trailing_zeros() as i64;
+    hi52 >>= shift;
+    let mut seq = fmt_li(&rd, hi52);
+    seq.push((
+        "slli".to_string(),
+        vec![rd.clone(), rd.clone(), (shift + 12).to_string()],
+    ));
+    if lo12 != 0 {
+        seq.push(("addi".to_string(), vec![rd.clone(), rd, lo12.to_string()]));
+    }
+    seq
+}
+
+// pseudo instructions to base instructions
+fn expand(mnemonic: &str, ops: &[String]) -> AsmResult<Vec<(String, Vec<String>)>> {
+    let op = |i: usize| -> AsmResult<String> {
+        ops.get(i)
+            .cloned()
+            .ok_or(format!("{mnemonic}: missing operand {i}"))
+    };
+    let z = || "zero".to_string();
+    let one = |m: &str, o: Vec<String>| Ok(vec![(m.to_string(), o)]);
+
+    match (mnemonic, ops.len()) {
+        ("nop", 0) => one("addi", vec![z(), z(), "0".to_string()]),
+        ("li", 2) => Ok(fmt_li(&op(0)?, parse_imm(&op(1)?)?)),
+        ("mv", 2) => one("addi", vec![op(0)?, op(1)?, "0".to_string()]),
+        ("not", 2) => one("xori", vec![op(0)?, op(1)?, "-1".to_string()]),
+        ("neg", 2) => one("sub", vec![op(0)?, z(), op(1)?]),
+        ("negw", 2) => one("subw", vec![op(0)?, z(), op(1)?]),
+        ("sext.w", 2) => one("addiw", vec![op(0)?, op(1)?, "0".to_string()]),
+        ("seqz", 2) => one("sltiu", vec![op(0)?, op(1)?, "1".to_string()]),
+        ("snez", 2) => one("sltu", vec![op(0)?, z(), op(1)?]),
+        ("beqz", 2) => one("beq", vec![op(0)?, z(), op(1)?]),
+        ("bnez", 2) => one("bne", vec![op(0)?, z(), op(1)?]),
+        ("blez", 2) => one("bge", vec![z(), op(0)?, op(1)?]),
+        ("bgez", 2) => one("bge", vec![op(0)?, z(), op(1)?]),
+        ("bltz", 2) => one("blt", vec![op(0)?, z(), op(1)?]),
+        ("bgtz", 2) => one("blt", vec![z(), op(0)?, op(1)?]),
+        ("bgt", 3) => one("blt", vec![op(1)?, op(0)?, op(2)?]),
+        ("ble", 3) => one("bge", vec![op(1)?, op(0)?, op(2)?]),
+        ("bgtu", 3) => one("bltu", vec![op(1)?, op(0)?, op(2)?]),
+        ("bleu", 3) => one("bgeu", vec![op(1)?, op(0)?, op(2)?]),
+        ("j", 1) => one("jal", vec![z(), op(0)?]),
+        ("jal", 1) | ("call", 1) => one("jal", vec!["ra".to_string(), op(0)?]),
+        ("jr", 1) => one("jalr", vec![z(), format!("0({})", op(0)?)]),
+        ("jalr", 1) => one("jalr", vec!["ra".to_string(), format!("0({})", op(0)?)]),
+        ("ret", 0) => one("jalr", vec![z(), "0(ra)".to_string()]),
+        ("csrr", 2) => one("csrrs", vec![op(0)?, op(1)?, z()]),
+        ("csrw", 2) => one("csrrw", vec![z(), op(0)?, op(1)?]),
+        ("csrs", 2) => one("csrrs", vec![z(), op(0)?, op(1)?]),
+        ("csrc", 2) => one("csrrc", vec![z(), op(0)?, op(1)?]),
+        ("csrwi", 2) => one("csrrwi", vec![z(), op(0)?, op(1)?]),
+        ("csrsi", 2) => one("csrrsi", vec![z(), op(0)?, op(1)?]),
+        ("csrci", 2) => one("csrrci", vec![z(), op(0)?, op(1)?]),
+        ("fence", 0) => one("fence", vec!["iorw".to_string(), "iorw".to_string()]),
+        ("sfence.vma", 0) => one("sfence.vma", vec![z(), z()]),
+        ("sfence.vma", 1) => one("sfence.vma", vec![op(0)?, z()]),
+        ("fmv.s", 2) => one("fsgnj.s", vec![op(0)?, op(1)?, op(1)?]),
+        ("fneg.s", 2) => one("fsgnjn.s", vec![op(0)?, op(1)?, op(1)?]),
+        ("fabs.s", 2) => one("fsgnjx.s", vec![op(0)?, op(1)?, op(1)?]),
+        ("fmv.d", 2) => one("fsgnj.d", vec![op(0)?, op(1)?, op(1)?]),
+        ("fneg.d", 2) => one("fsgnjn.d", vec![op(0)?, op(1)?, op(1)?]),
+        ("fabs.d", 2) => one("fsgnjx.d", vec![op(0)?, op(1)?, op(1)?]),
+        ("fmv.h", 2) => one("fsgnj.h", vec![op(0)?, op(1)?, op(1)?]),
+        ("fneg.h", 2) => one("fsgnjn.h", vec![op(0)?, op(1)?, op(1)?]),
+        ("fabs.h", 2) => one("fsgnjx.h", vec![op(0)?, op(1)?, op(1)?]),
+        _ => one(mnemonic, ops.to_vec()),
+    }
+}
+
+fn encode(
+    mnemonic: &str,
+    ops: &[String],
+    pc: u64,
+    labels: &HashMap<String, u64>,
+) -> AsmResult<u32> {
+    // amo ordering suffix
+    let (name, aqrl) = if let Some(x) = mnemonic.strip_suffix(".aqrl") {
+        (x, 0b11)
+    } else if let Some(x) = mnemonic.strip_suffix(".aq") {
+        (x, 0b10)
+    } else if let Some(x) = mnemonic.strip_suffix(".rl") {
+        (x, 0b01)
+    } else {
+        (mnemonic, 0)
+    };
+
+    let inst = find_inst(name).ok_or(format!("unknown instruction:{mnemonic}"))?;
+    if name.starts_with('v') {
+        return encode_vector(inst, name, ops);
+    }
+    let word = inst.match_data;
+    let op = |i: usize| -> AsmResult<&str> {
+        ops.get(i)
+            .map(|x| x.as_str())
+            .ok_or(format!("{mnemonic}: missing operand {i}"))
+    };
+    let rd = |i| parse_reg(op(i)?).map(|x| x << 7);
+    let rs1 = |i| parse_reg(op(i)?).map(|x| x << 15);
+    let rs2 = |i| parse_reg(op(i)?).map(|x| x << 20);
+    let imm_i = |imm: i64| -> AsmResult<u32> { Ok((check_range(imm, -2048, 2047)? as u32) << 20) };
+
+    let opcode = word & 0x7f;
+    let word = match opcode {
+        // LUI,AUIPC
+        0b0110111 | 0b0010111 => {
+            let imm = check_range(parse_imm(op(1)?)?, -0x80000, 0xfffff)? as u32 & 0xfffff;
+            word | rd(0)? | (imm << 12)
+        }
+        // JAL
+        0b1101111 => {
+            let imm = check_range(parse_target(op(1)?, pc, labels)?, -(1 << 20), (1 << 20) - 2)?;
+            if imm & 1 != 0 {
+                return Err(format!("{mnemonic}: misaligned target"));
+            }
+            let imm = imm as u32;
+            let imm = ((imm >> 20) & 1) << 31
+                | ((imm >> 1) & 0x3ff) << 21
+                | ((imm >> 11) & 1) << 20
+                | ((imm >> 12) & 0xff) << 12;
+            word | rd(0)? | imm
+        }
+        // BRANCH
+        0b1100011 => {
+            let imm = check_range(parse_target(op(2)?, pc, labels)?, -(1 << 12), (1 << 12) - 2)?;
+            if imm & 1 != 0 {
+                return Err(format!("{mnemonic}: misaligned target"));
+            }
+            let imm = imm as u32;
+            let imm = ((imm >> 12) & 1) << 31
+                | ((imm >> 5) & 0x3f) << 25
+                | ((imm >> 1) & 0xf) << 8
+                | ((imm >> 11) & 1) << 7;
+            word | rs1(0)? | rs2(1)? | imm
+        }
+        // JALR,LOAD
+        0b1100111 | 0b0000011 => {
+            let (imm, base) = if ops.len() == 3 {
+                (parse_imm(op(2)?)?, parse_reg(op(1)?)?)
+            } else {
+                parse_mem(op(1)?)?
+            };
+            word | rd(0)? | (base << 15) | imm_i(imm)?
+        }
+        // STORE
+        0b0100011 => {
+            let (imm, base) = parse_mem(op(1)?)?;
+            let imm = check_range(imm, -2048, 2047)? as u32;
+            word | rs2(0)? | (base << 15) | ((imm >> 5) & 0x7f) << 25 | (imm & 0x1f) << 7
+        }
+        // the unary crypto instructions in OP-IMM
+        0b0010011 if name.starts_with("sha") || name.starts_with("sm3") || name == "aes64im" => {
+            word | rd(0)? | rs1(1)?
+        }
+        0b0010011 if name == "aes64ks1i" => {
+            let rnum = check_range(parse_imm(op(2)?)?, 0, 0xa)? as u32;
+            word | rd(0)? | rs1(1)? | rnum << 20
+        }
+        // OP-IMM,OP-IMM-32
+        0b0010011 | 0b0011011 => {
+            let funct3 = (word >> 12) & 0b111;
+            let imm = parse_imm(op(2)?)?;
+            let imm = if funct3 == 0b001 || funct3 == 0b101 {
+                // slli.uw takes a 6-bit shamt
+                let max = if opcode == 0b0010011 || name == "slli.uw" {
+                    63
+                } else {
+                    31
+                };
+                (check_range(imm, 0, max)? as u32) << 20
+            } else {
+                imm_i(imm)?
+            };
+            word | rd(0)? | rs1(1)? | imm
+        }
+        // AMO
+        0b0101111 => {
+            let strip = |s: &str| -> AsmResult<u32> {
+                let s = s.trim();
+                let reg = s
+                    .strip_prefix('(')
+                    .and_then(|x| x.strip_suffix(')'))
+                    .unwrap_or(s);
+                parse_reg(reg).map(|x| x << 15)
+            };
+            let word = word | (aqrl << 25) | rd(0)?;
+            if name.starts_with("lr") {
+                word | strip(op(1)?)?
+            } else {
+                word | rs2(1)? | strip(op(2)?)?
+            }
+        }
+        // MISC-MEM
+        0b0001111 => {
+            if name == "fence" {
+                word | parse_fence_set(op(0)?)? << 24 | parse_fence_set(op(1)?)? << 20
+            } else if name.starts_with("cbo") {
+                match parse_mem(op(0)?)? {
+                    (0, base) => word | (base << 15),
+                    _ => return Err(format!("{name}: offset must be zero")),
+                }
+            } else {
+                word
+            }
+        }
+        // LOAD-FP
+        0b0000111 => {
+            let (imm, base) = parse_mem(op(1)?)?;
+            word | parse_freg(op(0)?)? << 7 | (base << 15) | imm_i(imm)?
+        }
+        // STORE-FP
+        0b0100111 => {
+            let (imm, base) = parse_mem(op(1)?)?;
+            let imm = check_range(imm, -2048, 2047)? as u32;
+            word | parse_freg(op(0)?)? << 20
+                | (base << 15)
+                | ((imm >> 5) & 0x7f) << 25
+                | (imm & 0x1f) << 7
+        }
+        // MADD,MSUB,NMSUB,NMADD,OP-FP
+        0b1000011 | 0b1000111 | 0b1001011 | 0b1001111 | 0b1010011 => {
+            let kinds = fp_operands(name);
+            let reg_at = |i, is_x| -> AsmResult<u32> {
+                if is_x {
+                    parse_reg(op(i)?)
+                } else {
+                    parse_freg(op(i)?)
+                }
+            };
+            let mut word = word | reg_at(0, kinds.rd_x)? << 7 | reg_at(1, kinds.rs1_x)? << 15;
+            if kinds.srcs >= 2 {
+                word |= parse_freg(op(2)?)? << 20;
+            }
+            if kinds.srcs == 3 {
+                word |= parse_freg(op(3)?)? << 27;
+            }
+            if kinds.rm {
+                word |= parse_round_mode(ops.get(kinds.srcs + 1), kinds.default_rm)? << 12;
+            }
+            word
+        }
+        // SYSTEM
+        0b1110011 => match name {
+            "csrrw" | "csrrs" | "csrrc" | "csrrwi" | "csrrsi" | "csrrci" => {
+                let csr = check_range(parse_imm(op(1)?)?, 0, 0xfff)? as u32;
+                let src = if name.ends_with('i') {
+                    (check_range(parse_imm(op(2)?)?, 0, 31)? as u32) << 15
+                } else {
+                    rs1(2)?
+                };
+                word | rd(0)? | (csr << 20) | src
+            }
+            "sfence.vma" | "hfence.vvma" | "hfence.gvma" => word | rs1(0)? | rs2(1)?,
+            _ if name.starts_with("hlv") || name.starts_with("hsv") => {
+                let base = match parse_mem(op(1)?)? {
+                    (0, base) => base << 15,
+                    _ => return Err(format!("{name}: offset must be zero")),
+                };
+                match name.starts_with("hlv") {
+                    true => word | rd(0)? | base,
+                    false => word | rs2(0)? | base,
+                }
+            }
+            _ => word,
+        },
+        // sm4ed,sm4ks select a byte of rs2 with bs
+        0b0110011 if name.starts_with("sm4") => {
+            let bs = check_range(parse_imm(op(3)?)?, 0, 3)? as u32;
+            word | rd(0)? | rs1(1)? | rs2(2)? | bs << 30
+        }
+        // the ntl hints have fixed operands
+        0b0110011 if name.starts_with("ntl") => word,
+        // OP,OP-32
+        _ => word | rd(0)? | rs1(1)? | rs2(2)?,
+    };
+    Ok(word)
+}
+
+// vector instructions, "v0.t" as the last operand selects the masked form
+fn encode_vector(inst: &Instruction, name: &str, ops: &[String]) -> AsmResult<u32> {
+    let (ops, masked) = match ops.split_last() {
+        Some((last, rest)) if last == "v0.t" => (rest, true),
+        _ => (ops, false),
+    };
+    let op = |i: usize| -> AsmResult<&str> {
+        ops.get(i)
+            .map(|x| x.as_str())
+            .ok_or(format!("{name}: missing operand {i}"))
+    };
+    let vreg = |i, shift| parse_vreg(op(i)?).map(|x| x << shift);
+    let xreg = |i, shift| parse_reg(op(i)?).map(|x| x << shift);
+
+    let layout = v_layout(name);
+    let mut word = inst.match_data;
+    // vm is 1 for the unmasked form
+    if inst.mask & (1 << 25) == 0 && layout != VLayout::Vset && layout != VLayout::Merge {
+        if !masked {
+            word |= 1 << 25;
+        }
+    } else if masked {
+        return Err(format!("{name}: can not be masked"));
+    }
+
+    let f = parse_format_v(word);
+    // LOAD-FP,STORE-FP
+    if word & 0x7f != 0b1010111 {
+        let (imm, base) = parse_mem(op(1)?)?;
+        if imm != 0 {
+            return Err(format!("{name}: offset is not supported"));
+        }
+        let offset = match f.mop {
+            0b10 => xreg(2, 20)?,
+            0b01 | 0b11 => vreg(2, 20)?,
+            _ => 0,
+        };
+        return Ok(word | vreg(0, 7)? | base << 15 | offset);
+    }
+
+    let src1 = |i| -> AsmResult<u32> {
+        match f.funct3 {
+            0b011 => {
+                let imm = parse_imm(op(i)?)?;
+                let imm = match v_uimm(name) {
+                    true => check_range(imm, 0, 31)?,
+                    false => check_range(imm, -16, 15)?,
+                };
+                Ok((imm as u32 & 0x1f) << 15)
+            }
+            0b100 | 0b110 => xreg(i, 15),
+            _ => vreg(i, 15),
+        }
+    };
+    let word = match layout {
+        VLayout::Vset => match name {
+            "vsetvl" => word | xreg(0, 7)? | xreg(1, 15)? | xreg(2, 20)?,
+            "vsetivli" => {
+                let uimm = check_range(parse_imm(op(1)?)?, 0, 31)? as u32;
+                let vtype = check_range(parse_vtype(&ops[2..])?.into(), 0, 0x3ff)? as u32;
+                word | xreg(0, 7)? | uimm << 15 | vtype << 20
+            }
+            _ => {
+                let vtype = check_range(parse_vtype(&ops[2..])?.into(), 0, 0x7ff)? as u32;
+                word | xreg(0, 7)? | xreg(1, 15)? | vtype << 20
+            }
+        },
+        VLayout::XV => word | xreg(0, 7)? | vreg(1, 20)?,
+        VLayout::VX => word | vreg(0, 7)? | xreg(1, 15)?,
+        VLayout::VS1 => word | vreg(0, 7)? | src1(1)?,
+        VLayout::V => word | vreg(0, 7)?,
+        VLayout::V2 => word | vreg(0, 7)? | vreg(1, 20)?,
+        VLayout::Mac => word | vreg(0, 7)? | src1(1)? | vreg(2, 20)?,
+        VLayout::Merge => {
+            if op(3)? != "v0" {
+                return Err(format!("{name}: the mask must be v0"));
+            }
+            word | vreg(0, 7)? | vreg(1, 20)? | src1(2)?
+        }
+        VLayout::Arith => word | vreg(0, 7)? | vreg(1, 20)? | src1(2)?,
+    };
+    Ok(word)
+}
+
+fn parse_stmt(stmt: &str) -> (String, Vec<String>) {
+    let (mnemonic, rest) = stmt.split_once(char::is_whitespace).unwrap_or((stmt, ""));
+    let operands = rest
+        .split(',')
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect();
+    (mnemonic.to_ascii_lowercase(), operands)
+}
+
+/// Assemble `src` placed at `pc`, return the instruction words.
+pub fn assemble(src: &str, pc: u64) -> Result<Vec<u32>, String> {
+    let mut labels = HashMap::new();
+    let mut stmts = Vec::new();
+    let mut addr = pc;
+
+    // pass 1: expand pseudo instructions and collect labels
+    for line in src.split(['\n', ';']) {
+        let mut line = line.split('#').next().unwrap_or("").trim();
+        while let Some((label, rest)) = line.split_once(':') {
+            labels.insert(label.trim().to_string(), addr);
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = parse_stmt(line);
+        for (mnemonic, operands) in expand(&mnemonic, &operands)? {
+            stmts.push(Stmt {
+                addr,
+                mnemonic,
+                operands,
+            });
+            addr += 4;
+        }
+    }
+
+    // pass 2: encode
+    stmts
+        .iter()
+        .map(|x| {
+            encode(&x.mnemonic, &x.operands, x.addr, &labels)
+                .map_err(|e| format!("0x{:x}: {}", x.addr, e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_asm {
+    use alloc::rc::Rc;
+
+    use crate::{
+        config::Config,
+        rv64core::{inst::inst_disasm::disassemble, inst_decode::InstDecode},
+    };
+
+    use super::assemble;
+
+    #[test]
+    fn asm_test() {
+        assert_eq!(
+            assemble("li a0, 1; ret", 0x8000_0000).unwrap(),
+            [0x00100513, 0x00008067]
+        );
+        assert_eq!(
+            assemble("loop: addi a0,a0,-1\n bnez a0, loop # wait", 0).unwrap(),
+            [0xfff50513, 0xfe051ee3]
+        );
+        assert_eq!(
+            assemble("li t0, 0x80000000", 0).unwrap(),
+            [0x00100293, 0x01f29293]
+        );
+        assert_eq!(
+            assemble("li a0, 0x12345678", 0).unwrap(),
+            [0x12345537, 0x6785051b]
+        );
+        assert_eq!(
+            assemble("li a0, 0x123456789abcdef0", 0).unwrap(),
+            [
+                0x00247537, 0x8ad5051b, 0x00e51513, 0xc4d50513, 0x00c51513, 0x5e750513, 0x00d51513,
+                0xef050513
+            ]
+        );
+        assert_eq!(
+            assemble("amoswap.w.aq a0, a1, (a2)", 0).unwrap(),
+            [0x0cb6252f]
+        );
+        assert_eq!(assemble("csrr a0, 0xf14", 0).unwrap(), [0xf1402573]);
+        assert_eq!(
+            assemble("fadd.s fa0, fa1, fa2; fmv.s ft0, ft1", 0).unwrap(),
+            [0x00c5f553, 0x20108053]
+        );
+        assert_eq!(
+            assemble("fcvt.d.w fa0, a0; fneg.d fa0, fa1", 0).unwrap(),
+            [0xd2050553, 0x22b59553]
+        );
+        assert_eq!(
+            assemble(
+                "vsetvli t0, a0, e16, mf2, ta, mu; vadd.vx v4, v8, a0, v0.t",
+                0
+            )
+            .unwrap(),
+            [0x04f572d7, 0x00854257]
+        );
+        assert!(assemble("vmv.x.s a0, v2, v0.t", 0).is_err());
+        assert!(assemble("addi a0, a0, 4096", 0).is_err());
+        assert!(assemble("foo a0", 0).is_err());
+    }
+
+    #[test]
+    fn asm_disasm_round_trip() {
+        let src = "auipc t0,0x0; addi a1,t0,32; ld t0,24(t0); sd a1,0(a0); \
+                   fence iorw,iorw; mret; mul a0,a0,a1; srli a0,a0,0x3f; \
+                   lr.d a0,(a1); sc.w.rl a0,a1,(a2); csrrwi zero,0x300,8; \
+                   flw fa0,8(a1); fsw fs11,-8(sp); fmadd.s fa0,fa1,fa2,fa3,rtz; \
+                   fsqrt.s ft0,ft11; fcvt.s.lu fa0,a1,rne; fclass.s a0,fa1; \
+                   fld fa0,16(sp); fsd fa1,0(a0); fmsub.d ft0,ft1,ft2,ft3; \
+                   fcvt.d.s fa0,fa1; fcvt.s.d fa0,fa1,rup; fcvt.d.w fa0,a0; \
+                   fmv.x.d a0,fa0; fmv.d.x fa0,a0; feq.d a0,fa0,fa1; \
+                   flh fa0,2(a0); fsh fa0,-2(a0); fnmadd.h fa0,fa1,fa2,fa3,rdn; \
+                   fcvt.h.d fa0,fa1; fcvt.d.h fa0,fa1; fcvt.wu.h a0,fa1,rmm; fmv.h.x fa0,a0; \
+                   vsetivli a0,5,e64,m1,tu,mu; vsetvl a0,a1,a2; vle64ff.v v8,(a0),v0.t; \
+                   vsse32.v v4,(a1),t0; vloxei8.v v8,(a0),v4; vl2re16.v v2,(sp); vsm.v v1,(a0); \
+                   vsra.vi v4,v8,31; vrsub.vi v4,v8,-16; vmerge.vxm v4,v8,a0,v0; \
+                   vnmsac.vv v4,v1,v8,v0.t; vmv.s.x v4,a0; vfirst.m a0,v8; vsext.vf8 v8,v16; \
+                   vmv8r.v v8,v16; vmxnor.mm v1,v2,v3; vredmaxu.vs v1,v8,v2,v0.t; \
+                   add.uw a0,a1,a2; sh3add.uw a0,a1,a2; slli.uw a0,a1,0x3f; \
+                   clmulr a0,a1,a2; bext a0,a1,a2; binvi a0,a1,0x3f; \
+                   czero.eqz a0,a1,a2; czero.nez s0,t1,zero; \
+                   cbo.clean (a0); cbo.flush (sp); cbo.inval (t1); cbo.zero (a5); \
+                   wrs.nto; wrs.sto; pause; ntl.p1; ntl.pall; ntl.s1; ntl.all; \
+                   amocas.w a0,a2,(a4); amocas.d.aqrl t0,t1,(sp); amocas.q a2,a4,(a0); \
+                   amoadd.b a0,a2,(a4); amomaxu.h.aq t0,t1,(sp); amocas.b.rl a0,a1,(a2); \
+                   hfence.vvma a0,a1; hfence.gvma zero,zero; hlv.b a0,(a1); hlv.wu t0,(sp); \
+                   hlvx.hu a0,(a1); hlv.d s0,(a5); hsv.b a0,(a1); hsv.d t1,(sp); \
+                   aes64es a0,a1,a2; aes64dsm t0,t1,t2; aes64im a0,a1; aes64ks1i a0,a1,0xa; \
+                   aes64ks2 a0,a1,a2; sha256sig0 a0,a1; sha256sum1 t0,t1; sha512sig1 a0,a1; \
+                   sha512sum0 s0,s1; \
+                   sm4ed a0,a1,a2,0x3; sm4ks t0,t1,t2,0x0; sm3p0 a0,a1; sm3p1 s0,s1";
+        let mut config = Config::new();
+        config.set_isa(
+            "rv64imafdh_zfh_zve64x_zba_zbc_zbs_zicond_zicbom_zicboz_zawrs_zacas_zabha_zkn_zks_zihintpause_zihintntl",
+        );
+        let mut decoder = InstDecode::new(Rc::new(config));
+
+        let words = assemble(src, 0).unwrap();
+        for (word, expected) in words.iter().zip(src.split(';')) {
+            let inst = decoder.fast_path(*word).unwrap();
+            let text = disassemble(inst, *word, 0).replace('\t', " ");
+            assert_eq!(text, expected.trim());
+        }
+    }
+}
@@ -319,6 +319,796 @@ pub const MATCH_XOR: u32 = 0x4033;
 pub const MASK_XOR: u32 = 0xfe00707f;
 pub const MATCH_XORI: u32 = 0x4013;
 pub const MASK_XORI: u32 = 0x707f;
+// make EXTENSIONS='rv_f rv64_f'
+pub const MATCH_FADD_S: u32 = 0x53;
+pub const MASK_FADD_S: u32 = 0xfe00007f;
+pub const MATCH_FCLASS_S: u32 = 0xe0001053;
+pub const MASK_FCLASS_S: u32 = 0xfff0707f;
+pub const MATCH_FCVT_L_S: u32 = 0xc0200053;
+pub const MASK_FCVT_L_S: u32 = 0xfff0007f;
+pub const MATCH_FCVT_LU_S: u32 = 0xc0300053;
+pub const MASK_FCVT_LU_S: u32 = 0xfff0007f;
+pub const MATCH_FCVT_S_L: u32 = 0xd0200053;
+pub const MASK_FCVT_S_L: u32 = 0xfff0007f;
+pub const MATCH_FCVT_S_LU: u32 = 0xd0300053;
+pub const MASK_FCVT_S_LU: u32 = 0xfff0007f;
+pub const MATCH_FCVT_S_W: u32 = 0xd0000053;
+pub const MASK_FCVT_S_W: u32 = 0xfff0007f;
+pub const MATCH_FCVT_S_WU: u32 = 0xd0100053;
+pub const MASK_FCVT_S_WU: u32 = 0xfff0007f;
+pub const MATCH_FCVT_W_S: u32 = 0xc0000053;
+pub const MASK_FCVT_W_S: u32 = 0xfff0007f;
+pub const MATCH_FCVT_WU_S: u32 = 0xc0100053;
+pub const MASK_FCVT_WU_S: u32 = 0xfff0007f;
+pub const MATCH_FDIV_S: u32 = 0x18000053;
+pub const MASK_FDIV_S: u32 = 0xfe00007f;
+pub const MATCH_FEQ_S: u32 = 0xa0002053;
+pub const MASK_FEQ_S: u32 = 0xfe00707f;
+pub const MATCH_FLE_S: u32 = 0xa0000053;
+pub const MASK_FLE_S: u32 = 0xfe00707f;
+pub const MATCH_FLT_S: u32 = 0xa0001053;
+pub const MASK_FLT_S: u32 = 0xfe00707f;
+pub const MATCH_FLW: u32 = 0x2007;
+pub const MASK_FLW: u32 = 0x707f;
+pub const MATCH_FMADD_S: u32 = 0x43;
+pub const MASK_FMADD_S: u32 = 0x600007f;
+pub const MATCH_FMAX_S: u32 = 0x28001053;
+pub const MASK_FMAX_S: u32 = 0xfe00707f;
+pub const MATCH_FMIN_S: u32 = 0x28000053;
+pub const MASK_FMIN_S: u32 = 0xfe00707f;
+pub const MATCH_FMSUB_S: u32 = 0x47;
+pub const MASK_FMSUB_S: u32 = 0x600007f;
+pub const MATCH_FMUL_S: u32 = 0x10000053;
+pub const MASK_FMUL_S: u32 = 0xfe00007f;
+pub const MATCH_FMV_W_X: u32 = 0xf0000053;
+pub const MASK_FMV_W_X: u32 = 0xfff0707f;
+pub const MATCH_FMV_X_W: u32 = 0xe0000053;
+pub const MASK_FMV_X_W: u32 = 0xfff0707f;
+pub const MATCH_FNMADD_S: u32 = 0x4f;
+pub const MASK_FNMADD_S: u32 = 0x600007f;
+pub const MATCH_FNMSUB_S: u32 = 0x4b;
+pub const MASK_FNMSUB_S: u32 = 0x600007f;
+pub const MATCH_FSGNJ_S: u32 = 0x20000053;
+pub const MASK_FSGNJ_S: u32 = 0xfe00707f;
+pub const MATCH_FSGNJN_S: u32 = 0x20001053;
+pub const MASK_FSGNJN_S: u32 = 0xfe00707f;
+pub const MATCH_FSGNJX_S: u32 = 0x20002053;
+pub const MASK_FSGNJX_S: u32 = 0xfe00707f;
+pub const MATCH_FSQRT_S: u32 = 0x58000053;
+pub const MASK_FSQRT_S: u32 = 0xfff0007f;
+pub const MATCH_FSUB_S: u32 = 0x8000053;
+pub const MASK_FSUB_S: u32 = 0xfe00007f;
+pub const MATCH_FSW: u32 = 0x2027;
+pub const MASK_FSW: u32 = 0x707f;
+// make EXTENSIONS='rv_d rv64_d'
+pub const MATCH_FADD_D: u32 = 0x2000053;
+pub const MASK_FADD_D: u32 = 0xfe00007f;
+pub const MATCH_FCLASS_D: u32 = 0xe2001053;
+pub const MASK_FCLASS_D: u32 = 0xfff0707f;
+pub const MATCH_FCVT_D_L: u32 = 0xd2200053;
+pub const MASK_FCVT_D_L: u32 = 0xfff0007f;
+pub const MATCH_FCVT_D_LU: u32 = 0xd2300053;
+pub const MASK_FCVT_D_LU: u32 = 0xfff0007f;
+pub const MATCH_FCVT_D_S: u32 = 0x42000053;
+pub const MASK_FCVT_D_S: u32 = 0xfff0007f;
+pub const MATCH_FCVT_D_W: u32 = 0xd2000053;
+pub const MASK_FCVT_D_W: u32 = 0xfff0007f;
+pub const MATCH_FCVT_D_WU: u32 = 0xd2100053;
+pub const MASK_FCVT_D_WU: u32 = 0xfff0007f;
+pub const MATCH_FCVT_L_D: u32 = 0xc2200053;
+pub const MASK_FCVT_L_D: u32 = 0xfff0007f;
+pub const MATCH_FCVT_LU_D: u32 = 0xc2300053;
+pub const MASK_FCVT_LU_D: u32 = 0xfff0007f;
+pub const MATCH_FCVT_S_D: u32 = 0x40100053;
+pub const MASK_FCVT_S_D: u32 = 0xfff0007f;
+pub const MATCH_FCVT_W_D: u32 = 0xc2000053;
+pub const MASK_FCVT_W_D: u32 = 0xfff0007f;
+pub const MATCH_FCVT_WU_D: u32 = 0xc2100053;
+pub const MASK_FCVT_WU_D: u32 = 0xfff0007f;
+pub const MATCH_FDIV_D: u32 = 0x1a000053;
+pub const MASK_FDIV_D: u32 = 0xfe00007f;
+pub const MATCH_FEQ_D: u32 = 0xa2002053;
+pub const MASK_FEQ_D: u32 = 0xfe00707f;
+pub const MATCH_FLD: u32 = 0x3007;
+pub const MASK_FLD: u32 = 0x707f;
+pub const MATCH_FLE_D: u32 = 0xa2000053;
+pub const MASK_FLE_D: u32 = 0xfe00707f;
+pub const MATCH_FLT_D: u32 = 0xa2001053;
+pub const MASK_FLT_D: u32 = 0xfe00707f;
+pub const MATCH_FMADD_D: u32 = 0x2000043;
+pub const MASK_FMADD_D: u32 = 0x600007f;
+pub const MATCH_FMAX_D: u32 = 0x2a001053;
+pub const MASK_FMAX_D: u32 = 0xfe00707f;
+pub const MATCH_FMIN_D: u32 = 0x2a000053;
+pub const MASK_FMIN_D: u32 = 0xfe00707f;
+pub const MATCH_FMSUB_D: u32 = 0x2000047;
+pub const MASK_FMSUB_D: u32 = 0x600007f;
+pub const MATCH_FMUL_D: u32 = 0x12000053;
+pub const MASK_FMUL_D: u32 = 0xfe00007f;
+pub const MATCH_FMV_D_X: u32 = 0xf2000053;
+pub const MASK_FMV_D_X: u32 = 0xfff0707f;
+pub const MATCH_FMV_X_D: u32 = 0xe2000053;
+pub const MASK_FMV_X_D: u32 = 0xfff0707f;
+pub const MATCH_FNMADD_D: u32 = 0x200004f;
+pub const MASK_FNMADD_D: u32 = 0x600007f;
+pub const MATCH_FNMSUB_D: u32 = 0x200004b;
+pub const MASK_FNMSUB_D: u32 = 0x600007f;
+pub const MATCH_FSD: u32 = 0x3027;
+pub const MASK_FSD: u32 = 0x707f;
+pub const MATCH_FSGNJ_D: u32 = 0x22000053;
+pub const MASK_FSGNJ_D: u32 = 0xfe00707f;
+pub const MATCH_FSGNJN_D: u32 = 0x22001053;
+pub const MASK_FSGNJN_D: u32 = 0xfe00707f;
+pub const MATCH_FSGNJX_D: u32 = 0x22002053;
+pub const MASK_FSGNJX_D: u32 = 0xfe00707f;
+pub const MATCH_FSQRT_D: u32 = 0x5a000053;
+pub const MASK_FSQRT_D: u32 = 0xfff0007f;
+pub const MATCH_FSUB_D: u32 = 0xa000053;
+pub const MASK_FSUB_D: u32 = 0xfe00007f;
+// make EXTENSIONS='rv_zfh rv64_zfh rv_d_zfh'
+pub const MATCH_FADD_H: u32 = 0x4000053;
+pub const MASK_FADD_H: u32 = 0xfe00007f;
+pub const MATCH_FCLASS_H: u32 = 0xe4001053;
+pub const MASK_FCLASS_H: u32 = 0xfff0707f;
+pub const MATCH_FCVT_D_H: u32 = 0x42200053;
+pub const MASK_FCVT_D_H: u32 = 0xfff0007f;
+pub const MATCH_FCVT_H_D: u32 = 0x44100053;
+pub const MASK_FCVT_H_D: u32 = 0xfff0007f;
+pub const MATCH_FCVT_H_L: u32 = 0xd4200053;
+pub const MASK_FCVT_H_L: u32 = 0xfff0007f;
+pub const MATCH_FCVT_H_LU: u32 = 0xd4300053;
+pub const MASK_FCVT_H_LU: u32 = 0xfff0007f;
+pub const MATCH_FCVT_H_S: u32 = 0x44000053;
+pub const MASK_FCVT_H_S: u32 = 0xfff0007f;
+pub const MATCH_FCVT_H_W: u32 = 0xd4000053;
+pub const MASK_FCVT_H_W: u32 = 0xfff0007f;
+pub const MATCH_FCVT_H_WU: u32 = 0xd4100053;
+pub const MASK_FCVT_H_WU: u32 = 0xfff0007f;
+pub const MATCH_FCVT_L_H: u32 = 0xc4200053;
+pub const MASK_FCVT_L_H: u32 = 0xfff0007f;
+pub const MATCH_FCVT_LU_H: u32 = 0xc4300053;
+pub const MASK_FCVT_LU_H: u32 = 0xfff0007f;
+pub const MATCH_FCVT_S_H: u32 = 0x40200053;
+pub const MASK_FCVT_S_H: u32 = 0xfff0007f;
+pub const MATCH_FCVT_W_H: u32 = 0xc4000053;
+pub const MASK_FCVT_W_H: u32 = 0xfff0007f;
+pub const MATCH_FCVT_WU_H: u32 = 0xc4100053;
+pub const MASK_FCVT_WU_H: u32 = 0xfff0007f;
+pub const MATCH_FDIV_H: u32 = 0x1c000053;
+pub const MASK_FDIV_H: u32 = 0xfe00007f;
+pub const MATCH_FEQ_H: u32 = 0xa4002053;
+pub const MASK_FEQ_H: u32 = 0xfe00707f;
+pub const MATCH_FLE_H: u32 = 0xa4000053;
+pub const MASK_FLE_H: u32 = 0xfe00707f;
+pub const MATCH_FLH: u32 = 0x1007;
+pub const MASK_FLH: u32 = 0x707f;
+pub const MATCH_FLT_H: u32 = 0xa4001053;
+pub const MASK_FLT_H: u32 = 0xfe00707f;
+pub const MATCH_FMADD_H: u32 = 0x4000043;
+pub const MASK_FMADD_H: u32 = 0x600007f;
+pub const MATCH_FMAX_H: u32 = 0x2c001053;
+pub const MASK_FMAX_H: u32 = 0xfe00707f;
+pub const MATCH_FMIN_H: u32 = 0x2c000053;
+pub const MASK_FMIN_H: u32 = 0xfe00707f;
+pub const MATCH_FMSUB_H: u32 = 0x4000047;
+pub const MASK_FMSUB_H: u32 = 0x600007f;
+pub const MATCH_FMUL_H: u32 = 0x14000053;
+pub const MASK_FMUL_H: u32 = 0xfe00007f;
+pub const MATCH_FMV_H_X: u32 = 0xf4000053;
+pub const MASK_FMV_H_X: u32 = 0xfff0707f;
+pub const MATCH_FMV_X_H: u32 = 0xe4000053;
+pub const MASK_FMV_X_H: u32 = 0xfff0707f;
+pub const MATCH_FNMADD_H: u32 = 0x400004f;
+pub const MASK_FNMADD_H: u32 = 0x600007f;
+pub const MATCH_FNMSUB_H: u32 = 0x400004b;
+pub const MASK_FNMSUB_H: u32 = 0x600007f;
+pub const MATCH_FSGNJ_H: u32 = 0x24000053;
+pub const MASK_FSGNJ_H: u32 = 0xfe00707f;
+pub const MATCH_FSGNJN_H: u32 = 0x24001053;
+pub const MASK_FSGNJN_H: u32 = 0xfe00707f;
+pub const MATCH_FSGNJX_H: u32 = 0x24002053;
+pub const MASK_FSGNJX_H: u32 = 0xfe00707f;
+pub const MATCH_FSH: u32 = 0x1027;
+pub const MASK_FSH: u32 = 0x707f;
+pub const MATCH_FSQRT_H: u32 = 0x5c000053;
+pub const MASK_FSQRT_H: u32 = 0xfff0007f;
+pub const MATCH_FSUB_H: u32 = 0xc000053;
+pub const MASK_FSUB_H: u32 = 0xfe00007f;
+// make EXTENSIONS='rv_v'
+pub const MATCH_VADD_VI: u32 = 0x3057;
+pub const MASK_VADD_VI: u32 = 0xfc00707f;
+pub const MATCH_VADD_VV: u32 = 0x57;
+pub const MASK_VADD_VV: u32 = 0xfc00707f;
+pub const MATCH_VADD_VX: u32 = 0x4057;
+pub const MASK_VADD_VX: u32 = 0xfc00707f;
+pub const MATCH_VAND_VI: u32 = 0x24003057;
+pub const MASK_VAND_VI: u32 = 0xfc00707f;
+pub const MATCH_VAND_VV: u32 = 0x24000057;
+pub const MASK_VAND_VV: u32 = 0xfc00707f;
+pub const MATCH_VAND_VX: u32 = 0x24004057;
+pub const MASK_VAND_VX: u32 = 0xfc00707f;
+pub const MATCH_VCOMPRESS_VM: u32 = 0x5e002057;
+pub const MASK_VCOMPRESS_VM: u32 = 0xfe00707f;
+pub const MATCH_VCPOP_M: u32 = 0x40082057;
+pub const MASK_VCPOP_M: u32 = 0xfc0ff07f;
+pub const MATCH_VDIV_VV: u32 = 0x84002057;
+pub const MASK_VDIV_VV: u32 = 0xfc00707f;
+pub const MATCH_VDIV_VX: u32 = 0x84006057;
+pub const MASK_VDIV_VX: u32 = 0xfc00707f;
+pub const MATCH_VDIVU_VV: u32 = 0x80002057;
+pub const MASK_VDIVU_VV: u32 = 0xfc00707f;
+pub const MATCH_VDIVU_VX: u32 = 0x80006057;
+pub const MASK_VDIVU_VX: u32 = 0xfc00707f;
+pub const MATCH_VFIRST_M: u32 = 0x4008a057;
+pub const MASK_VFIRST_M: u32 = 0xfc0ff07f;
+pub const MATCH_VID_V: u32 = 0x5008a057;
+pub const MASK_VID_V: u32 = 0xfdfff07f;
+pub const MATCH_VIOTA_M: u32 = 0x50082057;
+pub const MASK_VIOTA_M: u32 = 0xfc0ff07f;
+pub const MATCH_VL1RE16_V: u32 = 0x2805007;
+pub const MASK_VL1RE16_V: u32 = 0xfff0707f;
+pub const MATCH_VL1RE32_V: u32 = 0x2806007;
+pub const MASK_VL1RE32_V: u32 = 0xfff0707f;
+pub const MATCH_VL1RE64_V: u32 = 0x2807007;
+pub const MASK_VL1RE64_V: u32 = 0xfff0707f;
+pub const MATCH_VL1RE8_V: u32 = 0x2800007;
+pub const MASK_VL1RE8_V: u32 = 0xfff0707f;
+pub const MATCH_VL2RE16_V: u32 = 0x22805007;
+pub const MASK_VL2RE16_V: u32 = 0xfff0707f;
+pub const MATCH_VL2RE32_V: u32 = 0x22806007;
+pub const MASK_VL2RE32_V: u32 = 0xfff0707f;
+pub const MATCH_VL2RE64_V: u32 = 0x22807007;
+pub const MASK_VL2RE64_V: u32 = 0xfff0707f;
+pub const MATCH_VL2RE8_V: u32 = 0x22800007;
+pub const MASK_VL2RE8_V: u32 = 0xfff0707f;
+pub const MATCH_VL4RE16_V: u32 = 0x62805007;
+pub const MASK_VL4RE16_V: u32 = 0xfff0707f;
+pub const MATCH_VL4RE32_V: u32 = 0x62806007;
+pub const MASK_VL4RE32_V: u32 = 0xfff0707f;
+pub const MATCH_VL4RE64_V: u32 = 0x62807007;
+pub const MASK_VL4RE64_V: u32 = 0xfff0707f;
+pub const MATCH_VL4RE8_V: u32 = 0x62800007;
+pub const MASK_VL4RE8_V: u32 = 0xfff0707f;
+pub const MATCH_VL8RE16_V: u32 = 0xe2805007;
+pub const MASK_VL8RE16_V: u32 = 0xfff0707f;
+pub const MATCH_VL8RE32_V: u32 = 0xe2806007;
+pub const MASK_VL8RE32_V: u32 = 0xfff0707f;
+pub const MATCH_VL8RE64_V: u32 = 0xe2807007;
+pub const MASK_VL8RE64_V: u32 = 0xfff0707f;
+pub const MATCH_VL8RE8_V: u32 = 0xe2800007;
+pub const MASK_VL8RE8_V: u32 = 0xfff0707f;
+pub const MATCH_VLE16_V: u32 = 0x5007;
+pub const MASK_VLE16_V: u32 = 0x1df0707f;
+pub const MATCH_VLE16FF_V: u32 = 0x1005007;
+pub const MASK_VLE16FF_V: u32 = 0x1df0707f;
+pub const MATCH_VLE32_V: u32 = 0x6007;
+pub const MASK_VLE32_V: u32 = 0x1df0707f;
+pub const MATCH_VLE32FF_V: u32 = 0x1006007;
+pub const MASK_VLE32FF_V: u32 = 0x1df0707f;
+pub const MATCH_VLE64_V: u32 = 0x7007;
+pub const MASK_VLE64_V: u32 = 0x1df0707f;
+pub const MATCH_VLE64FF_V: u32 = 0x1007007;
+pub const MASK_VLE64FF_V: u32 = 0x1df0707f;
+pub const MATCH_VLE8_V: u32 = 0x7;
+pub const MASK_VLE8_V: u32 = 0x1df0707f;
+pub const MATCH_VLE8FF_V: u32 = 0x1000007;
+pub const MASK_VLE8FF_V: u32 = 0x1df0707f;
+pub const MATCH_VLM_V: u32 = 0x2b00007;
+pub const MASK_VLM_V: u32 = 0xfff0707f;
+pub const MATCH_VLOXEI16_V: u32 = 0xc005007;
+pub const MASK_VLOXEI16_V: u32 = 0x1c00707f;
+pub const MATCH_VLOXEI32_V: u32 = 0xc006007;
+pub const MASK_VLOXEI32_V: u32 = 0x1c00707f;
+pub const MATCH_VLOXEI64_V: u32 = 0xc007007;
+pub const MASK_VLOXEI64_V: u32 = 0x1c00707f;
+pub const MATCH_VLOXEI8_V: u32 = 0xc000007;
+pub const MASK_VLOXEI8_V: u32 = 0x1c00707f;
+pub const MATCH_VLSE16_V: u32 = 0x8005007;
+pub const MASK_VLSE16_V: u32 = 0x1c00707f;
+pub const MATCH_VLSE32_V: u32 = 0x8006007;
+pub const MASK_VLSE32_V: u32 = 0x1c00707f;
+pub const MATCH_VLSE64_V: u32 = 0x8007007;
+pub const MASK_VLSE64_V: u32 = 0x1c00707f;
+pub const MATCH_VLSE8_V: u32 = 0x8000007;
+pub const MASK_VLSE8_V: u32 = 0x1c00707f;
+pub const MATCH_VLUXEI16_V: u32 = 0x4005007;
+pub const MASK_VLUXEI16_V: u32 = 0x1c00707f;
+pub const MATCH_VLUXEI32_V: u32 = 0x4006007;
+pub const MASK_VLUXEI32_V: u32 = 0x1c00707f;
+pub const MATCH_VLUXEI64_V: u32 = 0x4007007;
+pub const MASK_VLUXEI64_V: u32 = 0x1c00707f;
+pub const MATCH_VLUXEI8_V: u32 = 0x4000007;
+pub const MASK_VLUXEI8_V: u32 = 0x1c00707f;
+pub const MATCH_VMACC_VV: u32 = 0xb4002057;
+pub const MASK_VMACC_VV: u32 = 0xfc00707f;
+pub const MATCH_VMACC_VX: u32 = 0xb4006057;
+pub const MASK_VMACC_VX: u32 = 0xfc00707f;
+pub const MATCH_VMADD_VV: u32 = 0xa4002057;
+pub const MASK_VMADD_VV: u32 = 0xfc00707f;
+pub const MATCH_VMADD_VX: u32 = 0xa4006057;
+pub const MASK_VMADD_VX: u32 = 0xfc00707f;
+pub const MATCH_VMAND_MM: u32 = 0x66002057;
+pub const MASK_VMAND_MM: u32 = 0xfe00707f;
+pub const MATCH_VMANDN_MM: u32 = 0x62002057;
+pub const MASK_VMANDN_MM: u32 = 0xfe00707f;
+pub const MATCH_VMAX_VV: u32 = 0x1c000057;
+pub const MASK_VMAX_VV: u32 = 0xfc00707f;
+pub const MATCH_VMAX_VX: u32 = 0x1c004057;
+pub const MASK_VMAX_VX: u32 = 0xfc00707f;
+pub const MATCH_VMAXU_VV: u32 = 0x18000057;
+pub const MASK_VMAXU_VV: u32 = 0xfc00707f;
+pub const MATCH_VMAXU_VX: u32 = 0x18004057;
+pub const MASK_VMAXU_VX: u32 = 0xfc00707f;
+pub const MATCH_VMERGE_VIM: u32 = 0x5c003057;
+pub const MASK_VMERGE_VIM: u32 = 0xfe00707f;
+pub const MATCH_VMERGE_VVM: u32 = 0x5c000057;
+pub const MASK_VMERGE_VVM: u32 = 0xfe00707f;
+pub const MATCH_VMERGE_VXM: u32 = 0x5c004057;
+pub const MASK_VMERGE_VXM: u32 = 0xfe00707f;
+pub const MATCH_VMIN_VV: u32 = 0x14000057;
+pub const MASK_VMIN_VV: u32 = 0xfc00707f;
+pub const MATCH_VMIN_VX: u32 = 0x14004057;
+pub const MASK_VMIN_VX: u32 = 0xfc00707f;
+pub const MATCH_VMINU_VV: u32 = 0x10000057;
+pub const MASK_VMINU_VV: u32 = 0xfc00707f;
+pub const MATCH_VMINU_VX: u32 = 0x10004057;
+pub const MASK_VMINU_VX: u32 = 0xfc00707f;
+pub const MATCH_VMNAND_MM: u32 = 0x76002057;
+pub const MASK_VMNAND_MM: u32 = 0xfe00707f;
+pub const MATCH_VMNOR_MM: u32 = 0x7a002057;
+pub const MASK_VMNOR_MM: u32 = 0xfe00707f;
+pub const MATCH_VMOR_MM: u32 = 0x6a002057;
+pub const MASK_VMOR_MM: u32 = 0xfe00707f;
+pub const MATCH_VMORN_MM: u32 = 0x72002057;
+pub const MASK_VMORN_MM: u32 = 0xfe00707f;
+pub const MATCH_VMSBF_M: u32 = 0x5000a057;
+pub const MASK_VMSBF_M: u32 = 0xfc0ff07f;
+pub const MATCH_VMSEQ_VI: u32 = 0x60003057;
+pub const MASK_VMSEQ_VI: u32 = 0xfc00707f;
+pub const MATCH_VMSEQ_VV: u32 = 0x60000057;
+pub const MASK_VMSEQ_VV: u32 = 0xfc00707f;
+pub const MATCH_VMSEQ_VX: u32 = 0x60004057;
+pub const MASK_VMSEQ_VX: u32 = 0xfc00707f;
+pub const MATCH_VMSGT_VI: u32 = 0x7c003057;
+pub const MASK_VMSGT_VI: u32 = 0xfc00707f;
+pub const MATCH_VMSGT_VX: u32 = 0x7c004057;
+pub const MASK_VMSGT_VX: u32 = 0xfc00707f;
+pub const MATCH_VMSGTU_VI: u32 = 0x78003057;
+pub const MASK_VMSGTU_VI: u32 = 0xfc00707f;
+pub const MATCH_VMSGTU_VX: u32 = 0x78004057;
+pub const MASK_VMSGTU_VX: u32 = 0xfc00707f;
+pub const MATCH_VMSIF_M: u32 = 0x5001a057;
+pub const MASK_VMSIF_M: u32 = 0xfc0ff07f;
+pub const MATCH_VMSLE_VI: u32 = 0x74003057;
+pub const MASK_VMSLE_VI: u32 = 0xfc00707f;
+pub const MATCH_VMSLE_VV: u32 = 0x74000057;
+pub const MASK_VMSLE_VV: u32 = 0xfc00707f;
+pub const MATCH_VMSLE_VX: u32 = 0x74004057;
+pub const MASK_VMSLE_VX: u32 = 0xfc00707f;
+pub const MATCH_VMSLEU_VI: u32 = 0x70003057;
+pub const MASK_VMSLEU_VI: u32 = 0xfc00707f;
+pub const MATCH_VMSLEU_VV: u32 = 0x70000057;
+pub const MASK_VMSLEU_VV: u32 = 0xfc00707f;
+pub const MATCH_VMSLEU_VX: u32 = 0x70004057;
+pub const MASK_VMSLEU_VX: u32 = 0xfc00707f;
+pub const MATCH_VMSLT_VV: u32 = 0x6c000057;
+pub const MASK_VMSLT_VV: u32 = 0xfc00707f;
+pub const MATCH_VMSLT_VX: u32 = 0x6c004057;
+pub const MASK_VMSLT_VX: u32 = 0xfc00707f;
+pub const MATCH_VMSLTU_VV: u32 = 0x68000057;
+pub const MASK_VMSLTU_VV: u32 = 0xfc00707f;
+pub const MATCH_VMSLTU_VX: u32 = 0x68004057;
+pub const MASK_VMSLTU_VX: u32 = 0xfc00707f;
+pub const MATCH_VMSNE_VI: u32 = 0x64003057;
+pub const MASK_VMSNE_VI: u32 = 0xfc00707f;
+pub const MATCH_VMSNE_VV: u32 = 0x64000057;
+pub const MASK_VMSNE_VV: u32 = 0xfc00707f;
+pub const MATCH_VMSNE_VX: u32 = 0x64004057;
+pub const MASK_VMSNE_VX: u32 = 0xfc00707f;
+pub const MATCH_VMSOF_M: u32 = 0x50012057;
+pub const MASK_VMSOF_M: u32 = 0xfc0ff07f;
+pub const MATCH_VMUL_VV: u32 = 0x94002057;
+pub const MASK_VMUL_VV: u32 = 0xfc00707f;
+pub const MATCH_VMUL_VX: u32 = 0x94006057;
+pub const MASK_VMUL_VX: u32 = 0xfc00707f;
+pub const MATCH_VMULH_VV: u32 = 0x9c002057;
+pub const MASK_VMULH_VV: u32 = 0xfc00707f;
+pub const MATCH_VMULH_VX: u32 = 0x9c006057;
+pub const MASK_VMULH_VX: u32 = 0xfc00707f;
+pub const MATCH_VMULHSU_VV: u32 = 0x98002057;
+pub const MASK_VMULHSU_VV: u32 = 0xfc00707f;
+pub const MATCH_VMULHSU_VX: u32 = 0x98006057;
+pub const MASK_VMULHSU_VX: u32 = 0xfc00707f;
+pub const MATCH_VMULHU_VV: u32 = 0x90002057;
+pub const MASK_VMULHU_VV: u32 = 0xfc00707f;
+pub const MATCH_VMULHU_VX: u32 = 0x90006057;
+pub const MASK_VMULHU_VX: u32 = 0xfc00707f;
+pub const MATCH_VMV_S_X: u32 = 0x42006057;
+pub const MASK_VMV_S_X: u32 = 0xfff0707f;
+pub const MATCH_VMV_V_I: u32 = 0x5e003057;
+pub const MASK_VMV_V_I: u32 = 0xfff0707f;
+pub const MATCH_VMV_V_V: u32 = 0x5e000057;
+pub const MASK_VMV_V_V: u32 = 0xfff0707f;
+pub const MATCH_VMV_V_X: u32 = 0x5e004057;
+pub const MASK_VMV_V_X: u32 = 0xfff0707f;
+pub const MATCH_VMV_X_S: u32 = 0x42002057;
+pub const MASK_VMV_X_S: u32 = 0xfe0ff07f;
+pub const MATCH_VMV1R_V: u32 = 0x9e003057;
+pub const MASK_VMV1R_V: u32 = 0xfe0ff07f;
+pub const MATCH_VMV2R_V: u32 = 0x9e00b057;
+pub const MASK_VMV2R_V: u32 = 0xfe0ff07f;
+pub const MATCH_VMV4R_V: u32 = 0x9e01b057;
+pub const MASK_VMV4R_V: u32 = 0xfe0ff07f;
+pub const MATCH_VMV8R_V: u32 = 0x9e03b057;
+pub const MASK_VMV8R_V: u32 = 0xfe0ff07f;
+pub const MATCH_VMXNOR_MM: u32 = 0x7e002057;
+pub const MASK_VMXNOR_MM: u32 = 0xfe00707f;
+pub const MATCH_VMXOR_MM: u32 = 0x6e002057;
+pub const MASK_VMXOR_MM: u32 = 0xfe00707f;
+pub const MATCH_VNMSAC_VV: u32 = 0xbc002057;
+pub const MASK_VNMSAC_VV: u32 = 0xfc00707f;
+pub const MATCH_VNMSAC_VX: u32 = 0xbc006057;
+pub const MASK_VNMSAC_VX: u32 = 0xfc00707f;
+pub const MATCH_VNMSUB_VV: u32 = 0xac002057;
+pub const MASK_VNMSUB_VV: u32 = 0xfc00707f;
+pub const MATCH_VNMSUB_VX: u32 = 0xac006057;
+pub const MASK_VNMSUB_VX: u32 = 0xfc00707f;
+pub const MATCH_VOR_VI: u32 = 0x28003057;
+pub const MASK_VOR_VI: u32 = 0xfc00707f;
+pub const MATCH_VOR_VV: u32 = 0x28000057;
+pub const MASK_VOR_VV: u32 = 0xfc00707f;
+pub const MATCH_VOR_VX: u32 = 0x28004057;
+pub const MASK_VOR_VX: u32 = 0xfc00707f;
+pub const MATCH_VREDAND_VS: u32 = 0x4002057;
+pub const MASK_VREDAND_VS: u32 = 0xfc00707f;
+pub const MATCH_VREDMAX_VS: u32 = 0x1c002057;
+pub const MASK_VREDMAX_VS: u32 = 0xfc00707f;
+pub const MATCH_VREDMAXU_VS: u32 = 0x18002057;
+pub const MASK_VREDMAXU_VS: u32 = 0xfc00707f;
+pub const MATCH_VREDMIN_VS: u32 = 0x14002057;
+pub const MASK_VREDMIN_VS: u32 = 0xfc00707f;
+pub const MATCH_VREDMINU_VS: u32 = 0x10002057;
+pub const MASK_VREDMINU_VS: u32 = 0xfc00707f;
+pub const MATCH_VREDOR_VS: u32 = 0x8002057;
+pub const MASK_VREDOR_VS: u32 = 0xfc00707f;
+pub const MATCH_VREDSUM_VS: u32 = 0x2057;
+pub const MASK_VREDSUM_VS: u32 = 0xfc00707f;
+pub const MATCH_VREDXOR_VS: u32 = 0xc002057;
+pub const MASK_VREDXOR_VS: u32 = 0xfc00707f;
+pub const MATCH_VREM_VV: u32 = 0x8c002057;
+pub const MASK_VREM_VV: u32 = 0xfc00707f;
+pub const MATCH_VREM_VX: u32 = 0x8c006057;
+pub const MASK_VREM_VX: u32 = 0xfc00707f;
+pub const MATCH_VREMU_VV: u32 = 0x88002057;
+pub const MASK_VREMU_VV: u32 = 0xfc00707f;
+pub const MATCH_VREMU_VX: u32 = 0x88006057;
+pub const MASK_VREMU_VX: u32 = 0xfc00707f;
+pub const MATCH_VRGATHER_VI: u32 = 0x30003057;
+pub const MASK_VRGATHER_VI: u32 = 0xfc00707f;
+pub const MATCH_VRGATHER_VV: u32 = 0x30000057;
+pub const MASK_VRGATHER_VV: u32 = 0xfc00707f;
+pub const MATCH_VRGATHER_VX: u32 = 0x30004057;
+pub const MASK_VRGATHER_VX: u32 = 0xfc00707f;
+pub const MATCH_VRSUB_VI: u32 = 0xc003057;
+pub const MASK_VRSUB_VI: u32 = 0xfc00707f;
+pub const MATCH_VRSUB_VX: u32 = 0xc004057;
+pub const MASK_VRSUB_VX: u32 = 0xfc00707f;
+pub const MATCH_VS1R_V: u32 = 0x2800027;
+pub const MASK_VS1R_V: u32 = 0xfff0707f;
+pub const MATCH_VS2R_V: u32 = 0x22800027;
+pub const MASK_VS2R_V: u32 = 0xfff0707f;
+pub const MATCH_VS4R_V: u32 = 0x62800027;
+pub const MASK_VS4R_V: u32 = 0xfff0707f;
+pub const MATCH_VS8R_V: u32 = 0xe2800027;
+pub const MASK_VS8R_V: u32 = 0xfff0707f;
+pub const MATCH_VSADD_VI: u32 = 0x84003057;
+pub const MASK_VSADD_VI: u32 = 0xfc00707f;
+pub const MATCH_VSADD_VV: u32 = 0x84000057;
+pub const MASK_VSADD_VV: u32 = 0xfc00707f;
+pub const MATCH_VSADD_VX: u32 = 0x84004057;
+pub const MASK_VSADD_VX: u32 = 0xfc00707f;
+pub const MATCH_VSADDU_VI: u32 = 0x80003057;
+pub const MASK_VSADDU_VI: u32 = 0xfc00707f;
+pub const MATCH_VSADDU_VV: u32 = 0x80000057;
+pub const MASK_VSADDU_VV: u32 = 0xfc00707f;
+pub const MATCH_VSADDU_VX: u32 = 0x80004057;
+pub const MASK_VSADDU_VX: u32 = 0xfc00707f;
+pub const MATCH_VSE16_V: u32 = 0x5027;
+pub const MASK_VSE16_V: u32 = 0x1df0707f;
+pub const MATCH_VSE32_V: u32 = 0x6027;
+pub const MASK_VSE32_V: u32 = 0x1df0707f;
+pub const MATCH_VSE64_V: u32 = 0x7027;
+pub const MASK_VSE64_V: u32 = 0x1df0707f;
+pub const MATCH_VSE8_V: u32 = 0x27;
+pub const MASK_VSE8_V: u32 = 0x1df0707f;
+pub const MATCH_VSETIVLI: u32 = 0xc0007057;
+pub const MASK_VSETIVLI: u32 = 0xc000707f;
+pub const MATCH_VSETVL: u32 = 0x80007057;
+pub const MASK_VSETVL: u32 = 0xfe00707f;
+pub const MATCH_VSETVLI: u32 = 0x7057;
+pub const MASK_VSETVLI: u32 = 0x8000707f;
+pub const MATCH_VSEXT_VF2: u32 = 0x4803a057;
+pub const MASK_VSEXT_VF2: u32 = 0xfc0ff07f;
+pub const MATCH_VSEXT_VF4: u32 = 0x4802a057;
+pub const MASK_VSEXT_VF4: u32 = 0xfc0ff07f;
+pub const MATCH_VSEXT_VF8: u32 = 0x4801a057;
+pub const MASK_VSEXT_VF8: u32 = 0xfc0ff07f;
+pub const MATCH_VSLIDE1DOWN_VX: u32 = 0x3c006057;
+pub const MASK_VSLIDE1DOWN_VX: u32 = 0xfc00707f;
+pub const MATCH_VSLIDE1UP_VX: u32 = 0x38006057;
+pub const MASK_VSLIDE1UP_VX: u32 = 0xfc00707f;
+pub const MATCH_VSLIDEDOWN_VI: u32 = 0x3c003057;
+pub const MASK_VSLIDEDOWN_VI: u32 = 0xfc00707f;
+pub const MATCH_VSLIDEDOWN_VX: u32 = 0x3c004057;
+pub const MASK_VSLIDEDOWN_VX: u32 = 0xfc00707f;
+pub const MATCH_VSLIDEUP_VI: u32 = 0x38003057;
+pub const MASK_VSLIDEUP_VI: u32 = 0xfc00707f;
+pub const MATCH_VSLIDEUP_VX: u32 = 0x38004057;
+pub const MASK_VSLIDEUP_VX: u32 = 0xfc00707f;
+pub const MATCH_VSLL_VI: u32 = 0x94003057;
+pub const MASK_VSLL_VI: u32 = 0xfc00707f;
+pub const MATCH_VSLL_VV: u32 = 0x94000057;
+pub const MASK_VSLL_VV: u32 = 0xfc00707f;
+pub const MATCH_VSLL_VX: u32 = 0x94004057;
+pub const MASK_VSLL_VX: u32 = 0xfc00707f;
+pub const MATCH_VSM_V: u32 = 0x2b00027;
+pub const MASK_VSM_V: u32 = 0xfff0707f;
+pub const MATCH_VSOXEI16_V: u32 = 0xc005027;
+pub const MASK_VSOXEI16_V: u32 = 0x1c00707f;
+pub const MATCH_VSOXEI32_V: u32 = 0xc006027;
+pub const MASK_VSOXEI32_V: u32 = 0x1c00707f;
+pub const MATCH_VSOXEI64_V: u32 = 0xc007027;
+pub const MASK_VSOXEI64_V: u32 = 0x1c00707f;
+pub const MATCH_VSOXEI8_V: u32 = 0xc000027;
+pub const MASK_VSOXEI8_V: u32 = 0x1c00707f;
+pub const MATCH_VSRA_VI: u32 = 0xa4003057;
+pub const MASK_VSRA_VI: u32 = 0xfc00707f;
+pub const MATCH_VSRA_VV: u32 = 0xa4000057;
+pub const MASK_VSRA_VV: u32 = 0xfc00707f;
+pub const MATCH_VSRA_VX: u32 = 0xa4004057;
+pub const MASK_VSRA_VX: u32 = 0xfc00707f;
+pub const MATCH_VSRL_VI: u32 = 0xa0003057;
+pub const MASK_VSRL_VI: u32 = 0xfc00707f;
+pub const MATCH_VSRL_VV: u32 = 0xa0000057;
+pub const MASK_VSRL_VV: u32 = 0xfc00707f;
+pub const MATCH_VSRL_VX: u32 = 0xa0004057;
+pub const MASK_VSRL_VX: u32 = 0xfc00707f;
+pub const MATCH_VSSE16_V: u32 = 0x8005027;
+pub const MASK_VSSE16_V: u32 = 0x1c00707f;
+pub const MATCH_VSSE32_V: u32 = 0x8006027;
+pub const MASK_VSSE32_V: u32 = 0x1c00707f;
+pub const MATCH_VSSE64_V: u32 = 0x8007027;
+pub const MASK_VSSE64_V: u32 = 0x1c00707f;
+pub const MATCH_VSSE8_V: u32 = 0x8000027;
+pub const MASK_VSSE8_V: u32 = 0x1c00707f;
+pub const MATCH_VSSUB_VV: u32 = 0x8c000057;
+pub const MASK_VSSUB_VV: u32 = 0xfc00707f;
+pub const MATCH_VSSUB_VX: u32 = 0x8c004057;
+pub const MASK_VSSUB_VX: u32 = 0xfc00707f;
+pub const MATCH_VSSUBU_VV: u32 = 0x88000057;
+pub const MASK_VSSUBU_VV: u32 = 0xfc00707f;
+pub const MATCH_VSSUBU_VX: u32 = 0x88004057;
+pub const MASK_VSSUBU_VX: u32 = 0xfc00707f;
+pub const MATCH_VSUB_VV: u32 = 0x8000057;
+pub const MASK_VSUB_VV: u32 = 0xfc00707f;
+pub const MATCH_VSUB_VX: u32 = 0x8004057;
+pub const MASK_VSUB_VX: u32 = 0xfc00707f;
+pub const MATCH_VSUXEI16_V: u32 = 0x4005027;
+pub const MASK_VSUXEI16_V: u32 = 0x1c00707f;
+pub const MATCH_VSUXEI32_V: u32 = 0x4006027;
+pub const MASK_VSUXEI32_V: u32 = 0x1c00707f;
+pub const MATCH_VSUXEI64_V: u32 = 0x4007027;
+pub const MASK_VSUXEI64_V: u32 = 0x1c00707f;
+pub const MATCH_VSUXEI8_V: u32 = 0x4000027;
+pub const MASK_VSUXEI8_V: u32 = 0x1c00707f;
+pub const MATCH_VXOR_VI: u32 = 0x2c003057;
+pub const MASK_VXOR_VI: u32 = 0xfc00707f;
+pub const MATCH_VXOR_VV: u32 = 0x2c000057;
+pub const MASK_VXOR_VV: u32 = 0xfc00707f;
+pub const MATCH_VXOR_VX: u32 = 0x2c004057;
+pub const MASK_VXOR_VX: u32 = 0xfc00707f;
+pub const MATCH_VZEXT_VF2: u32 = 0x48032057;
+pub const MASK_VZEXT_VF2: u32 = 0xfc0ff07f;
+pub const MATCH_VZEXT_VF4: u32 = 0x48022057;
+pub const MASK_VZEXT_VF4: u32 = 0xfc0ff07f;
+pub const MATCH_VZEXT_VF8: u32 = 0x48012057;
+pub const MASK_VZEXT_VF8: u32 = 0xfc0ff07f;
+// make EXTENSIONS='rv_zba rv64_zba'
+pub const MATCH_ADD_UW: u32 = 0x800003b;
+pub const MASK_ADD_UW: u32 = 0xfe00707f;
+pub const MATCH_SH1ADD: u32 = 0x20002033;
+pub const MASK_SH1ADD: u32 = 0xfe00707f;
+pub const MATCH_SH1ADD_UW: u32 = 0x2000203b;
+pub const MASK_SH1ADD_UW: u32 = 0xfe00707f;
+pub const MATCH_SH2ADD: u32 = 0x20004033;
+pub const MASK_SH2ADD: u32 = 0xfe00707f;
+pub const MATCH_SH2ADD_UW: u32 = 0x2000403b;
+pub const MASK_SH2ADD_UW: u32 = 0xfe00707f;
+pub const MATCH_SH3ADD: u32 = 0x20006033;
+pub const MASK_SH3ADD: u32 = 0xfe00707f;
+pub const MATCH_SH3ADD_UW: u32 = 0x2000603b;
+pub const MASK_SH3ADD_UW: u32 = 0xfe00707f;
+pub const MATCH_SLLI_UW: u32 = 0x800101b;
+pub const MASK_SLLI_UW: u32 = 0xfc00707f;
+// make EXTENSIONS='rv_zbc'
+pub const MATCH_CLMUL: u32 = 0xa001033;
+pub const MASK_CLMUL: u32 = 0xfe00707f;
+pub const MATCH_CLMULH: u32 = 0xa003033;
+pub const MASK_CLMULH: u32 = 0xfe00707f;
+pub const MATCH_CLMULR: u32 = 0xa002033;
+pub const MASK_CLMULR: u32 = 0xfe00707f;
+// make EXTENSIONS='rv_zbs rv64_zbs'
+pub const MATCH_BCLR: u32 = 0x48001033;
+pub const MASK_BCLR: u32 = 0xfe00707f;
+pub const MATCH_BCLRI: u32 = 0x48001013;
+pub const MASK_BCLRI: u32 = 0xfc00707f;
+pub const MATCH_BEXT: u32 = 0x48005033;
+pub const MASK_BEXT: u32 = 0xfe00707f;
+pub const MATCH_BEXTI: u32 = 0x48005013;
+pub const MASK_BEXTI: u32 = 0xfc00707f;
+pub const MATCH_BINV: u32 = 0x68001033;
+pub const MASK_BINV: u32 = 0xfe00707f;
+pub const MATCH_BINVI: u32 = 0x68001013;
+pub const MASK_BINVI: u32 = 0xfc00707f;
+pub const MATCH_BSET: u32 = 0x28001033;
+pub const MASK_BSET: u32 = 0xfe00707f;
+pub const MATCH_BSETI: u32 = 0x28001013;
+pub const MASK_BSETI: u32 = 0xfc00707f;
+// make EXTENSIONS='rv_zicond'
+pub const MATCH_CZERO_EQZ: u32 = 0xe005033;
+pub const MASK_CZERO_EQZ: u32 = 0xfe00707f;
+pub const MATCH_CZERO_NEZ: u32 = 0xe007033;
+pub const MASK_CZERO_NEZ: u32 = 0xfe00707f;
+// make EXTENSIONS='rv_zicbo'
+pub const MATCH_CBO_CLEAN: u32 = 0x10200f;
+pub const MASK_CBO_CLEAN: u32 = 0xfff07fff;
+pub const MATCH_CBO_FLUSH: u32 = 0x20200f;
+pub const MASK_CBO_FLUSH: u32 = 0xfff07fff;
+pub const MATCH_CBO_INVAL: u32 = 0x200f;
+pub const MASK_CBO_INVAL: u32 = 0xfff07fff;
+pub const MATCH_CBO_ZERO: u32 = 0x40200f;
+pub const MASK_CBO_ZERO: u32 = 0xfff07fff;
+// make EXTENSIONS='rv_zawrs'
+pub const MATCH_WRS_NTO: u32 = 0xd00073;
+pub const MASK_WRS_NTO: u32 = 0xffffffff;
+pub const MATCH_WRS_STO: u32 = 0x1d00073;
+pub const MASK_WRS_STO: u32 = 0xffffffff;
+// make EXTENSIONS='rv_zacas rv64_zacas'
+pub const MATCH_AMOCAS_D: u32 = 0x2800302f;
+pub const MASK_AMOCAS_D: u32 = 0xf800707f;
+pub const MATCH_AMOCAS_Q: u32 = 0x2800402f;
+pub const MASK_AMOCAS_Q: u32 = 0xf800707f;
+pub const MATCH_AMOCAS_W: u32 = 0x2800202f;
+pub const MASK_AMOCAS_W: u32 = 0xf800707f;
+// make EXTENSIONS='rv_zabha rv_zacas'
+pub const MATCH_AMOADD_B: u32 = 0x2f;
+pub const MASK_AMOADD_B: u32 = 0xf800707f;
+pub const MATCH_AMOADD_H: u32 = 0x102f;
+pub const MASK_AMOADD_H: u32 = 0xf800707f;
+pub const MATCH_AMOAND_B: u32 = 0x6000002f;
+pub const MASK_AMOAND_B: u32 = 0xf800707f;
+pub const MATCH_AMOAND_H: u32 = 0x6000102f;
+pub const MASK_AMOAND_H: u32 = 0xf800707f;
+pub const MATCH_AMOCAS_B: u32 = 0x2800002f;
+pub const MASK_AMOCAS_B: u32 = 0xf800707f;
+pub const MATCH_AMOCAS_H: u32 = 0x2800102f;
+pub const MASK_AMOCAS_H: u32 = 0xf800707f;
+pub const MATCH_AMOMAX_B: u32 = 0xa000002f;
+pub const MASK_AMOMAX_B: u32 = 0xf800707f;
+pub const MATCH_AMOMAX_H: u32 = 0xa000102f;
+pub const MASK_AMOMAX_H: u32 = 0xf800707f;
+pub const MATCH_AMOMAXU_B: u32 = 0xe000002f;
+pub const MASK_AMOMAXU_B: u32 = 0xf800707f;
+pub const MATCH_AMOMAXU_H: u32 = 0xe000102f;
+pub const MASK_AMOMAXU_H: u32 = 0xf800707f;
+pub const MATCH_AMOMIN_B: u32 = 0x8000002f;
+pub const MASK_AMOMIN_B: u32 = 0xf800707f;
+pub const MATCH_AMOMIN_H: u32 = 0x8000102f;
+pub const MASK_AMOMIN_H: u32 = 0xf800707f;
+pub const MATCH_AMOMINU_B: u32 = 0xc000002f;
+pub const MASK_AMOMINU_B: u32 = 0xf800707f;
+pub const MATCH_AMOMINU_H: u32 = 0xc000102f;
+pub const MASK_AMOMINU_H: u32 = 0xf800707f;
+pub const MATCH_AMOOR_B: u32 = 0x4000002f;
+pub const MASK_AMOOR_B: u32 = 0xf800707f;
+pub const MATCH_AMOOR_H: u32 = 0x4000102f;
+pub const MASK_AMOOR_H: u32 = 0xf800707f;
+pub const MATCH_AMOSWAP_B: u32 = 0x800002f;
+pub const MASK_AMOSWAP_B: u32 = 0xf800707f;
+pub const MATCH_AMOSWAP_H: u32 = 0x800102f;
+pub const MASK_AMOSWAP_H: u32 = 0xf800707f;
+pub const MATCH_AMOXOR_B: u32 = 0x2000002f;
+pub const MASK_AMOXOR_B: u32 = 0xf800707f;
+pub const MATCH_AMOXOR_H: u32 = 0x2000102f;
+pub const MASK_AMOXOR_H: u32 = 0xf800707f;
+// make EXTENSIONS='rv_h rv64_h'
+pub const MATCH_HFENCE_GVMA: u32 = 0x62000073;
+pub const MASK_HFENCE_GVMA: u32 = 0xfe007fff;
+pub const MATCH_HFENCE_VVMA: u32 = 0x22000073;
+pub const MASK_HFENCE_VVMA: u32 = 0xfe007fff;
+pub const MATCH_HLV_B: u32 = 0x60004073;
+pub const MASK_HLV_B: u32 = 0xfff0707f;
+pub const MATCH_HLV_BU: u32 = 0x60104073;
+pub const MASK_HLV_BU: u32 = 0xfff0707f;
+pub const MATCH_HLV_D: u32 = 0x6c004073;
+pub const MASK_HLV_D: u32 = 0xfff0707f;
+pub const MATCH_HLV_H: u32 = 0x64004073;
+pub const MASK_HLV_H: u32 = 0xfff0707f;
+pub const MATCH_HLV_HU: u32 = 0x64104073;
+pub const MASK_HLV_HU: u32 = 0xfff0707f;
+pub const MATCH_HLV_W: u32 = 0x68004073;
+pub const MASK_HLV_W: u32 = 0xfff0707f;
+pub const MATCH_HLV_WU: u32 = 0x68104073;
+pub const MASK_HLV_WU: u32 = 0xfff0707f;
+pub const MATCH_HLVX_HU: u32 = 0x64304073;
+pub const MASK_HLVX_HU: u32 = 0xfff0707f;
+pub const MATCH_HLVX_WU: u32 = 0x68304073;
+pub const MASK_HLVX_WU: u32 = 0xfff0707f;
+pub const MATCH_HSV_B: u32 = 0x62004073;
+pub const MASK_HSV_B: u32 = 0xfe007fff;
+pub const MATCH_HSV_D: u32 = 0x6e004073;
+pub const MASK_HSV_D: u32 = 0xfe007fff;
+pub const MATCH_HSV_H: u32 = 0x66004073;
+pub const MASK_HSV_H: u32 = 0xfe007fff;
+pub const MATCH_HSV_W: u32 = 0x6a004073;
+pub const MASK_HSV_W: u32 = 0xfe007fff;
+// make EXTENSIONS='rv64_zknd rv64_zkne rv_zknh rv64_zknh'
+pub const MATCH_AES64DS: u32 = 0x3a000033;
+pub const MASK_AES64DS: u32 = 0xfe00707f;
+pub const MATCH_AES64DSM: u32 = 0x3e000033;
+pub const MASK_AES64DSM: u32 = 0xfe00707f;
+pub const MATCH_AES64ES: u32 = 0x32000033;
+pub const MASK_AES64ES: u32 = 0xfe00707f;
+pub const MATCH_AES64ESM: u32 = 0x36000033;
+pub const MASK_AES64ESM: u32 = 0xfe00707f;
+pub const MATCH_AES64IM: u32 = 0x30001013;
+pub const MASK_AES64IM: u32 = 0xfff0707f;
+pub const MATCH_AES64KS1I: u32 = 0x31001013;
+pub const MASK_AES64KS1I: u32 = 0xff00707f;
+pub const MATCH_AES64KS2: u32 = 0x7e000033;
+pub const MASK_AES64KS2: u32 = 0xfe00707f;
+pub const MATCH_SHA256SIG0: u32 = 0x10201013;
+pub const MASK_SHA256SIG0: u32 = 0xfff0707f;
+pub const MATCH_SHA256SIG1: u32 = 0x10301013;
+pub const MASK_SHA256SIG1: u32 = 0xfff0707f;
+pub const MATCH_SHA256SUM0: u32 = 0x10001013;
+pub const MASK_SHA256SUM0: u32 = 0xfff0707f;
+pub const MATCH_SHA256SUM1: u32 = 0x10101013;
+pub const MASK_SHA256SUM1: u32 = 0xfff0707f;
+pub const MATCH_SHA512SIG0: u32 = 0x10601013;
+pub const MASK_SHA512SIG0: u32 = 0xfff0707f;
+pub const MATCH_SHA512SIG1: u32 = 0x10701013;
+pub const MASK_SHA512SIG1: u32 = 0xfff0707f;
+pub const MATCH_SHA512SUM0: u32 = 0x10401013;
+pub const MASK_SHA512SUM0: u32 = 0xfff0707f;
+pub const MATCH_SHA512SUM1: u32 = 0x10501013;
+pub const MASK_SHA512SUM1: u32 = 0xfff0707f;
+// make EXTENSIONS='rv_zksed rv_zksh'
+pub const MATCH_SM3P0: u32 = 0x10801013;
+pub const MASK_SM3P0: u32 = 0xfff0707f;
+pub const MATCH_SM3P1: u32 = 0x10901013;
+pub const MASK_SM3P1: u32 = 0xfff0707f;
+pub const MATCH_SM4ED: u32 = 0x30000033;
+pub const MASK_SM4ED: u32 = 0x3e00707f;
+pub const MATCH_SM4KS: u32 = 0x34000033;
+pub const MASK_SM4KS: u32 = 0x3e00707f;
+// make EXTENSIONS='rv_zihintntl rv_c_zihintntl'
+pub const MATCH_NTL_P1: u32 = 0x200033;
+pub const MASK_NTL_P1: u32 = 0xffffffff;
+pub const MATCH_NTL_PALL: u32 = 0x300033;
+pub const MASK_NTL_PALL: u32 = 0xffffffff;
+pub const MATCH_NTL_S1: u32 = 0x400033;
+pub const MASK_NTL_S1: u32 = 0xffffffff;
+pub const MATCH_NTL_ALL: u32 = 0x500033;
+pub const MASK_NTL_ALL: u32 = 0xffffffff;
+pub const MATCH_C_NTL_P1: u32 = 0x900a;
+pub const MASK_C_NTL_P1: u32 = 0xffff;
+pub const MATCH_C_NTL_PALL: u32 = 0x900e;
+pub const MASK_C_NTL_PALL: u32 = 0xffff;
+pub const MATCH_C_NTL_S1: u32 = 0x9012;
+pub const MASK_C_NTL_S1: u32 = 0xffff;
+pub const MATCH_C_NTL_ALL: u32 = 0x9016;
+pub const MASK_C_NTL_ALL: u32 = 0xffff;
 pub const CSR_FFLAGS: u16 = 0x1;
 pub const CSR_FRM: u16 = 0x2;
 pub const CSR_FCSR: u16 = 0x3;
@@ -745,6 +1535,442 @@ pub const CSR_MHPMCOUNTER29H: u16 = 0xb9d;
 pub const CSR_MHPMCOUNTER30H: u16 = 0xb9e;
 pub const CSR_MHPMCOUNTER31H: u16 = 0xb9f;
 
+// the csr names of the encoding above, used by the reports
+pub const CSR_NAMES: &[(u16, &str)] = &[
+    (CSR_FFLAGS, "fflags"),
+    (CSR_FRM, "frm"),
+    (CSR_FCSR, "fcsr"),
+    (CSR_VSTART, "vstart"),
+    (CSR_VXSAT, "vxsat"),
+    (CSR_VXRM, "vxrm"),
+    (CSR_VCSR, "vcsr"),
+    (CSR_SEED, "seed"),
+    (CSR_JVT, "jvt"),
+    (CSR_CYCLE, "cycle"),
+    (CSR_TIME, "time"),
+    (CSR_INSTRET, "instret"),
+    (CSR_HPMCOUNTER3, "hpmcounter3"),
+    (CSR_HPMCOUNTER4, "hpmcounter4"),
+    (CSR_HPMCOUNTER5, "hpmcounter5"),
+    (CSR_HPMCOUNTER6, "hpmcounter6"),
+    (CSR_HPMCOUNTER7, "hpmcounter7"),
+    (CSR_HPMCOUNTER8, "hpmcounter8"),
+    (CSR_HPMCOUNTER9, "hpmcounter9"),
+    (CSR_HPMCOUNTER10, "hpmcounter10"),
+    (CSR_HPMCOUNTER11, "hpmcounter11"),
+    (CSR_HPMCOUNTER12, "hpmcounter12"),
+    (CSR_HPMCOUNTER13, "hpmcounter13"),
+    (CSR_HPMCOUNTER14, "hpmcounter14"),
+    (CSR_HPMCOUNTER15, "hpmcounter15"),
+    (CSR_HPMCOUNTER16, "hpmcounter16"),
+    (CSR_HPMCOUNTER17, "hpmcounter17"),
+    (CSR_HPMCOUNTER18, "hpmcounter18"),
+    (CSR_HPMCOUNTER19, "hpmcounter19"),
+    (CSR_HPMCOUNTER20, "hpmcounter20"),
+    (CSR_HPMCOUNTER21, "hpmcounter21"),
+    (CSR_HPMCOUNTER22, "hpmcounter22"),
+    (CSR_HPMCOUNTER23, "hpmcounter23"),
+    (CSR_HPMCOUNTER24, "hpmcounter24"),
+    (CSR_HPMCOUNTER25, "hpmcounter25"),
+    (CSR_HPMCOUNTER26, "hpmcounter26"),
+    (CSR_HPMCOUNTER27, "hpmcounter27"),
+    (CSR_HPMCOUNTER28, "hpmcounter28"),
+    (CSR_HPMCOUNTER29, "hpmcounter29"),
+    (CSR_HPMCOUNTER30, "hpmcounter30"),
+    (CSR_HPMCOUNTER31, "hpmcounter31"),
+    (CSR_VL, "vl"),
+    (CSR_VTYPE, "vtype"),
+    (CSR_VLENB, "vlenb"),
+    (CSR_SSTATUS, "sstatus"),
+    (CSR_SEDELEG, "sedeleg"),
+    (CSR_SIDELEG, "sideleg"),
+    (CSR_SIE, "sie"),
+    (CSR_STVEC, "stvec"),
+    (CSR_SCOUNTEREN, "scounteren"),
+    (CSR_SENVCFG, "senvcfg"),
+    (CSR_SSTATEEN0, "sstateen0"),
+    (CSR_SSTATEEN1, "sstateen1"),
+    (CSR_SSTATEEN2, "sstateen2"),
+    (CSR_SSTATEEN3, "sstateen3"),
+    (CSR_SSCRATCH, "sscratch"),
+    (CSR_SEPC, "sepc"),
+    (CSR_SCAUSE, "scause"),
+    (CSR_STVAL, "stval"),
+    (CSR_SIP, "sip"),
+    (CSR_STIMECMP, "stimecmp"),
+    (CSR_SISELECT, "siselect"),
+    (CSR_SIREG, "sireg"),
+    (CSR_STOPEI, "stopei"),
+    (CSR_SATP, "satp"),
+    (CSR_SCONTEXT, "scontext"),
+    (CSR_VSSTATUS, "vsstatus"),
+    (CSR_VSIE, "vsie"),
+    (CSR_VSTVEC, "vstvec"),
+    (CSR_VSSCRATCH, "vsscratch"),
+    (CSR_VSEPC, "vsepc"),
+    (CSR_VSCAUSE, "vscause"),
+    (CSR_VSTVAL, "vstval"),
+    (CSR_VSIP, "vsip"),
+    (CSR_VSTIMECMP, "vstimecmp"),
+    (CSR_VSISELECT, "vsiselect"),
+    (CSR_VSIREG, "vsireg"),
+    (CSR_VSTOPEI, "vstopei"),
+    (CSR_VSATP, "vsatp"),
+    (CSR_HSTATUS, "hstatus"),
+    (CSR_HEDELEG, "hedeleg"),
+    (CSR_HIDELEG, "hideleg"),
+    (CSR_HIE, "hie"),
+    (CSR_HTIMEDELTA, "htimedelta"),
+    (CSR_HCOUNTEREN, "hcounteren"),
+    (CSR_HGEIE, "hgeie"),
+    (CSR_HVIEN, "hvien"),
+    (CSR_HVICTL, "hvictl"),
+    (CSR_HENVCFG, "henvcfg"),
+    (CSR_HSTATEEN0, "hstateen0"),
+    (CSR_HSTATEEN1, "hstateen1"),
+    (CSR_HSTATEEN2, "hstateen2"),
+    (CSR_HSTATEEN3, "hstateen3"),
+    (CSR_HTVAL, "htval"),
+    (CSR_HIP, "hip"),
+    (CSR_HVIP, "hvip"),
+    (CSR_HVIPRIO1, "hviprio1"),
+    (CSR_HVIPRIO2, "hviprio2"),
+    (CSR_HTINST, "htinst"),
+    (CSR_HGATP, "hgatp"),
+    (CSR_HCONTEXT, "hcontext"),
+    (CSR_HGEIP, "hgeip"),
+    (CSR_VSTOPI, "vstopi"),
+    (CSR_SCOUNTOVF, "scountovf"),
+    (CSR_STOPI, "stopi"),
+    (CSR_UTVT, "utvt"),
+    (CSR_UNXTI, "unxti"),
+    (CSR_UINTSTATUS, "uintstatus"),
+    (CSR_USCRATCHCSW, "uscratchcsw"),
+    (CSR_USCRATCHCSWL, "uscratchcswl"),
+    (CSR_STVT, "stvt"),
+    (CSR_SNXTI, "snxti"),
+    (CSR_SINTSTATUS, "sintstatus"),
+    (CSR_SSCRATCHCSW, "sscratchcsw"),
+    (CSR_SSCRATCHCSWL, "sscratchcswl"),
+    (CSR_MTVT, "mtvt"),
+    (CSR_MNXTI, "mnxti"),
+    (CSR_MINTSTATUS, "mintstatus"),
+    (CSR_MSCRATCHCSW, "mscratchcsw"),
+    (CSR_MSCRATCHCSWL, "mscratchcswl"),
+    (CSR_MSTATUS, "mstatus"),
+    (CSR_MISA, "misa"),
+    (CSR_MEDELEG, "medeleg"),
+    (CSR_MIDELEG, "mideleg"),
+    (CSR_MIE, "mie"),
+    (CSR_MTVEC, "mtvec"),
+    (CSR_MCOUNTEREN, "mcounteren"),
+    (CSR_MVIEN, "mvien"),
+    (CSR_MVIP, "mvip"),
+    (CSR_MENVCFG, "menvcfg"),
+    (CSR_MSTATEEN0, "mstateen0"),
+    (CSR_MSTATEEN1, "mstateen1"),
+    (CSR_MSTATEEN2, "mstateen2"),
+    (CSR_MSTATEEN3, "mstateen3"),
+    (CSR_MCOUNTINHIBIT, "mcountinhibit"),
+    (CSR_MSCRATCH, "mscratch"),
+    (CSR_MEPC, "mepc"),
+    (CSR_MCAUSE, "mcause"),
+    (CSR_MTVAL, "mtval"),
+    (CSR_MIP, "mip"),
+    (CSR_MTINST, "mtinst"),
+    (CSR_MTVAL2, "mtval2"),
+    (CSR_MISELECT, "miselect"),
+    (CSR_MIREG, "mireg"),
+    (CSR_MTOPEI, "mtopei"),
+    (CSR_PMPCFG0, "pmpcfg0"),
+    (CSR_PMPCFG1, "pmpcfg1"),
+    (CSR_PMPCFG2, "pmpcfg2"),
+    (CSR_PMPCFG3, "pmpcfg3"),
+    (CSR_PMPCFG4, "pmpcfg4"),
+    (CSR_PMPCFG5, "pmpcfg5"),
+    (CSR_PMPCFG6, "pmpcfg6"),
+    (CSR_PMPCFG7, "pmpcfg7"),
+    (CSR_PMPCFG8, "pmpcfg8"),
+    (CSR_PMPCFG9, "pmpcfg9"),
+    (CSR_PMPCFG10, "pmpcfg10"),
+    (CSR_PMPCFG11, "pmpcfg11"),
+    (CSR_PMPCFG12, "pmpcfg12"),
+    (CSR_PMPCFG13, "pmpcfg13"),
+    (CSR_PMPCFG14, "pmpcfg14"),
+    (CSR_PMPCFG15, "pmpcfg15"),
+    (CSR_PMPADDR0, "pmpaddr0"),
+    (CSR_PMPADDR1, "pmpaddr1"),
+    (CSR_PMPADDR2, "pmpaddr2"),
+    (CSR_PMPADDR3, "pmpaddr3"),
+    (CSR_PMPADDR4, "pmpaddr4"),
+    (CSR_PMPADDR5, "pmpaddr5"),
+    (CSR_PMPADDR6, "pmpaddr6"),
+    (CSR_PMPADDR7, "pmpaddr7"),
+    (CSR_PMPADDR8, "pmpaddr8"),
+    (CSR_PMPADDR9, "pmpaddr9"),
+    (CSR_PMPADDR10, "pmpaddr10"),
+    (CSR_PMPADDR11, "pmpaddr11"),
+    (CSR_PMPADDR12, "pmpaddr12"),
+    (CSR_PMPADDR13, "pmpaddr13"),
+    (CSR_PMPADDR14, "pmpaddr14"),
+    (CSR_PMPADDR15, "pmpaddr15"),
+    (CSR_PMPADDR16, "pmpaddr16"),
+    (CSR_PMPADDR17, "pmpaddr17"),
+    (CSR_PMPADDR18, "pmpaddr18"),
+    (CSR_PMPADDR19, "pmpaddr19"),
+    (CSR_PMPADDR20, "pmpaddr20"),
+    (CSR_PMPADDR21, "pmpaddr21"),
+    (CSR_PMPADDR22, "pmpaddr22"),
+    (CSR_PMPADDR23, "pmpaddr23"),
+    (CSR_PMPADDR24, "pmpaddr24"),
+    (CSR_PMPADDR25, "pmpaddr25"),
+    (CSR_PMPADDR26, "pmpaddr26"),
+    (CSR_PMPADDR27, "pmpaddr27"),
+    (CSR_PMPADDR28, "pmpaddr28"),
+    (CSR_PMPADDR29, "pmpaddr29"),
+    (CSR_PMPADDR30, "pmpaddr30"),
+    (CSR_PMPADDR31, "pmpaddr31"),
+    (CSR_PMPADDR32, "pmpaddr32"),
+    (CSR_PMPADDR33, "pmpaddr33"),
+    (CSR_PMPADDR34, "pmpaddr34"),
+    (CSR_PMPADDR35, "pmpaddr35"),
+    (CSR_PMPADDR36, "pmpaddr36"),
+    (CSR_PMPADDR37, "pmpaddr37"),
+    (CSR_PMPADDR38, "pmpaddr38"),
+    (CSR_PMPADDR39, "pmpaddr39"),
+    (CSR_PMPADDR40, "pmpaddr40"),
+    (CSR_PMPADDR41, "pmpaddr41"),
+    (CSR_PMPADDR42, "pmpaddr42"),
+    (CSR_PMPADDR43, "pmpaddr43"),
+    (CSR_PMPADDR44, "pmpaddr44"),
+    (CSR_PMPADDR45, "pmpaddr45"),
+    (CSR_PMPADDR46, "pmpaddr46"),
+    (CSR_PMPADDR47, "pmpaddr47"),
+    (CSR_PMPADDR48, "pmpaddr48"),
+    (CSR_PMPADDR49, "pmpaddr49"),
+    (CSR_PMPADDR50, "pmpaddr50"),
+    (CSR_PMPADDR51, "pmpaddr51"),
+    (CSR_PMPADDR52, "pmpaddr52"),
+    (CSR_PMPADDR53, "pmpaddr53"),
+    (CSR_PMPADDR54, "pmpaddr54"),
+    (CSR_PMPADDR55, "pmpaddr55"),
+    (CSR_PMPADDR56, "pmpaddr56"),
+    (CSR_PMPADDR57, "pmpaddr57"),
+    (CSR_PMPADDR58, "pmpaddr58"),
+    (CSR_PMPADDR59, "pmpaddr59"),
+    (CSR_PMPADDR60, "pmpaddr60"),
+    (CSR_PMPADDR61, "pmpaddr61"),
+    (CSR_PMPADDR62, "pmpaddr62"),
+    (CSR_PMPADDR63, "pmpaddr63"),
+    (CSR_MSECCFG, "mseccfg"),
+    (CSR_TSELECT, "tselect"),
+    (CSR_TDATA1, "tdata1"),
+    (CSR_TDATA2, "tdata2"),
+    (CSR_TDATA3, "tdata3"),
+    (CSR_TINFO, "tinfo"),
+    (CSR_TCONTROL, "tcontrol"),
+    (CSR_MCONTEXT, "mcontext"),
+    (CSR_MSCONTEXT, "mscontext"),
+    (CSR_DCSR, "dcsr"),
+    (CSR_DPC, "dpc"),
+    (CSR_DSCRATCH0, "dscratch0"),
+    (CSR_DSCRATCH1, "dscratch1"),
+    (CSR_MCYCLE, "mcycle"),
+    (CSR_MINSTRET, "minstret"),
+    (CSR_MHPMCOUNTER3, "mhpmcounter3"),
+    (CSR_MHPMCOUNTER4, "mhpmcounter4"),
+    (CSR_MHPMCOUNTER5, "mhpmcounter5"),
+    (CSR_MHPMCOUNTER6, "mhpmcounter6"),
+    (CSR_MHPMCOUNTER7, "mhpmcounter7"),
+    (CSR_MHPMCOUNTER8, "mhpmcounter8"),
+    (CSR_MHPMCOUNTER9, "mhpmcounter9"),
+    (CSR_MHPMCOUNTER10, "mhpmcounter10"),
+    (CSR_MHPMCOUNTER11, "mhpmcounter11"),
+    (CSR_MHPMCOUNTER12, "mhpmcounter12"),
+    (CSR_MHPMCOUNTER13, "mhpmcounter13"),
+    (CSR_MHPMCOUNTER14, "mhpmcounter14"),
+    (CSR_MHPMCOUNTER15, "mhpmcounter15"),
+    (CSR_MHPMCOUNTER16, "mhpmcounter16"),
+    (CSR_MHPMCOUNTER17, "mhpmcounter17"),
+    (CSR_MHPMCOUNTER18, "mhpmcounter18"),
+    (CSR_MHPMCOUNTER19, "mhpmcounter19"),
+    (CSR_MHPMCOUNTER20, "mhpmcounter20"),
+    (CSR_MHPMCOUNTER21, "mhpmcounter21"),
+    (CSR_MHPMCOUNTER22, "mhpmcounter22"),
+    (CSR_MHPMCOUNTER23, "mhpmcounter23"),
+    (CSR_MHPMCOUNTER24, "mhpmcounter24"),
+    (CSR_MHPMCOUNTER25, "mhpmcounter25"),
+    (CSR_MHPMCOUNTER26, "mhpmcounter26"),
+    (CSR_MHPMCOUNTER27, "mhpmcounter27"),
+    (CSR_MHPMCOUNTER28, "mhpmcounter28"),
+    (CSR_MHPMCOUNTER29, "mhpmcounter29"),
+    (CSR_MHPMCOUNTER30, "mhpmcounter30"),
+    (CSR_MHPMCOUNTER31, "mhpmcounter31"),
+    (CSR_MHPMEVENT3, "mhpmevent3"),
+    (CSR_MHPMEVENT4, "mhpmevent4"),
+    (CSR_MHPMEVENT5, "mhpmevent5"),
+    (CSR_MHPMEVENT6, "mhpmevent6"),
+    (CSR_MHPMEVENT7, "mhpmevent7"),
+    (CSR_MHPMEVENT8, "mhpmevent8"),
+    (CSR_MHPMEVENT9, "mhpmevent9"),
+    (CSR_MHPMEVENT10, "mhpmevent10"),
+    (CSR_MHPMEVENT11, "mhpmevent11"),
+    (CSR_MHPMEVENT12, "mhpmevent12"),
+    (CSR_MHPMEVENT13, "mhpmevent13"),
+    (CSR_MHPMEVENT14, "mhpmevent14"),
+    (CSR_MHPMEVENT15, "mhpmevent15"),
+    (CSR_MHPMEVENT16, "mhpmevent16"),
+    (CSR_MHPMEVENT17, "mhpmevent17"),
+    (CSR_MHPMEVENT18, "mhpmevent18"),
+    (CSR_MHPMEVENT19, "mhpmevent19"),
+    (CSR_MHPMEVENT20, "mhpmevent20"),
+    (CSR_MHPMEVENT21, "mhpmevent21"),
+    (CSR_MHPMEVENT22, "mhpmevent22"),
+    (CSR_MHPMEVENT23, "mhpmevent23"),
+    (CSR_MHPMEVENT24, "mhpmevent24"),
+    (CSR_MHPMEVENT25, "mhpmevent25"),
+    (CSR_MHPMEVENT26, "mhpmevent26"),
+    (CSR_MHPMEVENT27, "mhpmevent27"),
+    (CSR_MHPMEVENT28, "mhpmevent28"),
+    (CSR_MHPMEVENT29, "mhpmevent29"),
+    (CSR_MHPMEVENT30, "mhpmevent30"),
+    (CSR_MHPMEVENT31, "mhpmevent31"),
+    (CSR_MVENDORID, "mvendorid"),
+    (CSR_MARCHID, "marchid"),
+    (CSR_MIMPID, "mimpid"),
+    (CSR_MHARTID, "mhartid"),
+    (CSR_MCONFIGPTR, "mconfigptr"),
+    (CSR_MTOPI, "mtopi"),
+    (CSR_SIEH, "sieh"),
+    (CSR_SIPH, "siph"),
+    (CSR_STIMECMPH, "stimecmph"),
+    (CSR_VSIEH, "vsieh"),
+    (CSR_VSIPH, "vsiph"),
+    (CSR_VSTIMECMPH, "vstimecmph"),
+    (CSR_HTIMEDELTAH, "htimedeltah"),
+    (CSR_HIDELEGH, "hidelegh"),
+    (CSR_HVIENH, "hvienh"),
+    (CSR_HENVCFGH, "henvcfgh"),
+    (CSR_HVIPH, "hviph"),
+    (CSR_HVIPRIO1H, "hviprio1h"),
+    (CSR_HVIPRIO2H, "hviprio2h"),
+    (CSR_HSTATEEN0H, "hstateen0h"),
+    (CSR_HSTATEEN1H, "hstateen1h"),
+    (CSR_HSTATEEN2H, "hstateen2h"),
+    (CSR_HSTATEEN3H, "hstateen3h"),
+    (CSR_CYCLEH, "cycleh"),
+    (CSR_TIMEH, "timeh"),
+    (CSR_INSTRETH, "instreth"),
+    (CSR_HPMCOUNTER3H, "hpmcounter3h"),
+    (CSR_HPMCOUNTER4H, "hpmcounter4h"),
+    (CSR_HPMCOUNTER5H, "hpmcounter5h"),
+    (CSR_HPMCOUNTER6H, "hpmcounter6h"),
+    (CSR_HPMCOUNTER7H, "hpmcounter7h"),
+    (CSR_HPMCOUNTER8H, "hpmcounter8h"),
+    (CSR_HPMCOUNTER9H, "hpmcounter9h"),
+    (CSR_HPMCOUNTER10H, "hpmcounter10h"),
+    (CSR_HPMCOUNTER11H, "hpmcounter11h"),
+    (CSR_HPMCOUNTER12H, "hpmcounter12h"),
+    (CSR_HPMCOUNTER13H, "hpmcounter13h"),
+    (CSR_HPMCOUNTER14H, "hpmcounter14h"),
+    (CSR_HPMCOUNTER15H, "hpmcounter15h"),
+    (CSR_HPMCOUNTER16H, "hpmcounter16h"),
+    (CSR_HPMCOUNTER17H, "hpmcounter17h"),
+    (CSR_HPMCOUNTER18H, "hpmcounter18h"),
+    (CSR_HPMCOUNTER19H, "hpmcounter19h"),
+    (CSR_HPMCOUNTER20H, "hpmcounter20h"),
+    (CSR_HPMCOUNTER21H, "hpmcounter21h"),
+    (CSR_HPMCOUNTER22H, "hpmcounter22h"),
+    (CSR_HPMCOUNTER23H, "hpmcounter23h"),
+    (CSR_HPMCOUNTER24H, "hpmcounter24h"),
+    (CSR_HPMCOUNTER25H, "hpmcounter25h"),
+    (CSR_HPMCOUNTER26H, "hpmcounter26h"),
+    (CSR_HPMCOUNTER27H, "hpmcounter27h"),
+    (CSR_HPMCOUNTER28H, "hpmcounter28h"),
+    (CSR_HPMCOUNTER29H, "hpmcounter29h"),
+    (CSR_HPMCOUNTER30H, "hpmcounter30h"),
+    (CSR_HPMCOUNTER31H, "hpmcounter31h"),
+    (CSR_MSTATUSH, "mstatush"),
+    (CSR_MIDELEGH, "midelegh"),
+    (CSR_MIEH, "mieh"),
+    (CSR_MVIENH, "mvienh"),
+    (CSR_MVIPH, "mviph"),
+    (CSR_MENVCFGH, "menvcfgh"),
+    (CSR_MSTATEEN0H, "mstateen0h"),
+    (CSR_MSTATEEN1H, "mstateen1h"),
+    (CSR_MSTATEEN2H, "mstateen2h"),
+    (CSR_MSTATEEN3H, "mstateen3h"),
+    (CSR_MIPH, "miph"),
+    (CSR_MHPMEVENT3H, "mhpmevent3h"),
+    (CSR_MHPMEVENT4H, "mhpmevent4h"),
+    (CSR_MHPMEVENT5H, "mhpmevent5h"),
+    (CSR_MHPMEVENT6H, "mhpmevent6h"),
+    (CSR_MHPMEVENT7H, "mhpmevent7h"),
+    (CSR_MHPMEVENT8H, "mhpmevent8h"),
+    (CSR_MHPMEVENT9H, "mhpmevent9h"),
+    (CSR_MHPMEVENT10H, "mhpmevent10h"),
+    (CSR_MHPMEVENT11H, "mhpmevent11h"),
+    (CSR_MHPMEVENT12H, "mhpmevent12h"),
+    (CSR_MHPMEVENT13H, "mhpmevent13h"),
+    (CSR_MHPMEVENT14H, "mhpmevent14h"),
+    (CSR_MHPMEVENT15H, "mhpmevent15h"),
+    (CSR_MHPMEVENT16H, "mhpmevent16h"),
+    (CSR_MHPMEVENT17H, "mhpmevent17h"),
+    (CSR_MHPMEVENT18H, "mhpmevent18h"),
+    (CSR_MHPMEVENT19H, "mhpmevent19h"),
+    (CSR_MHPMEVENT20H, "mhpmevent20h"),
+    (CSR_MHPMEVENT21H, "mhpmevent21h"),
+    (CSR_MHPMEVENT22H, "mhpmevent22h"),
+    (CSR_MHPMEVENT23H, "mhpmevent23h"),
+    (CSR_MHPMEVENT24H, "mhpmevent24h"),
+    (CSR_MHPMEVENT25H, "mhpmevent25h"),
+    (CSR_MHPMEVENT26H, "mhpmevent26h"),
+    (CSR_MHPMEVENT27H, "mhpmevent27h"),
+    (CSR_MHPMEVENT28H, "mhpmevent28h"),
+    (CSR_MHPMEVENT29H, "mhpmevent29h"),
+    (CSR_MHPMEVENT30H, "mhpmevent30h"),
+    (CSR_MHPMEVENT31H, "mhpmevent31h"),
+    (CSR_MSECCFGH, "mseccfgh"),
+    (CSR_MCYCLEH, "mcycleh"),
+    (CSR_MINSTRETH, "minstreth"),
+    (CSR_MHPMCOUNTER3H, "mhpmcounter3h"),
+    (CSR_MHPMCOUNTER4H, "mhpmcounter4h"),
+    (CSR_MHPMCOUNTER5H, "mhpmcounter5h"),
+    (CSR_MHPMCOUNTER6H, "mhpmcounter6h"),
+    (CSR_MHPMCOUNTER7H, "mhpmcounter7h"),
+    (CSR_MHPMCOUNTER8H, "mhpmcounter8h"),
+    (CSR_MHPMCOUNTER9H, "mhpmcounter9h"),
+    (CSR_MHPMCOUNTER10H, "mhpmcounter10h"),
+    (CSR_MHPMCOUNTER11H, "mhpmcounter11h"),
+    (CSR_MHPMCOUNTER12H, "mhpmcounter12h"),
+    (CSR_MHPMCOUNTER13H, "mhpmcounter13h"),
+    (CSR_MHPMCOUNTER14H, "mhpmcounter14h"),
+    (CSR_MHPMCOUNTER15H, "mhpmcounter15h"),
+    (CSR_MHPMCOUNTER16H, "mhpmcounter16h"),
+    (CSR_MHPMCOUNTER17H, "mhpmcounter17h"),
+    (CSR_MHPMCOUNTER18H, "mhpmcounter18h"),
+    (CSR_MHPMCOUNTER19H, "mhpmcounter19h"),
+    (CSR_MHPMCOUNTER20H, "mhpmcounter20h"),
+    (CSR_MHPMCOUNTER21H, "mhpmcounter21h"),
+    (CSR_MHPMCOUNTER22H, "mhpmcounter22h"),
+    (CSR_MHPMCOUNTER23H, "mhpmcounter23h"),
+    (CSR_MHPMCOUNTER24H, "mhpmcounter24h"),
+    (CSR_MHPMCOUNTER25H, "mhpmcounter25h"),
+    (CSR_MHPMCOUNTER26H, "mhpmcounter26h"),
+    (CSR_MHPMCOUNTER27H, "mhpmcounter27h"),
+    (CSR_MHPMCOUNTER28H, "mhpmcounter28h"),
+    (CSR_MHPMCOUNTER29H, "mhpmcounter29h"),
+    (CSR_MHPMCOUNTER30H, "mhpmcounter30h"),
+    (CSR_MHPMCOUNTER31H, "mhpmcounter31h"),
+];
+
+pub fn csr_name(addr: u16) -> Option<&'static str> {
+    CSR_NAMES
+        .iter()
+        .find(|(x, _)| *x == addr)
+        .map(|(_, name)| *name)
+}
+
 pub struct Instruction {
     pub mask: u32,
     pub match_data: u32,
@@ -896,6 +2122,52 @@ pub fn parse_format_r(word: u32) -> FormatR {
     }
 }
 
+pub struct FormatR4 {
+    pub rd: u64,
+    pub rs1: u64,
+    pub rs2: u64,
+    pub rs3: u64,
+    pub rm: u8,
+}
+
+pub fn parse_format_r4(word: u32) -> FormatR4 {
+    FormatR4 {
+        rd: ((word >> 7) & 0x1f) as u64,   // [11:7]
+        rs1: ((word >> 15) & 0x1f) as u64, // [19:15]
+        rs2: ((word >> 20) & 0x1f) as u64, // [24:20]
+        rs3: ((word >> 27) & 0x1f) as u64, // [31:27]
+        rm: ((word >> 12) & 0x7) as u8,    // [14:12]
+    }
+}
+
+pub struct FormatV {
+    pub vd: u64,  // also rd, vs3
+    pub vs1: u64, // also rs1, simm5
+    pub vs2: u64, // also rs2, lumop, sumop
+    pub vm: bool,
+    pub funct3: u8, // also width of loads and stores
+    pub mop: u8,
+    pub nf: u8,
+}
+
+impl FormatV {
+    pub fn simm5(&self) -> i64 {
+        ((self.vs1 as i64) << 59) >> 59
+    }
+}
+
+pub fn parse_format_v(word: u32) -> FormatV {
+    FormatV {
+        vd: ((word >> 7) & 0x1f) as u64,    // [11:7]
+        vs1: ((word >> 15) & 0x1f) as u64,  // [19:15]
+        vs2: ((word >> 20) & 0x1f) as u64,  // [24:20]
+        vm: (word >> 25) & 1 != 0,          // [25]
+        funct3: ((word >> 12) & 0x7) as u8, // [14:12]
+        mop: ((word >> 26) & 0x3) as u8,    // [27:26]
+        nf: ((word >> 29) & 0x7) as u8,     // [31:29]
+    }
+}
+
 pub fn parse_format_s(word: u32) -> FormatS {
     FormatS {
         rs1: ((word >> 15) & 0x1f) as u64, // [19:15]
@@ -960,6 +2232,10 @@ impl FormatCR {
     pub fn rs2(&self) -> u64 {
         self.rs2
     }
+    // c.jr through ra or t0, like jalr x0 (see `FormatI::get_jalr_type`)
+    pub fn is_ret(&self) -> bool {
+        self.rd_rs1 == 1 || self.rd_rs1 == 5
+    }
 }
 
 pub struct FormatCI {
@@ -1420,6 +2696,17 @@ impl AccessType {
         }
     }
 
+    // a G-stage fault, tval is the guest virtual address
+    pub fn throw_guest_page_exception(&self, gpa: u64) -> TrapType {
+        match self {
+            AccessType::Fetch(tval) => TrapType::InstructionGuestPageFault(*tval, gpa),
+            AccessType::Load(tval) => TrapType::LoadGuestPageFault(*tval, gpa),
+            AccessType::Store(tval) | AccessType::Amo(tval) => {
+                TrapType::StoreGuestPageFault(*tval, gpa)
+            }
+        }
+    }
+
     pub fn throw_access_exception(&self) -> TrapType {
         match self {
             AccessType::Fetch(tval) => TrapType::InstructionAccessFault(*tval),
@@ -1436,6 +2723,16 @@ impl AccessType {
             AccessType::Amo(tval) => TrapType::StoreAddressMisaligned(*tval), // todo! ???
         }
     }
+
+    // the same access at another address, for the parts of a split access
+    pub fn with_addr(&self, addr: u64) -> Self {
+        match self {
+            AccessType::Fetch(_) => AccessType::Fetch(addr),
+            AccessType::Load(_) => AccessType::Load(addr),
+            AccessType::Store(_) => AccessType::Store(addr),
+            AccessType::Amo(_) => AccessType::Amo(addr),
+        }
+    }
 }
 
 impl PrivilegeLevels {
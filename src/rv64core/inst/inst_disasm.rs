@@ -0,0 +1,553 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+
+use crate::rv64core::{fpr::Fpr, gpr::Gpr, vector::vpr::Vpr, vector::vtype::VtypeIn};
+
+use super::inst_base::*;
+
+// The output follows `objdump -d -M no-aliases`, so that the result can be
+// diffed against the toolchain directly. CSRs are printed by number.
+
+fn reg<T: Into<u64>>(idx: T) -> &'static str {
+    Gpr::get_register_name(idx.into())
+}
+
+fn reg_c(idx: usize) -> &'static str {
+    Gpr::get_register_name(idx as u64)
+}
+
+fn freg<T: Into<u64>>(idx: T) -> &'static str {
+    Fpr::get_register_name(idx.into())
+}
+
+fn vreg<T: Into<u64>>(idx: T) -> &'static str {
+    Vpr::get_register_name(idx.into())
+}
+
+// "AMOADD_W" -> "amoadd.w", "c.addi" -> "c.addi"
+pub fn inst_name(inst: &Instruction) -> String {
+    inst.name.to_ascii_lowercase().replace('_', ".")
+}
+
+fn fence_set(bits: u32) -> String {
+    let mut s = String::new();
+    for (bit, c) in [(8, 'i'), (4, 'o'), (2, 'r'), (1, 'w')] {
+        if bits & bit != 0 {
+            s.push(c);
+        }
+    }
+    if s.is_empty() {
+        s.push('0');
+    }
+    s
+}
+
+fn amo_suffix(word: u32) -> &'static str {
+    match (word >> 25) & 0b11 {
+        0b10 => ".aq",
+        0b01 => ".rl",
+        0b11 => ".aqrl",
+        _ => "",
+    }
+}
+
+pub const ROUND_MODE_NAMES: [&str; 8] = ["rne", "rtz", "rdn", "rup", "rmm", "5", "6", "dyn"];
+
+// operand kinds of the floating point instructions, shared with the assembler
+pub struct FpOperands {
+    // rd is an integer register
+    pub rd_x: bool,
+    // rs1 is an integer register
+    pub rs1_x: bool,
+    // number of source registers
+    pub srcs: usize,
+    // has a rounding mode field
+    pub rm: bool,
+    // rounding mode that is omitted, exact conversions use rne instead of dyn
+    pub default_rm: u8,
+}
+
+pub fn fp_operands(name: &str) -> FpOperands {
+    let mut parts = name.split('.');
+    let op = parts.next().unwrap_or("");
+    let dst = parts.next().unwrap_or("");
+    let src = parts.next().unwrap_or("");
+    // fmv.x.w, fcvt.w.s
+    let is_int = |x: &str| match op {
+        "fmv" => x == "x",
+        "fcvt" => matches!(x, "w" | "wu" | "l" | "lu"),
+        _ => false,
+    };
+
+    FpOperands {
+        rd_x: matches!(op, "feq" | "flt" | "fle" | "fclass") || is_int(dst),
+        rs1_x: is_int(src),
+        srcs: match op {
+            "fmadd" | "fmsub" | "fnmsub" | "fnmadd" => 3,
+            "fsqrt" | "fcvt" | "fmv" | "fclass" => 1,
+            _ => 2,
+        },
+        rm: matches!(
+            op,
+            "fadd"
+                | "fsub"
+                | "fmul"
+                | "fdiv"
+                | "fsqrt"
+                | "fcvt"
+                | "fmadd"
+                | "fmsub"
+                | "fnmsub"
+                | "fnmadd"
+        ),
+        default_rm: match name {
+            "fcvt.d.s" | "fcvt.d.w" | "fcvt.d.wu" | "fcvt.s.h" | "fcvt.d.h" => 0b000,
+            _ => 0b111,
+        },
+    }
+}
+
+fn disasm_fp(name: &str, word: u32) -> String {
+    let opcode = word & 0x7f;
+    match opcode {
+        // LOAD-FP
+        0b0000111 => {
+            let f = parse_format_i(word);
+            format!("{},{}({})", freg(f.rd), f.imm, reg(f.rs1))
+        }
+        // STORE-FP
+        0b0100111 => {
+            let f = parse_format_s(word);
+            format!("{},{}({})", freg(f.rs2), f.imm, reg(f.rs1))
+        }
+        // MADD,MSUB,NMSUB,NMADD,OP-FP
+        _ => {
+            let f = parse_format_r4(word);
+            let kinds = fp_operands(name);
+            let rd = if kinds.rd_x { reg(f.rd) } else { freg(f.rd) };
+            let rs1 = if kinds.rs1_x { reg(f.rs1) } else { freg(f.rs1) };
+            let mut s = match kinds.srcs {
+                1 => format!("{},{}", rd, rs1),
+                2 => format!("{},{},{}", rd, rs1, freg(f.rs2)),
+                _ => format!("{},{},{},{}", rd, rs1, freg(f.rs2), freg(f.rs3)),
+            };
+            // default rounding mode is omitted
+            if kinds.rm && f.rm != kinds.default_rm {
+                s.push(',');
+                s.push_str(ROUND_MODE_NAMES[f.rm as usize]);
+            }
+            s
+        }
+    }
+}
+
+// operand layout of the vector arithmetic instructions, shared with the assembler
+#[derive(Clone, Copy, PartialEq)]
+pub enum VLayout {
+    // vsetvli rd,rs1,vtypei; vsetivli rd,uimm,vtypei; vsetvl rd,rs1,rs2
+    Vset,
+    // rd,vs2
+    XV,
+    // vd,rs1
+    VX,
+    // vd,src1
+    VS1,
+    // vd
+    V,
+    // vd,vs2
+    V2,
+    // vd,src1,vs2
+    Mac,
+    // vd,vs2,src1,v0
+    Merge,
+    // vd,vs2,src1
+    Arith,
+}
+
+pub fn v_layout(name: &str) -> VLayout {
+    let op = name.split('.').next().unwrap_or("");
+    match name {
+        "vsetvli" | "vsetivli" | "vsetvl" => VLayout::Vset,
+        "vmv.x.s" | "vcpop.m" | "vfirst.m" => VLayout::XV,
+        "vmv.s.x" => VLayout::VX,
+        "vmv.v.v" | "vmv.v.x" | "vmv.v.i" => VLayout::VS1,
+        "vid.v" => VLayout::V,
+        _ if name.ends_with(".vvm") || name.ends_with(".vxm") || name.ends_with(".vim") => {
+            VLayout::Merge
+        }
+        _ if matches!(op, "vmacc" | "vnmsac" | "vmadd" | "vnmsub") => VLayout::Mac,
+        _ if matches!(
+            op,
+            "vmsbf"
+                | "vmsof"
+                | "vmsif"
+                | "viota"
+                | "vzext"
+                | "vsext"
+                | "vmv1r"
+                | "vmv2r"
+                | "vmv4r"
+                | "vmv8r"
+        ) =>
+        {
+            VLayout::V2
+        }
+        _ => VLayout::Arith,
+    }
+}
+
+// the immediate of these instructions is unsigned
+pub fn v_uimm(name: &str) -> bool {
+    let op = name.split('.').next().unwrap_or("");
+    matches!(
+        op,
+        "vsll" | "vsrl" | "vsra" | "vrgather" | "vslideup" | "vslidedown"
+    )
+}
+
+pub const LMUL_NAMES: [&str; 8] = ["m1", "m2", "m4", "m8", "", "mf8", "mf4", "mf2"];
+
+// "e32,m2,ta,ma", reserved settings are printed as a number
+pub fn vtype_name(vtypei: u32) -> String {
+    let vtype = VtypeIn::from(vtypei as u64);
+    if vtype.reserved() != 0 || vtype.vlmul() == 0b100 || vtype.vsew() > 0b011 {
+        return format!("0x{:x}", vtypei);
+    }
+    format!(
+        "e{},{},{},{}",
+        vtype.sew(),
+        LMUL_NAMES[vtype.vlmul() as usize],
+        if vtype.vta() { "ta" } else { "tu" },
+        if vtype.vma() { "ma" } else { "mu" }
+    )
+}
+
+fn disasm_vector(name: &str, word: u32) -> String {
+    let f = parse_format_v(word);
+    let mask = if f.vm { "" } else { ",v0.t" };
+    // LOAD-FP,STORE-FP
+    if word & 0x7f != 0b1010111 {
+        let offset = match f.mop {
+            0b10 => format!(",{}", reg(f.vs2)),
+            0b01 | 0b11 => format!(",{}", vreg(f.vs2)),
+            _ => String::new(),
+        };
+        return format!("{},({}){}{}", vreg(f.vd), reg(f.vs1), offset, mask);
+    }
+    let src1 = match f.funct3 {
+        0b011 if v_uimm(name) => format!("{}", f.vs1),
+        0b011 => format!("{}", f.simm5()),
+        0b100 | 0b110 => reg(f.vs1).to_string(),
+        _ => vreg(f.vs1).to_string(),
+    };
+    let (vd, vs2) = (vreg(f.vd), vreg(f.vs2));
+    match v_layout(name) {
+        VLayout::Vset => match name {
+            "vsetvl" => format!("{},{},{}", reg(f.vd), reg(f.vs1), reg(f.vs2)),
+            "vsetivli" => format!(
+                "{},{},{}",
+                reg(f.vd),
+                f.vs1,
+                vtype_name((word >> 20) & 0x3ff)
+            ),
+            _ => format!(
+                "{},{},{}",
+                reg(f.vd),
+                reg(f.vs1),
+                vtype_name((word >> 20) & 0x7ff)
+            ),
+        },
+        VLayout::XV => format!("{},{}{}", reg(f.vd), vs2, mask),
+        VLayout::VX => format!("{},{}", vd, reg(f.vs1)),
+        VLayout::VS1 => format!("{},{}", vd, src1),
+        VLayout::V => format!("{}{}", vd, mask),
+        VLayout::V2 => format!("{},{}{}", vd, vs2, mask),
+        VLayout::Mac => format!("{},{},{}{}", vd, src1, vs2, mask),
+        VLayout::Merge => format!("{},{},{},v0", vd, vs2, src1),
+        VLayout::Arith => format!("{},{},{}{}", vd, vs2, src1, mask),
+    }
+}
+
+fn disasm_compressed(name: &str, word: u32, pc: u64) -> String {
+    let ci = FormatCI::new(word);
+    let operands = match name {
+        "c.addi4spn" => {
+            let f = FormatCIW::new(word);
+            format!("{},sp,{}", reg_c(f.rd()), f.imm_c_addi4spn())
+        }
+        "c.lw" => {
+            let f = FormatCL::new(word);
+            format!("{},{}({})", reg_c(f.rd()), f.imm_c_lw(), reg_c(f.rs1()))
+        }
+        "c.ld" => {
+            let f = FormatCL::new(word);
+            format!("{},{}({})", reg_c(f.rd()), f.imm_c_ld(), reg_c(f.rs1()))
+        }
+        "c.sw" => {
+            let f = FormatCS::new(word);
+            format!("{},{}({})", reg_c(f.rs2()), f.imm_c_sw(), reg_c(f.rs1()))
+        }
+        "c.sd" => {
+            let f = FormatCS::new(word);
+            format!("{},{}({})", reg_c(f.rs2()), f.imm_c_sd(), reg_c(f.rs1()))
+        }
+        "c.lwsp" => format!("{},{}(sp)", reg_c(ci.rd()), ci.imm_c_lwsp()),
+        "c.ldsp" => format!("{},{}(sp)", reg_c(ci.rd()), ci.imm_c_ldsp()),
+        "c.swsp" => {
+            let f = FormatCSS::new(word);
+            format!("{},{}(sp)", reg_c(f.rs2()), f.imm_c_swsp())
+        }
+        "c.sdsp" => {
+            let f = FormatCSS::new(word);
+            format!("{},{}(sp)", reg_c(f.rs2()), f.imm_c_sdsp())
+        }
+        "c.j" | "c.jal" => {
+            let f = FormatCJ::new(word);
+            format!("{:x}", pc.wrapping_add(f.imm_c_j() as u64))
+        }
+        "c.jr" | "c.jalr" => reg(FormatCR::new(word).rs1()).to_string(),
+        "c.beqz" | "c.bnez" => {
+            let f = FormatCB::new(word);
+            format!(
+                "{},{:x}",
+                reg_c(f.rs1()),
+                pc.wrapping_add(f.imm_c_beqz() as u64)
+            )
+        }
+        "c.li" | "c.addi" | "c.addiw" => format!("{},{}", reg_c(ci.rd()), ci.imm_c_li()),
+        "c.lui" => format!(
+            "{},0x{:x}",
+            reg_c(ci.rd()),
+            (ci.imm_c_lui() >> 12) as u64 & 0xfffff
+        ),
+        // c.nop with non-zero imm is a HINT
+        "c.nop" if ci.imm_c_li() != 0 => format!("{}", ci.imm_c_li()),
+        "c.addi16sp" => format!("sp,{}", ci.imm_c_addi16sp()),
+        "c.slli" => format!("{},0x{:x}", reg_c(ci.rd()), ci.imm_c_slli()),
+        "c.srli" | "c.srai" => {
+            let f = FormatCB::new(word);
+            format!("{},0x{:x}", reg_c(f.rd()), f.imm_c_srli())
+        }
+        "c.andi" => {
+            let f = FormatCB::new(word);
+            format!("{},{}", reg_c(f.rd()), f.imm_c_andi())
+        }
+        "c.mv" | "c.add" => {
+            let f = FormatCR::new(word);
+            format!("{},{}", reg(f.rd()), reg(f.rs2()))
+        }
+        "c.and" | "c.or" | "c.xor" | "c.sub" | "c.addw" | "c.subw" => {
+            let f = FormatCA::new(word);
+            format!("{},{}", reg_c(f.rd()), reg_c(f.rs2()))
+        }
+        _ => String::new(),
+    };
+    operands
+}
+
+fn disasm_normal(name: &str, word: u32, pc: u64) -> String {
+    let opcode = word & 0x7f;
+    match opcode {
+        // LUI,AUIPC
+        0b0110111 | 0b0010111 => {
+            let f = parse_format_u(word);
+            format!("{},0x{:x}", reg(f.rd), (f.imm >> 12) & 0xfffff)
+        }
+        // JAL
+        0b1101111 => {
+            let f = parse_format_j(word);
+            format!("{},{:x}", reg(f.rd), pc.wrapping_add(f.imm))
+        }
+        // BRANCH
+        0b1100011 => {
+            let f = parse_format_b(word);
+            format!("{},{},{:x}", reg(f.rs1), reg(f.rs2), pc.wrapping_add(f.imm))
+        }
+        // JALR,LOAD
+        0b1100111 | 0b0000011 => {
+            let f = parse_format_i(word);
+            format!("{},{}({})", reg(f.rd), f.imm, reg(f.rs1))
+        }
+        // STORE
+        0b0100011 => {
+            let f = parse_format_s(word);
+            format!("{},{}({})", reg(f.rs2), f.imm, reg(f.rs1))
+        }
+        // the unary crypto instructions in OP-IMM
+        0b0010011 if name.starts_with("sha") || name.starts_with("sm3") || name == "aes64im" => {
+            let f = parse_format_r(word);
+            format!("{},{}", reg(f.rd), reg(f.rs1))
+        }
+        0b0010011 if name == "aes64ks1i" => {
+            let f = parse_format_r(word);
+            format!("{},{},0x{:x}", reg(f.rd), reg(f.rs1), (word >> 20) & 0xf)
+        }
+        // OP-IMM,OP-IMM-32
+        0b0010011 | 0b0011011 => {
+            let f = parse_format_i(word);
+            let funct3 = (word >> 12) & 0b111;
+            if funct3 == 0b001 || funct3 == 0b101 {
+                let shamt_mask = if opcode == 0b0010011 || name == "slli.uw" {
+                    0x3f
+                } else {
+                    0x1f
+                };
+                format!(
+                    "{},{},0x{:x}",
+                    reg(f.rd),
+                    reg(f.rs1),
+                    (word >> 20) & shamt_mask
+                )
+            } else {
+                format!("{},{},{}", reg(f.rd), reg(f.rs1), f.imm)
+            }
+        }
+        // AMO
+        0b0101111 => {
+            let f = parse_format_r(word);
+            if name.starts_with("lr") {
+                format!("{},({})", reg(f.rd), reg(f.rs1))
+            } else {
+                format!("{},{},({})", reg(f.rd), reg(f.rs2), reg(f.rs1))
+            }
+        }
+        // MISC-MEM
+        0b0001111 => {
+            if name == "fence" {
+                format!("{},{}", fence_set(word >> 24), fence_set(word >> 20))
+            } else if name.starts_with("cbo") {
+                format!("({})", reg(parse_format_i(word).rs1))
+            } else {
+                String::new()
+            }
+        }
+        // OP-V, vector loads and stores
+        0b1010111 => disasm_vector(name, word),
+        0b0000111 | 0b0100111 if name.starts_with('v') => disasm_vector(name, word),
+        // LOAD-FP,STORE-FP,MADD,MSUB,NMSUB,NMADD,OP-FP
+        0b0000111 | 0b0100111 | 0b1000011 | 0b1000111 | 0b1001011 | 0b1001111 | 0b1010011 => {
+            disasm_fp(name, word)
+        }
+        // SYSTEM
+        0b1110011 => {
+            let f = parse_format_csr(word);
+            match name {
+                "csrrw" | "csrrs" | "csrrc" => {
+                    format!("{},0x{:x},{}", reg(f.rd), f.csr, reg(f.rs1))
+                }
+                "csrrwi" | "csrrsi" | "csrrci" => {
+                    format!("{},0x{:x},{}", reg(f.rd), f.csr, f.rs1)
+                }
+                "sfence.vma" | "hfence.vvma" | "hfence.gvma" => {
+                    let f = parse_format_r(word);
+                    format!("{},{}", reg(f.rs1), reg(f.rs2))
+                }
+                _ if name.starts_with("hlv") => {
+                    let f = parse_format_r(word);
+                    format!("{},({})", reg(f.rd), reg(f.rs1))
+                }
+                _ if name.starts_with("hsv") => {
+                    let f = parse_format_r(word);
+                    format!("{},({})", reg(f.rs2), reg(f.rs1))
+                }
+                _ => String::new(),
+            }
+        }
+        0b0110011 if name.starts_with("sm4") => {
+            let f = parse_format_r(word);
+            let bs = word >> 30;
+            format!("{},{},{},0x{bs:x}", reg(f.rd), reg(f.rs1), reg(f.rs2))
+        }
+        0b0110011 if name.starts_with("ntl") => String::new(),
+        // OP,OP-32 and others with rd,rs1,rs2
+        _ => {
+            let f = parse_format_r(word);
+            format!("{},{},{}", reg(f.rd), reg(f.rs1), reg(f.rs2))
+        }
+    }
+}
+
+/// Disassemble one decoded instruction at `pc`.
+pub fn disassemble(inst: &Instruction, word: u32, pc: u64) -> String {
+    // all zero is defined as illegal instruction, not c.addi4spn
+    if word == 0 {
+        return "c.unimp".to_string();
+    }
+    let mut name = inst_name(inst);
+    let operands = if is_compressed_instruction(word) {
+        disasm_compressed(&name, word, pc)
+    } else {
+        if word & 0x7f == 0b0101111 {
+            name.push_str(amo_suffix(word));
+        }
+        disasm_normal(&name, word, pc)
+    };
+
+    if operands.is_empty() {
+        name
+    } else {
+        format!("{name}\t{operands}")
+    }
+}
+
+#[cfg(test)]
+mod test_disasm {
+    use alloc::rc::Rc;
+
+    use crate::{config::Config, rv64core::inst_decode::InstDecode};
+
+    use super::disassemble;
+
+    fn disasm(word: u32, pc: u64) -> alloc::string::String {
+        let mut config = Config::new();
+        config.set_isa("rv64imafdc_zfh_zve64x");
+        let mut decoder = InstDecode::new(Rc::new(config));
+        let inst = decoder.fast_path(word).unwrap();
+        disassemble(inst, word, pc)
+    }
+
+    #[test]
+    fn disasm_test() {
+        // reference output from riscv64-unknown-elf-objdump -d -M no-aliases
+        assert_eq!(disasm(0x00000297, 0x8000_0000), "auipc\tt0,0x0");
+        assert_eq!(disasm(0x02028593, 0), "addi\ta1,t0,32");
+        assert_eq!(disasm(0xf1402573, 0), "csrrs\ta0,0xf14,zero");
+        assert_eq!(disasm(0x0182b283, 0), "ld\tt0,24(t0)");
+        assert_eq!(disasm(0x00b53023, 0), "sd\ta1,0(a0)");
+        assert_eq!(disasm(0xfe0718e3, 0x8000_0010), "bne\ta4,zero,80000000");
+        assert_eq!(disasm(0x0ff0000f, 0), "fence\tiorw,iorw");
+        assert_eq!(disasm(0x30200073, 0), "mret");
+        assert_eq!(disasm(0x02b50533, 0), "mul\ta0,a0,a1");
+        assert_eq!(disasm(0x0605252f, 0), "amoadd.w.aqrl\ta0,zero,(a0)");
+        assert_eq!(disasm(0x03f55513, 0), "srli\ta0,a0,0x3f");
+        assert_eq!(disasm(0x1141, 0), "c.addi\tsp,-16");
+        assert_eq!(disasm(0xe406, 0), "c.sdsp\tra,8(sp)");
+        assert_eq!(disasm(0x8082, 0), "c.jr\tra");
+        assert_eq!(disasm(0x4505, 0), "c.li\ta0,1");
+        assert_eq!(disasm(0x0085a507, 0), "flw\tfa0,8(a1)");
+        assert_eq!(disasm(0xfea12c27, 0), "fsw\tfa0,-8(sp)");
+        assert_eq!(disasm(0x68c5f543, 0), "fmadd.s\tfa0,fa1,fa2,fa3");
+        assert_eq!(disasm(0x68c59543, 0), "fmadd.s\tfa0,fa1,fa2,fa3,rtz");
+        assert_eq!(disasm(0xc0059553, 0), "fcvt.w.s\ta0,fa1,rtz");
+        assert_eq!(disasm(0xa0c5a553, 0), "feq.s\ta0,fa1,fa2");
+        assert_eq!(disasm(0xf0058553, 0), "fmv.w.x\tfa0,a1");
+        assert_eq!(disasm(0x01013507, 0), "fld\tfa0,16(sp)");
+        assert_eq!(disasm(0x02c5f553, 0), "fadd.d\tfa0,fa1,fa2");
+        assert_eq!(disasm(0x42058553, 0), "fcvt.d.s\tfa0,fa1");
+        assert_eq!(disasm(0xc2259553, 0), "fcvt.l.d\ta0,fa1,rtz");
+        assert_eq!(disasm(0xe2058553, 0), "fmv.x.d\ta0,fa1");
+        assert_eq!(disasm(0x00811507, 0), "flh\tfa0,8(sp)");
+        assert_eq!(disasm(0x04c5f553, 0), "fadd.h\tfa0,fa1,fa2");
+        assert_eq!(disasm(0x40258553, 0), "fcvt.s.h\tfa0,fa1");
+        assert_eq!(disasm(0x4405f553, 0), "fcvt.h.s\tfa0,fa1");
+        assert_eq!(disasm(0xe4058553, 0), "fmv.x.h\ta0,fa1");
+        assert_eq!(disasm(0x0c067657, 0), "vsetvli\ta2,a2,e8,m1,ta,ma");
+        assert_eq!(disasm(0x02050087, 0), "vle8.v\tv1,(a0)");
+        assert_eq!(disasm(0x08a5f407, 0), "vlse64.v\tv8,(a1),a0,v0.t");
+        assert_eq!(disasm(0x02218257, 0), "vadd.vv\tv4,v2,v3");
+        assert_eq!(disasm(0x5e01b157, 0), "vmv.v.i\tv2,3");
+        assert_eq!(disasm(0x42082557, 0), "vcpop.m\ta0,v0");
+    }
+}
@@ -0,0 +1,206 @@
+use crate::rv64core::inst::inst_base::*;
+
+#[cfg(feature = "rv_debug_trace")]
+use crate::trace::traces::TraceType;
+
+// the gprs of a rv32 hart hold sign extended 32-bit values, so most of the
+// rv64 instructions give the right result once the write is truncated.
+// these are the ones that see the upper bits: the shift amounts are 5 bits,
+// the logical right shifts and the unsigned M instructions zero extend rs1
+// and rs2 first, and MULH* take bits 63:32 of the product
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_RV32I: &[Instruction] = &[
+    Instruction {
+        mask: MASK_SRLI,
+        match_data: MATCH_SRLI,
+        name: "SRLI",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as u32;
+            let shamt = f.imm & 0x1f;
+
+            cpu.gpr.write(f.rd, (rs1 >> shamt) as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SLL,
+        match_data: MATCH_SLL,
+        name: "SLL",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as u32;
+            let rs2 = cpu.gpr.read(f.rs2) as u32;
+
+            cpu.gpr.write(f.rd, rs1.wrapping_shl(rs2) as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SRL,
+        match_data: MATCH_SRL,
+        name: "SRL",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as u32;
+            let rs2 = cpu.gpr.read(f.rs2) as u32;
+
+            cpu.gpr.write(f.rd, rs1.wrapping_shr(rs2) as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SRA,
+        match_data: MATCH_SRA,
+        name: "SRA",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i32;
+            let rs2 = cpu.gpr.read(f.rs2) as u32;
+
+            cpu.gpr.write(f.rd, rs1.wrapping_shr(rs2) as u64);
+            Ok(())
+        },
+    },
+];
+
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_RV32M: &[Instruction] = &[
+    Instruction {
+        mask: MASK_MULH,
+        match_data: MATCH_MULH,
+        name: "MULH",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i32 as i64;
+            let rs2 = cpu.gpr.read(f.rs2) as i32 as i64;
+
+            cpu.gpr.write(f.rd, ((rs1 * rs2) >> 32) as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_MULHSU,
+        match_data: MATCH_MULHSU,
+        name: "MULHSU",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i32 as i64;
+            let rs2 = cpu.gpr.read(f.rs2) as u32 as i64;
+
+            cpu.gpr.write(f.rd, ((rs1 * rs2) >> 32) as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_MULHU,
+        match_data: MATCH_MULHU,
+        name: "MULHU",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as u32 as u64;
+            let rs2 = cpu.gpr.read(f.rs2) as u32 as u64;
+
+            cpu.gpr.write(f.rd, (rs1 * rs2) >> 32);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_DIVU,
+        match_data: MATCH_DIVU,
+        name: "DIVU",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as u32;
+            let rs2 = cpu.gpr.read(f.rs2) as u32;
+
+            let wb_data = match rs2 {
+                0 => u32::MAX,
+                _ => rs1 / rs2,
+            };
+            cpu.gpr.write(f.rd, wb_data as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_REMU,
+        match_data: MATCH_REMU,
+        name: "REMU",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as u32;
+            let rs2 = cpu.gpr.read(f.rs2) as u32;
+
+            let wb_data = match rs2 {
+                0 => rs1,
+                _ => rs1 % rs2,
+            };
+            cpu.gpr.write(f.rd, wb_data as u64);
+            Ok(())
+        },
+    },
+];
+
+// c.jal takes the encoding of c.addiw
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_RV32C: &[Instruction] = &[
+    Instruction {
+        mask: MASK_C_JAL,
+        match_data: MATCH_C_JAL,
+        name: "c.jal",
+        operation: |cpu, inst, pc| {
+            let f = FormatCJ::new(inst);
+            let next_pc = pc.wrapping_add(f.imm_c_jal() as u64);
+            #[cfg(feature = "rv_debug_trace")]
+            if let Some(sender) = &cpu.trace_sender {
+                sender.send(TraceType::Call(pc, next_pc)).unwrap();
+            };
+            cpu.npc = next_pc;
+            cpu.gpr.write(1, pc.wrapping_add(2));
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_C_SRLI,
+        match_data: MATCH_C_SRLI,
+        name: "c.srli",
+        operation: |cpu, inst, pc| {
+            let f = FormatCB::new(inst);
+            let shamt = f.imm_c_srli() & 0x1f;
+            let rd = f.rd() as u64;
+            let rd_data = cpu.gpr.read(rd) as u32;
+
+            cpu.gpr.write(rd, (rd_data >> shamt) as u64);
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv32 {
+    use alloc::rc::Rc;
+
+    use crate::{config::Config, rv64core::inst_decode::InstDecode};
+
+    #[test]
+    fn rv32_decode_test() {
+        let mut config = Config::new();
+        config.set_isa("rv32imac");
+        assert!(config.validate().is_ok());
+        assert_eq!(config.xlen(), 32);
+        let mut decoder = InstDecode::new(Rc::new(config));
+        let name = |decoder: &mut InstDecode, word| decoder.fast_path(word).map(|x| x.name);
+        // c.jal 16 is c.addiw on rv64, srli a0,a0,31, c.srli a0,31
+        assert_eq!(name(&mut decoder, 0x2801), Some("c.jal"));
+        assert_eq!(name(&mut decoder, 0x01f55513), Some("SRLI"));
+        assert_eq!(name(&mut decoder, 0x817d), Some("c.srli"));
+        // addiw, ld, slli a0,a0,32, c.srli a0,32, c.ld
+        for word in [0x0015051b, 0x00053503, 0x02051513, 0x9101, 0x6108] {
+            assert_eq!(name(&mut decoder, word), None);
+        }
+
+        let mut config = Config::new();
+        config.set_isa("rv32imafc_zba");
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,4 +1,7 @@
-use crate::rv64core::inst::inst_base::*;
+use crate::{
+    rv64core::{cpu_core::CpuCore, inst::inst_base::*, traptype::TrapType},
+    tools::check_aligned,
+};
 
 pub struct LrScReservation {
     pub val: u64,
@@ -20,6 +23,9 @@ impl LrScReservation {
     pub fn clear(&mut self) {
         self.val = u64::MAX
     }
+    pub fn is_valid(&self) -> bool {
+        self.val != u64::MAX
+    }
 }
 
 impl Default for LrScReservation {
@@ -33,10 +39,14 @@ pub const INSTRUCTIONS_A: &[Instruction] = &[
     Instruction {
         mask: MASK_LR_W,
         match_data: MATCH_LR_W,
-        name: "LR_D",
+        name: "LR_W",
         operation: |cpu, inst, pc| {
             let f = parse_format_r(inst);
             let rs1_data = cpu.gpr.read(f.rs1);
+            // never split like a misaligned load
+            if !check_aligned(rs1_data, 4) {
+                return Err(TrapType::LoadAddressMisaligned(rs1_data));
+            }
             let r_data = match cpu.read(rs1_data, 4, AccessType::Load(rs1_data)) {
                 Ok(data) => data as i32 as i64,
                 Err(trap_type) => return Err(trap_type),
@@ -55,6 +65,10 @@ pub const INSTRUCTIONS_A: &[Instruction] = &[
         operation: |cpu, inst, pc| {
             let f = parse_format_r(inst);
             let rs1_data = cpu.gpr.read(f.rs1);
+            // never split like a misaligned load
+            if !check_aligned(rs1_data, 8) {
+                return Err(TrapType::LoadAddressMisaligned(rs1_data));
+            }
             let r_data = match cpu.read(rs1_data, 8, AccessType::Load(rs1_data)) {
                 Ok(data) => data,
                 Err(trap_type) => return Err(trap_type),
@@ -76,6 +90,9 @@ pub const INSTRUCTIONS_A: &[Instruction] = &[
 
             let rs1_data = cpu.gpr.read(f.rs1);
             let rs2_data = cpu.gpr.read(f.rs2);
+            if !check_aligned(rs1_data, 4) {
+                return Err(TrapType::StoreAddressMisaligned(rs1_data));
+            }
 
             if cpu.lr_sc_reservation_check_and_clear(rs1_data) {
                 match cpu.write(rs1_data, rs2_data, 4, AccessType::Store(rs1_data)) {
@@ -98,6 +115,9 @@ pub const INSTRUCTIONS_A: &[Instruction] = &[
 
             let rs1_data = cpu.gpr.read(f.rs1);
             let rs2_data = cpu.gpr.read(f.rs2);
+            if !check_aligned(rs1_data, 8) {
+                return Err(TrapType::StoreAddressMisaligned(rs1_data));
+            }
 
             if cpu.lr_sc_reservation_check_and_clear(rs1_data) {
                 // todo!
@@ -534,3 +554,429 @@ pub const INSTRUCTIONS_A: &[Instruction] = &[
         },
     },
 ];
+
+// AMOCAS.Q works on the register pairs (rd, rd+1) and (rs2, rs2+1),
+// the low half in the even register. x0 as a pair reads zero, writes are ignored
+fn read_pair(cpu: &CpuCore, idx: u64) -> u128 {
+    if idx == 0 {
+        0
+    } else {
+        ((cpu.gpr.read(idx + 1) as u128) << 64) | cpu.gpr.read(idx) as u128
+    }
+}
+
+fn write_pair(cpu: &mut CpuCore, idx: u64, val: u128) {
+    if idx != 0 {
+        cpu.gpr.write(idx, val as u64);
+        cpu.gpr.write(idx + 1, (val >> 64) as u64);
+    }
+}
+
+// Zacas: atomic compare-and-swap, rd holds the compare value and gets
+// the loaded value. the swap value is written only if they are equal
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZACAS: &[Instruction] = &[
+    Instruction {
+        mask: MASK_AMOCAS_W,
+        match_data: MATCH_AMOCAS_W,
+        name: "AMOCAS_W",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1_data = cpu.gpr.read(f.rs1);
+            let rs2_data = cpu.gpr.read(f.rs2);
+            let rd_data = cpu.gpr.read(f.rd);
+
+            let tmp = cpu.read(rs1_data, 4, AccessType::Amo(rs1_data))?;
+            if tmp as u32 == rd_data as u32 {
+                // no err happenes here
+                cpu.write(rs1_data, rs2_data, 4, AccessType::Amo(rs1_data))
+                    .unwrap();
+            }
+            cpu.gpr.write(f.rd, tmp as u32 as i32 as i64 as u64);
+
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_AMOCAS_D,
+        match_data: MATCH_AMOCAS_D,
+        name: "AMOCAS_D",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            let rs1_data = cpu.gpr.read(f.rs1);
+            let rs2_data = cpu.gpr.read(f.rs2);
+            let rd_data = cpu.gpr.read(f.rd);
+
+            let tmp = cpu.read(rs1_data, 8, AccessType::Amo(rs1_data))?;
+            if tmp == rd_data {
+                // no err happenes here
+                cpu.write(rs1_data, rs2_data, 8, AccessType::Amo(rs1_data))
+                    .unwrap();
+            }
+            cpu.gpr.write(f.rd, tmp);
+
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_AMOCAS_Q,
+        match_data: MATCH_AMOCAS_Q,
+        name: "AMOCAS_Q",
+        operation: |cpu, inst, pc| {
+            let f = parse_format_r(inst);
+            // odd registers are reserved
+            if f.rd & 1 == 1 || f.rs2 & 1 == 1 {
+                return Err(TrapType::IllegalInstruction(inst.into()));
+            }
+            let rs1_data = cpu.gpr.read(f.rs1);
+            let rs2_data = read_pair(cpu, f.rs2);
+            let rd_data = read_pair(cpu, f.rd);
+
+            let tmp = cpu.read128(rs1_data, AccessType::Amo(rs1_data))?;
+            if tmp == rd_data {
+                // no err happenes here
+                cpu.write128(rs1_data, rs2_data, AccessType::Amo(rs1_data))
+                    .unwrap();
+            }
+            write_pair(cpu, f.rd, tmp);
+
+            Ok(())
+        },
+    },
+];
+
+#[derive(Clone, Copy)]
+enum AmoOp {
+    Swap,
+    Add,
+    Xor,
+    And,
+    Or,
+    Min,
+    Max,
+    Minu,
+    Maxu,
+}
+
+fn sign_extend(val: u64, len: usize) -> u64 {
+    let shift = 64 - len * 8;
+    (((val << shift) as i64) >> shift) as u64
+}
+
+fn zero_extend(val: u64, len: usize) -> u64 {
+    val & (u64::MAX >> (64 - len * 8))
+}
+
+// byte and halfword AMOs, the memory value is sign-extended into rd.
+// the address must be naturally aligned
+fn amo_narrow(cpu: &mut CpuCore, inst: u32, len: usize, op: AmoOp) -> Result<(), TrapType> {
+    let f = parse_format_r(inst);
+    let rs1_data = cpu.gpr.read(f.rs1);
+    let rs2_data = cpu.gpr.read(f.rs2);
+
+    let tmp = cpu.read(rs1_data, len, AccessType::Amo(rs1_data))?;
+    let (s_tmp, s_rs2) = (
+        sign_extend(tmp, len) as i64,
+        sign_extend(rs2_data, len) as i64,
+    );
+    let (u_tmp, u_rs2) = (zero_extend(tmp, len), zero_extend(rs2_data, len));
+
+    let amo_write = match op {
+        AmoOp::Swap => rs2_data,
+        AmoOp::Add => tmp.wrapping_add(rs2_data),
+        AmoOp::Xor => tmp ^ rs2_data,
+        AmoOp::And => tmp & rs2_data,
+        AmoOp::Or => tmp | rs2_data,
+        AmoOp::Min => s_tmp.min(s_rs2) as u64,
+        AmoOp::Max => s_tmp.max(s_rs2) as u64,
+        AmoOp::Minu => u_tmp.min(u_rs2),
+        AmoOp::Maxu => u_tmp.max(u_rs2),
+    };
+
+    // no err happenes here
+    cpu.write(rs1_data, amo_write, len, AccessType::Amo(rs1_data))
+        .unwrap();
+    cpu.gpr.write(f.rd, s_tmp as u64);
+
+    Ok(())
+}
+
+fn amocas_narrow(cpu: &mut CpuCore, inst: u32, len: usize) -> Result<(), TrapType> {
+    let f = parse_format_r(inst);
+    let rs1_data = cpu.gpr.read(f.rs1);
+    let rs2_data = cpu.gpr.read(f.rs2);
+    let rd_data = cpu.gpr.read(f.rd);
+
+    let tmp = cpu.read(rs1_data, len, AccessType::Amo(rs1_data))?;
+    if tmp == zero_extend(rd_data, len) {
+        // no err happenes here
+        cpu.write(rs1_data, rs2_data, len, AccessType::Amo(rs1_data))
+            .unwrap();
+    }
+    cpu.gpr.write(f.rd, sign_extend(tmp, len));
+
+    Ok(())
+}
+
+// Zabha: byte and halfword atomic memory operations
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZABHA: &[Instruction] = &[
+    Instruction {
+        mask: MASK_AMOSWAP_B,
+        match_data: MATCH_AMOSWAP_B,
+        name: "AMOSWAP_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::Swap),
+    },
+    Instruction {
+        mask: MASK_AMOSWAP_H,
+        match_data: MATCH_AMOSWAP_H,
+        name: "AMOSWAP_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::Swap),
+    },
+    Instruction {
+        mask: MASK_AMOADD_B,
+        match_data: MATCH_AMOADD_B,
+        name: "AMOADD_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::Add),
+    },
+    Instruction {
+        mask: MASK_AMOADD_H,
+        match_data: MATCH_AMOADD_H,
+        name: "AMOADD_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::Add),
+    },
+    Instruction {
+        mask: MASK_AMOXOR_B,
+        match_data: MATCH_AMOXOR_B,
+        name: "AMOXOR_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::Xor),
+    },
+    Instruction {
+        mask: MASK_AMOXOR_H,
+        match_data: MATCH_AMOXOR_H,
+        name: "AMOXOR_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::Xor),
+    },
+    Instruction {
+        mask: MASK_AMOAND_B,
+        match_data: MATCH_AMOAND_B,
+        name: "AMOAND_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::And),
+    },
+    Instruction {
+        mask: MASK_AMOAND_H,
+        match_data: MATCH_AMOAND_H,
+        name: "AMOAND_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::And),
+    },
+    Instruction {
+        mask: MASK_AMOOR_B,
+        match_data: MATCH_AMOOR_B,
+        name: "AMOOR_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::Or),
+    },
+    Instruction {
+        mask: MASK_AMOOR_H,
+        match_data: MATCH_AMOOR_H,
+        name: "AMOOR_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::Or),
+    },
+    Instruction {
+        mask: MASK_AMOMIN_B,
+        match_data: MATCH_AMOMIN_B,
+        name: "AMOMIN_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::Min),
+    },
+    Instruction {
+        mask: MASK_AMOMIN_H,
+        match_data: MATCH_AMOMIN_H,
+        name: "AMOMIN_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::Min),
+    },
+    Instruction {
+        mask: MASK_AMOMAX_B,
+        match_data: MATCH_AMOMAX_B,
+        name: "AMOMAX_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::Max),
+    },
+    Instruction {
+        mask: MASK_AMOMAX_H,
+        match_data: MATCH_AMOMAX_H,
+        name: "AMOMAX_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::Max),
+    },
+    Instruction {
+        mask: MASK_AMOMINU_B,
+        match_data: MATCH_AMOMINU_B,
+        name: "AMOMINU_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::Minu),
+    },
+    Instruction {
+        mask: MASK_AMOMINU_H,
+        match_data: MATCH_AMOMINU_H,
+        name: "AMOMINU_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::Minu),
+    },
+    Instruction {
+        mask: MASK_AMOMAXU_B,
+        match_data: MATCH_AMOMAXU_B,
+        name: "AMOMAXU_B",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 1, AmoOp::Maxu),
+    },
+    Instruction {
+        mask: MASK_AMOMAXU_H,
+        match_data: MATCH_AMOMAXU_H,
+        name: "AMOMAXU_H",
+        operation: |cpu, inst, pc| amo_narrow(cpu, inst, 2, AmoOp::Maxu),
+    },
+];
+
+// AMOCAS.B and AMOCAS.H, only with Zacas
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZABHA_ZACAS: &[Instruction] = &[
+    Instruction {
+        mask: MASK_AMOCAS_B,
+        match_data: MATCH_AMOCAS_B,
+        name: "AMOCAS_B",
+        operation: |cpu, inst, pc| amocas_narrow(cpu, inst, 1),
+    },
+    Instruction {
+        mask: MASK_AMOCAS_H,
+        match_data: MATCH_AMOCAS_H,
+        name: "AMOCAS_H",
+        operation: |cpu, inst, pc| amocas_narrow(cpu, inst, 2),
+    },
+];
+
+#[cfg(test)]
+mod test_rv64a {
+    use crate::{
+        config::Config,
+        device::device_trait::MEM_BASE,
+        rv64core::{
+            test_cpu::{exec, test_bus, test_cpu, test_cpu_on},
+            traptype::TrapType,
+        },
+    };
+
+    #[test]
+    fn zacas_test() {
+        let bus = test_bus(4096);
+        let mut config = Config::new();
+        config.set_isa("rv64imac_zacas");
+        config.set_dcache_size(16);
+        let mut cpu = test_cpu_on(&bus, config);
+        let ram = |addr: u64| bus.borrow_mut().read(addr, 8).unwrap();
+
+        // amocas.w a0,a2,(a4)
+        cpu.gpr.write(14, MEM_BASE);
+        bus.borrow_mut().write(MEM_BASE, 0xffff_fff0, 4).unwrap();
+        cpu.gpr.write(10, 0x1);
+        cpu.gpr.write(12, 0x1234);
+        exec(&mut cpu, 0x28c7252f).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0xffff_ffff_ffff_fff0);
+        // the compare ignores the upper 32 bits
+        exec(&mut cpu, 0x28c7252f).unwrap();
+        cpu.cache_system.borrow_mut().clear();
+        assert_eq!(ram(MEM_BASE), 0x1234);
+
+        // amocas.d a0,a2,(a4)
+        cpu.gpr.write(10, 0x1234);
+        cpu.gpr.write(12, u64::MAX);
+        exec(&mut cpu, 0x28c7352f).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0x1234);
+        cpu.cache_system.borrow_mut().clear();
+        assert_eq!(ram(MEM_BASE), u64::MAX);
+
+        // amocas.q a0,a2,(a4), the pairs are (a0,a1) and (a2,a3)
+        cpu.gpr.write(14, MEM_BASE + 0x10);
+        bus.borrow_mut().write(MEM_BASE + 0x10, 0x1111, 8).unwrap();
+        bus.borrow_mut().write(MEM_BASE + 0x18, 0x2222, 8).unwrap();
+        cpu.gpr.write(10, 0x1111);
+        cpu.gpr.write(11, 0x0);
+        cpu.gpr.write(12, 0x3333);
+        cpu.gpr.write(13, 0x4444);
+        // compare fails, memory unchanged
+        exec(&mut cpu, 0x28c7452f).unwrap();
+        assert_eq!((cpu.gpr.read(10), cpu.gpr.read(11)), (0x1111, 0x2222));
+        cpu.cache_system.borrow_mut().clear();
+        assert_eq!(
+            (ram(MEM_BASE + 0x10), ram(MEM_BASE + 0x18)),
+            (0x1111, 0x2222)
+        );
+        // compare succeeds
+        exec(&mut cpu, 0x28c7452f).unwrap();
+        cpu.cache_system.borrow_mut().clear();
+        assert_eq!(
+            (ram(MEM_BASE + 0x10), ram(MEM_BASE + 0x18)),
+            (0x3333, 0x4444)
+        );
+
+        // amocas.q a1,a2,(a4), odd rd is reserved
+        let inst = 0x28c745af;
+        assert_eq!(
+            exec(&mut cpu, inst),
+            Err(TrapType::IllegalInstruction(inst.into()))
+        );
+        // misaligned
+        cpu.gpr.write(14, MEM_BASE + 0x8);
+        assert_eq!(
+            exec(&mut cpu, 0x28c7452f),
+            Err(TrapType::StoreAddressMisaligned(MEM_BASE + 0x8))
+        );
+    }
+
+    #[test]
+    fn zabha_test() {
+        let (mut cpu, bus) = test_cpu("rv64imac_zacas_zabha", 4096);
+        let ram = |addr: u64| bus.borrow_mut().read(addr, 8).unwrap();
+
+        // a4 -> MEM_BASE + 1
+        cpu.gpr.write(14, MEM_BASE + 1);
+        bus.borrow_mut()
+            .write(MEM_BASE, 0x1122_3344_5566_80ff, 8)
+            .unwrap();
+
+        let cases = [
+            // (inst, a2, a0 expect, memory expect)
+            // amoadd.b a0,a2,(a4): 0x80 + 0x7f, the carry is dropped
+            (
+                0x00c7052f,
+                0x17f,
+                0xffff_ffff_ffff_ff80,
+                0x1122_3344_5566_ffff,
+            ),
+            // amomax.b a0,a2,(a4): max(-1, 1)
+            (0xa0c7052f, 0x1, u64::MAX, 0x1122_3344_5566_01ff),
+            // amominu.b a0,a2,(a4): minu(1, 0xfe)
+            (0xc0c7052f, 0xfe, 0x1, 0x1122_3344_5566_01ff),
+            // amoswap.b a0,a2,(a4)
+            (0x08c7052f, 0xaa, 0x1, 0x1122_3344_5566_aaff),
+        ];
+        for (inst, rs2, rd_expect, mem_expect) in cases {
+            cpu.gpr.write(12, rs2);
+            exec(&mut cpu, inst).unwrap();
+            assert_eq!(cpu.gpr.read(10), rd_expect, "{inst:x}");
+            assert_eq!(ram(MEM_BASE), mem_expect, "{inst:x}");
+        }
+
+        // amoxor.h a0,a2,(a4), misaligned
+        assert_eq!(
+            exec(&mut cpu, 0x20c7152f),
+            Err(TrapType::StoreAddressMisaligned(MEM_BASE + 1))
+        );
+        // amomin.h a0,a2,(a4): min(0x5566, -2)
+        cpu.gpr.write(14, MEM_BASE + 2);
+        cpu.gpr.write(12, 0xfffe);
+        exec(&mut cpu, 0x80c7152f).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0x5566);
+        assert_eq!(ram(MEM_BASE), 0x1122_3344_fffe_aaff);
+
+        // amocas.h a0,a2,(a4), the compare value is sign-extended in a0
+        cpu.gpr.write(10, 0xffff_ffff_ffff_fffe);
+        cpu.gpr.write(12, 0x1234);
+        exec(&mut cpu, 0x28c7152f).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0xffff_ffff_ffff_fffe);
+        assert_eq!(ram(MEM_BASE), 0x1122_3344_1234_aaff);
+    }
+}
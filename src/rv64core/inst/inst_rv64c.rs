@@ -1,6 +1,6 @@
 use crate::{rv64core::{inst::inst_base::*, traptype::TrapType}, tools::check_aligned};
 
-#[cfg(feature = "rvc_debug_trace")]
+#[cfg(feature = "rv_debug_trace")]
 use crate::trace::traces::TraceType;
 
 use super::inst_rv64z::handle_ebreak;
@@ -159,17 +159,12 @@ pub const INSTRUCTIONS_C: &[Instruction] = &[
         mask: MASK_C_J,
         match_data: MATCH_C_J,
         name: "c.j",
-        operation: |cpu, inst, pc| {
+        operation: |cpu, inst, _pc| {
             let f = FormatCJ::new(inst);
             let imm = f.imm_c_j() as i64;
 
+            // jal x0, never a call
             let next_pc = cpu.pc.wrapping_add(imm as u64);
-            #[cfg(feature = "rvc_debug_trace")]
-            if f.is_call() {
-                if let Some(sender) = &cpu.trace_sender {
-                    sender.send(TraceType::Call(pc, next_pc)).unwrap();
-                };
-            };
             cpu.npc = next_pc;
             Ok(())
         },
@@ -184,7 +179,7 @@ pub const INSTRUCTIONS_C: &[Instruction] = &[
     //         panic!("c.jal is rv32 only");
     //         // todo! check align
     //         let next_pc = cpu.pc.wrapping_add(imm as u64);
-    //         #[cfg(feature = "rvc_debug_trace")]
+    //         #[cfg(feature = "rv_debug_trace")]
     //         if f.is_call() {
     //             if let Some(sender) = &cpu.trace_sender {
     //                 sender.send(TraceType::Call(pc, next_pc)).unwrap();
@@ -203,10 +198,10 @@ pub const INSTRUCTIONS_C: &[Instruction] = &[
             let rs1_data = cpu.gpr.read(f.rs1());
 
             let next_pc = rs1_data;
-            #[cfg(feature = "rvc_debug_trace")]
-            if f.is_call() {
+            #[cfg(feature = "rv_debug_trace")]
+            if f.is_ret() {
                 if let Some(sender) = &cpu.trace_sender {
-                    sender.send(TraceType::Call(pc, next_pc)).unwrap();
+                    sender.send(TraceType::Return(pc, next_pc)).unwrap();
                 };
             };
             cpu.npc = next_pc;
@@ -224,11 +219,10 @@ pub const INSTRUCTIONS_C: &[Instruction] = &[
             let wdata = pc.wrapping_add(2);
 
             let next_pc = rs1_data;
-            #[cfg(feature = "rvc_debug_trace")]
-            if f.is_call() {
-                if let Some(sender) = &cpu.trace_sender {
-                    sender.send(TraceType::Call(pc, next_pc)).unwrap();
-                };
+            // links ra, always a call
+            #[cfg(feature = "rv_debug_trace")]
+            if let Some(sender) = &cpu.trace_sender {
+                sender.send(TraceType::Call(pc, next_pc)).unwrap();
             };
             cpu.npc = next_pc;
             cpu.gpr.write(1, wdata);
@@ -0,0 +1,379 @@
+use core::cmp::Ordering;
+
+use rustc_apfloat::ieee::{Double, Single};
+use rustc_apfloat::Float;
+
+use crate::rv64core::{
+    cpu_core::CpuCore,
+    inst::{inst_base::*, inst_rv64f::*},
+};
+
+// Zdinx on RV64 keeps doubles in single x registers
+impl FpReg for Double {
+    fn read_fpr(cpu: &CpuCore, idx: u64) -> Self {
+        let bits = match cpu.fpr.in_x() {
+            true => cpu.gpr.read(idx),
+            false => cpu.fpr.read(idx),
+        };
+        Double::from_bits(bits.into())
+    }
+    fn write_fpr(cpu: &mut CpuCore, idx: u64, val: Self) {
+        let bits = val.to_bits() as u64;
+        match cpu.fpr.in_x() {
+            true => cpu.gpr.write(idx, bits),
+            false => cpu.fpr.write(idx, bits),
+        }
+    }
+}
+
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_D: &[Instruction] = &[
+    Instruction {
+        mask: MASK_FLD,
+        match_data: MATCH_FLD,
+        name: "FLD",
+        operation: |cpu, inst, pc| {
+            // f[rd] = M[x[rs1] + sext(offset)][63:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i64;
+            let mem_addr = rs1.wrapping_add(f.imm) as u64;
+            let mem_data = cpu.read(mem_addr, 8, AccessType::Load(mem_addr))?;
+            cpu.fpr.write(f.rd, mem_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FSD,
+        match_data: MATCH_FSD,
+        name: "FSD",
+        operation: |cpu, inst, pc| {
+            // M[x[rs1] + sext(offset)] = f[rs2][63:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_s(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i64;
+            let rs2 = cpu.fpr.read(f.rs2);
+            let mem_addr = rs1.wrapping_add(f.imm) as u64;
+            cpu.write(mem_addr, rs2, 8, AccessType::Store(mem_addr))?;
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FMADD_D,
+        match_data: MATCH_FMADD_D,
+        name: "FMADD_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1]×f[rs2]+f[rs3]
+            fp_op3::<Double>(cpu, inst, fp_fma)
+        },
+    },
+    Instruction {
+        mask: MASK_FMSUB_D,
+        match_data: MATCH_FMSUB_D,
+        name: "FMSUB_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1]×f[rs2]-f[rs3]
+            fp_op3::<Double>(cpu, inst, |a, b, c, rm| fp_fma(a, b, -c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FNMSUB_D,
+        match_data: MATCH_FNMSUB_D,
+        name: "FNMSUB_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = -f[rs1]×f[rs2]+f[rs3]
+            fp_op3::<Double>(cpu, inst, |a, b, c, rm| fp_fma(-a, b, c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FNMADD_D,
+        match_data: MATCH_FNMADD_D,
+        name: "FNMADD_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = -f[rs1]×f[rs2]-f[rs3]
+            fp_op3::<Double>(cpu, inst, |a, b, c, rm| fp_fma(-a, b, -c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FADD_D,
+        match_data: MATCH_FADD_D,
+        name: "FADD_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] + f[rs2]
+            fp_op2::<Double>(cpu, inst, |a, b, rm| a.add_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSUB_D,
+        match_data: MATCH_FSUB_D,
+        name: "FSUB_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] - f[rs2]
+            fp_op2::<Double>(cpu, inst, |a, b, rm| a.sub_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FMUL_D,
+        match_data: MATCH_FMUL_D,
+        name: "FMUL_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] × f[rs2]
+            fp_op2::<Double>(cpu, inst, |a, b, rm| a.mul_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FDIV_D,
+        match_data: MATCH_FDIV_D,
+        name: "FDIV_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] ÷ f[rs2]
+            fp_op2::<Double>(cpu, inst, |a, b, rm| a.div_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSQRT_D,
+        match_data: MATCH_FSQRT_D,
+        name: "FSQRT_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = √f[rs1]
+            fp_op2::<Double>(cpu, inst, |a, _, rm| fp_sqrt(a, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJ_D,
+        match_data: MATCH_FSGNJ_D,
+        name: "FSGNJ_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {f[rs2][63], f[rs1][62:0]}
+            fp_sgnj::<Double>(cpu, inst, 0)
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJN_D,
+        match_data: MATCH_FSGNJN_D,
+        name: "FSGNJN_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {~f[rs2][63], f[rs1][62:0]}
+            fp_sgnj::<Double>(cpu, inst, 1)
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJX_D,
+        match_data: MATCH_FSGNJX_D,
+        name: "FSGNJX_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {f[rs1][31] ^ f[rs2][63], f[rs1][62:0]}
+            fp_sgnj::<Double>(cpu, inst, 2)
+        },
+    },
+    Instruction {
+        mask: MASK_FMIN_D,
+        match_data: MATCH_FMIN_D,
+        name: "FMIN_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = min(f[rs1], f[rs2])
+            fp_min_max_op::<Double>(cpu, inst, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FMAX_D,
+        match_data: MATCH_FMAX_D,
+        name: "FMAX_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = max(f[rs1], f[rs2])
+            fp_min_max_op::<Double>(cpu, inst, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_W_D,
+        match_data: MATCH_FCVT_W_D,
+        name: "FCVT_W_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = sext(s32_f64(f[rs1]))
+            fp_to_int_op::<Double>(cpu, inst, 32, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_WU_D,
+        match_data: MATCH_FCVT_WU_D,
+        name: "FCVT_WU_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = sext(u32_f64(f[rs1]))
+            fp_to_int_op::<Double>(cpu, inst, 32, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_L_D,
+        match_data: MATCH_FCVT_L_D,
+        name: "FCVT_L_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = s64_f64(f[rs1])
+            fp_to_int_op::<Double>(cpu, inst, 64, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_LU_D,
+        match_data: MATCH_FCVT_LU_D,
+        name: "FCVT_LU_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = u64_f64(f[rs1])
+            fp_to_int_op::<Double>(cpu, inst, 64, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_D_W,
+        match_data: MATCH_FCVT_D_W,
+        name: "FCVT_D_W",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f64_s32(x[rs1])
+            int_to_fp_op::<Double>(cpu, inst, 32, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_D_WU,
+        match_data: MATCH_FCVT_D_WU,
+        name: "FCVT_D_WU",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f64_u32(x[rs1])
+            int_to_fp_op::<Double>(cpu, inst, 32, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_D_L,
+        match_data: MATCH_FCVT_D_L,
+        name: "FCVT_D_L",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f64_s64(x[rs1])
+            int_to_fp_op::<Double>(cpu, inst, 64, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_D_LU,
+        match_data: MATCH_FCVT_D_LU,
+        name: "FCVT_D_LU",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f64_u64(x[rs1])
+            int_to_fp_op::<Double>(cpu, inst, 64, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FEQ_D,
+        match_data: MATCH_FEQ_D,
+        name: "FEQ_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] == f[rs2]
+            fp_cmp_op::<Double>(cpu, inst, false, |x| x == Ordering::Equal)
+        },
+    },
+    Instruction {
+        mask: MASK_FLT_D,
+        match_data: MATCH_FLT_D,
+        name: "FLT_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] < f[rs2]
+            fp_cmp_op::<Double>(cpu, inst, true, |x| x == Ordering::Less)
+        },
+    },
+    Instruction {
+        mask: MASK_FLE_D,
+        match_data: MATCH_FLE_D,
+        name: "FLE_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] <= f[rs2]
+            fp_cmp_op::<Double>(cpu, inst, true, |x| x != Ordering::Greater)
+        },
+    },
+    Instruction {
+        mask: MASK_FCLASS_D,
+        match_data: MATCH_FCLASS_D,
+        name: "FCLASS_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = classifyd(f[rs1])
+            fp_class_op::<Double>(cpu, inst)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_S_D,
+        match_data: MATCH_FCVT_S_D,
+        name: "FCVT_S_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f32_f64(f[rs1])
+            fp_convert_op::<Double, Single>(cpu, inst)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_D_S,
+        match_data: MATCH_FCVT_D_S,
+        name: "FCVT_D_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f64_f32(f[rs1])
+            fp_convert_op::<Single, Double>(cpu, inst)
+        },
+    },
+    Instruction {
+        mask: MASK_FMV_X_D,
+        match_data: MATCH_FMV_X_D,
+        name: "FMV_X_D",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1][63:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_r(inst);
+            let rs1 = cpu.fpr.read(f.rs1);
+            cpu.gpr.write(f.rd, rs1);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FMV_D_X,
+        match_data: MATCH_FMV_D_X,
+        name: "FMV_D_X",
+        operation: |cpu, inst, pc| {
+            // f[rd] = x[rs1][63:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            cpu.fpr.write(f.rd, rs1);
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64d {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use rustc_apfloat::{ieee::Double, Float, Round, Status};
+
+    use crate::rv64core::{csr_regs_define::XstatusIn, fpr::Fpr, inst::inst_rv64f::fp_sqrt};
+
+    #[test]
+    fn sqrt_test() {
+        let cases = [
+            (0x4000000000000000, 0x3ff6a09e667f3bcd, Status::INEXACT), // 2.0
+            (0x4022000000000000, 0x4008000000000000, Status::OK),      // 9.0
+            (0x0000000000000001, 0x1e60000000000000, Status::OK),      // 2^-1074
+        ];
+        for (x, expect, status) in cases {
+            let ret = fp_sqrt(Double::from_bits(x), Round::NearestTiesToEven);
+            assert_eq!(ret.value.to_bits(), expect, "sqrt({x:x})");
+            assert_eq!(ret.status, status, "sqrt({x:x})");
+        }
+    }
+
+    #[test]
+    fn nan_boxing_test() {
+        let xstatus = Rc::new(Cell::new(XstatusIn::new()));
+        let mut fpr = Fpr::new(xstatus.clone(), false);
+
+        fpr.write_f32(1, 0x3f80_0000);
+        assert_eq!(fpr.read(1), 0xffff_ffff_3f80_0000);
+        assert_eq!(fpr.read_f32(1), 0x3f80_0000);
+        // a double is not a valid single
+        fpr.write(2, 0x3ff0_0000_0000_0000);
+        assert_eq!(fpr.read_f32(2), 0x7fc0_0000);
+        // writes make the fp state dirty
+        assert_eq!(xstatus.get().fs(), 0b11);
+        assert!(xstatus.get().sd());
+    }
+}
@@ -0,0 +1,814 @@
+use core::cmp::Ordering;
+
+use rustc_apfloat::{ieee::Single, Category, Float, FloatConvert, Round, Status, StatusAnd};
+
+use crate::rv64core::{cpu_core::CpuCore, inst::inst_base::*, traptype::TrapType};
+
+// The helpers below are generic over the float format, they are shared by
+// the F, D and Zfh tables. Softfloat is provided by rustc_apfloat (no_std).
+
+// register access for each float format
+pub trait FpReg: Float {
+    fn read_fpr(cpu: &CpuCore, idx: u64) -> Self;
+    fn write_fpr(cpu: &mut CpuCore, idx: u64, val: Self);
+}
+
+// Zfinx keeps singles in the low 32 bits of x registers, sign-extended and not NaN-boxed
+impl FpReg for Single {
+    fn read_fpr(cpu: &CpuCore, idx: u64) -> Self {
+        let bits = match cpu.fpr.in_x() {
+            true => cpu.gpr.read(idx) as u32,
+            false => cpu.fpr.read_f32(idx),
+        };
+        Single::from_bits(bits.into())
+    }
+    fn write_fpr(cpu: &mut CpuCore, idx: u64, val: Self) {
+        let bits = val.to_bits() as u32;
+        match cpu.fpr.in_x() {
+            true => cpu.gpr.write(idx, bits as i32 as i64 as u64),
+            false => cpu.fpr.write_f32(idx, bits),
+        }
+    }
+}
+
+// all floating point instructions are illegal when mstatus.FS is off,
+// or vsstatus.FS is off in VS-mode and VU-mode. Zfinx has no FS, the
+// FCSR bit of the stateen csrs gates the fp state instead
+pub fn check_fs(cpu: &CpuCore, inst: u32) -> Result<(), TrapType> {
+    if cpu.fpr.in_x() {
+        let ret = cpu
+            .csr_regs
+            .check_stateen(CSR_FCSR.into(), cpu.cur_priv.get());
+        return ret.map_err(|x| match x {
+            TrapType::VirtualInstruction(_) => TrapType::VirtualInstruction(inst.into()),
+            _ => TrapType::IllegalInstruction(inst.into()),
+        });
+    }
+    let vs_off = cpu.csr_regs.virt() && cpu.csr_regs.h.vsstatus.get().fs() == 0;
+    if cpu.csr_regs.xstatus.get().fs() == 0 || vs_off {
+        Err(TrapType::IllegalInstruction(inst.into()))
+    } else {
+        Ok(())
+    }
+}
+
+// rm 0b111 selects frm, reserved rounding modes are illegal
+pub fn get_round(cpu: &CpuCore, rm: u8, inst: u32) -> Result<Round, TrapType> {
+    let rm = if rm == 0b111 {
+        cpu.csr_regs.fcsr.get().frm()
+    } else {
+        rm
+    };
+    match rm {
+        0b000 => Ok(Round::NearestTiesToEven),
+        0b001 => Ok(Round::TowardZero),
+        0b010 => Ok(Round::TowardNegative),
+        0b011 => Ok(Round::TowardPositive),
+        0b100 => Ok(Round::NearestTiesToAway),
+        _ => Err(TrapType::IllegalInstruction(inst.into())),
+    }
+}
+
+// accrue exception flags into fflags
+pub fn accrue_fflags(cpu: &mut CpuCore, status: Status) {
+    let mut flags = 0;
+    if status.contains(Status::INEXACT) {
+        flags |= 1 << 0;
+    }
+    if status.contains(Status::UNDERFLOW) {
+        flags |= 1 << 1;
+    }
+    if status.contains(Status::OVERFLOW) {
+        flags |= 1 << 2;
+    }
+    if status.contains(Status::DIV_BY_ZERO) {
+        flags |= 1 << 3;
+    }
+    if status.contains(Status::INVALID_OP) {
+        flags |= 1 << 4;
+    }
+    if flags != 0 {
+        let mut fcsr = cpu.csr_regs.fcsr.get();
+        fcsr.set_fflags(fcsr.fflags() | flags);
+        cpu.csr_regs.fcsr.set(fcsr);
+        cpu.fpr.set_dirty();
+    }
+}
+
+// arithmetic instructions always return the canonical NaN
+pub fn canonical_nan<F: Float>(val: F) -> F {
+    if val.is_nan() {
+        F::NAN
+    } else {
+        val
+    }
+}
+
+pub fn fp_fma<F: Float>(a: F, b: F, c: F, round: Round) -> StatusAnd<F> {
+    let mut ret = a.mul_add_r(b, c, round);
+    // ∞ × 0 is invalid, even when the addend is a quiet NaN
+    if (a.is_infinite() && b.is_zero()) || (a.is_zero() && b.is_infinite()) {
+        ret.status |= Status::INVALID_OP;
+    }
+    ret
+}
+
+// IEEE 754-2019 minimumNumber/maximumNumber, -0.0 is less than +0.0
+pub fn fp_min_max<F: Float>(a: F, b: F, is_max: bool) -> StatusAnd<F> {
+    let status = if a.is_signaling() || b.is_signaling() {
+        Status::INVALID_OP
+    } else {
+        Status::OK
+    };
+    let val = match (a.is_nan(), b.is_nan()) {
+        (true, true) => F::NAN,
+        (true, false) => b,
+        (false, true) => a,
+        _ if a.is_zero() && b.is_zero() => {
+            if a.is_negative() ^ is_max {
+                a
+            } else {
+                b
+            }
+        }
+        _ if (a < b) ^ is_max => a,
+        _ => b,
+    };
+    status.and(val)
+}
+
+// feq is a quiet comparison, flt and fle are signaling comparisons
+pub fn fp_compare<F: Float>(a: F, b: F, signaling: bool) -> StatusAnd<Option<Ordering>> {
+    let invalid = if signaling {
+        a.is_nan() || b.is_nan()
+    } else {
+        a.is_signaling() || b.is_signaling()
+    };
+    let status = if invalid {
+        Status::INVALID_OP
+    } else {
+        Status::OK
+    };
+    status.and(a.partial_cmp(&b))
+}
+
+pub fn fp_class<F: Float>(val: F) -> u64 {
+    let neg = val.is_negative();
+    let bit = match val.category() {
+        Category::Infinity if neg => 0,
+        Category::Normal if neg && !val.is_denormal() => 1,
+        Category::Normal if neg => 2,
+        Category::Zero if neg => 3,
+        Category::Zero => 4,
+        Category::Normal if val.is_denormal() => 5,
+        Category::Normal => 6,
+        Category::Infinity => 7,
+        Category::NaN if val.is_signaling() => 8,
+        Category::NaN => 9,
+    };
+    1 << bit
+}
+
+// float to integer of `width` bits, out of range values and NaN saturate.
+// 32 bit results are sign extended to 64 bits
+pub fn fp_to_int<F: Float>(val: F, round: Round, width: usize, signed: bool) -> StatusAnd<u64> {
+    let (min, max): (i128, i128) = if signed {
+        (-(1 << (width - 1)), (1 << (width - 1)) - 1)
+    } else {
+        (0, (1 << width) - 1)
+    };
+    let sext = |x: i128| {
+        if width == 32 {
+            x as i32 as i64 as u64
+        } else {
+            x as u64
+        }
+    };
+
+    if val.is_nan() {
+        return Status::INVALID_OP.and(sext(max));
+    }
+    let rounded = val.round_to_integral(round);
+    let mut is_exact = false;
+    let x = rounded
+        .value
+        .to_i128_r(128, Round::TowardZero, &mut is_exact)
+        .value;
+    if x < min {
+        Status::INVALID_OP.and(sext(min))
+    } else if x > max {
+        Status::INVALID_OP.and(sext(max))
+    } else {
+        rounded.status.and(sext(x))
+    }
+}
+
+fn isqrt(n: u128) -> u128 {
+    let mut rem = n;
+    let mut root = 0;
+    let mut bit = 1 << 126;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+// correctly rounded square root, rustc_apfloat does not provide one
+pub fn fp_sqrt<F: Float>(val: F, round: Round) -> StatusAnd<F> {
+    if val.is_nan() {
+        let status = if val.is_signaling() {
+            Status::INVALID_OP
+        } else {
+            Status::OK
+        };
+        return status.and(F::NAN);
+    }
+    if val.is_zero() || val.is_pos_infinity() {
+        return Status::OK.and(val);
+    }
+    if val.is_negative() {
+        return Status::INVALID_OP.and(F::NAN);
+    }
+
+    // val = m * 2^e, m is an integer of PRECISION bits, e is even
+    let prec = F::PRECISION as i32;
+    let mut e = val.ilogb() - (prec - 1);
+    let mut is_exact = false;
+    let mut m = val
+        .scalbn(-e)
+        .to_u128_r(128, Round::TowardZero, &mut is_exact)
+        .value;
+    if e & 1 != 0 {
+        m <<= 1;
+        e -= 1;
+    }
+    // the root keeps at least two bits more than PRECISION,
+    // the lowest bit is sticky, so that rounding happens only once
+    let k = prec / 2 + 3;
+    let m = m << (2 * k);
+    let root = isqrt(m);
+    let sticky = (root * root != m) as u128;
+    F::from_u128_r((root << 1) | sticky, round).map(|x| x.scalbn(e / 2 - k - 1))
+}
+
+pub fn fp_sign_inject<F: Float>(a: F, b: F, op: u8) -> F {
+    let sign_mask = 1 << (F::BITS - 1);
+    let a_bits = a.to_bits();
+    let b_bits = b.to_bits();
+    let sign = match op {
+        0 => b_bits,          // fsgnj
+        1 => !b_bits,         // fsgnjn
+        _ => a_bits ^ b_bits, // fsgnjx
+    } & sign_mask;
+    F::from_bits((a_bits & !sign_mask) | sign)
+}
+
+// f[rd] = op(f[rs1], f[rs2], f[rs3])
+pub fn fp_op3<F: FpReg>(
+    cpu: &mut CpuCore,
+    inst: u32,
+    op: fn(F, F, F, Round) -> StatusAnd<F>,
+) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r4(inst);
+    let round = get_round(cpu, f.rm, inst)?;
+    let a = F::read_fpr(cpu, f.rs1);
+    let b = F::read_fpr(cpu, f.rs2);
+    let c = F::read_fpr(cpu, f.rs3);
+    let ret = op(a, b, c, round);
+    accrue_fflags(cpu, ret.status);
+    F::write_fpr(cpu, f.rd, canonical_nan(ret.value));
+    Ok(())
+}
+
+// f[rd] = op(f[rs1], f[rs2])
+pub fn fp_op2<F: FpReg>(
+    cpu: &mut CpuCore,
+    inst: u32,
+    op: fn(F, F, Round) -> StatusAnd<F>,
+) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r4(inst);
+    let round = get_round(cpu, f.rm, inst)?;
+    let a = F::read_fpr(cpu, f.rs1);
+    let b = F::read_fpr(cpu, f.rs2);
+    let ret = op(a, b, round);
+    accrue_fflags(cpu, ret.status);
+    F::write_fpr(cpu, f.rd, canonical_nan(ret.value));
+    Ok(())
+}
+
+// f[rd] = sgnj(f[rs1], f[rs2]), no exceptions, NaNs are kept
+pub fn fp_sgnj<F: FpReg>(cpu: &mut CpuCore, inst: u32, op: u8) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r(inst);
+    let a = F::read_fpr(cpu, f.rs1);
+    let b = F::read_fpr(cpu, f.rs2);
+    F::write_fpr(cpu, f.rd, fp_sign_inject(a, b, op));
+    Ok(())
+}
+
+// f[rd] = min/max(f[rs1], f[rs2])
+pub fn fp_min_max_op<F: FpReg>(cpu: &mut CpuCore, inst: u32, is_max: bool) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r(inst);
+    let a = F::read_fpr(cpu, f.rs1);
+    let b = F::read_fpr(cpu, f.rs2);
+    let ret = fp_min_max(a, b, is_max);
+    accrue_fflags(cpu, ret.status);
+    F::write_fpr(cpu, f.rd, ret.value);
+    Ok(())
+}
+
+// x[rd] = f[rs1] op f[rs2]
+pub fn fp_cmp_op<F: FpReg>(
+    cpu: &mut CpuCore,
+    inst: u32,
+    signaling: bool,
+    op: fn(Ordering) -> bool,
+) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r(inst);
+    let a = F::read_fpr(cpu, f.rs1);
+    let b = F::read_fpr(cpu, f.rs2);
+    let ret = fp_compare(a, b, signaling);
+    accrue_fflags(cpu, ret.status);
+    cpu.gpr.write(f.rd, ret.value.is_some_and(op) as u64);
+    Ok(())
+}
+
+// x[rd] = classify(f[rs1])
+pub fn fp_class_op<F: FpReg>(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r(inst);
+    let a = F::read_fpr(cpu, f.rs1);
+    cpu.gpr.write(f.rd, fp_class(a));
+    Ok(())
+}
+
+// x[rd] = f[rs1] to integer
+pub fn fp_to_int_op<F: FpReg>(
+    cpu: &mut CpuCore,
+    inst: u32,
+    width: usize,
+    signed: bool,
+) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r4(inst);
+    let round = get_round(cpu, f.rm, inst)?;
+    let a = F::read_fpr(cpu, f.rs1);
+    let ret = fp_to_int(a, round, width, signed);
+    accrue_fflags(cpu, ret.status);
+    cpu.gpr.write(f.rd, ret.value);
+    Ok(())
+}
+
+// f[rd] = x[rs1] to float
+pub fn int_to_fp_op<F: FpReg>(
+    cpu: &mut CpuCore,
+    inst: u32,
+    width: usize,
+    signed: bool,
+) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r4(inst);
+    let round = get_round(cpu, f.rm, inst)?;
+    let rs1 = cpu.gpr.read(f.rs1);
+    let ret = match (width, signed) {
+        (32, true) => F::from_i128_r(rs1 as i32 as i128, round),
+        (32, false) => F::from_u128_r(rs1 as u32 as u128, round),
+        (_, true) => F::from_i128_r(rs1 as i64 as i128, round),
+        (_, false) => F::from_u128_r(rs1 as u128, round),
+    };
+    accrue_fflags(cpu, ret.status);
+    F::write_fpr(cpu, f.rd, ret.value);
+    Ok(())
+}
+
+// f[rd] = f[rs1] to another float format
+pub fn fp_convert_op<S: FpReg + FloatConvert<D>, D: FpReg>(
+    cpu: &mut CpuCore,
+    inst: u32,
+) -> Result<(), TrapType> {
+    check_fs(cpu, inst)?;
+    let f = parse_format_r4(inst);
+    let round = get_round(cpu, f.rm, inst)?;
+    let a = S::read_fpr(cpu, f.rs1);
+    let mut loses_info = false;
+    let ret = a.convert_r(round, &mut loses_info);
+    accrue_fflags(cpu, ret.status);
+    D::write_fpr(cpu, f.rd, canonical_nan(ret.value));
+    Ok(())
+}
+
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_F: &[Instruction] = &[
+    Instruction {
+        mask: MASK_FLW,
+        match_data: MATCH_FLW,
+        name: "FLW",
+        operation: |cpu, inst, pc| {
+            // f[rd] = M[x[rs1] + sext(offset)][31:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i64;
+            let mem_addr = rs1.wrapping_add(f.imm) as u64;
+            let mem_data = cpu.read(mem_addr, 4, AccessType::Load(mem_addr))?;
+            cpu.fpr.write_f32(f.rd, mem_data as u32);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FSW,
+        match_data: MATCH_FSW,
+        name: "FSW",
+        operation: |cpu, inst, pc| {
+            // M[x[rs1] + sext(offset)] = f[rs2][31:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_s(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i64;
+            let rs2 = cpu.fpr.read(f.rs2) as u32;
+            let mem_addr = rs1.wrapping_add(f.imm) as u64;
+            cpu.write(mem_addr, rs2.into(), 4, AccessType::Store(mem_addr))?;
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FMADD_S,
+        match_data: MATCH_FMADD_S,
+        name: "FMADD_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1]×f[rs2]+f[rs3]
+            fp_op3::<Single>(cpu, inst, fp_fma)
+        },
+    },
+    Instruction {
+        mask: MASK_FMSUB_S,
+        match_data: MATCH_FMSUB_S,
+        name: "FMSUB_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1]×f[rs2]-f[rs3]
+            fp_op3::<Single>(cpu, inst, |a, b, c, rm| fp_fma(a, b, -c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FNMSUB_S,
+        match_data: MATCH_FNMSUB_S,
+        name: "FNMSUB_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = -f[rs1]×f[rs2]+f[rs3]
+            fp_op3::<Single>(cpu, inst, |a, b, c, rm| fp_fma(-a, b, c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FNMADD_S,
+        match_data: MATCH_FNMADD_S,
+        name: "FNMADD_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = -f[rs1]×f[rs2]-f[rs3]
+            fp_op3::<Single>(cpu, inst, |a, b, c, rm| fp_fma(-a, b, -c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FADD_S,
+        match_data: MATCH_FADD_S,
+        name: "FADD_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] + f[rs2]
+            fp_op2::<Single>(cpu, inst, |a, b, rm| a.add_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSUB_S,
+        match_data: MATCH_FSUB_S,
+        name: "FSUB_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] - f[rs2]
+            fp_op2::<Single>(cpu, inst, |a, b, rm| a.sub_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FMUL_S,
+        match_data: MATCH_FMUL_S,
+        name: "FMUL_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] × f[rs2]
+            fp_op2::<Single>(cpu, inst, |a, b, rm| a.mul_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FDIV_S,
+        match_data: MATCH_FDIV_S,
+        name: "FDIV_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] ÷ f[rs2]
+            fp_op2::<Single>(cpu, inst, |a, b, rm| a.div_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSQRT_S,
+        match_data: MATCH_FSQRT_S,
+        name: "FSQRT_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = √f[rs1]
+            fp_op2::<Single>(cpu, inst, |a, _, rm| fp_sqrt(a, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJ_S,
+        match_data: MATCH_FSGNJ_S,
+        name: "FSGNJ_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {f[rs2][31], f[rs1][30:0]}
+            fp_sgnj::<Single>(cpu, inst, 0)
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJN_S,
+        match_data: MATCH_FSGNJN_S,
+        name: "FSGNJN_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {~f[rs2][31], f[rs1][30:0]}
+            fp_sgnj::<Single>(cpu, inst, 1)
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJX_S,
+        match_data: MATCH_FSGNJX_S,
+        name: "FSGNJX_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {f[rs1][31] ^ f[rs2][31], f[rs1][30:0]}
+            fp_sgnj::<Single>(cpu, inst, 2)
+        },
+    },
+    Instruction {
+        mask: MASK_FMIN_S,
+        match_data: MATCH_FMIN_S,
+        name: "FMIN_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = min(f[rs1], f[rs2])
+            fp_min_max_op::<Single>(cpu, inst, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FMAX_S,
+        match_data: MATCH_FMAX_S,
+        name: "FMAX_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = max(f[rs1], f[rs2])
+            fp_min_max_op::<Single>(cpu, inst, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_W_S,
+        match_data: MATCH_FCVT_W_S,
+        name: "FCVT_W_S",
+        operation: |cpu, inst, pc| {
+            // x[rd] = sext(s32_f32(f[rs1]))
+            fp_to_int_op::<Single>(cpu, inst, 32, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_WU_S,
+        match_data: MATCH_FCVT_WU_S,
+        name: "FCVT_WU_S",
+        operation: |cpu, inst, pc| {
+            // x[rd] = sext(u32_f32(f[rs1]))
+            fp_to_int_op::<Single>(cpu, inst, 32, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_L_S,
+        match_data: MATCH_FCVT_L_S,
+        name: "FCVT_L_S",
+        operation: |cpu, inst, pc| {
+            // x[rd] = s64_f32(f[rs1])
+            fp_to_int_op::<Single>(cpu, inst, 64, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_LU_S,
+        match_data: MATCH_FCVT_LU_S,
+        name: "FCVT_LU_S",
+        operation: |cpu, inst, pc| {
+            // x[rd] = u64_f32(f[rs1])
+            fp_to_int_op::<Single>(cpu, inst, 64, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_S_W,
+        match_data: MATCH_FCVT_S_W,
+        name: "FCVT_S_W",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f32_s32(x[rs1])
+            int_to_fp_op::<Single>(cpu, inst, 32, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_S_WU,
+        match_data: MATCH_FCVT_S_WU,
+        name: "FCVT_S_WU",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f32_u32(x[rs1])
+            int_to_fp_op::<Single>(cpu, inst, 32, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_S_L,
+        match_data: MATCH_FCVT_S_L,
+        name: "FCVT_S_L",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f32_s64(x[rs1])
+            int_to_fp_op::<Single>(cpu, inst, 64, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_S_LU,
+        match_data: MATCH_FCVT_S_LU,
+        name: "FCVT_S_LU",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f32_u64(x[rs1])
+            int_to_fp_op::<Single>(cpu, inst, 64, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FMV_X_W,
+        match_data: MATCH_FMV_X_W,
+        name: "FMV_X_W",
+        operation: |cpu, inst, pc| {
+            // x[rd] = sext(f[rs1][31:0])
+            check_fs(cpu, inst)?;
+            let f = parse_format_r(inst);
+            let rs1 = cpu.fpr.read(f.rs1) as u32;
+            cpu.gpr.write(f.rd, rs1 as i32 as i64 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FMV_W_X,
+        match_data: MATCH_FMV_W_X,
+        name: "FMV_W_X",
+        operation: |cpu, inst, pc| {
+            // f[rd] = x[rs1][31:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as u32;
+            cpu.fpr.write_f32(f.rd, rs1);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FEQ_S,
+        match_data: MATCH_FEQ_S,
+        name: "FEQ_S",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] == f[rs2]
+            fp_cmp_op::<Single>(cpu, inst, false, |x| x == Ordering::Equal)
+        },
+    },
+    Instruction {
+        mask: MASK_FLT_S,
+        match_data: MATCH_FLT_S,
+        name: "FLT_S",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] < f[rs2]
+            fp_cmp_op::<Single>(cpu, inst, true, |x| x == Ordering::Less)
+        },
+    },
+    Instruction {
+        mask: MASK_FLE_S,
+        match_data: MATCH_FLE_S,
+        name: "FLE_S",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] <= f[rs2]
+            fp_cmp_op::<Single>(cpu, inst, true, |x| x != Ordering::Greater)
+        },
+    },
+    Instruction {
+        mask: MASK_FCLASS_S,
+        match_data: MATCH_FCLASS_S,
+        name: "FCLASS_S",
+        operation: |cpu, inst, pc| {
+            // x[rd] = classifys(f[rs1])
+            fp_class_op::<Single>(cpu, inst)
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64f {
+    use rustc_apfloat::{ieee::Single, Float, Round, Status};
+
+    use super::{fp_class, fp_min_max, fp_sqrt, fp_to_int};
+    use crate::rv64core::test_cpu::{exec, test_cpu};
+
+    fn f32(bits: u32) -> Single {
+        Single::from_bits(bits.into())
+    }
+
+    #[test]
+    fn sqrt_test() {
+        let cases = [
+            (0x40800000, 0x40000000, Status::OK),      // 4.0
+            (0x40000000, 0x3fb504f3, Status::INEXACT), // 2.0
+            (0x00000001, 0x1a3504f3, Status::INEXACT), // min subnormal
+            (0x7f7fffff, 0x5f7fffff, Status::INEXACT), // max normal
+            (0x80000000, 0x80000000, Status::OK),      // -0.0
+            (0xbf800000, 0x7fc00000, Status::INVALID_OP),
+        ];
+        for (x, expect, status) in cases {
+            let ret = fp_sqrt(f32(x), Round::NearestTiesToEven);
+            assert_eq!(ret.value.to_bits() as u32, expect, "sqrt({x:x})");
+            assert_eq!(ret.status, status, "sqrt({x:x})");
+        }
+        // 2.0 rounds up toward positive
+        let ret = fp_sqrt(f32(0x40000000), Round::TowardPositive);
+        assert_eq!(ret.value.to_bits(), 0x3fb504f4);
+    }
+
+    #[test]
+    fn convert_test() {
+        let rtz = Round::TowardZero;
+        // -1.5
+        let ret = fp_to_int(f32(0xbfc00000), rtz, 32, true);
+        assert_eq!((ret.value, ret.status), (u64::MAX, Status::INEXACT));
+        let ret = fp_to_int(f32(0xbfc00000), rtz, 32, false);
+        assert_eq!((ret.value, ret.status), (0, Status::INVALID_OP));
+        // -0.5 to unsigned is only inexact
+        let ret = fp_to_int(f32(0xbf000000), rtz, 64, false);
+        assert_eq!((ret.value, ret.status), (0, Status::INEXACT));
+        // NaN and +inf saturate to the max value
+        let ret = fp_to_int(Single::NAN, rtz, 32, true);
+        assert_eq!((ret.value, ret.status), (0x7fff_ffff, Status::INVALID_OP));
+        let ret = fp_to_int(Single::INFINITY, rtz, 32, false);
+        assert_eq!((ret.value, ret.status), (u64::MAX, Status::INVALID_OP));
+        let ret = fp_to_int(-Single::INFINITY, rtz, 64, true);
+        assert_eq!(ret.value, 1 << 63);
+        // 2.5 ties to even and away
+        let ret = fp_to_int(f32(0x40200000), Round::NearestTiesToEven, 64, true);
+        assert_eq!(ret.value, 2);
+        let ret = fp_to_int(f32(0x40200000), Round::NearestTiesToAway, 64, true);
+        assert_eq!(ret.value, 3);
+    }
+
+    #[test]
+    fn min_max_class_test() {
+        let pos_zero = f32(0);
+        let neg_zero = f32(0x80000000);
+        let snan = f32(0x7f800001);
+        assert!(fp_min_max(pos_zero, neg_zero, false).value.is_neg_zero());
+        assert!(fp_min_max(neg_zero, pos_zero, true).value.is_pos_zero());
+        let ret = fp_min_max(snan, f32(0x3f800000), false);
+        assert_eq!(ret.value.to_bits(), 0x3f800000);
+        assert_eq!(ret.status, Status::INVALID_OP);
+        assert_eq!(fp_min_max(snan, snan, true).value.to_bits(), 0x7fc00000);
+
+        assert_eq!(fp_class(-Single::INFINITY), 1 << 0);
+        assert_eq!(fp_class(f32(0x80000001)), 1 << 2);
+        assert_eq!(fp_class(pos_zero), 1 << 4);
+        assert_eq!(fp_class(f32(0x3f800000)), 1 << 6);
+        assert_eq!(fp_class(snan), 1 << 8);
+        assert_eq!(fp_class(Single::NAN), 1 << 9);
+    }
+
+    #[test]
+    fn zfinx_test() {
+        let (mut cpu, _) = test_cpu("rv64imac_zdinx", 4096);
+
+        // fadd.s a0,a1,a2, the result is sign-extended
+        cpu.gpr.write(11, 0x3fc0_0000);
+        cpu.gpr.write(12, 0xc000_0000);
+        exec(&mut cpu, 0x00c5f553).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0xffff_ffff_bf00_0000);
+        // fcvt.d.s a0,a1 only reads the low 32 bits
+        cpu.gpr.write(11, 0x1234_5678_3fc0_0000);
+        exec(&mut cpu, 0x42058553).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0x3ff8_0000_0000_0000);
+        // fadd.d a0,a1,a2
+        cpu.gpr.write(11, 0x3ff8_0000_0000_0000);
+        cpu.gpr.write(12, 0x4002_0000_0000_0000);
+        exec(&mut cpu, 0x02c5f553).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0x400e_0000_0000_0000);
+        // fadd.s zero,a1,a2 is discarded
+        exec(&mut cpu, 0x00c5f053).unwrap();
+        assert_eq!(cpu.gpr.read(0), 0);
+
+        // flw and fmv.x.w do not exist
+        assert!(cpu.decode.fast_path(0x0005a507).is_none());
+        assert!(cpu.decode.fast_path(0xe0058553).is_none());
+        // fflags is accessible with mstatus.FS off, FS stays off
+        exec(&mut cpu, 0x00185073).unwrap();
+        exec(&mut cpu, 0x00102573).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0b10000);
+        assert_eq!(cpu.csr_regs.xstatus.get().fs(), 0);
+    }
+}
@@ -0,0 +1,387 @@
+use crate::rv64core::{cpu_core::CpuCore, inst::inst_base::*, traptype::TrapType};
+
+// HLV, HLVX and HSV are virtual instructions in VS-mode and VU-mode,
+// and illegal in U-mode unless hstatus.HU=1
+fn check_hlv(cpu: &CpuCore, inst: u32) -> Result<(), TrapType> {
+    if cpu.csr_regs.virt() {
+        return Err(TrapType::VirtualInstruction(inst.into()));
+    }
+    let hu = cpu.csr_regs.h.hstatus.get().hu();
+    if cpu.cur_priv.get() == PrivilegeLevels::User && !hu {
+        return Err(TrapType::IllegalInstruction(inst.into()));
+    }
+    Ok(())
+}
+
+// HFENCE.GVMA is also illegal in HS-mode when mstatus.TVM=1
+fn check_hfence(cpu: &CpuCore, inst: u32, gvma: bool) -> Result<(), TrapType> {
+    if cpu.csr_regs.virt() {
+        return Err(TrapType::VirtualInstruction(inst.into()));
+    }
+    let tvm = cpu.csr_regs.xstatus.get().tvm();
+    match cpu.cur_priv.get() {
+        PrivilegeLevels::User => Err(TrapType::IllegalInstruction(inst.into())),
+        PrivilegeLevels::Supervisor if gvma && tvm => {
+            Err(TrapType::IllegalInstruction(inst.into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn hlv(cpu: &mut CpuCore, inst: u32, len: usize, hlvx: bool) -> Result<u64, TrapType> {
+    check_hlv(cpu, inst)?;
+    let f = parse_format_r(inst);
+    let addr = cpu.gpr.read(f.rs1);
+    cpu.read_virt(addr, len, AccessType::Load(addr), hlvx)
+}
+
+fn hsv(cpu: &mut CpuCore, inst: u32, len: usize) -> Result<(), TrapType> {
+    check_hlv(cpu, inst)?;
+    let f = parse_format_r(inst);
+    let addr = cpu.gpr.read(f.rs1);
+    let data = cpu.gpr.read(f.rs2);
+    cpu.write_virt(addr, data, len, AccessType::Store(addr))?;
+    Ok(())
+}
+
+fn write_rd(cpu: &mut CpuCore, inst: u32, val: u64) {
+    let f = parse_format_r(inst);
+    cpu.gpr.write(f.rd, val);
+}
+
+// H extension: hypervisor load, store and fence instructions
+// the guest translations are not cached in the TLB, so the fences only drop the TLB
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_H: &[Instruction] = &[
+    Instruction {
+        mask: MASK_HFENCE_VVMA,
+        match_data: MATCH_HFENCE_VVMA,
+        name: "HFENCE_VVMA",
+        operation: |cpu, inst, pc| {
+            check_hfence(cpu, inst, false)?;
+            cpu.mmu.clear_tlb();
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HFENCE_GVMA,
+        match_data: MATCH_HFENCE_GVMA,
+        name: "HFENCE_GVMA",
+        operation: |cpu, inst, pc| {
+            check_hfence(cpu, inst, true)?;
+            cpu.mmu.clear_tlb();
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLV_B,
+        match_data: MATCH_HLV_B,
+        name: "HLV_B",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 1, false)?;
+            write_rd(cpu, inst, data as i8 as i64 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLV_BU,
+        match_data: MATCH_HLV_BU,
+        name: "HLV_BU",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 1, false)?;
+            write_rd(cpu, inst, data as u8 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLV_H,
+        match_data: MATCH_HLV_H,
+        name: "HLV_H",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 2, false)?;
+            write_rd(cpu, inst, data as i16 as i64 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLV_HU,
+        match_data: MATCH_HLV_HU,
+        name: "HLV_HU",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 2, false)?;
+            write_rd(cpu, inst, data as u16 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLVX_HU,
+        match_data: MATCH_HLVX_HU,
+        name: "HLVX_HU",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 2, true)?;
+            write_rd(cpu, inst, data as u16 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLV_W,
+        match_data: MATCH_HLV_W,
+        name: "HLV_W",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 4, false)?;
+            write_rd(cpu, inst, data as i32 as i64 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLV_WU,
+        match_data: MATCH_HLV_WU,
+        name: "HLV_WU",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 4, false)?;
+            write_rd(cpu, inst, data as u32 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLVX_WU,
+        match_data: MATCH_HLVX_WU,
+        name: "HLVX_WU",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 4, true)?;
+            write_rd(cpu, inst, data as u32 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HLV_D,
+        match_data: MATCH_HLV_D,
+        name: "HLV_D",
+        operation: |cpu, inst, pc| {
+            let data = hlv(cpu, inst, 8, false)?;
+            write_rd(cpu, inst, data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_HSV_B,
+        match_data: MATCH_HSV_B,
+        name: "HSV_B",
+        operation: |cpu, inst, pc| hsv(cpu, inst, 1),
+    },
+    Instruction {
+        mask: MASK_HSV_H,
+        match_data: MATCH_HSV_H,
+        name: "HSV_H",
+        operation: |cpu, inst, pc| hsv(cpu, inst, 2),
+    },
+    Instruction {
+        mask: MASK_HSV_W,
+        match_data: MATCH_HSV_W,
+        name: "HSV_W",
+        operation: |cpu, inst, pc| hsv(cpu, inst, 4),
+    },
+    Instruction {
+        mask: MASK_HSV_D,
+        match_data: MATCH_HSV_D,
+        name: "HSV_D",
+        operation: |cpu, inst, pc| hsv(cpu, inst, 8),
+    },
+];
+
+#[cfg(test)]
+mod test_rv64h {
+    use crate::{
+        config::Config,
+        device::device_trait::MEM_BASE,
+        rv64core::{
+            bus::Bus,
+            cpu_core::CpuCore,
+            csr_regs_define::{HgatpIn, SatpIn, StapMode},
+            inst::inst_base::*,
+            test_cpu::{exec, test_bus, test_cpu_on},
+            traptype::TrapType,
+        },
+        tools::RcRefCell,
+    };
+
+    const MEM_SIZE: u64 = 0x10000;
+
+    fn build_cpu(isa: &str) -> (CpuCore, RcRefCell<Bus>) {
+        let bus = test_bus(MEM_SIZE);
+        let mut config = Config::new();
+        config.set_isa(isa);
+        config.set_s_mode();
+        config.set_mmu_type("sv39");
+        // no PMP entries, S-mode and U-mode can access all memory
+        config.set_pmp_entries(0);
+        (test_cpu_on(&bus, config), bus)
+    }
+
+    #[test]
+    fn hlv_hsv_test() {
+        let (mut cpu, bus) = build_cpu("rv64imach");
+        // Sv39x4, the root page table at MEM_BASE maps the first 1GiB guest
+        // physical addresses to MEM_BASE with a U superpage
+        let pte = (MEM_BASE >> 12) << 10 | 0xd7;
+        bus.borrow_mut().write(MEM_BASE, pte, 8).unwrap();
+        let hgatp = HgatpIn::new().with_mode(8).with_ppn(MEM_BASE >> 12);
+        cpu.csr_regs.h.hgatp.set(hgatp);
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        bus.borrow_mut()
+            .write(MEM_BASE + 0x8000, 0x8877_6655_4433_22f1, 8)
+            .unwrap();
+
+        // hlv.d a0,(a1) and hlv.b a0,(a1), vsatp is bare
+        cpu.gpr.write(11, 0x8000);
+        exec(&mut cpu, MATCH_HLV_D | 10 << 7 | 11 << 15).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0x8877_6655_4433_22f1);
+        exec(&mut cpu, MATCH_HLV_B | 10 << 7 | 11 << 15).unwrap();
+        assert_eq!(cpu.gpr.read(10), 0xffff_ffff_ffff_fff1);
+        // hsv.w a2,(a1)
+        cpu.gpr.write(12, 0x1234_5678);
+        exec(&mut cpu, MATCH_HSV_W | 12 << 20 | 11 << 15).unwrap();
+        let data = bus.borrow_mut().read(MEM_BASE + 0x8000, 8).unwrap();
+        assert_eq!(data, 0x8877_6655_1234_5678);
+        // hlvx needs the execute permission
+        let ret = exec(&mut cpu, MATCH_HLVX_WU | 10 << 7 | 11 << 15);
+        assert_eq!(ret, Err(TrapType::LoadGuestPageFault(0x8000, 0x8000)));
+
+        // not mapped by the G-stage
+        cpu.gpr.write(11, 0x4000_0000);
+        let ret = exec(&mut cpu, MATCH_HLV_W | 10 << 7 | 11 << 15);
+        let trap = TrapType::LoadGuestPageFault(0x4000_0000, 0x4000_0000);
+        assert_eq!(ret, Err(trap));
+
+        // the guest page fault is taken in HS-mode, htval holds gpa >> 2
+        cpu.csr_regs.h.virt.set(true);
+        cpu.csr_regs.medeleg.set((1 << 21).into());
+        cpu.handle_exceptions(trap);
+        assert!(!cpu.csr_regs.virt());
+        let hstatus = cpu.csr_regs.h.hstatus.get();
+        assert!(hstatus.spv() && hstatus.spvp() && hstatus.gva());
+        assert_eq!(cpu.csr_regs.h.htval.get(), 0x4000_0000 >> 2);
+        assert_eq!(cpu.csr_regs.stval.get(), 0x4000_0000);
+
+        // U-mode needs hstatus.HU, VS-mode raises a virtual instruction exception
+        let inst = MATCH_HLV_D | 10 << 7 | 11 << 15;
+        cpu.cur_priv.set(PrivilegeLevels::User);
+        let ret = exec(&mut cpu, inst);
+        assert_eq!(ret, Err(TrapType::IllegalInstruction(inst.into())));
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        cpu.csr_regs.h.virt.set(true);
+        let ret = exec(&mut cpu, inst);
+        assert_eq!(ret, Err(TrapType::VirtualInstruction(inst.into())));
+        let ret = exec(&mut cpu, MATCH_HFENCE_GVMA);
+        assert_eq!(
+            ret,
+            Err(TrapType::VirtualInstruction(MATCH_HFENCE_GVMA.into()))
+        );
+    }
+
+    #[test]
+    fn vs_csr_test() {
+        let (mut cpu, _bus) = build_cpu("rv64imach");
+        let s = PrivilegeLevels::Supervisor;
+        cpu.cur_priv.set(s);
+
+        // sret with hstatus.SPV=1 enters VS-mode
+        let hstatus = cpu.csr_regs.h.hstatus.get().with_spv(true);
+        cpu.csr_regs.h.hstatus.set(hstatus);
+        cpu.csr_regs.sepc.set(MEM_BASE + 0x100);
+        let mut mstatus = cpu.csr_regs.xstatus.get();
+        mstatus.set_spp(true);
+        cpu.csr_regs.xstatus.set(mstatus);
+        exec(&mut cpu, MATCH_SRET).unwrap();
+        assert!(cpu.csr_regs.virt());
+        assert_eq!(cpu.npc, MEM_BASE + 0x100);
+
+        // sscratch is vsscratch in VS-mode, the hypervisor CSRs are not accessible
+        cpu.csr_regs.write(CSR_SSCRATCH.into(), 0x55, s).unwrap();
+        let ret = cpu.csr_regs.read(CSR_HSTATUS.into(), s);
+        assert_eq!(ret, Err(TrapType::VirtualInstruction(0)));
+        assert_eq!(
+            exec(&mut cpu, MATCH_ECALL),
+            Err(TrapType::EnvironmentCallFromVSMode)
+        );
+
+        cpu.csr_regs.h.virt.set(false);
+        assert_eq!(cpu.csr_regs.read(CSR_VSSCRATCH.into(), s), Ok(0x55));
+        assert_eq!(cpu.csr_regs.read(CSR_SSCRATCH.into(), s), Ok(0));
+        // the VS-level interrupts are always delegated to HS-mode
+        let mideleg = cpu
+            .csr_regs
+            .read(CSR_MIDELEG.into(), PrivilegeLevels::Machine);
+        assert_eq!(mideleg.unwrap() & 0x1444, 0x1444);
+    }
+
+    #[test]
+    fn vs_fs_test() {
+        let (mut cpu, _bus) = build_cpu("rv64imafdch");
+        let s = PrivilegeLevels::Supervisor;
+        cpu.cur_priv.set(s);
+        let mstatus = cpu.csr_regs.xstatus.get().with_fs(1);
+        cpu.csr_regs.xstatus.set(mstatus);
+        assert_eq!(cpu.csr_regs.read(CSR_FRM.into(), s), Ok(0));
+
+        // in VS-mode vsstatus.FS has to be on as well
+        cpu.csr_regs.h.virt.set(true);
+        for csr in [CSR_FFLAGS, CSR_FRM, CSR_FCSR] {
+            let ret = cpu.csr_regs.read(csr.into(), s);
+            assert_eq!(ret, Err(TrapType::IllegalInstruction(0)));
+        }
+        let vsstatus = cpu.csr_regs.h.vsstatus.get().with_fs(1);
+        cpu.csr_regs.h.vsstatus.set(vsstatus);
+        cpu.csr_regs.write(CSR_FRM.into(), 2, s).unwrap();
+        assert_eq!(cpu.csr_regs.read(CSR_FCSR.into(), s), Ok(2 << 5));
+    }
+
+    #[test]
+    fn two_stage_test() {
+        let (mut cpu, bus) = build_cpu("rv64imach");
+        // the G-stage maps the first 1GiB guest physical addresses to MEM_BASE,
+        // the VS-stage root page table at gpa 0x1000 maps the first 1GiB to gpa 0
+        let g_pte = (MEM_BASE >> 12) << 10 | 0xd7;
+        bus.borrow_mut().write(MEM_BASE, g_pte, 8).unwrap();
+        bus.borrow_mut().write(MEM_BASE + 0x1000, 0xc7, 8).unwrap();
+        bus.borrow_mut()
+            .write(MEM_BASE + 0x8000, 0x1122_3344, 8)
+            .unwrap();
+        let hgatp = HgatpIn::new().with_mode(8).with_ppn(MEM_BASE >> 12);
+        cpu.csr_regs.h.hgatp.set(hgatp);
+        let vsatp = SatpIn::new().with_mode(StapMode::Sv39).with_ppn(0x1);
+        cpu.csr_regs.h.vsatp.set(vsatp);
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        cpu.csr_regs.h.virt.set(true);
+
+        let ret = cpu.read(0x8000, 4, AccessType::Load(0x8000));
+        assert_eq!(ret, Ok(0x1122_3344));
+
+        // the VS-stage PTE at gpa 0x4000_0000 is not mapped by the G-stage
+        cpu.csr_regs.h.vsatp.set(vsatp.with_ppn(0x4_0000));
+        let ret = cpu.read(0x8000, 4, AccessType::Load(0x8000));
+        let trap = TrapType::LoadGuestPageFault(0x8000, 0x4000_0000);
+        assert_eq!(ret, Err(trap));
+
+        // htinst holds the pseudoinstruction of the implicit 64-bit read
+        cpu.csr_regs.medeleg.set((1 << 21).into());
+        cpu.handle_exceptions(trap);
+        assert_eq!(cpu.csr_regs.h.htinst.get(), 0x3000);
+        assert_eq!(cpu.csr_regs.h.htval.get(), 0x4000_0000 >> 2);
+        assert_eq!(cpu.csr_regs.stval.get(), 0x8000);
+
+        // the guest page fault of the access itself writes 0, the VS-stage maps
+        // the second 1GiB to gpa 0x4000_0000
+        let vs_pte = (0x4000_0000 >> 12) << 10 | 0xc7;
+        bus.borrow_mut()
+            .write(MEM_BASE + 0x1008, vs_pte, 8)
+            .unwrap();
+        cpu.csr_regs.h.virt.set(true);
+        cpu.csr_regs.h.vsatp.set(vsatp);
+        let ret = cpu.read(0x4000_0000, 4, AccessType::Load(0x4000_0000));
+        let trap = TrapType::LoadGuestPageFault(0x4000_0000, 0x4000_0000);
+        assert_eq!(ret, Err(trap));
+        cpu.handle_exceptions(trap);
+        assert_eq!(cpu.csr_regs.h.htinst.get(), 0);
+    }
+}
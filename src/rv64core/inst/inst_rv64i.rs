@@ -38,7 +38,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
 
             let next_pc = pc.wrapping_add(f.imm);
 
-            if !cpu.config.is_enable_isa(b'c') && !check_aligned(next_pc, 4) {
+            if !cpu.csr_regs.misa_ext(b'c') && !check_aligned(next_pc, 4) {
                 return Err(TrapType::InstructionAddressMisaligned(next_pc));
             };
 
@@ -65,8 +65,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
 
             let next_pc = (rs1_data.wrapping_add(f.imm as u64)) & !1_u64;
 
-            
-            if !cpu.config.is_enable_isa(b'c') && !check_aligned(next_pc, 4) {
+            if !cpu.csr_regs.misa_ext(b'c') && !check_aligned(next_pc, 4) {
                 return Err(TrapType::InstructionAddressMisaligned(next_pc));
             };
 
@@ -97,7 +96,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
 
             if rs1 == rs2 {
                 let next_pc = pc.wrapping_add(f.imm);
-                if !cpu.config.is_enable_isa(b'c') && !check_aligned(next_pc, 4) {
+                if !cpu.csr_regs.misa_ext(b'c') && !check_aligned(next_pc, 4) {
                     return Err(TrapType::InstructionAddressMisaligned(next_pc));
                 }
                 cpu.npc = next_pc;
@@ -118,7 +117,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
             if rs1 != rs2 {
                 let next_pc = pc.wrapping_add(f.imm);
 
-                if !cpu.config.is_enable_isa(b'c') && !check_aligned(next_pc, 4) {
+                if !cpu.csr_regs.misa_ext(b'c') && !check_aligned(next_pc, 4) {
                     return Err(TrapType::InstructionAddressMisaligned(next_pc));
                 }
                 cpu.npc = next_pc;
@@ -139,7 +138,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
             if rs1 < rs2 {
                 let next_pc = pc.wrapping_add(f.imm);
 
-                if !cpu.config.is_enable_isa(b'c') && !check_aligned(next_pc, 4) {
+                if !cpu.csr_regs.misa_ext(b'c') && !check_aligned(next_pc, 4) {
                     return Err(TrapType::InstructionAddressMisaligned(next_pc));
                 }
                 cpu.npc = next_pc;
@@ -160,7 +159,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
             if rs1 >= rs2 {
                 let next_pc = pc.wrapping_add(f.imm);
 
-                if !cpu.config.is_enable_isa(b'c') && !check_aligned(next_pc, 4) {
+                if !cpu.csr_regs.misa_ext(b'c') && !check_aligned(next_pc, 4) {
                     return Err(TrapType::InstructionAddressMisaligned(next_pc));
                 }
                 cpu.npc = next_pc;
@@ -181,7 +180,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
             if rs1 < rs2 {
                 let next_pc = pc.wrapping_add(f.imm);
 
-                if !cpu.config.is_enable_isa(b'c') && !check_aligned(next_pc, 4) {
+                if !cpu.csr_regs.misa_ext(b'c') && !check_aligned(next_pc, 4) {
                     return Err(TrapType::InstructionAddressMisaligned(next_pc));
                 }
                 cpu.npc = next_pc;
@@ -201,7 +200,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
             if rs1 >= rs2 {
                 let next_pc = pc.wrapping_add(f.imm);
 
-                if !cpu.config.is_enable_isa(b'c') && !check_aligned(next_pc, 4) {
+                if !cpu.csr_regs.misa_ext(b'c') && !check_aligned(next_pc, 4) {
                     return Err(TrapType::InstructionAddressMisaligned(next_pc));
                 }
                 cpu.npc = next_pc;
@@ -253,7 +252,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
     Instruction {
         mask: MASK_LW,
         match_data: MATCH_LW,
-        name: "LW ",
+        name: "LW",
         operation: |cpu, inst, pc| {
             // x[rd] = sext(M[x[rs1] + sext(offset)][31:0])
             let f = parse_format_i(inst);
@@ -272,7 +271,7 @@ pub const INSTRUCTIONS_I: &[Instruction] = &[
     Instruction {
         mask: MASK_LBU,
         match_data: MATCH_LBU,
-        name: "LBU ",
+        name: "LBU",
         operation: |cpu, inst, pc| {
             // x[rd] = M[x[rs1] + sext(offset)][7:0]
             let f = parse_format_i(inst);
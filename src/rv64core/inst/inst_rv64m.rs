@@ -62,7 +62,7 @@ pub const INSTRUCTIONS_M: &[Instruction] = &[
     Instruction {
         mask: MASK_MULHU,
         match_data: MATCH_MULHU,
-        name: "MULHSU",
+        name: "MULHU",
         operation: |cpu, inst, pc| {
             //  x[rd] = (x[rs1] u×u x[rs2]) >>u XLEN
             let f = parse_format_r(inst);
@@ -0,0 +1,2211 @@
+use alloc::vec::Vec;
+
+use crate::rv64core::{
+    cpu_core::CpuCore,
+    inst::inst_base::*,
+    traptype::TrapType,
+    vector::{vpr::Vpr, vtype::VtypeIn},
+};
+
+// Zve64x: vector loads and stores, integer arithmetic, mask, permutation
+// and reduction instructions. Segment loads and stores, widening, narrowing
+// and fixed-point rounding instructions are not supported.
+// Masked-off and tail elements are always left undisturbed.
+
+fn illegal(inst: u32) -> TrapType {
+    TrapType::IllegalInstruction(inst.into())
+}
+
+// all vector instructions are illegal when mstatus.VS is off,
+// or vsstatus.VS is off in VS-mode and VU-mode
+pub fn check_vs(cpu: &CpuCore, inst: u32) -> Result<(), TrapType> {
+    let vs_off = cpu.csr_regs.virt() && cpu.csr_regs.h.vsstatus.get().vs() == 0;
+    if cpu.csr_regs.xstatus.get().vs() == 0 || vs_off {
+        Err(illegal(inst))
+    } else {
+        Ok(())
+    }
+}
+
+// the settings of vtype, instructions depending on them are illegal when vill is set
+struct VState {
+    sew: usize,
+    lmul_log2: i32,
+    vl: usize,
+    vstart: usize,
+    vlmax: usize,
+}
+
+fn vstate(cpu: &CpuCore, inst: u32) -> Result<VState, TrapType> {
+    check_vs(cpu, inst)?;
+    let vtype = VtypeIn::from(cpu.csr_regs.vtype.get());
+    if vtype.vill() {
+        return Err(illegal(inst));
+    }
+    Ok(VState {
+        sew: vtype.sew(),
+        lmul_log2: vtype.lmul_log2(),
+        vl: cpu.csr_regs.vl.get() as usize,
+        vstart: cpu.csr_regs.vstart.get() as usize,
+        vlmax: vtype.vlmax(cpu.vpr.vlen()),
+    })
+}
+
+// register groups must be aligned to EMUL
+fn check_group(reg: u64, emul_log2: i32, inst: u32) -> Result<(), TrapType> {
+    if !reg.is_multiple_of(1 << emul_log2.max(0)) {
+        Err(illegal(inst))
+    } else {
+        Ok(())
+    }
+}
+
+fn group_overlap(a: u64, a_log2: i32, b: u64, b_log2: i32) -> bool {
+    let a_end = a + (1 << a_log2.max(0));
+    let b_end = b + (1 << b_log2.max(0));
+    a < b_end && b < a_end
+}
+
+fn active(vpr: &Vpr, vm: bool, idx: usize) -> bool {
+    vm || vpr.read_mask(0, idx)
+}
+
+fn finish(cpu: &mut CpuCore) -> Result<(), TrapType> {
+    cpu.csr_regs.vstart.set(0);
+    Ok(())
+}
+
+pub fn sew_mask(sew: usize) -> u64 {
+    u64::MAX >> (64 - sew)
+}
+
+pub fn sext(val: u64, sew: usize) -> i64 {
+    ((val << (64 - sew)) as i64) >> (64 - sew)
+}
+
+// the first operand of OPIVV/OPIVX/OPIVI/OPMVV/OPMVX: vs1[i], x[rs1] or simm5
+#[derive(Clone, Copy, PartialEq)]
+pub enum VSrc {
+    V,
+    X,
+    I,
+}
+
+fn src1(cpu: &CpuCore, f: &FormatV, src: VSrc, idx: usize, sew: usize) -> u64 {
+    match src {
+        VSrc::V => cpu.vpr.read_elem(f.vs1, idx, sew),
+        VSrc::X => cpu.gpr.read(f.vs1) & sew_mask(sew),
+        VSrc::I => f.simm5() as u64 & sew_mask(sew),
+    }
+}
+
+// scalar operand of slides and gathers, the immediate is unsigned
+fn offset(cpu: &CpuCore, f: &FormatV, src: VSrc) -> usize {
+    match src {
+        VSrc::X => cpu.gpr.read(f.vs1) as usize,
+        _ => f.vs1 as usize,
+    }
+}
+
+// checks of the instructions writing a vector register group
+fn check_arith(f: &FormatV, s: &VState, src: VSrc, inst: u32) -> Result<(), TrapType> {
+    check_group(f.vd, s.lmul_log2, inst)?;
+    check_group(f.vs2, s.lmul_log2, inst)?;
+    if src == VSrc::V {
+        check_group(f.vs1, s.lmul_log2, inst)?;
+    }
+    // the mask register can not be the destination of a masked instruction
+    if !f.vm && f.vd == 0 {
+        return Err(illegal(inst));
+    }
+    Ok(())
+}
+
+// vd[i] = op(vs2[i], src1)
+pub fn vint_op(
+    cpu: &mut CpuCore,
+    inst: u32,
+    src: VSrc,
+    op: impl Fn(u64, u64, usize) -> u64,
+) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, src, inst)?;
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let a = cpu.vpr.read_elem(f.vs2, i, s.sew);
+            let b = src1(cpu, &f, src, i, s.sew);
+            cpu.vpr
+                .write_elem(f.vd, i, s.sew, op(a, b, s.sew) & sew_mask(s.sew));
+        }
+    }
+    finish(cpu)
+}
+
+// saturating add and sub, op returns the result and whether it is saturated
+pub fn vsat_op(
+    cpu: &mut CpuCore,
+    inst: u32,
+    src: VSrc,
+    op: impl Fn(u64, u64, usize) -> (u64, bool),
+) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, src, inst)?;
+    let mut sat = false;
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let a = cpu.vpr.read_elem(f.vs2, i, s.sew);
+            let b = src1(cpu, &f, src, i, s.sew);
+            let (ret, x) = op(a, b, s.sew);
+            sat |= x;
+            cpu.vpr.write_elem(f.vd, i, s.sew, ret & sew_mask(s.sew));
+        }
+    }
+    if sat {
+        // vxsat
+        let vcsr = cpu.csr_regs.vcsr.get();
+        cpu.csr_regs.vcsr.set(vcsr | 1);
+    }
+    finish(cpu)
+}
+
+pub fn sat_add(a: u64, b: u64, sew: usize, signed: bool) -> (u64, bool) {
+    let (a, b, min, max) = match signed {
+        true => (
+            sext(a, sew) as i128,
+            sext(b, sew) as i128,
+            -(1i128 << (sew - 1)),
+            (1i128 << (sew - 1)) - 1,
+        ),
+        false => (a as i128, b as i128, 0, sew_mask(sew) as i128),
+    };
+    let ret = a + b;
+    (ret.clamp(min, max) as u64, ret < min || ret > max)
+}
+
+pub fn sat_sub(a: u64, b: u64, sew: usize, signed: bool) -> (u64, bool) {
+    let (a, b, min, max) = match signed {
+        true => (
+            sext(a, sew) as i128,
+            sext(b, sew) as i128,
+            -(1i128 << (sew - 1)),
+            (1i128 << (sew - 1)) - 1,
+        ),
+        false => (a as i128, b as i128, 0, sew_mask(sew) as i128),
+    };
+    let ret = a - b;
+    (ret.clamp(min, max) as u64, ret < min || ret > max)
+}
+
+// vd[i] = op(vs2[i], src1, vd[i])
+pub fn vmac_op(
+    cpu: &mut CpuCore,
+    inst: u32,
+    src: VSrc,
+    op: impl Fn(u64, u64, u64) -> u64,
+) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, src, inst)?;
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let a = cpu.vpr.read_elem(f.vs2, i, s.sew);
+            let b = src1(cpu, &f, src, i, s.sew);
+            let d = cpu.vpr.read_elem(f.vd, i, s.sew);
+            cpu.vpr
+                .write_elem(f.vd, i, s.sew, op(a, b, d) & sew_mask(s.sew));
+        }
+    }
+    finish(cpu)
+}
+
+// vd.mask[i] = op(vs2[i], src1)
+pub fn vcmp_op(
+    cpu: &mut CpuCore,
+    inst: u32,
+    src: VSrc,
+    op: impl Fn(u64, u64, usize) -> bool,
+) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_group(f.vs2, s.lmul_log2, inst)?;
+    if src == VSrc::V {
+        check_group(f.vs1, s.lmul_log2, inst)?;
+    }
+    // vd may overlap the sources, compute all the results first
+    let mut ret = Vec::new();
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let a = cpu.vpr.read_elem(f.vs2, i, s.sew);
+            let b = src1(cpu, &f, src, i, s.sew);
+            ret.push((i, op(a, b, s.sew)));
+        }
+    }
+    for (i, x) in ret {
+        cpu.vpr.write_mask(f.vd, i, x);
+    }
+    finish(cpu)
+}
+
+// vmerge and vmv.v, vd[i] = v0.mask[i] ? src1 : vs2[i]
+pub fn vmerge_op(cpu: &mut CpuCore, inst: u32, src: VSrc) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, src, inst)?;
+    for i in s.vstart..s.vl {
+        let data = if active(&cpu.vpr, f.vm, i) {
+            src1(cpu, &f, src, i, s.sew)
+        } else {
+            cpu.vpr.read_elem(f.vs2, i, s.sew)
+        };
+        cpu.vpr.write_elem(f.vd, i, s.sew, data);
+    }
+    finish(cpu)
+}
+
+// vd[i] = vs2[src1]
+pub fn vgather_op(cpu: &mut CpuCore, inst: u32, src: VSrc) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, src, inst)?;
+    if group_overlap(f.vd, s.lmul_log2, f.vs2, s.lmul_log2)
+        || (src == VSrc::V && group_overlap(f.vd, s.lmul_log2, f.vs1, s.lmul_log2))
+    {
+        return Err(illegal(inst));
+    }
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let idx = match src {
+                VSrc::V => cpu.vpr.read_elem(f.vs1, i, s.sew) as usize,
+                _ => offset(cpu, &f, src),
+            };
+            let data = match idx < s.vlmax {
+                true => cpu.vpr.read_elem(f.vs2, idx, s.sew),
+                false => 0,
+            };
+            cpu.vpr.write_elem(f.vd, i, s.sew, data);
+        }
+    }
+    finish(cpu)
+}
+
+pub fn vslideup_op(cpu: &mut CpuCore, inst: u32, src: VSrc) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, src, inst)?;
+    if group_overlap(f.vd, s.lmul_log2, f.vs2, s.lmul_log2) {
+        return Err(illegal(inst));
+    }
+    let offset = offset(cpu, &f, src);
+    // vd[i] = vs2[i - offset], the elements below offset are unchanged
+    for i in s.vstart.max(offset)..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let data = cpu.vpr.read_elem(f.vs2, i - offset, s.sew);
+            cpu.vpr.write_elem(f.vd, i, s.sew, data);
+        }
+    }
+    finish(cpu)
+}
+
+pub fn vslidedown_op(cpu: &mut CpuCore, inst: u32, src: VSrc) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, src, inst)?;
+    let offset = offset(cpu, &f, src);
+    // vd[i] = vs2[i + offset], the elements past vlmax are zero
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let data = match i.checked_add(offset) {
+                Some(idx) if idx < s.vlmax => cpu.vpr.read_elem(f.vs2, idx, s.sew),
+                _ => 0,
+            };
+            cpu.vpr.write_elem(f.vd, i, s.sew, data);
+        }
+    }
+    finish(cpu)
+}
+
+// vslide1up and vslide1down, x[rs1] is inserted at the first or the last element
+pub fn vslide1_op(cpu: &mut CpuCore, inst: u32, up: bool) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, VSrc::X, inst)?;
+    if up && group_overlap(f.vd, s.lmul_log2, f.vs2, s.lmul_log2) {
+        return Err(illegal(inst));
+    }
+    let x = cpu.gpr.read(f.vs1) & sew_mask(s.sew);
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let data = match up {
+                true if i == 0 => x,
+                true => cpu.vpr.read_elem(f.vs2, i - 1, s.sew),
+                false if i == s.vl - 1 => x,
+                false => cpu.vpr.read_elem(f.vs2, i + 1, s.sew),
+            };
+            cpu.vpr.write_elem(f.vd, i, s.sew, data);
+        }
+    }
+    finish(cpu)
+}
+
+// vd[0] = op(... op(vs1[0], vs2[0]) ..., vs2[vl - 1])
+pub fn vred_op(
+    cpu: &mut CpuCore,
+    inst: u32,
+    op: impl Fn(u64, u64, usize) -> u64,
+) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_group(f.vs2, s.lmul_log2, inst)?;
+    if s.vstart != 0 {
+        return Err(illegal(inst));
+    }
+    if s.vl == 0 {
+        return finish(cpu);
+    }
+    let mut acc = cpu.vpr.read_elem(f.vs1, 0, s.sew);
+    for i in 0..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            acc = op(acc, cpu.vpr.read_elem(f.vs2, i, s.sew), s.sew) & sew_mask(s.sew);
+        }
+    }
+    cpu.vpr.write_elem(f.vd, 0, s.sew, acc);
+    finish(cpu)
+}
+
+// vd.mask[i] = op(vs2.mask[i], vs1.mask[i])
+pub fn vmask_op(
+    cpu: &mut CpuCore,
+    inst: u32,
+    op: impl Fn(bool, bool) -> bool,
+) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    for i in s.vstart..s.vl {
+        let a = cpu.vpr.read_mask(f.vs2, i);
+        let b = cpu.vpr.read_mask(f.vs1, i);
+        cpu.vpr.write_mask(f.vd, i, op(a, b));
+    }
+    finish(cpu)
+}
+
+// the set bits of the active elements of vs2.mask
+fn mask_bits(vpr: &Vpr, vm: bool, vs2: u64, vl: usize) -> impl Iterator<Item = usize> + '_ {
+    (0..vl).filter(move |i| active(vpr, vm, *i) && vpr.read_mask(vs2, *i))
+}
+
+pub fn vcpop_op(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    if s.vstart != 0 {
+        return Err(illegal(inst));
+    }
+    let count = mask_bits(&cpu.vpr, f.vm, f.vs2, s.vl).count();
+    cpu.gpr.write(f.vd, count as u64);
+    finish(cpu)
+}
+
+pub fn vfirst_op(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    if s.vstart != 0 {
+        return Err(illegal(inst));
+    }
+    let first = mask_bits(&cpu.vpr, f.vm, f.vs2, s.vl).next();
+    cpu.gpr.write(f.vd, first.map_or(u64::MAX, |x| x as u64));
+    finish(cpu)
+}
+
+// vmsbf, vmsif and vmsof: set before, including and only the first set bit
+pub fn vmsbf_op(cpu: &mut CpuCore, inst: u32, name: &str) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    if s.vstart != 0 || f.vd == f.vs2 || (!f.vm && f.vd == 0) {
+        return Err(illegal(inst));
+    }
+    let mut found = false;
+    for i in 0..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let bit = cpu.vpr.read_mask(f.vs2, i);
+            let ret = match name {
+                "vmsbf" => !found && !bit,
+                "vmsif" => !found,
+                _ => !found && bit,
+            };
+            found |= bit;
+            cpu.vpr.write_mask(f.vd, i, ret);
+        }
+    }
+    finish(cpu)
+}
+
+// vd[i] = number of the set bits of vs2.mask below i
+pub fn viota_op(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_group(f.vd, s.lmul_log2, inst)?;
+    if s.vstart != 0
+        || group_overlap(f.vd, s.lmul_log2, f.vs2, 0)
+        || (!f.vm && group_overlap(f.vd, s.lmul_log2, 0, 0))
+    {
+        return Err(illegal(inst));
+    }
+    let mut count = 0;
+    for i in 0..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            cpu.vpr.write_elem(f.vd, i, s.sew, count & sew_mask(s.sew));
+            count += cpu.vpr.read_mask(f.vs2, i) as u64;
+        }
+    }
+    finish(cpu)
+}
+
+pub fn vid_op(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_arith(&f, &s, VSrc::I, inst)?;
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            cpu.vpr
+                .write_elem(f.vd, i, s.sew, i as u64 & sew_mask(s.sew));
+        }
+    }
+    finish(cpu)
+}
+
+// vzext.vf<n> and vsext.vf<n>, the source EEW is SEW / n
+pub fn vext_op(cpu: &mut CpuCore, inst: u32, n: usize, signed: bool) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    let eew = s.sew / n;
+    let emul_log2 = s.lmul_log2 - n.trailing_zeros() as i32;
+    if eew < 8 || emul_log2 < -3 {
+        return Err(illegal(inst));
+    }
+    check_arith(&f, &s, VSrc::I, inst)?;
+    check_group(f.vs2, emul_log2, inst)?;
+    if group_overlap(f.vd, s.lmul_log2, f.vs2, emul_log2) {
+        return Err(illegal(inst));
+    }
+    for i in s.vstart..s.vl {
+        if active(&cpu.vpr, f.vm, i) {
+            let data = cpu.vpr.read_elem(f.vs2, i, eew);
+            let data = match signed {
+                true => sext(data, eew) as u64 & sew_mask(s.sew),
+                false => data,
+            };
+            cpu.vpr.write_elem(f.vd, i, s.sew, data);
+        }
+    }
+    finish(cpu)
+}
+
+// pack the elements of vs2 selected by vs1.mask into vd
+pub fn vcompress_op(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    check_group(f.vd, s.lmul_log2, inst)?;
+    check_group(f.vs2, s.lmul_log2, inst)?;
+    if s.vstart != 0
+        || group_overlap(f.vd, s.lmul_log2, f.vs2, s.lmul_log2)
+        || group_overlap(f.vd, s.lmul_log2, f.vs1, 0)
+    {
+        return Err(illegal(inst));
+    }
+    let mut j = 0;
+    for i in 0..s.vl {
+        if cpu.vpr.read_mask(f.vs1, i) {
+            let data = cpu.vpr.read_elem(f.vs2, i, s.sew);
+            cpu.vpr.write_elem(f.vd, j, s.sew, data);
+            j += 1;
+        }
+    }
+    finish(cpu)
+}
+
+// vmv<nr>r.v ignores vtype and vl
+pub fn vmvnr_op(cpu: &mut CpuCore, inst: u32, nr: usize) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    check_vs(cpu, inst)?;
+    let nr_log2 = nr.trailing_zeros() as i32;
+    check_group(f.vd, nr_log2, inst)?;
+    check_group(f.vs2, nr_log2, inst)?;
+    if f.vd != f.vs2 {
+        cpu.vpr.copy_regs(f.vd, f.vs2, nr);
+    }
+    finish(cpu)
+}
+
+// vmv.x.s, x[rd] = sext(vs2[0])
+pub fn vmv_x_s_op(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    let data = cpu.vpr.read_elem(f.vs2, 0, s.sew);
+    cpu.gpr.write(f.vd, sext(data, s.sew) as u64);
+    finish(cpu)
+}
+
+// vmv.s.x, vd[0] = x[rs1]
+pub fn vmv_s_x_op(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    let s = vstate(cpu, inst)?;
+    if s.vstart < s.vl {
+        let data = cpu.gpr.read(f.vs1) & sew_mask(s.sew);
+        cpu.vpr.write_elem(f.vd, 0, s.sew, data);
+    }
+    finish(cpu)
+}
+
+// avl: None keeps the current vl
+pub fn vset_op(
+    cpu: &mut CpuCore,
+    inst: u32,
+    rd: u64,
+    avl: Option<u64>,
+    vtype: u64,
+) -> Result<(), TrapType> {
+    check_vs(cpu, inst)?;
+    let vtype = VtypeIn::from_vset(vtype);
+    let vlmax = match vtype.vill() {
+        true => 0,
+        false => vtype.vlmax(cpu.vpr.vlen()) as u64,
+    };
+    let vl = avl.unwrap_or(cpu.csr_regs.vl.get()).min(vlmax);
+    cpu.csr_regs.vtype.set(vtype.into());
+    cpu.csr_regs.vl.set(vl);
+    cpu.vpr.set_dirty();
+    cpu.gpr.write(rd, vl);
+    finish(cpu)
+}
+
+// AVL of vsetvli and vsetvl
+fn avl(cpu: &CpuCore, inst: u32) -> Option<u64> {
+    let f = parse_format_v(inst);
+    match (f.vs1, f.vd) {
+        (0, 0) => None,
+        (0, _) => Some(u64::MAX),
+        (rs1, _) => Some(cpu.gpr.read(rs1)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VMem {
+    Unit,
+    FaultFirst,
+    Stride,
+    Index,
+    // vlm.v and vsm.v
+    Mask,
+    // whole register loads and stores
+    Whole(usize),
+}
+
+// eew is the width of the data, or the width of the indices for indexed accesses
+pub fn vmem_op(
+    cpu: &mut CpuCore,
+    inst: u32,
+    mode: VMem,
+    eew: usize,
+    store: bool,
+) -> Result<(), TrapType> {
+    let f = parse_format_v(inst);
+    check_vs(cpu, inst)?;
+    // segment loads and stores are not supported
+    if f.nf != 0 && !matches!(mode, VMem::Whole(_)) {
+        return Err(illegal(inst));
+    }
+    // (data eew, data emul, number of elements)
+    let (data_eew, emul_log2, evl) = match mode {
+        VMem::Whole(nr) => (
+            eew,
+            nr.trailing_zeros() as i32,
+            nr * cpu.vpr.vlenb() / (eew / 8),
+        ),
+        VMem::Mask => (8, 0, vstate(cpu, inst)?.vl.div_ceil(8)),
+        VMem::Index => {
+            let s = vstate(cpu, inst)?;
+            let index_emul_log2 =
+                eew.trailing_zeros() as i32 - s.sew.trailing_zeros() as i32 + s.lmul_log2;
+            if !(-3..=3).contains(&index_emul_log2) {
+                return Err(illegal(inst));
+            }
+            check_group(f.vs2, index_emul_log2, inst)?;
+            (s.sew, s.lmul_log2, s.vl)
+        }
+        _ => {
+            let s = vstate(cpu, inst)?;
+            let emul_log2 =
+                eew.trailing_zeros() as i32 - s.sew.trailing_zeros() as i32 + s.lmul_log2;
+            if !(-3..=3).contains(&emul_log2) {
+                return Err(illegal(inst));
+            }
+            (eew, emul_log2, s.vl)
+        }
+    };
+    check_group(f.vd, emul_log2, inst)?;
+    if !store && !f.vm && f.vd == 0 {
+        return Err(illegal(inst));
+    }
+
+    let base = cpu.gpr.read(f.vs1);
+    let len = data_eew / 8;
+    let vstart = cpu.csr_regs.vstart.get() as usize;
+    for i in vstart..evl {
+        if !active(&cpu.vpr, f.vm, i) {
+            continue;
+        }
+        let addr = match mode {
+            VMem::Stride => base.wrapping_add(cpu.gpr.read(f.vs2).wrapping_mul(i as u64)),
+            VMem::Index => base.wrapping_add(cpu.vpr.read_elem(f.vs2, i, eew)),
+            _ => base.wrapping_add((i * len) as u64),
+        };
+        let ret = if store {
+            let data = cpu.vpr.read_elem(f.vd, i, data_eew);
+            cpu.write(addr, data, len, AccessType::Store(addr))
+                .map(|_| ())
+        } else {
+            cpu.read(addr, len, AccessType::Load(addr))
+                .map(|data| cpu.vpr.write_elem(f.vd, i, data_eew, data))
+        };
+        if let Err(trap) = ret {
+            // fault-only-first loads only trap on the first element, vl is reduced otherwise
+            if mode == VMem::FaultFirst && i > 0 {
+                cpu.csr_regs.vl.set(i as u64);
+                break;
+            }
+            cpu.csr_regs.vstart.set(i as u64);
+            return Err(trap);
+        }
+    }
+    finish(cpu)
+}
+
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_V: &[Instruction] = &[
+    Instruction {
+        mask: MASK_VADD_VV,
+        match_data: MATCH_VADD_VV,
+        name: "VADD_VV",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::V, |a, b, _| a.wrapping_add(b)),
+    },
+    Instruction {
+        mask: MASK_VADD_VX,
+        match_data: MATCH_VADD_VX,
+        name: "VADD_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| a.wrapping_add(b)),
+    },
+    Instruction {
+        mask: MASK_VADD_VI,
+        match_data: MATCH_VADD_VI,
+        name: "VADD_VI",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::I, |a, b, _| a.wrapping_add(b)),
+    },
+    Instruction {
+        mask: MASK_VSUB_VV,
+        match_data: MATCH_VSUB_VV,
+        name: "VSUB_VV",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::V, |a, b, _| a.wrapping_sub(b)),
+    },
+    Instruction {
+        mask: MASK_VSUB_VX,
+        match_data: MATCH_VSUB_VX,
+        name: "VSUB_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| a.wrapping_sub(b)),
+    },
+    Instruction {
+        mask: MASK_VRSUB_VX,
+        match_data: MATCH_VRSUB_VX,
+        name: "VRSUB_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| b.wrapping_sub(a)),
+    },
+    Instruction {
+        mask: MASK_VRSUB_VI,
+        match_data: MATCH_VRSUB_VI,
+        name: "VRSUB_VI",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::I, |a, b, _| b.wrapping_sub(a)),
+    },
+    Instruction {
+        mask: MASK_VMINU_VV,
+        match_data: MATCH_VMINU_VV,
+        name: "VMINU_VV",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::V, |a, b, _| a.min(b)),
+    },
+    Instruction {
+        mask: MASK_VMINU_VX,
+        match_data: MATCH_VMINU_VX,
+        name: "VMINU_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| a.min(b)),
+    },
+    Instruction {
+        mask: MASK_VMIN_VV,
+        match_data: MATCH_VMIN_VV,
+        name: "VMIN_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| {
+                match sext(a, sew) < sext(b, sew) {
+                    true => a,
+                    false => b,
+                }
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMIN_VX,
+        match_data: MATCH_VMIN_VX,
+        name: "VMIN_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| {
+                match sext(a, sew) < sext(b, sew) {
+                    true => a,
+                    false => b,
+                }
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMAXU_VV,
+        match_data: MATCH_VMAXU_VV,
+        name: "VMAXU_VV",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::V, |a, b, _| a.max(b)),
+    },
+    Instruction {
+        mask: MASK_VMAXU_VX,
+        match_data: MATCH_VMAXU_VX,
+        name: "VMAXU_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| a.max(b)),
+    },
+    Instruction {
+        mask: MASK_VMAX_VV,
+        match_data: MATCH_VMAX_VV,
+        name: "VMAX_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| {
+                match sext(a, sew) > sext(b, sew) {
+                    true => a,
+                    false => b,
+                }
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMAX_VX,
+        match_data: MATCH_VMAX_VX,
+        name: "VMAX_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| {
+                match sext(a, sew) > sext(b, sew) {
+                    true => a,
+                    false => b,
+                }
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VAND_VV,
+        match_data: MATCH_VAND_VV,
+        name: "VAND_VV",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::V, |a, b, _| a & b),
+    },
+    Instruction {
+        mask: MASK_VAND_VX,
+        match_data: MATCH_VAND_VX,
+        name: "VAND_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| a & b),
+    },
+    Instruction {
+        mask: MASK_VAND_VI,
+        match_data: MATCH_VAND_VI,
+        name: "VAND_VI",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::I, |a, b, _| a & b),
+    },
+    Instruction {
+        mask: MASK_VOR_VV,
+        match_data: MATCH_VOR_VV,
+        name: "VOR_VV",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::V, |a, b, _| a | b),
+    },
+    Instruction {
+        mask: MASK_VOR_VX,
+        match_data: MATCH_VOR_VX,
+        name: "VOR_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| a | b),
+    },
+    Instruction {
+        mask: MASK_VOR_VI,
+        match_data: MATCH_VOR_VI,
+        name: "VOR_VI",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::I, |a, b, _| a | b),
+    },
+    Instruction {
+        mask: MASK_VXOR_VV,
+        match_data: MATCH_VXOR_VV,
+        name: "VXOR_VV",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::V, |a, b, _| a ^ b),
+    },
+    Instruction {
+        mask: MASK_VXOR_VX,
+        match_data: MATCH_VXOR_VX,
+        name: "VXOR_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| a ^ b),
+    },
+    Instruction {
+        mask: MASK_VXOR_VI,
+        match_data: MATCH_VXOR_VI,
+        name: "VXOR_VI",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::I, |a, b, _| a ^ b),
+    },
+    Instruction {
+        mask: MASK_VSLL_VV,
+        match_data: MATCH_VSLL_VV,
+        name: "VSLL_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| {
+                a << (b as usize & (sew - 1))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VSLL_VX,
+        match_data: MATCH_VSLL_VX,
+        name: "VSLL_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| {
+                a << (b as usize & (sew - 1))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VSLL_VI,
+        match_data: MATCH_VSLL_VI,
+        name: "VSLL_VI",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::I, |a, b, sew| {
+                a << (b as usize & (sew - 1))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VSRL_VV,
+        match_data: MATCH_VSRL_VV,
+        name: "VSRL_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| {
+                a >> (b as usize & (sew - 1))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VSRL_VX,
+        match_data: MATCH_VSRL_VX,
+        name: "VSRL_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| {
+                a >> (b as usize & (sew - 1))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VSRL_VI,
+        match_data: MATCH_VSRL_VI,
+        name: "VSRL_VI",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::I, |a, b, sew| {
+                a >> (b as usize & (sew - 1))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VSRA_VV,
+        match_data: MATCH_VSRA_VV,
+        name: "VSRA_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| {
+                (sext(a, sew) >> (b as usize & (sew - 1))) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VSRA_VX,
+        match_data: MATCH_VSRA_VX,
+        name: "VSRA_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| {
+                (sext(a, sew) >> (b as usize & (sew - 1))) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VSRA_VI,
+        match_data: MATCH_VSRA_VI,
+        name: "VSRA_VI",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::I, |a, b, sew| {
+                (sext(a, sew) >> (b as usize & (sew - 1))) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMSEQ_VV,
+        match_data: MATCH_VMSEQ_VV,
+        name: "VMSEQ_VV",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::V, |a, b, _| a == b),
+    },
+    Instruction {
+        mask: MASK_VMSEQ_VX,
+        match_data: MATCH_VMSEQ_VX,
+        name: "VMSEQ_VX",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::X, |a, b, _| a == b),
+    },
+    Instruction {
+        mask: MASK_VMSEQ_VI,
+        match_data: MATCH_VMSEQ_VI,
+        name: "VMSEQ_VI",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::I, |a, b, _| a == b),
+    },
+    Instruction {
+        mask: MASK_VMSNE_VV,
+        match_data: MATCH_VMSNE_VV,
+        name: "VMSNE_VV",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::V, |a, b, _| a != b),
+    },
+    Instruction {
+        mask: MASK_VMSNE_VX,
+        match_data: MATCH_VMSNE_VX,
+        name: "VMSNE_VX",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::X, |a, b, _| a != b),
+    },
+    Instruction {
+        mask: MASK_VMSNE_VI,
+        match_data: MATCH_VMSNE_VI,
+        name: "VMSNE_VI",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::I, |a, b, _| a != b),
+    },
+    Instruction {
+        mask: MASK_VMSLEU_VV,
+        match_data: MATCH_VMSLEU_VV,
+        name: "VMSLEU_VV",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::V, |a, b, _| a <= b),
+    },
+    Instruction {
+        mask: MASK_VMSLEU_VX,
+        match_data: MATCH_VMSLEU_VX,
+        name: "VMSLEU_VX",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::X, |a, b, _| a <= b),
+    },
+    Instruction {
+        mask: MASK_VMSLEU_VI,
+        match_data: MATCH_VMSLEU_VI,
+        name: "VMSLEU_VI",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::I, |a, b, _| a <= b),
+    },
+    Instruction {
+        mask: MASK_VMSLE_VV,
+        match_data: MATCH_VMSLE_VV,
+        name: "VMSLE_VV",
+        operation: |cpu, inst, pc| {
+            vcmp_op(cpu, inst, VSrc::V, |a, b, sew| sext(a, sew) <= sext(b, sew))
+        },
+    },
+    Instruction {
+        mask: MASK_VMSLE_VX,
+        match_data: MATCH_VMSLE_VX,
+        name: "VMSLE_VX",
+        operation: |cpu, inst, pc| {
+            vcmp_op(cpu, inst, VSrc::X, |a, b, sew| sext(a, sew) <= sext(b, sew))
+        },
+    },
+    Instruction {
+        mask: MASK_VMSLE_VI,
+        match_data: MATCH_VMSLE_VI,
+        name: "VMSLE_VI",
+        operation: |cpu, inst, pc| {
+            vcmp_op(cpu, inst, VSrc::I, |a, b, sew| sext(a, sew) <= sext(b, sew))
+        },
+    },
+    Instruction {
+        mask: MASK_VMSLTU_VV,
+        match_data: MATCH_VMSLTU_VV,
+        name: "VMSLTU_VV",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::V, |a, b, _| a < b),
+    },
+    Instruction {
+        mask: MASK_VMSLTU_VX,
+        match_data: MATCH_VMSLTU_VX,
+        name: "VMSLTU_VX",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::X, |a, b, _| a < b),
+    },
+    Instruction {
+        mask: MASK_VMSLT_VV,
+        match_data: MATCH_VMSLT_VV,
+        name: "VMSLT_VV",
+        operation: |cpu, inst, pc| {
+            vcmp_op(cpu, inst, VSrc::V, |a, b, sew| sext(a, sew) < sext(b, sew))
+        },
+    },
+    Instruction {
+        mask: MASK_VMSLT_VX,
+        match_data: MATCH_VMSLT_VX,
+        name: "VMSLT_VX",
+        operation: |cpu, inst, pc| {
+            vcmp_op(cpu, inst, VSrc::X, |a, b, sew| sext(a, sew) < sext(b, sew))
+        },
+    },
+    Instruction {
+        mask: MASK_VMSGTU_VX,
+        match_data: MATCH_VMSGTU_VX,
+        name: "VMSGTU_VX",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::X, |a, b, _| a > b),
+    },
+    Instruction {
+        mask: MASK_VMSGTU_VI,
+        match_data: MATCH_VMSGTU_VI,
+        name: "VMSGTU_VI",
+        operation: |cpu, inst, pc| vcmp_op(cpu, inst, VSrc::I, |a, b, _| a > b),
+    },
+    Instruction {
+        mask: MASK_VMSGT_VX,
+        match_data: MATCH_VMSGT_VX,
+        name: "VMSGT_VX",
+        operation: |cpu, inst, pc| {
+            vcmp_op(cpu, inst, VSrc::X, |a, b, sew| sext(a, sew) > sext(b, sew))
+        },
+    },
+    Instruction {
+        mask: MASK_VMSGT_VI,
+        match_data: MATCH_VMSGT_VI,
+        name: "VMSGT_VI",
+        operation: |cpu, inst, pc| {
+            vcmp_op(cpu, inst, VSrc::I, |a, b, sew| sext(a, sew) > sext(b, sew))
+        },
+    },
+    Instruction {
+        mask: MASK_VSADDU_VV,
+        match_data: MATCH_VSADDU_VV,
+        name: "VSADDU_VV",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::V, |a, b, sew| sat_add(a, b, sew, false))
+        },
+    },
+    Instruction {
+        mask: MASK_VSADDU_VX,
+        match_data: MATCH_VSADDU_VX,
+        name: "VSADDU_VX",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::X, |a, b, sew| sat_add(a, b, sew, false))
+        },
+    },
+    Instruction {
+        mask: MASK_VSADDU_VI,
+        match_data: MATCH_VSADDU_VI,
+        name: "VSADDU_VI",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::I, |a, b, sew| sat_add(a, b, sew, false))
+        },
+    },
+    Instruction {
+        mask: MASK_VSADD_VV,
+        match_data: MATCH_VSADD_VV,
+        name: "VSADD_VV",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::V, |a, b, sew| sat_add(a, b, sew, true))
+        },
+    },
+    Instruction {
+        mask: MASK_VSADD_VX,
+        match_data: MATCH_VSADD_VX,
+        name: "VSADD_VX",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::X, |a, b, sew| sat_add(a, b, sew, true))
+        },
+    },
+    Instruction {
+        mask: MASK_VSADD_VI,
+        match_data: MATCH_VSADD_VI,
+        name: "VSADD_VI",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::I, |a, b, sew| sat_add(a, b, sew, true))
+        },
+    },
+    Instruction {
+        mask: MASK_VSSUBU_VV,
+        match_data: MATCH_VSSUBU_VV,
+        name: "VSSUBU_VV",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::V, |a, b, sew| sat_sub(a, b, sew, false))
+        },
+    },
+    Instruction {
+        mask: MASK_VSSUBU_VX,
+        match_data: MATCH_VSSUBU_VX,
+        name: "VSSUBU_VX",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::X, |a, b, sew| sat_sub(a, b, sew, false))
+        },
+    },
+    Instruction {
+        mask: MASK_VSSUB_VV,
+        match_data: MATCH_VSSUB_VV,
+        name: "VSSUB_VV",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::V, |a, b, sew| sat_sub(a, b, sew, true))
+        },
+    },
+    Instruction {
+        mask: MASK_VSSUB_VX,
+        match_data: MATCH_VSSUB_VX,
+        name: "VSSUB_VX",
+        operation: |cpu, inst, pc| {
+            vsat_op(cpu, inst, VSrc::X, |a, b, sew| sat_sub(a, b, sew, true))
+        },
+    },
+    Instruction {
+        mask: MASK_VRGATHER_VV,
+        match_data: MATCH_VRGATHER_VV,
+        name: "VRGATHER_VV",
+        operation: |cpu, inst, pc| vgather_op(cpu, inst, VSrc::V),
+    },
+    Instruction {
+        mask: MASK_VRGATHER_VX,
+        match_data: MATCH_VRGATHER_VX,
+        name: "VRGATHER_VX",
+        operation: |cpu, inst, pc| vgather_op(cpu, inst, VSrc::X),
+    },
+    Instruction {
+        mask: MASK_VRGATHER_VI,
+        match_data: MATCH_VRGATHER_VI,
+        name: "VRGATHER_VI",
+        operation: |cpu, inst, pc| vgather_op(cpu, inst, VSrc::I),
+    },
+    Instruction {
+        mask: MASK_VSLIDEUP_VX,
+        match_data: MATCH_VSLIDEUP_VX,
+        name: "VSLIDEUP_VX",
+        operation: |cpu, inst, pc| vslideup_op(cpu, inst, VSrc::X),
+    },
+    Instruction {
+        mask: MASK_VSLIDEUP_VI,
+        match_data: MATCH_VSLIDEUP_VI,
+        name: "VSLIDEUP_VI",
+        operation: |cpu, inst, pc| vslideup_op(cpu, inst, VSrc::I),
+    },
+    Instruction {
+        mask: MASK_VSLIDEDOWN_VX,
+        match_data: MATCH_VSLIDEDOWN_VX,
+        name: "VSLIDEDOWN_VX",
+        operation: |cpu, inst, pc| vslidedown_op(cpu, inst, VSrc::X),
+    },
+    Instruction {
+        mask: MASK_VSLIDEDOWN_VI,
+        match_data: MATCH_VSLIDEDOWN_VI,
+        name: "VSLIDEDOWN_VI",
+        operation: |cpu, inst, pc| vslidedown_op(cpu, inst, VSrc::I),
+    },
+    Instruction {
+        mask: MASK_VSLIDE1UP_VX,
+        match_data: MATCH_VSLIDE1UP_VX,
+        name: "VSLIDE1UP_VX",
+        operation: |cpu, inst, pc| vslide1_op(cpu, inst, true),
+    },
+    Instruction {
+        mask: MASK_VSLIDE1DOWN_VX,
+        match_data: MATCH_VSLIDE1DOWN_VX,
+        name: "VSLIDE1DOWN_VX",
+        operation: |cpu, inst, pc| vslide1_op(cpu, inst, false),
+    },
+    Instruction {
+        mask: MASK_VMUL_VV,
+        match_data: MATCH_VMUL_VV,
+        name: "VMUL_VV",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::V, |a, b, _| a.wrapping_mul(b)),
+    },
+    Instruction {
+        mask: MASK_VMUL_VX,
+        match_data: MATCH_VMUL_VX,
+        name: "VMUL_VX",
+        operation: |cpu, inst, pc| vint_op(cpu, inst, VSrc::X, |a, b, _| a.wrapping_mul(b)),
+    },
+    Instruction {
+        mask: MASK_VMULH_VV,
+        match_data: MATCH_VMULH_VV,
+        name: "VMULH_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| {
+                ((sext(a, sew) as i128 * sext(b, sew) as i128) >> sew) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMULH_VX,
+        match_data: MATCH_VMULH_VX,
+        name: "VMULH_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| {
+                ((sext(a, sew) as i128 * sext(b, sew) as i128) >> sew) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMULHU_VV,
+        match_data: MATCH_VMULHU_VV,
+        name: "VMULHU_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| {
+                ((a as u128 * b as u128) >> sew) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMULHU_VX,
+        match_data: MATCH_VMULHU_VX,
+        name: "VMULHU_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| {
+                ((a as u128 * b as u128) >> sew) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMULHSU_VV,
+        match_data: MATCH_VMULHSU_VV,
+        name: "VMULHSU_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| {
+                ((sext(a, sew) as i128 * b as i128) >> sew) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMULHSU_VX,
+        match_data: MATCH_VMULHSU_VX,
+        name: "VMULHSU_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| {
+                ((sext(a, sew) as i128 * b as i128) >> sew) as u64
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VDIVU_VV,
+        match_data: MATCH_VDIVU_VV,
+        name: "VDIVU_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, _| {
+                a.checked_div(b).unwrap_or(u64::MAX)
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VDIVU_VX,
+        match_data: MATCH_VDIVU_VX,
+        name: "VDIVU_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, _| {
+                a.checked_div(b).unwrap_or(u64::MAX)
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VDIV_VV,
+        match_data: MATCH_VDIV_VV,
+        name: "VDIV_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| match b {
+                0 => u64::MAX,
+                _ => sext(a, sew).wrapping_div(sext(b, sew)) as u64,
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VDIV_VX,
+        match_data: MATCH_VDIV_VX,
+        name: "VDIV_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| match b {
+                0 => u64::MAX,
+                _ => sext(a, sew).wrapping_div(sext(b, sew)) as u64,
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VREMU_VV,
+        match_data: MATCH_VREMU_VV,
+        name: "VREMU_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, _| a.checked_rem(b).unwrap_or(a))
+        },
+    },
+    Instruction {
+        mask: MASK_VREMU_VX,
+        match_data: MATCH_VREMU_VX,
+        name: "VREMU_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, _| a.checked_rem(b).unwrap_or(a))
+        },
+    },
+    Instruction {
+        mask: MASK_VREM_VV,
+        match_data: MATCH_VREM_VV,
+        name: "VREM_VV",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::V, |a, b, sew| match b {
+                0 => a,
+                _ => sext(a, sew).wrapping_rem(sext(b, sew)) as u64,
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VREM_VX,
+        match_data: MATCH_VREM_VX,
+        name: "VREM_VX",
+        operation: |cpu, inst, pc| {
+            vint_op(cpu, inst, VSrc::X, |a, b, sew| match b {
+                0 => a,
+                _ => sext(a, sew).wrapping_rem(sext(b, sew)) as u64,
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMACC_VV,
+        match_data: MATCH_VMACC_VV,
+        name: "VMACC_VV",
+        operation: |cpu, inst, pc| {
+            vmac_op(cpu, inst, VSrc::V, |a, b, d| {
+                b.wrapping_mul(a).wrapping_add(d)
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMACC_VX,
+        match_data: MATCH_VMACC_VX,
+        name: "VMACC_VX",
+        operation: |cpu, inst, pc| {
+            vmac_op(cpu, inst, VSrc::X, |a, b, d| {
+                b.wrapping_mul(a).wrapping_add(d)
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VNMSAC_VV,
+        match_data: MATCH_VNMSAC_VV,
+        name: "VNMSAC_VV",
+        operation: |cpu, inst, pc| {
+            vmac_op(cpu, inst, VSrc::V, |a, b, d| {
+                d.wrapping_sub(b.wrapping_mul(a))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VNMSAC_VX,
+        match_data: MATCH_VNMSAC_VX,
+        name: "VNMSAC_VX",
+        operation: |cpu, inst, pc| {
+            vmac_op(cpu, inst, VSrc::X, |a, b, d| {
+                d.wrapping_sub(b.wrapping_mul(a))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMADD_VV,
+        match_data: MATCH_VMADD_VV,
+        name: "VMADD_VV",
+        operation: |cpu, inst, pc| {
+            vmac_op(cpu, inst, VSrc::V, |a, b, d| {
+                b.wrapping_mul(d).wrapping_add(a)
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMADD_VX,
+        match_data: MATCH_VMADD_VX,
+        name: "VMADD_VX",
+        operation: |cpu, inst, pc| {
+            vmac_op(cpu, inst, VSrc::X, |a, b, d| {
+                b.wrapping_mul(d).wrapping_add(a)
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VNMSUB_VV,
+        match_data: MATCH_VNMSUB_VV,
+        name: "VNMSUB_VV",
+        operation: |cpu, inst, pc| {
+            vmac_op(cpu, inst, VSrc::V, |a, b, d| {
+                a.wrapping_sub(b.wrapping_mul(d))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VNMSUB_VX,
+        match_data: MATCH_VNMSUB_VX,
+        name: "VNMSUB_VX",
+        operation: |cpu, inst, pc| {
+            vmac_op(cpu, inst, VSrc::X, |a, b, d| {
+                a.wrapping_sub(b.wrapping_mul(d))
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VREDSUM_VS,
+        match_data: MATCH_VREDSUM_VS,
+        name: "VREDSUM_VS",
+        operation: |cpu, inst, pc| vred_op(cpu, inst, |acc, x, _| acc.wrapping_add(x)),
+    },
+    Instruction {
+        mask: MASK_VREDAND_VS,
+        match_data: MATCH_VREDAND_VS,
+        name: "VREDAND_VS",
+        operation: |cpu, inst, pc| vred_op(cpu, inst, |acc, x, _| acc & x),
+    },
+    Instruction {
+        mask: MASK_VREDOR_VS,
+        match_data: MATCH_VREDOR_VS,
+        name: "VREDOR_VS",
+        operation: |cpu, inst, pc| vred_op(cpu, inst, |acc, x, _| acc | x),
+    },
+    Instruction {
+        mask: MASK_VREDXOR_VS,
+        match_data: MATCH_VREDXOR_VS,
+        name: "VREDXOR_VS",
+        operation: |cpu, inst, pc| vred_op(cpu, inst, |acc, x, _| acc ^ x),
+    },
+    Instruction {
+        mask: MASK_VREDMINU_VS,
+        match_data: MATCH_VREDMINU_VS,
+        name: "VREDMINU_VS",
+        operation: |cpu, inst, pc| vred_op(cpu, inst, |acc, x, _| acc.min(x)),
+    },
+    Instruction {
+        mask: MASK_VREDMIN_VS,
+        match_data: MATCH_VREDMIN_VS,
+        name: "VREDMIN_VS",
+        operation: |cpu, inst, pc| {
+            vred_op(cpu, inst, |acc, x, sew| {
+                match sext(x, sew) < sext(acc, sew) {
+                    true => x,
+                    false => acc,
+                }
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VREDMAXU_VS,
+        match_data: MATCH_VREDMAXU_VS,
+        name: "VREDMAXU_VS",
+        operation: |cpu, inst, pc| vred_op(cpu, inst, |acc, x, _| acc.max(x)),
+    },
+    Instruction {
+        mask: MASK_VREDMAX_VS,
+        match_data: MATCH_VREDMAX_VS,
+        name: "VREDMAX_VS",
+        operation: |cpu, inst, pc| {
+            vred_op(cpu, inst, |acc, x, sew| {
+                match sext(x, sew) > sext(acc, sew) {
+                    true => x,
+                    false => acc,
+                }
+            })
+        },
+    },
+    Instruction {
+        mask: MASK_VMERGE_VVM,
+        match_data: MATCH_VMERGE_VVM,
+        name: "VMERGE_VVM",
+        operation: |cpu, inst, pc| vmerge_op(cpu, inst, VSrc::V),
+    },
+    Instruction {
+        mask: MASK_VMERGE_VXM,
+        match_data: MATCH_VMERGE_VXM,
+        name: "VMERGE_VXM",
+        operation: |cpu, inst, pc| vmerge_op(cpu, inst, VSrc::X),
+    },
+    Instruction {
+        mask: MASK_VMERGE_VIM,
+        match_data: MATCH_VMERGE_VIM,
+        name: "VMERGE_VIM",
+        operation: |cpu, inst, pc| vmerge_op(cpu, inst, VSrc::I),
+    },
+    Instruction {
+        mask: MASK_VMV_V_V,
+        match_data: MATCH_VMV_V_V,
+        name: "VMV_V_V",
+        operation: |cpu, inst, pc| vmerge_op(cpu, inst, VSrc::V),
+    },
+    Instruction {
+        mask: MASK_VMV_V_X,
+        match_data: MATCH_VMV_V_X,
+        name: "VMV_V_X",
+        operation: |cpu, inst, pc| vmerge_op(cpu, inst, VSrc::X),
+    },
+    Instruction {
+        mask: MASK_VMV_V_I,
+        match_data: MATCH_VMV_V_I,
+        name: "VMV_V_I",
+        operation: |cpu, inst, pc| vmerge_op(cpu, inst, VSrc::I),
+    },
+    Instruction {
+        mask: MASK_VMANDN_MM,
+        match_data: MATCH_VMANDN_MM,
+        name: "VMANDN_MM",
+        operation: |cpu, inst, pc| vmask_op(cpu, inst, |a, b| a & !b),
+    },
+    Instruction {
+        mask: MASK_VMAND_MM,
+        match_data: MATCH_VMAND_MM,
+        name: "VMAND_MM",
+        operation: |cpu, inst, pc| vmask_op(cpu, inst, |a, b| a & b),
+    },
+    Instruction {
+        mask: MASK_VMOR_MM,
+        match_data: MATCH_VMOR_MM,
+        name: "VMOR_MM",
+        operation: |cpu, inst, pc| vmask_op(cpu, inst, |a, b| a | b),
+    },
+    Instruction {
+        mask: MASK_VMXOR_MM,
+        match_data: MATCH_VMXOR_MM,
+        name: "VMXOR_MM",
+        operation: |cpu, inst, pc| vmask_op(cpu, inst, |a, b| a ^ b),
+    },
+    Instruction {
+        mask: MASK_VMORN_MM,
+        match_data: MATCH_VMORN_MM,
+        name: "VMORN_MM",
+        operation: |cpu, inst, pc| vmask_op(cpu, inst, |a, b| a | !b),
+    },
+    Instruction {
+        mask: MASK_VMNAND_MM,
+        match_data: MATCH_VMNAND_MM,
+        name: "VMNAND_MM",
+        operation: |cpu, inst, pc| vmask_op(cpu, inst, |a, b| !(a & b)),
+    },
+    Instruction {
+        mask: MASK_VMNOR_MM,
+        match_data: MATCH_VMNOR_MM,
+        name: "VMNOR_MM",
+        operation: |cpu, inst, pc| vmask_op(cpu, inst, |a, b| !(a | b)),
+    },
+    Instruction {
+        mask: MASK_VMXNOR_MM,
+        match_data: MATCH_VMXNOR_MM,
+        name: "VMXNOR_MM",
+        operation: |cpu, inst, pc| vmask_op(cpu, inst, |a, b| !(a ^ b)),
+    },
+    Instruction {
+        mask: MASK_VMV_X_S,
+        match_data: MATCH_VMV_X_S,
+        name: "VMV_X_S",
+        operation: |cpu, inst, pc| vmv_x_s_op(cpu, inst),
+    },
+    Instruction {
+        mask: MASK_VMV_S_X,
+        match_data: MATCH_VMV_S_X,
+        name: "VMV_S_X",
+        operation: |cpu, inst, pc| vmv_s_x_op(cpu, inst),
+    },
+    Instruction {
+        mask: MASK_VCPOP_M,
+        match_data: MATCH_VCPOP_M,
+        name: "VCPOP_M",
+        operation: |cpu, inst, pc| vcpop_op(cpu, inst),
+    },
+    Instruction {
+        mask: MASK_VFIRST_M,
+        match_data: MATCH_VFIRST_M,
+        name: "VFIRST_M",
+        operation: |cpu, inst, pc| vfirst_op(cpu, inst),
+    },
+    Instruction {
+        mask: MASK_VMSBF_M,
+        match_data: MATCH_VMSBF_M,
+        name: "VMSBF_M",
+        operation: |cpu, inst, pc| vmsbf_op(cpu, inst, "vmsbf"),
+    },
+    Instruction {
+        mask: MASK_VMSOF_M,
+        match_data: MATCH_VMSOF_M,
+        name: "VMSOF_M",
+        operation: |cpu, inst, pc| vmsbf_op(cpu, inst, "vmsof"),
+    },
+    Instruction {
+        mask: MASK_VMSIF_M,
+        match_data: MATCH_VMSIF_M,
+        name: "VMSIF_M",
+        operation: |cpu, inst, pc| vmsbf_op(cpu, inst, "vmsif"),
+    },
+    Instruction {
+        mask: MASK_VIOTA_M,
+        match_data: MATCH_VIOTA_M,
+        name: "VIOTA_M",
+        operation: |cpu, inst, pc| viota_op(cpu, inst),
+    },
+    Instruction {
+        mask: MASK_VID_V,
+        match_data: MATCH_VID_V,
+        name: "VID_V",
+        operation: |cpu, inst, pc| vid_op(cpu, inst),
+    },
+    Instruction {
+        mask: MASK_VZEXT_VF2,
+        match_data: MATCH_VZEXT_VF2,
+        name: "VZEXT_VF2",
+        operation: |cpu, inst, pc| vext_op(cpu, inst, 2, false),
+    },
+    Instruction {
+        mask: MASK_VSEXT_VF2,
+        match_data: MATCH_VSEXT_VF2,
+        name: "VSEXT_VF2",
+        operation: |cpu, inst, pc| vext_op(cpu, inst, 2, true),
+    },
+    Instruction {
+        mask: MASK_VZEXT_VF4,
+        match_data: MATCH_VZEXT_VF4,
+        name: "VZEXT_VF4",
+        operation: |cpu, inst, pc| vext_op(cpu, inst, 4, false),
+    },
+    Instruction {
+        mask: MASK_VSEXT_VF4,
+        match_data: MATCH_VSEXT_VF4,
+        name: "VSEXT_VF4",
+        operation: |cpu, inst, pc| vext_op(cpu, inst, 4, true),
+    },
+    Instruction {
+        mask: MASK_VZEXT_VF8,
+        match_data: MATCH_VZEXT_VF8,
+        name: "VZEXT_VF8",
+        operation: |cpu, inst, pc| vext_op(cpu, inst, 8, false),
+    },
+    Instruction {
+        mask: MASK_VSEXT_VF8,
+        match_data: MATCH_VSEXT_VF8,
+        name: "VSEXT_VF8",
+        operation: |cpu, inst, pc| vext_op(cpu, inst, 8, true),
+    },
+    Instruction {
+        mask: MASK_VCOMPRESS_VM,
+        match_data: MATCH_VCOMPRESS_VM,
+        name: "VCOMPRESS_VM",
+        operation: |cpu, inst, pc| vcompress_op(cpu, inst),
+    },
+    Instruction {
+        mask: MASK_VMV1R_V,
+        match_data: MATCH_VMV1R_V,
+        name: "VMV1R_V",
+        operation: |cpu, inst, pc| vmvnr_op(cpu, inst, 1),
+    },
+    Instruction {
+        mask: MASK_VMV2R_V,
+        match_data: MATCH_VMV2R_V,
+        name: "VMV2R_V",
+        operation: |cpu, inst, pc| vmvnr_op(cpu, inst, 2),
+    },
+    Instruction {
+        mask: MASK_VMV4R_V,
+        match_data: MATCH_VMV4R_V,
+        name: "VMV4R_V",
+        operation: |cpu, inst, pc| vmvnr_op(cpu, inst, 4),
+    },
+    Instruction {
+        mask: MASK_VMV8R_V,
+        match_data: MATCH_VMV8R_V,
+        name: "VMV8R_V",
+        operation: |cpu, inst, pc| vmvnr_op(cpu, inst, 8),
+    },
+    Instruction {
+        mask: MASK_VSETVLI,
+        match_data: MATCH_VSETVLI,
+        name: "VSETVLI",
+        operation: |cpu, inst, pc| {
+            // vsetvli rd, rs1, vtypei
+            let f = parse_format_v(inst);
+            let avl = avl(cpu, inst);
+            vset_op(cpu, inst, f.vd, avl, ((inst >> 20) & 0x7ff).into())
+        },
+    },
+    Instruction {
+        mask: MASK_VSETIVLI,
+        match_data: MATCH_VSETIVLI,
+        name: "VSETIVLI",
+        operation: |cpu, inst, pc| {
+            // vsetivli rd, uimm, vtypei
+            let f = parse_format_v(inst);
+            vset_op(cpu, inst, f.vd, Some(f.vs1), ((inst >> 20) & 0x3ff).into())
+        },
+    },
+    Instruction {
+        mask: MASK_VSETVL,
+        match_data: MATCH_VSETVL,
+        name: "VSETVL",
+        operation: |cpu, inst, pc| {
+            // vsetvl rd, rs1, rs2
+            let f = parse_format_v(inst);
+            let avl = avl(cpu, inst);
+            let vtype = cpu.gpr.read(f.vs2);
+            vset_op(cpu, inst, f.vd, avl, vtype)
+        },
+    },
+    Instruction {
+        mask: MASK_VLE8_V,
+        match_data: MATCH_VLE8_V,
+        name: "VLE8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Unit, 8, false),
+    },
+    Instruction {
+        mask: MASK_VLE8FF_V,
+        match_data: MATCH_VLE8FF_V,
+        name: "VLE8FF_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::FaultFirst, 8, false),
+    },
+    Instruction {
+        mask: MASK_VSE8_V,
+        match_data: MATCH_VSE8_V,
+        name: "VSE8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Unit, 8, true),
+    },
+    Instruction {
+        mask: MASK_VLSE8_V,
+        match_data: MATCH_VLSE8_V,
+        name: "VLSE8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Stride, 8, false),
+    },
+    Instruction {
+        mask: MASK_VSSE8_V,
+        match_data: MATCH_VSSE8_V,
+        name: "VSSE8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Stride, 8, true),
+    },
+    Instruction {
+        mask: MASK_VLUXEI8_V,
+        match_data: MATCH_VLUXEI8_V,
+        name: "VLUXEI8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 8, false),
+    },
+    Instruction {
+        mask: MASK_VLOXEI8_V,
+        match_data: MATCH_VLOXEI8_V,
+        name: "VLOXEI8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 8, false),
+    },
+    Instruction {
+        mask: MASK_VSUXEI8_V,
+        match_data: MATCH_VSUXEI8_V,
+        name: "VSUXEI8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 8, true),
+    },
+    Instruction {
+        mask: MASK_VSOXEI8_V,
+        match_data: MATCH_VSOXEI8_V,
+        name: "VSOXEI8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 8, true),
+    },
+    Instruction {
+        mask: MASK_VL1RE8_V,
+        match_data: MATCH_VL1RE8_V,
+        name: "VL1RE8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(1), 8, false),
+    },
+    Instruction {
+        mask: MASK_VL2RE8_V,
+        match_data: MATCH_VL2RE8_V,
+        name: "VL2RE8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(2), 8, false),
+    },
+    Instruction {
+        mask: MASK_VL4RE8_V,
+        match_data: MATCH_VL4RE8_V,
+        name: "VL4RE8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(4), 8, false),
+    },
+    Instruction {
+        mask: MASK_VL8RE8_V,
+        match_data: MATCH_VL8RE8_V,
+        name: "VL8RE8_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(8), 8, false),
+    },
+    Instruction {
+        mask: MASK_VLE16_V,
+        match_data: MATCH_VLE16_V,
+        name: "VLE16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Unit, 16, false),
+    },
+    Instruction {
+        mask: MASK_VLE16FF_V,
+        match_data: MATCH_VLE16FF_V,
+        name: "VLE16FF_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::FaultFirst, 16, false),
+    },
+    Instruction {
+        mask: MASK_VSE16_V,
+        match_data: MATCH_VSE16_V,
+        name: "VSE16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Unit, 16, true),
+    },
+    Instruction {
+        mask: MASK_VLSE16_V,
+        match_data: MATCH_VLSE16_V,
+        name: "VLSE16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Stride, 16, false),
+    },
+    Instruction {
+        mask: MASK_VSSE16_V,
+        match_data: MATCH_VSSE16_V,
+        name: "VSSE16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Stride, 16, true),
+    },
+    Instruction {
+        mask: MASK_VLUXEI16_V,
+        match_data: MATCH_VLUXEI16_V,
+        name: "VLUXEI16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 16, false),
+    },
+    Instruction {
+        mask: MASK_VLOXEI16_V,
+        match_data: MATCH_VLOXEI16_V,
+        name: "VLOXEI16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 16, false),
+    },
+    Instruction {
+        mask: MASK_VSUXEI16_V,
+        match_data: MATCH_VSUXEI16_V,
+        name: "VSUXEI16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 16, true),
+    },
+    Instruction {
+        mask: MASK_VSOXEI16_V,
+        match_data: MATCH_VSOXEI16_V,
+        name: "VSOXEI16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 16, true),
+    },
+    Instruction {
+        mask: MASK_VL1RE16_V,
+        match_data: MATCH_VL1RE16_V,
+        name: "VL1RE16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(1), 16, false),
+    },
+    Instruction {
+        mask: MASK_VL2RE16_V,
+        match_data: MATCH_VL2RE16_V,
+        name: "VL2RE16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(2), 16, false),
+    },
+    Instruction {
+        mask: MASK_VL4RE16_V,
+        match_data: MATCH_VL4RE16_V,
+        name: "VL4RE16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(4), 16, false),
+    },
+    Instruction {
+        mask: MASK_VL8RE16_V,
+        match_data: MATCH_VL8RE16_V,
+        name: "VL8RE16_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(8), 16, false),
+    },
+    Instruction {
+        mask: MASK_VLE32_V,
+        match_data: MATCH_VLE32_V,
+        name: "VLE32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Unit, 32, false),
+    },
+    Instruction {
+        mask: MASK_VLE32FF_V,
+        match_data: MATCH_VLE32FF_V,
+        name: "VLE32FF_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::FaultFirst, 32, false),
+    },
+    Instruction {
+        mask: MASK_VSE32_V,
+        match_data: MATCH_VSE32_V,
+        name: "VSE32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Unit, 32, true),
+    },
+    Instruction {
+        mask: MASK_VLSE32_V,
+        match_data: MATCH_VLSE32_V,
+        name: "VLSE32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Stride, 32, false),
+    },
+    Instruction {
+        mask: MASK_VSSE32_V,
+        match_data: MATCH_VSSE32_V,
+        name: "VSSE32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Stride, 32, true),
+    },
+    Instruction {
+        mask: MASK_VLUXEI32_V,
+        match_data: MATCH_VLUXEI32_V,
+        name: "VLUXEI32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 32, false),
+    },
+    Instruction {
+        mask: MASK_VLOXEI32_V,
+        match_data: MATCH_VLOXEI32_V,
+        name: "VLOXEI32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 32, false),
+    },
+    Instruction {
+        mask: MASK_VSUXEI32_V,
+        match_data: MATCH_VSUXEI32_V,
+        name: "VSUXEI32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 32, true),
+    },
+    Instruction {
+        mask: MASK_VSOXEI32_V,
+        match_data: MATCH_VSOXEI32_V,
+        name: "VSOXEI32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 32, true),
+    },
+    Instruction {
+        mask: MASK_VL1RE32_V,
+        match_data: MATCH_VL1RE32_V,
+        name: "VL1RE32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(1), 32, false),
+    },
+    Instruction {
+        mask: MASK_VL2RE32_V,
+        match_data: MATCH_VL2RE32_V,
+        name: "VL2RE32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(2), 32, false),
+    },
+    Instruction {
+        mask: MASK_VL4RE32_V,
+        match_data: MATCH_VL4RE32_V,
+        name: "VL4RE32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(4), 32, false),
+    },
+    Instruction {
+        mask: MASK_VL8RE32_V,
+        match_data: MATCH_VL8RE32_V,
+        name: "VL8RE32_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(8), 32, false),
+    },
+    Instruction {
+        mask: MASK_VLE64_V,
+        match_data: MATCH_VLE64_V,
+        name: "VLE64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Unit, 64, false),
+    },
+    Instruction {
+        mask: MASK_VLE64FF_V,
+        match_data: MATCH_VLE64FF_V,
+        name: "VLE64FF_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::FaultFirst, 64, false),
+    },
+    Instruction {
+        mask: MASK_VSE64_V,
+        match_data: MATCH_VSE64_V,
+        name: "VSE64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Unit, 64, true),
+    },
+    Instruction {
+        mask: MASK_VLSE64_V,
+        match_data: MATCH_VLSE64_V,
+        name: "VLSE64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Stride, 64, false),
+    },
+    Instruction {
+        mask: MASK_VSSE64_V,
+        match_data: MATCH_VSSE64_V,
+        name: "VSSE64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Stride, 64, true),
+    },
+    Instruction {
+        mask: MASK_VLUXEI64_V,
+        match_data: MATCH_VLUXEI64_V,
+        name: "VLUXEI64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 64, false),
+    },
+    Instruction {
+        mask: MASK_VLOXEI64_V,
+        match_data: MATCH_VLOXEI64_V,
+        name: "VLOXEI64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 64, false),
+    },
+    Instruction {
+        mask: MASK_VSUXEI64_V,
+        match_data: MATCH_VSUXEI64_V,
+        name: "VSUXEI64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 64, true),
+    },
+    Instruction {
+        mask: MASK_VSOXEI64_V,
+        match_data: MATCH_VSOXEI64_V,
+        name: "VSOXEI64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Index, 64, true),
+    },
+    Instruction {
+        mask: MASK_VL1RE64_V,
+        match_data: MATCH_VL1RE64_V,
+        name: "VL1RE64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(1), 64, false),
+    },
+    Instruction {
+        mask: MASK_VL2RE64_V,
+        match_data: MATCH_VL2RE64_V,
+        name: "VL2RE64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(2), 64, false),
+    },
+    Instruction {
+        mask: MASK_VL4RE64_V,
+        match_data: MATCH_VL4RE64_V,
+        name: "VL4RE64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(4), 64, false),
+    },
+    Instruction {
+        mask: MASK_VL8RE64_V,
+        match_data: MATCH_VL8RE64_V,
+        name: "VL8RE64_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(8), 64, false),
+    },
+    Instruction {
+        mask: MASK_VLM_V,
+        match_data: MATCH_VLM_V,
+        name: "VLM_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Mask, 8, false),
+    },
+    Instruction {
+        mask: MASK_VSM_V,
+        match_data: MATCH_VSM_V,
+        name: "VSM_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Mask, 8, true),
+    },
+    Instruction {
+        mask: MASK_VS1R_V,
+        match_data: MATCH_VS1R_V,
+        name: "VS1R_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(1), 8, true),
+    },
+    Instruction {
+        mask: MASK_VS2R_V,
+        match_data: MATCH_VS2R_V,
+        name: "VS2R_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(2), 8, true),
+    },
+    Instruction {
+        mask: MASK_VS4R_V,
+        match_data: MATCH_VS4R_V,
+        name: "VS4R_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(4), 8, true),
+    },
+    Instruction {
+        mask: MASK_VS8R_V,
+        match_data: MATCH_VS8R_V,
+        name: "VS8R_V",
+        operation: |cpu, inst, pc| vmem_op(cpu, inst, VMem::Whole(8), 8, true),
+    },
+];
+
+#[cfg(test)]
+mod test_rv64v {
+    use alloc::vec::Vec;
+
+    use crate::{
+        device::device_trait::MEM_BASE,
+        rv64core::{
+            cpu_core::CpuCore,
+            inst::inst_base::AccessType,
+            test_cpu::{exec, test_cpu},
+        },
+    };
+
+    const MEM_SIZE: u64 = 4096;
+
+    fn build_cpu() -> CpuCore {
+        let (cpu, _) = test_cpu("rv64imac_zve64x", MEM_SIZE);
+        let mut status = cpu.csr_regs.xstatus.get();
+        status.set_vs(0b01);
+        cpu.csr_regs.xstatus.set(status);
+        cpu
+    }
+
+    #[test]
+    fn memcpy_test() {
+        let mut cpu = build_cpu();
+        for i in 0..20 {
+            let addr = MEM_BASE + i;
+            cpu.write(addr, i + 1, 1, AccessType::Store(addr)).unwrap();
+        }
+        let (src, dst) = (MEM_BASE, MEM_BASE + 0x100);
+        cpu.gpr.write(10, src);
+        cpu.gpr.write(11, dst);
+        cpu.gpr.write(12, 20);
+        // vsetvli a2, a2, e8, m1, ta, ma
+        exec(&mut cpu, 0x0c067657).unwrap();
+        assert_eq!(cpu.gpr.read(12), 16);
+        // vle8.v v1, (a0); vse8.v v1, (a1)
+        exec(&mut cpu, 0x02050087).unwrap();
+        exec(&mut cpu, 0x020580a7).unwrap();
+        for i in 0..20 {
+            let addr = dst + i;
+            let data = cpu.read(addr, 1, AccessType::Load(addr)).unwrap();
+            assert_eq!(data, if i < 16 { i + 1 } else { 0 });
+        }
+        // mstatus.VS is dirty
+        assert_eq!(cpu.csr_regs.xstatus.get().vs(), 0b11);
+        assert!(cpu.csr_regs.xstatus.get().sd());
+
+        // vle8ff.v stops at the end of the memory
+        let addr = MEM_BASE + MEM_SIZE - 4;
+        cpu.gpr.write(10, addr);
+        exec(&mut cpu, 0x03050087).unwrap();
+        assert_eq!(cpu.csr_regs.vl.get(), 4);
+        // vle8.v traps and records the faulting element
+        cpu.gpr.write(12, 16);
+        exec(&mut cpu, 0x0c067657).unwrap();
+        assert!(exec(&mut cpu, 0x02050087).is_err());
+        assert_eq!(cpu.csr_regs.vstart.get(), 4);
+    }
+
+    #[test]
+    fn arith_test() {
+        let mut cpu = build_cpu();
+        // vsetivli zero, 4, e32, m1, ta, ma
+        exec(&mut cpu, 0xcd027057).unwrap();
+        assert_eq!(cpu.csr_regs.vl.get(), 4);
+        // vmv.v.i v2, 3; vid.v v3; vadd.vv v4, v2, v3
+        exec(&mut cpu, 0x5e01b157).unwrap();
+        exec(&mut cpu, 0x5208a1d7).unwrap();
+        exec(&mut cpu, 0x02218257).unwrap();
+        let v4: Vec<u64> = (0..4).map(|i| cpu.vpr.read_elem(4, i, 32)).collect();
+        assert_eq!(v4, [3, 4, 5, 6]);
+        // vmseq.vi v0, v4, 5
+        exec(&mut cpu, 0x6242b057).unwrap();
+        assert_eq!(cpu.vpr.read_elem(0, 0, 8) & 0xf, 0b0100);
+        // vredsum.vs v5, v4, v2; vmv.x.s a2, v5
+        exec(&mut cpu, 0x024122d7).unwrap();
+        exec(&mut cpu, 0x42502657).unwrap();
+        assert_eq!(cpu.gpr.read(12), 21);
+        // vcpop.m a0, v0; vfirst.m a1, v0
+        exec(&mut cpu, 0x42082557).unwrap();
+        exec(&mut cpu, 0x4208a5d7).unwrap();
+        assert_eq!(cpu.gpr.read(10), 1);
+        assert_eq!(cpu.gpr.read(11), 2);
+        // vsub.vx v4, v4, a0, v0.t, only element 2 is active
+        exec(&mut cpu, 0x08454257).unwrap();
+        let v4: Vec<u64> = (0..4).map(|i| cpu.vpr.read_elem(4, i, 32)).collect();
+        assert_eq!(v4, [3, 4, 4, 6]);
+
+        // vsetvli a2, zero, e64, m2, ta, ma: vlmax
+        exec(&mut cpu, 0x0d907657).unwrap();
+        assert_eq!(cpu.gpr.read(12), 4);
+        // vsadd.vx v6, v2, a0 saturates and sets vxsat
+        cpu.gpr.write(10, i64::MAX as u64);
+        exec(&mut cpu, 0x86254357).unwrap();
+        assert_eq!(cpu.vpr.read_elem(6, 0, 64), i64::MAX as u64);
+        assert_eq!(cpu.csr_regs.vcsr.get() & 1, 1);
+    }
+
+    #[test]
+    fn illegal_test() {
+        let mut cpu = build_cpu();
+        // vtype.vill is set after reset
+        assert!(exec(&mut cpu, 0x02218257).is_err());
+        // vsetvli a0, a1, e64, m4: v4 is a valid group, but vadd.vv v4, v2, v3 is not
+        exec(&mut cpu, 0x0da5f557).unwrap();
+        assert!(exec(&mut cpu, 0x02218257).is_err());
+        // mstatus.VS is off
+        let mut status = cpu.csr_regs.xstatus.get();
+        status.set_vs(0);
+        cpu.csr_regs.xstatus.set(status);
+        assert!(exec(&mut cpu, 0x0c307757).is_err());
+    }
+}
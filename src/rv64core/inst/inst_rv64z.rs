@@ -19,6 +19,9 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         name: "ECALL",
         operation: |cpu, inst, pc| match cpu.cur_priv.get() {
             PrivilegeLevels::User => Err(TrapType::EnvironmentCallFromUMode),
+            PrivilegeLevels::Supervisor if cpu.csr_regs.virt() => {
+                Err(TrapType::EnvironmentCallFromVSMode)
+            }
             PrivilegeLevels::Supervisor => Err(TrapType::EnvironmentCallFromSMode),
             PrivilegeLevels::Machine => Err(TrapType::EnvironmentCallFromMMode),
         },
@@ -27,7 +30,25 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         mask: MASK_WFI,
         match_data: MATCH_WFI,
         name: "WFI",
-        operation: |cpu, inst, pc| Ok(()),
+        operation: |cpu, inst, pc| {
+            // WFI in VU-mode, or in VS-mode when hstatus.VTW=1, is a virtual instruction
+            let mstatus = cpu.csr_regs.xstatus.get();
+            let vtw = cpu.csr_regs.h.hstatus.get().vtw();
+            let vu_mode = cpu.cur_priv.get() == PrivilegeLevels::User;
+            if cpu.csr_regs.virt() && !mstatus.tw() && (vu_mode || vtw) {
+                return Err(TrapType::VirtualInstruction(inst.into()));
+            }
+            // below M-mode with TW=1, or in U-mode when S-mode is implemented,
+            // WFI traps unless an interrupt comes within the timeout
+            let privi = cpu.cur_priv.get();
+            let timeout = privi < PrivilegeLevels::Machine
+                && (mstatus.tw() || (vu_mode && cpu.config.s_mode()));
+            if timeout && cpu.config.wfi_timeout() == 0 {
+                return Err(TrapType::IllegalInstruction(inst.into()));
+            }
+            cpu.wait_for_interrupt(timeout);
+            Ok(())
+        },
     },
     Instruction {
         mask: MASK_MRET,
@@ -40,6 +61,10 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
             // mode (U if U-mode is implemented, else M). If xPP̸=M, xRET also sets MPRV=0.
             // let mstatus_val = cpu.csr_regs.read_raw(CSR_MSTATUS.into());
             // let mut mstatus = Mstatus::from(mstatus_val);
+            // MRET is illegal below M-mode, VS-mode and VU-mode included
+            if cpu.cur_priv.get() != PrivilegeLevels::Machine {
+                return Err(TrapType::IllegalInstruction(inst.into()));
+            }
             let mut mstatus = cpu.csr_regs.xstatus.get();
 
             // supposing xPP holds the value y
@@ -62,13 +87,21 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
             if y != PrivilegeLevels::Machine {
                 mstatus.set_mprv(false);
             }
+            // the virtualization mode is changed to MPV if y!=M, MPV is set to 0
+            if cpu.config.is_enable_isa(b'h') {
+                cpu.csr_regs
+                    .h
+                    .virt
+                    .set(mstatus.mpv() && y != PrivilegeLevels::Machine);
+                mstatus.set_mpv(false);
+            }
 
             // warn!("MRET:mstatus_now:{mstatus_val:x}");
             cpu.csr_regs.xstatus.set(mstatus);
             // warn!("MRET:mstatus_now2:{mstatus_val:x}");
 
             // let mepc_val = cpu.csr_regs.read_raw(CSR_MEPC.into());
-            let mepc = cpu.csr_regs.mepc.get();
+            let mepc = cpu.csr_regs.xepc(cpu.csr_regs.mepc.get());
             // warn!("mret->{mepc_val:x}");
             cpu.npc = mepc;
 
@@ -88,12 +121,27 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
             //  xRET sets the pc to the value stored in the xepc register.
             // let mstatus_val = cpu.csr_regs.read_raw(CSR_MSTATUS.into());
             // let mut mstatus = Mstatus::from(mstatus_val);
+            if cpu.csr_regs.virt() {
+                return sret_vs(cpu, inst);
+            }
             let mut mstatus = cpu.csr_regs.xstatus.get();
 
-            // SRET should also raise an illegal instruction exception when TSR=1 in mstatus
-            if mstatus.tsr() {
+            // SRET is illegal in U-mode, and in S-mode when TSR=1 in mstatus
+            let illegal = match cpu.cur_priv.get() {
+                PrivilegeLevels::User => true,
+                PrivilegeLevels::Supervisor => mstatus.tsr(),
+                PrivilegeLevels::Machine => false,
+            };
+            if illegal {
                 return Err(TrapType::IllegalInstruction(inst.into()));
             }
+            // the virtualization mode is changed to hstatus.SPV, SPV is set to 0
+            if cpu.config.is_enable_isa(b'h') {
+                let mut hstatus = cpu.csr_regs.h.hstatus.get();
+                cpu.csr_regs.h.virt.set(hstatus.spv());
+                hstatus.set_spv(false);
+                cpu.csr_regs.h.hstatus.set(hstatus);
+            }
 
             // supposing xPP holds the value y
             let y = mstatus.get_spp_priv();
@@ -118,22 +166,13 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
 
             // xRET sets the pc to the value stored in the xepc register.
             // let sepc_val = cpu.csr_regs.read_raw(CSR_SEPC.into());
-            let sepc = cpu.csr_regs.sepc.get();
+            let sepc = cpu.csr_regs.xepc(cpu.csr_regs.sepc.get());
             // warn!("sret->{sepc_val:x}");
             cpu.npc = sepc;
 
             Ok(())
         },
     },
-    Instruction {
-        mask: MASK_FENCE_I,
-        match_data: MATCH_FENCE_I,
-        name: "FENCE_I",
-        operation: |cpu, inst, pc| {
-            cpu.cache_system.borrow_mut().clear();
-            Ok(())
-        },
-    },
     Instruction {
         mask: MASK_SFENCE_VMA,
         match_data: MATCH_SFENCE_VMA,
@@ -150,6 +189,15 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
             let mstatus = cpu.csr_regs.xstatus.get();
             let cur_priv = cpu.cur_priv.get();
 
+            // the VS-stage translations are not cached in the TLB
+            if cpu.csr_regs.virt() {
+                let vtvm = cpu.csr_regs.h.hstatus.get().vtvm();
+                if cur_priv == PrivilegeLevels::User || vtvm {
+                    return Err(TrapType::VirtualInstruction(inst.into()));
+                }
+                return Ok(());
+            }
+
             let require_priv = if mstatus.tvm() {
                 PrivilegeLevels::Machine
             } else {
@@ -162,7 +210,11 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
                 Err(TrapType::IllegalInstruction(inst.into()))
             } else {
                 // info!("SFENCE_VMA:rs1_data:{:x},rs2_data:{:x}", rs1_data, rs2_data);
-                cpu.mmu.fence_vma(rs1_data, rs2_data as u16);
+                // rs1 and rs2 select the page and the ASID unless they are x0, not 0.
+                // the decode cache is keyed by the instruction bits, it has no pages
+                let va = (f.rs1 != 0).then_some(rs1_data);
+                let asid = (f.rs2 != 0).then_some(rs2_data as u16);
+                cpu.mmu.fence_vma(va, asid);
                 Ok(())
             }
         },
@@ -173,6 +225,23 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         name: "FENCE",
         operation: |cpu, inst, pc| Ok(()),
     },
+];
+
+// Zifencei, implied by I unless `Config::set_legacy_isa(false)`
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZIFENCEI: &[Instruction] = &[Instruction {
+    mask: MASK_FENCE_I,
+    match_data: MATCH_FENCE_I,
+    name: "FENCE_I",
+    operation: |cpu, inst, pc| {
+        cpu.cache_system.borrow_mut().clear();
+        Ok(())
+    },
+}];
+
+// Zicsr, implied by I unless `Config::set_legacy_isa(false)`
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZICSR: &[Instruction] = &[
     Instruction {
         mask: MASK_CSRRC,
         match_data: MATCH_CSRRC,
@@ -180,24 +249,8 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         operation: |cpu, inst, pc| {
             // t = CSRs[csr]; CSRs[csr] = t &∼x[rs1]; x[rd] = t
             let f = parse_format_csr(inst);
-            let csr_ret = cpu.csr_regs.read(f.csr, cpu.cur_priv.get());
-
-            let t = match csr_ret {
-                Ok(val) => val,
-                Err(trap_type) => return Err(trap_type),
-            };
-
             let rs1_data = cpu.gpr.read(f.rs1);
-
-            let csr_wb_data = t & !rs1_data;
-            // warn!("CSRRC:{csr_wb_data:x}");
-            if t != csr_wb_data {
-                let csr_ret = cpu.csr_regs.write(f.csr, csr_wb_data, cpu.cur_priv.get());
-                csr_ret?;
-            };
-            cpu.gpr.write(f.rd, t);
-
-            Ok(())
+            csr_op(cpu, &f, true, f.rs1 != 0, |t| t & !rs1_data)
         },
     },
     Instruction {
@@ -207,24 +260,8 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         operation: |cpu, inst, pc| {
             // t = CSRs[csr]; CSRs[csr] = t | x[rs1]; x[rd] = t
             let f = parse_format_csr(inst);
-            let csr_ret = cpu.csr_regs.read(f.csr, cpu.cur_priv.get());
-
-            let t = match csr_ret {
-                Ok(val) => val,
-                Err(trap_type) => return Err(trap_type),
-            };
-
             let rs1_data = cpu.gpr.read(f.rs1);
-            let csr_wb_data = t | rs1_data;
-
-            if t != csr_wb_data {
-                let csr_ret = cpu.csr_regs.write(f.csr, csr_wb_data, cpu.cur_priv.get());
-                csr_ret?;
-            }
-
-            cpu.gpr.write(f.rd, t);
-
-            Ok(())
+            csr_op(cpu, &f, true, f.rs1 != 0, |t| t | rs1_data)
         },
     },
     Instruction {
@@ -234,24 +271,8 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         operation: |cpu, inst, pc| {
             // t = CSRs[csr]; CSRs[csr] = x[rs1]; x[rd] = t
             let f = parse_format_csr(inst);
-
-            let csr_ret = cpu.csr_regs.read(f.csr, cpu.cur_priv.get());
-
-            let t = match csr_ret {
-                Ok(val) => val,
-                Err(trap_type) => return Err(trap_type),
-            };
             let rs1_data = cpu.gpr.read(f.rs1);
-            // warn!("CSRRW_pre:{t:x}");
-            let csr_wb_data = rs1_data;
-            // warn!("CSRRW_now:{csr_wb_data:x}");
-            if t != csr_wb_data {
-                let csr_ret = cpu.csr_regs.write(f.csr, csr_wb_data, cpu.cur_priv.get());
-                csr_ret?;
-            }
-            cpu.gpr.write(f.rd, t);
-
-            Ok(())
+            csr_op(cpu, &f, f.rd != 0, true, |_| rs1_data)
         },
     },
     Instruction {
@@ -259,27 +280,10 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         match_data: MATCH_CSRRCI,
         name: "CSRRCI",
         operation: |cpu, inst, pc| {
-            // t = CSRs[csr]; CSRs[csr] = t &∼zimm; x[rd] =
+            // t = CSRs[csr]; CSRs[csr] = t &∼zimm; x[rd] = t
             let f = parse_format_csr(inst);
-            let csr_ret = cpu.csr_regs.read(f.csr, cpu.cur_priv.get());
-
-            let t = match csr_ret {
-                Ok(val) => val,
-                Err(trap_type) => return Err(trap_type),
-            };
             let zimm = f.rs1;
-            // warn!("CSRRCI_zimm:{zimm:x}");
-            // warn!("CSRRCI_pre:{t:x}");
-
-            let csr_wb_data = t & !zimm;
-            // warn!("CSRRCI_now:{csr_wb_data:x}");
-            if t != csr_wb_data {
-                let csr_ret = cpu.csr_regs.write(f.csr, csr_wb_data, cpu.cur_priv.get());
-                csr_ret?;
-            }
-            cpu.gpr.write(f.rd, t);
-
-            Ok(())
+            csr_op(cpu, &f, true, zimm != 0, |t| t & !zimm)
         },
     },
     Instruction {
@@ -289,23 +293,8 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         operation: |cpu, inst, pc| {
             // t = CSRs[csr]; CSRs[csr] = t | zimm; x[rd] = t
             let f = parse_format_csr(inst);
-            let csr_ret = cpu.csr_regs.read(f.csr, cpu.cur_priv.get());
-
-            let t = match csr_ret {
-                Ok(val) => val,
-                Err(trap_type) => return Err(trap_type),
-            };
             let zimm = f.rs1;
-            // warn!("CSRRSI_pre:{t:x}");
-            let csr_wb_data = t | zimm;
-            // warn!("CSRRSI_now:{csr_wb_data:x}");
-            if t != csr_wb_data {
-                let csr_ret = cpu.csr_regs.write(f.csr, csr_wb_data, cpu.cur_priv.get());
-                csr_ret?;
-            }
-            cpu.gpr.write(f.rd, t);
-
-            Ok(())
+            csr_op(cpu, &f, true, zimm != 0, |t| t | zimm)
         },
     },
     Instruction {
@@ -315,28 +304,66 @@ pub const INSTRUCTIONS_Z: &[Instruction] = &[
         operation: |cpu, inst, pc| {
             // x[rd] = CSRs[csr]; CSRs[csr] = zimm
             let f = parse_format_csr(inst);
-
-            let csr_ret = cpu.csr_regs.read(f.csr, cpu.cur_priv.get());
-
-            let t = match csr_ret {
-                Ok(val) => val,
-                Err(trap_type) => return Err(trap_type),
-            };
             let zimm = f.rs1;
-            // warn!("CSRRWI_pre:{t:x}");
-            let csr_wb_data = zimm;
-            // warn!("CSRRWI_now:{csr_wb_data:x}");
-            if t != csr_wb_data {
-                let csr_ret = cpu.csr_regs.write(f.csr, csr_wb_data, cpu.cur_priv.get());
-                csr_ret?;
-            }
-            cpu.gpr.write(f.rd, t);
-
-            Ok(())
+            csr_op(cpu, &f, f.rd != 0, true, |_| zimm)
         },
     },
 ];
 
+// CSRRW(I) with rd=x0 does not read the csr, CSRRS(I) and CSRRC(I) with rs1=x0
+// or uimm=0 do not write it. the other ones always write, a read-only csr or
+// one of a higher privilege is illegal even if the value is unchanged
+fn csr_op(
+    cpu: &mut crate::rv64core::cpu_core::CpuCore,
+    f: &FormatCSR,
+    read: bool,
+    write: bool,
+    wb_data: impl Fn(u64) -> u64,
+) -> Result<(), TrapType> {
+    let t = match read {
+        true => cpu.csr_regs.read(f.csr, cpu.cur_priv.get())?,
+        false => 0,
+    };
+    if write {
+        csr_write(cpu, f.csr, wb_data(t))?;
+    }
+    if read {
+        cpu.gpr.write(f.rd, t);
+    }
+    Ok(())
+}
+
+// SRET in VS-mode returns with vsstatus and vsepc, V is not changed.
+// SRET in VU-mode, or in VS-mode when hstatus.VTSR=1, is a virtual instruction
+fn sret_vs(cpu: &mut crate::rv64core::cpu_core::CpuCore, inst: u32) -> Result<(), TrapType> {
+    let vtsr = cpu.csr_regs.h.hstatus.get().vtsr();
+    if cpu.cur_priv.get() == PrivilegeLevels::User || vtsr {
+        return Err(TrapType::VirtualInstruction(inst.into()));
+    }
+    let mut vsstatus = cpu.csr_regs.h.vsstatus.get();
+    let y = vsstatus.get_spp_priv();
+    vsstatus.set_sie(vsstatus.spie());
+    vsstatus.set_spie(true);
+    vsstatus.set_spp(false);
+    cpu.csr_regs.h.vsstatus.set(vsstatus);
+    cpu.cur_priv.set(y);
+    cpu.npc = cpu.csr_regs.xepc(cpu.csr_regs.h.vsepc.get());
+    Ok(())
+}
+
+// a misa write turning off C is ignored if the next instruction is not 4-byte aligned
+fn csr_write(
+    cpu: &mut crate::rv64core::cpu_core::CpuCore,
+    csr: u64,
+    data: u64,
+) -> Result<(), TrapType> {
+    let c_off = cpu.csr_regs.misa_ext(b'c') && data & 1 << (b'c' - b'a') == 0;
+    if csr == CSR_MISA.into() && c_off && cpu.npc & 0b10 != 0 {
+        return Ok(());
+    }
+    cpu.csr_regs.write(csr, data, cpu.cur_priv.get())
+}
+
 pub fn handle_ebreak(
     cpu: &mut crate::rv64core::cpu_core::CpuCore,
     pc: u64,
@@ -381,3 +408,202 @@ pub fn handle_ebreak(
     cpu.halt();
     Ok(())
 }
+
+#[cfg(test)]
+mod test_rv64z {
+    use crate::{
+        config::Config,
+        rv64core::{
+            cpu_core::{CpuCore, CpuState},
+            csr_regs_define::XipIn,
+            inst::inst_base::{
+                PrivilegeLevels, CSR_MEPC, CSR_MISA, MATCH_MRET, MATCH_SRET, MATCH_WFI,
+            },
+            test_cpu::{exec, test_bus, test_cpu, test_cpu_on},
+            traptype::TrapType,
+        },
+    };
+
+    #[test]
+    fn tsr_tvm_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        config.set_u_mode();
+        let mut cpu = test_cpu_on(&test_bus(0), config);
+        let exec_in = |cpu: &mut CpuCore, inst: u32, privi| {
+            cpu.cur_priv.set(privi);
+            exec(cpu, inst)
+        };
+        let (m, s, u) = (
+            PrivilegeLevels::Machine,
+            PrivilegeLevels::Supervisor,
+            PrivilegeLevels::User,
+        );
+        // sfence.vma x0,x0 and csrr a0,satp
+        let (sfence, csrr_satp) = (0x12000073, 0x18002573);
+        let illegal = |inst: u32| Err(TrapType::IllegalInstruction(inst.into()));
+
+        assert_eq!(exec_in(&mut cpu, MATCH_MRET, s), illegal(MATCH_MRET));
+        assert_eq!(exec_in(&mut cpu, MATCH_SRET, u), illegal(MATCH_SRET));
+        assert!(exec_in(&mut cpu, sfence, s).is_ok());
+        assert!(exec_in(&mut cpu, csrr_satp, s).is_ok());
+
+        let mut mstatus = cpu.csr_regs.xstatus.get();
+        mstatus.set_tsr(true);
+        mstatus.set_tvm(true);
+        cpu.csr_regs.xstatus.set(mstatus);
+        assert_eq!(exec_in(&mut cpu, MATCH_SRET, s), illegal(MATCH_SRET));
+        assert_eq!(exec_in(&mut cpu, sfence, s), illegal(sfence));
+        assert_eq!(
+            exec_in(&mut cpu, csrr_satp, s),
+            Err(TrapType::IllegalInstruction(0))
+        );
+        // M-mode is not trapped
+        assert!(exec_in(&mut cpu, sfence, m).is_ok());
+        assert!(exec_in(&mut cpu, csrr_satp, m).is_ok());
+        assert!(exec_in(&mut cpu, MATCH_SRET, m).is_ok());
+    }
+
+    #[test]
+    fn zicsr_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_u_mode();
+        let mut cpu = test_cpu_on(&test_bus(0), config);
+        let illegal = Err(TrapType::IllegalInstruction(0));
+
+        // csrr a0,cycle reads, csrrw x0,cycle,x0 and csrrs a0,cycle,x1 with x1=0 write
+        cpu.csr_regs.cycle.set(0);
+        assert!(exec(&mut cpu, 0xc0002573).is_ok());
+        assert_eq!(exec(&mut cpu, 0xc0001073), illegal);
+        assert_eq!(exec(&mut cpu, 0xc000a573), illegal);
+        // csrrwi x0,0x7c0,1, not implemented
+        assert_eq!(exec(&mut cpu, 0x7c00d073), illegal);
+        // csrrw x0,mscratch,x1 needs M-mode
+        cpu.gpr.write(1, 0x55);
+        assert!(exec(&mut cpu, 0x34009073).is_ok());
+        cpu.cur_priv.set(PrivilegeLevels::User);
+        assert_eq!(exec(&mut cpu, 0x34009073), illegal);
+        assert_eq!(cpu.csr_regs.read_raw(0x340), 0x55);
+    }
+
+    #[test]
+    fn wfi_test() {
+        let wfi = |timeout: u64| {
+            let mut config = Config::new();
+            config.set_isa("rv64imac");
+            config.set_mmu_type("sv39");
+            config.set_s_mode();
+            config.set_u_mode();
+            config.set_wfi_timeout(timeout);
+            let mut cpu = test_cpu_on(&test_bus(0), config);
+            cpu.cpu_state = CpuState::Running;
+            cpu.csr_regs.xie.set(cpu.csr_regs.xie.get().with_mtie(true));
+            cpu
+        };
+        let run = |cpu: &mut CpuCore| {
+            let operation = cpu.decode.fast_path(MATCH_WFI).unwrap().operation;
+            operation(cpu, MATCH_WFI, cpu.pc)
+        };
+
+        // sleeps until mtip, MIE=0 so it only resumes after the WFI
+        let mut cpu = wfi(0);
+        run(&mut cpu).unwrap();
+        assert_eq!(cpu.cpu_state, CpuState::Wfi);
+        let cycle = cpu.csr_regs.cycle.get();
+        cpu.execute(100);
+        assert_eq!(cpu.cpu_state, CpuState::Wfi);
+        assert_eq!(cpu.csr_regs.cycle.get(), cycle + 100);
+        cpu.csr_regs.xip.set(XipIn::new().with_mtip(true));
+        cpu.npc = 0x1234;
+        cpu.execute(1);
+        assert_eq!(cpu.cpu_state, CpuState::Running);
+        assert_eq!(cpu.npc, 0x1234);
+        // an interrupt already pending, WFI is a nop
+        run(&mut cpu).unwrap();
+        assert_eq!(cpu.cpu_state, CpuState::Running);
+
+        // TW=1 from S-mode and WFI in U-mode trap at once
+        for (privi, tw) in [
+            (PrivilegeLevels::Supervisor, true),
+            (PrivilegeLevels::User, false),
+        ] {
+            let mut cpu = wfi(0);
+            let mut mstatus = cpu.csr_regs.xstatus.get();
+            mstatus.set_tw(tw);
+            cpu.csr_regs.xstatus.set(mstatus);
+            cpu.cur_priv.set(privi);
+            assert_eq!(
+                run(&mut cpu),
+                Err(TrapType::IllegalInstruction(MATCH_WFI.into()))
+            );
+        }
+        let mut cpu = wfi(0);
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        run(&mut cpu).unwrap();
+        assert_eq!(cpu.cpu_state, CpuState::Wfi);
+
+        // or after the timeout, mepc is the WFI
+        let mut cpu = wfi(50);
+        let mut mstatus = cpu.csr_regs.xstatus.get();
+        mstatus.set_tw(true);
+        cpu.csr_regs.xstatus.set(mstatus);
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        cpu.pc = 0x8000_0100;
+        run(&mut cpu).unwrap();
+        for _ in 0..49 {
+            cpu.execute(1);
+        }
+        assert_eq!(cpu.cpu_state, CpuState::Wfi);
+        cpu.execute(1);
+        assert_eq!(cpu.cpu_state, CpuState::Running);
+        assert_eq!(cpu.csr_regs.mcause.get().exception_code(), 2);
+        assert_eq!(cpu.csr_regs.mepc.get(), 0x8000_0100);
+        assert_eq!(cpu.cur_priv.get(), PrivilegeLevels::Machine);
+    }
+
+    #[test]
+    fn misa_test() {
+        let (mut cpu, _) = test_cpu("rv64imafdc", 0);
+        let m_mode = PrivilegeLevels::Machine;
+        let misa = cpu.csr_regs.read(CSR_MISA.into(), m_mode).unwrap();
+        // csrrw x0, misa, x1
+        let csrrw = |cpu: &mut CpuCore, data: u64, npc: u64| {
+            cpu.gpr.write(1, data);
+            cpu.npc = npc;
+            exec(cpu, 0x30109073).unwrap();
+            cpu.csr_regs.read(CSR_MISA.into(), m_mode).unwrap()
+        };
+        let (c, f, d, i) = (1 << 2, 1 << 5, 1 << 3, 1 << 8);
+
+        // c.li a0,0 and mul
+        assert!(cpu.decode.fast_path(0x4501).is_some());
+        // the next instruction is not 4-byte aligned, C stays on
+        assert_eq!(csrrw(&mut cpu, misa & !c, 0x8000_0002), misa);
+        assert_eq!(csrrw(&mut cpu, misa & !c, 0x8000_0004), misa & !c);
+        assert!(cpu.decode.fast_path(0x4501).is_none());
+        assert!(cpu.decode.fast_path(0x02b50533).is_some());
+        // IALIGN=32, bit 1 of mepc reads 0
+        cpu.csr_regs.mepc.set(0x8000_0102);
+        assert_eq!(
+            cpu.csr_regs.read(CSR_MEPC.into(), m_mode).unwrap(),
+            0x8000_0100
+        );
+
+        // D goes with F, I is read-only
+        assert_eq!(csrrw(&mut cpu, misa & !c & !f & !i, 0), misa & !c & !f & !d);
+        // fadd.d
+        assert!(cpu.decode.fast_path(0x02b57553).is_none());
+        assert_eq!(csrrw(&mut cpu, misa, 0), misa);
+        assert!(cpu.decode.fast_path(0x02b57553).is_some());
+        assert!(cpu.decode.fast_path(0x4501).is_some());
+
+        csrrw(&mut cpu, 0, 0);
+        assert!(cpu.decode.fast_path(0x02b50533).is_none());
+        cpu.csr_regs.reset();
+        assert_eq!(cpu.csr_regs.read(CSR_MISA.into(), m_mode).unwrap(), misa);
+        assert!(cpu.decode.fast_path(0x02b50533).is_some());
+    }
+}
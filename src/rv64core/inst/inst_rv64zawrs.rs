@@ -0,0 +1,81 @@
+use crate::rv64core::{cpu_core::CpuCore, inst::inst_base::*};
+
+// how long WRS.NTO gives up the host cpu, WRS.STO only yields
+#[cfg(feature = "std")]
+const WRS_NTO_SLEEP: std::time::Duration = std::time::Duration::from_micros(50);
+
+// the stall of WRS.NTO and WRS.STO ends when the reservation set is empty,
+// or an interrupt is pending (even if it is disabled).
+fn wrs_stall(cpu: &CpuCore) -> bool {
+    let pending = u64::from(cpu.csr_regs.xip.get()) & u64::from(cpu.csr_regs.xie.get());
+    cpu.lr_sc_reservation_valid() && pending == 0
+}
+
+// Zawrs: wait-on-reservation-set instructions
+// the stall ends at once (an implementation-defined short time), the other
+// harts make progress in the next round. the host thread is given up
+// if `Config::wrs_yield` is set, so a spinning guest does not burn the host cpu
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZAWRS: &[Instruction] = &[
+    Instruction {
+        mask: MASK_WRS_NTO,
+        match_data: MATCH_WRS_NTO,
+        name: "WRS_NTO",
+        operation: |cpu, inst, pc| {
+            #[cfg(feature = "std")]
+            if cpu.config.wrs_yield() && wrs_stall(cpu) {
+                std::thread::sleep(WRS_NTO_SLEEP);
+            }
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_WRS_STO,
+        match_data: MATCH_WRS_STO,
+        name: "WRS_STO",
+        operation: |cpu, inst, pc| {
+            #[cfg(feature = "std")]
+            if cpu.config.wrs_yield() && wrs_stall(cpu) {
+                std::thread::yield_now();
+            }
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zawrs {
+    use crate::{
+        config::Config,
+        rv64core::{
+            csr_regs_define::XipIn,
+            test_cpu::{exec, test_bus, test_cpu_on},
+        },
+    };
+
+    use super::wrs_stall;
+
+    #[test]
+    fn zawrs_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imac_zawrs");
+        config.set_wrs_yield(true);
+        let mut cpu = test_cpu_on(&test_bus(0), config);
+
+        // no reservation, a nop
+        assert!(!wrs_stall(&cpu));
+        cpu.lr_sc_reservation_set(0x8000_0000);
+        assert!(wrs_stall(&cpu));
+        for inst in [0x00d00073, 0x01d00073] {
+            exec(&mut cpu, inst).unwrap();
+        }
+        // a pending interrupt ends the stall
+        cpu.csr_regs.xip.set(XipIn::new().with_mtip(true));
+        cpu.csr_regs.xie.set(cpu.csr_regs.xie.get().with_mtie(true));
+        assert!(!wrs_stall(&cpu));
+        cpu.csr_regs.xip.set(XipIn::new());
+        // the reservation is lost by a sc
+        assert!(cpu.lr_sc_reservation_check_and_clear(0x8000_0000));
+        assert!(!wrs_stall(&cpu));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::rv64core::inst::inst_base::*;
+
+// Zba: address generation instructions
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZBA: &[Instruction] = &[
+    Instruction {
+        mask: MASK_ADD_UW,
+        match_data: MATCH_ADD_UW,
+        name: "ADD_UW",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] + zext(x[rs1][31:0])
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = (rs1 & 0xffff_ffff).wrapping_add(rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SH1ADD,
+        match_data: MATCH_SH1ADD,
+        name: "SH1ADD",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] + (x[rs1] << 1)
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = (rs1 << 1).wrapping_add(rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SH1ADD_UW,
+        match_data: MATCH_SH1ADD_UW,
+        name: "SH1ADD_UW",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] + (zext(x[rs1][31:0]) << 1)
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = ((rs1 & 0xffff_ffff) << 1).wrapping_add(rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SH2ADD,
+        match_data: MATCH_SH2ADD,
+        name: "SH2ADD",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] + (x[rs1] << 2)
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = (rs1 << 2).wrapping_add(rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SH2ADD_UW,
+        match_data: MATCH_SH2ADD_UW,
+        name: "SH2ADD_UW",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] + (zext(x[rs1][31:0]) << 2)
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = ((rs1 & 0xffff_ffff) << 2).wrapping_add(rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SH3ADD,
+        match_data: MATCH_SH3ADD,
+        name: "SH3ADD",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] + (x[rs1] << 3)
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = (rs1 << 3).wrapping_add(rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SH3ADD_UW,
+        match_data: MATCH_SH3ADD_UW,
+        name: "SH3ADD_UW",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] + (zext(x[rs1][31:0]) << 3)
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = ((rs1 & 0xffff_ffff) << 3).wrapping_add(rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SLLI_UW,
+        match_data: MATCH_SLLI_UW,
+        name: "SLLI_UW",
+        operation: |cpu, inst, pc| {
+            // x[rd] = zext(x[rs1][31:0]) << shamt
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let shamt = (f.imm & 0x3f) as u64;
+
+            let wb_data = (rs1 & 0xffff_ffff) << shamt;
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zba {
+    use crate::rv64core::test_cpu::{check_a0, test_cpu};
+
+    #[test]
+    fn zba_test() {
+        let (mut cpu, _) = test_cpu("rv64imac_zba", 0);
+        cpu.gpr.write(11, 0xffff_ffff_8000_0001); // a1
+        cpu.gpr.write(12, 0x1000); // a2
+
+        let cases = [
+            (0x08c5853b, 0x8000_1001),           // add.uw a0, a1, a2
+            (0x20c5a533, 0xffff_ffff_0000_1002), // sh1add a0, a1, a2
+            (0x20c5c53b, 0x2_0000_1004),         // sh2add.uw a0, a1, a2
+            (0x20c5e533, 0xffff_fffc_0000_1008), // sh3add a0, a1, a2
+            (0x0835951b, 0x4_0000_0008),         // slli.uw a0, a1, 3
+        ];
+        check_a0(&mut cpu, &cases);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::rv64core::inst::inst_base::*;
+
+// carry-less product of rs1 and rs2, (high, low) 64 bits
+fn clmul128(rs1: u64, rs2: u64) -> (u64, u64) {
+    (0..64)
+        .filter(|i| (rs2 >> i) & 1 == 1)
+        .fold((0, 0), |(high, low), i| {
+            let high_part = if i == 0 { 0 } else { rs1 >> (64 - i) };
+            (high ^ high_part, low ^ (rs1 << i))
+        })
+}
+
+// Zbc: carry-less multiplication
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZBC: &[Instruction] = &[
+    Instruction {
+        mask: MASK_CLMUL,
+        match_data: MATCH_CLMUL,
+        name: "CLMUL",
+        operation: |cpu, inst, pc| {
+            // x[rd] = clmul(x[rs1], x[rs2])[63:0]
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let (_, wb_data) = clmul128(rs1, rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_CLMULH,
+        match_data: MATCH_CLMULH,
+        name: "CLMULH",
+        operation: |cpu, inst, pc| {
+            // x[rd] = clmul(x[rs1], x[rs2])[127:64]
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let (wb_data, _) = clmul128(rs1, rs2);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_CLMULR,
+        match_data: MATCH_CLMULR,
+        name: "CLMULR",
+        operation: |cpu, inst, pc| {
+            // x[rd] = clmul(x[rs1], x[rs2])[126:63]
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let (high, low) = clmul128(rs1, rs2);
+            let wb_data = (high << 1) | (low >> 63);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zbc {
+    use crate::rv64core::test_cpu::{check_a0, test_cpu};
+
+    #[test]
+    fn zbc_test() {
+        let (mut cpu, _) = test_cpu("rv64imac_zbc", 0);
+        cpu.gpr.write(11, 0x8000_0000_0000_0003); // a1
+        cpu.gpr.write(12, 0x7); // a2
+
+        let cases = [
+            (0x0ac59533, 0x8000_0000_0000_0009), // clmul a0, a1, a2
+            (0x0ac5b533, 0x3),                   // clmulh a0, a1, a2
+            (0x0ac5a533, 0x7),                   // clmulr a0, a1, a2
+        ];
+        check_a0(&mut cpu, &cases);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::rv64core::inst::inst_base::*;
+
+// Zbs: single-bit instructions
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZBS: &[Instruction] = &[
+    Instruction {
+        mask: MASK_BCLR,
+        match_data: MATCH_BCLR,
+        name: "BCLR",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs1] & ~(1 << (x[rs2] & 63))
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let index = cpu.gpr.read(f.rs2) & 0x3f;
+
+            let wb_data = rs1 & !(1 << index);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_BCLRI,
+        match_data: MATCH_BCLRI,
+        name: "BCLRI",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs1] & ~(1 << (shamt))
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let index = (f.imm & 0x3f) as u64;
+
+            let wb_data = rs1 & !(1 << index);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_BEXT,
+        match_data: MATCH_BEXT,
+        name: "BEXT",
+        operation: |cpu, inst, pc| {
+            // x[rd] = (x[rs1] >> (x[rs2] & 63)) & 1
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let index = cpu.gpr.read(f.rs2) & 0x3f;
+
+            let wb_data = (rs1 >> index) & 1;
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_BEXTI,
+        match_data: MATCH_BEXTI,
+        name: "BEXTI",
+        operation: |cpu, inst, pc| {
+            // x[rd] = (x[rs1] >> (shamt)) & 1
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let index = (f.imm & 0x3f) as u64;
+
+            let wb_data = (rs1 >> index) & 1;
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_BINV,
+        match_data: MATCH_BINV,
+        name: "BINV",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs1] ^ (1 << (x[rs2] & 63))
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let index = cpu.gpr.read(f.rs2) & 0x3f;
+
+            let wb_data = rs1 ^ (1 << index);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_BINVI,
+        match_data: MATCH_BINVI,
+        name: "BINVI",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs1] ^ (1 << (shamt))
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let index = (f.imm & 0x3f) as u64;
+
+            let wb_data = rs1 ^ (1 << index);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_BSET,
+        match_data: MATCH_BSET,
+        name: "BSET",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs1] | (1 << (x[rs2] & 63))
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let index = cpu.gpr.read(f.rs2) & 0x3f;
+
+            let wb_data = rs1 | (1 << index);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_BSETI,
+        match_data: MATCH_BSETI,
+        name: "BSETI",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs1] | (1 << (shamt))
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let index = (f.imm & 0x3f) as u64;
+
+            let wb_data = rs1 | (1 << index);
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zbs {
+    use crate::rv64core::test_cpu::{check_a0, test_cpu};
+
+    #[test]
+    fn zbs_test() {
+        let (mut cpu, _) = test_cpu("rv64imac_zbs", 0);
+        cpu.gpr.write(11, 0x8000_0000_0000_00f0); // a1
+        cpu.gpr.write(12, 0x44); // a2, only the low 6 bits are used
+
+        let cases = [
+            (0x48c59533, 0x8000_0000_0000_00e0), // bclr a0, a1, a2
+            (0x4bf59513, 0xf0),                  // bclri a0, a1, 63
+            (0x48c5d533, 0x1),                   // bext a0, a1, a2
+            (0x4bf5d513, 0x1),                   // bexti a0, a1, 63
+            (0x68c59533, 0x8000_0000_0000_00e0), // binv a0, a1, a2
+            (0x6bf59513, 0xf0),                  // binvi a0, a1, 63
+            (0x28c59533, 0x8000_0000_0000_00f0), // bset a0, a1, a2
+            (0x28159513, 0x8000_0000_0000_00f2), // bseti a0, a1, 1
+        ];
+        check_a0(&mut cpu, &cases);
+    }
+}
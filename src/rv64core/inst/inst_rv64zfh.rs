@@ -0,0 +1,393 @@
+use core::cmp::Ordering;
+
+use rustc_apfloat::ieee::{Double, Half, Single};
+use rustc_apfloat::Float;
+
+use crate::rv64core::{
+    cpu_core::CpuCore,
+    inst::{inst_base::*, inst_rv64f::*},
+};
+
+impl FpReg for Half {
+    fn read_fpr(cpu: &CpuCore, idx: u64) -> Self {
+        Half::from_bits(cpu.fpr.read_f16(idx).into())
+    }
+    fn write_fpr(cpu: &mut CpuCore, idx: u64, val: Self) {
+        cpu.fpr.write_f16(idx, val.to_bits() as u16);
+    }
+}
+
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZFH: &[Instruction] = &[
+    Instruction {
+        mask: MASK_FLH,
+        match_data: MATCH_FLH,
+        name: "FLH",
+        operation: |cpu, inst, pc| {
+            // f[rd] = M[x[rs1] + sext(offset)][15:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_i(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i64;
+            let mem_addr = rs1.wrapping_add(f.imm) as u64;
+            let mem_data = cpu.read(mem_addr, 2, AccessType::Load(mem_addr))?;
+            cpu.fpr.write_f16(f.rd, mem_data as u16);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FSH,
+        match_data: MATCH_FSH,
+        name: "FSH",
+        operation: |cpu, inst, pc| {
+            // M[x[rs1] + sext(offset)] = f[rs2][15:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_s(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as i64;
+            let rs2 = cpu.fpr.read(f.rs2) as u16;
+            let mem_addr = rs1.wrapping_add(f.imm) as u64;
+            cpu.write(mem_addr, rs2.into(), 2, AccessType::Store(mem_addr))?;
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FMADD_H,
+        match_data: MATCH_FMADD_H,
+        name: "FMADD_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1]×f[rs2]+f[rs3]
+            fp_op3::<Half>(cpu, inst, fp_fma)
+        },
+    },
+    Instruction {
+        mask: MASK_FMSUB_H,
+        match_data: MATCH_FMSUB_H,
+        name: "FMSUB_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1]×f[rs2]-f[rs3]
+            fp_op3::<Half>(cpu, inst, |a, b, c, rm| fp_fma(a, b, -c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FNMSUB_H,
+        match_data: MATCH_FNMSUB_H,
+        name: "FNMSUB_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = -f[rs1]×f[rs2]+f[rs3]
+            fp_op3::<Half>(cpu, inst, |a, b, c, rm| fp_fma(-a, b, c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FNMADD_H,
+        match_data: MATCH_FNMADD_H,
+        name: "FNMADD_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = -f[rs1]×f[rs2]-f[rs3]
+            fp_op3::<Half>(cpu, inst, |a, b, c, rm| fp_fma(-a, b, -c, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FADD_H,
+        match_data: MATCH_FADD_H,
+        name: "FADD_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] + f[rs2]
+            fp_op2::<Half>(cpu, inst, |a, b, rm| a.add_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSUB_H,
+        match_data: MATCH_FSUB_H,
+        name: "FSUB_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] - f[rs2]
+            fp_op2::<Half>(cpu, inst, |a, b, rm| a.sub_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FMUL_H,
+        match_data: MATCH_FMUL_H,
+        name: "FMUL_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] × f[rs2]
+            fp_op2::<Half>(cpu, inst, |a, b, rm| a.mul_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FDIV_H,
+        match_data: MATCH_FDIV_H,
+        name: "FDIV_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f[rs1] ÷ f[rs2]
+            fp_op2::<Half>(cpu, inst, |a, b, rm| a.div_r(b, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSQRT_H,
+        match_data: MATCH_FSQRT_H,
+        name: "FSQRT_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = √f[rs1]
+            fp_op2::<Half>(cpu, inst, |a, _, rm| fp_sqrt(a, rm))
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJ_H,
+        match_data: MATCH_FSGNJ_H,
+        name: "FSGNJ_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {f[rs2][15], f[rs1][14:0]}
+            fp_sgnj::<Half>(cpu, inst, 0)
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJN_H,
+        match_data: MATCH_FSGNJN_H,
+        name: "FSGNJN_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {~f[rs2][15], f[rs1][14:0]}
+            fp_sgnj::<Half>(cpu, inst, 1)
+        },
+    },
+    Instruction {
+        mask: MASK_FSGNJX_H,
+        match_data: MATCH_FSGNJX_H,
+        name: "FSGNJX_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = {f[rs1][15] ^ f[rs2][15], f[rs1][14:0]}
+            fp_sgnj::<Half>(cpu, inst, 2)
+        },
+    },
+    Instruction {
+        mask: MASK_FMIN_H,
+        match_data: MATCH_FMIN_H,
+        name: "FMIN_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = min(f[rs1], f[rs2])
+            fp_min_max_op::<Half>(cpu, inst, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FMAX_H,
+        match_data: MATCH_FMAX_H,
+        name: "FMAX_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = max(f[rs1], f[rs2])
+            fp_min_max_op::<Half>(cpu, inst, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_W_H,
+        match_data: MATCH_FCVT_W_H,
+        name: "FCVT_W_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = sext(s32_f16(f[rs1]))
+            fp_to_int_op::<Half>(cpu, inst, 32, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_WU_H,
+        match_data: MATCH_FCVT_WU_H,
+        name: "FCVT_WU_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = sext(u32_f16(f[rs1]))
+            fp_to_int_op::<Half>(cpu, inst, 32, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_L_H,
+        match_data: MATCH_FCVT_L_H,
+        name: "FCVT_L_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = s64_f16(f[rs1])
+            fp_to_int_op::<Half>(cpu, inst, 64, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_LU_H,
+        match_data: MATCH_FCVT_LU_H,
+        name: "FCVT_LU_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = u64_f16(f[rs1])
+            fp_to_int_op::<Half>(cpu, inst, 64, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_H_W,
+        match_data: MATCH_FCVT_H_W,
+        name: "FCVT_H_W",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f16_s32(x[rs1])
+            int_to_fp_op::<Half>(cpu, inst, 32, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_H_WU,
+        match_data: MATCH_FCVT_H_WU,
+        name: "FCVT_H_WU",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f16_u32(x[rs1])
+            int_to_fp_op::<Half>(cpu, inst, 32, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_H_L,
+        match_data: MATCH_FCVT_H_L,
+        name: "FCVT_H_L",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f16_s64(x[rs1])
+            int_to_fp_op::<Half>(cpu, inst, 64, true)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_H_LU,
+        match_data: MATCH_FCVT_H_LU,
+        name: "FCVT_H_LU",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f16_u64(x[rs1])
+            int_to_fp_op::<Half>(cpu, inst, 64, false)
+        },
+    },
+    Instruction {
+        mask: MASK_FEQ_H,
+        match_data: MATCH_FEQ_H,
+        name: "FEQ_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] == f[rs2]
+            fp_cmp_op::<Half>(cpu, inst, false, |x| x == Ordering::Equal)
+        },
+    },
+    Instruction {
+        mask: MASK_FLT_H,
+        match_data: MATCH_FLT_H,
+        name: "FLT_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] < f[rs2]
+            fp_cmp_op::<Half>(cpu, inst, true, |x| x == Ordering::Less)
+        },
+    },
+    Instruction {
+        mask: MASK_FLE_H,
+        match_data: MATCH_FLE_H,
+        name: "FLE_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = f[rs1] <= f[rs2]
+            fp_cmp_op::<Half>(cpu, inst, true, |x| x != Ordering::Greater)
+        },
+    },
+    Instruction {
+        mask: MASK_FCLASS_H,
+        match_data: MATCH_FCLASS_H,
+        name: "FCLASS_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = classifyh(f[rs1])
+            fp_class_op::<Half>(cpu, inst)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_S_H,
+        match_data: MATCH_FCVT_S_H,
+        name: "FCVT_S_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f32_f16(f[rs1])
+            fp_convert_op::<Half, Single>(cpu, inst)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_H_S,
+        match_data: MATCH_FCVT_H_S,
+        name: "FCVT_H_S",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f16_f32(f[rs1])
+            fp_convert_op::<Single, Half>(cpu, inst)
+        },
+    },
+    Instruction {
+        mask: MASK_FMV_X_H,
+        match_data: MATCH_FMV_X_H,
+        name: "FMV_X_H",
+        operation: |cpu, inst, pc| {
+            // x[rd] = sext(f[rs1][15:0])
+            check_fs(cpu, inst)?;
+            let f = parse_format_r(inst);
+            let rs1 = cpu.fpr.read(f.rs1) as u16;
+            cpu.gpr.write(f.rd, rs1 as i16 as i64 as u64);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_FMV_H_X,
+        match_data: MATCH_FMV_H_X,
+        name: "FMV_H_X",
+        operation: |cpu, inst, pc| {
+            // f[rd] = x[rs1][15:0]
+            check_fs(cpu, inst)?;
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1) as u16;
+            cpu.fpr.write_f16(f.rd, rs1);
+            Ok(())
+        },
+    },
+];
+
+// conversions between half and double, only with D
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZFH_D: &[Instruction] = &[
+    Instruction {
+        mask: MASK_FCVT_D_H,
+        match_data: MATCH_FCVT_D_H,
+        name: "FCVT_D_H",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f64_f16(f[rs1])
+            fp_convert_op::<Half, Double>(cpu, inst)
+        },
+    },
+    Instruction {
+        mask: MASK_FCVT_H_D,
+        match_data: MATCH_FCVT_H_D,
+        name: "FCVT_H_D",
+        operation: |cpu, inst, pc| {
+            // f[rd] = f16_f64(f[rs1])
+            fp_convert_op::<Double, Half>(cpu, inst)
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zfh {
+    use rustc_apfloat::{ieee::Half, Float, Round, Status};
+
+    use crate::rv64core::inst::inst_rv64f::{fp_sqrt, fp_to_int};
+
+    fn f16(bits: u16) -> Half {
+        Half::from_bits(bits.into())
+    }
+
+    #[test]
+    fn sqrt_test() {
+        let cases = [
+            (0x4000, 0x3da8, Status::INEXACT), // 2.0
+            (0x4880, 0x4200, Status::OK),      // 9.0
+            (0x0001, 0x0c00, Status::OK),      // 2^-24
+        ];
+        for (x, expect, status) in cases {
+            let ret = fp_sqrt(f16(x), Round::NearestTiesToEven);
+            assert_eq!(ret.value.to_bits(), expect, "sqrt({x:x})");
+            assert_eq!(ret.status, status, "sqrt({x:x})");
+        }
+    }
+
+    #[test]
+    fn convert_test() {
+        // 65504.0, the largest half
+        let ret = fp_to_int(f16(0x7bff), Round::TowardZero, 64, true);
+        assert_eq!(ret.value, 65504);
+        // -1.5
+        let ret = fp_to_int(f16(0xbe00), Round::NearestTiesToEven, 32, true);
+        assert_eq!(ret.value, -2i64 as u64);
+        assert_eq!(ret.status, Status::INEXACT);
+        // -inf saturates to 0 for unsigned
+        let ret = fp_to_int(f16(0xfc00), Round::TowardZero, 64, false);
+        assert_eq!(ret.value, 0);
+        assert_eq!(ret.status, Status::INVALID_OP);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::rv64core::{
+    cpu_core::CpuCore, csr_regs_define::EnvcfgIn, inst::inst_base::*, traptype::TrapType,
+};
+
+// the cache block size reported to the software, the same as the dcache line
+pub const CBO_BLOCK_SIZE: u64 = 64;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CboOp {
+    Clean,
+    Flush,
+    Inval,
+}
+
+// the enable bits of a cbo instruction, M-mode is always allowed. below M-mode
+// menvcfg gives an illegal instruction, in VS-mode and VU-mode henvcfg gives a
+// virtual instruction, senvcfg applies to U-mode and VU-mode
+fn cbo_enabled(cpu: &CpuCore, inst: u32, enable: fn(EnvcfgIn) -> bool) -> Result<(), TrapType> {
+    let privi = cpu.cur_priv.get();
+    if privi == PrivilegeLevels::Machine {
+        return Ok(());
+    }
+    if !enable(cpu.csr_regs.menvcfg.get()) {
+        return Err(TrapType::IllegalInstruction(inst.into()));
+    }
+    let u_mode = privi == PrivilegeLevels::User && cpu.config.s_mode();
+    let s_enabled = !u_mode || enable(cpu.csr_regs.senvcfg.get());
+    if cpu.csr_regs.virt() {
+        if !enable(cpu.csr_regs.h.henvcfg.get()) || !s_enabled {
+            return Err(TrapType::VirtualInstruction(inst.into()));
+        }
+    } else if !s_enabled {
+        return Err(TrapType::IllegalInstruction(inst.into()));
+    }
+    Ok(())
+}
+
+// cbo.inval is executed as a flush or an invalidate depending on CBIE,
+// the weakest of the settings wins
+fn cbo_inval_op(cpu: &CpuCore, inst: u32) -> Result<CboOp, TrapType> {
+    cbo_enabled(cpu, inst, |envcfg| envcfg.cbie() != 0)?;
+    let privi = cpu.cur_priv.get();
+    let mut cbie = match privi {
+        PrivilegeLevels::Machine => 0b11,
+        _ => cpu.csr_regs.menvcfg.get().cbie(),
+    };
+    if cpu.csr_regs.virt() {
+        cbie &= cpu.csr_regs.h.henvcfg.get().cbie();
+    }
+    if privi == PrivilegeLevels::User && cpu.config.s_mode() {
+        cbie &= cpu.csr_regs.senvcfg.get().cbie();
+    }
+    match cbie {
+        0b01 => Ok(CboOp::Flush),
+        _ => Ok(CboOp::Inval),
+    }
+}
+
+// clean, flush and inval need read or write permission of the block,
+// a fault is reported as a store fault
+fn cbo_translate(cpu: &mut CpuCore, base: u64) -> Result<u64, TrapType> {
+    let len = CBO_BLOCK_SIZE as usize;
+    cpu.mmu.update_access_type(&AccessType::Load(base));
+    match cpu.mmu.translate(base, len) {
+        Ok(paddr) => Ok(paddr),
+        Err(_) => {
+            cpu.mmu.update_access_type(&AccessType::Store(base));
+            cpu.mmu.translate(base, len)
+        }
+    }
+}
+
+fn cbo_manage(cpu: &mut CpuCore, inst: u32, op: CboOp) -> Result<(), TrapType> {
+    let f = parse_format_i(inst);
+    let base = cpu.gpr.read(f.rs1) & !(CBO_BLOCK_SIZE - 1);
+    let paddr = cbo_translate(cpu, base)?;
+
+    let mut cache_system = cpu.cache_system.borrow_mut();
+    match op {
+        CboOp::Clean => cache_system.dcache.clean(paddr),
+        CboOp::Flush => cache_system.dcache.flush(paddr),
+        CboOp::Inval => cache_system.dcache.invalidate(paddr),
+    }
+    Ok(())
+}
+
+// Zicbom: cache-block management instructions
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZICBOM: &[Instruction] = &[
+    Instruction {
+        mask: MASK_CBO_CLEAN,
+        match_data: MATCH_CBO_CLEAN,
+        name: "CBO_CLEAN",
+        operation: |cpu, inst, pc| {
+            cbo_enabled(cpu, inst, |envcfg| envcfg.cbcfe())?;
+            cbo_manage(cpu, inst, CboOp::Clean)
+        },
+    },
+    Instruction {
+        mask: MASK_CBO_FLUSH,
+        match_data: MATCH_CBO_FLUSH,
+        name: "CBO_FLUSH",
+        operation: |cpu, inst, pc| {
+            cbo_enabled(cpu, inst, |envcfg| envcfg.cbcfe())?;
+            cbo_manage(cpu, inst, CboOp::Flush)
+        },
+    },
+    Instruction {
+        mask: MASK_CBO_INVAL,
+        match_data: MATCH_CBO_INVAL,
+        name: "CBO_INVAL",
+        operation: |cpu, inst, pc| {
+            let op = cbo_inval_op(cpu, inst)?;
+            cbo_manage(cpu, inst, op)
+        },
+    },
+];
+
+// Zicboz: cache-block zero instructions
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZICBOZ: &[Instruction] = &[Instruction {
+    mask: MASK_CBO_ZERO,
+    match_data: MATCH_CBO_ZERO,
+    name: "CBO_ZERO",
+    operation: |cpu, inst, pc| {
+        cbo_enabled(cpu, inst, |envcfg| envcfg.cbze())?;
+        let f = parse_format_i(inst);
+        let base = cpu.gpr.read(f.rs1) & !(CBO_BLOCK_SIZE - 1);
+        let access_type = AccessType::Store(base);
+        cpu.mmu.update_access_type(&access_type);
+        let paddr = cpu.mmu.translate(base, CBO_BLOCK_SIZE as usize)?;
+
+        let mut cache_system = cpu.cache_system.borrow_mut();
+        for offset in (0..CBO_BLOCK_SIZE).step_by(8) {
+            cache_system
+                .dcache
+                .write(paddr + offset, 0, 8)
+                .map_err(|_| access_type.throw_access_exception())?;
+        }
+        Ok(())
+    },
+}];
+
+#[cfg(test)]
+mod test_rv64zicbo {
+    use crate::{
+        config::Config,
+        device::device_trait::MEM_BASE,
+        rv64core::{
+            cpu_core::CpuCore,
+            csr_regs_define::EnvcfgIn,
+            inst::inst_base::{AccessType, PrivilegeLevels, CSR_HENVCFG, CSR_MENVCFG},
+            test_cpu::{exec, test_bus, test_cpu_on},
+            traptype::TrapType,
+        },
+    };
+
+    const MEM_SIZE: u64 = 4096;
+
+    #[test]
+    fn zicbo_test() {
+        let bus = test_bus(MEM_SIZE);
+        let mut config = Config::new();
+        config.set_isa("rv64imac_zicbom_zicboz");
+        config.set_dcache_size(16);
+        // no PMP entries, S-mode and U-mode can access all memory
+        config.set_pmp_entries(0);
+        let mut cpu = test_cpu_on(&bus, config);
+        let ram = |addr: u64| bus.borrow_mut().read(addr, 8).unwrap();
+        let store = |cpu: &mut CpuCore, addr: u64, data: u64| {
+            cpu.write(addr, data, 8, AccessType::Store(addr)).unwrap();
+        };
+
+        // a0 points into the middle of the second block
+        cpu.gpr.write(10, MEM_BASE + 0x48);
+
+        // cbo.clean (a0), the dirty line is written back
+        store(&mut cpu, MEM_BASE + 0x40, 0x1234);
+        assert_eq!(ram(MEM_BASE + 0x40), 0);
+        exec(&mut cpu, 0x0015200f).unwrap();
+        assert_eq!(ram(MEM_BASE + 0x40), 0x1234);
+
+        // cbo.inval (a0), the dirty data is dropped
+        store(&mut cpu, MEM_BASE + 0x40, 0x5678);
+        exec(&mut cpu, 0x0005200f).unwrap();
+        assert_eq!(ram(MEM_BASE + 0x40), 0x1234);
+        let data = cpu.read(MEM_BASE + 0x40, 8, AccessType::Load(MEM_BASE + 0x40));
+        assert_eq!(data, Ok(0x1234));
+
+        // cbo.flush (a0)
+        store(&mut cpu, MEM_BASE + 0x78, 0x9abc);
+        exec(&mut cpu, 0x0025200f).unwrap();
+        assert_eq!(ram(MEM_BASE + 0x78), 0x9abc);
+
+        // cbo.zero (a0), the whole block reads zero
+        exec(&mut cpu, 0x0045200f).unwrap();
+        cpu.cache_system.borrow_mut().clear();
+        assert_eq!(ram(MEM_BASE + 0x40), 0);
+        assert_eq!(ram(MEM_BASE + 0x78), 0);
+
+        // S-mode, disabled by menvcfg
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        for inst in [0x0015200f, 0x0025200f, 0x0005200f, 0x0045200f] {
+            let ret = exec(&mut cpu, inst);
+            assert_eq!(ret, Err(TrapType::IllegalInstruction(inst.into())));
+        }
+        // cbie = 01, cbo.inval is executed as a flush
+        let menvcfg = cpu.csr_regs.menvcfg.get();
+        let menvcfg = menvcfg.with_cbie(0b01).with_cbcfe(true).with_cbze(true);
+        cpu.csr_regs.menvcfg.set(menvcfg);
+        store(&mut cpu, MEM_BASE + 0x40, 0xdead);
+        exec(&mut cpu, 0x0005200f).unwrap();
+        assert_eq!(ram(MEM_BASE + 0x40), 0xdead);
+        exec(&mut cpu, 0x0045200f).unwrap();
+    }
+
+    // VS-mode and VU-mode also need henvcfg, senvcfg applies to VU-mode
+    #[test]
+    fn zicbo_virt_test() {
+        let mut config = Config::new();
+        config.set_isa("rv64imach_zicbom_zicboz_svpbmt");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        config.set_u_mode();
+        config.set_pmp_entries(0);
+        let mut cpu = test_cpu_on(&test_bus(MEM_SIZE), config);
+        cpu.gpr.write(10, MEM_BASE + 0x40);
+        let cbo_zero = 0x0045200f;
+        let cbze = EnvcfgIn::new().with_cbze(true);
+        cpu.csr_regs.menvcfg.set(cbze);
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        cpu.csr_regs.h.virt.set(true);
+        let virtual_inst = Err(TrapType::VirtualInstruction(cbo_zero.into()));
+        assert_eq!(exec(&mut cpu, cbo_zero), virtual_inst);
+        cpu.csr_regs.h.henvcfg.set(cbze);
+        assert_eq!(exec(&mut cpu, cbo_zero), Ok(()));
+        cpu.cur_priv.set(PrivilegeLevels::User);
+        assert_eq!(exec(&mut cpu, cbo_zero), virtual_inst);
+        cpu.csr_regs.senvcfg.set(cbze);
+        assert_eq!(exec(&mut cpu, cbo_zero), Ok(()));
+
+        // henvcfg.PBMTE and STCE read 0 while menvcfg has them clear
+        let m_mode = PrivilegeLevels::Machine;
+        cpu.csr_regs.h.virt.set(false);
+        let pbmte: u64 = EnvcfgIn::new().with_pbmte(true).into();
+        cpu.csr_regs
+            .write(CSR_HENVCFG.into(), pbmte, m_mode)
+            .unwrap();
+        assert_eq!(cpu.csr_regs.read(CSR_HENVCFG.into(), m_mode), Ok(0));
+        cpu.csr_regs
+            .write(CSR_MENVCFG.into(), pbmte, m_mode)
+            .unwrap();
+        assert_eq!(cpu.csr_regs.read(CSR_HENVCFG.into(), m_mode), Ok(pbmte));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::rv64core::inst::inst_base::*;
+
+// Zicond: integer conditional operations
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZICOND: &[Instruction] = &[
+    Instruction {
+        mask: MASK_CZERO_EQZ,
+        match_data: MATCH_CZERO_EQZ,
+        name: "CZERO_EQZ",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] == 0 ? 0 : x[rs1]
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = if rs2 == 0 { 0 } else { rs1 };
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_CZERO_NEZ,
+        match_data: MATCH_CZERO_NEZ,
+        name: "CZERO_NEZ",
+        operation: |cpu, inst, pc| {
+            // x[rd] = x[rs2] != 0 ? 0 : x[rs1]
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            let rs2 = cpu.gpr.read(f.rs2);
+
+            let wb_data = if rs2 != 0 { 0 } else { rs1 };
+            cpu.gpr.write(f.rd, wb_data);
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zicond {
+    use crate::rv64core::test_cpu::{exec, test_cpu};
+
+    #[test]
+    fn zicond_test() {
+        let (mut cpu, _) = test_cpu("rv64imac_zicond", 0);
+        cpu.gpr.write(11, 0x1234); // a1
+
+        let cases = [
+            // (a2, inst, expect)
+            (0, 0x0ec5d533, 0),        // czero.eqz a0, a1, a2
+            (1, 0x0ec5d533, 0x1234),   // czero.eqz a0, a1, a2
+            (0, 0x0ec5f533, 0x1234),   // czero.nez a0, a1, a2
+            (u64::MAX, 0x0ec5f533, 0), // czero.nez a0, a1, a2
+        ];
+        for (rs2, inst, expect) in cases {
+            cpu.gpr.write(12, rs2);
+            exec(&mut cpu, inst).unwrap();
+            assert_eq!(cpu.gpr.read(10), expect, "{inst:x}");
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::rv64core::inst::inst_base::*;
+
+// Zihintpause: PAUSE is a FENCE with pred=W and succ=0, it gives up the
+// host thread if `Config::pause_yield` is set, so a guest spinlock does not
+// burn the host cpu
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZIHINTPAUSE: &[Instruction] = &[Instruction {
+    mask: MASK_PAUSE,
+    match_data: MATCH_PAUSE,
+    name: "PAUSE",
+    operation: |cpu, inst, pc| {
+        #[cfg(feature = "std")]
+        if cpu.config.pause_yield() {
+            std::thread::yield_now();
+        }
+        Ok(())
+    },
+}];
+
+// Zihintntl: the non-temporal locality hints are ADD/C.ADD with rd=x0,
+// there is no cache hierarchy to tune, so they are nops
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZIHINTNTL: &[Instruction] = &[
+    Instruction {
+        mask: MASK_NTL_P1,
+        match_data: MATCH_NTL_P1,
+        name: "NTL_P1",
+        operation: |cpu, inst, pc| Ok(()),
+    },
+    Instruction {
+        mask: MASK_NTL_PALL,
+        match_data: MATCH_NTL_PALL,
+        name: "NTL_PALL",
+        operation: |cpu, inst, pc| Ok(()),
+    },
+    Instruction {
+        mask: MASK_NTL_S1,
+        match_data: MATCH_NTL_S1,
+        name: "NTL_S1",
+        operation: |cpu, inst, pc| Ok(()),
+    },
+    Instruction {
+        mask: MASK_NTL_ALL,
+        match_data: MATCH_NTL_ALL,
+        name: "NTL_ALL",
+        operation: |cpu, inst, pc| Ok(()),
+    },
+];
+
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZIHINTNTL_C: &[Instruction] = &[
+    Instruction {
+        mask: MASK_C_NTL_P1,
+        match_data: MATCH_C_NTL_P1,
+        name: "c.ntl.p1",
+        operation: |cpu, inst, pc| Ok(()),
+    },
+    Instruction {
+        mask: MASK_C_NTL_PALL,
+        match_data: MATCH_C_NTL_PALL,
+        name: "c.ntl.pall",
+        operation: |cpu, inst, pc| Ok(()),
+    },
+    Instruction {
+        mask: MASK_C_NTL_S1,
+        match_data: MATCH_C_NTL_S1,
+        name: "c.ntl.s1",
+        operation: |cpu, inst, pc| Ok(()),
+    },
+    Instruction {
+        mask: MASK_C_NTL_ALL,
+        match_data: MATCH_C_NTL_ALL,
+        name: "c.ntl.all",
+        operation: |cpu, inst, pc| Ok(()),
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zihint {
+    use alloc::rc::Rc;
+
+    use crate::{
+        config::Config,
+        rv64core::{inst::inst_disasm::disassemble, inst_decode::InstDecode},
+    };
+
+    fn disasm(isa: &str, word: u32) -> alloc::string::String {
+        let mut config = Config::new();
+        config.set_isa(isa);
+        config.set_pause_yield(true);
+        let mut decoder = InstDecode::new(Rc::new(config));
+        let inst = decoder.fast_path(word).unwrap();
+        disassemble(inst, word, 0)
+    }
+
+    #[test]
+    fn zihint_test() {
+        let isa = "rv64imac_zihintpause_zihintntl";
+        assert_eq!(disasm(isa, 0x0100000f), "pause");
+        assert_eq!(disasm(isa, 0x00200033), "ntl.p1");
+        assert_eq!(disasm(isa, 0x00500033), "ntl.all");
+        assert_eq!(disasm(isa, 0x900e), "c.ntl.pall");
+        assert_eq!(disasm(isa, 0x9016), "c.ntl.all");
+        // the other rd=x0 hints are still plain ADD
+        assert_eq!(disasm(isa, 0x00600033), "add\tzero,zero,t1");
+        assert_eq!(disasm(isa, 0x901a), "c.add\tzero,t1");
+        // without the extensions the hints decode as the base instructions
+        assert_eq!(disasm("rv64imac", 0x0100000f), "fence\tw,0");
+        assert_eq!(disasm("rv64imac", 0x00200033), "add\tzero,zero,sp");
+        assert_eq!(disasm("rv64imac", 0x900a), "c.add\tzero,sp");
+    }
+}
@@ -0,0 +1,369 @@
+use crate::rv64core::{cpu_core::CpuCore, inst::inst_base::*, traptype::TrapType};
+
+const AES_SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const AES_INV_SBOX: [u8; 256] = {
+    let mut inv = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[AES_SBOX[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+};
+
+// the round constants of aes64ks1i, rnum 0xa is used by the AES-256 key schedule
+const AES_RCON: [u8; 11] = [
+    0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36, 0x00,
+];
+
+// multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x + 1
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            p ^= a;
+        }
+        a = (a << 1) ^ if a & 0x80 != 0 { 0x1b } else { 0 };
+        b >>= 1;
+    }
+    p
+}
+
+fn sub_bytes(x: u64, sbox: &[u8; 256]) -> u64 {
+    let bytes = x.to_le_bytes().map(|b| sbox[b as usize]);
+    u64::from_le_bytes(bytes)
+}
+
+// the state is rs2:rs1 in column-major order, byte 4 * col + row.
+// returns the low two columns after ShiftRows or InvShiftRows
+fn shift_rows(rs1: u64, rs2: u64, inverse: bool) -> u64 {
+    let state = ((rs2 as u128) << 64 | rs1 as u128).to_le_bytes();
+    let mut out = [0; 8];
+    for (k, byte) in out.iter_mut().enumerate() {
+        let (col, row) = (k / 4, k % 4);
+        let src_col = match inverse {
+            false => (col + row) % 4,
+            true => (col + 4 - row) % 4,
+        };
+        *byte = state[4 * src_col + row];
+    }
+    u64::from_le_bytes(out)
+}
+
+// MixColumns or InvMixColumns of the two 32-bit columns
+fn mix_columns(x: u64, inverse: bool) -> u64 {
+    let coef: [u8; 4] = match inverse {
+        false => [2, 3, 1, 1],
+        true => [14, 11, 13, 9],
+    };
+    let mut bytes = x.to_le_bytes();
+    for col in bytes.chunks_exact_mut(4) {
+        let a = [col[0], col[1], col[2], col[3]];
+        for (row, byte) in col.iter_mut().enumerate() {
+            *byte = (0..4).fold(0, |acc, i| acc ^ gf_mul(a[i], coef[(i + 4 - row) % 4]));
+        }
+    }
+    u64::from_le_bytes(bytes)
+}
+
+fn aes64_ks1i(rs1: u64, rnum: u8) -> u64 {
+    let tmp1 = (rs1 >> 32) as u32;
+    let rcon = AES_RCON[rnum as usize] as u32;
+    let tmp2 = if rnum == 0xa {
+        tmp1
+    } else {
+        tmp1.rotate_right(8)
+    };
+    let tmp3 = sub_bytes(tmp2 as u64, &AES_SBOX) as u32 ^ rcon;
+    (tmp3 as u64) << 32 | tmp3 as u64
+}
+
+fn aes64_ks2(rs1: u64, rs2: u64) -> u64 {
+    let w0 = (rs1 >> 32) as u32 ^ rs2 as u32;
+    let w1 = w0 ^ (rs2 >> 32) as u32;
+    (w1 as u64) << 32 | w0 as u64
+}
+
+// the SHA-256 functions use the low 32 bits, the result is sign-extended
+fn sha256(rs1: u64, f: fn(u32) -> u32) -> u64 {
+    f(rs1 as u32) as i32 as i64 as u64
+}
+
+fn op_r(cpu: &mut CpuCore, inst: u32, f: fn(u64, u64) -> u64) {
+    let fr = parse_format_r(inst);
+    let rs1 = cpu.gpr.read(fr.rs1);
+    let rs2 = cpu.gpr.read(fr.rs2);
+    cpu.gpr.write(fr.rd, f(rs1, rs2));
+}
+
+fn op_i(cpu: &mut CpuCore, inst: u32, f: fn(u64) -> u64) {
+    let fr = parse_format_r(inst);
+    let rs1 = cpu.gpr.read(fr.rs1);
+    cpu.gpr.write(fr.rd, f(rs1));
+}
+
+// Zkn: NIST suite, the AES (Zkne, Zknd) and SHA-2 (Zknh) instructions
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZKN: &[Instruction] = &[
+    Instruction {
+        mask: MASK_AES64ES,
+        match_data: MATCH_AES64ES,
+        name: "AES64ES",
+        operation: |cpu, inst, pc| {
+            op_r(cpu, inst, |rs1, rs2| {
+                sub_bytes(shift_rows(rs1, rs2, false), &AES_SBOX)
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_AES64ESM,
+        match_data: MATCH_AES64ESM,
+        name: "AES64ESM",
+        operation: |cpu, inst, pc| {
+            op_r(cpu, inst, |rs1, rs2| {
+                let sb = sub_bytes(shift_rows(rs1, rs2, false), &AES_SBOX);
+                mix_columns(sb, false)
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_AES64DS,
+        match_data: MATCH_AES64DS,
+        name: "AES64DS",
+        operation: |cpu, inst, pc| {
+            op_r(cpu, inst, |rs1, rs2| {
+                sub_bytes(shift_rows(rs1, rs2, true), &AES_INV_SBOX)
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_AES64DSM,
+        match_data: MATCH_AES64DSM,
+        name: "AES64DSM",
+        operation: |cpu, inst, pc| {
+            op_r(cpu, inst, |rs1, rs2| {
+                let sb = sub_bytes(shift_rows(rs1, rs2, true), &AES_INV_SBOX);
+                mix_columns(sb, true)
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_AES64IM,
+        match_data: MATCH_AES64IM,
+        name: "AES64IM",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |rs1| mix_columns(rs1, true));
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_AES64KS1I,
+        match_data: MATCH_AES64KS1I,
+        name: "AES64KS1I",
+        operation: |cpu, inst, pc| {
+            // rnum 0xb..=0xf are reserved
+            let rnum = ((inst >> 20) & 0xf) as u8;
+            if rnum > 0xa {
+                return Err(TrapType::IllegalInstruction(inst.into()));
+            }
+            let f = parse_format_r(inst);
+            let rs1 = cpu.gpr.read(f.rs1);
+            cpu.gpr.write(f.rd, aes64_ks1i(rs1, rnum));
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_AES64KS2,
+        match_data: MATCH_AES64KS2,
+        name: "AES64KS2",
+        operation: |cpu, inst, pc| {
+            op_r(cpu, inst, aes64_ks2);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SHA256SIG0,
+        match_data: MATCH_SHA256SIG0,
+        name: "SHA256SIG0",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |rs1| {
+                sha256(rs1, |x| x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3))
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SHA256SIG1,
+        match_data: MATCH_SHA256SIG1,
+        name: "SHA256SIG1",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |rs1| {
+                sha256(rs1, |x| x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10))
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SHA256SUM0,
+        match_data: MATCH_SHA256SUM0,
+        name: "SHA256SUM0",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |rs1| {
+                sha256(rs1, |x| {
+                    x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22)
+                })
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SHA256SUM1,
+        match_data: MATCH_SHA256SUM1,
+        name: "SHA256SUM1",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |rs1| {
+                sha256(rs1, |x| {
+                    x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25)
+                })
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SHA512SIG0,
+        match_data: MATCH_SHA512SIG0,
+        name: "SHA512SIG0",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |x| {
+                x.rotate_right(1) ^ x.rotate_right(8) ^ (x >> 7)
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SHA512SIG1,
+        match_data: MATCH_SHA512SIG1,
+        name: "SHA512SIG1",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |x| {
+                x.rotate_right(19) ^ x.rotate_right(61) ^ (x >> 6)
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SHA512SUM0,
+        match_data: MATCH_SHA512SUM0,
+        name: "SHA512SUM0",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |x| {
+                x.rotate_right(28) ^ x.rotate_right(34) ^ x.rotate_right(39)
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SHA512SUM1,
+        match_data: MATCH_SHA512SUM1,
+        name: "SHA512SUM1",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |x| {
+                x.rotate_right(14) ^ x.rotate_right(18) ^ x.rotate_right(41)
+            });
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zkn {
+    use super::{
+        aes64_ks1i, aes64_ks2, mix_columns, shift_rows, sub_bytes, AES_INV_SBOX, AES_SBOX,
+    };
+
+    // FIPS-197 appendix C.1, AES-128
+    const KEY: u128 = 0x0f0e0d0c_0b0a0908_07060504_03020100;
+    const PLAIN: u128 = 0xffeeddcc_bbaa9988_77665544_33221100;
+    const CIPHER: u128 = 0x5ac5b470_80b7cdd8_30047b6a_d8e0c469;
+
+    fn split(x: u128) -> (u64, u64) {
+        (x as u64, (x >> 64) as u64)
+    }
+
+    fn key_schedule() -> [(u64, u64); 11] {
+        let mut rk = [split(KEY); 11];
+        for rnum in 0..10 {
+            let (k0, k1) = rk[rnum];
+            let t = aes64_ks1i(k1, rnum as u8);
+            let n0 = aes64_ks2(t, k0);
+            let n1 = aes64_ks2(n0, k1);
+            rk[rnum + 1] = (n0, n1);
+        }
+        rk
+    }
+
+    #[test]
+    fn aes128_test() {
+        let rk = key_schedule();
+        let (mut s0, mut s1) = split(PLAIN);
+        s0 ^= rk[0].0;
+        s1 ^= rk[0].1;
+        for (round, key) in rk.iter().enumerate().skip(1) {
+            let (n0, n1) = if round == 10 {
+                let es = |a, b| sub_bytes(shift_rows(a, b, false), &AES_SBOX);
+                (es(s0, s1), es(s1, s0))
+            } else {
+                let esm = |a, b| mix_columns(sub_bytes(shift_rows(a, b, false), &AES_SBOX), false);
+                (esm(s0, s1), esm(s1, s0))
+            };
+            s0 = n0 ^ key.0;
+            s1 = n1 ^ key.1;
+        }
+        assert_eq!((s0, s1), split(CIPHER));
+
+        // the equivalent inverse cipher, the middle round keys pass aes64im
+        let (mut s0, mut s1) = split(CIPHER);
+        s0 ^= rk[10].0;
+        s1 ^= rk[10].1;
+        for round in (0..10).rev() {
+            let key = rk[round];
+            let (n0, n1) = if round == 0 {
+                let ds = |a, b| sub_bytes(shift_rows(a, b, true), &AES_INV_SBOX);
+                (ds(s0, s1), ds(s1, s0))
+            } else {
+                let dsm =
+                    |a, b| mix_columns(sub_bytes(shift_rows(a, b, true), &AES_INV_SBOX), true);
+                (dsm(s0, s1), dsm(s1, s0))
+            };
+            let key = match round {
+                0 => key,
+                _ => (mix_columns(key.0, true), mix_columns(key.1, true)),
+            };
+            s0 = n0 ^ key.0;
+            s1 = n1 ^ key.1;
+        }
+        assert_eq!((s0, s1), split(PLAIN));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::rv64core::{cpu_core::CpuCore, inst::inst_base::*};
+
+const SM4_SBOX: [u8; 256] = [
+    0xd6, 0x90, 0xe9, 0xfe, 0xcc, 0xe1, 0x3d, 0xb7, 0x16, 0xb6, 0x14, 0xc2, 0x28, 0xfb, 0x2c, 0x05,
+    0x2b, 0x67, 0x9a, 0x76, 0x2a, 0xbe, 0x04, 0xc3, 0xaa, 0x44, 0x13, 0x26, 0x49, 0x86, 0x06, 0x99,
+    0x9c, 0x42, 0x50, 0xf4, 0x91, 0xef, 0x98, 0x7a, 0x33, 0x54, 0x0b, 0x43, 0xed, 0xcf, 0xac, 0x62,
+    0xe4, 0xb3, 0x1c, 0xa9, 0xc9, 0x08, 0xe8, 0x95, 0x80, 0xdf, 0x94, 0xfa, 0x75, 0x8f, 0x3f, 0xa6,
+    0x47, 0x07, 0xa7, 0xfc, 0xf3, 0x73, 0x17, 0xba, 0x83, 0x59, 0x3c, 0x19, 0xe6, 0x85, 0x4f, 0xa8,
+    0x68, 0x6b, 0x81, 0xb2, 0x71, 0x64, 0xda, 0x8b, 0xf8, 0xeb, 0x0f, 0x4b, 0x70, 0x56, 0x9d, 0x35,
+    0x1e, 0x24, 0x0e, 0x5e, 0x63, 0x58, 0xd1, 0xa2, 0x25, 0x22, 0x7c, 0x3b, 0x01, 0x21, 0x78, 0x87,
+    0xd4, 0x00, 0x46, 0x57, 0x9f, 0xd3, 0x27, 0x52, 0x4c, 0x36, 0x02, 0xe7, 0xa0, 0xc4, 0xc8, 0x9e,
+    0xea, 0xbf, 0x8a, 0xd2, 0x40, 0xc7, 0x38, 0xb5, 0xa3, 0xf7, 0xf2, 0xce, 0xf9, 0x61, 0x15, 0xa1,
+    0xe0, 0xae, 0x5d, 0xa4, 0x9b, 0x34, 0x1a, 0x55, 0xad, 0x93, 0x32, 0x30, 0xf5, 0x8c, 0xb1, 0xe3,
+    0x1d, 0xf6, 0xe2, 0x2e, 0x82, 0x66, 0xca, 0x60, 0xc0, 0x29, 0x23, 0xab, 0x0d, 0x53, 0x4e, 0x6f,
+    0xd5, 0xdb, 0x37, 0x45, 0xde, 0xfd, 0x8e, 0x2f, 0x03, 0xff, 0x6a, 0x72, 0x6d, 0x6c, 0x5b, 0x51,
+    0x8d, 0x1b, 0xaf, 0x92, 0xbb, 0xdd, 0xbc, 0x7f, 0x11, 0xd9, 0x5c, 0x41, 0x1f, 0x10, 0x5a, 0xd8,
+    0x0a, 0xc1, 0x31, 0x88, 0xa5, 0xcd, 0x7b, 0xbd, 0x2d, 0x74, 0xd0, 0x12, 0xb8, 0xe5, 0xb4, 0xb0,
+    0x89, 0x69, 0x97, 0x4a, 0x0c, 0x96, 0x77, 0x7e, 0x65, 0xb9, 0xf1, 0x09, 0xc5, 0x6e, 0xc6, 0x84,
+    0x18, 0xf0, 0x7d, 0xec, 0x3a, 0xdc, 0x4d, 0x20, 0x79, 0xee, 0x5f, 0x3e, 0xd7, 0xcb, 0x39, 0x48,
+];
+
+// one byte of the SM4 round function: the sbox output of byte bs of rs2
+// passes the linear transform in place, then it is xored into rs1.
+// the transforms are linear, so four bytes give the full round
+fn sm4_round(rs1: u64, rs2: u64, bs: u32, linear: fn(u32) -> u32) -> u64 {
+    let x = SM4_SBOX[(rs2 >> (8 * bs)) as u8 as usize] as u32;
+    let z = linear(x).rotate_left(8 * bs);
+    (z ^ rs1 as u32) as i32 as i64 as u64
+}
+
+// L of the encryption rounds
+fn sm4_ed(rs1: u64, rs2: u64, bs: u32) -> u64 {
+    sm4_round(rs1, rs2, bs, |x| {
+        x ^ x.rotate_left(2) ^ x.rotate_left(10) ^ x.rotate_left(18) ^ x.rotate_left(24)
+    })
+}
+
+// L' of the key schedule
+fn sm4_ks(rs1: u64, rs2: u64, bs: u32) -> u64 {
+    sm4_round(rs1, rs2, bs, |x| x ^ x.rotate_left(13) ^ x.rotate_left(23))
+}
+
+fn sm3(rs1: u64, f: fn(u32) -> u32) -> u64 {
+    f(rs1 as u32) as i32 as i64 as u64
+}
+
+fn op_bs(cpu: &mut CpuCore, inst: u32, f: fn(u64, u64, u32) -> u64) {
+    let fr = parse_format_r(inst);
+    let rs1 = cpu.gpr.read(fr.rs1);
+    let rs2 = cpu.gpr.read(fr.rs2);
+    cpu.gpr.write(fr.rd, f(rs1, rs2, inst >> 30));
+}
+
+fn op_i(cpu: &mut CpuCore, inst: u32, f: fn(u64) -> u64) {
+    let fr = parse_format_r(inst);
+    let rs1 = cpu.gpr.read(fr.rs1);
+    cpu.gpr.write(fr.rd, f(rs1));
+}
+
+// Zks: ShangMi suite, the SM4 (Zksed) and SM3 (Zksh) instructions
+#[allow(unused_variables)]
+pub const INSTRUCTIONS_ZKS: &[Instruction] = &[
+    Instruction {
+        mask: MASK_SM4ED,
+        match_data: MATCH_SM4ED,
+        name: "SM4ED",
+        operation: |cpu, inst, pc| {
+            op_bs(cpu, inst, sm4_ed);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SM4KS,
+        match_data: MATCH_SM4KS,
+        name: "SM4KS",
+        operation: |cpu, inst, pc| {
+            op_bs(cpu, inst, sm4_ks);
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SM3P0,
+        match_data: MATCH_SM3P0,
+        name: "SM3P0",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |rs1| {
+                sm3(rs1, |x| x ^ x.rotate_left(9) ^ x.rotate_left(17))
+            });
+            Ok(())
+        },
+    },
+    Instruction {
+        mask: MASK_SM3P1,
+        match_data: MATCH_SM3P1,
+        name: "SM3P1",
+        operation: |cpu, inst, pc| {
+            op_i(cpu, inst, |rs1| {
+                sm3(rs1, |x| x ^ x.rotate_left(15) ^ x.rotate_left(23))
+            });
+            Ok(())
+        },
+    },
+];
+
+#[cfg(test)]
+mod test_rv64zks {
+    use super::{sm4_ed, sm4_ks};
+
+    // GB/T 32907-2016 appendix A.1, the key is also the plaintext
+    const KEY: [u32; 4] = [0x01234567, 0x89abcdef, 0xfedcba98, 0x76543210];
+    const CIPHER: [u32; 4] = [0x681edf34, 0xd206965e, 0x86b3e94f, 0x536e4246];
+    const FK: [u32; 4] = [0xa3b1bac6, 0x56aa3350, 0x677d9197, 0xb27022dc];
+
+    // x0 ^ T(x1 ^ x2 ^ x3 ^ rk), four sm4ed or sm4ks
+    fn round(f: fn(u64, u64, u32) -> u64, x: [u32; 4], rk: u32) -> u32 {
+        let rs2 = (x[1] ^ x[2] ^ x[3] ^ rk) as u64;
+        let rd = (0..4).fold(x[0] as u64, |rd, bs| f(rd, rs2, bs));
+        assert_eq!(rd, rd as u32 as i32 as i64 as u64);
+        rd as u32
+    }
+
+    #[test]
+    fn sm4_test() {
+        let mut k: [u32; 4] = core::array::from_fn(|i| KEY[i] ^ FK[i]);
+        let mut rk = [0; 32];
+        for (i, rk) in rk.iter_mut().enumerate() {
+            let ck = u32::from_be_bytes(core::array::from_fn(|j| ((4 * i + j) * 7) as u8));
+            *rk = round(sm4_ks, k, ck);
+            k = [k[1], k[2], k[3], *rk];
+        }
+        assert_eq!(rk[0], 0xf12186f9);
+        assert_eq!(rk[31], 0x9124a012);
+
+        let mut x = KEY;
+        for rk in rk {
+            x = [x[1], x[2], x[3], round(sm4_ed, x, rk)];
+        }
+        x.reverse();
+        assert_eq!(x, CIPHER);
+    }
+}
@@ -4,3 +4,20 @@ pub mod inst_rv64i;
 pub mod inst_rv64z;
 pub mod inst_rv64m;
 pub mod inst_rv64c;
+pub mod inst_rv64f;
+pub mod inst_rv64d;
+pub mod inst_rv64zfh;
+pub mod inst_rv64v;
+pub mod inst_rv64zba;
+pub mod inst_rv64zbc;
+pub mod inst_rv64zbs;
+pub mod inst_rv64zawrs;
+pub mod inst_rv64h;
+pub mod inst_rv64zicbo;
+pub mod inst_rv64zicond;
+pub mod inst_rv64zkn;
+pub mod inst_rv64zks;
+pub mod inst_rv64zihint;
+pub mod inst_rv32;
+pub mod inst_disasm;
+pub mod inst_asm;
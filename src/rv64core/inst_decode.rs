@@ -2,16 +2,41 @@ use alloc::{rc::Rc, vec::Vec};
 use hashlink::LruCache;
 use log::info;
 
-use crate::rv64core::inst::inst_rv64a::INSTRUCTIONS_A;
+use crate::rv64core::inst::inst_rv32::{
+    INSTRUCTIONS_RV32C, INSTRUCTIONS_RV32I, INSTRUCTIONS_RV32M,
+};
+use crate::rv64core::inst::inst_rv64a::{
+    INSTRUCTIONS_A, INSTRUCTIONS_ZABHA, INSTRUCTIONS_ZABHA_ZACAS, INSTRUCTIONS_ZACAS,
+};
 use crate::rv64core::inst::inst_rv64c::INSTRUCTIONS_C;
+use crate::rv64core::inst::inst_rv64d::INSTRUCTIONS_D;
+use crate::rv64core::inst::inst_rv64f::INSTRUCTIONS_F;
+use crate::rv64core::inst::inst_rv64h::INSTRUCTIONS_H;
 use crate::rv64core::inst::inst_rv64m::INSTRUCTIONS_M;
+use crate::rv64core::inst::inst_rv64v::INSTRUCTIONS_V;
+use crate::rv64core::inst::inst_rv64zawrs::INSTRUCTIONS_ZAWRS;
+use crate::rv64core::inst::inst_rv64zba::INSTRUCTIONS_ZBA;
+use crate::rv64core::inst::inst_rv64zbc::INSTRUCTIONS_ZBC;
+use crate::rv64core::inst::inst_rv64zbs::INSTRUCTIONS_ZBS;
+use crate::rv64core::inst::inst_rv64zfh::{INSTRUCTIONS_ZFH, INSTRUCTIONS_ZFH_D};
+use crate::rv64core::inst::inst_rv64zicbo::{INSTRUCTIONS_ZICBOM, INSTRUCTIONS_ZICBOZ};
+use crate::rv64core::inst::inst_rv64zicond::INSTRUCTIONS_ZICOND;
+use crate::rv64core::inst::inst_rv64zihint::{
+    INSTRUCTIONS_ZIHINTNTL, INSTRUCTIONS_ZIHINTNTL_C, INSTRUCTIONS_ZIHINTPAUSE,
+};
+use crate::rv64core::inst::inst_rv64zkn::INSTRUCTIONS_ZKN;
+use crate::rv64core::inst::inst_rv64zks::INSTRUCTIONS_ZKS;
 
 use crate::{
-    config::Config,
+    config::{Compliance, Config},
     rv64core::{
-        inst::inst_base::Instruction, inst::inst_rv64i::INSTRUCTIONS_I,
-        inst::inst_rv64z::INSTRUCTIONS_Z,
+        csr_regs_define::MisaIn,
+        inst::inst_base::{is_compressed_instruction, Instruction},
+        inst::inst_disasm::{fp_operands, inst_name},
+        inst::inst_rv64i::INSTRUCTIONS_I,
+        inst::inst_rv64z::{INSTRUCTIONS_Z, INSTRUCTIONS_ZICSR, INSTRUCTIONS_ZIFENCEI},
     },
+    tools::RcCell,
 };
 
 pub struct InstDecode {
@@ -20,37 +45,256 @@ pub struct InstDecode {
     pub hit: u64,
     pub miss: u64,
     remove_count: u64,
+    #[allow(dead_code)]
     config: Rc<Config>,
+    misa: Option<RcCell<MisaIn>>,
+    built_misa: u64,
 }
 
-impl InstDecode {
-    pub fn new(config: Rc<Config>) -> Self {
-        let mut i_vec = Vec::new();
-        i_vec.extend(INSTRUCTIONS_I);
-        i_vec.extend(INSTRUCTIONS_Z);
+const ZFINX_EXCLUDED: [&str; 8] = [
+    "FLW", "FSW", "FMV_X_W", "FMV_W_X", "FLD", "FSD", "FMV_X_D", "FMV_D_X",
+];
+
+// the rv64 only instructions, and the ones `inst_rv32` replaces
+const RV32_EXCLUDED: [&str; 29] = [
+    "LWU", "LD", "SD", "ADDIW", "SLLIW", "SRLIW", "SRAIW", "ADDW", "SUBW", "SLLW", "SRLW", "SRAW",
+    "MULW", "DIVW", "DIVUW", "REMW", "REMUW", "c.ldsp", "c.sdsp", "c.ld", "c.sd", "c.addiw",
+    "c.addw", "c.subw", "SRLI", "SLL", "SRL", "SRA", "c.srli",
+];
+const RV32_EXCLUDED_M: [&str; 5] = ["MULH", "MULHSU", "MULHU", "DIVU", "REMU"];
+
+fn rv64_only(inst: &Instruction) -> bool {
+    let name = inst.name;
+    let amo_d = name.ends_with("_D") && (name.starts_with("AMO") || name.starts_with("LR"));
+    RV32_EXCLUDED.contains(&name) || RV32_EXCLUDED_M.contains(&name) || amo_d || name == "SC_D"
+}
 
-        if config.is_enable_isa(b'm') {
-            i_vec.extend(INSTRUCTIONS_M);
+// the shifts by 32 or more are reserved on rv32
+fn rv32_reserved(word: u32) -> bool {
+    if is_compressed_instruction(word) {
+        // c.slli, c.srli and c.srai with shamt[5] set
+        return match (word & 0b11, (word >> 13) & 0b111) {
+            (0b10, 0b000) => word & 1 << 12 != 0,
+            (0b01, 0b100) => (word >> 10) & 0b11 < 0b10 && word & 1 << 12 != 0,
+            _ => false,
+        };
+    }
+    // slli, srli and srai with shamt[5] set
+    word & 0x7f == 0b0010011 && matches!((word >> 12) & 0b111, 0b001 | 0b101) && word & 1 << 25 != 0
+}
+
+// RV64E has only x0-x15, the instructions naming x16-x31 are illegal
+fn uses_upper_gpr(inst: &Instruction, word: u32, zfinx: bool) -> bool {
+    let upper = |lsb: u32| (word >> lsb) & 0x1f >= 16;
+    let (rd, rs1, rs2) = (upper(7), upper(15), upper(20));
+    if is_compressed_instruction(word) {
+        // the 3-bit register fields are x8-x15
+        return match (word & 0b11, (word >> 13) & 0b111) {
+            // c.addi, c.addiw, c.li, c.lui
+            (0b01, 0b000..=0b011) => rd,
+            // c.slli, c.lwsp, c.ldsp
+            (0b10, 0b000 | 0b010 | 0b011) => rd,
+            // c.jr, c.mv, c.ebreak, c.jalr, c.add
+            (0b10, 0b100) => rd || upper(2),
+            // c.swsp, c.sdsp
+            (0b10, 0b110 | 0b111) => upper(2),
+            _ => false,
+        };
+    }
+    match word & 0x7f {
+        // LUI,AUIPC,JAL
+        0b0110111 | 0b0010111 | 0b1101111 => rd,
+        // JALR,LOAD,OP-IMM,OP-IMM-32,MISC-MEM
+        0b1100111 | 0b0000011 | 0b0010011 | 0b0011011 | 0b0001111 => rd || rs1,
+        // BRANCH,STORE
+        0b1100011 | 0b0100011 => rs1 || rs2,
+        // OP,OP-32,AMO
+        0b0110011 | 0b0111011 | 0b0101111 => rd || rs1 || rs2,
+        // LOAD-FP,STORE-FP
+        0b0000111 | 0b0100111 => rs1,
+        // MADD,MSUB,NMSUB,NMADD,OP-FP, all operands are x registers with Zfinx
+        0b1000011 | 0b1000111 | 0b1001011 | 0b1001111 | 0b1010011 => {
+            let kinds = fp_operands(&inst_name(inst));
+            if zfinx {
+                rd || rs1 || (kinds.srcs >= 2 && rs2) || (kinds.srcs == 3 && upper(27))
+            } else {
+                (kinds.rd_x && rd) || (kinds.rs1_x && rs1)
+            }
+        }
+        // SYSTEM, csrr*i take a uimm in rs1
+        0b1110011 => match (word >> 12) & 0b111 {
+            0b001..=0b011 => rd || rs1,
+            0b101..=0b111 => rd,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// reserved compressed encodings that still match an instruction,
+// only rejected in strict mode
+fn reserved_compressed(word: u32) -> bool {
+    if !is_compressed_instruction(word) {
+        return false;
+    }
+    let rd = (word >> 7) & 0x1f;
+    let rs2 = (word >> 2) & 0x1f;
+    let bit12 = (word >> 12) & 1;
+    match (word & 0b11, (word >> 13) & 0b111) {
+        // c.addi4spn with nzuimm=0, the all-zero word included
+        (0b00, 0b000) => (word >> 5) & 0xff == 0,
+        // c.addiw with rd=0
+        (0b01, 0b001) => rd == 0,
+        // c.addi16sp and c.lui with nzimm=0
+        (0b01, 0b011) => bit12 == 0 && rs2 == 0,
+        // c.lwsp, c.ldsp with rd=0
+        (0b10, 0b010 | 0b011) => rd == 0,
+        // c.jr with rs1=0
+        (0b10, 0b100) => bit12 == 0 && rd == 0 && rs2 == 0,
+        _ => false,
+    }
+}
+
+// the instructions of the isa string, the letters cleared in `misa` are left out
+fn inst_vec(config: &Config, misa: u64) -> Vec<&'static Instruction> {
+    let isa = |ext: u8| config.is_enable_isa(ext) && misa & 1 << (ext - b'a') != 0;
+    let mut i_vec = Vec::new();
+    i_vec.extend(INSTRUCTIONS_I);
+    i_vec.extend(INSTRUCTIONS_Z);
+    if config.is_enable_ext("zicsr") {
+        i_vec.extend(INSTRUCTIONS_ZICSR);
+    }
+    if config.is_enable_ext("zifencei") {
+        i_vec.extend(INSTRUCTIONS_ZIFENCEI);
+    }
+
+    if isa(b'm') {
+        i_vec.extend(INSTRUCTIONS_M);
+    }
+    if isa(b'a') {
+        i_vec.extend(INSTRUCTIONS_A);
+        if config.is_enable_ext("zacas") {
+            i_vec.extend(INSTRUCTIONS_ZACAS);
+        }
+        if config.is_enable_ext("zabha") {
+            i_vec.extend(INSTRUCTIONS_ZABHA);
+            if config.is_enable_ext("zacas") {
+                i_vec.extend(INSTRUCTIONS_ZABHA_ZACAS);
+            }
+        }
+    }
+    if isa(b'c') {
+        i_vec.extend(INSTRUCTIONS_C);
+    }
+    if isa(b'f') {
+        i_vec.extend(INSTRUCTIONS_F);
+    }
+    if isa(b'd') {
+        i_vec.extend(INSTRUCTIONS_D);
+    }
+    // Zfinx and Zdinx: no fp loads, stores and moves between the register files
+    let in_x = |x: &&Instruction| !ZFINX_EXCLUDED.contains(&x.name);
+    if config.is_enable_ext("zfinx") {
+        i_vec.extend(INSTRUCTIONS_F.iter().filter(in_x));
+    }
+    if config.is_enable_ext("zdinx") {
+        i_vec.extend(INSTRUCTIONS_D.iter().filter(in_x));
+    }
+    if config.is_enable_ext("zfh") && isa(b'f') {
+        i_vec.extend(INSTRUCTIONS_ZFH);
+        if isa(b'd') {
+            i_vec.extend(INSTRUCTIONS_ZFH_D);
         }
-        if config.is_enable_isa(b'a') {
-            i_vec.extend(INSTRUCTIONS_A);
+    }
+    if isa(b'h') {
+        i_vec.extend(INSTRUCTIONS_H);
+    }
+    if config.is_enable_ext("zba") {
+        i_vec.extend(INSTRUCTIONS_ZBA);
+    }
+    if config.is_enable_ext("zbc") {
+        i_vec.extend(INSTRUCTIONS_ZBC);
+    }
+    if config.is_enable_ext("zbs") {
+        i_vec.extend(INSTRUCTIONS_ZBS);
+    }
+    if config.is_enable_ext("zicond") {
+        i_vec.extend(INSTRUCTIONS_ZICOND);
+    }
+    if config.is_enable_ext("zicbom") {
+        i_vec.extend(INSTRUCTIONS_ZICBOM);
+    }
+    if config.is_enable_ext("zicboz") {
+        i_vec.extend(INSTRUCTIONS_ZICBOZ);
+    }
+    if config.is_enable_ext("zihintpause") {
+        i_vec.extend(INSTRUCTIONS_ZIHINTPAUSE);
+    }
+    if config.is_enable_ext("zihintntl") {
+        i_vec.extend(INSTRUCTIONS_ZIHINTNTL);
+        if isa(b'c') {
+            i_vec.extend(INSTRUCTIONS_ZIHINTNTL_C);
         }
-        if config.is_enable_isa(b'c') {
-            i_vec.extend(INSTRUCTIONS_C);
+    }
+    if config.is_enable_ext("zawrs") {
+        i_vec.extend(INSTRUCTIONS_ZAWRS);
+    }
+    if config.is_enable_ext("zkn") {
+        i_vec.extend(INSTRUCTIONS_ZKN);
+    }
+    if config.is_enable_ext("zks") {
+        i_vec.extend(INSTRUCTIONS_ZKS);
+    }
+    if config.is_enable_ext("zve64x") {
+        i_vec.extend(INSTRUCTIONS_V);
+    }
+
+    if config.xlen() == 32 {
+        i_vec.retain(|x| !rv64_only(x));
+        i_vec.extend(INSTRUCTIONS_RV32I);
+        if isa(b'm') {
+            i_vec.extend(INSTRUCTIONS_RV32M);
+        }
+        if isa(b'c') {
+            i_vec.extend(INSTRUCTIONS_RV32C);
         }
+    }
 
-        i_vec.sort_by(|a: &&Instruction, b: &&Instruction| Instruction::inst_cmp(a, b));
+    i_vec.sort_by(|a: &&Instruction, b: &&Instruction| Instruction::inst_cmp(a, b));
+    i_vec
+}
 
+impl InstDecode {
+    pub fn new(config: Rc<Config>) -> Self {
         InstDecode {
-            inst_vec: i_vec,
+            inst_vec: inst_vec(&config, u64::MAX),
             inst_hash: LruCache::new(config.decode_cache_size().unwrap_or(0)),
             hit: 0,
             miss: 0,
             remove_count: 0,
+            misa: None,
+            built_misa: u64::MAX,
             config,
         }
     }
 
+    // the instructions follow the letters turned on and off in misa
+    pub fn follow_misa(&mut self, misa: RcCell<MisaIn>) {
+        self.misa = Some(misa);
+        self.sync_misa();
+    }
+
+    fn sync_misa(&mut self) {
+        let Some(misa) = &self.misa else {
+            return;
+        };
+        let misa = u64::from(misa.get());
+        if misa != self.built_misa {
+            self.inst_vec = inst_vec(&self.config, misa);
+            self.built_misa = misa;
+            self.inst_hash.clear();
+        }
+    }
     fn no_decode_cache(&self) -> bool {
         self.inst_hash.capacity() == 0
     }
@@ -63,10 +307,17 @@ impl InstDecode {
     }
 
     fn slow_path(&mut self, inst_i: u32) -> Option<&Instruction> {
+        let rve = self.config.is_enable_isa(b'e');
+        let zfinx = self.config.is_enable_ext("zfinx");
+        let strict = self.config.compliance() == Compliance::Strict;
+        let rv32 = self.config.xlen() == 32;
         let slowpath = self
             .inst_vec
             .iter()
             .find(|x| x.mask & inst_i == x.match_data)
+            .filter(|x| !rve || !uses_upper_gpr(x, inst_i, zfinx))
+            .filter(|_| !strict || !reserved_compressed(inst_i))
+            .filter(|_| !rv32 || !rv32_reserved(inst_i))
             .copied();
 
         if !self.no_decode_cache() {
@@ -79,6 +330,7 @@ impl InstDecode {
     }
 
     pub fn fast_path(&mut self, inst_i: u32) -> Option<&Instruction> {
+        self.sync_misa();
         if self.no_decode_cache() {
             return self.slow_path(inst_i);
         }
@@ -1,12 +1,11 @@
-use core::cell::Cell;
+use core::{cell::Cell, fmt};
 
-use alloc::{rc::Rc, vec::Vec};
-use hashlink::LruCache;
+use alloc::{rc::Rc, vec, vec::Vec};
 use log::{info, trace};
 
 use crate::{
     config::Config,
-    rv64core::csr_regs_define::{SatpIn, StapMode, XstatusIn},
+    rv64core::csr_regs_define::{EnvcfgIn, HgatpIn, HstatusIn, SatpIn, StapMode, XstatusIn},
     rv64core::{
         cache::cache_system::CacheSystem,
         inst::inst_base::{AccessType, PrivilegeLevels},
@@ -16,24 +15,86 @@ use crate::{
 };
 
 use super::{
+    pmp::Pmp,
     sv48::{Sv48PA, Sv48PTE, Sv48VA},
-    vm_info::{PAenume, PAops, PTEenume, PTEops, PageSize, TLBEntry, TLBKey, VAenume, VAops},
+    tlb::{Tlb, TlbStats},
+    vm_info::{
+        MemType, PAenume, PAops, PTEenume, PTEops, PageSize, TLBEntry, TLBKey, VAenume, VAops,
+    },
 };
 
 const PAGESIZE: u64 = 4096; // 2 ^ 12
 
+// the TLB keys hold the page size, the most used sizes are looked up first
+const TLB_PAGE_SIZES: [PageSize; 7] = [
+    PageSize::P2M,
+    PageSize::P4K,
+    PageSize::P64K,
+    PageSize::P4M,
+    PageSize::P1G,
+    PageSize::P512G,
+    PageSize::P256T,
+];
+
+// the H extension state used by the two-stage translation
+#[derive(Clone)]
+pub struct VirtRegs {
+    pub virt: RcCell<bool>,
+    pub hstatus: RcCell<HstatusIn>,
+    pub vsstatus: RcCell<XstatusIn>,
+    pub vsatp: RcCell<SatpIn>,
+    pub hgatp: RcCell<HgatpIn>,
+    pub henvcfg: RcCell<EnvcfgIn>,
+}
+
+// one walk of the Ptw trace, the G-stage PTEs of a guest walk are in `ptes` too
+#[derive(Debug, Clone, PartialEq)]
+pub struct PtwRecord {
+    pub va: u64,
+    pub satp: u64,
+    // (address, value) of every PTE read
+    pub ptes: Vec<(u64, u64)>,
+    pub result: Result<u64, TrapType>,
+}
+
+impl fmt::Display for PtwRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ptw va:{:x},satp:{:x}", self.va, self.satp)?;
+        for (addr, pte) in self.ptes.iter() {
+            write!(f, ",pte[{addr:x}]:{pte:x}")?;
+        }
+        match self.result {
+            Ok(pa) => write!(f, " -> pa:{pa:x}"),
+            Err(trap) => write!(f, " -> {trap}"),
+        }
+    }
+}
+
 pub struct Mmu {
     pub caches: RcRefCell<CacheSystem>,
     pub access_type: AccessType,
     mstatus: RcCell<XstatusIn>,
     satp: RcCell<SatpIn>,
+    menvcfg: RcCell<EnvcfgIn>,
     cur_priv: Rc<Cell<PrivilegeLevels>>,
     mmu_effective_priv: PrivilegeLevels,
     satp_mode: StapMode,
+    virt_regs: VirtRegs,
+    // the access is translated by the VS-stage and the G-stage
+    effective_virt: bool,
+    // the last guest page fault is the one of a VS-stage PTE read
+    implicit_fault: bool,
+    // Some(hlvx) for the HLV, HLVX and HSV accesses
+    hlv: Option<bool>,
+    // the Svpbmt memory type of the last translated access
+    mem_type: MemType,
+    pmp: RcRefCell<Pmp>,
     config: Rc<Config>,
-    tlb: LruCache<TLBKey, TLBEntry>,
+    tlb: Tlb,
     tlb_hit: u64,
     tlb_miss: u64,
+    // the walks since the last take_ptw_records, None if the trace is off
+    ptw: Option<Vec<PtwRecord>>,
     /* tmp val */
     i: i8,
     level: i8,
@@ -50,6 +111,8 @@ impl Mmu {
         privilege: Rc<Cell<PrivilegeLevels>>,
         mstatus: RcCell<XstatusIn>,
         satp: RcCell<SatpIn>,
+        menvcfg: RcCell<EnvcfgIn>,
+        virt_regs: VirtRegs,
         config: Rc<Config>,
     ) -> Self {
         Mmu {
@@ -57,22 +120,45 @@ impl Mmu {
             access_type: AccessType::Load(0),
             mstatus,
             satp,
+            menvcfg,
             cur_priv: privilege,
             mmu_effective_priv: PrivilegeLevels::Machine,
             satp_mode: StapMode::Bare,
+            virt_regs,
+            effective_virt: false,
+            implicit_fault: false,
+            hlv: None,
+            mem_type: MemType::Pma,
+            // no entries until with_pmp, every access passes
+            pmp: RcRefCell::new(Pmp::new(0).into()),
             i: 0,
             level: 0,
             a: 0,
             va: Sv48VA::new().into(),
             pa: Sv48PA::new().into(),
             pte: Sv48PTE::new().into(),
-            tlb: LruCache::new(config.tlb_size().unwrap_or(0)),
+            tlb: Tlb::new(
+                config.tlb_size().unwrap_or(0),
+                config.tlb_ways().unwrap_or(0),
+                config.tlb_policy(),
+            ),
             config,
             tlb_hit: 0,
             tlb_miss: 0,
+            ptw: None,
         }
     }
 
+    pub fn with_pmp(mut self, pmp: RcRefCell<Pmp>) -> Self {
+        self.pmp = pmp;
+        self
+    }
+
+    pub fn with_ptw_trace(mut self, enable: bool) -> Self {
+        self.set_ptw_trace(enable);
+        self
+    }
+
     // 1. Let a be satp.ppn × PAGESIZE, and let i = LEVELS − 1. (For Sv32, PAGESIZE=2^12 and
     // LEVELS=2.) The satp register must be active, i.e., the effective privilege mode must be
     // S-mode or U-mode.
@@ -82,7 +168,7 @@ impl Mmu {
         assert_ne!(self.mmu_effective_priv, PrivilegeLevels::Machine); // check privilege mode
         self.level = self.satp_mode.get_levels() as i8;
         self.i = self.level - 1;
-        self.a = self.satp.get().ppn() * PAGESIZE;
+        self.a = self.cur_satp().ppn() * PAGESIZE;
         Ok(2)
     }
     // 2. Let pte be the value of the PTE at address a+va.vpn[i]×PTESIZE. (For Sv32, PTESIZE=4.)
@@ -93,11 +179,17 @@ impl Mmu {
     fn va_translation_step2(&mut self) -> Result<(), TrapType> {
         let pte_size = self.satp_mode.get_ptesize() as u64;
 
-        let pte_addr = self.a + self.va.get_ppn_by_idx(self.i as u8) * pte_size;
+        let mut pte_addr = self.a + self.va.get_ppn_by_idx(self.i as u8) * pte_size;
+        // the VS-stage page table is in the guest physical address space
+        if self.effective_virt {
+            pte_addr = self
+                .g_stage_translate(pte_addr, true)
+                .inspect_err(|_| self.implicit_fault = true)?;
+        }
         // warn!("va:{:?}", self.stap);
         // warn!("va:{:?}", self.va);
         // assert_eq!(self.stap.ppn() * 4096, self.a);
-        // todo! PMA or PMP check
+        self.check_pte_access(pte_addr, pte_size as usize)?;
         let pte_data = self
             .caches
             .borrow_mut()
@@ -105,6 +197,7 @@ impl Mmu {
             .read(pte_addr, pte_size as usize)
             .unwrap();
         // self.pte = Sv39PTE::from(pte_data).into();
+        self.ptw_pte(pte_addr, pte_data);
         self.pte = self.get_pteops(pte_data);
 
         Ok(())
@@ -113,7 +206,11 @@ impl Mmu {
     // future standard use are set within pte, stop and raise a page-fault exception corresponding
     // to the original access type.
     fn va_translation_step3(&self) -> Result<(), TrapType> {
-        if !self.pte.v() || (!self.pte.r() && self.pte.w()) {
+        if !self.pte.v()
+            || (!self.pte.r() && self.pte.w())
+            || self.napot_reserved(&self.pte)
+            || self.pbmt_reserved(&self.pte, self.effective_virt)
+        {
             Err(self.access_type.throw_page_exception())
         } else {
             Ok(())
@@ -156,19 +253,16 @@ impl Mmu {
         // };
 
         match self.access_type {
-            AccessType::Fetch(_) if !self.pte.x() => {
+            AccessType::Fetch(_) if !self.pte.x() || !self.check_u_bit() => {
                 return Err(self.access_type.throw_page_exception());
             }
             // When MXR=0, only loads from pages marked readable (R=1 in Figure 4.18) will succeed.
             // When MXR=1, loads from pages marked either readable or executable (R=1 or X=1) will succeed.
             // MXR has no effect when page-based virtual memory is not in effect.
-            AccessType::Load(_)
-                if !(self.pte.r() || self.pte.x() & self.mstatus.get().mxr())
-                    || !self.check_sum_bit() =>
-            {
+            AccessType::Load(_) if !self.load_allowed() || !self.check_u_bit() => {
                 return Err(self.access_type.throw_page_exception());
             }
-            AccessType::Store(_) | AccessType::Amo(_) if !self.pte.w() || !self.check_sum_bit() => {
+            AccessType::Store(_) | AccessType::Amo(_) if !self.pte.w() || !self.check_u_bit() => {
                 return Err(self.access_type.throw_page_exception());
             }
             _ => {}
@@ -190,7 +284,7 @@ impl Mmu {
             false
         };
 
-        if is_misalign_superpage() {
+        if is_misalign_superpage() || !Self::napot_valid(&self.pte, self.i) {
             return Err(self.access_type.throw_page_exception());
         }
 
@@ -221,28 +315,35 @@ impl Mmu {
     //      + pa.ppn[LEVELS − 1 : i] = pte.ppn[LEVELS − 1 : i].
 
     fn va_translation_step8(&mut self) -> Result<u8, TrapType> {
-        let asid = self.satp.get().asid() as u16;
-        let page_size = PageSize::from_i(self.i as usize);
+        let asid = self.cur_satp().asid() as u16;
+        let page_size = match (self.pte.n(), self.satp_mode) {
+            (true, _) => PageSize::P64K,
+            (false, StapMode::Sv32) if self.i == 1 => PageSize::P4M,
+            (false, _) => PageSize::from_i(self.i as usize),
+        };
 
         let tlb_key = TLBKey {
             va: self.va.raw() & page_size.get_mask(),
-            asid,
+            asid: (!self.pte.g()).then_some(asid),
+            page_size,
         };
         let entry = TLBEntry::new(self.pte, page_size, asid);
 
         let pa = entry.get_pa(&self.va);
 
         self.pa = self.get_paops(pa);
+        self.mem_type = MemType::from_pbmt(self.pte.pbmt());
 
         // debug!("page_size:{:?}", PageSize::from_i(self.i as usize));
 
-        if !self.no_tlb() {
+        // the guest translations are not cached, the TLB is not tagged by VMID
+        if !self.no_tlb() && !self.effective_virt {
             self.tlb.insert(tlb_key, entry);
             // if (self.va.raw() & !(0xfff_u64)) == 0x1b6000 {
             //     self.debug_tlb();
             //     println!("ppn:{:x},asid:{:x}", self.va.raw(), asid);
             // }
-            let res = self.fast_path(self.va.raw()).unwrap();
+            let res = *self.tlb.peek(&tlb_key).unwrap();
             assert_eq!(res.asid, entry.asid);
             assert_eq!(res.page_size, entry.page_size);
             assert_eq!(res.pte.raw(), entry.pte.raw());
@@ -251,12 +352,151 @@ impl Mmu {
         Ok(1)
     }
 
-    fn check_sum_bit(&self) -> bool {
-        if self.mmu_effective_priv != PrivilegeLevels::Supervisor {
+    // N is reserved without Svnapot, and in the non-leaf PTEs
+    fn napot_reserved(&self, pte: &PTEenume) -> bool {
+        pte.n() && (!self.config.is_enable_ext("svnapot") || pte.point_next_level())
+    }
+
+    // Svnapot only defines the 64KiB range of a level 0 PTE, pte.ppn[0][3:0] = 0b1000
+    fn napot_valid(pte: &PTEenume, i: i8) -> bool {
+        !pte.n() || (i == 0 && pte.get_ppn_by_idx(0) & 0xf == 0b1000)
+    }
+
+    // Svpbmt: PBMT=3 is reserved, PBMT of a non-leaf PTE is reserved, and PBMT is
+    // reserved unless menvcfg.PBMTE (and henvcfg.PBMTE for the VS-stage) is set
+    fn pbmt_reserved(&self, pte: &PTEenume, vs_stage: bool) -> bool {
+        let mut enabled = self.menvcfg.get().pbmte();
+        if vs_stage {
+            enabled &= self.virt_regs.henvcfg.get().pbmte();
+        }
+        pte.pbmt() != 0 && (pte.pbmt() == 3 || !enabled || pte.point_next_level())
+    }
+
+    // the memory type of the last translated access, the NC and IO pages bypass the dcache
+    pub fn mem_type(&self) -> MemType {
+        self.mem_type
+    }
+
+    // U-mode only accesses the U=1 pages. S-mode never executes them, the
+    // loads and stores need SUM=1, vsstatus.SUM is used in VS-mode
+    fn check_u_bit(&self) -> bool {
+        match self.mmu_effective_priv {
+            PrivilegeLevels::User => return self.pte.u(),
+            PrivilegeLevels::Supervisor => (),
+            PrivilegeLevels::Machine => return true,
+        }
+        let sum = if self.effective_virt {
+            self.virt_regs.vsstatus.get().sum()
+        } else {
+            self.mstatus.get().sum()
+        };
+        let fetch = self.access_type == AccessType::Fetch(0);
+        !self.pte.u() || (sum && !fetch)
+    }
+
+    // When MXR=0, only loads from pages marked readable (R=1 in Figure 4.18) will succeed.
+    // When MXR=1, loads from pages marked either readable or executable (R=1 or X=1) will succeed.
+    // MXR has no effect when page-based virtual memory is not in effect.
+    // In VS-mode, the VS-stage uses mstatus.MXR | vsstatus.MXR, HLVX only needs X.
+    fn load_allowed(&self) -> bool {
+        if self.hlv == Some(true) {
+            return self.pte.x();
+        }
+        let mut mxr = self.mstatus.get().mxr();
+        if self.effective_virt {
+            mxr |= self.virt_regs.vsstatus.get().mxr();
+        }
+        self.pte.r() || self.pte.x() & mxr
+    }
+
+    // the bits above the virtual address (38 for Sv39, 47 for Sv48, 56 for Sv57)
+    // have to be copies of its top bit, the Sv32 addresses are 32 bits
+    fn va_canonical(&self, va: u64) -> bool {
+        if matches!(self.satp_mode, StapMode::Bare | StapMode::Sv32) {
             return true;
         }
-        // in S-mode
-        self.mstatus.get().sum() || !self.pte.u()
+        let va_bits = 12 + 9 * self.satp_mode.get_levels() as u32;
+        let high = (va as i64) >> (va_bits - 1);
+        high == 0 || high == -1
+    }
+
+    fn cur_satp(&self) -> SatpIn {
+        if self.effective_virt {
+            self.virt_regs.vsatp.get()
+        } else {
+            self.satp.get()
+        }
+    }
+
+    // G-stage translation (Sv39x4, Sv48x4, Sv57x4) of a guest physical address,
+    // the root page table is 16KiB and the guest physical address has 2 more bits.
+    // every G-stage access is treated as a U-mode access. implicit is the access of a VS-stage PTE
+    fn g_stage_translate(&mut self, gpa: u64, implicit: bool) -> Result<u64, TrapType> {
+        let hgatp = self.virt_regs.hgatp.get();
+        let mode = hgatp.stage_mode();
+        if mode == StapMode::Bare {
+            return Ok(gpa);
+        }
+        let fault = self.access_type.throw_guest_page_exception(gpa);
+        let levels = mode.get_levels();
+        if gpa >> (12 + 9 * levels + 2) != 0 {
+            return Err(fault);
+        }
+
+        let mut a = hgatp.ppn() * PAGESIZE;
+        let mut i = levels - 1;
+        let pte = loop {
+            let vpn_bits = if i == levels - 1 { 11 } else { 9 };
+            let vpn = (gpa >> (12 + 9 * i)) & ((1 << vpn_bits) - 1);
+            self.check_pte_access(a + vpn * 8, 8)?;
+            let pte_data = self
+                .caches
+                .borrow_mut()
+                .dcache
+                .read(a + vpn * 8, 8)
+                .map_err(|_| self.access_type.throw_access_exception())?;
+            self.ptw_pte(a + vpn * 8, pte_data);
+            let pte = Self::pteops_of(mode, pte_data);
+            if !pte.v()
+                || (!pte.r() && pte.w())
+                || self.napot_reserved(&pte)
+                || self.pbmt_reserved(&pte, false)
+            {
+                return Err(fault);
+            }
+            if pte.r() || pte.x() {
+                break pte;
+            }
+            if i == 0 {
+                return Err(fault);
+            }
+            i -= 1;
+            a = pte.ppn_all() * PAGESIZE;
+        };
+
+        let allowed = match self.access_type {
+            _ if implicit => pte.r(),
+            AccessType::Fetch(_) => pte.x(),
+            AccessType::Load(_) if self.hlv == Some(true) => pte.x(),
+            AccessType::Load(_) => pte.r() || pte.x() & self.mstatus.get().mxr(),
+            AccessType::Store(_) | AccessType::Amo(_) => pte.w(),
+        };
+        let misalign_superpage = (0..i).any(|idx| pte.get_ppn_by_idx(idx as u8) != 0)
+            || !Self::napot_valid(&pte, i as i8);
+        let store = !implicit && self.access_type.is_store();
+        if !pte.u() || !allowed || misalign_superpage || !pte.a() || (store && !pte.d()) {
+            return Err(fault);
+        }
+
+        let offset_mask = match pte.n() {
+            true => (1 << 16) - 1,
+            false => (1 << (12 + 9 * i)) - 1,
+        };
+        // a non-zero PBMT of the VS-stage overrides the G-stage
+        if !implicit && self.mem_type == MemType::Pma {
+            self.mem_type = MemType::from_pbmt(pte.pbmt());
+        }
+        Ok(((pte.ppn_all() * PAGESIZE) & !offset_mask) | (gpa & offset_mask))
     }
 
     pub fn page_table_walk(&mut self) -> Result<u64, TrapType> {
@@ -288,17 +528,30 @@ impl Mmu {
     }
 
     fn no_mmu(&mut self) -> bool {
-        let satp_bare_mode = self.satp_mode.eq(&StapMode::Bare);
         let mstatus: XstatusIn = self.mstatus.get();
         self.mmu_effective_priv = self.cur_priv.get();
+        self.effective_virt = self.virt_regs.virt.get();
 
+        // HLV, HLVX and HSV are translated as though V=1 and the privilege mode were hstatus.SPVP
         // When MPRV=1, load and store memory addresses are translated and protected, and endianness is applied, as though
         //the current privilege mode were set to MPP. Instruction address-translation and protection are
         // unaffected by the setting of MPRV. MPRV is read-only 0 if U-mode is not supported.
-        if self.access_type != AccessType::Fetch(0) && mstatus.mprv() {
+        if self.hlv.is_some() {
+            self.mmu_effective_priv = self.virt_regs.hstatus.get().get_spvp_priv();
+            self.effective_virt = true;
+        } else if self.access_type != AccessType::Fetch(0) && mstatus.mprv() {
             self.mmu_effective_priv = mstatus.get_mpp_priv();
+            self.effective_virt =
+                mstatus.mpv() && self.mmu_effective_priv != PrivilegeLevels::Machine;
         }
 
+        // the G-stage translation is always active when V=1
+        if self.effective_virt {
+            self.satp_mode = self.virt_regs.vsatp.get().mode();
+            return false;
+        }
+        let satp_bare_mode = self.satp_mode.eq(&StapMode::Bare);
+
         // If the effective privilege level is machine mode or if the satp mode is bare mode, then the MMU is effectively disabled
         // (i.e. no_mmu() returns true)
         let machine_mdoe = self.mmu_effective_priv.eq(&PrivilegeLevels::Machine);
@@ -306,12 +559,61 @@ impl Mmu {
     }
 
     pub fn translate(&mut self, addr: u64, len: usize) -> Result<u64, TrapType> {
+        let pa = self.translate_va(addr, len)?;
+        // M-mode loads and stores with MPRV=1 are checked with the MPP privilege
+        let pmp = self.pmp.borrow();
+        if !pmp.check(pa, len, &self.access_type, self.mmu_effective_priv) {
+            return Err(self.access_type.throw_access_exception());
+        }
+        // an unmapped address is left to the bus
+        if let Some(pma) = self.caches.borrow().bus.borrow().pma(pa) {
+            if !pma.allows(&self.access_type) {
+                return Err(self.access_type.throw_access_exception());
+            }
+            // Svpbmt NC and IO stay as they are
+            if !pma.cacheable && self.mem_type == MemType::Pma {
+                self.mem_type = MemType::Io;
+            }
+        }
+        Ok(pa)
+    }
+
+    // the PTE reads are S-mode loads, the access fault is the one of the original access.
+    // the page tables can not be in MMIO
+    fn check_pte_access(&self, pte_addr: u64, len: usize) -> Result<(), TrapType> {
+        let load = AccessType::Load(0);
+        let pmp = self.pmp.borrow();
+        let pmp_ok = pmp.check(pte_addr, len, &load, PrivilegeLevels::Supervisor);
+        let pma = self.caches.borrow().bus.borrow().pma(pte_addr);
+        match pmp_ok && pma.is_none_or(|x| x.cacheable && x.allows(&load)) {
+            true => Ok(()),
+            false => Err(self.access_type.throw_access_exception()),
+        }
+    }
+
+    fn translate_va(&mut self, addr: u64, len: usize) -> Result<u64, TrapType> {
+        // rv32 addresses come from sign extended gprs
+        let addr = match self.config.xlen() {
+            32 => addr & 0xffff_ffff,
+            _ => addr,
+        };
         if !check_aligned(addr, len) {
             return Err(self.access_type.throw_addr_misaligned_exception());
         }
+        self.mem_type = MemType::Pma;
+        self.implicit_fault = false;
         if self.no_mmu() {
             return Ok(addr);
         }
+        if !self.va_canonical(addr) {
+            return Err(self.access_type.throw_page_exception());
+        }
+
+        if self.effective_virt {
+            self.ptw_begin(addr);
+            let ret = self.two_stage_translate(addr);
+            return self.ptw_end(ret);
+        }
 
         if !self.no_tlb() {
             // todo! refactor!!!!!!!!!!!!!
@@ -331,21 +633,20 @@ impl Mmu {
                 // SUM and MXR fields of the mstatus register. If not, stop and raise a page-fault exception
                 // corresponding to the original access type.
 
-                let sum_bit = self.check_sum_bit();
+                let u_bit = self.check_u_bit();
                 match self.access_type {
-                    AccessType::Fetch(_) if !self.pte.x() => {
+                    AccessType::Fetch(_) if !self.pte.x() || !u_bit => {
                         return Err(self.access_type.throw_page_exception());
                     }
                     // When MXR=0, only loads from pages marked readable (R=1 in Figure 4.18) will succeed.
                     // When MXR=1, loads from pages marked either readable or executable (R=1 or X=1) will succeed.
                     // MXR has no effect when page-based virtual memory is not in effect.
                     AccessType::Load(_)
-                        if !(self.pte.r() || self.pte.x() & self.mstatus.get().mxr())
-                            || !sum_bit =>
+                        if !(self.pte.r() || self.pte.x() & self.mstatus.get().mxr()) || !u_bit =>
                     {
                         return Err(self.access_type.throw_page_exception());
                     }
-                    AccessType::Store(_) | AccessType::Amo(_) if !self.pte.w() || !sum_bit => {
+                    AccessType::Store(_) | AccessType::Amo(_) if !self.pte.w() || !u_bit => {
                         return Err(self.access_type.throw_page_exception());
                     }
                     _ => {}
@@ -356,25 +657,97 @@ impl Mmu {
                     return Err(self.access_type.throw_page_exception());
                 }
                 let pa = tlb_entry.get_pa(&self.get_vaops(addr));
+                self.mem_type = MemType::from_pbmt(self.pte.pbmt());
                 // debug!("translate: {:x} -> {:x}", addr, pa);
                 return Ok(pa);
             }
         }
 
         // do page table walk
+        self.ptw_begin(addr);
         self.va = self.get_vaops(addr);
         self.pa = self.get_paops(0);
-        self.page_table_walk()
+        let ret = self.page_table_walk();
+        self.ptw_end(ret)
+    }
+
+    // record the walks, see take_ptw_records
+    pub fn set_ptw_trace(&mut self, enable: bool) {
+        self.ptw = enable.then(Vec::new);
+    }
+
+    pub fn take_ptw_records(&mut self) -> Vec<PtwRecord> {
+        self.ptw.as_mut().map(core::mem::take).unwrap_or_default()
+    }
+
+    fn ptw_begin(&mut self, va: u64) {
+        let satp = u64::from(self.cur_satp());
+        if let Some(records) = self.ptw.as_mut() {
+            records.push(PtwRecord {
+                va,
+                satp,
+                ptes: vec![],
+                result: Ok(0),
+            });
+        }
+    }
+
+    fn ptw_pte(&mut self, addr: u64, pte: u64) {
+        if let Some(record) = self.ptw.as_mut().and_then(|x| x.last_mut()) {
+            record.ptes.push((addr, pte));
+        }
+    }
+
+    fn ptw_end(&mut self, ret: Result<u64, TrapType>) -> Result<u64, TrapType> {
+        if let Some(record) = self.ptw.as_mut().and_then(|x| x.last_mut()) {
+            record.result = ret;
+        }
+        ret
+    }
+
+    // the guest accesses: the VS-stage walk of vsatp gives the guest physical
+    // address, its PTE reads and the result are translated by the G-stage of hgatp
+    fn two_stage_translate(&mut self, gva: u64) -> Result<u64, TrapType> {
+        let gpa = match self.satp_mode {
+            StapMode::Bare => gva,
+            _ => {
+                self.va = self.get_vaops(gva);
+                self.pa = self.get_paops(0);
+                self.page_table_walk()?
+            }
+        };
+        self.g_stage_translate(gpa, false)
     }
 
     pub fn update_access_type(&mut self, access_type: &AccessType) {
         self.access_type = access_type.clone();
+        self.hlv = None;
         // update satp mode
         self.satp_mode = self.satp.get().mode();
     }
 
+    // the next access is a HLV/HLVX/HSV access, call after update_access_type
+    pub fn set_hlv(&mut self, hlvx: bool) {
+        self.hlv = Some(hlvx);
+    }
+
+    // the last translated access used the two-stage translation
+    pub fn virt_access(&self) -> bool {
+        self.effective_virt
+    }
+
+    // the G-stage fault of the last access was the read of a VS-stage PTE
+    pub fn implicit_fault(&self) -> bool {
+        self.implicit_fault
+    }
+
     fn get_pteops(&self, pte_data: u64) -> PTEenume {
-        match self.satp_mode {
+        Self::pteops_of(self.satp_mode, pte_data)
+    }
+
+    fn pteops_of(mode: StapMode, pte_data: u64) -> PTEenume {
+        match mode {
+            StapMode::Sv32 => PTEenume::Sv32PTE(pte_data.into()),
             StapMode::Sv39 => PTEenume::Sv39PTE(pte_data.into()),
             StapMode::Sv48 => PTEenume::Sv48PTE(pte_data.into()),
             StapMode::Sv57 => PTEenume::Sv57PTE(pte_data.into()),
@@ -384,6 +757,7 @@ impl Mmu {
 
     fn get_paops(&self, pa_data: u64) -> PAenume {
         match self.satp_mode {
+            StapMode::Sv32 => PAenume::Sv32PA(pa_data.into()),
             StapMode::Sv39 => PAenume::Sv39PA(pa_data.into()),
             StapMode::Sv48 => PAenume::Sv48PA(pa_data.into()),
             StapMode::Sv57 => PAenume::Sv57PA(pa_data.into()),
@@ -393,6 +767,7 @@ impl Mmu {
 
     fn get_vaops(&self, va_data: u64) -> VAenume {
         match self.satp_mode {
+            StapMode::Sv32 => VAenume::Sv32VA(va_data.into()),
             StapMode::Sv39 => VAenume::Sv39VA(va_data.into()),
             StapMode::Sv48 => VAenume::Sv48VA(va_data.into()),
             StapMode::Sv57 => VAenume::Sv57VA(va_data.into()),
@@ -402,32 +777,21 @@ impl Mmu {
 
     pub fn fast_path(&mut self, va: u64) -> Option<TLBEntry> {
         // println!("fast_path: {:x}", va);
-        // Compute the masks only once
-        let asid = self.satp.get().asid() as u16;
-        let va_p2m = va & PageSize::P2M.get_mask();
-        // Check for P2M page size
-        if let Some(entry) = self.tlb.get(&TLBKey { va: va_p2m, asid }).copied() {
-            if entry.page_size == PageSize::P2M {
-                self.tlb_hit += 1;
-                return Some(entry);
-            }
-        }
-        let va_p4k = va & PageSize::P4K.get_mask();
-
-        // Check for P4K page size
-        if let Some(entry) = self.tlb.get(&TLBKey { va: va_p4k, asid }).copied() {
-            if entry.page_size == PageSize::P4K {
-                self.tlb_hit += 1;
-                return Some(entry);
-            }
-        }
-
-        let va_p1g = va & PageSize::P1G.get_mask();
-        // Check for P1G page size
-        if let Some(entry) = self.tlb.get(&TLBKey { va: va_p1g, asid }).copied() {
-            if entry.page_size == PageSize::P1G {
-                self.tlb_hit += 1;
-                return Some(entry);
+        let satp = self.satp.get();
+        let asid = satp.asid() as u16;
+        let page_sizes = TLB_PAGE_SIZES.iter().filter(|x| x.in_mode(satp.mode()));
+        for &page_size in page_sizes {
+            let va = va & page_size.get_mask();
+            for asid in [Some(asid), None] {
+                let key = TLBKey {
+                    va,
+                    asid,
+                    page_size,
+                };
+                if let Some(entry) = self.tlb.get(&key).copied() {
+                    self.tlb_hit += 1;
+                    return Some(entry);
+                }
             }
         }
 
@@ -435,8 +799,30 @@ impl Mmu {
         None
     }
 
+    pub fn tlb_misses(&self) -> u64 {
+        self.tlb_miss
+    }
+
+    pub fn tlb_stats(&self) -> TlbStats {
+        TlbStats {
+            policy: self.tlb.policy(),
+            hits: self.tlb_hit,
+            misses: self.tlb_miss,
+            evictions: self.tlb.evictions(),
+        }
+    }
+
     pub fn show_perf(&self) {
-        info!("tlb hit: {}, tlb miss: {}", self.tlb_hit, self.tlb_miss);
+        if self.no_tlb() {
+            return;
+        }
+        info!(
+            "tlb {}: hit: {}, miss: {}, evictions: {}",
+            self.tlb.policy().name(),
+            self.tlb_hit,
+            self.tlb_miss,
+            self.tlb.evictions()
+        );
         info!(
             "tlb hit rate: {}",
             self.tlb_hit as f64 / (self.tlb_hit + self.tlb_miss) as f64
@@ -461,19 +847,37 @@ impl Mmu {
         }
     }
 
-    pub fn fence_vma(&mut self, va: u64, asid: u16) {
+    // sfence.vma rs1, rs2: None is x0. a fence of one page only looks up the
+    // keys of that page, the global pages are kept by the fences of one ASID
+    pub fn fence_vma(&mut self, va: Option<u64>, asid: Option<u16>) {
         // self.debug_tlb();
-
-
+        let va = match self.config.xlen() {
+            32 => va.map(|x| x & 0xffff_ffff),
+            _ => va,
+        };
         match (va, asid) {
-            (0, 0) => {
-                self.clear_tlb();
+            (None, None) => self.clear_tlb(),
+            (Some(va), Some(asid)) => {
+                for page_size in TLB_PAGE_SIZES {
+                    let tlb_key = TLBKey {
+                        va: va & page_size.get_mask(),
+                        asid: Some(asid),
+                        page_size,
+                    };
+                    if let Some(res) = self.tlb.remove(&tlb_key) {
+                        self.fence_vma_trace_log(Some(res));
+                    }
+                }
             }
-            (0, asid) => {
+            _ => {
                 let key_list = self
                     .tlb
                     .iter()
-                    .filter(|tlb_item| (tlb_item.1.asid == asid) && !tlb_item.1.pte.g())
+                    .filter(|(key, _)| {
+                        let va_match = va.is_none_or(|va| key.va == key.page_size.get_mask() & va);
+                        let asid_match = asid.is_none_or(|asid| key.asid == Some(asid));
+                        va_match && asid_match
+                    })
                     .map(|(key, _val)| *key)
                     .collect::<Vec<_>>();
 
@@ -482,34 +886,618 @@ impl Mmu {
                     self.fence_vma_trace_log(res);
                 });
             }
-            (va, 0) => {
-                let tlb_key = self
-                    .tlb
-                    .iter()
-                    .find(|tlb_item| (tlb_item.0.va == tlb_item.1.page_size.get_mask() & va))
-                    .map(|(key, _val)| *key);
+        }
+    }
+}
 
-                if let Some(tlb_key) = tlb_key {
-                    let res = self.tlb.remove(&tlb_key);
-                    self.fence_vma_trace_log(res);
-                }
+#[cfg(test)]
+mod test_mmu {
+    use alloc::{string::ToString, vec::Vec};
+
+    use crate::{
+        config::Config,
+        device::device_trait::MEM_BASE,
+        rv64core::{
+            bus::Bus,
+            cpu_core::CpuCore,
+            csr_regs_define::{SatpIn, StapMode},
+            inst::inst_base::{
+                AccessType, PrivilegeLevels, CSR_MENVCFG, CSR_PMPADDR0, CSR_PMPADDR1, CSR_PMPCFG0,
+                CSR_SATP,
+            },
+            mmu::vm_info::MemType,
+            test_cpu::{exec, test_bus, test_cpu_on},
+            traptype::TrapType,
+        },
+        tools::RcRefCell,
+    };
+
+    const NAPOT: u64 = 1 << 63;
+    const PBMT_NC: u64 = 1 << 61;
+    const PBMT_IO: u64 = 2 << 61;
+    // V R W A D
+    const LEAF: u64 = 0xc7;
+
+    fn create_cpu(bus: &RcRefCell<Bus>, isa: &str, dcache_size: usize) -> CpuCore {
+        let mut config = Config::new();
+        config.set_isa(isa);
+        config.set_dcache_size(dcache_size);
+        // no PMP entries, S-mode and U-mode can access all memory
+        config.set_pmp_entries(0);
+        create_cpu_with(bus, config)
+    }
+
+    fn create_cpu_with(bus: &RcRefCell<Bus>, mut config: Config) -> CpuCore {
+        if config.get_mmu_type() == StapMode::Bare {
+            config.set_mmu_type("sv39");
+        }
+        config.set_s_mode();
+        config.set_tlb_size(16);
+        let mode = config.get_mmu_type();
+        let cpu = test_cpu_on(bus, config);
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        let satp = SatpIn::new().with_mode(mode).with_ppn(MEM_BASE >> 12);
+        cpu.csr_regs.satp.set(satp);
+        cpu
+    }
+
+    fn load(cpu: &mut CpuCore, va: u64) -> Result<u64, TrapType> {
+        cpu.read(va, 4, AccessType::Load(va))
+    }
+
+    fn store(cpu: &mut CpuCore, va: u64, data: u64) -> Result<u64, TrapType> {
+        cpu.write(va, data, 4, AccessType::Store(va))
+    }
+
+    fn create_bus() -> RcRefCell<Bus> {
+        let bus = test_bus(0x20000);
+        // va 0x4000_0000 -> level 1 table at 0x8000_1000 -> level 0 table at 0x8000_2000
+        let mut bus_u = bus.borrow_mut();
+        bus_u
+            .write(MEM_BASE + 8, ((MEM_BASE + 0x1000) >> 2) | 1, 8)
+            .unwrap();
+        bus_u
+            .write(MEM_BASE + 0x1000, ((MEM_BASE + 0x2000) >> 2) | 1, 8)
+            .unwrap();
+        drop(bus_u);
+        bus
+    }
+
+    // va 0x4001_0000 -> pa 0x8001_0000, one 64KiB NAPOT range in the level 0 table
+    #[test]
+    fn svnapot_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let napot_pte = (((MEM_BASE + 0x10000) >> 12 | 0b1000) << 10) | NAPOT | LEAF;
+        for vpn0 in 0x10..0x20 {
+            write(MEM_BASE + 0x2000 + vpn0 * 8, napot_pte);
+        }
+        write(MEM_BASE + 0x12340, 0xdead_beef);
+        write(MEM_BASE + 0x1f000, 0x1234_5678);
+
+        let mut cpu = create_cpu(&bus, "rv64imac_svnapot", 0);
+        assert_eq!(load(&mut cpu, 0x4001_2340), Ok(0xdead_beef));
+        // the other pages of the range hit the same TLB entry
+        assert_eq!(load(&mut cpu, 0x4001_f000), Ok(0x1234_5678));
+        assert_eq!(cpu.mmu.tlb_miss, 1);
+
+        // only ppn[0][3:0] = 0b1000 is defined
+        write(MEM_BASE + 0x2000 + 0x11 * 8, napot_pte ^ (0b1100 << 10));
+        cpu.mmu.fence_vma(None, None);
+        assert_eq!(
+            load(&mut cpu, 0x4001_1000),
+            Err(TrapType::LoadPageFault(0x4001_1000))
+        );
+
+        // N is reserved without Svnapot
+        let mut cpu = create_cpu(&bus, "rv64imac", 0);
+        assert_eq!(
+            load(&mut cpu, 0x4001_2340),
+            Err(TrapType::LoadPageFault(0x4001_2340))
+        );
+    }
+
+    // va 0x4001_0000 with PBMT=IO and va 0x4001_1000 with PMA, both map pa 0x8001_0000
+    #[test]
+    fn svpbmt_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let pte = ((MEM_BASE + 0x10000) >> 12 << 10) | LEAF;
+        write(MEM_BASE + 0x2000 + 0x10 * 8, pte | PBMT_IO);
+        write(MEM_BASE + 0x2000 + 0x11 * 8, pte);
+        write(MEM_BASE + 0x2000 + 0x12 * 8, pte | PBMT_IO | PBMT_NC);
+        write(MEM_BASE + 0x10000, 0x1111);
+
+        let mut cpu = create_cpu(&bus, "rv64imac_svpbmt", 64);
+        // PBMT is reserved until menvcfg.PBMTE is set
+        assert_eq!(
+            load(&mut cpu, 0x4001_0000),
+            Err(TrapType::LoadPageFault(0x4001_0000))
+        );
+        let menvcfg = cpu.csr_regs.menvcfg.get().with_pbmte(true);
+        cpu.csr_regs.menvcfg.set(menvcfg);
+        assert_eq!(load(&mut cpu, 0x4001_0000), Ok(0x1111));
+        assert_eq!(cpu.mmu.mem_type(), MemType::Io);
+
+        // a PMA store stays in the dcache, the IO alias still reads the memory
+        assert_eq!(store(&mut cpu, 0x4001_1000, 0x2222), Ok(0));
+        assert_eq!(cpu.mmu.mem_type(), MemType::Pma);
+        assert_eq!(load(&mut cpu, 0x4001_0000), Ok(0x1111));
+        assert_eq!(load(&mut cpu, 0x4001_1000), Ok(0x2222));
+        // an IO store goes to the bus right away
+        assert!(store(&mut cpu, 0x4001_0000, 0x3333).is_ok());
+        assert_eq!(bus.borrow_mut().read(MEM_BASE + 0x10000, 4).unwrap(), 0x3333);
+
+        // PBMT=3 is reserved
+        assert_eq!(
+            load(&mut cpu, 0x4001_2000),
+            Err(TrapType::LoadPageFault(0x4001_2000))
+        );
+
+        // without Svpbmt menvcfg.PBMTE is read-only zero
+        let mut cpu = create_cpu(&bus, "rv64imac", 64);
+        cpu.csr_regs
+            .write(CSR_MENVCFG.into(), 1 << 62, PrivilegeLevels::Machine)
+            .unwrap();
+        assert!(!cpu.csr_regs.menvcfg.get().pbmte());
+        assert_eq!(
+            load(&mut cpu, 0x4001_0000),
+            Err(TrapType::LoadPageFault(0x4001_0000))
+        );
+    }
+
+    // va 0x4001_0000 -> pa 0x8001_0000, va 0x4001_1000 -> pa 0x8001_5000
+    #[test]
+    fn misaligned_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let pte = |pa: u64| (pa >> 12 << 10) | LEAF;
+        write(MEM_BASE + 0x2000 + 0x10 * 8, pte(MEM_BASE + 0x10000));
+        write(MEM_BASE + 0x10ff8, 0x1122_3344_5566_7788);
+        write(MEM_BASE + 0x15000, 0x99aa_bbcc_ddee_ff00);
+
+        let mut cpu = create_cpu(&bus, "rv64imac", 0);
+        assert_eq!(
+            load(&mut cpu, 0x4001_0ffe),
+            Err(TrapType::LoadAddressMisaligned(0x4001_0ffe))
+        );
+
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_misaligned(true);
+        config.set_pmp_entries(0);
+        let mut cpu = create_cpu_with(&bus, config);
+        assert_eq!(load(&mut cpu, 0x4001_0ff9), Ok(0x4455_6677));
+        // the second page is not mapped yet, the first one is not written
+        assert_eq!(
+            load(&mut cpu, 0x4001_0ffe),
+            Err(TrapType::LoadPageFault(0x4001_1000))
+        );
+        assert_eq!(
+            store(&mut cpu, 0x4001_0ffe, 0),
+            Err(TrapType::StorePageFault(0x4001_1000))
+        );
+        assert_eq!(load(&mut cpu, 0x4001_0ffc), Ok(0x1122_3344));
+
+        write(MEM_BASE + 0x2000 + 0x11 * 8, pte(MEM_BASE + 0x15000));
+        cpu.mmu.fence_vma(None, None);
+        assert_eq!(load(&mut cpu, 0x4001_0ffe), Ok(0xff00_1122));
+        assert_eq!(store(&mut cpu, 0x4001_0ffe, 0x5566_7788), Ok(0));
+        assert_eq!(
+            bus.borrow_mut().read(MEM_BASE + 0x10ffe, 2).unwrap(),
+            0x7788
+        );
+        assert_eq!(
+            bus.borrow_mut().read(MEM_BASE + 0x15000, 2).unwrap(),
+            0x5566
+        );
+        // the AMOs still trap
+        assert_eq!(
+            cpu.read(0x4001_0ffe, 4, AccessType::Amo(0x4001_0ffe)),
+            Err(TrapType::StoreAddressMisaligned(0x4001_0ffe))
+        );
+    }
+
+    // pmp entry 0: TOR below 0x8001_0000 R, the page tables. entry 1: NAPOT 0x8001_0000 4KiB R
+    #[test]
+    fn pmp_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let pte = |pa: u64| (pa >> 12 << 10) | LEAF;
+        write(MEM_BASE + 0x2000 + 0x10 * 8, pte(MEM_BASE + 0x10000));
+        write(MEM_BASE + 0x2000 + 0x11 * 8, pte(MEM_BASE + 0x11000));
+        write(MEM_BASE + 0x10000, 0x1122_3344);
+
+        // no entry matches the page table walk
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        let mut cpu = create_cpu_with(&bus, config);
+        assert_eq!(
+            load(&mut cpu, 0x4001_0000),
+            Err(TrapType::LoadAccessFault(0x4001_0000))
+        );
+
+        let mut csr_write = |addr: u16, data: u64| {
+            cpu.csr_regs
+                .write(addr.into(), data, PrivilegeLevels::Machine)
+                .unwrap()
+        };
+        csr_write(CSR_PMPADDR0, (MEM_BASE + 0x10000) >> 2);
+        let napot_4k = (0x1000 >> 3) - 1;
+        csr_write(CSR_PMPADDR1, (MEM_BASE + 0x10000) >> 2 | napot_4k);
+        csr_write(CSR_PMPCFG0, 0x19_09);
+        cpu.mmu.fence_vma(None, None);
+        assert_eq!(load(&mut cpu, 0x4001_0000), Ok(0x1122_3344));
+        assert_eq!(
+            store(&mut cpu, 0x4001_0000, 0),
+            Err(TrapType::StoreAccessFault(0x4001_0000))
+        );
+        assert_eq!(
+            load(&mut cpu, 0x4001_1000),
+            Err(TrapType::LoadAccessFault(0x4001_1000))
+        );
+        // M-mode is not checked by the unlocked entries
+        cpu.cur_priv.set(PrivilegeLevels::Machine);
+        assert_eq!(store(&mut cpu, 0x8001_1000, 0), Ok(0));
+    }
+
+    // the CLINT is MMIO: no fetch, no AMO, not cached
+    #[test]
+    fn pma_test() {
+        let bus = create_bus();
+        let mut cpu = create_cpu(&bus, "rv64imac", 64);
+        cpu.cur_priv.set(PrivilegeLevels::Machine);
+        let msip = 0x0200_0000;
+        assert_eq!(store(&mut cpu, msip, 0), Ok(0));
+        assert_eq!(load(&mut cpu, msip), Ok(0));
+        assert_eq!(cpu.mmu.mem_type(), MemType::Io);
+        assert_eq!(
+            cpu.read(msip, 4, AccessType::Amo(msip)),
+            Err(TrapType::StoreAccessFault(msip))
+        );
+        assert_eq!(
+            cpu.icahce_read(msip, 4),
+            Err(TrapType::InstructionAccessFault(msip))
+        );
+        assert!(cpu.icahce_read(MEM_BASE, 4).is_ok());
+        assert_eq!(cpu.mmu.mem_type(), MemType::Pma);
+
+        // the page tables can not be in MMIO
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        let satp = SatpIn::new().with_mode(StapMode::Sv39).with_ppn(msip >> 12);
+        cpu.csr_regs.satp.set(satp);
+        assert_eq!(
+            load(&mut cpu, 0x4001_0000),
+            Err(TrapType::LoadAccessFault(0x4001_0000))
+        );
+    }
+
+    // Sv48, the root table at 0x8000_0000 maps a 512GiB page at va 1TiB,
+    // the level 2 table at 0x8000_3000 a 1GiB page, the level 1 table at 0x8000_4000
+    // a 2MiB page and the level 0 table at 0x8000_5000 a 4KiB page
+    #[test]
+    fn sv48_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let table = |pa: u64| (pa >> 12 << 10) | 1;
+        let leaf = |pa: u64| (pa >> 12 << 10) | LEAF;
+        write(MEM_BASE, table(MEM_BASE + 0x3000));
+        write(MEM_BASE + 8, 0);
+        write(MEM_BASE + 2 * 8, leaf(0));
+        write(MEM_BASE + 0x3000 + 2 * 8, leaf(MEM_BASE));
+        write(MEM_BASE + 0x3000 + 3 * 8, table(MEM_BASE + 0x4000));
+        write(MEM_BASE + 0x4000, leaf(MEM_BASE));
+        write(MEM_BASE + 0x4000 + 8, table(MEM_BASE + 0x5000));
+        write(MEM_BASE + 0x5000, leaf(MEM_BASE + 0x10000));
+        write(MEM_BASE + 0x6000, 0x1111);
+        write(MEM_BASE + 0x10000, 0x4444);
+
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_mmu_type("sv48");
+        config.set_pmp_entries(0);
+        let mut cpu = create_cpu_with(&bus, config);
+        let vas = [0x100_8000_6000, 0x8000_6000, 0xc000_6000, 0xc020_0000];
+        for _ in 0..2 {
+            for (va, data) in vas.into_iter().zip([0x1111, 0x1111, 0x1111, 0x4444]) {
+                assert_eq!(load(&mut cpu, va), Ok(data));
             }
-            (va, asid) => {
-                let tlb_key = self
-                    .tlb
-                    .iter()
-                    .find(|tlb_item| {
-                        (tlb_item.0.asid == asid)
-                            && (tlb_item.0.va == tlb_item.1.page_size.get_mask() & va)
-                            && (!tlb_item.1.pte.g())
-                    })
-                    .map(|(key, _val)| *key);
+        }
+        // one TLB entry for each page size
+        assert_eq!(cpu.mmu.tlb_misses(), 4);
+        cpu.mmu.fence_vma(Some(0x100_8000_0000), None);
+        assert_eq!(load(&mut cpu, 0x100_8001_0000), Ok(0x4444));
+        assert_eq!(cpu.mmu.tlb_misses(), 5);
 
-                if let Some(tlb_key) = tlb_key {
-                    let res = self.tlb.remove(&tlb_key);
-                    self.fence_vma_trace_log(res);
-                }
+        // bits 63:48 are copies of bit 47
+        assert_eq!(
+            load(&mut cpu, 0x8000_8000_6000),
+            Err(TrapType::LoadPageFault(0x8000_8000_6000))
+        );
+        // Sv39 sees the level 2 table as a level 1 table, a 2MiB page at va 4MiB
+        let satp = cpu.csr_regs.satp.get().with_mode(StapMode::Sv39);
+        cpu.csr_regs.satp.set(satp);
+        cpu.mmu.fence_vma(None, None);
+        assert_eq!(load(&mut cpu, 0x40_6000), Ok(0x1111));
+        assert_eq!(
+            load(&mut cpu, 0x100_8000_6000),
+            Err(TrapType::LoadPageFault(0x100_8000_6000))
+        );
+
+        // Sv57 is not supported, the write has no effect
+        let m_mode = PrivilegeLevels::Machine;
+        cpu.csr_regs
+            .write(CSR_SATP.into(), 10 << 60 | 0x1234, m_mode)
+            .unwrap();
+        assert_eq!(u64::from(cpu.csr_regs.satp.get()), u64::from(satp));
+        cpu.csr_regs
+            .write(CSR_SATP.into(), 9 << 60 | 0x1234, m_mode)
+            .unwrap();
+        assert_eq!(cpu.csr_regs.satp.get().mode(), StapMode::Sv48);
+    }
+
+    // Sv57, the root table at 0x8000_0000 maps a 256TiB page at va 256TiB, the
+    // level 3 table at 0x8000_3000 a 512GiB page, the level 2 table at 0x8000_4000
+    // a 1GiB page, the level 1 table at 0x8000_5000 a 2MiB page and the level 0
+    // table at 0x8000_7000 a 4KiB page
+    #[test]
+    fn sv57_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let table = |pa: u64| (pa >> 12 << 10) | 1;
+        let leaf = |pa: u64| (pa >> 12 << 10) | LEAF;
+        write(MEM_BASE, table(MEM_BASE + 0x3000));
+        write(MEM_BASE + 8, leaf(0));
+        write(MEM_BASE + 0x3000, table(MEM_BASE + 0x4000));
+        write(MEM_BASE + 0x3000 + 2 * 8, leaf(0));
+        write(MEM_BASE + 0x4000 + 2 * 8, leaf(MEM_BASE));
+        write(MEM_BASE + 0x4000 + 3 * 8, table(MEM_BASE + 0x5000));
+        write(MEM_BASE + 0x5000, leaf(MEM_BASE));
+        write(MEM_BASE + 0x5000 + 8, table(MEM_BASE + 0x7000));
+        write(MEM_BASE + 0x7000, leaf(MEM_BASE + 0x10000));
+        write(MEM_BASE + 0x6000, 0x1111);
+        write(MEM_BASE + 0x10000, 0x4444);
+
+        let mut config = Config::new();
+        config.set_isa("rv64imac");
+        config.set_mmu_type("sv57");
+        config.set_pmp_entries(0);
+        let mut cpu = create_cpu_with(&bus, config);
+        let vas = [
+            0x1_0000_8000_6000,
+            0x100_8000_6000,
+            0x8000_6000,
+            0xc000_6000,
+            0xc020_0000,
+        ];
+        let datas = [0x1111, 0x1111, 0x1111, 0x1111, 0x4444];
+        for _ in 0..2 {
+            for (va, data) in vas.into_iter().zip(datas) {
+                assert_eq!(load(&mut cpu, va), Ok(data));
             }
         }
+        assert_eq!(cpu.mmu.tlb_misses(), 5);
+
+        // bits 63:57 are copies of bit 56
+        assert_eq!(
+            load(&mut cpu, 0x100_0000_8000_6000),
+            Err(TrapType::LoadPageFault(0x100_0000_8000_6000))
+        );
+        // Sv48 sees the root table entry 1 as a 512GiB page
+        let satp = cpu.csr_regs.satp.get().with_mode(StapMode::Sv48);
+        cpu.csr_regs.satp.set(satp);
+        cpu.mmu.fence_vma(None, None);
+        assert_eq!(load(&mut cpu, 0x80_8000_6000), Ok(0x1111));
+        assert_eq!(
+            load(&mut cpu, 0x1_0000_8000_6000),
+            Err(TrapType::LoadPageFault(0x1_0000_8000_6000))
+        );
+    }
+
+    // Sv32, the root table at 0x8000_0000 maps a 4MiB megapage at va 0x8000_0000
+    // and the level 0 table at 0x8000_3000 a 4KiB page at va 0xc040_0000
+    #[test]
+    fn sv32_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 4).unwrap();
+        let table = |pa: u64| (pa >> 12 << 10) | 1;
+        let leaf = |pa: u64| (pa >> 12 << 10) | LEAF;
+        write(MEM_BASE + 0x200 * 4, leaf(MEM_BASE));
+        write(MEM_BASE + 0x301 * 4, table(MEM_BASE + 0x3000));
+        write(MEM_BASE + 0x302 * 4, leaf(0x2_0000_0000));
+        write(MEM_BASE + 0x303 * 4, leaf(MEM_BASE + 0x1000));
+        write(MEM_BASE + 0x3000, leaf(MEM_BASE + 0x10000));
+        write(MEM_BASE + 0x6000, 0x1111);
+        write(MEM_BASE + 0x10000, 0x4444);
+
+        let mut config = Config::new();
+        config.set_isa("rv32imac");
+        config.set_mmu_type("sv32");
+        config.set_pmp_entries(0);
+        let mut cpu = create_cpu_with(&bus, config);
+        // the gprs hold sign extended addresses
+        assert_eq!(load(&mut cpu, 0xffff_ffff_8000_6000), Ok(0x1111));
+        assert_eq!(load(&mut cpu, 0xc040_0000), Ok(0x4444));
+        assert_eq!(load(&mut cpu, 0x8000_6000), Ok(0x1111));
+        assert_eq!(cpu.mmu.tlb_misses(), 2);
+        // the physical addresses are 34 bits
+        cpu.mmu.update_access_type(&AccessType::Load(0xc080_0010));
+        assert_eq!(cpu.mmu.translate(0xc080_0010, 4), Ok(0x2_0000_0010));
+        // a misaligned megapage
+        assert_eq!(
+            load(&mut cpu, 0xc0c0_0000),
+            Err(TrapType::LoadPageFault(0xc0c0_0000))
+        );
+
+        // satp is MODE[31] ASID[30:22] PPN[21:0], the rv64 modes are not supported
+        let m_mode = PrivilegeLevels::Machine;
+        let satp_val = 1 << 31 | 5 << 22 | MEM_BASE >> 12;
+        let satp = CSR_SATP.into();
+        cpu.csr_regs.write(satp, satp_val, m_mode).unwrap();
+        assert_eq!(cpu.csr_regs.read(satp, m_mode), Ok(satp_val));
+        assert_eq!(cpu.csr_regs.satp.get().mode(), StapMode::Sv32);
+        assert_eq!(cpu.csr_regs.satp.get().asid(), 5);
+        cpu.csr_regs.write_raw(satp, 8 << 60);
+        assert_eq!(cpu.csr_regs.satp.get().mode(), StapMode::Sv32);
+        cpu.csr_regs.write(satp, 0, m_mode).unwrap();
+        assert_eq!(cpu.csr_regs.satp.get().mode(), StapMode::Bare);
+    }
+
+    // va 0x4001_0000 -> pa 0x8001_0000, the global page va 0x4001_1000 -> pa 0x8001_1000
+    // and the U-mode page va 0x4001_2000 -> pa 0x8001_0000
+    #[test]
+    fn asid_test() {
+        const GLOBAL: u64 = 1 << 5;
+        const USER: u64 = 1 << 4;
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let pte = |pa: u64| (pa >> 12 << 10) | LEAF;
+        write(MEM_BASE + 0x2000 + 0x10 * 8, pte(MEM_BASE + 0x10000));
+        let global_pte = pte(MEM_BASE + 0x11000) | GLOBAL;
+        write(MEM_BASE + 0x2000 + 0x11 * 8, global_pte);
+        write(MEM_BASE + 0x2000 + 0x12 * 8, pte(MEM_BASE + 0x10000) | USER);
+        write(MEM_BASE + 0x10000, 0x1111);
+        write(MEM_BASE + 0x11000, 0x2222);
+        write(MEM_BASE + 0x12000, 0x3333);
+
+        let mut cpu = create_cpu(&bus, "rv64imac", 0);
+        let set_asid = |cpu: &mut CpuCore, asid: u64| {
+            let satp = cpu.csr_regs.satp.get().with_asid(asid);
+            cpu.csr_regs.satp.set(satp);
+        };
+        set_asid(&mut cpu, 1);
+        assert_eq!(load(&mut cpu, 0x4001_0000), Ok(0x1111));
+        assert_eq!(load(&mut cpu, 0x4001_1000), Ok(0x2222));
+        // ASID 2 has its own entry, the global page is shared
+        write(MEM_BASE + 0x2000 + 0x10 * 8, pte(MEM_BASE + 0x12000));
+        set_asid(&mut cpu, 2);
+        assert_eq!(load(&mut cpu, 0x4001_0000), Ok(0x3333));
+        assert_eq!(load(&mut cpu, 0x4001_1000), Ok(0x2222));
+        set_asid(&mut cpu, 1);
+        assert_eq!(load(&mut cpu, 0x4001_0000), Ok(0x1111));
+        assert_eq!(cpu.mmu.tlb_misses(), 3);
+        // the fence of ASID 1 keeps ASID 2 and the global page
+        cpu.mmu.fence_vma(None, Some(1));
+        assert_eq!(load(&mut cpu, 0x4001_0000), Ok(0x3333));
+        assert_eq!(load(&mut cpu, 0x4001_1000), Ok(0x2222));
+        set_asid(&mut cpu, 2);
+        assert_eq!(load(&mut cpu, 0x4001_0000), Ok(0x3333));
+        assert_eq!(cpu.mmu.tlb_misses(), 4);
+
+        // U-mode only accesses the U=1 pages, S-mode needs SUM and never executes them
+        let user_page = 0x4001_2000;
+        assert_eq!(
+            load(&mut cpu, user_page),
+            Err(TrapType::LoadPageFault(user_page))
+        );
+        cpu.cur_priv.set(PrivilegeLevels::User);
+        assert_eq!(load(&mut cpu, user_page), Ok(0x1111));
+        assert_eq!(
+            load(&mut cpu, 0x4001_0000),
+            Err(TrapType::LoadPageFault(0x4001_0000))
+        );
+        cpu.cur_priv.set(PrivilegeLevels::Supervisor);
+        let mstatus = cpu.csr_regs.xstatus.get().with_sum(true);
+        cpu.csr_regs.xstatus.set(mstatus);
+        assert_eq!(load(&mut cpu, user_page), Ok(0x1111));
+        assert_eq!(
+            cpu.icahce_read(user_page, 4),
+            Err(TrapType::InstructionPageFault(user_page))
+        );
+    }
+
+    // va 0x4001_0000 -> pa 0x8001_0000 and the global page va 0x4001_1000 -> pa 0x8001_1000,
+    // sfence.vma a0,a1 with a0 = 0 or a1 = 0 is not sfence.vma x0,x0
+    #[test]
+    fn sfence_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let pte = |pa: u64| (pa >> 12 << 10) | LEAF;
+        write(MEM_BASE + 0x2000 + 0x10 * 8, pte(MEM_BASE + 0x10000));
+        let global_pte = pte(MEM_BASE + 0x11000) | 1 << 5;
+        write(MEM_BASE + 0x2000 + 0x11 * 8, global_pte);
+
+        let mut cpu = create_cpu(&bus, "rv64imac", 0);
+        let (page, global) = (0x4001_0000, 0x4001_1000);
+        // sfence.vma x0,a1, sfence.vma a0,x0 and sfence.vma a0,a1
+        let (fence_asid, fence_va, fence_both) = (0x12b00073, 0x12050073, 0x12b50073);
+        let sfence = |cpu: &mut CpuCore, inst: u32, a0: u64, a1: u64| {
+            cpu.gpr.write(10, a0);
+            cpu.gpr.write(11, a1);
+            exec(cpu, inst).unwrap();
+        };
+        let loads = |cpu: &mut CpuCore| {
+            assert!(load(cpu, page).is_ok() && load(cpu, global).is_ok());
+            cpu.mmu.tlb_misses()
+        };
+        assert_eq!(loads(&mut cpu), 2);
+        // ASID 0 only, the global page is kept
+        sfence(&mut cpu, fence_asid, 0, 0);
+        assert_eq!(loads(&mut cpu), 3);
+        // one page of every ASID, the global one too
+        sfence(&mut cpu, fence_va, global, 0);
+        assert_eq!(loads(&mut cpu), 4);
+        sfence(&mut cpu, fence_va, 0, 0);
+        assert_eq!(loads(&mut cpu), 4);
+        // one page of ASID 0
+        sfence(&mut cpu, fence_both, page, 0);
+        assert_eq!(loads(&mut cpu), 5);
+        sfence(&mut cpu, fence_both, global, 0);
+        assert_eq!(loads(&mut cpu), 5);
+    }
+
+    // Sv39, va 0x8000_0000 is a 1GiB page and va 0x4020_0000 a 2MiB page,
+    // both identity mapped to 0x8000_0000
+    #[test]
+    fn superpage_test() {
+        let bus = create_bus();
+        let write = |addr: u64, data: u64| bus.borrow_mut().write(addr, data, 8).unwrap();
+        let leaf = (MEM_BASE >> 12 << 10) | LEAF;
+        write(MEM_BASE + 2 * 8, leaf);
+        write(MEM_BASE + 0x1000 + 8, leaf);
+        write(MEM_BASE + 0x6000, 0x1111);
+        write(MEM_BASE + 0x1f000, 0x2222);
+
+        let mut cpu = create_cpu(&bus, "rv64imac", 0);
+        for base in [0x8000_0000, 0x4020_0000] {
+            assert_eq!(load(&mut cpu, base + 0x6000), Ok(0x1111));
+            assert_eq!(load(&mut cpu, base + 0x1f000), Ok(0x2222));
+            assert!(load(&mut cpu, base + 0x1_0000).is_ok());
+        }
+        // one entry for each superpage, the walks are not counted as hits
+        let stats = cpu.mmu.tlb_stats();
+        assert_eq!((stats.misses, stats.hits), (2, 4));
+        // a fence of one 4KiB page in the range drops the whole superpage
+        cpu.mmu.fence_vma(Some(0x8001_f000), Some(0));
+        assert_eq!(load(&mut cpu, 0x8000_6000), Ok(0x1111));
+        assert_eq!(load(&mut cpu, 0x4020_6000), Ok(0x1111));
+        assert_eq!(cpu.mmu.tlb_misses(), 3);
+    }
+
+    #[test]
+    fn ptw_trace_test() {
+        let bus = create_bus();
+        let leaf = ((MEM_BASE + 0x6000) >> 12 << 10) | LEAF;
+        bus.borrow_mut().write(MEM_BASE + 0x2000, leaf, 8).unwrap();
+        let mut cpu = create_cpu(&bus, "rv64imac", 0);
+        cpu.mmu.set_ptw_trace(true);
+        assert!(load(&mut cpu, 0x4000_0010).is_ok());
+        // a TLB hit is not a walk
+        assert!(load(&mut cpu, 0x4000_0020).is_ok());
+        assert!(load(&mut cpu, 0x4000_1000).is_err());
+
+        let records = cpu.mmu.take_ptw_records();
+        assert_eq!(records.len(), 2);
+        let tables = [MEM_BASE + 8, MEM_BASE + 0x1000, MEM_BASE + 0x2000];
+        assert_eq!(records[0].satp, u64::from(cpu.csr_regs.satp.get()));
+        let addrs: Vec<u64> = records[0].ptes.iter().map(|x| x.0).collect();
+        assert_eq!(addrs, tables);
+        assert_eq!(records[0].ptes[2].1, leaf);
+        assert_eq!(records[0].result, Ok(MEM_BASE + 0x6010));
+        assert_eq!(records[1].ptes[2], (MEM_BASE + 0x2008, 0));
+        let log = records[1].to_string();
+        assert!(log.starts_with("ptw va:40001000,satp:8000000000080000,pte[80000008]:"));
+        assert!(log.ends_with(" -> LoadPageFault"));
+        assert!(cpu.mmu.take_ptw_records().is_empty());
     }
 }
@@ -3,3 +3,6 @@ pub mod sv39;
 pub mod cpu_mmu;
 pub mod vm_info;
 pub mod sv57;
+pub mod pmp;
+pub mod sv32;
+pub mod tlb;
\ No newline at end of file
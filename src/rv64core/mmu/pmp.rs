@@ -0,0 +1,204 @@
+use crate::rv64core::{
+    csr_regs_define::PMPcfgIn,
+    inst::inst_base::{AccessType, PrivilegeLevels},
+};
+
+pub const PMP_MAX_ENTRIES: usize = 64;
+
+// pmpaddr holds bits 55:2 of the physical address
+const PMPADDR_MASK: u64 = (1 << 54) - 1;
+
+const PMP_OFF: u8 = 0;
+const PMP_TOR: u8 = 1;
+const PMP_NA4: u8 = 2;
+const PMP_NAPOT: u8 = 3;
+
+// Physical Memory Protection, shared by the pmpcfg and pmpaddr csrs and the mmu.
+// the grain is 4 bytes, the entries after `entries` are read-only zero
+pub struct Pmp {
+    cfg: [PMPcfgIn; PMP_MAX_ENTRIES],
+    addr: [u64; PMP_MAX_ENTRIES],
+    entries: usize,
+    // an entry is not OFF, M-mode can skip the check otherwise
+    active: bool,
+}
+
+impl Pmp {
+    pub fn new(entries: usize) -> Self {
+        Pmp {
+            cfg: [PMPcfgIn::new(); PMP_MAX_ENTRIES],
+            addr: [0; PMP_MAX_ENTRIES],
+            entries: entries.min(PMP_MAX_ENTRIES),
+            active: false,
+        }
+    }
+
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    // A and L are reset to 0
+    pub fn reset(&mut self) {
+        self.cfg = [PMPcfgIn::new(); PMP_MAX_ENTRIES];
+        self.active = false;
+    }
+
+    // pmpcfg`reg` holds the cfg of the entries 4*reg.., eight of them on rv64
+    pub fn read_cfg(&self, reg: usize) -> u64 {
+        (0..8)
+            .filter_map(|i| self.cfg.get(reg * 4 + i))
+            .enumerate()
+            .fold(0, |acc, (i, cfg)| acc | (u8::from(*cfg) as u64) << (i * 8))
+    }
+
+    // the locked entries keep their cfg, R=0 W=1 is reserved and W is cleared
+    pub fn write_cfg(&mut self, reg: usize, data: u64) {
+        for i in 0..8 {
+            let idx = reg * 4 + i;
+            if idx >= self.entries || self.cfg[idx].l() {
+                continue;
+            }
+            let mut cfg = PMPcfgIn::from((data >> (i * 8)) as u8 & 0b1001_1111);
+            if !cfg.r() {
+                cfg.set_w(false);
+            }
+            self.cfg[idx] = cfg;
+        }
+        self.active = self.cfg.iter().any(|x| x.a() != PMP_OFF);
+    }
+
+    pub fn read_addr(&self, idx: usize) -> u64 {
+        self.addr[idx]
+    }
+
+    // a locked entry also locks pmpaddr[i-1] if it is TOR
+    pub fn write_addr(&mut self, idx: usize, data: u64) {
+        let next_tor_locked = self
+            .cfg
+            .get(idx + 1)
+            .is_some_and(|x| x.l() && x.a() == PMP_TOR && idx + 1 < self.entries);
+        if idx >= self.entries || self.cfg[idx].l() || next_tor_locked {
+            return;
+        }
+        self.addr[idx] = data & PMPADDR_MASK;
+    }
+
+    // [start, end) of an entry, None if it is OFF or an empty TOR range
+    fn range(&self, idx: usize) -> Option<(u64, u64)> {
+        let addr = self.addr[idx];
+        match self.cfg[idx].a() {
+            PMP_TOR => {
+                let start = match idx {
+                    0 => 0,
+                    _ => self.addr[idx - 1] << 2,
+                };
+                let end = addr << 2;
+                (start < end).then_some((start, end))
+            }
+            PMP_NA4 => Some((addr << 2, (addr << 2) + 4)),
+            PMP_NAPOT => {
+                // pmpaddr = base | (size/8 - 1)
+                let ones = addr.trailing_ones();
+                let start = (addr & !((1 << ones) - 1)) << 2;
+                Some((start, start + (1 << (ones + 3))))
+            }
+            _ => None,
+        }
+    }
+
+    // the lowest-numbered entry matching any byte decides, it has to cover the
+    // whole access. without a match only M-mode is allowed
+    pub fn check(
+        &self,
+        addr: u64,
+        len: usize,
+        access: &AccessType,
+        privi: PrivilegeLevels,
+    ) -> bool {
+        let machine = privi == PrivilegeLevels::Machine;
+        if self.entries == 0 || (machine && !self.active) {
+            return true;
+        }
+        // the entries end below 1 << 56, an access reaching the top of the
+        // address space matches none of them
+        let Some(end) = addr.checked_add(len as u64) else {
+            return machine;
+        };
+        for idx in 0..self.entries {
+            let Some((lo, hi)) = self.range(idx) else {
+                continue;
+            };
+            if addr >= hi || end <= lo {
+                continue;
+            }
+            if addr < lo || end > hi {
+                return false;
+            }
+            let cfg = self.cfg[idx];
+            if machine && !cfg.l() {
+                return true;
+            }
+            return match access {
+                AccessType::Fetch(_) => cfg.x(),
+                AccessType::Load(_) => cfg.r(),
+                AccessType::Store(_) => cfg.w(),
+                AccessType::Amo(_) => cfg.r() && cfg.w(),
+            };
+        }
+        machine
+    }
+}
+
+#[cfg(test)]
+mod test_pmp {
+    use super::Pmp;
+    use crate::rv64core::inst::inst_base::{AccessType, PrivilegeLevels};
+
+    #[test]
+    fn pmp_test() {
+        let (m, s) = (PrivilegeLevels::Machine, PrivilegeLevels::Supervisor);
+        let load = AccessType::Load(0);
+        let store = AccessType::Store(0);
+        let fetch = AccessType::Fetch(0);
+        let mut pmp = Pmp::new(16);
+        // nothing configured: M-mode only
+        assert!(pmp.check(0x8000_0000, 8, &load, m));
+        assert!(!pmp.check(0x8000_0000, 8, &load, s));
+
+        // 0: NAPOT 0x8000_0000..+64KiB R, 1: TOR up to 0x8002_0000 RWX, 2: NA4 at 0x100 locked, no perms
+        pmp.write_addr(0, (0x8000_0000 >> 2) | ((0x1_0000 >> 3) - 1));
+        pmp.write_addr(1, 0x8002_0000 >> 2);
+        pmp.write_addr(2, 0x100 >> 2);
+        pmp.write_cfg(0, 0x90_0f_19);
+        assert_eq!(pmp.read_cfg(0), 0x90_0f_19);
+        assert!(pmp.check(0x8000_fff8, 8, &load, s));
+        assert!(!pmp.check(0x8000_fff8, 8, &store, s));
+        // the first matching entry decides, an access across two entries fails
+        assert!(!pmp.check(0x8000_fffc, 8, &load, s));
+        assert!(pmp.check(0x8001_0000, 8, &store, s));
+        assert!(pmp.check(0x8001_0000, 4, &fetch, s));
+        assert!(!pmp.check(0x8002_0000, 4, &load, s));
+        // M-mode ignores the unlocked entries, not the locked ones
+        assert!(pmp.check(0x8000_0000, 8, &store, m));
+        assert!(!pmp.check(0x100, 4, &load, m));
+        assert!(pmp.check(0x104, 4, &load, m));
+
+        // locked: cfg and addr keep their values
+        pmp.write_cfg(0, 0x1f_1f_1f);
+        assert_eq!(pmp.read_cfg(0) >> 16, 0x90);
+        pmp.write_addr(2, 0);
+        assert_eq!(pmp.read_addr(2), 0x100 >> 2);
+        // R=0 W=1 is reserved
+        pmp.write_cfg(0, 0x90_0f_1a);
+        assert_eq!(pmp.read_cfg(0) & 0xff, 0x18);
+
+        // an access ending at the top of the address space matches no entry
+        assert!(pmp.check(0xffff_ffff_ffff_fff8, 8, &load, m));
+        assert!(!pmp.check(0xffff_ffff_ffff_fff8, 8, &load, s));
+        assert!(!pmp.check(0xffff_ffff_ffff_fffc, 8, &load, s));
+
+        pmp.reset();
+        assert!(!pmp.check(0x8001_0000, 8, &load, s));
+        assert!(Pmp::new(0).check(0, 8, &load, s));
+    }
+}
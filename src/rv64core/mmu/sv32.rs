@@ -0,0 +1,167 @@
+// 31               22 21               12 11               0
+// +------------------+-------------------+-----------------+
+// |      VPN[1]      |      VPN[0]       |    page offset  |
+// +------------------+-------------------+-----------------+
+//          10                 10                 12
+//                      Sv32 virtual address.
+
+// 33                    22 21               12 11               0
+// +-----------------------+-------------------+-----------------+
+// |        PPN[1]         |      PPN[0]       |    page offset  |
+// +-----------------------+-------------------+-----------------+
+//            12                    10                 12
+//                      Sv32 physical address.
+
+// 31                    20 19               10  9    8  7   6   5   4   3   2   1   0
+// +-----------------------+-------------------+------+---+---+---+---+---+---+---+---+
+// |        PPN[1]         |      PPN[0]       | RSW  | D | A | G | U | X | W | R | V |
+// +-----------------------+-------------------+------+---+---+---+---+---+---+---+---+
+//            12                    10             2    1   1   1   1   1   1   1   1
+//                                Sv32 page table entry.
+
+use bitfield_struct::bitfield;
+
+use super::vm_info::{PAops, PTEops, VAops};
+
+#[bitfield(u64)]
+pub struct Sv32VA {
+    #[bits(12)]
+    pub offset: u64,
+    #[bits(10)]
+    pub ppn0: u64,
+    #[bits(10)]
+    pub ppn1: u64,
+    #[bits(32)]
+    _pad: u64,
+}
+
+impl VAops for Sv32VA {
+    fn get_ppn_by_idx(&self, idx: u8) -> u64 {
+        match idx {
+            0 => self.ppn0(),
+            1 => self.ppn1(),
+            _ => panic!("Sv32Va ppn idx err:{idx}"),
+        }
+    }
+
+    fn offset(&self) -> usize {
+        self.offset() as usize
+    }
+
+    fn set_offset(&mut self, val: usize) {
+        self.set_offset(val as u64);
+    }
+    fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
+#[bitfield(u64)]
+pub struct Sv32PA {
+    #[bits(12)]
+    pub offset: usize,
+    #[bits(10)]
+    pub ppn0: u64,
+    #[bits(12)]
+    pub ppn1: u64,
+    #[bits(30)]
+    _pad: u64,
+}
+
+impl PAops for Sv32PA {
+    fn set_ppn_by_idx(&mut self, val: u64, idx: u8) {
+        match idx {
+            0 => self.set_ppn0(val),
+            1 => self.set_ppn1(val),
+            _ => panic!("Sv32Pa ppn idx err:{idx}"),
+        }
+    }
+    fn offset(&self) -> usize {
+        self.offset()
+    }
+
+    fn set_offset(&mut self, val: usize) {
+        self.set_offset(val);
+    }
+    fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
+// the PTE is 4 bytes, there is no PBMT and N
+#[bitfield(u64)]
+pub struct Sv32PTE {
+    pub v: bool,
+    pub r: bool,
+    pub w: bool,
+    pub x: bool,
+    pub u: bool,
+    pub g: bool,
+    pub a: bool,
+    pub d: bool,
+    #[bits(2)]
+    pub rsw: u8,
+    #[bits(10)]
+    pub ppn0: u64,
+    #[bits(12)]
+    pub ppn1: u64,
+    #[bits(32)]
+    _pad: u64,
+}
+
+impl PTEops for Sv32PTE {
+    fn raw(&self) -> u64 {
+        self.0
+    }
+    fn get_ppn_by_idx(&self, idx: u8) -> u64 {
+        match idx {
+            0 => self.ppn0(),
+            1 => self.ppn1(),
+            _ => panic!("Sv32PTE ppn idx err:{idx}"),
+        }
+    }
+
+    fn v(&self) -> bool {
+        self.v()
+    }
+
+    fn r(&self) -> bool {
+        self.r()
+    }
+
+    fn w(&self) -> bool {
+        self.w()
+    }
+
+    fn x(&self) -> bool {
+        self.x()
+    }
+
+    fn u(&self) -> bool {
+        self.u()
+    }
+
+    fn g(&self) -> bool {
+        self.g()
+    }
+
+    fn a(&self) -> bool {
+        self.a()
+    }
+
+    fn d(&self) -> bool {
+        self.d()
+    }
+
+    fn rsw(&self) -> u8 {
+        self.rsw()
+    }
+
+    fn pbmt(&self) -> u8 {
+        0
+    }
+
+    fn n(&self) -> bool {
+        false
+    }
+}
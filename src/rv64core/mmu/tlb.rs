@@ -0,0 +1,223 @@
+use alloc::{vec, vec::Vec};
+use hashbrown::HashMap;
+
+use crate::config::TlbPolicy;
+
+use super::vm_info::{TLBEntry, TLBKey};
+
+struct TlbSlot {
+    entry: TLBEntry,
+    set: usize,
+    // the last use (lru) or the insertion (fifo)
+    stamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlbStats {
+    pub policy: TlbPolicy,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+// a set-associative TLB of `size` entries, `ways` entries in each set.
+// the entries are found by their key, the sets only decide the victim
+pub struct Tlb {
+    slots: HashMap<TLBKey, TlbSlot>,
+    sets: Vec<Vec<TLBKey>>,
+    ways: usize,
+    policy: TlbPolicy,
+    stamp: u64,
+    // xorshift state of the random policy
+    rand: u64,
+    evictions: u64,
+}
+
+impl Tlb {
+    // ways = 0 or ways >= size is fully associative
+    pub fn new(size: usize, ways: usize, policy: TlbPolicy) -> Self {
+        let ways = match ways {
+            0 => size,
+            _ => ways.min(size),
+        };
+        let set_num = match ways {
+            0 => 0,
+            _ => size / ways,
+        };
+        Tlb {
+            slots: HashMap::with_capacity(size),
+            sets: vec![Vec::with_capacity(ways); set_num],
+            ways,
+            policy,
+            stamp: 0,
+            rand: 0x2545_f491_4f6c_dd1d,
+            evictions: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ways * self.sets.len()
+    }
+
+    pub fn policy(&self) -> TlbPolicy {
+        self.policy
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    // the vpn bits folded, so the superpages with zero low vpns spread too
+    fn set_idx(&self, key: &TLBKey) -> usize {
+        let vpn = key.va >> 12;
+        let hash = vpn ^ vpn >> 9 ^ vpn >> 18 ^ vpn >> 27 ^ vpn >> 36;
+        (hash % self.sets.len() as u64) as usize
+    }
+
+    fn next_stamp(&mut self) -> u64 {
+        self.stamp += 1;
+        self.stamp
+    }
+
+    pub fn get(&mut self, key: &TLBKey) -> Option<&TLBEntry> {
+        let stamp = match self.policy {
+            TlbPolicy::Lru => Some(self.next_stamp()),
+            _ => None,
+        };
+        let slot = self.slots.get_mut(key)?;
+        if let Some(stamp) = stamp {
+            slot.stamp = stamp;
+        }
+        Some(&slot.entry)
+    }
+
+    pub fn peek(&self, key: &TLBKey) -> Option<&TLBEntry> {
+        self.slots.get(key).map(|slot| &slot.entry)
+    }
+
+    pub fn insert(&mut self, key: TLBKey, entry: TLBEntry) {
+        if self.capacity() == 0 {
+            return;
+        }
+        let stamp = self.next_stamp();
+        // an entry inserted again is a use, it is not a new insertion
+        if let Some(slot) = self.slots.get_mut(&key) {
+            slot.entry = entry;
+            if self.policy == TlbPolicy::Lru {
+                slot.stamp = stamp;
+            }
+            return;
+        }
+        let set = self.set_idx(&key);
+        if self.sets[set].len() == self.ways {
+            let victim = self.victim(set);
+            let old = self.sets[set].swap_remove(victim);
+            self.slots.remove(&old);
+            self.evictions += 1;
+        }
+        self.sets[set].push(key);
+        self.slots.insert(key, TlbSlot { entry, set, stamp });
+    }
+
+    fn victim(&mut self, set: usize) -> usize {
+        match self.policy {
+            TlbPolicy::Random => {
+                self.rand ^= self.rand << 13;
+                self.rand ^= self.rand >> 7;
+                self.rand ^= self.rand << 17;
+                (self.rand % self.ways as u64) as usize
+            }
+            TlbPolicy::Lru | TlbPolicy::Fifo => {
+                let stamp = |idx: &usize| self.slots[&self.sets[set][*idx]].stamp;
+                (0..self.sets[set].len()).min_by_key(stamp).unwrap_or(0)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &TLBKey) -> Option<TLBEntry> {
+        let slot = self.slots.remove(key)?;
+        let keys = &mut self.sets[slot.set];
+        if let Some(idx) = keys.iter().position(|x| x == key) {
+            keys.swap_remove(idx);
+        }
+        Some(slot.entry)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&TLBKey, &TLBEntry)> {
+        self.slots.iter().map(|(key, slot)| (key, &slot.entry))
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.sets.iter_mut().for_each(|x| x.clear());
+    }
+}
+
+#[cfg(test)]
+mod test_tlb {
+    use super::Tlb;
+    use crate::{
+        config::TlbPolicy,
+        rv64core::mmu::{
+            sv39::Sv39PTE,
+            vm_info::{PageSize, TLBEntry, TLBKey},
+        },
+    };
+
+    fn key(vpn: u64) -> TLBKey {
+        TLBKey {
+            va: vpn << 12,
+            asid: Some(0),
+            page_size: PageSize::P4K,
+        }
+    }
+
+    fn entry() -> TLBEntry {
+        TLBEntry::new(Sv39PTE::new().into(), PageSize::P4K, 0)
+    }
+
+    #[test]
+    fn tlb_policy_test() {
+        // fully associative, 4 entries: 0 is used again, 1 is the lru victim
+        let mut lru = Tlb::new(4, 0, TlbPolicy::Lru);
+        let mut fifo = Tlb::new(4, 0, TlbPolicy::Fifo);
+        for tlb in [&mut lru, &mut fifo] {
+            (0..4).for_each(|x| tlb.insert(key(x), entry()));
+            assert!(tlb.get(&key(0)).is_some());
+            tlb.insert(key(4), entry());
+            assert_eq!(tlb.evictions(), 1);
+        }
+        assert!(lru.peek(&key(0)).is_some() && lru.peek(&key(1)).is_none());
+        assert!(fifo.peek(&key(0)).is_none() && fifo.peek(&key(1)).is_some());
+
+        // 0 is inserted again: a use for lru, fifo keeps its insertion
+        let mut lru = Tlb::new(2, 0, TlbPolicy::Lru);
+        let mut fifo = Tlb::new(2, 0, TlbPolicy::Fifo);
+        for tlb in [&mut lru, &mut fifo] {
+            [0, 1, 0, 2]
+                .iter()
+                .for_each(|x| tlb.insert(key(*x), entry()));
+        }
+        assert!(lru.peek(&key(0)).is_some() && lru.peek(&key(1)).is_none());
+        assert!(fifo.peek(&key(0)).is_none() && fifo.peek(&key(1)).is_some());
+
+        // 2 ways, 4 sets: the vpns 0, 4 and 8 share set 0
+        let mut tlb = Tlb::new(8, 2, TlbPolicy::Random);
+        assert_eq!(tlb.capacity(), 8);
+        [0, 4, 1, 2]
+            .iter()
+            .for_each(|x| tlb.insert(key(*x), entry()));
+        tlb.insert(key(8), entry());
+        assert_eq!(tlb.evictions(), 1);
+        assert_eq!(tlb.iter().count(), 4);
+        assert!(tlb.peek(&key(1)).is_some() && tlb.peek(&key(2)).is_some());
+        assert!(tlb.remove(&key(8)).is_some());
+        tlb.insert(key(0), entry());
+        tlb.insert(key(4), entry());
+        assert_eq!(tlb.evictions(), 1);
+
+        tlb.clear();
+        assert_eq!(tlb.iter().count(), 0);
+        assert_eq!(Tlb::new(0, 0, TlbPolicy::Lru).capacity(), 0);
+    }
+}
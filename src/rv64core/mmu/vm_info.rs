@@ -1,12 +1,18 @@
+use super::sv32::{Sv32PA, Sv32PTE, Sv32VA};
 use super::sv39::{Sv39PA, Sv39PTE, Sv39VA};
 use super::sv48::{Sv48PA, Sv48PTE, Sv48VA};
 use super::sv57::{Sv57PA, Sv57PTE, Sv57VA};
+use crate::rv64core::csr_regs_define::StapMode;
 use enum_dispatch::enum_dispatch;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PageSize {
     P4K,
+    // Svnapot, 16 contiguous 4KiB pages
+    P64K,
     P2M,
+    // Sv32 megapages
+    P4M,
     P1G,
     P512G,
     P256T,
@@ -26,12 +32,45 @@ impl PageSize {
     pub const fn get_mask(&self) -> u64 {
         match self {
             PageSize::P4K => zero_mask(12),
+            PageSize::P64K => zero_mask(16),
             PageSize::P2M => zero_mask(21),
+            PageSize::P4M => zero_mask(22),
             PageSize::P1G => zero_mask(30),
-
+            PageSize::P512G => zero_mask(39),
+            PageSize::P256T => zero_mask(48),
             _ => panic!("Invalid page size"),
         }
     }
+    // a leaf of this size exists in the page tables of `mode`
+    pub fn in_mode(&self, mode: StapMode) -> bool {
+        let levels = mode.get_levels();
+        match self {
+            PageSize::P4K => true,
+            PageSize::P4M => mode == StapMode::Sv32,
+            PageSize::P64K | PageSize::P2M | PageSize::P1G => levels >= 3,
+            PageSize::P512G => levels >= 4,
+            PageSize::P256T => levels >= 5,
+            PageSize::InValid => false,
+        }
+    }
+}
+
+// Svpbmt memory types, PMA keeps the attributes of the physical memory
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemType {
+    Pma,
+    Nc,
+    Io,
+}
+
+impl MemType {
+    pub const fn from_pbmt(pbmt: u8) -> Self {
+        match pbmt {
+            1 => MemType::Nc,
+            2 => MemType::Io,
+            _ => MemType::Pma,
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -41,10 +80,14 @@ pub struct TLBEntry {
     pub asid: u16,
 }
 
+// the entries are tagged by the satp ASID, the global pages have no ASID
+// and are shared by every address space. a superpage is one entry, the va
+// is masked by its page size
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct TLBKey {
     pub va: u64,
-    pub asid: u16,
+    pub asid: Option<u16>,
+    pub page_size: PageSize,
 }
 
 impl core::fmt::Debug for TLBEntry {
@@ -89,11 +132,21 @@ impl TLBEntry {
         // debug!("pagesize:{:?}", self.page_size);
         match self.page_size {
             PageSize::P4K => (self.pte.ppn_all() << 12) | va.offset() as u64,
+            PageSize::P64K => {
+                ((self.pte.ppn_all() & zero_mask(4)) << 12)
+                    | (va.get_ppn_by_idx(0) & 0xf) << 12
+                    | va.offset() as u64
+            }
             PageSize::P2M => {
                 ((self.pte.ppn_all() & zero_mask(9)) << 12)
                     | va.get_ppn_by_idx(0) << 12
                     | va.offset() as u64
             }
+            PageSize::P4M => {
+                ((self.pte.ppn_all() & zero_mask(10)) << 12)
+                    | va.get_ppn_by_idx(0) << 12
+                    | va.offset() as u64
+            }
             PageSize::P1G => {
                 ((self.pte.ppn_all() & zero_mask(18)) << 12)
                     | va.get_ppn_by_idx(1) << (12 + 9)
@@ -164,6 +217,7 @@ pub trait VAops {
 #[enum_dispatch]
 #[derive(Copy, Clone)]
 pub enum PTEenume {
+    Sv32PTE,
     Sv39PTE,
     Sv48PTE,
     Sv57PTE,
@@ -171,6 +225,7 @@ pub enum PTEenume {
 
 #[enum_dispatch]
 pub enum PAenume {
+    Sv32PA,
     Sv39PA,
     Sv48PA,
     Sv57PA,
@@ -178,6 +233,7 @@ pub enum PAenume {
 
 #[enum_dispatch]
 pub enum VAenume {
+    Sv32VA,
     Sv39VA,
     Sv48VA,
     Sv57VA,
@@ -4,7 +4,14 @@ pub mod csr_regs;
 pub mod csr_regs_define;
 pub mod mmu;
 pub mod gpr;
+pub mod fpr;
+pub mod vector;
 pub mod inst_decode;
 pub mod traptype;
 pub mod inst;
-pub mod cache;
\ No newline at end of file
+pub mod cache;
+pub mod pipeline;
+pub mod energy;
+pub mod trigger;
+#[cfg(test)]
+pub mod test_cpu;
@@ -0,0 +1,379 @@
+use alloc::{boxed::Box, collections::VecDeque, format, string::String, vec::Vec};
+
+use super::inst::inst_base::is_compressed_instruction;
+
+// a textbook 5-stage in-order pipeline (IF ID EX MEM WB) with full forwarding,
+// it is driven by the retired instructions and only models the timing.
+// ID reads the register file in the second half of the cycle WB writes it,
+// branches and jumps are predicted not taken and resolved in EX,
+// a trap is taken in MEM and the handler is fetched in the next cycle
+const BRANCH_RESOLVE_BUBBLES: u64 = 2;
+// the rows of one diagram block share a cycle base
+const BLOCK_ROWS: usize = 8;
+const HISTORY: usize = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Operands {
+    pub rd: Option<u8>,
+    pub srcs: [Option<u8>; 2],
+    pub load: bool,
+}
+
+// the x register operands of an instruction, the f and v registers are not tracked
+pub fn operands(word: u32) -> Operands {
+    let full = |lsb: u32| ((word >> lsb) & 0x1f) as u8;
+    let prime = |lsb: u32| 8 + ((word >> lsb) & 0b111) as u8;
+    let reg = |r: u8| (r != 0).then_some(r);
+    let op = |rd: Option<u8>, rs1: Option<u8>, rs2: Option<u8>, load: bool| Operands {
+        rd: rd.and_then(reg),
+        srcs: [rs1.and_then(reg), rs2.and_then(reg)],
+        load,
+    };
+    if is_compressed_instruction(word) {
+        let (rd, rs2) = (full(7), full(2));
+        return match (word & 0b11, (word >> 13) & 0b111) {
+            // c.addi4spn
+            (0b00, 0b000) => op(Some(prime(2)), Some(2), None, false),
+            // c.fld
+            (0b00, 0b001) => op(None, Some(prime(7)), None, true),
+            // c.lw, c.ld
+            (0b00, 0b010 | 0b011) => op(Some(prime(2)), Some(prime(7)), None, true),
+            // c.fsd
+            (0b00, 0b101) => op(None, Some(prime(7)), None, false),
+            // c.sw, c.sd
+            (0b00, 0b110 | 0b111) => op(None, Some(prime(7)), Some(prime(2)), false),
+            // c.addi, c.addiw
+            (0b01, 0b000 | 0b001) => op(Some(rd), Some(rd), None, false),
+            // c.li
+            (0b01, 0b010) => op(Some(rd), None, None, false),
+            // c.addi16sp, c.lui
+            (0b01, 0b011) if rd == 2 => op(Some(2), Some(2), None, false),
+            (0b01, 0b011) => op(Some(rd), None, None, false),
+            // c.sub, c.xor, c.or, c.and, c.subw, c.addw
+            (0b01, 0b100) if (word >> 10) & 0b11 == 0b11 => {
+                op(Some(prime(7)), Some(prime(7)), Some(prime(2)), false)
+            }
+            // c.srli, c.srai, c.andi
+            (0b01, 0b100) => op(Some(prime(7)), Some(prime(7)), None, false),
+            // c.beqz, c.bnez
+            (0b01, 0b110 | 0b111) => op(None, Some(prime(7)), None, false),
+            // c.slli
+            (0b10, 0b000) => op(Some(rd), Some(rd), None, false),
+            // c.fldsp
+            (0b10, 0b001) => op(None, Some(2), None, true),
+            // c.lwsp, c.ldsp
+            (0b10, 0b010 | 0b011) => op(Some(rd), Some(2), None, true),
+            // c.jr, c.mv
+            (0b10, 0b100) if word & (1 << 12) == 0 => match rs2 {
+                0 => op(None, Some(rd), None, false),
+                _ => op(Some(rd), Some(rs2), None, false),
+            },
+            // c.ebreak, c.jalr, c.add
+            (0b10, 0b100) => match (rd, rs2) {
+                (0, 0) => Operands::default(),
+                (_, 0) => op(Some(1), Some(rd), None, false),
+                _ => op(Some(rd), Some(rd), Some(rs2), false),
+            },
+            // c.fsdsp
+            (0b10, 0b101) => op(None, Some(2), None, false),
+            // c.swsp, c.sdsp
+            (0b10, 0b110 | 0b111) => op(None, Some(2), Some(rs2), false),
+            _ => Operands::default(),
+        };
+    }
+    let (rd, rs1, rs2) = (Some(full(7)), Some(full(15)), Some(full(20)));
+    match word & 0x7f {
+        // LUI,AUIPC,JAL
+        0b0110111 | 0b0010111 | 0b1101111 => op(rd, None, None, false),
+        // JALR,OP-IMM,OP-IMM-32
+        0b1100111 | 0b0010011 | 0b0011011 => op(rd, rs1, None, false),
+        // LOAD
+        0b0000011 => op(rd, rs1, None, true),
+        // BRANCH,STORE
+        0b1100011 | 0b0100011 => op(None, rs1, rs2, false),
+        // OP,OP-32
+        0b0110011 | 0b0111011 => op(rd, rs1, rs2, false),
+        // AMO, the old value comes from the memory
+        0b0101111 => op(rd, rs1, rs2, true),
+        // LOAD-FP,STORE-FP
+        0b0000111 => op(None, rs1, None, true),
+        0b0100111 => op(None, rs1, None, false),
+        // SYSTEM, csrr*i take a uimm in rs1
+        0b1110011 => match (word >> 12) & 0b111 {
+            0b001..=0b011 => op(rd, rs1, None, false),
+            0b101..=0b111 => op(rd, None, None, false),
+            _ => Operands::default(),
+        },
+        _ => Operands::default(),
+    }
+}
+
+// the cycles an instruction enters each stage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stages {
+    pub fetch: u64,
+    pub decode: u64,
+    pub execute: u64,
+    pub memory: u64,
+    pub writeback: u64,
+}
+
+#[derive(Clone, Copy)]
+struct InFlight {
+    stages: Stages,
+    ops: Operands,
+    // the fetch of the next instruction is redirected to this cycle
+    redirect: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineStats {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub stalls: u64,
+    pub flushes: u64,
+    pub forwards: u64,
+}
+
+pub type RowSink = Box<dyn FnMut(&str)>;
+
+pub struct Pipeline {
+    history: VecDeque<InFlight>,
+    stats: PipelineStats,
+    rows: usize,
+    block_base: u64,
+    sink: Option<RowSink>,
+}
+
+impl Pipeline {
+    // every row of the diagram is handed to `sink`, without a newline
+    pub fn new(sink: RowSink) -> Self {
+        Pipeline {
+            sink: Some(sink),
+            ..Self::without_diagram()
+        }
+    }
+
+    // only the timing and the stats, e.g. for the energy model
+    pub fn without_diagram() -> Self {
+        Pipeline {
+            history: VecDeque::with_capacity(HISTORY),
+            stats: PipelineStats::default(),
+            rows: 0,
+            block_base: 0,
+            sink: None,
+        }
+    }
+
+    pub fn has_diagram(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub fn stats(&self) -> PipelineStats {
+        self.stats
+    }
+
+    // a retired instruction, `npc` is the pc of the next one
+    pub fn retire(&mut self, pc: u64, word: u32, npc: u64, disasm: &str) -> Stages {
+        let len = if is_compressed_instruction(word) {
+            2
+        } else {
+            4
+        };
+        let taken = npc != pc.wrapping_add(len);
+        let ops = operands(word);
+        let (stages, mut notes) = self.issue(ops);
+        let redirect = taken.then_some(stages.execute + 1);
+        if taken {
+            self.stats.flushes += 1;
+            notes.push(format!("taken, flush {}", BRANCH_RESOLVE_BUBBLES));
+        }
+        self.push(stages, ops, redirect);
+        self.stats.instructions += 1;
+        self.row(pc, disasm, stages, &notes);
+        stages
+    }
+
+    // an instruction that traps, it is squashed in MEM
+    pub fn trap(&mut self, pc: u64, word: u32, cause: &str) -> Stages {
+        let (stages, mut notes) = self.issue(Operands::default());
+        self.stats.flushes += 1;
+        notes.push(format!("trap {}, flush", cause));
+        self.push(stages, Operands::default(), Some(stages.memory + 1));
+        self.row(pc, &format!("{:08x}", word), stages, &notes);
+        stages
+    }
+
+    fn issue(&mut self, ops: Operands) -> (Stages, Vec<String>) {
+        let (fetch, decode) = match self.history.back() {
+            None => (self.stats.cycles, self.stats.cycles + 1),
+            Some(prev) => match prev.redirect {
+                Some(cycle) => (cycle, cycle + 1),
+                // IF and ID are held while the older instruction stalls in ID
+                None => (prev.stages.decode, prev.stages.execute),
+            },
+        };
+        let mut execute = decode + 1;
+        let mut notes = Vec::new();
+        // the result of an ALU op is ready after EX, a load after MEM
+        let mut srcs = ops.srcs;
+        if srcs[0] == srcs[1] {
+            srcs[1] = None;
+        }
+        let producers: Vec<_> = srcs
+            .iter()
+            .flatten()
+            .filter_map(|src| {
+                self.history
+                    .iter()
+                    .rev()
+                    .find(|x| x.ops.rd == Some(*src))
+                    .map(|x| (*src, *x))
+            })
+            .collect();
+        for (_, p) in producers.iter() {
+            let ready = if p.ops.load {
+                p.stages.memory
+            } else {
+                p.stages.execute
+            };
+            execute = execute.max(ready + 1);
+        }
+        let stall = execute - decode - 1;
+        if stall > 0 {
+            self.stats.stalls += stall;
+            notes.push(format!("stall {}, load-use", stall));
+        }
+        for (src, p) in producers.iter() {
+            let read = execute - 1;
+            let path = if read >= p.stages.writeback {
+                continue;
+            } else if read == p.stages.memory {
+                "MEM/WB"
+            } else {
+                "EX/MEM"
+            };
+            self.stats.forwards += 1;
+            notes.push(format!("fwd x{} {}->EX", src, path));
+        }
+        let stages = Stages {
+            fetch,
+            decode,
+            execute,
+            memory: execute + 1,
+            writeback: execute + 2,
+        };
+        self.stats.cycles = stages.writeback + 1;
+        (stages, notes)
+    }
+
+    fn push(&mut self, stages: Stages, ops: Operands, redirect: Option<u64>) {
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(InFlight {
+            stages,
+            ops,
+            redirect,
+        });
+    }
+
+    fn row(&mut self, pc: u64, disasm: &str, stages: Stages, notes: &[String]) {
+        let Some(sink) = self.sink.as_mut() else {
+            return;
+        };
+        if self.rows.is_multiple_of(BLOCK_ROWS) {
+            self.block_base = stages.fetch;
+            let header = format!(
+                "{:>10} {:>16}  {:<28} {}",
+                "cycle", "pc", "inst", "IF ID EX ME WB"
+            );
+            sink(&header);
+        }
+        self.rows += 1;
+        let mut diagram = String::new();
+        for cycle in self.block_base..=stages.writeback {
+            let cell = match cycle {
+                x if x < stages.fetch => "   ",
+                x if x == stages.fetch => "IF ",
+                x if x == stages.decode => "ID ",
+                x if x < stages.execute => "-- ",
+                x if x == stages.execute => "EX ",
+                x if x == stages.memory => "ME ",
+                _ => "WB ",
+            };
+            diagram.push_str(cell);
+        }
+        let row = format!(
+            "{:>10} {:>16x}  {:<28} {} {}",
+            stages.fetch,
+            pc,
+            disasm.replace('\t', " "),
+            diagram.trim_end(),
+            notes.join(", ")
+        );
+        sink(row.trim_end());
+    }
+}
+
+#[cfg(test)]
+mod test_pipeline {
+    use alloc::{boxed::Box, rc::Rc, string::String, vec::Vec};
+    use core::cell::RefCell;
+
+    use super::{operands, Operands, Pipeline};
+
+    fn run(insts: &[(u32, u64)]) -> (Pipeline, Rc<RefCell<Vec<String>>>) {
+        let rows = Rc::new(RefCell::new(Vec::new()));
+        let sink_rows = rows.clone();
+        let mut pipeline =
+            Pipeline::new(Box::new(move |row| sink_rows.borrow_mut().push(row.into())));
+        let mut pc = 0x8000_0000;
+        for (word, npc) in insts {
+            let npc = if *npc == 0 { pc + 4 } else { *npc };
+            pipeline.retire(pc, *word, npc, "");
+            pc = npc;
+        }
+        (pipeline, rows)
+    }
+
+    #[test]
+    fn operands_test() {
+        // ld a1,0(a0)
+        let ld = Operands {
+            rd: Some(11),
+            srcs: [Some(10), None],
+            load: true,
+        };
+        assert_eq!(operands(0x00053583), ld);
+        // c.ld a1,0(a0)
+        assert_eq!(operands(0x610c), ld);
+        // add zero,a0,a1 has no destination
+        assert_eq!(operands(0x00b50033).rd, None);
+        // c.jalr a0 links ra
+        assert_eq!(operands(0x9502).rd, Some(1));
+    }
+
+    #[test]
+    fn hazard_test() {
+        // ld a1,0(a0); add a2,a1,a1; add a3,a2,a1; beq zero,zero,-12
+        let (pipeline, rows) = run(&[
+            (0x00053583, 0),
+            (0x00b58633, 0),
+            (0x00b606b3, 0),
+            (0xfe000ae3, 0x8000_0000),
+            (0x00053583, 0),
+        ]);
+        let stats = pipeline.stats();
+        assert_eq!(stats.instructions, 5);
+        // one load-use bubble, two bubbles behind the taken branch
+        assert_eq!(stats.stalls, 1);
+        assert_eq!(stats.flushes, 1);
+        assert_eq!(stats.cycles, 5 + 4 + 1 + 2);
+        let rows = rows.borrow();
+        assert!(rows[2].ends_with("IF ID -- EX ME WB stall 1, load-use, fwd x11 MEM/WB->EX"));
+        // x11 is written back in the cycle it is read
+        assert!(rows[3].ends_with("IF -- ID EX ME WB fwd x12 EX/MEM->EX"));
+        assert!(rows[4].ends_with("taken, flush 2"));
+    }
+}
@@ -0,0 +1,60 @@
+// the hart the unit tests run single instructions on
+use alloc::{boxed::Box, rc::Rc};
+
+use crate::{
+    config::Config,
+    device::{
+        device_memory::DeviceMemory,
+        device_trait::{DeviceBase, MEM_BASE},
+    },
+    rv64core::{
+        bus::{Bus, DeviceType},
+        cpu_core::{CpuCore, CpuCoreBuild},
+        traptype::TrapType,
+    },
+    tools::RcRefCell,
+};
+
+// a bus with `mem_size` bytes of ram at MEM_BASE, no ram at all for 0
+pub fn test_bus(mem_size: u64) -> RcRefCell<Bus> {
+    let bus: RcRefCell<Bus> = RcRefCell::new(Bus::new().into());
+    if mem_size != 0 {
+        let mem = DeviceMemory::new(mem_size as usize);
+        let name = mem.get_name();
+        bus.borrow_mut()
+            .add_device(DeviceType {
+                start: MEM_BASE,
+                len: mem_size,
+                instance: Box::new(mem),
+                name,
+            })
+            .unwrap();
+    }
+    bus
+}
+
+pub fn test_cpu_on(bus: &RcRefCell<Bus>, config: Config) -> CpuCore {
+    CpuCoreBuild::new(bus.clone(), Rc::new(config)).build()
+}
+
+// a hart with `isa` on a bus with `mem_size` bytes of ram, see `test_bus`
+pub fn test_cpu(isa: &str, mem_size: u64) -> (CpuCore, RcRefCell<Bus>) {
+    let mut config = Config::new();
+    config.set_isa(isa);
+    let bus = test_bus(mem_size);
+    (test_cpu_on(&bus, config), bus)
+}
+
+// runs `inst` at pc 0, without fetching it
+pub fn exec(cpu: &mut CpuCore, inst: u32) -> Result<(), TrapType> {
+    let operation = cpu.decode.fast_path(inst).unwrap().operation;
+    operation(cpu, inst, 0)
+}
+
+// runs each (inst, a0 expect) of `cases`
+pub fn check_a0(cpu: &mut CpuCore, cases: &[(u32, u64)]) {
+    for &(inst, expect) in cases {
+        exec(cpu, inst).unwrap();
+        assert_eq!(cpu.gpr.read(10), expect, "{inst:x}");
+    }
+}
@@ -15,19 +15,31 @@ pub enum TrapType {
     StoreAccessFault(u64),
     EnvironmentCallFromUMode = 8,
     EnvironmentCallFromSMode = 9,
+    EnvironmentCallFromVSMode = 10,
     EnvironmentCallFromMMode = 11,
     InstructionPageFault(u64),
     LoadPageFault(u64),
     StorePageFault(u64),
+    // H extension, the guest page faults carry (guest virtual address, guest physical address)
+    InstructionGuestPageFault(u64, u64),
+    LoadGuestPageFault(u64, u64),
+    VirtualInstruction(u64),
+    StoreGuestPageFault(u64, u64),
     UserSoftwareInterrupt,
     SupervisorSoftwareInterrupt,
+    VirtualSupervisorSoftwareInterrupt,
     MachineSoftwareInterrupt,
     UserTimerInterrupt,
     SupervisorTimerInterrupt,
+    VirtualSupervisorTimerInterrupt,
     MachineTimerInterrupt,
     UserExternalInterrupt,
     SupervisorExternalInterrupt,
+    VirtualSupervisorExternalInterrupt,
     MachineExternalInterrupt,
+    SupervisorGuestExternalInterrupt,
+    // Sscofpmf
+    LocalCounterOverflowInterrupt,
 }
 
 impl fmt::Display for TrapType {
@@ -43,19 +55,37 @@ impl fmt::Display for TrapType {
             TrapType::StoreAccessFault(_) => write!(f, "StoreAccessFault"),
             TrapType::EnvironmentCallFromUMode => write!(f, "EnvironmentCallFromUMode"),
             TrapType::EnvironmentCallFromSMode => write!(f, "EnvironmentCallFromSMode"),
+            TrapType::EnvironmentCallFromVSMode => write!(f, "EnvironmentCallFromVSMode"),
             TrapType::EnvironmentCallFromMMode => write!(f, "EnvironmentCallFromMMode"),
             TrapType::InstructionPageFault(_) => write!(f, "InstructionPageFault"),
             TrapType::LoadPageFault(_) => write!(f, "LoadPageFault"),
             TrapType::StorePageFault(_) => write!(f, "StorePageFault"),
+            TrapType::InstructionGuestPageFault(..) => write!(f, "InstructionGuestPageFault"),
+            TrapType::LoadGuestPageFault(..) => write!(f, "LoadGuestPageFault"),
+            TrapType::VirtualInstruction(_) => write!(f, "VirtualInstruction"),
+            TrapType::StoreGuestPageFault(..) => write!(f, "StoreGuestPageFault"),
             TrapType::UserSoftwareInterrupt => write!(f, "UserSoftwareInterrupt"),
             TrapType::SupervisorSoftwareInterrupt => write!(f, "SupervisorSoftwareInterrupt"),
+            TrapType::VirtualSupervisorSoftwareInterrupt => {
+                write!(f, "VirtualSupervisorSoftwareInterrupt")
+            }
             TrapType::MachineSoftwareInterrupt => write!(f, "MachineSoftwareInterrupt"),
             TrapType::UserTimerInterrupt => write!(f, "UserTimerInterrupt"),
             TrapType::SupervisorTimerInterrupt => write!(f, "SupervisorTimerInterrupt"),
+            TrapType::VirtualSupervisorTimerInterrupt => {
+                write!(f, "VirtualSupervisorTimerInterrupt")
+            }
             TrapType::MachineTimerInterrupt => write!(f, "MachineTimerInterrupt"),
             TrapType::UserExternalInterrupt => write!(f, "UserExternalInterrupt"),
             TrapType::SupervisorExternalInterrupt => write!(f, "SupervisorExternalInterrupt"),
+            TrapType::VirtualSupervisorExternalInterrupt => {
+                write!(f, "VirtualSupervisorExternalInterrupt")
+            }
             TrapType::MachineExternalInterrupt => write!(f, "MachineExternalInterrupt"),
+            TrapType::SupervisorGuestExternalInterrupt => {
+                write!(f, "SupervisorGuestExternalInterrupt")
+            }
+            TrapType::LocalCounterOverflowInterrupt => write!(f, "LocalCounterOverflowInterrupt"),
         }
     }
 }
@@ -73,22 +103,51 @@ impl TrapType {
             TrapType::StoreAccessFault(_) => 7,
             TrapType::EnvironmentCallFromUMode => 8,
             TrapType::EnvironmentCallFromSMode => 9,
+            TrapType::EnvironmentCallFromVSMode => 10,
             TrapType::EnvironmentCallFromMMode => 11,
             TrapType::InstructionPageFault(_) => 12,
             TrapType::LoadPageFault(_) => 13,
             TrapType::StorePageFault(_) => 15,
+            TrapType::InstructionGuestPageFault(..) => 20,
+            TrapType::LoadGuestPageFault(..) => 21,
+            TrapType::VirtualInstruction(_) => 22,
+            TrapType::StoreGuestPageFault(..) => 23,
             TrapType::UserSoftwareInterrupt => INTERRUPT_BIT,
             TrapType::SupervisorSoftwareInterrupt => INTERRUPT_BIT + 1,
+            TrapType::VirtualSupervisorSoftwareInterrupt => INTERRUPT_BIT + 2,
             TrapType::MachineSoftwareInterrupt => INTERRUPT_BIT + 3,
             TrapType::UserTimerInterrupt => INTERRUPT_BIT + 4,
             TrapType::SupervisorTimerInterrupt => INTERRUPT_BIT + 5,
+            TrapType::VirtualSupervisorTimerInterrupt => INTERRUPT_BIT + 6,
             TrapType::MachineTimerInterrupt => INTERRUPT_BIT + 7,
             TrapType::UserExternalInterrupt => INTERRUPT_BIT + 8,
             TrapType::SupervisorExternalInterrupt => INTERRUPT_BIT + 9,
+            TrapType::VirtualSupervisorExternalInterrupt => INTERRUPT_BIT + 10,
             TrapType::MachineExternalInterrupt => INTERRUPT_BIT + 11,
+            TrapType::SupervisorGuestExternalInterrupt => INTERRUPT_BIT + 12,
+            TrapType::LocalCounterOverflowInterrupt => INTERRUPT_BIT + 13,
         }
     }
 
+    // a VS-level interrupt taken in VS-mode is reported as the S-level interrupt
+    pub fn to_vs_interrupt(self) -> TrapType {
+        match self {
+            TrapType::VirtualSupervisorSoftwareInterrupt => TrapType::SupervisorSoftwareInterrupt,
+            TrapType::VirtualSupervisorTimerInterrupt => TrapType::SupervisorTimerInterrupt,
+            TrapType::VirtualSupervisorExternalInterrupt => TrapType::SupervisorExternalInterrupt,
+            other => other,
+        }
+    }
+
+    pub fn is_guest_page_fault(&self) -> bool {
+        matches!(
+            self,
+            TrapType::InstructionGuestPageFault(..)
+                | TrapType::LoadGuestPageFault(..)
+                | TrapType::StoreGuestPageFault(..)
+        )
+    }
+
     pub fn is_interupt(&self) -> bool {
         ((self.idx()) & INTERRUPT_BIT) != 0
     }
@@ -116,12 +175,43 @@ impl TrapType {
             | TrapType::InstructionPageFault(val)
             | TrapType::InstructionAddressMisaligned(val)
             | TrapType::Breakpoint(val)
-            | TrapType::IllegalInstruction(val) => *val,
+            | TrapType::IllegalInstruction(val)
+            | TrapType::VirtualInstruction(val)
+            | TrapType::InstructionGuestPageFault(val, _)
+            | TrapType::LoadGuestPageFault(val, _)
+            | TrapType::StoreGuestPageFault(val, _) => *val,
             _ => 0,
         }
     }
+
+    // htval and mtval2: the guest physical address shifted right by 2
+    pub fn get_tval2(&self) -> u64 {
+        match self {
+            TrapType::InstructionGuestPageFault(_, gpa)
+            | TrapType::LoadGuestPageFault(_, gpa)
+            | TrapType::StoreGuestPageFault(_, gpa) => gpa >> 2,
+            _ => 0,
+        }
+    }
+
+    // the trap writes a virtual address into xtval
+    pub fn has_address_tval(&self) -> bool {
+        matches!(
+            self,
+            TrapType::InstructionAddressMisaligned(_)
+                | TrapType::InstructionAccessFault(_)
+                | TrapType::Breakpoint(_)
+                | TrapType::LoadAddressMisaligned(_)
+                | TrapType::LoadAccessFault(_)
+                | TrapType::StoreAddressMisaligned(_)
+                | TrapType::StoreAccessFault(_)
+                | TrapType::InstructionPageFault(_)
+                | TrapType::LoadPageFault(_)
+                | TrapType::StorePageFault(_)
+        ) || self.is_guest_page_fault()
+    }
 }
-#[derive(Debug,Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum DebugCause {
     NoDebug = 0,
     Ebreak = 1,
@@ -0,0 +1,276 @@
+use super::inst::inst_base::PrivilegeLevels;
+
+pub const TRIGGER_COUNT: usize = 4;
+
+const TYPE_SHIFT: u64 = 60;
+const TYPE_MCONTROL: u64 = 2;
+const TYPE_MCONTROL6: u64 = 6;
+const TYPE_DISABLED: u64 = 15;
+pub const TDATA1_DMODE: u64 = 1 << 59;
+
+// the fields shared by mcontrol and mcontrol6: action, match, m, s, u, execute, store, load
+const ACTION_SHIFT: u64 = 12;
+const MATCH_SHIFT: u64 = 7;
+const TDATA1_M: u64 = 1 << 6;
+const TDATA1_S: u64 = 1 << 4;
+const TDATA1_U: u64 = 1 << 3;
+const TDATA1_EXECUTE: u64 = 1 << 2;
+const TDATA1_STORE: u64 = 1 << 1;
+const TDATA1_LOAD: u64 = 1 << 0;
+const TDATA1_COMMON: u64 = TDATA1_M | TDATA1_S | TDATA1_U | 0b111;
+
+const MCONTROL_HIT: u64 = 1 << 20;
+const MCONTROL_SELECT: u64 = 1 << 19;
+const MCONTROL6_VS: u64 = 1 << 24;
+const MCONTROL6_VU: u64 = 1 << 23;
+const MCONTROL6_HIT0: u64 = 1 << 22;
+const MCONTROL6_SELECT: u64 = 1 << 21;
+
+// Sdtrig version 1.0
+const TINFO: u64 = 1 << 24 | 1 << TYPE_MCONTROL | 1 << TYPE_MCONTROL6 | 1 << TYPE_DISABLED;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerAccess {
+    Fetch,
+    Load,
+    Store,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerAction {
+    Breakpoint,
+    DebugMode,
+}
+
+// the trigger module of Sdtrig, mcontrol and mcontrol6 address/data match
+// triggers. chain, size, timing=after and the mask matches are not supported,
+// the breakpoints fire before the access
+pub struct Triggers {
+    tselect: usize,
+    tdata1: [u64; TRIGGER_COUNT],
+    tdata2: [u64; TRIGGER_COUNT],
+    hypervisor: bool,
+    // a trigger has execute, store or load set
+    active: bool,
+}
+
+impl Triggers {
+    pub fn new(hypervisor: bool) -> Self {
+        Triggers {
+            tselect: 0,
+            tdata1: [TYPE_MCONTROL << TYPE_SHIFT; TRIGGER_COUNT],
+            tdata2: [0; TRIGGER_COUNT],
+            hypervisor,
+            active: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Triggers::new(self.hypervisor);
+    }
+
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    pub fn tselect(&self) -> u64 {
+        self.tselect as u64
+    }
+
+    // WARL, the value is kept if there is no such trigger
+    pub fn write_tselect(&mut self, data: u64) {
+        if (data as usize) < TRIGGER_COUNT {
+            self.tselect = data as usize;
+        }
+    }
+
+    pub fn tdata1(&self) -> u64 {
+        self.tdata1[self.tselect]
+    }
+
+    pub fn tdata2(&self) -> u64 {
+        self.tdata2[self.tselect]
+    }
+
+    pub fn tinfo(&self) -> u64 {
+        TINFO
+    }
+
+    // the selected trigger belongs to Debug Mode
+    pub fn dmode(&self) -> bool {
+        self.tdata1() & TDATA1_DMODE != 0
+    }
+
+    // the unsupported types disable the trigger, action is 0 or 1 (dmode only)
+    // and match is 0-3
+    pub fn write_tdata1(&mut self, data: u64) {
+        let dmode = data & TDATA1_DMODE;
+        let mask = match data >> TYPE_SHIFT {
+            TYPE_MCONTROL => MCONTROL_HIT | MCONTROL_SELECT | TDATA1_COMMON,
+            TYPE_MCONTROL6 if self.hypervisor => {
+                MCONTROL6_VS | MCONTROL6_VU | MCONTROL6_HIT0 | MCONTROL6_SELECT | TDATA1_COMMON
+            }
+            TYPE_MCONTROL6 => MCONTROL6_HIT0 | MCONTROL6_SELECT | TDATA1_COMMON,
+            _ => {
+                self.tdata1[self.tselect] = TYPE_DISABLED << TYPE_SHIFT | dmode;
+                self.update_active();
+                return;
+            }
+        };
+        let mut val = data & (0xf << TYPE_SHIFT | TDATA1_DMODE | mask);
+        let action = (data >> ACTION_SHIFT) & 0xf;
+        if action == 1 && dmode != 0 {
+            val |= action << ACTION_SHIFT;
+        }
+        let kind = (data >> MATCH_SHIFT) & 0xf;
+        if kind <= 3 {
+            val |= kind << MATCH_SHIFT;
+        }
+        self.tdata1[self.tselect] = val;
+        self.update_active();
+    }
+
+    pub fn write_tdata2(&mut self, data: u64) {
+        self.tdata2[self.tselect] = data;
+    }
+
+    fn update_active(&mut self) {
+        self.active = self
+            .tdata1
+            .iter()
+            .any(|x| matches!(x >> TYPE_SHIFT, TYPE_MCONTROL | TYPE_MCONTROL6) && x & 0b111 != 0);
+    }
+
+    // the lowest-numbered trigger matching the access, its hit bit is set.
+    // the address triggers are checked with data = None before the access,
+    // the data triggers with the value loaded, stored or fetched
+    pub fn hit(
+        &mut self,
+        access: TriggerAccess,
+        addr: u64,
+        data: Option<u64>,
+        privi: PrivilegeLevels,
+        virt: bool,
+    ) -> Option<TriggerAction> {
+        if !self.active {
+            return None;
+        }
+        let access_bit = match access {
+            TriggerAccess::Fetch => TDATA1_EXECUTE,
+            TriggerAccess::Load => TDATA1_LOAD,
+            TriggerAccess::Store => TDATA1_STORE,
+        };
+        for idx in 0..TRIGGER_COUNT {
+            let tdata1 = self.tdata1[idx];
+            let (select, hit, vs, vu) = match tdata1 >> TYPE_SHIFT {
+                TYPE_MCONTROL => (MCONTROL_SELECT, MCONTROL_HIT, TDATA1_S, TDATA1_U),
+                TYPE_MCONTROL6 => (MCONTROL6_SELECT, MCONTROL6_HIT0, MCONTROL6_VS, MCONTROL6_VU),
+                _ => continue,
+            };
+            let mode = match (privi, virt) {
+                (PrivilegeLevels::Machine, _) => TDATA1_M,
+                (PrivilegeLevels::Supervisor, false) => TDATA1_S,
+                (PrivilegeLevels::Supervisor, true) => vs,
+                (PrivilegeLevels::User, false) => TDATA1_U,
+                (PrivilegeLevels::User, true) => vu,
+            };
+            if tdata1 & access_bit == 0 || tdata1 & mode == 0 {
+                continue;
+            }
+            let value = match (tdata1 & select != 0, data) {
+                (false, None) => addr,
+                (true, Some(data)) => data,
+                _ => continue,
+            };
+            if !Self::match_value(tdata1, self.tdata2[idx], value) {
+                continue;
+            }
+            self.tdata1[idx] |= hit;
+            return match (tdata1 >> ACTION_SHIFT) & 0xf {
+                1 => Some(TriggerAction::DebugMode),
+                _ => Some(TriggerAction::Breakpoint),
+            };
+        }
+        None
+    }
+
+    fn match_value(tdata1: u64, tdata2: u64, value: u64) -> bool {
+        match (tdata1 >> MATCH_SHIFT) & 0xf {
+            // napot: the bits above the lowest 0 of tdata2
+            1 => {
+                let mask = u64::MAX
+                    .checked_shl(tdata2.trailing_ones() + 1)
+                    .unwrap_or(0);
+                value & mask == tdata2 & mask
+            }
+            2 => value >= tdata2,
+            3 => value < tdata2,
+            _ => value == tdata2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_trigger {
+    use super::{TriggerAccess, TriggerAction, Triggers, TDATA1_DMODE};
+    use crate::rv64core::inst::inst_base::PrivilegeLevels;
+
+    #[test]
+    fn trigger_test() {
+        let (m, u) = (PrivilegeLevels::Machine, PrivilegeLevels::User);
+        let mut triggers = Triggers::new(false);
+        assert_eq!(triggers.tdata1() >> 60, 2);
+        assert!(triggers
+            .hit(TriggerAccess::Fetch, 0, None, m, false)
+            .is_none());
+
+        // mcontrol, M-mode execute at 0x8000_0000
+        let exec_m = 2 << 60 | 1 << 6 | 1 << 2;
+        triggers.write_tdata2(0x8000_0000);
+        triggers.write_tdata1(exec_m);
+        assert_eq!(triggers.tdata1(), exec_m);
+        let fetch =
+            |t: &mut Triggers, addr, privi| t.hit(TriggerAccess::Fetch, addr, None, privi, false);
+        assert_eq!(
+            fetch(&mut triggers, 0x8000_0000, m),
+            Some(TriggerAction::Breakpoint)
+        );
+        assert_eq!(triggers.tdata1() & 1 << 20, 1 << 20);
+        assert!(fetch(&mut triggers, 0x8000_0004, m).is_none());
+        assert!(fetch(&mut triggers, 0x8000_0000, u).is_none());
+        assert!(triggers
+            .hit(TriggerAccess::Load, 0x8000_0000, None, m, false)
+            .is_none());
+
+        // mcontrol6, U-mode store data match 0x55 on trigger 1 with action 1 (dmode)
+        triggers.write_tselect(1);
+        assert_eq!(triggers.tselect(), 1);
+        triggers.write_tselect(4);
+        assert_eq!(triggers.tselect(), 1);
+        triggers.write_tdata2(0x55);
+        triggers.write_tdata1(6 << 60 | TDATA1_DMODE | 1 << 21 | 1 << 12 | 1 << 3 | 1 << 1);
+        assert!(triggers.dmode());
+        let store = |t: &mut Triggers, data| t.hit(TriggerAccess::Store, 0x1000, data, u, false);
+        assert!(store(&mut triggers, None).is_none());
+        assert!(store(&mut triggers, Some(0x54)).is_none());
+        assert_eq!(
+            store(&mut triggers, Some(0x55)),
+            Some(TriggerAction::DebugMode)
+        );
+
+        // napot 0x2000..0x2100 and action 1 without dmode is a breakpoint
+        triggers.write_tdata2(0x2000 | 0x7f);
+        triggers.write_tdata1(6 << 60 | 1 << 12 | 1 << 7 | 1 << 3 | 1 << 0);
+        assert_eq!((triggers.tdata1() >> 12) & 0xf, 0);
+        let load = |t: &mut Triggers, addr| t.hit(TriggerAccess::Load, addr, None, u, false);
+        assert_eq!(load(&mut triggers, 0x20ff), Some(TriggerAction::Breakpoint));
+        assert!(load(&mut triggers, 0x2100).is_none());
+
+        // the unsupported types disable the trigger
+        triggers.write_tdata1(3 << 60 | 1 << 0);
+        assert_eq!(triggers.tdata1() >> 60, 15);
+        triggers.write_tselect(0);
+        triggers.write_tdata1(0);
+        assert!(!triggers.active());
+    }
+}
@@ -0,0 +1,2 @@
+pub mod vpr;
+pub mod vtype;
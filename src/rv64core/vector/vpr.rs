@@ -0,0 +1,110 @@
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use crate::{rv64core::csr_regs_define::XstatusIn, tools::RcCell};
+
+// vector register file, 32 registers of VLEN bits.
+// elements are stored in little-endian order, a register group is
+// a set of consecutive registers, so element idx may be in register reg + n
+pub struct Vpr {
+    regs: Vec<u8>,
+    vlenb: usize,
+    // any write sets mstatus.VS to dirty
+    xstatus: RcCell<XstatusIn>,
+}
+
+impl Vpr {
+    pub fn new(vlen: usize, xstatus: RcCell<XstatusIn>) -> Self {
+        assert!(vlen.is_power_of_two() && vlen >= 64);
+        Vpr {
+            regs: vec![0; vlen / 8 * 32],
+            vlenb: vlen / 8,
+            xstatus,
+        }
+    }
+
+    pub fn vlenb(&self) -> usize {
+        self.vlenb
+    }
+
+    pub fn vlen(&self) -> usize {
+        self.vlenb * 8
+    }
+
+    pub fn set_dirty(&self) {
+        let mut status = self.xstatus.get();
+        status.set_vs(0b11);
+        status.update_sd();
+        self.xstatus.set(status);
+    }
+
+    fn elem_offset(&self, reg: u64, idx: usize, eew: usize) -> usize {
+        assert!(reg < 32);
+        let offset = reg as usize * self.vlenb + idx * (eew / 8);
+        assert!(offset + eew / 8 <= self.regs.len());
+        offset
+    }
+
+    // element idx of the register group starting at reg, eew in bits, zero extended
+    pub fn read_elem(&self, reg: u64, idx: usize, eew: usize) -> u64 {
+        let offset = self.elem_offset(reg, idx, eew);
+        let mut buf = [0u8; 8];
+        buf[..eew / 8].copy_from_slice(&self.regs[offset..offset + eew / 8]);
+        u64::from_le_bytes(buf)
+    }
+
+    pub fn write_elem(&mut self, reg: u64, idx: usize, eew: usize, data: u64) {
+        let offset = self.elem_offset(reg, idx, eew);
+        self.regs[offset..offset + eew / 8].copy_from_slice(&data.to_le_bytes()[..eew / 8]);
+        self.set_dirty();
+    }
+
+    // mask registers hold one bit per element
+    pub fn read_mask(&self, reg: u64, idx: usize) -> bool {
+        let byte = self.read_elem(reg, idx / 8, 8);
+        (byte >> (idx % 8)) & 1 != 0
+    }
+
+    pub fn write_mask(&mut self, reg: u64, idx: usize, val: bool) {
+        let byte = self.read_elem(reg, idx / 8, 8);
+        let byte = (byte & !(1 << (idx % 8))) | ((val as u64) << (idx % 8));
+        self.write_elem(reg, idx / 8, 8, byte);
+    }
+
+    // copy whole registers, used by vmv<nr>r.v
+    pub fn copy_regs(&mut self, dst: u64, src: u64, nr: usize) {
+        let len = nr * self.vlenb;
+        let src = self.elem_offset(src, 0, 8);
+        let dst = self.elem_offset(dst, 0, 8);
+        assert!(src + len <= self.regs.len() && dst + len <= self.regs.len());
+        self.regs.copy_within(src..src + len, dst);
+        self.set_dirty();
+    }
+
+    pub fn get_register_name(num: u64) -> &'static str {
+        const NAMES: [&str; 32] = [
+            "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "v10", "v11", "v12", "v13",
+            "v14", "v15", "v16", "v17", "v18", "v19", "v20", "v21", "v22", "v23", "v24", "v25",
+            "v26", "v27", "v28", "v29", "v30", "v31",
+        ];
+        NAMES[num as usize]
+    }
+
+    pub fn reset(&mut self) {
+        self.regs.fill(0);
+    }
+}
+
+impl fmt::Display for Vpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..32 {
+            f.write_fmt(format_args!("{}:", Vpr::get_register_name(i)))?;
+            // the most significant byte first
+            for byte in (0..self.vlenb).rev() {
+                f.write_fmt(format_args!("{:02x}", self.read_elem(i, byte, 8)))?;
+            }
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use bitfield_struct::bitfield;
+
+// only integer vector instructions are supported now (Zve64x)
+pub const ELEN: usize = 64;
+
+#[bitfield(u64)]
+pub struct VtypeIn {
+    #[bits(3)]
+    pub vlmul: u8,
+    #[bits(3)]
+    pub vsew: u8,
+    pub vta: bool,
+    pub vma: bool,
+    #[bits(55)]
+    pub reserved: u64,
+    pub vill: bool,
+}
+
+impl VtypeIn {
+    // vtype written by vset{i}vl{i}, unsupported settings set vill
+    pub fn from_vset(val: u64) -> Self {
+        let vtype = VtypeIn::from(val);
+        if vtype.vill() || vtype.reserved() != 0 || vtype.vlmul() == 0b100 || vtype.sew() > ELEN {
+            return VtypeIn::new().with_vill(true);
+        }
+        // fractional lmul: SEW <= LMUL * ELEN
+        let lmul_log2 = vtype.lmul_log2();
+        if lmul_log2 < 0 && vtype.sew() << -lmul_log2 > ELEN {
+            return VtypeIn::new().with_vill(true);
+        }
+        vtype
+    }
+
+    // SEW in bits
+    pub fn sew(&self) -> usize {
+        8 << self.vsew()
+    }
+
+    // log2(LMUL), -3..=3
+    pub fn lmul_log2(&self) -> i32 {
+        match self.vlmul() {
+            x @ 0..=3 => x as i32,
+            x => x as i32 - 8,
+        }
+    }
+
+    // number of registers of a group, fractional lmul still occupies one register
+    pub fn lmul_regs(&self) -> usize {
+        1 << self.lmul_log2().max(0)
+    }
+
+    pub fn vlmax(&self, vlen: usize) -> usize {
+        emul_vlmax(vlen, self.sew(), self.lmul_log2())
+    }
+}
+
+// VLEN * EMUL / EEW
+pub fn emul_vlmax(vlen: usize, eew: usize, emul_log2: i32) -> usize {
+    if emul_log2 >= 0 {
+        (vlen << emul_log2) / eew
+    } else {
+        (vlen >> -emul_log2) / eew
+    }
+}
+
+#[cfg(test)]
+mod test_vtype {
+    use super::VtypeIn;
+
+    #[test]
+    fn vtype_test() {
+        // e32, m2
+        let vtype = VtypeIn::from_vset(0b010_001);
+        assert!(!vtype.vill());
+        assert_eq!(vtype.sew(), 32);
+        assert_eq!(vtype.lmul_regs(), 2);
+        assert_eq!(vtype.vlmax(128), 8);
+        // e8, mf8
+        let vtype = VtypeIn::from_vset(0b000_101);
+        assert!(!vtype.vill());
+        assert_eq!(vtype.lmul_regs(), 1);
+        assert_eq!(vtype.vlmax(128), 2);
+        // e16, mf8 is not supported with ELEN=64
+        assert!(VtypeIn::from_vset(0b001_101).vill());
+        // e128
+        assert!(VtypeIn::from_vset(0b100_000).vill());
+        // reserved lmul
+        assert!(VtypeIn::from_vset(0b000_100).vill());
+        // reserved bits
+        assert!(VtypeIn::from_vset(1 << 8).vill());
+        assert_eq!(u64::from(VtypeIn::from_vset(1 << 8)), 1 << 63);
+    }
+}
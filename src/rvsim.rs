@@ -4,6 +4,7 @@ use core::ops;
 use std::{fs::File, io::Write};
 
 use alloc::{
+    boxed::Box,
     rc::Rc,
     string::{String, ToString},
     vec::Vec,
@@ -14,14 +15,24 @@ use elf::{
 };
 use log::info;
 
+#[cfg(feature = "std")]
+use crate::dbg::control_server::{ControlCmd, ControlServer, HELP};
 use crate::{
-    config::{self, Config},
+    boot_image::{boot_image, BootImage},
+    config::Config,
     dbg::{debug_module::DebugModule, jtag_driver::JtagDriver, remote_bitbang::RemoteBitBang},
+    device::{
+        device_boot_rom::{boot_rom, BOOT_ROM_SIZE},
+        device_trait::{PmaAttr, MEM_BASE},
+        device_watchdog::{DeviceWatchdog, WatchdogAction},
+    },
+    error::{RvEmuError, RvEmuResult},
+    fdt::{machine_fdt, FDT_MAGIC},
 };
 #[allow(unused_imports)]
 use crate::{
     rv64core::{
-        bus::Bus,
+        bus::{Bus, DeviceType},
         cpu_core::{CpuCore, CpuState},
         // csr_regs_define::Misa,
         inst::inst_base::FesvrCmd,
@@ -29,6 +40,28 @@ use crate::{
     tools::RcRefCell,
 };
 
+// 1ms, the longest sleep of `RVsim::idle`
+#[cfg(feature = "std")]
+const MAX_IDLE_TICKS: u64 = crate::fdt::FDT_TIMEBASE as u64 / 1000;
+
+// the result of a run with a time limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Pass,
+    Fail,
+    Timeout,
+}
+
+// how the guest finished: GOOD/BAD TRAP or the tohost test finisher
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestExit {
+    Pass,
+    // the exit code of the guest
+    Fail(u64),
+}
+
+pub type ExitHook = Box<dyn FnMut(GuestExit)>;
+
 // #[derive(Default)]
 pub struct RVsim {
     /* riscv-arch-tests need this symbol */
@@ -44,22 +77,43 @@ pub struct RVsim {
     elf_symbols: hashbrown::HashMap<String, u64>,
 
     /*  debug module */
+    debug_module: RcRefCell<DebugModule>,
     remote_bitbang: RemoteBitBang,
     jtag_driver: JtagDriver,
+    /* irq injection */
+    #[cfg(feature = "std")]
+    control_server: Option<ControlServer>,
+    /* exit hooks */
+    tohost_exit: Option<GuestExit>,
+    exit_hooks: Vec<ExitHook>,
+    // the images loaded before the run, loaded again by `reset`
+    images: Vec<Vec<u8>>,
+    // the memory they take, see `check_clear`
+    loaded: Vec<ops::Range<u64>>,
+    watchdogs: Vec<DeviceWatchdog>,
+    // where the boot rom jumps, the raw binaries are loaded there
+    boot_entry: Option<u64>,
+    // the device tree and where it is, see `add_fdt` and `load_dtb`
+    fdt: Option<(u64, Vec<u8>)>,
+    // the same for the initrd, see `load_initrd`
+    initrd: Option<(u64, Vec<u8>)>,
     // Config
     config: Rc<Config>,
 }
 
 impl RVsim {
-    pub fn new(harts: Vec<RcRefCell<CpuCore>>, rbb_port: u16) -> Self {
-        assert_ne!(harts.len(), 0, "No hart in rvsim");
+    pub fn new(harts: Vec<RcRefCell<CpuCore>>, rbb_port: u16) -> RvEmuResult<Self> {
+        if harts.is_empty() {
+            return Err(RvEmuError::BadConfig("no hart in rvsim".to_string()));
+        }
         let bus = harts[0].borrow_mut().mmu.caches.borrow_mut().bus.clone();
 
-        let dm = DebugModule::new(harts[0].clone());
+        let debug_module: RcRefCell<DebugModule> =
+            RcRefCell::new(DebugModule::new(harts[0].clone()).into());
         let remote_bitbang = RemoteBitBang::new("0.0.0.0", rbb_port);
-        let jtag_driver = JtagDriver::new(dm);
+        let jtag_driver = JtagDriver::new(debug_module.clone());
         let config = harts[0].borrow().config.clone();
-        Self {
+        Ok(Self {
             harts,
             bus,
             config,
@@ -68,10 +122,283 @@ impl RVsim {
             _fromhost: None,
             signature_range: None,
             signature_file: None,
+            debug_module,
             remote_bitbang,
             jtag_driver,
+            #[cfg(feature = "std")]
+            control_server: None,
+            tohost_exit: None,
+            exit_hooks: Vec::new(),
+            images: Vec::new(),
+            loaded: Vec::new(),
+            watchdogs: Vec::new(),
+            boot_entry: None,
+            fdt: None,
+            initrd: None,
+        })
+    }
+
+    // the DM of the JTAG port, map it with DeviceDebugModule to reach it from the bus
+    pub fn debug_module(&self) -> RcRefCell<DebugModule> {
+        self.debug_module.clone()
+    }
+
+    // accept commands from the control socket, see dbg::control_server
+    #[cfg(feature = "std")]
+    pub fn enable_control_server(&mut self, ip: &str, port: u16) {
+        self.control_server = Some(ControlServer::new(ip, port));
+    }
+    // called once when the guest exits, see `run_exit_hooks`
+    pub fn add_exit_hook(&mut self, hook: impl FnMut(GuestExit) + 'static) {
+        self.exit_hooks.push(Box::new(hook));
+    }
+
+    // the host side of a watchdog on the bus, polled after every bus update
+    pub fn add_watchdog(&mut self, watchdog: DeviceWatchdog) {
+        self.watchdogs.push(watchdog);
+    }
+
+    // maps the boot rom at `addr`, the harts start there and jump to `entry`
+    // (the firmware) with a0 = mhartid and a1 = `fdt`. the raw binaries are
+    // loaded at `entry`, call it before `load_image`
+    pub fn add_boot_rom(&mut self, addr: u64, entry: u64, fdt: u64) -> RvEmuResult<()> {
+        self.bus.borrow_mut().add_device(DeviceType {
+            start: addr,
+            len: BOOT_ROM_SIZE,
+            instance: Box::new(boot_rom(entry, fdt)),
+            name: "BOOT_ROM",
+        })?;
+        self.harts
+            .iter()
+            .for_each(|hart| hart.borrow_mut().set_boot_pc(addr));
+        self.boot_entry = Some(entry);
+        Ok(())
+    }
+
+    // a device tree of the machine from `fdt::machine_fdt`, at the end of
+    // the first ram. the harts start with a0 = mhartid and a1 = its address,
+    // pass it to `add_boot_rom` as well. call it after the devices are added
+    // and after `load_initrd`
+    pub fn add_fdt(&mut self, bootargs: &str) -> RvEmuResult<u64> {
+        let config = self.harts[0].borrow().config.clone();
+        let initrd = self
+            .initrd
+            .as_ref()
+            .map(|(addr, data)| *addr..*addr + data.len() as u64);
+        let dtb = machine_fdt(
+            &self.bus.borrow(),
+            &config,
+            self.harts.len(),
+            bootargs,
+            initrd.clone(),
+        )?;
+        let addr = self.fdt_addr(dtb.len())?;
+        self.load_dtb_from_slice(addr, &dtb)?;
+        Ok(addr)
+    }
+
+    // the images, the initrd and the fdt must not overlap, `what` is
+    // one of them at `range`. the images may overlap each other
+    fn check_clear(&self, what: &str, range: &ops::Range<u64>) -> RvEmuResult<()> {
+        let blob = |name, blob: &Option<(u64, Vec<u8>)>| {
+            blob.as_ref()
+                .map(|(addr, data)| (name, *addr..*addr + data.len() as u64))
+        };
+        let taken = self.loaded.iter().map(|x| ("image", x.clone()));
+        let taken = taken
+            .chain(blob("initrd", &self.initrd))
+            .chain(blob("fdt", &self.fdt));
+        for (name, other) in taken {
+            if name != what && range.start < other.end && other.start < range.end {
+                return Err(RvEmuError::ImageLoad(format!(
+                    "{what} at {:#x}..{:#x} overlaps the {name} at {:#x}..{:#x}",
+                    range.start, range.end, other.start, other.end
+                )));
+            }
         }
+        Ok(())
     }
+
+    // (start, len) of the first ram from MEM_BASE
+    fn first_ram(&self) -> RvEmuResult<(u64, u64)> {
+        self.bus
+            .borrow()
+            .devices
+            .iter()
+            .filter(|x| x.start >= MEM_BASE && x.instance.pma() == PmaAttr::MEMORY)
+            .min_by_key(|x| x.start)
+            .map(|x| (x.start, x.len))
+            .ok_or_else(|| RvEmuError::BadConfig("no ram for the fdt".to_string()))
+    }
+
+    // where `add_fdt` puts a device tree of `size` bytes: the last pages of
+    // the first ram
+    pub fn fdt_addr(&self, size: usize) -> RvEmuResult<u64> {
+        let (start, len) = self.first_ram()?;
+        let size = (size as u64 + 0xfff) & !0xfff;
+        if size > len {
+            return Err(RvEmuError::ImageLoad(format!(
+                "fdt of {size:#x} bytes does not fit the ram"
+            )));
+        }
+        Ok(start + len - size)
+    }
+
+    // an initrd (initramfs) in the first ram, like qemu: halfway into it,
+    // or at 128MiB from 256MiB of ram on, clear of the kernel unpacking
+    // below it. the range goes to /chosen of `add_fdt`
+    #[cfg(feature = "std")]
+    pub fn load_initrd(&mut self, file_name: &str) -> RvEmuResult<ops::Range<u64>> {
+        let data = std::fs::read(file_name)
+            .map_err(|e| RvEmuError::ImageLoad(format!("{file_name}: {e}")))?;
+        self.load_initrd_from_slice(&data)
+            .map_err(|e| RvEmuError::ImageLoad(format!("{file_name}: {e}")))
+    }
+    pub fn load_initrd_from_slice(&mut self, data: &[u8]) -> RvEmuResult<ops::Range<u64>> {
+        let (start, len) = self.first_ram()?;
+        let addr = start + (len / 2).min(0x800_0000);
+        // a page is left for the fdt at the end
+        if addr + data.len() as u64 > start + len - 0x1000 {
+            return Err(RvEmuError::ImageLoad(format!(
+                "initrd of {:#x} bytes does not fit the ram above {addr:#x}",
+                data.len()
+            )));
+        }
+        self.check_clear("initrd", &(addr..addr + data.len() as u64))?;
+        info!("initrd at {addr:#x}, {:#x} bytes", data.len());
+        self.initrd = Some((addr, data.to_vec()));
+        self.place_fdt()?;
+        Ok(addr..addr + data.len() as u64)
+    }
+
+    // a device tree of the user at `addr`, the harts start with a0 = mhartid
+    // and a1 = `addr` like with `add_fdt`
+    #[cfg(feature = "std")]
+    pub fn load_dtb(&mut self, addr: u64, file_name: &str) -> RvEmuResult<()> {
+        let dtb = std::fs::read(file_name)
+            .map_err(|e| RvEmuError::ImageLoad(format!("{file_name}: {e}")))?;
+        self.load_dtb_from_slice(addr, &dtb)
+            .map_err(|e| RvEmuError::ImageLoad(format!("{file_name}: {e}")))
+    }
+    pub fn load_dtb_from_slice(&mut self, addr: u64, dtb: &[u8]) -> RvEmuResult<()> {
+        let be32 = |offset: usize| {
+            dtb.get(offset..offset + 4)
+                .map(|x| u32::from_be_bytes(x.try_into().unwrap()))
+        };
+        if be32(0) != Some(FDT_MAGIC) {
+            return Err(RvEmuError::ImageLoad("not a dtb, no fdt magic".to_string()));
+        }
+        if be32(4).is_none_or(|x| x as usize > dtb.len()) {
+            return Err(RvEmuError::ImageLoad(format!(
+                "the dtb is cut, {:#x} bytes",
+                dtb.len()
+            )));
+        }
+        if !addr.is_multiple_of(8) {
+            return Err(RvEmuError::ImageLoad(format!(
+                "the dtb at {addr:#x} is not 8-byte aligned"
+            )));
+        }
+        self.check_clear("fdt", &(addr..addr + dtb.len() as u64))?;
+        info!("fdt at {addr:#x}, {:#x} bytes", dtb.len());
+        let old = self.fdt.replace((addr, dtb.to_vec()));
+        let ret = self.place_fdt();
+        if ret.is_err() {
+            self.fdt = old;
+        }
+        ret
+    }
+
+    // the fdt and the initrd into the memory, and a0, a1 of the harts
+    fn place_fdt(&mut self) -> RvEmuResult<()> {
+        if let Some((addr, data)) = self.initrd.as_ref() {
+            self.bus
+                .borrow_mut()
+                .copy_from_slice(*addr, data)
+                .map_err(|_| {
+                    RvEmuError::ImageLoad(format!("initrd at {addr:#x} is out of memory"))
+                })?;
+        }
+        let Some((addr, dtb)) = self.fdt.as_ref() else {
+            return Ok(());
+        };
+        self.bus
+            .borrow_mut()
+            .copy_from_slice(*addr, dtb)
+            .map_err(|_| RvEmuError::ImageLoad(format!("fdt at {addr:#x} is out of memory")))?;
+        for (hart_id, hart) in self.harts.iter().enumerate() {
+            let mut hart = hart.borrow_mut();
+            hart.gpr.write(10, hart_id as u64);
+            hart.gpr.write(11, *addr);
+        }
+        Ok(())
+    }
+
+    // a full system reset: every device and hart, and the images are
+    // loaded again. the guest starts over from the boot pc
+    pub fn reset(&mut self) -> RvEmuResult<()> {
+        info!("system reset");
+        self.bus.borrow_mut().reset();
+        self.harts.iter().for_each(|hart| hart.borrow_mut().reset());
+        self.tohost_exit = None;
+        self.loaded.clear();
+        let images = core::mem::take(&mut self.images);
+        let ret = images.iter().try_for_each(|x| self._load_elf(x, false));
+        self.images = images;
+        ret?;
+        self.place_fdt()
+    }
+
+    fn check_watchdogs(&mut self) {
+        let expired: Vec<WatchdogAction> = self
+            .watchdogs
+            .iter()
+            .filter_map(|x| x.take_expired())
+            .collect();
+        match expired.first() {
+            Some(WatchdogAction::Reset) => {
+                info!("watchdog reset");
+                if let Err(e) = self.reset() {
+                    panic!("watchdog reset: {e}");
+                }
+            }
+            Some(WatchdogAction::Stop) => {
+                info!("watchdog timeout, stop");
+                self.harts
+                    .iter()
+                    .for_each(|hart| hart.borrow_mut().cpu_state = CpuState::Abort);
+            }
+            None => {}
+        }
+    }
+
+    pub fn guest_exit(&self) -> Option<GuestExit> {
+        if self.tohost_exit.is_some() {
+            return self.tohost_exit;
+        }
+        // GOOD/BAD TRAP, the exit code is in a0
+        #[cfg(feature = "support_am")]
+        for hart in self.harts.iter() {
+            let hart = hart.borrow();
+            if hart.cpu_state == CpuState::Stop {
+                return match hart.gpr.read(10) {
+                    0 => Some(GuestExit::Pass),
+                    code => Some(GuestExit::Fail(code)),
+                };
+            }
+        }
+        None
+    }
+
+    // `run` does it, the frontends with their own loop call it after the loop
+    pub fn run_exit_hooks(&mut self) {
+        let Some(exit) = self.guest_exit() else {
+            return;
+        };
+        let mut hooks = core::mem::take(&mut self.exit_hooks);
+        hooks.iter_mut().for_each(|hook| hook(exit));
+    }
+
     fn get_symbol_values(&mut self) {
         let tohost_addr = self.elf_symbols.get("tohost").copied();
         let fromhost_addr = self.elf_symbols.get("fromhost").copied();
@@ -115,49 +442,125 @@ impl RVsim {
             self.get_symbol_values();
         }
     }
-    fn _load_elf(&mut self, slice: &[u8], collect_symbol: bool) {
+    fn _load_elf(&mut self, slice: &[u8], collect_symbol: bool) -> RvEmuResult<()> {
         let elf_data = elf::ElfBytes::<AnyEndian>::minimal_parse(slice);
         if let Ok(elf_data) = elf_data {
             let ehdr: elf::file::FileHeader<AnyEndian> = elf_data.ehdr;
             // Check e_machine
-            assert_eq!(ehdr.e_machine, EM_RISCV);
+            if ehdr.e_machine != EM_RISCV {
+                return Err(RvEmuError::ImageLoad(format!(
+                    "elf machine {:#x} is not riscv",
+                    ehdr.e_machine
+                )));
+            }
             // Check Program header Table
-            assert_ne!(ehdr.e_phnum, 0);
-            let phdr: elf::parse::ParsingTable<AnyEndian, elf::segment::ProgramHeader> =
-                elf_data.segments().unwrap();
+            let phdr = elf_data
+                .segments()
+                .filter(|_| ehdr.e_phnum != 0)
+                .ok_or_else(|| RvEmuError::ImageLoad("elf has no program header".to_string()))?;
 
             // Load program segments to memory
-            phdr.iter().filter(|x| x.p_type == PT_LOAD).for_each(|p| {
-                let data = elf_data.segment_data(&p).unwrap();
-                assert_eq!(data.len(), p.p_filesz as usize);
+            for p in phdr.iter().filter(|x| x.p_type == PT_LOAD) {
+                let data = elf_data
+                    .segment_data(&p)
+                    .map_err(|e| RvEmuError::ImageLoad(format!("elf segment: {e}")))?;
+                if data.len() != p.p_filesz as usize {
+                    return Err(RvEmuError::ImageLoad(format!(
+                        "elf segment at {:#x} is truncated",
+                        p.p_paddr
+                    )));
+                }
+                let range = p.p_paddr..p.p_paddr.saturating_add(p.p_memsz);
+                self.check_clear("image", &range)?;
                 let mut bus = self.bus.borrow_mut();
-
-                bus.copy_from_slice(p.p_paddr, data).unwrap();
-            });
+                bus.copy_from_slice(p.p_paddr, data).map_err(|_| {
+                    RvEmuError::ImageLoad(format!(
+                        "elf segment at {:#x}+{:#x} is out of memory",
+                        p.p_paddr,
+                        data.len()
+                    ))
+                })?;
+                drop(bus);
+                self.loaded.push(range);
+            }
             info!("Elf file match,elf load success");
 
             // Collect elf symbols into self.elf_symbols(hashmap)
             if collect_symbol {
                 self.collect_elf_symbols(&elf_data);
             }
+        } else if let Some(kernel) = self.boot_kernel(slice) {
+            let kernel = kernel?;
+            let bus = self.bus.borrow();
+            let end = kernel.load.checked_add(kernel.size.max(1) - 1);
+            if end.and_then(|x| bus.pma(x)).is_none() {
+                return Err(RvEmuError::ImageLoad(format!(
+                    "kernel at {:#x}+{:#x} is out of memory",
+                    kernel.load, kernel.size
+                )));
+            }
+            drop(bus);
+            let range = kernel.load..kernel.load.saturating_add(kernel.size);
+            self.check_clear("image", &range)?;
+            self.bus
+                .borrow_mut()
+                .copy_from_slice(kernel.load, kernel.data)
+                .map_err(|_| {
+                    RvEmuError::ImageLoad(format!("kernel at {:#x} is out of memory", kernel.load))
+                })?;
+            self.loaded.push(range);
+            // the boot rom still starts the firmware, OpenSBI finds the
+            // kernel 2MiB after it
+            if self.boot_entry.is_none() {
+                self.harts
+                    .iter()
+                    .for_each(|hart| hart.borrow_mut().set_boot_pc(kernel.entry));
+            }
+            info!("kernel at {:#x}, entry {:#x}", kernel.load, kernel.entry);
         } else {
-            let boot_pc = self.harts.get(0).unwrap().borrow().pc;
+            let boot_pc = self
+                .boot_entry
+                .unwrap_or_else(|| self.harts[0].borrow().pc);
 
+            let range = boot_pc..boot_pc.saturating_add(slice.len() as u64);
+            self.check_clear("image", &range)?;
             let mut bus = self.bus.borrow_mut();
-            bus.copy_from_slice(boot_pc, slice).unwrap();
+            bus.copy_from_slice(boot_pc, slice).map_err(|_| {
+                RvEmuError::ImageLoad(format!(
+                    "binary at {boot_pc:#x}+{:#x} is out of memory",
+                    slice.len()
+                ))
+            })?;
+            drop(bus);
+            self.loaded.push(range);
 
             info!("Elf file not match, bin load success");
         }
+        Ok(())
+    }
+
+    // a Linux Image or uImage, see `boot_image`. the Image goes into the
+    // first ram, or at the boot pc without one
+    fn boot_kernel<'a>(&self, slice: &'a [u8]) -> Option<RvEmuResult<BootImage<'a>>> {
+        let ram_base = self
+            .first_ram()
+            .map_or_else(|_| self.harts[0].borrow().pc, |(start, _)| start);
+        boot_image(slice, ram_base)
     }
 
     #[cfg(feature = "std")]
-    pub fn load_image(&mut self, file_name: &str) {
-        let file_data = std::fs::read(file_name).unwrap();
+    pub fn load_image(&mut self, file_name: &str) -> RvEmuResult<()> {
+        let file_data = std::fs::read(file_name)
+            .map_err(|e| RvEmuError::ImageLoad(format!("{file_name}: {e}")))?;
         info!("load image from file: {}", file_name);
-        self._load_elf(&file_data, true);
+        self._load_elf(&file_data, true)?;
+        self.images.push(file_data);
+        Ok(())
     }
-    pub fn load_image_from_slice(&mut self, slice: &[u8]) {
-        self._load_elf(slice, false);
+    pub fn load_image_from_slice(&mut self, slice: &[u8]) -> RvEmuResult<()> {
+        self._load_elf(slice, false)?;
+        self.images.push(slice.to_vec());
+        Ok(())
     }
 
     pub fn prepare_to_run(&mut self) {
@@ -166,9 +569,17 @@ impl RVsim {
             .for_each(|hart| hart.borrow_mut().cpu_state = CpuState::Running);
     }
 
-    // run 5000 cycles
+    // every hart retires `interval_cycle` instructions in hart_id order,
+    // then all devices are updated once at the instruction boundary (see
+    // `Bus::update` for their order). So the interrupts raised by devices
+    // are always seen by the harts at the same retired instruction, even
+    // if some of the instructions trapped, which keeps traces reproducible.
     pub fn run_once(&mut self, interval_cycle: usize) {
         self.remote_bitbang.tick(&mut self.jtag_driver);
+        #[cfg(feature = "std")]
+        if let Some(control_server) = self.control_server.as_mut() {
+            control_server.tick(|cmd| exec_control_cmd(&self.harts, &self.bus, cmd));
+        }
 
         self.harts.iter_mut().for_each(|hart| {
             hart.borrow_mut().execute(interval_cycle);
@@ -177,16 +588,41 @@ impl RVsim {
         bus.update(interval_cycle);
 
         drop(bus);
+        self.check_watchdogs();
 
         #[cfg(feature = "std")]
         self.check_to_host();
+        #[cfg(feature = "std")]
+        if self
+            .harts
+            .iter()
+            .all(|hart| hart.borrow().cpu_state == CpuState::Wfi)
+        {
+            self.idle();
+        }
     }
 
-    // true: exit, false: abort
+    // every hart sleeps in WFI: mtime jumps to the next timer deadline, at
+    // most MAX_IDLE_TICKS, and the host sleeps as long at the timebase of
+    // the device tree instead of spinning. the interrupts of the host side
+    // devices (a key, a packet) are seen at the next update, which the cap
+    // keeps close
+    #[cfg(feature = "std")]
+    fn idle(&mut self) {
+        let mut bus = self.bus.borrow_mut();
+        let ticks = bus.clint.instance.ticks_to_deadline().min(MAX_IDLE_TICKS);
+        bus.clint.instance.tick(ticks as usize);
+        drop(bus);
+        let nanos = ticks * 1_000_000_000 / crate::fdt::FDT_TIMEBASE as u64;
+        std::thread::sleep(std::time::Duration::from_nanos(nanos));
+    }
+
+    // a hart has stopped or aborted
     pub fn is_finish(&self) -> bool {
-        self.harts
-            .iter()
-            .any(|hart| hart.borrow().cpu_state == CpuState::Stop)
+        self.harts.iter().any(|hart| {
+            let state = &hart.borrow().cpu_state;
+            *state == CpuState::Stop || *state == CpuState::Abort
+        })
     }
 
     pub fn is_exit_normal(&self) -> bool {
@@ -195,25 +631,91 @@ impl RVsim {
             .all(|hart| hart.borrow().cpu_state == CpuState::Stop)
     }
 
+    // retired instructions of all harts
+    pub fn instret(&self) -> u64 {
+        self.harts
+            .iter()
+            .map(|hart| hart.borrow().csr_regs.instret.get())
+            .sum()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn tohost(&self) -> Option<u64> {
+        self.tohost
+    }
+
+    // for images without symbols, such as flat binaries
+    pub fn set_tohost(&mut self, addr: u64) {
+        self.tohost = Some(addr);
+    }
+
     pub fn show_perf(&self) {
         self.harts.iter().for_each(|hart| {
             hart.borrow().show_perf();
         });
+        self.bus.borrow().show_perf();
+        if let Some(report) = self.energy_report() {
+            info!("\n{}", report);
+        }
+    }
+
+    // the harts with an energy model, the shared bus is added to the first one
+    pub fn energy_report(&self) -> Option<String> {
+        let bus_perf = self.bus.borrow().perf();
+        let mut devices = bus_perf.as_slice();
+        let reports: Vec<String> = self
+            .harts
+            .iter()
+            .filter_map(|hart| {
+                let hart = hart.borrow();
+                let pipeline = hart.pipeline.as_ref().map(|x| x.stats());
+                let report = hart.energy.as_ref()?.report(pipeline, devices);
+                devices = &[];
+                Some(report)
+            })
+            .collect();
+        (!reports.is_empty()).then(|| reports.concat())
     }
 
     // true: exit, false: abort
     pub fn run(&mut self) -> bool {
         self.prepare_to_run();
 
+        let budget = self.config.update_budget();
         while !self.is_finish() {
-            self.run_once(5000);
+            self.run_once(budget);
         }
         #[cfg(feature = "std")]
         self.dump_signature();
         self.show_perf();
+        self.run_exit_hooks();
         self.is_exit_normal()
     }
 
+    // like `run`, but gives up after `timeout` of host time
+    #[cfg(feature = "std")]
+    pub fn run_with_timeout(&mut self, timeout: std::time::Duration) -> RunOutcome {
+        self.prepare_to_run();
+
+        let start = std::time::Instant::now();
+        let budget = self.config.update_budget();
+        while !self.is_finish() {
+            if start.elapsed() > timeout {
+                return RunOutcome::Timeout;
+            }
+            self.run_once(budget);
+        }
+        self.dump_signature();
+        self.run_exit_hooks();
+        match self.is_exit_normal() {
+            true => RunOutcome::Pass,
+            false => RunOutcome::Fail,
+        }
+    }
+
     // for riscv-tests
     // It seems in riscv-tests ends with end code
     // written to a certain physical memory address
@@ -224,7 +726,6 @@ impl RVsim {
             return;
         }
 
-
         self.harts[0].borrow_mut().cache_system.borrow_mut().clear();
         let mut bus_u = self.bus.borrow_mut();
         let tohost = self.tohost.unwrap_or(0x8000_1000);
@@ -234,6 +735,10 @@ impl RVsim {
         // debug!("check to host: {:#x}", data);
         let cmd = FesvrCmd::from(data);
         if let Some(pass) = cmd.syscall_device() {
+            self.tohost_exit = match pass {
+                true => Some(GuestExit::Pass),
+                false => Some(GuestExit::Fail(cmd.exit_code())),
+            };
             if pass {
                 self.harts
                     .iter_mut()
@@ -282,3 +787,265 @@ impl RVsim {
         );
     }
 }
+
+#[cfg(feature = "std")]
+fn exec_control_cmd(
+    harts: &[RcRefCell<CpuCore>],
+    bus: &RcRefCell<Bus>,
+    cmd: ControlCmd,
+) -> Result<String, String> {
+    let hart = |hart_id: usize| {
+        harts
+            .get(hart_id)
+            .ok_or(format!("invalid hart id:{hart_id}"))
+    };
+    match cmd {
+        ControlCmd::PlicIrq { irq_id, level } => {
+            bus.borrow_mut().plic.instance.set_irq_line(irq_id, level);
+            Ok(String::new())
+        }
+        ControlCmd::Mip {
+            hart_id,
+            bit,
+            level,
+        } => {
+            let hart = hart(hart_id)?.borrow();
+            let mut xip = hart.csr_regs.xip.get();
+            match level {
+                true => xip.set_irq(bit),
+                false => xip.clear_irq(bit),
+            }
+            hart.csr_regs.xip.set(xip);
+            Ok(format!("{:#x}", u64::from(xip)))
+        }
+        ControlCmd::MipGet { hart_id } => {
+            let xip = hart(hart_id)?.borrow().csr_regs.xip.get();
+            Ok(format!("{:#x}", u64::from(xip)))
+        }
+        ControlCmd::Help => Ok(HELP.to_string()),
+    }
+}
+
+// run a host command for an exit hook with `sh -c`,
+// RV64EMU_EXIT (pass or fail) and RV64EMU_EXIT_CODE tell it how the guest finished
+#[cfg(feature = "std")]
+pub fn run_host_command(cmd: &str, exit: GuestExit) {
+    let (result, code) = match exit {
+        GuestExit::Pass => ("pass", 0),
+        GuestExit::Fail(code) => ("fail", code),
+    };
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .env("RV64EMU_EXIT", result)
+        .env("RV64EMU_EXIT_CODE", code.to_string())
+        .status();
+    match status {
+        Ok(status) if !status.success() => log::warn!("exit hook '{cmd}' failed: {status}"),
+        Err(e) => log::warn!("exit hook '{cmd}' can not run: {e}"),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test_rvsim {
+    use alloc::{boxed::Box, rc::Rc, vec::Vec};
+    use core::cell::{Cell, RefCell};
+
+    use crate::{
+        device::device_trait::{DeviceBase, DEVICE_BASE, MEM_BASE},
+        fdt::FDT_MAGIC,
+        rv64core::{
+            bus::DeviceType, cpu_core::CpuState, inst::inst_asm::assemble, test_cpu::test_cpu,
+        },
+        tools::rc_refcell_new,
+    };
+
+    use super::{RVsim, MAX_IDLE_TICKS};
+
+    const RAM_SIZE: u64 = 0x100_0000;
+
+    // counts the stores to it, and keeps the count seen by each update
+    struct Probe {
+        stores: Rc<Cell<u32>>,
+        updates: Rc<RefCell<Vec<u32>>>,
+    }
+
+    impl DeviceBase for Probe {
+        fn do_read(&mut self, _addr: u64, _len: usize) -> u64 {
+            0
+        }
+        fn do_write(&mut self, _addr: u64, data: u64, _len: usize) -> u64 {
+            self.stores.set(self.stores.get() + 1);
+            data
+        }
+        fn get_name(&self) -> &'static str {
+            "PROBE"
+        }
+        fn do_update(&mut self) {
+            self.updates.borrow_mut().push(self.stores.get());
+        }
+    }
+
+    fn create_sim(isa: &str, mem_size: u64) -> RVsim {
+        let (hart, _) = test_cpu(isa, mem_size);
+        RVsim::new(vec![rc_refcell_new(hart)], 0).unwrap()
+    }
+
+    // a Linux Image header of a kernel taking `size` bytes from the ram base
+    fn linux_image(size: u64) -> Vec<u8> {
+        let mut image = vec![0_u8; 64];
+        image[16..24].copy_from_slice(&size.to_le_bytes());
+        image[56..60].copy_from_slice(b"RSC\x05");
+        image
+    }
+
+    // an empty device tree, only the header
+    fn empty_dtb() -> Vec<u8> {
+        let mut dtb = vec![0_u8; 64];
+        dtb[0..4].copy_from_slice(&FDT_MAGIC.to_be_bytes());
+        dtb[4..8].copy_from_slice(&64_u32.to_be_bytes());
+        dtb
+    }
+
+    #[test]
+    fn load_initrd_test() {
+        // halfway into the ram
+        let mut sim = create_sim("rv64imac", RAM_SIZE);
+        let initrd = [0x5a_u8; 0x100];
+        let range = sim.load_initrd_from_slice(&initrd).unwrap();
+        let addr = MEM_BASE + RAM_SIZE / 2;
+        assert_eq!(range, addr..addr + 0x100);
+        let data = sim.bus.borrow_mut().read(addr + 0xf8, 8).unwrap();
+        assert_eq!(data, 0x5a5a_5a5a_5a5a_5a5a);
+
+        // a kernel loaded later can not run into it
+        let ret = sim.load_image_from_slice(&linux_image(RAM_SIZE / 2 + 1));
+        assert!(ret.is_err());
+        assert!(sim
+            .load_image_from_slice(&linux_image(RAM_SIZE / 2))
+            .is_ok());
+
+        // nor an earlier one, or the dtb
+        let mut sim = create_sim("rv64imac", RAM_SIZE);
+        sim.load_image_from_slice(&linux_image(RAM_SIZE - 0x1000))
+            .unwrap();
+        assert!(sim.load_initrd_from_slice(&initrd).is_err());
+        let mut sim = create_sim("rv64imac", RAM_SIZE);
+        sim.load_dtb_from_slice(addr + 0x80, &empty_dtb()).unwrap();
+        assert!(sim.load_initrd_from_slice(&initrd).is_err());
+
+        // out of the ram, a page is kept for the fdt
+        let mut sim = create_sim("rv64imac", RAM_SIZE);
+        let initrd = vec![0_u8; (RAM_SIZE / 2) as usize - 0x1000];
+        assert!(sim.load_initrd_from_slice(&initrd).is_ok());
+        assert!(sim
+            .load_initrd_from_slice(&[initrd, vec![0]].concat())
+            .is_err());
+        let mut sim = create_sim("rv64imac", 0);
+        assert!(sim.load_initrd_from_slice(&[0; 0x100]).is_err());
+    }
+
+    #[test]
+    fn update_budget_test() {
+        let src = "loop: sw zero, 0(a0); ecall; j loop
+            trap: csrr t0, 0x341; addi t0, t0, 4; csrw 0x341, t0; mret";
+        let program = assemble(src, MEM_BASE).unwrap();
+        let mut sim = create_sim("rv64imac", 0x1000);
+        let updates = Rc::new(RefCell::new(Vec::new()));
+        sim.bus
+            .borrow_mut()
+            .add_device(DeviceType {
+                start: DEVICE_BASE,
+                len: 0x1000,
+                instance: Box::new(Probe {
+                    stores: Rc::new(Cell::new(0)),
+                    updates: updates.clone(),
+                }),
+                name: "PROBE",
+            })
+            .unwrap();
+        for (i, inst) in program.iter().enumerate() {
+            let addr = MEM_BASE + i as u64 * 4;
+            sim.bus.borrow_mut().write(addr, *inst as u64, 4).unwrap();
+        }
+        let hart = sim.harts[0].clone();
+        hart.borrow_mut().npc = MEM_BASE;
+        hart.borrow_mut().gpr.write(10, DEVICE_BASE);
+        hart.borrow().csr_regs.mtvec.set((MEM_BASE + 12).into());
+        sim.prepare_to_run();
+
+        // a round of the loop retires 6 instructions, the ecall traps and
+        // is not one of them, so every update sees one more store
+        for round in 1..=8 {
+            sim.run_once(6);
+            assert_eq!(hart.borrow().csr_regs.instret.get(), round * 6);
+        }
+        assert_eq!(*updates.borrow(), [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    // mtimecmp of hart 0 in the sifive clint
+    const MTIMECMP0: u64 = 0x4000;
+
+    #[test]
+    fn wfi_idle_test() {
+        let mut sim = create_sim("rv64imac", 0);
+        let hart = sim.harts[0].clone();
+        let clint = sim.bus.borrow().clint.start;
+        let mtime = sim.bus.borrow().clint.instance.mtime();
+        let xie = hart.borrow().csr_regs.xie.clone();
+        xie.set(xie.get().with_mtie(true));
+        hart.borrow_mut().cpu_state = CpuState::Wfi;
+
+        // the round ticks 10, then mtime jumps to the compare
+        sim.bus
+            .borrow_mut()
+            .write(clint + MTIMECMP0, 5000, 8)
+            .unwrap();
+        sim.run_once(100);
+        assert_eq!(mtime.get(), 5000);
+        sim.run_once(100);
+        assert_ne!(hart.borrow().cpu_state, CpuState::Wfi);
+
+        // no closer deadline than the cap
+        hart.borrow_mut().cpu_state = CpuState::Wfi;
+        xie.set(xie.get().with_mtie(false));
+        sim.run_once(100);
+        assert_eq!(mtime.get(), 5000 + 10 + 10 + MAX_IDLE_TICKS);
+    }
+
+    #[test]
+    fn load_dtb_test() {
+        let mut sim = create_sim("rv64imac", RAM_SIZE);
+        let dtb = empty_dtb();
+        let addr = sim.fdt_addr(dtb.len()).unwrap();
+        assert_eq!(addr, MEM_BASE + RAM_SIZE - 0x1000);
+        sim.load_dtb_from_slice(addr, &dtb).unwrap();
+        let hart = sim.harts[0].clone();
+        assert_eq!(
+            (hart.borrow().gpr.read(10), hart.borrow().gpr.read(11)),
+            (0, addr)
+        );
+        let magic = sim.bus.borrow_mut().read(addr, 4).unwrap() as u32;
+        assert_eq!(magic.swap_bytes(), FDT_MAGIC);
+        // placed again by a reset
+        sim.bus.borrow_mut().write(addr, 0, 4).unwrap();
+        hart.borrow_mut().gpr.write(11, 0);
+        sim.reset().unwrap();
+        assert_eq!(hart.borrow().gpr.read(11), addr);
+        assert_ne!(sim.bus.borrow_mut().read(addr, 4).unwrap(), 0);
+
+        // bad magic, a header cut before totalsize, a totalsize past the
+        // end, misaligned and out of the ram
+        let mut sim = create_sim("rv64imac", RAM_SIZE);
+        let mut bad = dtb.clone();
+        bad[0] ^= 1;
+        assert!(sim.load_dtb_from_slice(addr, &bad).is_err());
+        assert!(sim.load_dtb_from_slice(addr, &dtb[..6]).is_err());
+        assert!(sim.load_dtb_from_slice(addr, &dtb[..32]).is_err());
+        assert!(sim.load_dtb_from_slice(addr + 4, &dtb).is_err());
+        let end = MEM_BASE + RAM_SIZE;
+        assert!(sim.load_dtb_from_slice(end - 0x20, &dtb).is_err());
+        assert_eq!(sim.harts[0].borrow().gpr.read(11), 0);
+    }
+}
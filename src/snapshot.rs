@@ -0,0 +1,169 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::error::{RvEmuError, RvEmuResult};
+
+// what a state starts with, see `StateWriter::put_header`. the version
+// changes with the field layout of any device
+const STATE_MAGIC: &[u8; 8] = b"RV64SNAP";
+pub const STATE_VERSION: u32 = 1;
+
+// the state of a device in a snapshot, see `DeviceBase::save_state`.
+// the fields are little endian in the order the device puts them and
+// are got back in the same order, so a state only loads into the
+// device type which saved it. `Bus::save_state` tags each device state
+// with the device name and type
+#[derive(Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter { buf: Vec::new() }
+    }
+
+    // the magic and the version, a state of another build is refused
+    pub fn put_header(&mut self) {
+        self.buf.extend_from_slice(STATE_MAGIC);
+        self.put_u32(STATE_VERSION);
+    }
+
+    pub fn put_u8(&mut self, x: u8) {
+        self.buf.push(x);
+    }
+
+    pub fn put_bool(&mut self, x: bool) {
+        self.buf.push(x as u8);
+    }
+
+    pub fn put_u32(&mut self, x: u32) {
+        self.buf.extend_from_slice(&x.to_le_bytes());
+    }
+
+    pub fn put_u64(&mut self, x: u64) {
+        self.buf.extend_from_slice(&x.to_le_bytes());
+    }
+
+    // with its length, for the fields of a variable size
+    pub fn put_bytes(&mut self, x: &[u8]) {
+        self.put_u64(x.len() as u64);
+        self.buf.extend_from_slice(x);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub struct StateReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        StateReader { buf }
+    }
+
+    fn take(&mut self, n: usize) -> RvEmuResult<&'a [u8]> {
+        if n > self.buf.len() {
+            return Err(RvEmuError::Snapshot("the state is truncated".to_string()));
+        }
+        let (x, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(x)
+    }
+
+    pub fn get_header(&mut self) -> RvEmuResult<()> {
+        let bad = |reason: String| Err(RvEmuError::Snapshot(reason));
+        if self.take(STATE_MAGIC.len()).ok() != Some(STATE_MAGIC.as_slice()) {
+            return bad("not a state".to_string());
+        }
+        match self.get_u32()? {
+            STATE_VERSION => Ok(()),
+            version => bad(format!(
+                "a state of version {version}, this build has {STATE_VERSION}"
+            )),
+        }
+    }
+
+    pub fn get_u8(&mut self) -> RvEmuResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_bool(&mut self) -> RvEmuResult<bool> {
+        Ok(self.get_u8()? != 0)
+    }
+
+    pub fn get_u32(&mut self) -> RvEmuResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn get_u64(&mut self) -> RvEmuResult<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn get_bytes(&mut self) -> RvEmuResult<&'a [u8]> {
+        let len = self.get_u64()?;
+        self.take(len.try_into().unwrap_or(usize::MAX))
+    }
+
+    // a field of `len` bytes, e.g. a memory of a fixed size
+    pub fn get_bytes_of(&mut self, len: usize, what: &str) -> RvEmuResult<&'a [u8]> {
+        let x = self.get_bytes()?;
+        if x.len() != len {
+            return Err(RvEmuError::Snapshot(format!(
+                "{what} of {len:#x} bytes, the state has {:#x}",
+                x.len()
+            )));
+        }
+        Ok(x)
+    }
+
+    // the state was read to its end
+    pub fn finish(&self) -> RvEmuResult<()> {
+        match self.buf.len() {
+            0 => Ok(()),
+            n => Err(RvEmuError::Snapshot(format!(
+                "{n} bytes left after the state"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_snapshot {
+    use super::{StateReader, StateWriter, STATE_VERSION};
+
+    #[test]
+    fn state_test() {
+        let mut out = StateWriter::new();
+        out.put_u8(1);
+        out.put_bool(true);
+        out.put_u32(0x1234_5678);
+        out.put_u64(u64::MAX);
+        out.put_bytes(&[5, 6, 7]);
+        let state = out.finish();
+
+        let mut input = StateReader::new(&state);
+        assert_eq!(input.get_u8().unwrap(), 1);
+        assert!(input.get_bool().unwrap());
+        assert_eq!(input.get_u32().unwrap(), 0x1234_5678);
+        assert_eq!(input.get_u64().unwrap(), u64::MAX);
+        assert!(input.finish().is_err());
+        assert!(StateReader::new(&state[14..]).get_bytes_of(4, "x").is_err());
+        assert_eq!(input.get_bytes().unwrap(), [5, 6, 7]);
+        input.finish().unwrap();
+        assert!(input.get_u8().is_err());
+
+        let mut out = StateWriter::new();
+        out.put_header();
+        let mut state = out.finish();
+        StateReader::new(&state).get_header().unwrap();
+        assert!(StateReader::new(&state[1..]).get_header().is_err());
+        state[8..12].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+        assert!(StateReader::new(&state).get_header().is_err());
+    }
+}
@@ -5,3 +5,5 @@ pub mod itrace;
 pub mod ftrace;
 #[cfg(feature = "rv_debug_trace")]
 pub mod traces;
+#[cfg(feature = "rv_debug_trace")]
+pub mod ptwtrace;
@@ -0,0 +1,20 @@
+use std::{fs::File, io::Write};
+
+use crate::rv64core::mmu::cpu_mmu::PtwRecord;
+
+pub struct Ptwtrace {
+    log_file: File,
+}
+
+impl Ptwtrace {
+    pub fn new(hart_id: usize) -> Self {
+        let path = format!("/tmp/rv64emu_ptwtrace_logs_{}", hart_id);
+        let fd = File::create(path).unwrap();
+        Ptwtrace { log_file: fd }
+    }
+
+    pub fn walk_record(&mut self, record: &PtwRecord) {
+        let walk_str = format!("{record}\n");
+        self.log_file.write_all(walk_str.as_bytes()).unwrap();
+    }
+}
@@ -1,17 +1,19 @@
-use crate::rv64core::traptype::TrapType;
+use crate::rv64core::{mmu::cpu_mmu::PtwRecord, traptype::TrapType};
 
 #[cfg(feature = "rv_debug_trace")]
-use super::{ftrace::Ftrace, itrace::Itrace};
+use super::{ftrace::Ftrace, itrace::Itrace, ptwtrace::Ptwtrace};
 pub enum TraceType {
     Itrace(u64, u32),         // (pc, inst)
     Call(u64, u64),           // (inst_pc,jump_pc)
     Return(u64, u64),         // (inst_pc,jump_pc)
     Trap(TrapType, u64, u64), //trap_type: TrapType, epc: u64, tval: u64
+    Ptw(PtwRecord),           // one page table walk
 }
 
 pub struct Traces {
     pub itrace: Itrace,
     pub ftrace: Ftrace,
+    pub ptwtrace: Ptwtrace,
     receiver: crossbeam_channel::Receiver<TraceType>,
 }
 
@@ -21,6 +23,7 @@ impl Traces {
             itrace: Itrace::new(hart_id),
 
             ftrace: Ftrace::new(hart_id),
+            ptwtrace: Ptwtrace::new(hart_id),
             receiver,
         }
     }
@@ -40,6 +43,9 @@ impl Traces {
                 Ok(TraceType::Return(inst_pc, pc)) => {
                     self.ftrace.ret_record(inst_pc, pc);
                 }
+                Ok(TraceType::Ptw(record)) => {
+                    self.ptwtrace.walk_record(&record);
+                }
                 Err(_) => {}
             }
         }
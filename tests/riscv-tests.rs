@@ -1,11 +1,12 @@
 extern crate rv64emu;
-use std::{fs, path::Path, rc::Rc};
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
 
 use log::LevelFilter;
 use rv64emu::{
     config::Config,
     device::device_memory::DeviceMemory,
-    rvsim::RVsim,
+    rv64core::inst::inst_asm::assemble,
+    rvsim::{GuestExit, RVsim},
     tools::{rc_refcell_new, RcRefCell},
 };
 
@@ -13,6 +14,7 @@ use crate::{
     rv64emu::device::device_trait::{DeviceBase, MEM_BASE},
     rv64emu::rv64core::bus::{Bus, DeviceType},
     rv64emu::rv64core::cpu_core::CpuCoreBuild,
+    rv64emu::rv64core::gpr::Gpr,
 };
 
 fn get_riscv_tests_path() -> std::path::PathBuf {
@@ -24,11 +26,7 @@ fn get_riscv_tests_path() -> std::path::PathBuf {
     elf_path
 }
 
-// ture: pass, false: fail
-fn start_test(img: &str) -> bool {
-    // let bus_u = Rc::new(Mutex::new(Bus::new()));
-    let bus_u: RcRefCell<Bus> = RcRefCell::new(Bus::new().into());
-
+fn create_sim() -> RVsim {
     let mut config = Config::new();
     config.set_tlb_size(256);
     config.set_icache_size(4096);
@@ -36,31 +34,43 @@ fn start_test(img: &str) -> bool {
     config.set_isa("rv64ima");
     config.set_mmu_type("sv39");
     config.set_s_mode();
+    create_sim_with(config)
+}
 
+fn create_sim_with(config: Config) -> RVsim {
+    // let bus_u = Rc::new(Mutex::new(Bus::new()));
+    let bus_u: RcRefCell<Bus> = RcRefCell::new(Bus::new().into());
+    let smode = config.s_mode();
     let config = Rc::new(config);
 
     let cpu = rc_refcell_new(
         CpuCoreBuild::new(bus_u.clone(), config)
             .with_boot_pc(0x8000_0000)
             .with_hart_id(0)
-            .with_smode(true)
+            .with_smode(smode)
             .build(),
     );
 
     // device dram
     let mem: DeviceMemory = DeviceMemory::new(128 * 1024 * 1024);
     let device_name = mem.get_name();
-    bus_u.borrow_mut().add_device(DeviceType {
-        start: MEM_BASE,
-        len: mem.size() as u64,
-        instance: Box::new(mem),
-        name: device_name,
-    });
-
-    let mut sim = RVsim::new(vec![cpu], 23456);
+    bus_u
+        .borrow_mut()
+        .add_device(DeviceType {
+            start: MEM_BASE,
+            len: mem.size() as u64,
+            instance: Box::new(mem),
+            name: device_name,
+        })
+        .unwrap();
 
-    sim.load_image(img);
+    RVsim::new(vec![cpu], 23456).unwrap()
+}
 
+// ture: pass, false: fail
+fn start_test(img: &str) -> bool {
+    let mut sim = create_sim();
+    sim.load_image(img).unwrap();
     sim.run()
 }
 
@@ -71,6 +81,88 @@ fn test_once() {
     assert!(ret);
 }
 
+#[test]
+fn exit_hook_test() {
+    let exits = Rc::new(RefCell::new(Vec::new()));
+    // write the fesvr exit command to tohost: (code << 1) | 1
+    for cmd in [1, (3 << 1) | 1] {
+        let src = format!("li t0,0x80001000; li t1,{cmd}; sd t1,0(t0); halt: j halt");
+        let words = assemble(&src, 0x8000_0000).unwrap();
+        let image: Vec<u8> = words.iter().flat_map(|x| x.to_le_bytes()).collect();
+
+        let mut sim = create_sim();
+        sim.load_image_from_slice(&image).unwrap();
+        sim.set_tohost(0x8000_1000);
+        let exits = exits.clone();
+        sim.add_exit_hook(move |exit| exits.borrow_mut().push(exit));
+        sim.run();
+    }
+    assert_eq!(*exits.borrow(), [GuestExit::Pass, GuestExit::Fail(3)]);
+}
+
+#[test]
+fn ma_data_test() {
+    for name in ["rv64ui-p-ma_data", "rv64ui-v-ma_data"] {
+        let mut config = Config::new();
+        config.set_tlb_size(256);
+        config.set_isa("rv64ima");
+        config.set_mmu_type("sv39");
+        config.set_s_mode();
+        config.set_misaligned(true);
+        let mut sim = create_sim_with(config);
+        let img = get_riscv_tests_path().join(name);
+        sim.load_image(img.to_str().unwrap()).unwrap();
+        assert!(sim.run(), "{name}");
+    }
+}
+
+#[test]
+fn rv32_test() {
+    let src = "
+        lui t0,0x80000      # 0x80000000, sign extended in the gpr
+        srli t1,t0,4
+        li t2,-1
+        mulhu t3,t2,t2
+        divu t4,t2,t0
+        li a0,33
+        li a1,1
+        sll a2,a1,a0        # the shift amount is 5 bits
+        csrr a3,0x301
+        csrw 0x342,t0
+        csrr a4,0x342
+        lw a5,0(t0)         # the address is 0x80000000, not sign extended
+        jal ra,next
+        next:
+        mv s0,ra
+        lui a6,0x80001
+        li a7,1
+        sw a7,0(a6)
+        halt: j halt";
+    let words = assemble(src, 0x8000_0000).unwrap();
+    let image: Vec<u8> = words.iter().flat_map(|x| x.to_le_bytes()).collect();
+
+    let mut config = Config::new();
+    config.set_isa("rv32imac");
+    let mut sim = create_sim_with(config);
+    sim.load_image_from_slice(&image).unwrap();
+    sim.set_tohost(0x8000_1000);
+    assert!(sim.run());
+
+    let hart = sim.harts[0].borrow();
+    let reg = |name: &str| hart.gpr.read(Gpr::get_register_idx(name) as u64);
+    assert_eq!(reg("t0"), 0xffff_ffff_8000_0000);
+    assert_eq!(reg("t1"), 0x0800_0000);
+    assert_eq!(reg("t3"), 0xffff_ffff_ffff_fffe);
+    assert_eq!(reg("t4"), 1);
+    assert_eq!(reg("a2"), 2);
+    // MXL=1, I M A C
+    assert_eq!(reg("a3") as u32, 0x4000_1105);
+    assert_eq!(reg("a4"), 0xffff_ffff_8000_0000);
+    assert_eq!(reg("a5") as u32, words[0]);
+    assert_eq!(reg("s0"), 0xffff_ffff_8000_0034);
+    assert!(hart.csr_regs.mcause.get().interrupt());
+}
+
 struct TestRet {
     pub name: String,
     pub ret: bool,
@@ -78,13 +170,9 @@ struct TestRet {
 
 #[test]
 fn run_arch_tests() {
-    // not support misaligned load/store, so skip these tests
+    // ma_data needs the misaligned loads and stores split, see ma_data_test
 
-    let sikp_files = [
-        "rv64ui-p-ma_data",
-        "rv64ui-v-ma_data",
-        "rv64mi-p-breakpoint",
-    ];
+    let sikp_files = ["rv64ui-p-ma_data", "rv64ui-v-ma_data"];
     simple_logger::SimpleLogger::new()
         .with_level(LevelFilter::Debug)
         .init()
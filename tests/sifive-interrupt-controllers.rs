@@ -0,0 +1,374 @@
+extern crate rv64emu;
+use std::{cell::Cell, rc::Rc};
+
+use rv64emu::{
+    device::{
+        device_sifive_clint::Clint,
+        device_sifive_plic::SifvePlic,
+        device_trait::DeviceBase,
+        irq::{InterruptController, IrqTrigger},
+    },
+    rv64core::{
+        csr_regs::SstcTimer,
+        csr_regs_define::{EnvcfgIn, XipIn},
+    },
+};
+
+// MMIO conformance of the SiFive CLINT and PLIC, the devices are driven
+// directly through `DeviceBase`, without a cpu core.
+// ref: SiFive FU540-C000 Manual, chapter 9 (CLINT) and chapter 10 (PLIC)
+
+type Xip = Rc<Cell<XipIn>>;
+
+fn new_xip() -> Xip {
+    Rc::new(Cell::new(XipIn::new()))
+}
+
+const MSIP: u64 = 0x0;
+const MTIMECMP: u64 = 0x4000;
+const MTIME: u64 = 0xbff8;
+
+fn clint_with_harts(num: usize) -> (Clint, Vec<Xip>, Rc<Cell<u64>>) {
+    let mut clint = Clint::new();
+    let xips: Vec<_> = (0..num).map(|_| new_xip()).collect();
+    let mtime = xips
+        .iter()
+        .map(|xip| clint.add_hart(xip.clone()))
+        .last()
+        .unwrap();
+    (clint, xips, mtime)
+}
+
+#[test]
+fn clint_msip_test() {
+    let (mut clint, xips, _) = clint_with_harts(2);
+
+    for (hart, xip) in xips.iter().enumerate() {
+        let addr = MSIP + 4 * hart as u64;
+        assert_eq!(clint.do_read(addr, 4), 0);
+        clint.do_write(addr, 1, 4);
+        assert_eq!(clint.do_read(addr, 4), 1);
+        assert!(xip.get().msip());
+        // only bit 0 is writable
+        clint.do_write(addr, 0xffff_fffe, 4);
+        assert_eq!(clint.do_read(addr, 4), 0);
+        assert!(!xip.get().msip());
+        clint.do_write(addr, 0xffff_ffff, 4);
+        assert_eq!(clint.do_read(addr, 4), 1);
+    }
+    // each hart has its own msip
+    clint.do_write(MSIP, 0, 4);
+    assert!(!xips[0].get().msip());
+    assert!(xips[1].get().msip());
+}
+
+#[test]
+#[should_panic]
+fn clint_msip_width_test() {
+    let (mut clint, _, _) = clint_with_harts(1);
+    clint.do_write(MSIP, 1, 8);
+}
+
+#[test]
+fn clint_mtimecmp_test() {
+    let (mut clint, _, _) = clint_with_harts(2);
+
+    for hart in 0..2 {
+        let addr = MTIMECMP + 8 * hart as u64;
+        // reset value keeps the timer interrupt off
+        assert_eq!(clint.do_read(addr, 8), u64::MAX);
+
+        clint.do_write(addr, 0x1122_3344_5566_7788, 8);
+        assert_eq!(clint.do_read(addr, 8), 0x1122_3344_5566_7788);
+        assert_eq!(clint.do_read(addr, 4), 0x5566_7788);
+        assert_eq!(clint.do_read(addr + 4, 4), 0x1122_3344);
+
+        // 32-bit halves are written independently
+        clint.do_write(addr, 0xaaaa_bbbb_0000_0001, 4);
+        assert_eq!(clint.do_read(addr, 8), 0x1122_3344_0000_0001);
+        clint.do_write(addr + 4, 0xcccc_dddd_0000_0002, 4);
+        assert_eq!(clint.do_read(addr, 8), 0x0000_0002_0000_0001);
+    }
+    clint.do_write(MTIMECMP, 7, 8);
+    assert_eq!(clint.do_read(MTIMECMP + 8, 8), 0x0000_0002_0000_0001);
+}
+
+#[test]
+fn clint_mtime_test() {
+    let (mut clint, _, mtime) = clint_with_harts(1);
+
+    assert_eq!(clint.do_read(MTIME, 8), 0);
+    clint.tick(10);
+    assert_eq!(clint.do_read(MTIME, 8), 10);
+    assert_eq!(mtime.get(), 10);
+
+    clint.do_write(MTIME, 0x1_ffff_fff0, 8);
+    assert_eq!(mtime.get(), 0x1_ffff_fff0);
+    assert_eq!(clint.do_read(MTIME, 4), 0xffff_fff0);
+    assert_eq!(clint.do_read(MTIME + 4, 4), 0x1);
+
+    // carry into the high half
+    clint.tick(0x20);
+    assert_eq!(clint.do_read(MTIME + 4, 4), 0x2);
+    assert_eq!(clint.do_read(MTIME, 4), 0x10);
+
+    clint.do_write(MTIME, 0x5, 4);
+    assert_eq!(mtime.get(), 0x2_0000_0005);
+    clint.do_write(MTIME + 4, 0x0, 4);
+    assert_eq!(mtime.get(), 0x5);
+}
+
+#[test]
+fn clint_mtip_test() {
+    let (mut clint, xips, _) = clint_with_harts(2);
+
+    clint.do_write(MTIMECMP, 100, 8);
+    clint.tick(99);
+    assert!(!xips[0].get().mtip());
+    // mtip is set once mtime >= mtimecmp
+    clint.tick(1);
+    assert!(xips[0].get().mtip());
+    assert!(!xips[1].get().mtip());
+
+    // writing mtimecmp is the only way to clear it
+    clint.tick(1);
+    assert!(xips[0].get().mtip());
+    clint.do_write(MTIMECMP + 4, 1, 4);
+    clint.tick(0);
+    assert!(!xips[0].get().mtip());
+
+    // moving mtime backwards clears it as well
+    clint.do_write(MTIMECMP, 50, 8);
+    clint.tick(0);
+    assert!(xips[0].get().mtip());
+    clint.do_write(MTIME, 0, 8);
+    clint.tick(0);
+    assert!(!xips[0].get().mtip());
+}
+
+#[test]
+fn clint_sstc_test() {
+    let mut clint = Clint::new();
+    let xip = new_xip();
+    let sstc = SstcTimer {
+        stimecmp: Rc::new(Cell::new(100)),
+        vstimecmp: Rc::new(Cell::new(200)),
+        menvcfg: Rc::new(Cell::new(EnvcfgIn::new())),
+        henvcfg: Rc::new(Cell::new(EnvcfgIn::new())),
+        htimedelta: Rc::new(Cell::new(50)),
+    };
+    clint.add_hart_sstc(xip.clone(), sstc.clone());
+
+    // nothing happens before menvcfg.STCE is set
+    clint.tick(100);
+    assert!(!xip.get().stip());
+    sstc.menvcfg.set(EnvcfgIn::new().with_stce(true));
+    clint.tick(0);
+    assert!(xip.get().stip());
+    assert!(!xip.get().vstip());
+    sstc.stimecmp.set(1000);
+    clint.tick(0);
+    assert!(!xip.get().stip());
+
+    // vstimecmp is compared with mtime + htimedelta
+    sstc.henvcfg.set(EnvcfgIn::new().with_stce(true));
+    clint.tick(49);
+    assert!(!xip.get().vstip());
+    clint.tick(1);
+    assert!(xip.get().vstip());
+    assert!(!xip.get().mtip());
+}
+
+const PRIORITY: u64 = 0x0;
+const PENDING: u64 = 0x1000;
+const ENABLE: u64 = 0x2000;
+const ENABLE_PER_CONTEXT: u64 = 0x80;
+const THRESHOLD: u64 = 0x200000;
+const CLAIM: u64 = 0x200004;
+const CONTEXT_PER_HART: u64 = 0x1000;
+
+// context 0 is M-mode, context 1 is S-mode, on the same hart
+fn plic_with_contexts() -> (SifvePlic, Xip) {
+    let xip = new_xip();
+    let mut plic = SifvePlic::new();
+    plic.add_context(xip.clone(), true);
+    plic.add_context(xip.clone(), false);
+    (plic, xip)
+}
+
+#[test]
+fn plic_priority_test() {
+    let (mut plic, _) = plic_with_contexts();
+
+    // source 0 does not exist
+    plic.do_write(PRIORITY, 7, 4);
+    assert_eq!(plic.do_read(PRIORITY, 4), 0);
+
+    for id in 1..64 {
+        let addr = PRIORITY + 4 * id;
+        assert_eq!(plic.do_read(addr, 4), 0);
+        plic.do_write(addr, 0xffff_ffff, 4);
+        // 3-bit WARL
+        assert_eq!(plic.do_read(addr, 4), 7);
+        plic.do_write(addr, 0x5, 4);
+        assert_eq!(plic.do_read(addr, 4), 5);
+    }
+    // unimplemented sources are hardwired to zero
+    for id in [64, 100, 1023] {
+        let addr = PRIORITY + 4 * id;
+        plic.do_write(addr, 7, 4);
+        assert_eq!(plic.do_read(addr, 4), 0);
+    }
+}
+
+#[test]
+fn plic_pending_test() {
+    let (mut plic, _) = plic_with_contexts();
+    let line1 = plic.alloc_irq_line(1, IrqTrigger::Level);
+    let line40 = plic.alloc_irq_line(40, IrqTrigger::Level);
+
+    line1.raise();
+    line40.raise();
+    plic.tick();
+    assert_eq!(plic.do_read(PENDING, 4), 1 << 1);
+    assert_eq!(plic.do_read(PENDING + 4, 4), 1 << (40 - 32));
+
+    // pending bits are read-only
+    plic.do_write(PENDING, 0, 4);
+    plic.do_write(PENDING + 4, 0xffff_ffff, 4);
+    assert_eq!(plic.do_read(PENDING, 4), 1 << 1);
+    assert_eq!(plic.do_read(PENDING + 4, 4), 1 << (40 - 32));
+
+    for addr in (PENDING + 8..ENABLE).step_by(0x100) {
+        assert_eq!(plic.do_read(addr, 4), 0);
+    }
+}
+
+#[test]
+fn plic_enable_test() {
+    let (mut plic, _) = plic_with_contexts();
+
+    for context in 0..2 {
+        let addr = ENABLE + ENABLE_PER_CONTEXT * context;
+        // bit 0 (source 0) is hardwired to zero
+        plic.do_write(addr, 0xffff_ffff, 4);
+        assert_eq!(plic.do_read(addr, 4), 0xffff_fffe);
+        plic.do_write(addr + 4, 0xffff_ffff, 4);
+        assert_eq!(plic.do_read(addr + 4, 4), 0xffff_ffff);
+        // sources 64-1023 are not implemented
+        plic.do_write(addr + 8, 0xffff_ffff, 4);
+        assert_eq!(plic.do_read(addr + 8, 4), 0);
+        assert_eq!(plic.do_read(addr + ENABLE_PER_CONTEXT - 4, 4), 0);
+
+        plic.do_write(addr, 0x10, 4);
+        assert_eq!(plic.do_read(addr, 4), 0x10);
+    }
+    // each context has its own enables
+    plic.do_write(ENABLE, 0, 4);
+    assert_eq!(plic.do_read(ENABLE + ENABLE_PER_CONTEXT, 4), 0x10);
+
+    // the enables of a missing context are hardwired to zero
+    let addr = ENABLE + ENABLE_PER_CONTEXT * 2;
+    plic.do_write(addr, 0xffff_ffff, 4);
+    assert_eq!(plic.do_read(addr, 4), 0);
+}
+
+#[test]
+fn plic_threshold_test() {
+    let (mut plic, xip) = plic_with_contexts();
+    let line = plic.alloc_irq_line(5, IrqTrigger::Level);
+    plic.do_write(PRIORITY + 4 * 5, 3, 4);
+    plic.do_write(ENABLE, 1 << 5, 4);
+    line.raise();
+
+    // 3-bit WARL
+    plic.do_write(THRESHOLD, 0xffff_ffff, 4);
+    assert_eq!(plic.do_read(THRESHOLD, 4), 7);
+
+    // only a priority strictly greater than the threshold is signalled
+    for (threshold, meip) in [(7, false), (3, false), (2, true), (0, true)] {
+        plic.do_write(THRESHOLD, threshold, 4);
+        assert_eq!(plic.do_read(THRESHOLD, 4), threshold);
+        plic.tick();
+        assert_eq!(xip.get().meip(), meip, "threshold:{}", threshold);
+    }
+
+    // priority 0 never interrupts
+    plic.do_write(PRIORITY + 4 * 5, 0, 4);
+    plic.tick();
+    assert!(!xip.get().meip());
+
+    // the S-mode context sees nothing, its enables are clear
+    assert!(!xip.get().seip());
+}
+
+#[test]
+fn plic_claim_complete_test() {
+    let (mut plic, xip) = plic_with_contexts();
+    let line2 = plic.alloc_irq_line(2, IrqTrigger::Level);
+    let line3 = plic.alloc_irq_line(3, IrqTrigger::Level);
+    let line4 = plic.alloc_irq_line(4, IrqTrigger::Level);
+    plic.do_write(PRIORITY + 4 * 2, 1, 4);
+    plic.do_write(PRIORITY + 4 * 3, 2, 4);
+    plic.do_write(PRIORITY + 4 * 4, 2, 4);
+    let s_claim = CLAIM + CONTEXT_PER_HART;
+    plic.do_write(ENABLE, 0b11100, 4);
+    plic.do_write(ENABLE + ENABLE_PER_CONTEXT, 0b00100, 4);
+
+    assert_eq!(plic.do_read(CLAIM, 4), 0);
+    line2.raise();
+    line3.raise();
+    line4.raise();
+    plic.tick();
+    assert!(xip.get().meip());
+    assert!(xip.get().seip());
+
+    // highest priority first, the lower ID wins a tie
+    assert_eq!(plic.do_read(CLAIM, 4), 3);
+    assert_eq!(plic.do_read(CLAIM, 4), 4);
+    assert_eq!(plic.do_read(PENDING, 4), 1 << 2);
+    // a source is claimed by only one context
+    assert_eq!(plic.do_read(s_claim, 4), 2);
+    assert!(!xip.get().seip());
+    assert!(!xip.get().meip());
+    assert_eq!(plic.do_read(CLAIM, 4), 0);
+    assert_eq!(plic.do_read(PENDING, 4), 0);
+
+    // completion of a source that is not enabled for the context is ignored
+    plic.do_write(s_claim, 3, 4);
+    plic.tick();
+    assert_eq!(plic.do_read(PENDING, 4), 0);
+
+    plic.do_write(CLAIM, 3, 4);
+    plic.do_write(s_claim, 2, 4);
+    plic.tick();
+    assert_eq!(plic.do_read(PENDING, 4), (1 << 2) | (1 << 3));
+
+    // invalid ids are ignored
+    plic.do_write(CLAIM, 0, 4);
+    plic.do_write(CLAIM, 1000, 4);
+}
+
+#[test]
+fn plic_reserved_test() {
+    let (mut plic, _) = plic_with_contexts();
+
+    // reserved words of a context
+    for offset in [0x8, 0x100, 0xffc] {
+        plic.do_write(THRESHOLD + offset, 0xffff_ffff, 4);
+        assert_eq!(plic.do_read(THRESHOLD + offset, 4), 0);
+    }
+    // a missing context
+    let addr = THRESHOLD + CONTEXT_PER_HART * 2;
+    plic.do_write(addr, 7, 4);
+    assert_eq!(plic.do_read(addr, 4), 0);
+    assert_eq!(plic.do_read(addr + 4, 4), 0);
+    assert_eq!(plic.do_read(0xfff004, 4), 0);
+}
+
+#[test]
+#[should_panic]
+fn plic_width_test() {
+    let (mut plic, _) = plic_with_contexts();
+    plic.do_read(PRIORITY + 4, 8);
+}